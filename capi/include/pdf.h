@@ -0,0 +1,45 @@
+/* C API of the pdf crate, see capi/src/lib.rs. */
+#ifndef PDF_CAPI_H
+#define PDF_CAPI_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum PdfStatus {
+    PDF_OK = 0,
+    PDF_NULL_POINTER = 1,
+    PDF_INVALID_ARGUMENT = 2,
+    PDF_IO = 3,
+    PDF_SYNTAX = 4,
+    PDF_STRUCTURE = 5,
+    PDF_PASSWORD = 6,
+    PDF_PAGE_OUT_OF_BOUNDS = 7,
+    PDF_NOT_FOUND = 8,
+    PDF_PANIC = 9,
+    PDF_OTHER = 10
+} PdfStatus;
+
+typedef struct PdfDocument PdfDocument;
+
+/* password may be NULL */
+PdfStatus pdf_open(const char *path, const char *password, PdfDocument **out);
+void pdf_close(PdfDocument *doc);
+
+PdfStatus pdf_page_count(const PdfDocument *doc, uint32_t *out);
+/* page counts from 0; free *out with pdf_string_free */
+PdfStatus pdf_page_text(const PdfDocument *doc, uint32_t page, char **out);
+/* key: Title, Author, Subject, Keywords, Creator, Producer, CreationDate, ModDate, Trapped */
+PdfStatus pdf_metadata(const PdfDocument *doc, const char *key, char **out);
+
+void pdf_string_free(char *s);
+/* message of the last error on this thread, or NULL */
+const char *pdf_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
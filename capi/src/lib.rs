@@ -0,0 +1,216 @@
+//! C API: open a document, count its pages, extract text and read the metadata.
+//!
+//! Every function returns a `PdfStatus`; results are passed through out pointers.
+//! Strings returned to the caller are NUL terminated UTF-8 and are freed with `pdf_string_free`.
+//! The message of the last error on the calling thread is available from `pdf_last_error`.
+//! See `include/pdf.h` for the declarations.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use pdf::file::File;
+use pdf::error::{PdfError, ErrorKind};
+
+/// Result of every call. The values are part of the ABI and never change.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PdfStatus {
+    Ok = 0,
+    /// A pointer argument was NULL
+    NullPointer = 1,
+    /// A string argument was not valid UTF-8, or a key is unknown
+    InvalidArgument = 2,
+    Io = 3,
+    Syntax = 4,
+    Structure = 5,
+    Password = 6,
+    PageOutOfBounds = 7,
+    /// The requested entry does not exist
+    NotFound = 8,
+    /// The library panicked; the document should not be used anymore
+    Panic = 9,
+    Other = 10,
+}
+
+/// An open document, only handled through pointers.
+pub struct PdfDocument {
+    file: File<Vec<u8>>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+fn status(e: PdfError) -> PdfStatus {
+    let status = match e.kind() {
+        ErrorKind::Io => PdfStatus::Io,
+        ErrorKind::Syntax => PdfStatus::Syntax,
+        ErrorKind::Structure => PdfStatus::Structure,
+        ErrorKind::Password => PdfStatus::Password,
+        ErrorKind::PageOutOfBounds => PdfStatus::PageOutOfBounds,
+        ErrorKind::Other => PdfStatus::Other,
+    };
+    set_error(e.to_string());
+    status
+}
+
+/// Runs `f`, turning panics into `PdfStatus::Panic`.
+fn guard(f: impl FnOnce() -> Result<(), PdfStatus>) -> PdfStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => PdfStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => {
+            set_error("panic".into());
+            PdfStatus::Panic
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str, PdfStatus> {
+    if s.is_null() {
+        return Err(PdfStatus::NullPointer);
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| {
+        set_error("string argument is not valid UTF-8".into());
+        PdfStatus::InvalidArgument
+    })
+}
+
+unsafe fn doc_arg<'a>(doc: *const PdfDocument) -> Result<&'a PdfDocument, PdfStatus> {
+    doc.as_ref().ok_or(PdfStatus::NullPointer)
+}
+
+unsafe fn write_string(out: *mut *mut c_char, s: String) -> Result<(), PdfStatus> {
+    if out.is_null() {
+        return Err(PdfStatus::NullPointer);
+    }
+    *out = CString::new(s.replace('\0', "")).unwrap_or_default().into_raw();
+    Ok(())
+}
+
+/// Opens the file at `path`. `password` may be NULL for documents that are not encrypted.
+/// On success `*out` holds the document, to be closed with `pdf_close`.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_open(path: *const c_char, password: *const c_char, out: *mut *mut PdfDocument) -> PdfStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(PdfStatus::NullPointer);
+        }
+        *out = ptr::null_mut();
+        let path = str_arg(path)?;
+        let password = if password.is_null() { "" } else { str_arg(password)? };
+        let file = File::<Vec<u8>>::open_with_password(path, password.as_bytes()).map_err(status)?;
+        *out = Box::into_raw(Box::new(PdfDocument { file }));
+        Ok(())
+    })
+}
+
+/// Closes `doc`. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_close(doc: *mut PdfDocument) {
+    if !doc.is_null() {
+        drop(Box::from_raw(doc));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pdf_page_count(doc: *const PdfDocument, out: *mut u32) -> PdfStatus {
+    guard(|| {
+        let doc = doc_arg(doc)?;
+        if out.is_null() {
+            return Err(PdfStatus::NullPointer);
+        }
+        *out = doc.file.get_num_pages().map_err(status)?;
+        Ok(())
+    })
+}
+
+/// The text of page `page` (counting from 0), see `File::page_text`.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_page_text(doc: *const PdfDocument, page: u32, out: *mut *mut c_char) -> PdfStatus {
+    guard(|| {
+        let doc = doc_arg(doc)?;
+        let text = doc.file.page_text(page).map_err(status)?;
+        write_string(out, text)
+    })
+}
+
+/// The metadata entry `key`: one of Title, Author, Subject, Keywords, Creator, Producer,
+/// CreationDate, ModDate (as RFC 3339) and Trapped. `PDF_NOT_FOUND` if the document has none.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_metadata(doc: *const PdfDocument, key: *const c_char, out: *mut *mut c_char) -> PdfStatus {
+    guard(|| {
+        let doc = doc_arg(doc)?;
+        let key = str_arg(key)?;
+        let meta = doc.file.metadata().map_err(status)?;
+        let value = match key {
+            "Title" => meta.title,
+            "Author" => meta.author,
+            "Subject" => meta.subject,
+            "Keywords" => meta.keywords,
+            "Creator" => meta.creator,
+            "Producer" => meta.producer,
+            "CreationDate" => meta.creation_date.map(|d| d.to_rfc3339()),
+            "ModDate" => meta.mod_date.map(|d| d.to_rfc3339()),
+            "Trapped" => meta.trapped,
+            _ => {
+                set_error(format!("unknown metadata key {}", key));
+                return Err(PdfStatus::InvalidArgument);
+            }
+        };
+        match value {
+            Some(value) => write_string(out, value),
+            None => Err(PdfStatus::NotFound)
+        }
+    })
+}
+
+/// Frees a string returned by this library. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn pdf_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The message of the last error on this thread, or NULL. Valid until the next call that fails.
+#[no_mangle]
+pub extern "C" fn pdf_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_and_read() {
+        let path = CString::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../files/xelatex.pdf")).unwrap();
+        unsafe {
+            let mut doc = ptr::null_mut();
+            assert_eq!(pdf_open(path.as_ptr(), ptr::null(), &mut doc), PdfStatus::Ok);
+            let mut pages = 0;
+            assert_eq!(pdf_page_count(doc, &mut pages), PdfStatus::Ok);
+            assert!(pages > 0);
+
+            let mut text = ptr::null_mut();
+            assert_eq!(pdf_page_text(doc, 0, &mut text), PdfStatus::Ok);
+            pdf_string_free(text);
+            assert_eq!(pdf_page_text(doc, pages, &mut text), PdfStatus::PageOutOfBounds);
+            assert!(!pdf_last_error().is_null());
+
+            let key = CString::new("Nonsense").unwrap();
+            assert_eq!(pdf_metadata(doc, key.as_ptr(), &mut text), PdfStatus::InvalidArgument);
+            pdf_close(doc);
+        }
+        assert_eq!(unsafe { pdf_page_count(ptr::null(), ptr::null_mut()) }, PdfStatus::NullPointer);
+    }
+}
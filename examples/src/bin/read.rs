@@ -2,7 +2,7 @@ extern crate pdf;
 
 use std::env::args;
 use std::time::SystemTime;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use pdf::file::File;
 use pdf::object::*;
@@ -22,26 +22,22 @@ fn main() {
     let now = SystemTime::now();
     let file = run!(File::<Vec<u8>>::open(&path));
     
-    let mut resources = HashMap::new();
-    let mut num_fonts = 0;
+    // sorted by name, so the output is the same on every run
+    let mut fonts = BTreeMap::new();
     let mut num_images = 0;
     for page in file.pages() {
         let r = page.unwrap().resources(&file).unwrap();
-        for xobject in r.xobjects.iter().flat_map(|d| d.values()) {
+        for xobject in r.xobjects.values() {
             if let XObject::Image(ref im) = xobject {
                 num_images += 1;
             }
         }
-        resources.insert((&*r) as *const _, r);
-    }
-    println!("Found {} image(s).", num_images);
-
-    let mut fonts = HashMap::new();
-    for r in resources.values() {
         for (name, font) in r.fonts() {
-            fonts.insert(font.name.as_str(), font);
+            fonts.insert(font.name.clone(), font.clone());
         }
     }
+    println!("Found {} image(s).", num_images);
+
     for font in fonts.values() {
         println!("{:?}", font);
     }
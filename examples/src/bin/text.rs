@@ -4,41 +4,33 @@ use std::env::args;
 use std::time::SystemTime;
 use std::error::Error;
 use pdf::file::File;
-use pdf::content::*;
-use pdf::primitive::Primitive;
+use pdf::text::{CharPolicy, ExtractOptions, Hyphenation, SoftHyphens, postprocess};
 
-fn add_primitive(p: &Primitive, out: &mut String) {
-    // println!("p: {:?}", p);
-    match p {
-        &Primitive::String(ref s) => if let Ok(text) = s.as_str() {
-            out.push_str(text);
-        }
-        &Primitive::Array(ref a) => for p in a.iter() {
-            add_primitive(p, out);
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut options = ExtractOptions::default();
+    let mut path = None;
+    for arg in args().skip(1) {
+        match arg.as_str() {
+            "--normalize" => options.normalize = true,
+            "--expand-ligatures" => options.expand_ligatures = true,
+            "--logical-order" => options.logical_order = true,
+            "--join-hyphenated" => options.hyphenation = Hyphenation::Join,
+            "--remove-soft-hyphens" => options.soft_hyphens = SoftHyphens::Remove,
+            "--remove-control-chars" => options.control_chars = CharPolicy::Remove,
+            _ => path = Some(arg)
         }
-        _ => ()
     }
-}
-
-fn main() -> Result<(), Box<dyn Error>> {
-    let path = args().nth(1).expect("no file given");
+    let path = path.expect("no file given");
     println!("read: {}", path);
     let now = SystemTime::now();
     let file = File::<Vec<u8>>::open(&path)?;
     
     let mut out = String::new();
     for page in file.pages() {
-        for content in &page.unwrap().contents {
-            for &Operation { ref operator, ref operands } in &content.operations {
-                // println!("{} {:?}", operator, operands);
-                match operator.as_str() {
-                    "Tj" | "TJ" | "BT" => operands.iter().for_each(|p| add_primitive(p, &mut out)),
-                    _ => {}
-                }
-            }
-        }
+        out.push_str(&page?.extract_text(&file)?);
+        out.push('\n');
     }
-    println!("{}", out);
+    println!("{}", postprocess(&out, &options));
     
     Ok(())
 }
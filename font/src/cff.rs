@@ -46,6 +46,12 @@ impl<'a> Font for CffFont<'a> {
     fn font_matrix(&self) -> Transform2F {
         self.font_matrix
     }
+    fn built_in_encoding(&self) -> Option<Vec<(u8, String)>> {
+        self.encoding.as_ref().map(|codes| codes.iter().map(|&(code, name)| (code, name.to_owned())).collect())
+    }
+    fn glyph_for_name(&self, name: &str) -> Option<u32> {
+        self.glyph_map.get(name).cloned()
+    }
     fn glyph(&self, id: u32) -> Result<Glyph, Box<dyn Error>> {
         let mut state = State::new();
         debug!("charstring for glyph {}", id);
@@ -120,7 +126,7 @@ impl<'a> Cff<'a> {
         let char_strings = index(self.data.get(offset ..).unwrap()).get();
         let num_glyphs = char_strings.len() as usize;
         
-        let n = top_dict.get(&Operator::CharstringType).map(|v| v[0].to_int()).unwrap_or(2);
+        let n = top_dict.get(&Operator::CharstringType).and_then(|v| v.get(0)).map(|v| v.to_int()).unwrap_or(2);
         let char_string_type = match n {
             1 => CharstringType::Type1,
             2 => CharstringType::Type2,
@@ -130,24 +136,43 @@ impl<'a> Cff<'a> {
         let charset_offset = top_dict[&Operator::Charset][0].to_int() as usize;
         let charset = charset(self.data.get(charset_offset ..).unwrap(), num_glyphs).get();
         
-        let glyph_name = |sid: SID|
-            STANDARD_STRINGS.get(sid as usize).cloned().unwrap_or_else(||
-                ::std::str::from_utf8(self.string_index.get(sid as u32 - STANDARD_STRINGS.len() as u32).expect("no such string")).expect("Invalid glyph name")
-            );
+        // `None` for SIDs that are not in the string index or not UTF-8
+        let glyph_name = |sid: SID| -> Option<&'a str> {
+            STANDARD_STRINGS.get(sid as usize).cloned().or_else(||
+                self.string_index.get(sid as u32 - STANDARD_STRINGS.len() as u32)
+                    .and_then(|name| ::std::str::from_utf8(name).ok())
+            )
+        };
                 
-        let glyph_map: HashMap<&'a str, u32> = match charset {
-            Charset::Continous(sids) => sids.into_iter()
-                .enumerate()
-                .map(|(gid, sid)| (glyph_name(sid), gid as u32))
-                .collect(),
-            Charset::Ranges(ranges) => ranges.into_iter()
-                .flat_map(|(sid, num)| (sid .. sid + num + 1))
-                .enumerate()
-                .map(|(gid, sid)| (glyph_name(sid), gid as u32))
+        // .notdef is not listed in the charset
+        let glyph_names: Vec<Option<&'a str>> = match charset {
+            Charset::Continous(sids) => ::std::iter::once(0).chain(sids).map(glyph_name).collect(),
+            Charset::Ranges(ranges) => ::std::iter::once(0)
+                .chain(ranges.into_iter().flat_map(|(sid, num)| (sid ..= sid.saturating_add(num))))
+                .map(glyph_name)
                 .collect(),
         };
+        let glyph_map: HashMap<&'a str, u32> = glyph_names.iter()
+            .enumerate()
+            .filter_map(|(gid, &name)| Some((name?, gid as u32)))
+            .collect();
         debug!("charset: {:?}", glyph_map);
         
+        let encoding = match top_dict.get(&Operator::Encoding).and_then(|v| v.get(0)).map(|v| v.to_int()).unwrap_or(0) {
+            0 => Some(STANDARD_ENCODING.iter()
+                .flat_map(|&(first, last, sid)| (first ..= last).zip(sid ..))
+                .filter_map(|(code, sid)| Some((code, glyph_name(sid)?)))
+                .collect()),
+            // the expert encoding is not supported
+            1 => None,
+            offset => self.data.get(offset as usize ..).and_then(|i| encoding(i).ok()).map(|(_, (codes, supplements))| {
+                codes.into_iter()
+                    .filter_map(|(code, gid)| Some((code, (*glyph_names.get(gid as usize)?)?)))
+                    .chain(supplements.into_iter().filter_map(|(code, sid)| Some((code, glyph_name(sid)?))))
+                    .collect()
+            })
+        };
+        
         let private_dict_entry = top_dict.get(&Operator::Private)
             .expect("no private dict entry");
         
@@ -174,7 +199,8 @@ impl<'a> Cff<'a> {
             char_string_type,
             context,
             font_matrix,
-            glyph_map
+            glyph_map,
+            encoding
         }
     }
 }
@@ -184,7 +210,9 @@ pub struct CffFont<'a> {
     char_string_type: CharstringType,
     context: Context<'a>,
     font_matrix: Transform2F,
-    glyph_map: HashMap<&'a str, u32>
+    glyph_map: HashMap<&'a str, u32>,
+    /// glyph names by code
+    encoding: Option<Vec<(u8, &'a str)>>
 }
 
 fn dict(mut input: &[u8]) -> R<HashMap<Operator, Vec<Value>>> {
@@ -499,6 +527,55 @@ fn charset(i: &[u8], num_glyphs: usize) -> R<Charset> {
     }
 }
 
+/// Codes and glyph indices of a custom encoding, and supplementary codes with the SIDs of
+/// their glyph names.
+fn encoding(i: &[u8]) -> R<(Vec<(u8, u16)>, Vec<(u8, SID)>)> {
+    let (mut i, format) = be_u8(i)?;
+    let mut codes = Vec::new();
+    match format & 0x7f {
+        0 => {
+            let (rest, n) = be_u8(i)?;
+            let (rest, list) = count(be_u8, n as usize)(rest)?;
+            codes.extend(list.into_iter().zip(1 ..));
+            i = rest;
+        }
+        1 => {
+            let (mut rest, n) = be_u8(i)?;
+            let mut gid = 1;
+            for _ in 0 .. n {
+                let (r, first) = be_u8(rest)?;
+                let (r, left) = be_u8(r)?;
+                for code in first ..= first.saturating_add(left) {
+                    codes.push((code, gid));
+                    gid += 1;
+                }
+                rest = r;
+            }
+            i = rest;
+        }
+        _ => return Err(Failure(make_error(i, ErrorKind::Switch)))
+    }
+    let mut supplements = Vec::new();
+    if format & 0x80 != 0 {
+        let (mut rest, n) = be_u8(i)?;
+        for _ in 0 .. n {
+            let (r, code) = be_u8(rest)?;
+            let (r, sid) = be_u16(r)?;
+            supplements.push((code, sid));
+            rest = r;
+        }
+        i = rest;
+    }
+    Ok((i, (codes, supplements)))
+}
+
+/// The standard encoding as runs of codes and the SID of the first of them
+static STANDARD_ENCODING: [(u8, u8, SID); 14] = [
+    (32, 126, 1), (161, 175, 96), (177, 180, 111), (182, 189, 115), (191, 191, 123),
+    (193, 200, 124), (202, 203, 132), (205, 208, 134), (225, 225, 138), (227, 227, 139),
+    (232, 235, 140), (241, 241, 144), (245, 245, 145), (248, 251, 146),
+];
+
 static STANDARD_STRINGS: [&'static str; 391] = [
 /*   0 */ ".notdef",
 /*   1 */ "space",
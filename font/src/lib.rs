@@ -22,6 +22,14 @@ pub trait Font {
         Transform2F::row_major(1.0, 0., 0., 1., 0., 0.)
     }
     fn glyph(&self, id: u32) -> Result<Glyph, Box<dyn Error>>;
+    /// The encoding of the font program: the glyph name of each code it assigns.
+    fn built_in_encoding(&self) -> Option<Vec<(u8, String)>> {
+        None
+    }
+    /// The glyph with the name `name`.
+    fn glyph_for_name(&self, _name: &str) -> Option<u32> {
+        None
+    }
     fn glyphs(&self) -> Glyphs {
         Glyphs {
             glyphs: (0 .. self.num_glyphs()).map(|i| self.glyph(i).unwrap()).collect()
@@ -33,6 +41,9 @@ pub struct Glyphs {
     glyphs: Vec<Glyph>
 }
 impl Glyphs {
+    pub fn new(glyphs: Vec<Glyph>) -> Glyphs {
+        Glyphs { glyphs }
+    }
     pub fn get(&self, idx: u32) -> Option<&Glyph> {
         self.glyphs.get(idx as usize)
     }
@@ -57,12 +57,17 @@ impl<R: Read> Read for ExecReader<R> {
 }
 
 pub struct Type1Font {
+    /// glyph names by code, unless it is StandardEncoding
+    encoding: Option<Vec<(u8, String)>>,
 }
 impl Font for Type1Font {
     fn num_glyphs(&self) -> u32 { 0 }
     fn glyph(&self, _id: u32) -> Result<Glyph, Box<dyn Error>> {
         unimplemented!()
     }
+    fn built_in_encoding(&self) -> Option<Vec<(u8, String)>> {
+        self.encoding.clone()
+    }
 }
 impl Type1Font {
     pub fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
@@ -90,6 +95,45 @@ fn parse_text<'a>(vm: &mut Vm, data: &'a [u8]) -> R<'a, ()> {
     }
     Ok((input, ()))
 }
+/// The `/Encoding` array of the clear text part, which is filled with `dup code /name put`.
+/// `None` for StandardEncoding.
+fn encoding(text: &[u8]) -> Option<Vec<(u8, String)>> {
+    let start = text.windows(9).position(|w| w == b"/Encoding")? + 9;
+    // names can follow numbers without a space
+    let mut words = Vec::new();
+    for word in text[start ..].split(|&b| word_sep(b)).filter(|w| !w.is_empty()) {
+        let mut word = word;
+        while let Some(n) = word.iter().skip(1).position(|&b| b == b'/') {
+            words.push(&word[.. n + 1]);
+            word = &word[n + 1 ..];
+        }
+        words.push(word);
+    }
+    if words.first() == Some(&&b"StandardEncoding"[..]) {
+        return None;
+    }
+    let end = words.iter().position(|&w| w == b"def").unwrap_or(words.len());
+    let codes: Vec<(u8, String)> = words[.. end].windows(4).filter_map(|w| match *w {
+        [b"dup", code, name, b"put"] if name.starts_with(b"/") => Some((
+            std::str::from_utf8(code).ok()?.parse().ok()?,
+            String::from_utf8(name[1 ..].to_vec()).ok()?
+        )),
+        _ => None
+    }).collect();
+    if codes.is_empty() {
+        return None;
+    }
+    Some(codes)
+}
+
+#[test]
+fn test_encoding() {
+    let text = b"/Encoding 256 array\n0 1 255 {1 index exch /.notdef put} for\n\
+        dup 97 /alpha put\ndup 98/beta put\nreadonly def\ncurrentfile eexec";
+    assert_eq!(encoding(text), Some(vec![(97, "alpha".into()), (98, "beta".into())]));
+    assert_eq!(encoding(b"/Encoding StandardEncoding def"), None);
+}
+
 fn parse_binary<'a>(vm: &mut Vm, data: &'a [u8]) {
     let mut decoder = Decoder::new(55665);
     let decoded: Vec<u8> = data.iter().map(|&b| decoder.decode_byte(b)).collect();
@@ -106,6 +150,7 @@ fn test_parser() {
 }
 fn type1(i: &[u8]) -> R<Type1Font> {
     let mut vm = Vm::new();
+    let mut encoding = None;
     
     let mut input = i;
     while input.len() > 0 {
@@ -118,7 +163,12 @@ fn type1(i: &[u8]) -> R<Type1Font> {
     
         let block = &i[.. block_len as usize];
         match block_type {
-            1 => parse_text(&mut vm, block).get(),
+            1 => {
+                if encoding.is_none() {
+                    encoding = self::encoding(block);
+                }
+                parse_text(&mut vm, block).get()
+            }
             2 => parse_binary(&mut vm, block),
             n => panic!("unknown block type {}", n)
         }
@@ -126,7 +176,7 @@ fn type1(i: &[u8]) -> R<Type1Font> {
         input = &i[block_len as usize ..];
     }
     
-    panic!()
+    Ok((input, Type1Font { encoding }))
 }
 pub fn charstring<'a, 'b>(mut input: &'a [u8], ctx: &Context<'a>, s: &'b mut State) -> IResult<&'a [u8], ()> {
     let i = loop {
@@ -1,8 +1,8 @@
 use std::any::TypeId;
-use std::rc::Rc;
+use std::sync::Arc;
 use crate::object::Object;
 
-pub trait AnyObject {
+pub trait AnyObject: Send + Sync {
     fn serialize(&self, out: &mut Vec<u8>);
     fn type_name(&self) -> &'static str;
     fn type_id(&self) -> TypeId;
@@ -24,22 +24,22 @@ impl<T> AnyObject for T
 }
 
 #[derive(Clone)]
-pub struct Any(Rc<dyn AnyObject>);
+pub struct Any(Arc<dyn AnyObject>);
 
 impl Any {
-    pub fn downcast<T>(self) -> Option<Rc<T>> 
+    pub fn downcast<T>(self) -> Option<Arc<T>> 
         where T: AnyObject + 'static
     {
         if TypeId::of::<T>() == self.0.type_id() {
             unsafe {
-                let raw: *const dyn AnyObject = Rc::into_raw(self.0);
-                Some(Rc::from_raw(raw as *const T))
+                let raw: *const dyn AnyObject = Arc::into_raw(self.0);
+                Some(Arc::from_raw(raw as *const T))
             }
         } else {
             None
         }
     }
-    pub fn new<T>(rc: Rc<T>) -> Any
+    pub fn new<T>(rc: Arc<T>) -> Any
         where T: AnyObject + 'static
     {
         Any(rc as _)
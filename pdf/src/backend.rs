@@ -1,11 +1,16 @@
 use memmap::Mmap;
 use crate::error::*;
 use crate::parser::Lexer;
-use crate::parser::{read_xref_and_trailer_at};
-use crate::xref::{XRefTable};
-use crate::primitive::{Dictionary};
+use crate::parser::{read_xref_and_trailer_at, parse_with_lexer, parse_indirect_object};
+use crate::xref::{XRef, XRefTable};
+use crate::primitive::{Primitive, Dictionary};
 use crate::object::*;
 
+use std::collections::{BTreeMap, HashMap};
+use std::cell::RefCell;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::rc::Rc;
 use std::ops::{
     RangeFull,
     RangeFrom,
@@ -19,19 +24,46 @@ pub trait Backend: Sized {
     fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]>;
     fn len(&self) -> usize;
 
+    /// Where streams can take their data from without copying it, for backends that hold
+    /// all of it in memory.
+    fn stream_source(_this: Arc<Self>) -> Option<Arc<dyn StreamSource>> {
+        None
+    }
+
     /// Returns the value of startxref (currently only used internally!)
     fn locate_xref_offset(&self) -> Result<usize> {
         // locate the xref offset at the end of the file
         // `\nPOS\n%%EOF` where POS is the position encoded as base 10 integer.
         // u64::MAX has 20 digits + \n\n(2) + %%EOF(5) = 27 bytes max.
 
-        let mut lexer = Lexer::new(self.read(..)?);
-        lexer.set_pos_from_end(0);
-        lexer.seek_substr_back(b"startxref")?;
-        Ok(lexer.next()?.to::<usize>()?)
+        // it should be in the last kilobyte, so only that is read if it is
+        let tail = self.len().saturating_sub(1024);
+        for start in &[tail, 0] {
+            let mut lexer = Lexer::new(self.read(*start..)?);
+            lexer.set_pos_from_end(0);
+            if lexer.seek_substr_back(b"startxref").is_ok() {
+                return Ok(lexer.next()?.to::<usize>()?);
+            }
+            if tail == 0 {
+                break;
+            }
+        }
+        Err(PdfError::NotFound { word: "startxref".into() })
     }
     /// Used internally by File, but could also be useful for applications that want to look at the raw PDF objects.
+    ///
+    /// If the xref sections can't be read, the table is rebuilt with `reconstruct_xref_table`.
     fn read_xref_table_and_trailer(&self) -> Result<(XRefTable, Dictionary)> {
+        match self.read_xref_chain() {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                warn!("can't read the xref table ({}), reconstructing it", e);
+                self.reconstruct_xref_table()
+            }
+        }
+    }
+    /// Reads the xref sections at `startxref` and those they link to with `/Prev`.
+    fn read_xref_chain(&self) -> Result<(XRefTable, Dictionary)> {
         let xref_offset = self.locate_xref_offset()?;
         let mut lexer = Lexer::new(self.read(xref_offset..)?);
         
@@ -70,8 +102,187 @@ pub trait Backend: Sized {
         }
         Ok((refs, trailer))
     }
+    /// Rebuilds the xref table of a damaged file by scanning it for `N G obj`.
+    /// Where an object occurs several times, the last one wins, as with incremental updates.
+    ///
+    /// The trailer is the last `trailer` dictionary with a `/Root`, or the dictionary of the
+    /// last xref stream. Failing that, one is made up that points to the last `/Catalog`.
+    fn reconstruct_xref_table(&self) -> Result<(XRefTable, Dictionary)> {
+        let data = self.read(..)?;
+        let mut objects: BTreeMap<ObjNr, (usize, GenNr)> = BTreeMap::new();
+        for (pos, id, gen) in scan_objects(data) {
+            objects.insert(id, (pos, gen));
+        }
+        if objects.is_empty() {
+            bail!("no objects found");
+        }
+
+        let mut trailer = None;
+        let mut pos = 0;
+        while let Some(offset) = find(&data[pos..], b"trailer") {
+            let mut lexer = Lexer::new(&data[pos + offset + 7..]);
+            pos += offset + 7;
+            match parse_with_lexer(&mut lexer, &NoResolve).and_then(|p| p.to_dictionary(&NoResolve)) {
+                Ok(dict) if dict.get("Root").is_some() => trailer = Some(dict),
+                _ => {}
+            }
+        }
+
+        // objects in object streams are only listed in xref streams
+        let mut compressed = BTreeMap::new();
+        let mut xref_stream_trailer = None;
+        let mut catalog = None;
+        for (&id, &(pos, gen)) in &objects {
+            let mut lexer = Lexer::new(&data[pos..]);
+            match parse_indirect_object(&mut lexer, &NoResolve) {
+                Ok((_, Primitive::Stream(ref stream))) if is_type(&stream.info, "XRef") => {
+                    let mut lexer = Lexer::new(&data[pos..]);
+                    if let Ok((sections, dict)) = read_xref_and_trailer_at(&mut lexer, &NoResolve) {
+                        for section in &sections {
+                            for (i, entry) in section.entries() {
+                                match *entry {
+                                    XRef::Stream { .. } if i as ObjNr <= MAX_OBJ_NR => { compressed.insert(i as ObjNr, *entry); }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        xref_stream_trailer = Some(dict);
+                    }
+                }
+                Ok((_, Primitive::Dictionary(ref dict))) if is_type(dict, "Catalog") => {
+                    catalog = Some(PlainRef { id, gen });
+                }
+                _ => {}
+            }
+        }
+
+        let size = objects.keys().chain(compressed.keys()).max().map(|&id| id + 1).unwrap_or(0);
+        let mut refs = XRefTable::new(0);
+        for id in 0 .. size {
+            refs.push(match (objects.get(&id), compressed.get(&id)) {
+                (Some(&(pos, gen_nr)), _) => XRef::Raw { pos, gen_nr },
+                (None, Some(&entry)) => entry,
+                (None, None) => XRef::Free { next_obj_nr: 0, gen_nr: if id == 0 { 65535 } else { 0 } },
+            });
+        }
+
+        let mut trailer = match trailer.or(xref_stream_trailer) {
+            Some(trailer) => trailer,
+            None => {
+                let catalog = catalog.ok_or_else(|| PdfError::from("no trailer and no catalog found".to_string()))?;
+                let mut trailer = Dictionary::new();
+                trailer.insert("Root".into(), Primitive::Reference(catalog));
+                trailer
+            }
+        };
+        trailer.remove("Prev");
+        trailer.insert("Size".into(), Primitive::Integer(size as i32));
+        Ok((refs, trailer))
+    }
 }
 
+/// The largest object number that is accepted when reconstructing the xref table (Annex C.2),
+/// which limits the size of the table.
+const MAX_OBJ_NR: ObjNr = 8_388_607;
+
+/// Position, object number and generation of every `N G obj` in `data`, except in stream data.
+fn scan_objects(data: &[u8]) -> Vec<(usize, ObjNr, GenNr)> {
+    let is_space = |b: u8| b" \t\r\n\x0c\0".contains(&b);
+    let is_delimiter = |b: u8| b"()<>[]{}/%".contains(&b);
+    // the start of the digits that end right before `end`
+    let digits = |end: usize| {
+        let start = data[.. end].iter().rposition(|b| !b.is_ascii_digit()).map(|i| i + 1).unwrap_or(0);
+        if start < end { Some(start) } else { None }
+    };
+    let spaces = |end: usize| data[.. end].iter().rposition(|&b| !is_space(b)).map(|i| i + 1).unwrap_or(0);
+
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = find(&data[pos..], b"obj") {
+        let obj = pos + offset;
+        pos = obj + 3;
+        if data.get(obj + 3).map(|&b| !is_space(b) && !is_delimiter(b)).unwrap_or(false) {
+            continue;
+        }
+        let gen_end = spaces(obj);
+        if gen_end == obj {
+            continue;
+        }
+        let gen_start = match digits(gen_end) {
+            Some(start) => start,
+            None => continue
+        };
+        let id_end = spaces(gen_start);
+        if id_end == gen_start {
+            continue;
+        }
+        let id_start = match digits(id_end) {
+            Some(start) => start,
+            None => continue
+        };
+        if id_start > 0 && !is_space(data[id_start - 1]) && !is_delimiter(data[id_start - 1]) {
+            continue;
+        }
+        let number = |range: Range<usize>| -> Option<u64> { std::str::from_utf8(&data[range]).ok()?.parse().ok() };
+        match (number(id_start .. id_end), number(gen_start .. gen_end)) {
+            (Some(id), Some(gen)) if id <= MAX_OBJ_NR && gen <= GenNr::max_value() as u64 => found.push((id_start, id, gen as GenNr)),
+            _ => continue
+        }
+        // what looks like objects in the data of a stream is not
+        let endobj = find(&data[pos..], b"endobj").map(|end| pos + end).unwrap_or(data.len());
+        if let Some(stream) = find_keyword(&data[pos .. endobj], b"stream") {
+            let data_start = pos + stream + 6;
+            if let Some(end) = find(&data[data_start..], b"endstream") {
+                pos = data_start + end + 9;
+            }
+        }
+    }
+    found
+}
+
+/// Like `find`, but not as part of a longer word, like `stream` in `endstream`.
+fn find_keyword(haystack: &[u8], keyword: &[u8]) -> Option<usize> {
+    let is_regular = |b: &u8| b.is_ascii_alphanumeric();
+    let mut pos = 0;
+    while let Some(offset) = find(&haystack[pos..], keyword) {
+        let start = pos + offset;
+        let end = start + keyword.len();
+        if !(start > 0 && is_regular(&haystack[start - 1])) && !haystack.get(end).map(is_regular).unwrap_or(false) {
+            return Some(start);
+        }
+        pos = end;
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn is_type(dict: &Dictionary, typ: &str) -> bool {
+    match dict.get("Type") {
+        Some(&Primitive::Name(ref name)) => name == typ,
+        _ => false
+    }
+}
+
+
+/// Data that streams can refer to instead of copying it, see `Backend::stream_source`.
+/// Reading it can't fail, as all of it is in memory.
+pub trait StreamSource: Send + Sync {
+    /// All of the data
+    fn bytes(&self) -> &[u8];
+}
+impl StreamSource for Vec<u8> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+impl StreamSource for Arc<[u8]> {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
 
 impl Backend for Mmap {
     fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
@@ -104,9 +315,139 @@ impl Backend for Vec<u8> {
     fn len(&self) -> usize {
         self.len()
     }
+    fn stream_source(this: Arc<Self>) -> Option<Arc<dyn StreamSource>> {
+        Some(this)
+    }
 }
 
+/// Shared, read-only data.
+impl Backend for Arc<[u8]> {
+    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
+        let r = range.to_range(self.len())?;
+        Ok(&self[r])
+    }
+    fn write<T: IndexRange>(&mut self, _range: T) -> Result<&mut [u8]> {
+        bail!("Arc<[u8]> backend is read-only")
+    }
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+    fn stream_source(this: Arc<Self>) -> Option<Arc<dyn StreamSource>> {
+        Some(this)
+    }
+}
+
+/// Shared, read-only data, for a single thread.
+impl Backend for Rc<[u8]> {
+    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
+        let r = range.to_range(self.len())?;
+        Ok(&self[r])
+    }
+    fn write<T: IndexRange>(&mut self, _range: T) -> Result<&mut [u8]> {
+        bail!("Rc<[u8]> backend is read-only")
+    }
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+/// Borrowed, read-only data.
+impl<'a> Backend for &'a [u8] {
+    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
+        let r = range.to_range(self.len())?;
+        Ok(&self[r])
+    }
+    fn write<T: IndexRange>(&mut self, _range: T) -> Result<&mut [u8]> {
+        bail!("&[u8] backend is read-only")
+    }
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
 
+/// Reads any `Read + Seek`, like a `std::fs::File` or a network stream with range requests,
+/// on demand instead of loading it as a whole.
+///
+/// The data is read in chunks, which are kept until the backend is dropped or `clear_cache`
+/// is called. Reads that cross chunks are kept separately. Note that some reads go to the end
+/// of the file, such as those of the xref sections and of objects at the end of the xref table.
+pub struct SeekBackend<R> {
+    reader: RefCell<R>,
+    len: usize,
+    chunk_size: usize,
+    /// by chunk index
+    chunks: RefCell<HashMap<usize, Box<[u8]>>>,
+    /// reads across chunks, by their range
+    spans: RefCell<Vec<(Range<usize>, Box<[u8]>)>>,
+}
+impl<R: Read + Seek> SeekBackend<R> {
+    pub fn new(reader: R) -> Result<SeekBackend<R>> {
+        SeekBackend::with_chunk_size(reader, 64 * 1024)
+    }
+    pub fn with_chunk_size(mut reader: R, chunk_size: usize) -> Result<SeekBackend<R>> {
+        let len = reader.seek(SeekFrom::End(0))? as usize;
+        Ok(SeekBackend {
+            reader: RefCell::new(reader),
+            len,
+            chunk_size: chunk_size.max(1),
+            chunks: RefCell::new(HashMap::new()),
+            spans: RefCell::new(Vec::new()),
+        })
+    }
+    /// Forgets the data read so far.
+    pub fn clear_cache(&mut self) {
+        self.chunks.get_mut().clear();
+        self.spans.get_mut().clear();
+    }
+    /// Reads `range`, which has to be within the file.
+    fn load(&self, range: Range<usize>) -> Result<Box<[u8]>> {
+        let mut data = vec![0; range.end - range.start].into_boxed_slice();
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(range.start as u64))?;
+        reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+impl<R: Read + Seek> Backend for SeekBackend<R> {
+    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
+        let r = range.to_range(self.len)?;
+        if r.start == r.end {
+            return Ok(&[]);
+        }
+        let chunk = r.start / self.chunk_size;
+        let chunk_start = chunk * self.chunk_size;
+        let (ptr, len) = if r.end <= chunk_start + self.chunk_size {
+            let mut chunks = self.chunks.borrow_mut();
+            if !chunks.contains_key(&chunk) {
+                let data = self.load(chunk_start .. self.len.min(chunk_start + self.chunk_size))?;
+                chunks.insert(chunk, data);
+            }
+            let data = &chunks[&chunk][r.start - chunk_start .. r.end - chunk_start];
+            (data.as_ptr(), data.len())
+        } else {
+            let mut spans = self.spans.borrow_mut();
+            let i = match spans.iter().position(|(s, _)| s.start <= r.start && r.end <= s.end) {
+                Some(i) => i,
+                None => {
+                    let data = self.load(r.clone())?;
+                    spans.push((r.clone(), data));
+                    spans.len() - 1
+                }
+            };
+            let (ref span, ref data) = spans[i];
+            let data = &data[r.start - span.start .. r.end - span.start];
+            (data.as_ptr(), data.len())
+        };
+        // The boxes are only dropped through `&mut self`, and moving them doesn't move their data.
+        Ok(unsafe { std::slice::from_raw_parts(ptr, len) })
+    }
+    fn write<T: IndexRange>(&mut self, _range: T) -> Result<&mut [u8]> {
+        bail!("SeekBackend is read-only")
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
 
 /// `IndexRange` is implemented by Rust's built-in range types, produced
 /// by range syntax like `..`, `a..`, `..b` or `c..d`.
@@ -0,0 +1,80 @@
+//! QR codes for generated pages, drawn as filled rectangles.
+//!
+//! ```ignore
+//! let qr = QrCode::new("https://example.com/invoice/42")?;
+//! let mut content = ContentBuilder::new();
+//! qr.draw(&mut content, 450., 650., 100.);
+//! ```
+
+use crate::content::{ContentBuilder, Winding};
+use crate::error::*;
+
+/// The modules of a QR code.
+#[derive(Debug, Clone)]
+pub struct QrCode {
+    /// row by row from the top left
+    dark: Vec<bool>,
+    width: usize,
+}
+impl QrCode {
+    /// Encodes `data` in the smallest QR code that holds it, with medium error correction.
+    pub fn new(data: impl AsRef<[u8]>) -> Result<QrCode> {
+        let code = match qrcode::QrCode::new(data) {
+            Ok(code) => code,
+            Err(e) => bail!("QR code: {}", e)
+        };
+        Ok(QrCode {
+            width: code.width(),
+            dark: code.to_colors().into_iter().map(|c| c == qrcode::Color::Dark).collect(),
+        })
+    }
+    /// The number of modules on each side.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// Whether the module in column `x` and row `y`, counting from the top left, is dark.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.width && self.dark.get(y * self.width + x).cloned().unwrap_or(false)
+    }
+
+    /// Fills the dark modules with the current fill color, in a square of `size` with the lower
+    /// left corner at `x`, `y`. Scanners need a light margin of four modules around the code,
+    /// which is not drawn.
+    pub fn draw(&self, content: &mut ContentBuilder, x: f32, y: f32, size: f32) {
+        let module = size / self.width as f32;
+        for (row, modules) in self.dark.chunks(self.width).enumerate() {
+            let bottom = y + size - (row + 1) as f32 * module;
+            // a rectangle for each run of dark modules
+            let mut col = 0;
+            while col < self.width {
+                if !modules[col] {
+                    col += 1;
+                    continue;
+                }
+                let start = col;
+                while col < self.width && modules[col] {
+                    col += 1;
+                }
+                content.rect(x + start as f32 * module, bottom, (col - start) as f32 * module, module);
+            }
+        }
+        content.fill(Winding::NonZero);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw() {
+        assert_eq!(QrCode::new("hello").unwrap().width(), 21);
+
+        let qr = QrCode { dark: vec![true, true, false, false, false, true, true, false, true], width: 3 };
+        assert!(qr.is_dark(2, 1) && !qr.is_dark(2, 0) && !qr.is_dark(3, 0));
+        let mut content = ContentBuilder::new();
+        qr.draw(&mut content, 10., 20., 6.);
+        assert_eq!(content.build().to_data().unwrap(),
+            &b"10 24 4 2 re\n14 22 2 2 re\n10 20 2 2 re\n14 20 2 2 re\nf\n"[..]);
+    }
+}
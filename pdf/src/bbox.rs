@@ -0,0 +1,485 @@
+//! Bounding box and coverage of the marks a content stream makes, without rendering it.
+//!
+//! Curves are bounded by their control points and glyphs by their advance width
+//! and a fixed ascent and descent, so the result can be slightly too large, never too small
+//! (apart from glyphs that extend beyond their advance).
+//! Marks painted in white are not counted.
+
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::object::*;
+use crate::content::{Content, Operation};
+use crate::font::Font;
+use crate::primitive::Primitive;
+use crate::text::{Matrix, TextState};
+
+/// Glyph extent in text space units below and above the baseline
+const DESCENT: f32 = -0.25;
+const ASCENT: f32 = 0.9;
+/// Width for glyphs of fonts without widths
+const DEFAULT_WIDTH: f32 = 0.5;
+
+/// Color components at least this close to white count as white
+const WHITE: f32 = 0.99;
+/// Gray level below which image pixels count as marks
+const DARK: u8 = 0xC0;
+
+/// Form XObjects can contain each other.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Copy, Clone)]
+struct Bounds {
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+}
+impl Bounds {
+    /// Intersects with nothing
+    const EMPTY: Bounds = Bounds { min_x: 0., min_y: 0., max_x: -1., max_y: -1. };
+
+    fn point(x: f32, y: f32) -> Bounds {
+        Bounds { min_x: x, min_y: y, max_x: x, max_y: y }
+    }
+    fn from_rect(r: Rect, m: &Matrix) -> Bounds {
+        let mut b = Bounds::point(r.left, r.bottom);
+        b.add(r.right, r.bottom);
+        b.add(r.left, r.top);
+        b.add(r.right, r.top);
+        b.transform(m)
+    }
+    fn add(&mut self, x: f32, y: f32) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+    fn union(self, b: Bounds) -> Bounds {
+        Bounds {
+            min_x: self.min_x.min(b.min_x),
+            min_y: self.min_y.min(b.min_y),
+            max_x: self.max_x.max(b.max_x),
+            max_y: self.max_y.max(b.max_y),
+        }
+    }
+    fn intersect(self, b: Bounds) -> Option<Bounds> {
+        let r = Bounds {
+            min_x: self.min_x.max(b.min_x),
+            min_y: self.min_y.max(b.min_y),
+            max_x: self.max_x.min(b.max_x),
+            max_y: self.max_y.min(b.max_y),
+        };
+        if r.min_x <= r.max_x && r.min_y <= r.max_y {
+            Some(r)
+        } else {
+            None
+        }
+    }
+    fn grow(self, d: f32) -> Bounds {
+        Bounds {
+            min_x: self.min_x - d,
+            min_y: self.min_y - d,
+            max_x: self.max_x + d,
+            max_y: self.max_y + d,
+        }
+    }
+    /// Bounds of the four transformed corners
+    fn transform(self, m: &Matrix) -> Bounds {
+        let (x, y) = m.transform_point(self.min_x, self.min_y);
+        let mut b = Bounds::point(x, y);
+        for &(x, y) in &[(self.max_x, self.min_y), (self.min_x, self.max_y), (self.max_x, self.max_y)] {
+            let (x, y) = m.transform_point(x, y);
+            b.add(x, y);
+        }
+        b
+    }
+    fn area(&self) -> f32 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+    fn to_rect(self) -> Rect {
+        Rect { left: self.min_x, bottom: self.min_y, right: self.max_x, top: self.max_y }
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    clip: Option<Bounds>,
+    line_width: f32,
+    text: TextState,
+    font: Option<Arc<Font>>,
+    fill_white: bool,
+    stroke_white: bool,
+}
+
+struct BBoxCollector<'a, R: Resolve> {
+    resolve: &'a R,
+    bounds: Option<Bounds>,
+    /// Sum of the areas of all marks, images weighted by their fraction of dark pixels
+    coverage: f32,
+    /// Decode images to find out how much of them is dark (otherwise they count completely)
+    image_stats: bool,
+}
+
+fn numbers(ops: &[Primitive], n: usize) -> Option<Vec<f32>> {
+    if ops.len() < n {
+        return None;
+    }
+    ops[ops.len() - n ..].iter().map(|p| p.as_number().ok()).collect()
+}
+
+/// Whether the color set by a color operator is white, judged by the number of components.
+fn is_white(args: &[Primitive]) -> bool {
+    let n: Option<Vec<f32>> = args.iter().map(|p| p.as_number().ok()).collect();
+    match n {
+        Some(ref n) if n.len() == 1 || n.len() == 3 => n.iter().all(|&c| c >= WHITE),
+        Some(ref n) if n.len() == 4 => n.iter().all(|&c| c <= 1. - WHITE),
+        _ => false
+    }
+}
+
+fn glyph_width(widths: &Option<[f32; 256]>, code: usize) -> f32 {
+    match widths.as_ref().and_then(|w| w.get(code)) {
+        Some(&w) if w > 0. => w * 0.001,
+        _ => DEFAULT_WIDTH
+    }
+}
+
+impl<'a, R: Resolve> BBoxCollector<'a, R> {
+    /// Records a mark covering `b`, `weight` is the fraction of `b` that is actually dark.
+    fn mark(&mut self, b: Bounds, state: &GraphicsState, weight: f32) {
+        let b = match state.clip {
+            Some(clip) => match b.intersect(clip) {
+                Some(b) => b,
+                None => return
+            },
+            None => b
+        };
+        self.coverage += b.area() * weight;
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.union(b),
+            None => b
+        });
+    }
+
+    fn text(&mut self, data: &[u8], state: &mut GraphicsState) -> Result<()> {
+        let font = state.font.clone();
+        let cmap = font.as_ref().and_then(|f| f.cmap());
+        let is_cid = cmap.is_some();
+        let cid_font = font.as_ref().and_then(|f| f.cid_font());
+        let widths = match font {
+            Some(ref font) if !is_cid => font.widths().unwrap_or(None),
+            _ => None
+        };
+        let codes: Vec<(usize, bool)> = match cmap {
+            // codes without a CID show the glyph of CID 0
+            Some(cmap) => cmap.cids(data).into_iter().map(|(code, cid)| (cid.unwrap_or(0) as usize, code == b" ")).collect(),
+            None => data.iter().map(|&b| (b as usize, b == 0x20)).collect()
+        };
+        for (code, is_space) in codes {
+            let width = match cid_font {
+                Some(cid_font) if is_cid => cid_font.width(code as u32) * 0.001,
+                _ => glyph_width(&widths, code)
+            };
+            // fill, stroke, both or neither (invisible); modes from 4 on also clip
+            let visible = match state.text.render_mode % 4 {
+                0 => !state.fill_white,
+                1 => !state.stroke_white,
+                2 => !(state.fill_white && state.stroke_white),
+                _ => false
+            };
+            if visible && !is_space {
+                let glyph = Bounds { min_x: 0., min_y: DESCENT, max_x: width, max_y: ASCENT };
+                let m = state.text.text_rendering_matrix() * state.ctm;
+                self.mark(glyph.transform(&m), state, 1.);
+            }
+            state.text.advance_glyph(width, is_space);
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, ops: &[Operation], resources: Option<&Resources>, state: GraphicsState, depth: usize) -> Result<()> {
+        let mut state = state;
+        let mut stack = Vec::new();
+        let mut path: Option<Bounds> = None;
+        let mut clip_pending = false;
+
+        for op in ops {
+            let args = &op.operands;
+            match op.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => if let Some(s) = stack.pop() {
+                    state = s;
+                },
+                "cm" => if let Some(n) = numbers(args, 6) {
+                    state.ctm = Matrix::new(n[0], n[1], n[2], n[3], n[4], n[5]) * state.ctm;
+                },
+                "w" => if let Some(n) = numbers(args, 1) {
+                    state.line_width = n[0];
+                },
+
+                // path construction
+                "m" | "l" | "c" | "v" | "y" => {
+                    for p in args.chunks(2) {
+                        if let (Some(x), Some(y)) = (p.get(0).and_then(|p| p.as_number().ok()), p.get(1).and_then(|p| p.as_number().ok())) {
+                            let (x, y) = state.ctm.transform_point(x, y);
+                            match path {
+                                Some(ref mut b) => b.add(x, y),
+                                None => path = Some(Bounds::point(x, y))
+                            }
+                        }
+                    }
+                }
+                "re" => if let Some(n) = numbers(args, 4) {
+                    let r = Bounds::from_rect(Rect { left: n[0], bottom: n[1], right: n[0] + n[2], top: n[1] + n[3] }, &state.ctm);
+                    path = Some(match path {
+                        Some(b) => b.union(r),
+                        None => r
+                    });
+                },
+                "h" => {}
+                "W" | "W*" => clip_pending = true,
+
+                // path painting
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+                    if let Some(b) = path.take() {
+                        let (fill, stroke) = match op.operator.as_str() {
+                            "S" | "s" => (false, true),
+                            "f" | "F" | "f*" => (true, false),
+                            "n" => (false, false),
+                            _ => (true, true)
+                        };
+                        if stroke && !state.stroke_white {
+                            let m = &state.ctm;
+                            let scale = (m.a * m.a + m.b * m.b).sqrt().max((m.c * m.c + m.d * m.d).sqrt());
+                            self.mark(b.grow(0.5 * state.line_width * scale), &state, 1.);
+                        }
+                        if fill && !state.fill_white {
+                            self.mark(b, &state, 1.);
+                        }
+                        if clip_pending {
+                            state.clip = match state.clip {
+                                Some(clip) => Some(clip.intersect(b).unwrap_or(Bounds::EMPTY)),
+                                None => Some(b)
+                            };
+                        }
+                    }
+                    clip_pending = false;
+                }
+
+                // shading fills the clipping region
+                "sh" => if let Some(clip) = state.clip {
+                    self.mark(clip, &state, 1.);
+                },
+
+                // color
+                "g" | "rg" | "k" | "sc" | "scn" => state.fill_white = is_white(args),
+                "G" | "RG" | "K" | "SC" | "SCN" => state.stroke_white = is_white(args),
+                // the initial color of all color spaces is black or not white
+                "cs" => state.fill_white = false,
+                "CS" => state.stroke_white = false,
+
+                // text
+                "BT" => state.text.begin_text(),
+                "ET" => {}
+                "Tc" => if let Some(n) = numbers(args, 1) { state.text.char_space = n[0]; },
+                "Tw" => if let Some(n) = numbers(args, 1) { state.text.word_space = n[0]; },
+                "Tz" => if let Some(n) = numbers(args, 1) { state.text.horiz_scale = 0.01 * n[0]; },
+                "TL" => if let Some(n) = numbers(args, 1) { state.text.leading = n[0]; },
+                "Ts" => if let Some(n) = numbers(args, 1) { state.text.rise = n[0]; },
+                "Tr" => if let Some(&Primitive::Integer(mode)) = args.get(0) { state.text.render_mode = mode; },
+                "Tf" => {
+                    if let Some(n) = numbers(args, 1) {
+                        state.text.font_size = n[0];
+                    }
+                    if let (Some(&Primitive::Name(ref name)), Some(resources)) = (args.get(0), resources) {
+                        state.font = resources.fonts.get(name).cloned();
+                    }
+                }
+                "Td" => if let Some(n) = numbers(args, 2) {
+                    state.text.translate(n[0], n[1]);
+                },
+                "TD" => if let Some(n) = numbers(args, 2) {
+                    state.text.leading = -n[1];
+                    state.text.translate(n[0], n[1]);
+                },
+                "Tm" => if let Some(n) = numbers(args, 6) {
+                    state.text.set_matrix(Matrix::new(n[0], n[1], n[2], n[3], n[4], n[5]));
+                },
+                "T*" => state.text.next_line(),
+                "Tj" | "'" | "\"" => {
+                    if op.operator != "Tj" {
+                        if op.operator == "\"" {
+                            if let Some(n) = numbers(&args[.. args.len().saturating_sub(1)], 2) {
+                                state.text.word_space = n[0];
+                                state.text.char_space = n[1];
+                            }
+                        }
+                        state.text.next_line();
+                    }
+                    if let Some(&Primitive::String(ref s)) = args.last() {
+                        self.text(s.as_bytes(), &mut state)?;
+                    }
+                }
+                "TJ" => if let Some(&Primitive::Array(ref parts)) = args.get(0) {
+                    for part in parts {
+                        match *part {
+                            Primitive::String(ref s) => self.text(s.as_bytes(), &mut state)?,
+                            ref p => if let Ok(n) = p.as_number() {
+                                state.text.adjust(n);
+                            }
+                        }
+                    }
+                },
+
+                // images are painted into the unit square
+                "BI" => self.mark(Bounds { min_x: 0., min_y: 0., max_x: 1., max_y: 1. }.transform(&state.ctm), &state, 1.),
+                "Do" => {
+                    let xobject = match (args.get(0), resources) {
+                        (Some(&Primitive::Name(ref name)), Some(resources)) => resources.xobjects.get(name),
+                        _ => None
+                    };
+                    match xobject {
+                        Some(&XObject::Image(ref image)) => {
+                            let weight = match self.image_stats {
+                                // images that can't be decoded count completely
+                                true => image.image_data(self.resolve).map(|data| data.dark_fraction(DARK)).unwrap_or(1.),
+                                false => 1.
+                            };
+                            self.mark(Bounds { min_x: 0., min_y: 0., max_x: 1., max_y: 1. }.transform(&state.ctm), &state, weight);
+                        }
+                        Some(&XObject::Form(ref form)) if depth < MAX_DEPTH => {
+                            let mut form_state = state.clone();
+                            if let Some(ref m) = form.matrix {
+                                let n = m.clone().resolve(self.resolve)?.as_array()?
+                                    .iter().map(|p| p.as_number())
+                                    .collect::<Result<Vec<f32>>>()?;
+                                if n.len() == 6 {
+                                    form_state.ctm = Matrix::new(n[0], n[1], n[2], n[3], n[4], n[5]) * form_state.ctm;
+                                }
+                            }
+                            let form_bbox = Bounds::from_rect(form.bbox, &form_state.ctm);
+                            form_state.clip = match state.clip {
+                                Some(clip) => match clip.intersect(form_bbox) {
+                                    Some(b) => Some(b),
+                                    None => continue
+                                },
+                                None => Some(form_bbox)
+                            };
+                            let content = Content::parse_from(form.data()?, self.resolve)?;
+                            let form_resources = form.resources.as_ref().map(|r| &**r).or(resources);
+                            self.run(&content.operations, form_resources, form_state, depth + 1)?;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn collect<'a, R: Resolve>(content: &Content, resources: Option<&Resources>, clip: Rect, resolve: &'a R, image_stats: bool) -> Result<BBoxCollector<'a, R>> {
+    let mut collector = BBoxCollector { resolve, bounds: None, coverage: 0., image_stats };
+    let state = GraphicsState {
+        ctm: Matrix::identity(),
+        clip: Some(Bounds::from_rect(clip, &Matrix::identity())),
+        line_width: 1.0,
+        text: TextState::new(),
+        font: None,
+        fill_white: false,
+        stroke_white: false,
+    };
+    collector.run(&content.operations, resources, state, 0)?;
+    Ok(collector)
+}
+
+/// Bounding box of the marks of `content` in user space, clipped to `clip`.
+/// `None` if nothing is painted.
+pub fn content_bbox(content: &Content, resources: Option<&Resources>, clip: Rect, resolve: &impl Resolve) -> Result<Option<Rect>> {
+    Ok(collect(content, resources, clip, resolve, false)?.bounds.map(|b| b.to_rect()))
+}
+
+/// Area (in user space units) covered by the marks of `content` within `clip`.
+/// Images only count with the fraction of pixels that are not near white.
+pub fn coverage(content: &Content, resources: Option<&Resources>, clip: Rect, resolve: &impl Resolve) -> Result<f32> {
+    Ok(collect(content, resources, clip, resolve, true)?.coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: Rect = Rect { left: 0., bottom: 0., right: 200., top: 100. };
+
+    fn bbox(ops: &str) -> Option<(f32, f32, f32, f32)> {
+        let content = Content::parse_from(ops.as_bytes(), &NoResolve).unwrap();
+        content_bbox(&content, None, PAGE, &NoResolve).unwrap()
+            .map(|r| (r.left, r.bottom, r.right, r.top))
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(bbox(""), None);
+        assert_eq!(bbox("10 20 30 40 re f"), Some((10., 20., 40., 60.)));
+        // unpainted paths and white fills don't count
+        assert_eq!(bbox("10 20 30 40 re n"), None);
+        assert_eq!(bbox("1 g 10 20 30 40 re f"), None);
+        assert_eq!(bbox("1 g 10 20 30 40 re f 0 g 5 5 10 10 re f"), Some((5., 5., 15., 15.)));
+        // strokes grow by half the line width
+        assert_eq!(bbox("4 w 10 10 m 50 10 l S"), Some((8., 8., 52., 12.)));
+        assert_eq!(bbox("q 2 0 0 2 10 10 cm 0 0 5 5 re f Q 0 0 1 1 re f"), Some((0., 0., 20., 20.)));
+    }
+
+    #[test]
+    fn clipping() {
+        // to the page
+        assert_eq!(bbox("-10 -10 300 50 re f"), Some((0., 0., 200., 40.)));
+        // by the clipping path, until it is restored
+        assert_eq!(bbox("q 0 0 10 10 re W n 5 5 20 20 re f Q"), Some((5., 5., 10., 10.)));
+        assert_eq!(bbox("q 0 0 10 10 re W n Q 5 5 20 20 re f"), Some((5., 5., 25., 25.)));
+        assert_eq!(bbox("q 0 0 10 10 re W n 50 50 10 10 re f Q"), None);
+        // shadings fill the clipping region
+        assert_eq!(bbox("q 20 20 10 10 re W n /Sh1 sh Q"), Some((20., 20., 30., 30.)));
+    }
+
+    #[test]
+    fn text() {
+        // without a font, glyphs are half an em wide
+        assert_eq!(bbox("BT /F1 10 Tf 100 50 Td (a b) Tj ET"), Some((100., 47.5, 115., 59.)));
+        // invisible text
+        assert_eq!(bbox("BT /F1 10 Tf 3 Tr 100 50 Td (ab) Tj ET"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "write")]
+    fn blank_pages() {
+        use crate::file::File;
+        use crate::image::embed::ImageFormat;
+        use crate::primitive::Dictionary;
+
+        let mut file = File::new().unwrap();
+        // a white and a half black image, 2x1 pixels
+        let white = file.add_image(&[255; 8], ImageFormat::Rgba { width: 2, height: 1 }).unwrap();
+        let half = file.add_image(&[0, 0, 0, 255, 255, 255, 255, 255], ImageFormat::Rgba { width: 2, height: 1 }).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 100., top: 100. };
+        for &ops in &["", "1 g 0 0 100 100 re f", "0 0 5 5 re f", "q 100 0 0 100 0 0 cm /White Do Q", "q 100 0 0 100 0 0 cm /Half Do Q"] {
+            let mut xobjects = Dictionary::new();
+            xobjects.insert("White".into(), white.xobject.into());
+            xobjects.insert("Half".into(), half.xobject.into());
+            let mut resources = Dictionary::new();
+            resources.insert("XObject".into(), xobjects.into());
+            let content = Content::parse_from(ops.as_bytes(), &NoResolve).unwrap();
+            file.add_page(media_box, &content, resources).unwrap();
+        }
+        let blank = |n, tolerance| file.get_page(n).unwrap().is_blank(&file, tolerance).unwrap();
+        assert!(blank(0, 0.) && blank(1, 0.));
+        // a quarter percent of the page
+        assert!(blank(2, 0.01) && !blank(2, 0.001));
+        // scans count with their dark pixels
+        assert!(blank(3, 0.));
+        assert!(blank(4, 0.6) && !blank(4, 0.4));
+    }
+}
@@ -3,18 +3,37 @@ use std;
 use std::fmt::{Display, Formatter};
 use std::mem::replace;
 use std::io;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
 use itertools::Itertools;
 
 use crate::error::*;
 use crate::object::*;
-use crate::parser::{Lexer, parse_with_lexer};
+use crate::parser::{Lexer, parse_with_lexer, inline_image_end};
 use crate::primitive::*;
+use crate::text::Matrix;
 
 /// Operation in a PDF content stream.
 #[derive(Debug, Clone)]
 pub struct Operation {
     pub operator: String,
     pub operands: Vec<Primitive>,
+    /// How each operand was written in the parsed content stream
+    source: Vec<Option<Source>>,
+}
+
+/// The text an operand was parsed from, and a fingerprint of the operand as it would be
+/// written, so that writing can tell whether the operand still has the parsed value.
+#[derive(Debug, Clone)]
+struct Source {
+    text: Vec<u8>,
+    fingerprint: u64,
+}
+
+fn fingerprint(serialized: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serialized.hash(&mut hasher);
+    hasher.finish()
 }
 
 impl Operation {
@@ -22,10 +41,82 @@ impl Operation {
         Operation{
             operator: operator,
             operands: operands,
+            source: Vec::new(),
         }
     }
+    /// Writes the operation in content stream syntax.
+    /// Operands that still have the value they were parsed with are written exactly as they
+    /// were found, so numbers keep their formatting and precision.
+    pub fn write<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        if let ("BI", Some(&Primitive::Stream(ref image))) = (self.operator.as_str(), self.operands.first()) {
+            out.write_all(b"BI")?;
+            for (key, value) in image.info.iter() {
+                out.write_all(b" ")?;
+                Primitive::Name(key.clone()).serialize(out)?;
+                out.write_all(b" ")?;
+                value.serialize(out)?;
+            }
+            out.write_all(b"\nID ")?;
+            out.write_all(&image.data)?;
+            out.write_all(b"\nEI\n")?;
+            return Ok(());
+        }
+        let mut buf = Vec::new();
+        for (i, operand) in self.operands.iter().enumerate() {
+            buf.clear();
+            operand.serialize(&mut buf)?;
+            match self.source.get(i) {
+                Some(&Some(ref source)) if source.fingerprint == fingerprint(&buf) => out.write_all(&source.text)?,
+                _ => out.write_all(&buf)?
+            }
+            out.write_all(b" ")?;
+        }
+        write!(out, "{}\n", self.operator)?;
+        Ok(())
+    }
+    /// Whether the operator is defined by the specification.
+    /// Unknown operators are only allowed inside a `BX`/`EX` compatibility section.
+    pub fn is_known(&self) -> bool {
+        OPERATORS.contains(&self.operator.as_str())
+    }
 }
 
+/// All content stream operators (Annex A)
+pub static OPERATORS: &[&str] = &[
+    // general graphics state
+    "w", "J", "j", "M", "d", "ri", "i", "gs",
+    // special graphics state
+    "q", "Q", "cm",
+    // path construction
+    "m", "l", "c", "v", "y", "h", "re",
+    // path painting
+    "S", "s", "f", "F", "f*", "B", "B*", "b", "b*", "n",
+    // clipping paths
+    "W", "W*",
+    // text objects
+    "BT", "ET",
+    // text state
+    "Tc", "Tw", "Tz", "TL", "Tf", "Tr", "Ts",
+    // text positioning
+    "Td", "TD", "Tm", "T*",
+    // text showing
+    "Tj", "TJ", "'", "\"",
+    // Type 3 fonts
+    "d0", "d1",
+    // color
+    "CS", "cs", "SC", "SCN", "sc", "scn", "G", "g", "RG", "rg", "K", "k",
+    // shading patterns
+    "sh",
+    // inline images
+    "BI", "ID", "EI",
+    // XObjects
+    "Do",
+    // marked content
+    "MP", "DP", "BMC", "BDC", "EMC",
+    // compatibility
+    "BX", "EX",
+];
+
 
 /// Represents a PDF content stream - a `Vec` of `Operator`s
 #[derive(Debug)]
@@ -34,22 +125,13 @@ pub struct Content {
 }
 
 impl Content {
-    fn parse_from(data: &[u8], resolve: &impl Resolve) -> Result<Content> {
-        {
-            use std::io::Write;
-            let mut f = std::fs::OpenOptions::new()
-                .write(true)
-                .append(true)
-                .create(true)
-                .open("/tmp/content.txt")
-                .unwrap();
-            writeln!(f, "\n~~~~~~~~~~~\n");
-            f.write_all(data).unwrap();
-        }
+    pub(crate) fn parse_from(data: &[u8], resolve: &impl Resolve) -> Result<Content> {
         let mut lexer = Lexer::new(data);
 
         let mut content = Content {operations: Vec::new()};
         let mut buffer = Vec::new();
+        let mut source = Vec::new();
+        let mut serialized = Vec::new();
 
         loop {
             let backup_pos = lexer.get_pos();
@@ -57,15 +139,36 @@ impl Content {
             match obj {
                 Ok(obj) => {
                     // Operand
+                    let text = trim(&data[backup_pos .. lexer.get_pos()]);
+                    serialized.clear();
+                    // comments can't be kept, as they would swallow the rest of the line,
+                    // and numbers in exponent notation are written in the syntax of the specification
+                    let exponent = match obj {
+                        Primitive::Number(_) => text.iter().any(|&b| b == b'e' || b == b'E'),
+                        _ => false
+                    };
+                    let src = match (text.first(), obj.serialize(&mut serialized)) {
+                        _ if exponent => None,
+                        (Some(&b'%'), _) | (_, Err(_)) => None,
+                        _ => Some(Source { text: text.to_vec(), fingerprint: fingerprint(&serialized) })
+                    };
+                    source.push(src);
                     buffer.push(obj)
                 }
                 Err(_) => {
                     // It's not an object/operand - treat it as an operator.
                     lexer.set_pos(backup_pos);
                     let operator = lexer.next()?.to_string();
-                    let operation = Operation::new(operator, replace(&mut buffer, Vec::new()));
                     // Give operands to operation and empty buffer.
-                    content.operations.push(operation.clone());
+                    let mut operation = Operation::new(operator, replace(&mut buffer, Vec::new()));
+                    operation.source = replace(&mut source, Vec::new());
+                    if operation.operator == "BI" {
+                        // the dictionary and data of an inline image become the operand of `BI`
+                        let image = parse_inline_image(&mut lexer, resolve)?;
+                        operation.operands = vec![Primitive::Stream(image)];
+                        operation.source.clear();
+                    }
+                    content.operations.push(operation);
                 }
             }
             if lexer.get_pos() > data.len() {
@@ -76,11 +179,62 @@ impl Content {
         }
         Ok(content)
     }
+    /// A content stream with the operations, to add to a file and refer to from `/Contents`.
+    pub fn to_stream(&self) -> Result<PdfStream> {
+        Ok(PdfStream { info: Dictionary::new(), data: self.to_data()?.into() })
+    }
+    /// The content stream data: all operations in content stream syntax.
+    pub fn to_data(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for operation in &self.operations {
+            operation.write(&mut data)?;
+        }
+        Ok(data)
+    }
+}
+
+/// Reads the entries of an inline image dictionary after `BI`, the data after `ID`,
+/// and the `EI` after the data.
+fn parse_inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<PdfStream> {
+    let mut info = Dictionary::new();
+    while !lexer.peek()?.equals(b"ID") {
+        let key = parse_with_lexer(lexer, resolve)?.to_name()?;
+        let value = parse_with_lexer(lexer, resolve)?;
+        info.insert(key, value);
+    }
+    lexer.next()?;
+    // a single whitespace byte separates `ID` from the data
+    let rest = lexer.get_remaining_slice();
+    let start = rest.len().min(1);
+    let end = inline_image_end(rest, start);
+    let data = rest[start .. end].to_vec();
+    lexer.offset_pos(end);
+    if lexer.peek().map(|s| s.equals(b"EI")).unwrap_or(false) {
+        lexer.next()?;
+    }
+    Ok(PdfStream { info, data: data.into() })
+}
+
+/// Strips leading and trailing whitespace.
+fn trim(mut text: &[u8]) -> &[u8] {
+    let is_space = |b: &u8| match *b {
+        b' ' | b'\t' | b'\r' | b'\n' | b'\x0C' | 0 => true,
+        _ => false
+    };
+    while text.first().map(is_space).unwrap_or(false) {
+        text = &text[1..];
+    }
+    while text.last().map(is_space).unwrap_or(false) {
+        text = &text[.. text.len() - 1];
+    }
+    text
 }
 
 impl Object for Content {
     /// Write object as a byte stream
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> Result<()> {unimplemented!()}
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        self.to_stream()?.serialize(out)
+    }
     /// Convert primitive to Self
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         type ContentStream = Stream<()>;
@@ -105,6 +259,701 @@ impl Object for Content {
 }
 
 
+/// A point in user space or text space
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Rule that decides what is inside a path (8.5.3.3)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Winding {
+    NonZero,
+    EvenOdd,
+}
+
+/// Element of the array shown by `TJ`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextItem {
+    Text(PdfString),
+    /// In thousandths of text space units, subtracted from the position
+    Offset(f32),
+}
+
+/// Content stream operation with decoded operands, one variant per operator (Annex A).
+#[derive(Debug, Clone)]
+pub enum Op {
+    /// `w`
+    LineWidth(f32),
+    /// `J`
+    LineCap(LineCap),
+    /// `j`
+    LineJoin(LineJoin),
+    /// `M`
+    MiterLimit(f32),
+    /// `d`
+    Dash(DashPattern),
+    /// `ri`
+    RenderingIntent(String),
+    /// `i`
+    Flatness(f32),
+    /// `gs`
+    GraphicsState(String),
+
+    /// `q`
+    Save,
+    /// `Q`
+    Restore,
+    /// `cm`
+    Transform(Matrix),
+
+    /// `m`
+    MoveTo(Point),
+    /// `l`
+    LineTo(Point),
+    /// `c`
+    CurveTo { c1: Point, c2: Point, p: Point },
+    /// `v`: the first control point is the current point
+    CurveToV { c2: Point, p: Point },
+    /// `y`: the second control point is the end point
+    CurveToY { c1: Point, p: Point },
+    /// `h`
+    Close,
+    /// `re`
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+
+    /// `S`
+    Stroke,
+    /// `s`
+    CloseStroke,
+    /// `f`, `F` and `f*`
+    Fill(Winding),
+    /// `B` and `B*`
+    FillStroke(Winding),
+    /// `b` and `b*`
+    CloseFillStroke(Winding),
+    /// `n`
+    EndPath,
+    /// `W` and `W*`
+    Clip(Winding),
+
+    /// `BT`
+    BeginText,
+    /// `ET`
+    EndText,
+    /// `Tc`
+    CharSpacing(f32),
+    /// `Tw`
+    WordSpacing(f32),
+    /// `Tz`, in percent
+    HorizontalScaling(f32),
+    /// `TL`
+    Leading(f32),
+    /// `Tf`
+    SetFont { name: String, size: f32 },
+    /// `Tr`
+    TextRenderMode(i32),
+    /// `Ts`
+    TextRise(f32),
+    /// `Td`
+    MoveText(Point),
+    /// `TD`: also sets the leading to `-y`
+    MoveTextSetLeading(Point),
+    /// `Tm`
+    SetTextMatrix(Matrix),
+    /// `T*`
+    NextLine,
+    /// `Tj`
+    ShowText(PdfString),
+    /// `TJ`
+    ShowTextArray(Vec<TextItem>),
+    /// `'`
+    NextLineShowText(PdfString),
+    /// `"`
+    NextLineShowTextSpacing { word_spacing: f32, char_spacing: f32, text: PdfString },
+
+    /// `d0`
+    CharWidth(Point),
+    /// `d1`
+    CharWidthBBox { width: Point, bbox: Rect },
+
+    /// `CS`
+    StrokeColorSpace(String),
+    /// `cs`
+    FillColorSpace(String),
+    /// `SC` and `SCN`, with the name of a pattern after the components, if any
+    StrokeColor { color: Vec<f32>, pattern: Option<String> },
+    /// `sc` and `scn`
+    FillColor { color: Vec<f32>, pattern: Option<String> },
+    /// `G`
+    StrokeGray(f32),
+    /// `g`
+    FillGray(f32),
+    /// `RG`
+    StrokeRgb([f32; 3]),
+    /// `rg`
+    FillRgb([f32; 3]),
+    /// `K`
+    StrokeCmyk([f32; 4]),
+    /// `k`
+    FillCmyk([f32; 4]),
+
+    /// `sh`
+    Shading(String),
+    /// `Do`
+    XObject(String),
+
+    /// `MP`
+    MarkedContentPoint { tag: String, properties: Option<Primitive> },
+    /// `BMC` and `BDC`
+    BeginMarkedContent { tag: String, properties: Option<Primitive> },
+    /// `EMC`
+    EndMarkedContent,
+
+    /// `BX`
+    BeginCompatibility,
+    /// `EX`
+    EndCompatibility,
+
+    /// `BI`, `ID` and `EI`
+    InlineImage(InlineImage),
+
+    /// Operators the specification doesn't define, as they were found.
+    /// Every operator in `OPERATORS` has its own variant.
+    Other(Operation),
+}
+
+/// An image in a content stream. Its dictionary keeps the abbreviated keys it was written with.
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    pub dict: Dictionary,
+    pub data: Vec<u8>,
+}
+
+impl InlineImage {
+    /// The image as an image XObject, with the keys written out in full (Table 93).
+    /// Abbreviated filter and color space names are understood as well.
+    pub fn image(&self) -> Result<ImageXObject> {
+        let mut info = Dictionary::new();
+        for (key, value) in self.dict.iter() {
+            let key = match key.as_str() {
+                "BPC" => "BitsPerComponent",
+                "CS" => "ColorSpace",
+                "D" => "Decode",
+                "DP" => "DecodeParms",
+                "F" => "Filter",
+                "H" => "Height",
+                "IM" => "ImageMask",
+                "I" => "Interpolate",
+                "W" => "Width",
+                key => key
+            };
+            info.insert(key.into(), value.clone());
+        }
+        info.insert("Length".into(), Primitive::Integer(self.data.len() as i32));
+        ImageXObject::from_primitive(Primitive::Stream(PdfStream { info, data: self.data.clone().into() }), &NoResolve)
+    }
+}
+
+/// Takes the operands of one operation in order.
+struct Operands<'a> {
+    operator: &'a str,
+    operands: std::slice::Iter<'a, Primitive>,
+}
+impl<'a> Operands<'a> {
+    fn next(&mut self) -> Result<&'a Primitive> {
+        match self.operands.next() {
+            Some(p) => Ok(p),
+            None => bail!("missing operand for {}", self.operator)
+        }
+    }
+    fn number(&mut self) -> Result<f32> {
+        self.next()?.as_number()
+    }
+    fn numbers<A: Default + AsMut<[f32]>>(&mut self) -> Result<A> {
+        let mut a = A::default();
+        for n in a.as_mut() {
+            *n = self.number()?;
+        }
+        Ok(a)
+    }
+    fn point(&mut self) -> Result<Point> {
+        let [x, y]: [f32; 2] = self.numbers()?;
+        Ok(Point { x, y })
+    }
+    fn matrix(&mut self) -> Result<Matrix> {
+        let [a, b, c, d, e, f]: [f32; 6] = self.numbers()?;
+        Ok(Matrix::new(a, b, c, d, e, f))
+    }
+    fn name(&mut self) -> Result<String> {
+        self.next()?.as_name().map(|s| s.to_owned())
+    }
+    fn string(&mut self) -> Result<PdfString> {
+        match *self.next()? {
+            Primitive::String(ref s) => Ok(s.clone()),
+            ref p => unexpected_primitive!(String, p.get_debug_name())
+        }
+    }
+    /// The optional property list of marked content, a name or an inline dictionary.
+    fn properties(&mut self) -> Option<Primitive> {
+        self.operands.next().cloned()
+    }
+    /// Color components, and a trailing pattern name.
+    fn color(&mut self) -> Result<(Vec<f32>, Option<String>)> {
+        let mut color = Vec::new();
+        let mut pattern = None;
+        for p in &mut self.operands {
+            match *p {
+                Primitive::Name(ref name) => pattern = Some(name.clone()),
+                ref p => color.push(p.as_number()?)
+            }
+        }
+        Ok((color, pattern))
+    }
+}
+
+impl Op {
+    /// Decodes the operands of `operation`.
+    pub fn from_operation(operation: &Operation) -> Result<Op> {
+        let mut ops = Operands { operator: &operation.operator, operands: operation.operands.iter() };
+        let op = match operation.operator.as_str() {
+            "w" => Op::LineWidth(ops.number()?),
+            "J" => Op::LineCap(LineCap::from_primitive(ops.next()?.clone(), &NoResolve)?),
+            "j" => Op::LineJoin(LineJoin::from_primitive(ops.next()?.clone(), &NoResolve)?),
+            "M" => Op::MiterLimit(ops.number()?),
+            "d" => {
+                let array = Vec::<f32>::from_primitive(ops.next()?.clone(), &NoResolve)?;
+                Op::Dash(DashPattern { array, phase: ops.number()? })
+            }
+            "ri" => Op::RenderingIntent(ops.name()?),
+            "i" => Op::Flatness(ops.number()?),
+            "gs" => Op::GraphicsState(ops.name()?),
+            "q" => Op::Save,
+            "Q" => Op::Restore,
+            "cm" => Op::Transform(ops.matrix()?),
+            "m" => Op::MoveTo(ops.point()?),
+            "l" => Op::LineTo(ops.point()?),
+            "c" => Op::CurveTo { c1: ops.point()?, c2: ops.point()?, p: ops.point()? },
+            "v" => Op::CurveToV { c2: ops.point()?, p: ops.point()? },
+            "y" => Op::CurveToY { c1: ops.point()?, p: ops.point()? },
+            "h" => Op::Close,
+            "re" => {
+                let [x, y, width, height]: [f32; 4] = ops.numbers()?;
+                Op::Rect { x, y, width, height }
+            }
+            "S" => Op::Stroke,
+            "s" => Op::CloseStroke,
+            "f" | "F" => Op::Fill(Winding::NonZero),
+            "f*" => Op::Fill(Winding::EvenOdd),
+            "B" => Op::FillStroke(Winding::NonZero),
+            "B*" => Op::FillStroke(Winding::EvenOdd),
+            "b" => Op::CloseFillStroke(Winding::NonZero),
+            "b*" => Op::CloseFillStroke(Winding::EvenOdd),
+            "n" => Op::EndPath,
+            "W" => Op::Clip(Winding::NonZero),
+            "W*" => Op::Clip(Winding::EvenOdd),
+            "BT" => Op::BeginText,
+            "ET" => Op::EndText,
+            "Tc" => Op::CharSpacing(ops.number()?),
+            "Tw" => Op::WordSpacing(ops.number()?),
+            "Tz" => Op::HorizontalScaling(ops.number()?),
+            "TL" => Op::Leading(ops.number()?),
+            "Tf" => Op::SetFont { name: ops.name()?, size: ops.number()? },
+            "Tr" => Op::TextRenderMode(ops.next()?.as_integer()?),
+            "Ts" => Op::TextRise(ops.number()?),
+            "Td" => Op::MoveText(ops.point()?),
+            "TD" => Op::MoveTextSetLeading(ops.point()?),
+            "Tm" => Op::SetTextMatrix(ops.matrix()?),
+            "T*" => Op::NextLine,
+            "Tj" => Op::ShowText(ops.string()?),
+            "TJ" => {
+                let items = ops.next()?.as_array()?.iter().map(|p| -> Result<TextItem> { match *p {
+                    Primitive::String(ref s) => Ok(TextItem::Text(s.clone())),
+                    ref p => Ok(TextItem::Offset(p.as_number()?))
+                }}).collect::<Result<Vec<_>>>()?;
+                Op::ShowTextArray(items)
+            }
+            "'" => Op::NextLineShowText(ops.string()?),
+            "\"" => Op::NextLineShowTextSpacing { word_spacing: ops.number()?, char_spacing: ops.number()?, text: ops.string()? },
+            "d0" => Op::CharWidth(ops.point()?),
+            "d1" => {
+                let width = ops.point()?;
+                let [left, bottom, right, top]: [f32; 4] = ops.numbers()?;
+                Op::CharWidthBBox { width, bbox: Rect { left, bottom, right, top } }
+            }
+            "CS" => Op::StrokeColorSpace(ops.name()?),
+            "cs" => Op::FillColorSpace(ops.name()?),
+            "SC" | "SCN" => {
+                let (color, pattern) = ops.color()?;
+                Op::StrokeColor { color, pattern }
+            }
+            "sc" | "scn" => {
+                let (color, pattern) = ops.color()?;
+                Op::FillColor { color, pattern }
+            }
+            "G" => Op::StrokeGray(ops.number()?),
+            "g" => Op::FillGray(ops.number()?),
+            "RG" => Op::StrokeRgb(ops.numbers()?),
+            "rg" => Op::FillRgb(ops.numbers()?),
+            "K" => Op::StrokeCmyk(ops.numbers()?),
+            "k" => Op::FillCmyk(ops.numbers()?),
+            "sh" => Op::Shading(ops.name()?),
+            "Do" => Op::XObject(ops.name()?),
+            "MP" => Op::MarkedContentPoint { tag: ops.name()?, properties: None },
+            "DP" => Op::MarkedContentPoint { tag: ops.name()?, properties: ops.properties() },
+            "BMC" => Op::BeginMarkedContent { tag: ops.name()?, properties: None },
+            "BDC" => Op::BeginMarkedContent { tag: ops.name()?, properties: ops.properties() },
+            "EMC" => Op::EndMarkedContent,
+            "BX" => Op::BeginCompatibility,
+            "EX" => Op::EndCompatibility,
+            "BI" => match operation.operands.first() {
+                Some(&Primitive::Stream(ref image)) => Op::InlineImage(InlineImage { dict: image.info.clone(), data: image.data.to_vec() }),
+                _ => bail!("inline image without data")
+            },
+            _ => Op::Other(operation.clone())
+        };
+        Ok(op)
+    }
+
+    /// The operation in content stream form.
+    pub fn to_operation(&self) -> Operation {
+        let num = |n: f32| Primitive::Number(n);
+        let name = |s: &str| Primitive::Name(s.into());
+        let point = |p: &Point| vec![num(p.x), num(p.y)];
+        let matrix = |m: &Matrix| vec![num(m.a), num(m.b), num(m.c), num(m.d), num(m.e), num(m.f)];
+        let fill_stroke = |winding: Winding, nonzero: &'static str, evenodd: &'static str| match winding {
+            Winding::NonZero => nonzero,
+            Winding::EvenOdd => evenodd,
+        };
+        let color = |color: &[f32], pattern: &Option<String>| color.iter().cloned().map(num)
+            .chain(pattern.as_ref().map(|p| name(p)))
+            .collect();
+        let (operator, operands): (&str, Vec<Primitive>) = match *self {
+            Op::LineWidth(w) => ("w", vec![num(w)]),
+            Op::LineCap(cap) => ("J", vec![Primitive::Integer(cap as i32)]),
+            Op::LineJoin(join) => ("j", vec![Primitive::Integer(join as i32)]),
+            Op::MiterLimit(limit) => ("M", vec![num(limit)]),
+            Op::Dash(ref dash) => ("d", vec![
+                Primitive::Array(dash.array.iter().cloned().map(num).collect()),
+                num(dash.phase)
+            ]),
+            Op::RenderingIntent(ref intent) => ("ri", vec![name(intent)]),
+            Op::Flatness(f) => ("i", vec![num(f)]),
+            Op::GraphicsState(ref gs) => ("gs", vec![name(gs)]),
+            Op::Save => ("q", vec![]),
+            Op::Restore => ("Q", vec![]),
+            Op::Transform(ref m) => ("cm", matrix(m)),
+            Op::MoveTo(ref p) => ("m", point(p)),
+            Op::LineTo(ref p) => ("l", point(p)),
+            Op::CurveTo { ref c1, ref c2, ref p } => ("c", [point(c1), point(c2), point(p)].concat()),
+            Op::CurveToV { ref c2, ref p } => ("v", [point(c2), point(p)].concat()),
+            Op::CurveToY { ref c1, ref p } => ("y", [point(c1), point(p)].concat()),
+            Op::Close => ("h", vec![]),
+            Op::Rect { x, y, width, height } => ("re", vec![num(x), num(y), num(width), num(height)]),
+            Op::Stroke => ("S", vec![]),
+            Op::CloseStroke => ("s", vec![]),
+            Op::Fill(w) => (fill_stroke(w, "f", "f*"), vec![]),
+            Op::FillStroke(w) => (fill_stroke(w, "B", "B*"), vec![]),
+            Op::CloseFillStroke(w) => (fill_stroke(w, "b", "b*"), vec![]),
+            Op::EndPath => ("n", vec![]),
+            Op::Clip(w) => (fill_stroke(w, "W", "W*"), vec![]),
+            Op::BeginText => ("BT", vec![]),
+            Op::EndText => ("ET", vec![]),
+            Op::CharSpacing(c) => ("Tc", vec![num(c)]),
+            Op::WordSpacing(w) => ("Tw", vec![num(w)]),
+            Op::HorizontalScaling(s) => ("Tz", vec![num(s)]),
+            Op::Leading(l) => ("TL", vec![num(l)]),
+            Op::SetFont { name: ref font, size } => ("Tf", vec![name(font), num(size)]),
+            Op::TextRenderMode(mode) => ("Tr", vec![Primitive::Integer(mode)]),
+            Op::TextRise(r) => ("Ts", vec![num(r)]),
+            Op::MoveText(ref p) => ("Td", point(p)),
+            Op::MoveTextSetLeading(ref p) => ("TD", point(p)),
+            Op::SetTextMatrix(ref m) => ("Tm", matrix(m)),
+            Op::NextLine => ("T*", vec![]),
+            Op::ShowText(ref text) => ("Tj", vec![Primitive::String(text.clone())]),
+            Op::ShowTextArray(ref items) => ("TJ", vec![Primitive::Array(items.iter().map(|item| match *item {
+                TextItem::Text(ref text) => Primitive::String(text.clone()),
+                TextItem::Offset(offset) => num(offset),
+            }).collect())]),
+            Op::NextLineShowText(ref text) => ("'", vec![Primitive::String(text.clone())]),
+            Op::NextLineShowTextSpacing { word_spacing, char_spacing, ref text } =>
+                ("\"", vec![num(word_spacing), num(char_spacing), Primitive::String(text.clone())]),
+            Op::CharWidth(ref p) => ("d0", point(p)),
+            Op::CharWidthBBox { ref width, ref bbox } =>
+                ("d1", [point(width), vec![num(bbox.left), num(bbox.bottom), num(bbox.right), num(bbox.top)]].concat()),
+            Op::StrokeColorSpace(ref cs) => ("CS", vec![name(cs)]),
+            Op::FillColorSpace(ref cs) => ("cs", vec![name(cs)]),
+            Op::StrokeColor { color: ref c, ref pattern } => ("SCN", color(c, pattern)),
+            Op::FillColor { color: ref c, ref pattern } => ("scn", color(c, pattern)),
+            Op::StrokeGray(g) => ("G", vec![num(g)]),
+            Op::FillGray(g) => ("g", vec![num(g)]),
+            Op::StrokeRgb(ref c) => ("RG", color(c, &None)),
+            Op::FillRgb(ref c) => ("rg", color(c, &None)),
+            Op::StrokeCmyk(ref c) => ("K", color(c, &None)),
+            Op::FillCmyk(ref c) => ("k", color(c, &None)),
+            Op::Shading(ref sh) => ("sh", vec![name(sh)]),
+            Op::XObject(ref xobject) => ("Do", vec![name(xobject)]),
+            Op::MarkedContentPoint { ref tag, properties: None } => ("MP", vec![name(tag)]),
+            Op::MarkedContentPoint { ref tag, properties: Some(ref p) } => ("DP", vec![name(tag), p.clone()]),
+            Op::BeginMarkedContent { ref tag, properties: None } => ("BMC", vec![name(tag)]),
+            Op::BeginMarkedContent { ref tag, properties: Some(ref p) } => ("BDC", vec![name(tag), p.clone()]),
+            Op::EndMarkedContent => ("EMC", vec![]),
+            Op::BeginCompatibility => ("BX", vec![]),
+            Op::EndCompatibility => ("EX", vec![]),
+            Op::InlineImage(ref image) => ("BI", vec![Primitive::Stream(PdfStream { info: image.dict.clone(), data: image.data.clone().into() })]),
+            Op::Other(ref operation) => return operation.clone(),
+        };
+        Operation::new(operator.into(), operands)
+    }
+}
+
+/// Iterator over the operations of a content stream as `Op`s, see `Content::ops`.
+pub struct Ops<'a> {
+    operations: std::slice::Iter<'a, Operation>,
+}
+impl<'a> Iterator for Ops<'a> {
+    type Item = Result<Op>;
+    fn next(&mut self) -> Option<Result<Op>> {
+        self.operations.next().map(Op::from_operation)
+    }
+}
+
+impl Content {
+    /// The operations with their operands decoded. Operations with missing or
+    /// invalid operands give an error, and iteration can go on after it.
+    pub fn ops(&self) -> Ops {
+        Ops { operations: self.operations.iter() }
+    }
+    /// Parses the content stream `data` into `Op`s.
+    pub fn parse_ops(data: &[u8], resolve: &impl Resolve) -> Result<Vec<Op>> {
+        Content::parse_from(data, resolve)?.ops().collect()
+    }
+    pub fn from_ops(ops: &[Op]) -> Content {
+        Content { operations: ops.iter().map(Op::to_operation).collect() }
+    }
+}
+
+/// Writes content streams operator by operator.
+///
+/// ```ignore
+/// let mut content = ContentBuilder::new();
+/// content.set_fill_rgb(1., 0., 0.).rect(10., 10., 100., 50.).fill(Winding::NonZero);
+/// let stream = content.build().to_stream()?;
+/// ```
+#[derive(Debug, Default)]
+pub struct ContentBuilder {
+    operations: Vec<Operation>,
+}
+impl ContentBuilder {
+    pub fn new() -> ContentBuilder {
+        ContentBuilder::default()
+    }
+    /// Appends `op`.
+    pub fn op(&mut self, op: Op) -> &mut Self {
+        self.operations.push(op.to_operation());
+        self
+    }
+    pub fn build(self) -> Content {
+        Content { operations: self.operations }
+    }
+
+    /// `q`
+    pub fn save(&mut self) -> &mut Self {
+        self.op(Op::Save)
+    }
+    /// `Q`
+    pub fn restore(&mut self) -> &mut Self {
+        self.op(Op::Restore)
+    }
+    /// `cm`
+    pub fn transform(&mut self, matrix: Matrix) -> &mut Self {
+        self.op(Op::Transform(matrix))
+    }
+    /// `w`
+    pub fn line_width(&mut self, width: f32) -> &mut Self {
+        self.op(Op::LineWidth(width))
+    }
+    /// `J`
+    pub fn line_cap(&mut self, cap: LineCap) -> &mut Self {
+        self.op(Op::LineCap(cap))
+    }
+    /// `j`
+    pub fn line_join(&mut self, join: LineJoin) -> &mut Self {
+        self.op(Op::LineJoin(join))
+    }
+    /// `M`
+    pub fn miter_limit(&mut self, limit: f32) -> &mut Self {
+        self.op(Op::MiterLimit(limit))
+    }
+    /// `d`
+    pub fn dash(&mut self, array: Vec<f32>, phase: f32) -> &mut Self {
+        self.op(Op::Dash(DashPattern { array, phase }))
+    }
+    /// `gs`
+    pub fn graphics_state(&mut self, name: &str) -> &mut Self {
+        self.op(Op::GraphicsState(name.into()))
+    }
+
+    /// `m`
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.op(Op::MoveTo(Point { x, y }))
+    }
+    /// `l`
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.op(Op::LineTo(Point { x, y }))
+    }
+    /// `c`
+    pub fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) -> &mut Self {
+        self.op(Op::CurveTo { c1: Point { x: x1, y: y1 }, c2: Point { x: x2, y: y2 }, p: Point { x, y } })
+    }
+    /// `h`
+    pub fn close_path(&mut self) -> &mut Self {
+        self.op(Op::Close)
+    }
+    /// `re`
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) -> &mut Self {
+        self.op(Op::Rect { x, y, width, height })
+    }
+    /// `S`
+    pub fn stroke(&mut self) -> &mut Self {
+        self.op(Op::Stroke)
+    }
+    /// `s`
+    pub fn close_stroke(&mut self) -> &mut Self {
+        self.op(Op::CloseStroke)
+    }
+    /// `f` or `f*`
+    pub fn fill(&mut self, winding: Winding) -> &mut Self {
+        self.op(Op::Fill(winding))
+    }
+    /// `B` or `B*`
+    pub fn fill_stroke(&mut self, winding: Winding) -> &mut Self {
+        self.op(Op::FillStroke(winding))
+    }
+    /// `n`
+    pub fn end_path(&mut self) -> &mut Self {
+        self.op(Op::EndPath)
+    }
+    /// `W` or `W*`, which takes effect with the next painting operator
+    pub fn clip(&mut self, winding: Winding) -> &mut Self {
+        self.op(Op::Clip(winding))
+    }
+
+    /// `BT`
+    pub fn begin_text(&mut self) -> &mut Self {
+        self.op(Op::BeginText)
+    }
+    /// `ET`
+    pub fn end_text(&mut self) -> &mut Self {
+        self.op(Op::EndText)
+    }
+    /// `Tf`, with the name of a font in the resources
+    pub fn set_font(&mut self, name: &str, size: f32) -> &mut Self {
+        self.op(Op::SetFont { name: name.into(), size })
+    }
+    /// `Tc`
+    pub fn char_spacing(&mut self, spacing: f32) -> &mut Self {
+        self.op(Op::CharSpacing(spacing))
+    }
+    /// `Tw`
+    pub fn word_spacing(&mut self, spacing: f32) -> &mut Self {
+        self.op(Op::WordSpacing(spacing))
+    }
+    /// `TL`
+    pub fn leading(&mut self, leading: f32) -> &mut Self {
+        self.op(Op::Leading(leading))
+    }
+    /// `Tr`
+    pub fn text_render_mode(&mut self, mode: i32) -> &mut Self {
+        self.op(Op::TextRenderMode(mode))
+    }
+    /// `Ts`
+    pub fn text_rise(&mut self, rise: f32) -> &mut Self {
+        self.op(Op::TextRise(rise))
+    }
+    /// `Td`
+    pub fn move_text(&mut self, x: f32, y: f32) -> &mut Self {
+        self.op(Op::MoveText(Point { x, y }))
+    }
+    /// `Tm`
+    pub fn text_matrix(&mut self, matrix: Matrix) -> &mut Self {
+        self.op(Op::SetTextMatrix(matrix))
+    }
+    /// `T*`
+    pub fn next_line(&mut self) -> &mut Self {
+        self.op(Op::NextLine)
+    }
+    /// `Tj`. `text` is in the encoding of the current font.
+    pub fn show_text(&mut self, text: impl Into<Vec<u8>>) -> &mut Self {
+        self.op(Op::ShowText(PdfString::new(text.into())))
+    }
+    /// `TJ`
+    pub fn show_text_array(&mut self, items: Vec<TextItem>) -> &mut Self {
+        self.op(Op::ShowTextArray(items))
+    }
+
+    /// `g`
+    pub fn set_fill_gray(&mut self, gray: f32) -> &mut Self {
+        self.op(Op::FillGray(gray))
+    }
+    /// `G`
+    pub fn set_stroke_gray(&mut self, gray: f32) -> &mut Self {
+        self.op(Op::StrokeGray(gray))
+    }
+    /// `rg`
+    pub fn set_fill_rgb(&mut self, r: f32, g: f32, b: f32) -> &mut Self {
+        self.op(Op::FillRgb([r, g, b]))
+    }
+    /// `RG`
+    pub fn set_stroke_rgb(&mut self, r: f32, g: f32, b: f32) -> &mut Self {
+        self.op(Op::StrokeRgb([r, g, b]))
+    }
+    /// `k`
+    pub fn set_fill_cmyk(&mut self, c: f32, m: f32, y: f32, k: f32) -> &mut Self {
+        self.op(Op::FillCmyk([c, m, y, k]))
+    }
+    /// `K`
+    pub fn set_stroke_cmyk(&mut self, c: f32, m: f32, y: f32, k: f32) -> &mut Self {
+        self.op(Op::StrokeCmyk([c, m, y, k]))
+    }
+    /// `cs`, with a device color space or the name of one in the resources
+    pub fn fill_color_space(&mut self, name: &str) -> &mut Self {
+        self.op(Op::FillColorSpace(name.into()))
+    }
+    /// `CS`
+    pub fn stroke_color_space(&mut self, name: &str) -> &mut Self {
+        self.op(Op::StrokeColorSpace(name.into()))
+    }
+    /// `scn`, in the current fill color space
+    pub fn set_fill_color(&mut self, color: &[f32]) -> &mut Self {
+        self.op(Op::FillColor { color: color.to_vec(), pattern: None })
+    }
+    /// `SCN`
+    pub fn set_stroke_color(&mut self, color: &[f32]) -> &mut Self {
+        self.op(Op::StrokeColor { color: color.to_vec(), pattern: None })
+    }
+
+    /// `sh`
+    pub fn shading(&mut self, name: &str) -> &mut Self {
+        self.op(Op::Shading(name.into()))
+    }
+    /// `Do`
+    pub fn draw_xobject(&mut self, name: &str) -> &mut Self {
+        self.op(Op::XObject(name.into()))
+    }
+    /// `BMC`, or `BDC` with `properties`
+    pub fn begin_marked_content(&mut self, tag: &str, properties: Option<Primitive>) -> &mut Self {
+        self.op(Op::BeginMarkedContent { tag: tag.into(), properties })
+    }
+    /// `EMC`
+    pub fn end_marked_content(&mut self) -> &mut Self {
+        self.op(Op::EndMarkedContent)
+    }
+}
+
 impl Display for Content {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         write!(f, "Content: ")?;
@@ -120,3 +969,109 @@ impl Display for Operation {
         write!(f, "{} : {}", self.operator, self.operands.iter().format(", "))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_formatting() {
+        let data = b"0.333333333 0 0 1.0 10 20 cm\n/F1 12 Tf (a b) Tj";
+        let mut content = Content::parse_from(data, &NoResolve).unwrap();
+        assert_eq!(content.to_data().unwrap(), &b"0.333333333 0 0 1.0 10 20 cm\n/F1 12 Tf\n(a b) Tj\n"[..]);
+
+        content.operations[0].operands[4] = Primitive::Number(5.5);
+        assert_eq!(&content.to_data().unwrap()[.. 30], &b"0.333333333 0 0 1.0 5.5 20 cm\n"[..]);
+
+        // exponents are not PDF syntax
+        let content = Content::parse_from(b"1e-5 -2.5E2 Td", &NoResolve).unwrap();
+        assert_eq!(content.to_data().unwrap(), &b"0.00001 -250 Td\n"[..]);
+    }
+
+    #[test]
+    fn typed_ops() {
+        let data = b"1 0 0 1 10 20 cm /F1 12 Tf [(a) -250 (b)] TJ /P0 scn 0.5 g f* 1 w";
+        let ops = Content::parse_ops(data, &NoResolve).unwrap();
+        assert_eq!(ops.len(), 7);
+        match ops[1] {
+            Op::SetFont { ref name, size } => assert_eq!((name.as_str(), size), ("F1", 12.)),
+            ref op => panic!("{:?}", op)
+        }
+        match ops[2] {
+            Op::ShowTextArray(ref items) => assert_eq!(items.len(), 3),
+            ref op => panic!("{:?}", op)
+        }
+        match ops[3] {
+            Op::FillColor { ref color, ref pattern } => assert_eq!((color.len(), pattern.as_ref().map(|s| s.as_str())), (0, Some("P0"))),
+            ref op => panic!("{:?}", op)
+        }
+        match ops[5] {
+            Op::Fill(Winding::EvenOdd) => {}
+            ref op => panic!("{:?}", op)
+        }
+        let content = Content::from_ops(&ops);
+        assert_eq!(content.operations[0].operator, "cm");
+        assert_eq!(content.operations[5].operator, "f*");
+
+        let bad = Content::parse_from(b"1 Tf", &NoResolve).unwrap();
+        assert!(bad.ops().next().unwrap().is_err());
+    }
+
+    #[test]
+    fn rare_ops() {
+        let data = b"/Perceptual ri 0.5 i /GS0 gs /Sh0 sh 10 0 d0 10 0 0 0 8 8 d1 \
+            /Tag MP /Tag <</MCID 3>> DP BX 1 2 frob EX";
+        let content = Content::parse_from(data, &NoResolve).unwrap();
+        let ops: Vec<Op> = content.ops().collect::<Result<_>>().unwrap();
+        assert_eq!(ops.len(), 11);
+        // only operators the specification doesn't define are left untyped
+        for (op, operation) in ops.iter().zip(&content.operations) {
+            match *op {
+                Op::Other(ref other) => assert_eq!((other.operator.as_str(), other.is_known()), ("frob", false)),
+                _ => assert!(operation.is_known(), "{}", operation.operator)
+            }
+        }
+        match ops[5] {
+            Op::CharWidthBBox { ref width, ref bbox } => assert_eq!((width.x, bbox.right, bbox.top), (10., 8., 8.)),
+            ref op => panic!("{:?}", op)
+        }
+        let operators: Vec<String> = Content::from_ops(&ops).operations.into_iter().map(|op| op.operator).collect();
+        assert_eq!(operators, ["ri", "i", "gs", "sh", "d0", "d1", "MP", "DP", "BX", "frob", "EX"]);
+    }
+
+    #[test]
+    fn inline_image() {
+        let data = b"q BI /W 2 /H 1 /BPC 8 /CS /G /F /AHx ID 00ff> EI Q";
+        let content = Content::parse_from(data, &NoResolve).unwrap();
+        assert_eq!(content.operations.len(), 3);
+        let ops: Vec<Op> = content.ops().collect::<Result<_>>().unwrap();
+        let image = match ops[1] {
+            Op::InlineImage(ref image) => image.image().unwrap(),
+            ref op => panic!("{:?}", op)
+        };
+        let decoded = image.image_data(&NoResolve).unwrap();
+        assert_eq!((decoded.width, decoded.height, decoded.components), (2, 1, 1));
+        assert_eq!(decoded.data, vec![0, 255]);
+
+        let written = Content::from_ops(&ops).to_data().unwrap();
+        let reparsed = Content::parse_from(&written, &NoResolve).unwrap();
+        assert_eq!(reparsed.operations.len(), 3);
+        assert_eq!(reparsed.to_data().unwrap(), written);
+    }
+
+    #[test]
+    fn builder() {
+        let mut builder = ContentBuilder::new();
+        builder.save()
+            .set_fill_rgb(1., 0., 0.5)
+            .rect(10., 10., 100., 50.)
+            .fill(Winding::EvenOdd)
+            .restore()
+            .begin_text()
+            .set_font("F1", 12.)
+            .show_text("Hi")
+            .end_text();
+        let data = builder.build().to_data().unwrap();
+        assert_eq!(data, &b"q\n1 0 0.5 rg\n10 10 100 50 re\nf*\nQ\nBT\n/F1 12 Tf\n(Hi) Tj\nET\n"[..]);
+    }
+}
@@ -0,0 +1,332 @@
+//! Converting the colors of a document to gray or CMYK.
+
+use std::collections::{HashMap, HashSet};
+
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream};
+use crate::backend::Backend;
+use crate::file::File;
+use crate::content::{Content, Operation};
+
+/// Form XObjects can contain each other.
+const MAX_DEPTH: usize = 16;
+
+/// The color space `File::convert_colors` converts to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorTarget {
+    DeviceGray,
+    DeviceCMYK,
+}
+impl ColorTarget {
+    fn name(self) -> &'static str {
+        match self {
+            ColorTarget::DeviceGray => "DeviceGray",
+            ColorTarget::DeviceCMYK => "DeviceCMYK",
+        }
+    }
+    /// Converts `color` in `space`, `None` if the color space can't be converted.
+    fn convert(self, space: &ColorSpace, color: &[f32]) -> Option<Vec<f32>> {
+        match (self, space) {
+            (ColorTarget::DeviceGray, &ColorSpace::DeviceGray) |
+            (ColorTarget::DeviceCMYK, &ColorSpace::DeviceCMYK) => return Some(color.to_vec()),
+            (ColorTarget::DeviceCMYK, &ColorSpace::DeviceGray) => return Some(vec![0., 0., 0., 1. - color.get(0).cloned().unwrap_or(0.)]),
+            _ => {}
+        }
+        let (r, g, b) = space.to_rgb(color)?;
+        Some(match self {
+            ColorTarget::DeviceGray => vec![0.3 * r + 0.59 * g + 0.11 * b],
+            ColorTarget::DeviceCMYK => {
+                let k = 1. - r.max(g).max(b);
+                if k >= 1. {
+                    vec![0., 0., 0., 1.]
+                } else {
+                    vec![(1. - r - k) / (1. - k), (1. - g - k) / (1. - k), (1. - b - k) / (1. - k), k]
+                }
+            }
+        })
+    }
+    /// The operation that sets `color`.
+    fn operation(self, color: Vec<f32>, stroke: bool) -> Operation {
+        let operator = match (self, stroke) {
+            (ColorTarget::DeviceGray, false) => "g",
+            (ColorTarget::DeviceGray, true) => "G",
+            (ColorTarget::DeviceCMYK, false) => "k",
+            (ColorTarget::DeviceCMYK, true) => "K",
+        };
+        Operation::new(operator.into(), color.into_iter().map(Primitive::Number).collect())
+    }
+}
+
+impl<B: Backend> File<B> {
+    /// Rewrites the color operators of all pages and form XObjects, and the samples of images,
+    /// to use `target` instead of the device, calibrated, ICC based and indexed color spaces.
+    ///
+    /// Colors are converted through RGB without color management, so ICC profiles only
+    /// serve to count the components. Separation, DeviceN and Lab colors, patterns, shadings,
+    /// inline images, image masks and images with a color key mask are left as they are.
+    /// So are form XObjects without resources of their own that are shown on pages whose
+    /// resources make them look different.
+    pub fn convert_colors(&mut self, target: ColorTarget) -> Result<()> {
+        let mut converter = Converter { file: self, target, done: HashSet::new(), inherited: HashMap::new(), updates: Vec::new() };
+        let mut contents = Vec::new();
+        for (page_ref, page) in self.page_refs()?.into_iter().zip(self.pages()) {
+            let page = page?;
+            let resources = page.resources(self).ok();
+            let xobjects = self.xobject_refs(self.raw_page_resources(page_ref)?)?;
+            for &r in xobjects.values() {
+                converter.convert_xobject(r, resources.as_ref().map(|r| &**r), 0)?;
+            }
+            if let Some(ref content) = page.contents {
+                let operations = converter.convert_operations(&content.operations, resources.as_ref().map(|r| &**r));
+                contents.push((page_ref, content_stream(Dictionary::new(), &operations)?));
+            }
+        }
+        let mut updates = converter.updates;
+        for (r, stream) in converter.inherited {
+            match stream {
+                Some(stream) => updates.push((r, Primitive::Stream(stream))),
+                None => warn!("form {} {} is used with different resources", r.id, r.gen)
+            }
+        }
+
+        for (r, primitive) in updates {
+            self.update(r.id, primitive);
+        }
+        // pages may share their content streams, so each gets a new one
+        for (page_ref, stream) in contents {
+            let mut page = self.resolve(page_ref)?.to_dictionary(self)?;
+            let content_ref = self.add(Primitive::Stream(stream));
+            page.insert("Contents".into(), Primitive::Reference(content_ref.get_inner()));
+            self.update(page_ref.id, Primitive::Dictionary(page));
+        }
+        Ok(())
+    }
+}
+
+struct Converter<'a, B: Backend> {
+    file: &'a File<B>,
+    target: ColorTarget,
+    /// XObjects that were already looked at
+    done: HashSet<PlainRef>,
+    /// Forms without resources, which are converted with the resources of every page they
+    /// are on. `None` if the results differ.
+    inherited: HashMap<PlainRef, Option<PdfStream>>,
+    updates: Vec<(PlainRef, Primitive)>,
+}
+impl<'a, B: Backend> Converter<'a, B> {
+    fn convert_operations(&self, ops: &[Operation], resources: Option<&Resources>) -> Vec<Operation> {
+        let device = |name: &str| ColorSpace::from_name(name).cloned();
+        // the current color spaces, `None` if they are not converted
+        let mut fill = device("DeviceGray");
+        let mut stroke = device("DeviceGray");
+        let mut stack = Vec::new();
+        let mut out = Vec::with_capacity(ops.len());
+        for op in ops {
+            let stroking = match op.operator.as_str() {
+                "G" | "RG" | "K" | "CS" | "SC" | "SCN" => true,
+                _ => false
+            };
+            let space = match op.operator.as_str() {
+                "q" => {
+                    stack.push((fill.clone(), stroke.clone()));
+                    None
+                }
+                "Q" => {
+                    if let Some((f, s)) = stack.pop() {
+                        fill = f;
+                        stroke = s;
+                    }
+                    None
+                }
+                "g" | "G" => Some(device("DeviceGray")),
+                "rg" | "RG" => Some(device("DeviceRGB")),
+                "k" | "K" => Some(device("DeviceCMYK")),
+                "cs" | "CS" => {
+                    let space = match op.operands.first() {
+                        Some(&Primitive::Name(ref name)) => match resources {
+                            Some(resources) => resources.color_space(name).cloned(),
+                            None => device(name)
+                        },
+                        _ => None
+                    };
+                    Some(space.filter(|cs| self.target.convert(cs, &cs.initial_color()).is_some()))
+                }
+                _ => None
+            };
+            if let Some(space) = space {
+                if stroking {
+                    stroke = space;
+                } else {
+                    fill = space;
+                }
+            }
+            let current = if stroking { &stroke } else { &fill };
+            let color = match op.operator.as_str() {
+                "cs" | "CS" => current.as_ref().map(|cs| cs.initial_color()),
+                "g" | "G" | "rg" | "RG" | "k" | "K" | "sc" | "SC" | "scn" | "SCN" =>
+                    op.operands.iter().map(|p| p.as_number()).collect::<Result<Vec<f32>>>().ok(),
+                _ => None
+            };
+            let converted = match (current, color) {
+                (&Some(ref cs), Some(color)) => self.target.convert(cs, &color),
+                _ => None
+            };
+            match converted {
+                Some(color) => out.push(self.target.operation(color, stroking)),
+                None => out.push(op.clone())
+            }
+        }
+        out
+    }
+
+    fn convert_xobject(&mut self, r: PlainRef, resources: Option<&Resources>, depth: usize) -> Result<()> {
+        if self.done.contains(&r) {
+            return Ok(());
+        }
+        let stream = self.file.resolve(r)?.to_stream(self.file)?;
+        let subtype = match stream.info.get("Subtype") {
+            Some(&Primitive::Name(ref name)) => name.clone(),
+            _ => return Ok(())
+        };
+        let inherits = subtype == "Form" && stream.info.get("Resources").is_none();
+        if !inherits {
+            self.done.insert(r);
+        }
+        match subtype.as_str() {
+            "Image" => match self.convert_image(stream) {
+                Ok(Some(image)) => self.updates.push((r, Primitive::Stream(image))),
+                Ok(None) => {}
+                Err(e) => warn!("image {} {}: {}", r.id, r.gen, e)
+            },
+            "Form" if depth < MAX_DEPTH => {
+                let info = stream.info.clone();
+                // forms without resources use those of the page
+                let xobjects = match info.get("Resources") {
+                    Some(res) => self.file.xobject_refs(Some(res.clone()))?,
+                    None => Default::default()
+                };
+                let form = FormXObject::from_primitive(Primitive::Stream(stream), self.file)?;
+                let resources = form.resources.as_ref().map(|r| &**r).or(resources);
+                for &xobject in xobjects.values() {
+                    self.convert_xobject(xobject, resources, depth + 1)?;
+                }
+                let content = Content::parse_from(form.data()?, self.file)?;
+                let operations = self.convert_operations(&content.operations, resources);
+                let converted = content_stream(info, &operations)?;
+                if !inherits {
+                    self.updates.push((r, Primitive::Stream(converted)));
+                } else if let Some(previous) = self.inherited.get_mut(&r) {
+                    if previous.as_ref().map(|s| s.data[..] != converted.data[..]).unwrap_or(false) {
+                        *previous = None;
+                    }
+                } else {
+                    self.inherited.insert(r, Some(converted));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn convert_image(&self, stream: PdfStream) -> Result<Option<PdfStream>> {
+        let mut info = stream.info.clone();
+        if let Some(&Primitive::Array(_)) = info.get("Mask") {
+            return Ok(None);
+        }
+        let image = ImageXObject::from_primitive(Primitive::Stream(stream), self.file)?;
+        if image.image_mask {
+            return Ok(None);
+        }
+        let space = match image.color_space {
+            Some(ref cs) => ColorSpace::from_primitive(cs.clone(), self.file)?,
+            None => return Ok(None)
+        };
+        // image_data maps indexed samples through the palette
+        let space = match space {
+            ColorSpace::Indexed { base, .. } => *base,
+            cs => cs
+        };
+        match (self.target, &space) {
+            (ColorTarget::DeviceGray, &ColorSpace::DeviceGray) |
+            (ColorTarget::DeviceCMYK, &ColorSpace::DeviceCMYK) => return Ok(None),
+            _ => {}
+        }
+        if self.target.convert(&space, &space.initial_color()).is_none() {
+            return Ok(None);
+        }
+        let decoded = image.image_data(self.file)?;
+        let mut color = vec![0.; decoded.components];
+        let mut data = Vec::new();
+        for pixel in decoded.data.chunks(decoded.components) {
+            for (c, &v) in color.iter_mut().zip(pixel) {
+                *c = v as f32 / 255.;
+            }
+            let converted = self.target.convert(&space, &color)
+                .ok_or_else(|| PdfError::from(format!("can't convert {:?}", space)))?;
+            data.extend(converted.into_iter().map(|v| (v.max(0.).min(1.) * 255.).round() as u8));
+        }
+        for key in &["Decode", "DecodeParms", "Length"] {
+            info.remove(key);
+        }
+        info.insert("ColorSpace".into(), Primitive::Name(self.target.name().into()));
+        info.insert("BitsPerComponent".into(), Primitive::Integer(8));
+        info.insert("Filter".into(), Primitive::Name("FlateDecode".into()));
+        Ok(Some(PdfStream { info, data: deflate_bytes_zlib(&data).into() }))
+    }
+}
+
+/// A Flate compressed content stream with the entries of `info`.
+fn content_stream(mut info: Dictionary, operations: &[Operation]) -> Result<PdfStream> {
+    let content = Content { operations: operations.to_vec() };
+    for key in &["DecodeParms", "Length"] {
+        info.remove(key);
+    }
+    info.insert("Filter".into(), Primitive::Name("FlateDecode".into()));
+    Ok(PdfStream { info, data: deflate_bytes_zlib(&content.to_data()?).into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn form(file: &mut File<Vec<u8>>, ops: &str) -> PlainRef {
+        let mut info = Dictionary::new();
+        info.insert("Type".into(), "XObject".into());
+        info.insert("Subtype".into(), "Form".into());
+        info.insert("BBox".into(), Primitive::Array([0, 0, 1, 1].iter().map(|&n| Primitive::Integer(n)).collect()));
+        file.add(Primitive::Stream(PdfStream { info, data: ops.as_bytes().to_vec().into() })).get_inner()
+    }
+    fn operators(content: &Content) -> Vec<&str> {
+        content.operations.iter().map(|op| op.operator.as_str()).collect()
+    }
+    fn form_operators(file: &File<Vec<u8>>, r: PlainRef) -> Vec<String> {
+        let stream = Stream::<()>::from_primitive(file.resolve(r).unwrap(), file).unwrap();
+        let content = Content::parse_from(stream.data().unwrap(), file).unwrap();
+        operators(&content).into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn shared_forms() {
+        let mut file = File::new().unwrap();
+        let device = form(&mut file, "1 0 0 rg 0 0 1 1 re f");
+        let named = form(&mut file, "/CS0 cs 1 0 0 sc 0 0 1 1 re f");
+        let media_box = Rect { left: 0., bottom: 0., right: 100., top: 100. };
+        for cs in &["[/CalRGB << >>]", "[/CalGray << >>]"] {
+            let resources = format!("<< /ColorSpace << /CS0 {} >> /XObject << /Fm1 {} 0 R /Fm2 {} 0 R >> >>", cs, device.id, named.id);
+            let resources = parse(resources.as_bytes(), &NoResolve).unwrap().to_dictionary(&NoResolve).unwrap();
+            let content = Content::parse_from(b"0 0 1 RG /Fm1 Do /Fm2 Do", &NoResolve).unwrap();
+            file.add_page(media_box, &content, resources).unwrap();
+        }
+        file.convert_colors(ColorTarget::DeviceGray).unwrap();
+
+        let page = file.get_page(1).unwrap();
+        assert_eq!(operators(page.contents.as_ref().unwrap()), ["G", "Do", "Do"]);
+        assert_eq!(form_operators(&file, device), ["g", "re", "f"]);
+        // red on the first page and white on the second
+        assert_eq!(form_operators(&file, named), ["cs", "sc", "re", "f"]);
+    }
+}
@@ -1,6 +1,8 @@
 /// PDF "cryptography" – This is why you don't write your own crypto.
 
-use crate::primitive::PdfString;
+use std::collections::BTreeMap;
+
+use crate::primitive::{PdfString, PdfStream, Primitive, Dictionary};
 use crate::error::{PdfError, Result};
 
 const PADDING: [u8; 32] = [
@@ -48,6 +50,30 @@ impl Rc4 {
     }
 }
 
+/// 7.6.5 Table 25: how a crypt filter encrypts
+#[derive(Object, Debug, Copy, Clone, PartialEq)]
+pub enum CryptMethod {
+    /// No encryption
+    None,
+    /// RC4
+    V2,
+    /// AES-128 in CBC mode
+    AESV2,
+    /// AES-256 in CBC mode
+    AESV3,
+}
+
+/// 7.6.5 Table 25
+#[derive(Object, Debug, Clone)]
+pub struct CryptFilter {
+    #[pdf(key="CFM", default="CryptMethod::None")]
+    pub method: CryptMethod,
+
+    /// Key length, in bytes (some writers give bits)
+    #[pdf(key="Length")]
+    pub length: Option<u32>,
+}
+
 /// 7.6.1 Table 20 + 7.6.3.2 Table 21
 #[derive(Object, Debug, Clone)]
 pub struct CryptDict {
@@ -62,13 +88,77 @@ pub struct CryptDict {
     
     #[pdf(key="P")]
     p: i32,
+
+    #[pdf(key="V", default="0")]
+    v: i32,
     
     #[pdf(key="Length", default="40")]
     bits: u32,
+
+    #[pdf(key="CF")]
+    crypt_filters: BTreeMap<String, CryptFilter>,
+
+    /// Filter for streams
+    #[pdf(key="StmF", default="String::from(\"Identity\")")]
+    stream_filter: String,
+
+    /// Filter for strings
+    #[pdf(key="StrF", default="String::from(\"Identity\")")]
+    string_filter: String,
+
+    /// Revision 5 and 6: the file key, encrypted with the owner password
+    #[pdf(key="OE")]
+    oe: Option<PdfString>,
+
+    /// Revision 5 and 6: the file key, encrypted with the user password
+    #[pdf(key="UE")]
+    ue: Option<PdfString>,
+
+    #[pdf(key="EncryptMetadata", default="true")]
+    encrypt_metadata: bool,
+}
+impl CryptDict {
+    /// The method of the crypt filter `name`
+    fn method(&self, name: &str) -> Result<CryptMethod> {
+        if self.v < 4 {
+            return Ok(CryptMethod::V2);
+        }
+        match name {
+            "Identity" => Ok(CryptMethod::None),
+            name => match self.crypt_filters.get(name) {
+                Some(filter) => Ok(filter.method),
+                None => bail!("no crypt filter {:?}", name)
+            }
+        }
+    }
+    /// Length of the file key in bytes
+    fn key_size(&self) -> Result<usize> {
+        let size = match self.v {
+            0 | 1 => 5,
+            2 | 3 => self.bits as usize / 8,
+            4 => match self.crypt_filters.get(&self.stream_filter).and_then(|f| f.length) {
+                Some(n) if n > 32 => n as usize / 8,
+                Some(n) => n as usize,
+                None => 16
+            },
+            5 => 32,
+            v => bail!("unsupported encryption /V {}", v)
+        };
+        if size < 5 || size > 32 {
+            bail!("invalid key length {}", size);
+        }
+        Ok(size)
+    }
 }
+
 pub struct Decoder {
     key_size: usize,
-    key: [u8; 16] // maximum length
+    key: [u8; 32], // maximum length
+    stream_method: CryptMethod,
+    string_method: CryptMethod,
+    /// Methods of the crypt filters that streams can name in their `/Filter`
+    methods: BTreeMap<String, CryptMethod>,
+    encrypt_metadata: bool,
 }
 impl Decoder {
     pub fn default(dict: &CryptDict, id: &[u8]) -> Result<Decoder> {
@@ -78,12 +168,79 @@ impl Decoder {
         &self.key[.. self.key_size]
     }
     pub fn from_password(dict: &CryptDict, id: &[u8], pass: &[u8]) -> Result<Decoder> {
-        // 7.6.3.3 - Algorithm 2
+        let key_size = dict.key_size()?;
+        let mut decoder = Decoder {
+            key: [0; 32],
+            key_size,
+            stream_method: dict.method(&dict.stream_filter)?,
+            string_method: dict.method(&dict.string_filter)?,
+            methods: dict.crypt_filters.iter().map(|(name, f)| (name.clone(), f.method)).collect(),
+            encrypt_metadata: dict.encrypt_metadata,
+        };
+        match dict.r {
+            2 ..= 4 => {
+                // `pass` is the user password, or the owner password that the user password can be recovered from
+                let user_pass = Decoder::user_password(dict, pass, key_size);
+                for pass in [pass, &user_pass[..]].iter() {
+                    decoder.key[.. 16].copy_from_slice(&Decoder::compute_key(dict, id, pass, key_size));
+                    if decoder.check_password(dict, id) {
+                        return Ok(decoder);
+                    }
+                }
+                Err(PdfError::InvalidPassword)
+            }
+            5 | 6 => {
+                decoder.key = aes::file_key(dict, pass)?;
+                Ok(decoder)
+            }
+            r => bail!("unsupported encryption revision {}", r)
+        }
+    }
+    /// 7.6.3.4 - Algorithm 7: the (padded) user password, if `owner_pass` is the owner password
+    fn user_password(dict: &CryptDict, owner_pass: &[u8], key_size: usize) -> [u8; 32] {
+        // a)
+        let key = Decoder::owner_key(dict.r, owner_pass);
+        let n = key_size.min(16);
+
+        // b)
+        let mut data = [0; 32];
+        let o = dict.o.as_bytes();
+        data[.. o.len().min(32)].copy_from_slice(&o[.. o.len().min(32)]);
+        if dict.r == 2 {
+            Rc4::encrypt(&key[.. n], &mut data);
+        } else {
+            for i in (0u8 ..= 19).rev() {
+                let mut key = key;
+                for b in &mut key {
+                    *b ^= i;
+                }
+                Rc4::encrypt(&key[.. n], &mut data);
+            }
+        }
+        data
+    }
+    /// Algorithm 3 a) to d): the RC4 key that encrypts `/O`
+    fn owner_key(revision: u32, owner_pass: &[u8]) -> [u8; 16] {
+        let mut hash = md5::Context::new();
+        if owner_pass.len() < 32 {
+            hash.consume(owner_pass);
+            hash.consume(&PADDING[.. 32 - owner_pass.len()]);
+        } else {
+            hash.consume(&owner_pass[.. 32]);
+        }
+        let mut key = *hash.compute();
+        if revision >= 3 {
+            for _ in 0 .. 50 {
+                key = *md5::compute(&key);
+            }
+        }
+        key
+    }
+    /// 7.6.3.3 - Algorithm 2
+    fn compute_key(dict: &CryptDict, id: &[u8], pass: &[u8], key_size: usize) -> [u8; 16] {
         // get important data first
         let level = dict.r;
-        let key_size = dict.bits as usize / 8;
         let o = dict.o.as_bytes();
-        let u = dict.u.as_bytes();
         let p = dict.p;
         
         // a) and b)
@@ -96,7 +253,7 @@ impl Decoder {
         }
         
         // c)
-        hash.consume(o);
+        hash.consume(&o[.. o.len().min(32)]);
         
         // d)
         hash.consume(p.to_le_bytes());
@@ -105,7 +262,7 @@ impl Decoder {
         hash.consume(id);
         
         // f) 
-        if level >= 4 {
+        if level >= 4 && !dict.encrypt_metadata {
             hash.consume([0xff, 0xff, 0xff, 0xff]);
         }
         
@@ -115,19 +272,10 @@ impl Decoder {
         // h) 
         if level >= 3 {
             for _ in 0 .. 50 {
-                data = *md5::compute(&data[.. key_size]);
+                data = *md5::compute(&data[.. key_size.min(16)]);
             }
         }
-        
-        let decoder = Decoder {
-            key: data,
-            key_size
-        };
-        if decoder.check_password(dict, id) {
-            Ok(decoder)
-        } else {
-            Err(PdfError::InvalidPassword)
-        }
+        data
     }
     fn compute_u(&self, id: &[u8]) -> [u8; 16] {
         // algorithm 5
@@ -157,23 +305,576 @@ impl Decoder {
         data
     }
     pub fn check_password(&self, dict: &CryptDict, id: &[u8]) -> bool {
-        self.compute_u(id) == &dict.u.as_bytes()[.. 16]
+        let u = dict.u.as_bytes();
+        if dict.r == 2 {
+            // algorithm 4
+            let mut data = PADDING;
+            Rc4::encrypt(self.key(), &mut data);
+            return u.len() >= 32 && data == u[.. 32];
+        }
+        u.len() >= 16 && self.compute_u(id) == u[.. 16]
+    }
+    /// Decrypts the data of `stream`, unless it is a cross-reference stream, unencrypted metadata
+    /// or names the `Identity` crypt filter.
+    pub fn decrypt_stream(&self, id: u64, gen: u16, stream: &mut PdfStream) -> Result<()> {
+        match self.stream_method(&stream.info) {
+            // leaves data that refers to the backend there
+            CryptMethod::None => Ok(()),
+            method => self.decrypt(method, id, gen, stream.data.to_mut())
+        }
     }
-    pub fn decrypt(&self, id: u64, gen: u16, data: &mut [u8]) {
-        // Algorithm 1
+    /// Decrypts the bytes of a string.
+    pub fn decrypt_string(&self, id: u64, gen: u16, data: &mut Vec<u8>) -> Result<()> {
+        self.decrypt(self.string_method, id, gen, data)
+    }
+    /// Decrypts all strings and streams in `p`, the object `id`.
+    pub fn decrypt_primitive(&self, id: u64, gen: u16, p: &mut Primitive) -> Result<()> {
+        self.crypt_primitive(id, gen, p, false)
+    }
+    /// Encrypts all strings and streams in `p`, the object `id`, so that `decrypt_primitive` gives them back.
+    pub fn encrypt_primitive(&self, id: u64, gen: u16, p: &mut Primitive) -> Result<()> {
+        self.crypt_primitive(id, gen, p, true)
+    }
+    fn crypt_primitive(&self, id: u64, gen: u16, p: &mut Primitive, encrypt: bool) -> Result<()> {
+        let crypt = |method, data: &mut Vec<u8>| match encrypt {
+            false => self.decrypt(method, id, gen, data),
+            true => self.encrypt(method, id, gen, data),
+        };
+        match *p {
+            Primitive::String(ref mut s) => crypt(self.string_method, &mut s.data),
+            Primitive::Array(ref mut parts) => {
+                for p in parts {
+                    self.crypt_primitive(id, gen, p, encrypt)?;
+                }
+                Ok(())
+            }
+            Primitive::Dictionary(ref mut dict) => self.crypt_dict(id, gen, dict, encrypt),
+            Primitive::Stream(ref mut stream) => {
+                self.crypt_dict(id, gen, &mut stream.info, encrypt)?;
+                match self.stream_method(&stream.info) {
+                    CryptMethod::None => Ok(()),
+                    method => crypt(method, stream.data.to_mut())
+                }
+            }
+            _ => Ok(())
+        }
+    }
+    fn crypt_dict(&self, id: u64, gen: u16, dict: &mut Dictionary, encrypt: bool) -> Result<()> {
+        // the /Contents of signatures are not encrypted, so that the signed bytes can be found
+        let is_signature = match dict.get("Type") {
+            Some(&Primitive::Name(ref t)) => t == "Sig" || t == "DocTimeStamp",
+            _ => false
+        };
+        for (key, p) in dict.iter_mut() {
+            if !(is_signature && key == "Contents") {
+                self.crypt_primitive(id, gen, p, encrypt)?;
+            }
+        }
+        Ok(())
+    }
+    /// How a stream with the dictionary `info` is encrypted. Cross-reference streams are not,
+    /// neither is metadata with `/EncryptMetadata false`.
+    fn stream_method(&self, info: &Dictionary) -> CryptMethod {
+        match info.get("Type") {
+            Some(&Primitive::Name(ref t)) if t == "XRef" => CryptMethod::None,
+            Some(&Primitive::Name(ref t)) if t == "Metadata" && !self.encrypt_metadata => CryptMethod::None,
+            _ => self.crypt_filter(info).unwrap_or(self.stream_method)
+        }
+    }
+    /// The method of a `/Crypt` filter in `/Filter`, `None` if there is none.
+    fn crypt_filter(&self, info: &Dictionary) -> Option<CryptMethod> {
+        let is_crypt = |p: &Primitive| match *p {
+            Primitive::Name(ref name) => name == "Crypt",
+            _ => false
+        };
+        let params = match (info.get("Filter"), info.get("DecodeParms")) {
+            (Some(f), params) if is_crypt(f) => params.cloned(),
+            (Some(&Primitive::Array(ref filters)), params) => {
+                let index = filters.iter().position(is_crypt)?;
+                match params {
+                    Some(&Primitive::Array(ref params)) => params.get(index).cloned(),
+                    _ => None
+                }
+            }
+            _ => return None
+        };
+        let name = match params {
+            Some(Primitive::Dictionary(ref params)) => match params.get("Name") {
+                Some(&Primitive::Name(ref name)) => name.clone(),
+                _ => "Identity".into()
+            },
+            _ => "Identity".into()
+        };
+        match name.as_str() {
+            "Identity" => Some(CryptMethod::None),
+            name => self.methods.get(name).cloned()
+        }
+    }
+    fn decrypt(&self, method: CryptMethod, id: u64, gen: u16, data: &mut Vec<u8>) -> Result<()> {
+        match method {
+            CryptMethod::None => Ok(()),
+            CryptMethod::V2 => {
+                let key = self.object_key(id, gen, false);
+                Rc4::encrypt(&key[.. (self.key_size + 5).min(16)], data);
+                Ok(())
+            }
+            CryptMethod::AESV2 => aes::decrypt_cbc(&self.object_key(id, gen, true), data),
+            CryptMethod::AESV3 => aes::decrypt_cbc(self.key(), data),
+        }
+    }
+    fn encrypt(&self, method: CryptMethod, id: u64, gen: u16, data: &mut Vec<u8>) -> Result<()> {
+        match method {
+            CryptMethod::AESV2 => aes::encrypt_cbc(&self.object_key(id, gen, true), data),
+            CryptMethod::AESV3 => aes::encrypt_cbc(self.key(), data),
+            // RC4 is symmetric
+            method => self.decrypt(method, id, gen, data)
+        }
+    }
+    /// Algorithm 1: the key for the object `id`
+    fn object_key(&self, id: u64, gen: u16, aes: bool) -> [u8; 16] {
         // a) we have those already
         
         // b)
-        let mut key = [0; 16+5];
-        let n = self.key_size;
-        key[    .. n  ].copy_from_slice(self.key());
-        key[n   .. n+3].copy_from_slice(&id.to_le_bytes()[.. 3]);
-        key[n+3 .. n+5].copy_from_slice(&gen.to_le_bytes()[.. 2]);
+        let mut hash = md5::Context::new();
+        hash.consume(self.key());
+        hash.consume(&id.to_le_bytes()[.. 3]);
+        hash.consume(&gen.to_le_bytes()[.. 2]);
+        if aes {
+            hash.consume(b"sAlT");
+        }
         
         // c)
-        let key = *md5::compute(&key[.. n+5]);
-        
-        // d)
-        Rc4::encrypt(&key[.. (n+5).min(16)], data);
+        *hash.compute()
+    }
+}
+
+/// 7.6.3.2 Table 22: what users that don't know the owner password may do
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Permissions(pub u32);
+impl Permissions {
+    pub const PRINT: Permissions = Permissions(1 << 2);
+    pub const MODIFY: Permissions = Permissions(1 << 3);
+    pub const COPY: Permissions = Permissions(1 << 4);
+    pub const ANNOTATE: Permissions = Permissions(1 << 5);
+    pub const FILL_FORMS: Permissions = Permissions(1 << 8);
+    pub const EXTRACT_FOR_ACCESSIBILITY: Permissions = Permissions(1 << 9);
+    pub const ASSEMBLE: Permissions = Permissions(1 << 10);
+    pub const PRINT_HIGH_QUALITY: Permissions = Permissions(1 << 11);
+    pub const ALL: Permissions = Permissions(0xf3c);
+
+    pub fn contains(self, other: Permissions) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// The `/P` value, with the reserved bits set
+    fn p(self) -> i32 {
+        ((self.0 & 0xf3c) | 0xffff_f0c0) as i32
+    }
+}
+impl std::ops::BitOr for Permissions {
+    type Output = Permissions;
+    fn bitor(self, other: Permissions) -> Permissions {
+        Permissions(self.0 | other.0)
+    }
+}
+impl Default for Permissions {
+    fn default() -> Permissions {
+        Permissions::ALL
+    }
+}
+
+/// The cipher an encrypted document is written with.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EncryptionAlgorithm {
+    /// RC4 with a 40 bit key (revision 2); only for very old readers
+    Rc4_40,
+    /// RC4 with a 128 bit key (revision 3)
+    Rc4_128,
+    /// AES-128 crypt filters (revision 4)
+    Aes128,
+    /// AES-256 crypt filters (revision 6)
+    Aes256,
+}
+
+/// How `File::write_with_options` encrypts the document.
+#[derive(Debug, Clone)]
+pub struct EncryptionOptions {
+    /// Needed to open the document; may be empty
+    pub user_password: Vec<u8>,
+    /// Gives full access; the user password is used if empty
+    pub owner_password: Vec<u8>,
+    pub permissions: Permissions,
+    pub algorithm: EncryptionAlgorithm,
+}
+impl EncryptionOptions {
+    /// The encryption dictionary for the document with the first file identifier `id`,
+    /// and the decoder that encrypts the objects.
+    pub fn encoder(&self, id: &[u8]) -> Result<(Dictionary, Decoder)> {
+        let owner_pass = match self.owner_password.len() {
+            0 => &self.user_password,
+            _ => &self.owner_password
+        };
+        let (v, r, bits, method) = match self.algorithm {
+            EncryptionAlgorithm::Rc4_40 => (1, 2, 40, CryptMethod::V2),
+            EncryptionAlgorithm::Rc4_128 => (2, 3, 128, CryptMethod::V2),
+            EncryptionAlgorithm::Aes128 => (4, 4, 128, CryptMethod::AESV2),
+            EncryptionAlgorithm::Aes256 => (5, 6, 256, CryptMethod::AESV3),
+        };
+        let mut crypt_filters = BTreeMap::new();
+        if v >= 4 {
+            crypt_filters.insert("StdCF".into(), CryptFilter { method, length: Some(bits / 8) });
+        }
+        let filter = if v >= 4 { "StdCF" } else { "Identity" };
+        let mut dict = CryptDict {
+            o: PdfString::new(Vec::new()),
+            u: PdfString::new(Vec::new()),
+            r,
+            p: self.permissions.p(),
+            v,
+            bits,
+            crypt_filters,
+            stream_filter: filter.into(),
+            string_filter: filter.into(),
+            oe: None,
+            ue: None,
+            encrypt_metadata: true,
+        };
+        let key_size = dict.key_size()?;
+        let mut decoder = Decoder {
+            key: [0; 32],
+            key_size,
+            stream_method: dict.method(filter)?,
+            string_method: dict.method(filter)?,
+            methods: dict.crypt_filters.iter().map(|(name, f)| (name.clone(), f.method)).collect(),
+            encrypt_metadata: true,
+        };
+        let mut perms = None;
+        if r == 6 {
+            let keys = aes::r6_keys(&self.user_password, owner_pass, dict.p)?;
+            decoder.key = keys.key;
+            dict.u = PdfString::new(keys.u);
+            dict.ue = Some(PdfString::new(keys.ue));
+            dict.o = PdfString::new(keys.o);
+            dict.oe = Some(PdfString::new(keys.oe));
+            perms = Some(keys.perms);
+        } else {
+            // Algorithm 3: the padded user password, encrypted with the key of the owner password
+            let mut o = [0; 32];
+            let user_pass = &self.user_password[.. self.user_password.len().min(32)];
+            o[.. user_pass.len()].copy_from_slice(user_pass);
+            o[user_pass.len() ..].copy_from_slice(&PADDING[.. 32 - user_pass.len()]);
+            let key = Decoder::owner_key(r, owner_pass);
+            let n = key_size.min(16);
+            for i in 0 .. if r == 2 { 1 } else { 20 } {
+                let mut key = key;
+                for b in &mut key {
+                    *b ^= i;
+                }
+                Rc4::encrypt(&key[.. n], &mut o);
+            }
+            dict.o = PdfString::new(o.to_vec());
+
+            // Algorithms 4 and 5
+            decoder.key[.. 16].copy_from_slice(&Decoder::compute_key(&dict, id, &self.user_password, key_size));
+            let u = if r == 2 {
+                let mut u = PADDING;
+                Rc4::encrypt(decoder.key(), &mut u);
+                u.to_vec()
+            } else {
+                let mut u = decoder.compute_u(id).to_vec();
+                u.extend_from_slice(&PADDING[.. 16]);
+                u
+            };
+            dict.u = PdfString::new(u);
+        }
+
+        let mut out = Dictionary::new();
+        out.insert("Filter".into(), "Standard".into());
+        out.insert("V".into(), Primitive::Integer(v));
+        out.insert("R".into(), Primitive::Integer(r as i32));
+        out.insert("Length".into(), Primitive::Integer(bits as i32));
+        out.insert("P".into(), Primitive::Integer(dict.p));
+        out.insert("O".into(), Primitive::String(dict.o));
+        out.insert("U".into(), Primitive::String(dict.u));
+        if let (Some(oe), Some(ue)) = (dict.oe, dict.ue) {
+            out.insert("OE".into(), Primitive::String(oe));
+            out.insert("UE".into(), Primitive::String(ue));
+        }
+        if let Some(perms) = perms {
+            out.insert("Perms".into(), Primitive::String(PdfString::new(perms)));
+        }
+        if v >= 4 {
+            let mut std_cf = Dictionary::new();
+            std_cf.insert("Type".into(), "CryptFilter".into());
+            std_cf.insert("CFM".into(), Primitive::Name(format!("{:?}", method)));
+            std_cf.insert("AuthEvent".into(), "DocOpen".into());
+            std_cf.insert("Length".into(), Primitive::Integer(bits as i32 / 8));
+            let mut cf = Dictionary::new();
+            cf.insert("StdCF".into(), std_cf.into());
+            out.insert("CF".into(), cf.into());
+            out.insert("StmF".into(), "StdCF".into());
+            out.insert("StrF".into(), "StdCF".into());
+        }
+        Ok((out, decoder))
+    }
+}
+
+/// AES-CBC and the key derivation of revisions 5 and 6 (7.6.4.3.3)
+#[cfg(feature = "crypt-aes")]
+mod aes {
+    use ::aes::{Aes128, Aes256};
+    use block_modes::{BlockMode, Cbc};
+    use block_modes::block_padding::{Pkcs7, NoPadding};
+    use sha2::{Digest, Sha256, Sha384, Sha512};
+    use crate::primitive::PdfString;
+    use crate::error::{PdfError, Result};
+    use super::CryptDict;
+
+    /// Decrypts `data`, an initialization vector followed by the padded ciphertext.
+    pub fn decrypt_cbc(key: &[u8], data: &mut Vec<u8>) -> Result<()> {
+        if data.len() < 16 {
+            // empty strings are sometimes written without initialization vector
+            data.clear();
+            return Ok(());
+        }
+        let (iv, rest) = data.split_at_mut(16);
+        let len = match key.len() {
+            16 => Cbc::<Aes128, Pkcs7>::new_from_slices(key, iv)
+                .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+                .decrypt(rest).map_err(|_| PdfError::from("AES: invalid padding".to_string()))?
+                .len(),
+            32 => Cbc::<Aes256, Pkcs7>::new_from_slices(key, iv)
+                .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+                .decrypt(rest).map_err(|_| PdfError::from("AES: invalid padding".to_string()))?
+                .len(),
+            n => bail!("invalid AES key length {}", n)
+        };
+        data.drain(.. 16);
+        data.truncate(len);
+        Ok(())
+    }
+
+    /// Encrypts `data` with padding and prepends the random initialization vector.
+    pub fn encrypt_cbc(key: &[u8], data: &mut Vec<u8>) -> Result<()> {
+        let mut iv = [0; 16];
+        random(&mut iv)?;
+        let encrypted = match key.len() {
+            16 => Cbc::<Aes128, Pkcs7>::new_from_slices(key, &iv)
+                .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+                .encrypt_vec(data),
+            32 => Cbc::<Aes256, Pkcs7>::new_from_slices(key, &iv)
+                .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+                .encrypt_vec(data),
+            n => bail!("invalid AES key length {}", n)
+        };
+        data.clear();
+        data.extend_from_slice(&iv);
+        data.extend_from_slice(&encrypted);
+        Ok(())
+    }
+
+    fn random(buf: &mut [u8]) -> Result<()> {
+        getrandom::getrandom(buf).map_err(|e| PdfError::from(format!("no random numbers: {}", e)))
+    }
+
+    /// The entries of a revision 6 encryption dictionary
+    pub struct R6Keys {
+        /// The file key
+        pub key: [u8; 32],
+        pub u: Vec<u8>,
+        pub ue: Vec<u8>,
+        pub o: Vec<u8>,
+        pub oe: Vec<u8>,
+        pub perms: Vec<u8>,
+    }
+
+    /// Algorithms 8, 9 and 10: a random file key, encrypted for both passwords
+    pub fn r6_keys(user_pass: &[u8], owner_pass: &[u8], p: i32) -> Result<R6Keys> {
+        let user_pass = &user_pass[.. user_pass.len().min(127)];
+        let owner_pass = &owner_pass[.. owner_pass.len().min(127)];
+        let mut key = [0; 32];
+        random(&mut key)?;
+        // validation and key salts of both passwords
+        let mut salts = [0; 32];
+        random(&mut salts)?;
+        let encrypt_key = |intermediate: [u8; 32]| -> Result<Vec<u8>> {
+            Ok(Cbc::<Aes256, NoPadding>::new_from_slices(&intermediate, &[0; 16])
+                .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+                .encrypt_vec(&key))
+        };
+
+        let mut u = hash(6, user_pass, &salts[.. 8], &[]).to_vec();
+        u.extend_from_slice(&salts[.. 16]);
+        let ue = encrypt_key(hash(6, user_pass, &salts[8 .. 16], &[]))?;
+
+        let mut o = hash(6, owner_pass, &salts[16 .. 24], &u).to_vec();
+        o.extend_from_slice(&salts[16 .. 32]);
+        let oe = encrypt_key(hash(6, owner_pass, &salts[24 .. 32], &u))?;
+
+        let mut perms = [0; 16];
+        perms[.. 4].copy_from_slice(&p.to_le_bytes());
+        perms[4 .. 8].copy_from_slice(&[0xff; 4]);
+        perms[8 .. 12].copy_from_slice(b"Tadb");
+        random(&mut perms[12 ..])?;
+        // a single block, so CBC without initialization vector is ECB
+        let perms = Cbc::<Aes256, NoPadding>::new_from_slices(&key, &[0; 16])
+            .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+            .encrypt_vec(&perms);
+
+        Ok(R6Keys { key, u, ue, o, oe, perms })
+    }
+
+    /// Algorithm 2.A: the file key, from the owner or the user password
+    pub fn file_key(dict: &CryptDict, pass: &[u8]) -> Result<[u8; 32]> {
+        let pass = &pass[.. pass.len().min(127)];
+        let (o, u) = (dict.o.as_bytes(), dict.u.as_bytes());
+        if o.len() < 48 || u.len() < 48 {
+            bail!("/O and /U need 48 bytes");
+        }
+        let encrypted = |key: &Option<PdfString>| match *key {
+            Some(ref s) if s.as_bytes().len() == 32 => Ok(s.as_bytes().to_vec()),
+            _ => Err(PdfError::from("/OE and /UE need 32 bytes".to_string()))
+        };
+        let (intermediate, mut key) = if hash(dict.r, pass, &o[32 .. 40], &u[.. 48]) == o[.. 32] {
+            (hash(dict.r, pass, &o[40 .. 48], &u[.. 48]), encrypted(&dict.oe)?)
+        } else if hash(dict.r, pass, &u[32 .. 40], &[]) == u[.. 32] {
+            (hash(dict.r, pass, &u[40 .. 48], &[]), encrypted(&dict.ue)?)
+        } else {
+            return Err(PdfError::InvalidPassword);
+        };
+        Cbc::<Aes256, NoPadding>::new_from_slices(&intermediate, &[0; 16])
+            .map_err(|e| PdfError::from(format!("AES: {}", e)))?
+            .decrypt(&mut key).map_err(|_| PdfError::from("AES: can't decrypt file key".to_string()))?;
+        let mut out = [0; 32];
+        out.copy_from_slice(&key);
+        Ok(out)
+    }
+
+    /// Revision 5: SHA-256, revision 6: algorithm 2.B
+    fn hash(revision: u32, pass: &[u8], salt: &[u8], udata: &[u8]) -> [u8; 32] {
+        let mut k: Vec<u8> = Sha256::new().chain(pass).chain(salt).chain(udata).finalize().to_vec();
+        if revision == 6 {
+            let mut round = 0u32;
+            loop {
+                let mut k1 = Vec::with_capacity(64 * (pass.len() + k.len() + udata.len()));
+                for _ in 0 .. 64 {
+                    k1.extend_from_slice(pass);
+                    k1.extend_from_slice(&k);
+                    k1.extend_from_slice(udata);
+                }
+                let e = Cbc::<Aes128, NoPadding>::new_from_slices(&k[.. 16], &k[16 .. 32])
+                    .expect("16 byte key and iv")
+                    .encrypt_vec(&k1);
+                // the first 16 bytes as a number mod 3, 256 being 1 mod 3
+                k = match e[.. 16].iter().map(|&b| b as u32).sum::<u32>() % 3 {
+                    0 => Sha256::digest(&e).to_vec(),
+                    1 => Sha384::digest(&e).to_vec(),
+                    _ => Sha512::digest(&e).to_vec(),
+                };
+                round += 1;
+                if round >= 64 && *e.last().unwrap() as u32 <= round - 32 {
+                    break;
+                }
+            }
+        }
+        let mut out = [0; 32];
+        out.copy_from_slice(&k[.. 32]);
+        out
+    }
+}
+
+#[cfg(not(feature = "crypt-aes"))]
+mod aes {
+    use crate::error::Result;
+    use super::CryptDict;
+
+    pub fn decrypt_cbc(_key: &[u8], _data: &mut Vec<u8>) -> Result<()> {
+        bail!("AES encryption needs the `crypt-aes` feature")
+    }
+    pub fn file_key(_dict: &CryptDict, _pass: &[u8]) -> Result<[u8; 32]> {
+        bail!("AES encryption needs the `crypt-aes` feature")
+    }
+    pub fn encrypt_cbc(_key: &[u8], _data: &mut Vec<u8>) -> Result<()> {
+        bail!("AES encryption needs the `crypt-aes` feature")
+    }
+    pub struct R6Keys {
+        pub key: [u8; 32],
+        pub u: Vec<u8>,
+        pub ue: Vec<u8>,
+        pub o: Vec<u8>,
+        pub oe: Vec<u8>,
+        pub perms: Vec<u8>,
+    }
+    pub fn r6_keys(_user_pass: &[u8], _owner_pass: &[u8], _p: i32) -> Result<R6Keys> {
+        bail!("AES encryption needs the `crypt-aes` feature")
+    }
+}
+
+#[cfg(all(test, feature = "crypt-aes"))]
+mod tests {
+    use super::*;
+    use super::aes;
+    use ::aes::Aes256;
+    use block_modes::{BlockMode, Cbc};
+    use block_modes::block_padding::NoPadding;
+    use crate::object::{Object, NoResolve};
+    use crate::parser::parse;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0 .. s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i .. i + 2], 16).unwrap()).collect()
+    }
+    fn to_hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+    fn r6_dict(u: &str, ue: &str, o: &str, oe: &str) -> CryptDict {
+        let dict = format!("<< /Filter /Standard /V 5 /R 6 /Length 256 /P -4 /U <{}> /UE <{}> /O <{}> /OE <{}> \
+            /CF << /StdCF << /CFM /AESV3 /Length 32 >> >> /StmF /StdCF /StrF /StdCF >>", u, ue, o, oe);
+        CryptDict::from_primitive(parse(dict.as_bytes(), &NoResolve).unwrap(), &NoResolve).unwrap()
+    }
+
+    #[test]
+    fn aes_cbc() {
+        // the first block is F.2.1 of NIST SP 800-38A, the second one is padding
+        let mut data: Vec<u8> = (0 .. 16).collect();
+        data.extend(hex("7649abac8119b246cee98e9b12e9197d8964e0b149c10b7b682e6e39aaeb731c"));
+        aes::decrypt_cbc(&hex("2b7e151628aed2a6abf7158809cf4f3c"), &mut data).unwrap();
+        assert_eq!(data, hex("6bc1bee22e409f96e93d7e117393172a"));
+
+        let mut data = b"round trip".to_vec();
+        aes::encrypt_cbc(&[7; 32], &mut data).unwrap();
+        assert_eq!(data.len(), 32);
+        aes::decrypt_cbc(&[7; 32], &mut data).unwrap();
+        assert_eq!(data, b"round trip");
+    }
+
+    #[test]
+    fn r6_file_key() {
+        // the file key 0, 1, … 31 for the passwords "user" and "owner", with the salts 1 to 4
+        let dict = r6_dict(
+            "273444d38b9bfa2ef7f9ec30b363c84a94e674abb67e0e8c8ca3479a9655109001010101010101010202020202020202",
+            "1cd32a3e614179af9c4722c750024a4e47bda2cf30ecfb4cc97e7f77feffd90f",
+            "9f982be98253f0645861cac62fb244eac286ca130f3246b202b4aadcf58d8d0b03030303030303030404040404040404",
+            "c9e4b1e83a6f417a754ec46a8ad5da4a85b53b543812794e60500debf031ca15",
+        );
+        let key: Vec<u8> = (0 .. 32).collect();
+        for &pass in &[&b"user"[..], b"owner"] {
+            assert_eq!(Decoder::from_password(&dict, b"", pass).unwrap().key(), &key[..]);
+        }
+        match Decoder::from_password(&dict, b"", b"guess") {
+            Err(PdfError::InvalidPassword) => {}
+            _ => panic!("wrong password accepted")
+        }
+    }
+
+    #[test]
+    fn r6_keys() {
+        let keys = aes::r6_keys(b"user", b"owner", -4).unwrap();
+        let dict = r6_dict(&to_hex(&keys.u), &to_hex(&keys.ue), &to_hex(&keys.o), &to_hex(&keys.oe));
+        for &pass in &[&b"user"[..], b"owner"] {
+            assert_eq!(Decoder::from_password(&dict, b"", pass).unwrap().key(), &keys.key[..]);
+        }
+
+        let mut perms = keys.perms.clone();
+        Cbc::<Aes256, NoPadding>::new_from_slices(&keys.key, &[0; 16]).unwrap().decrypt(&mut perms).unwrap();
+        assert_eq!(&perms[.. 12], &[0xfc, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, b'T', b'a', b'd', b'b']);
     }
 }
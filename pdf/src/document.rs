@@ -0,0 +1,99 @@
+//! A document behind an interface without generics and without shared pointers in its results,
+//! for language bindings.
+//!
+//! `File` is generic over its backend, so it can't be put behind a trait object. `Document`
+//! owns its file and only returns owned values.
+
+#[cfg(feature = "write")]
+use std::path::Path;
+
+use crate::error::*;
+use crate::file::File;
+use crate::image::ImageData;
+use crate::metadata::Metadata;
+use crate::object::*;
+
+/// What bindings need from a document. Object safe, see `Document` for the implementation.
+pub trait PdfDocument: Send {
+    fn num_pages(&self) -> Result<u32>;
+    /// Width and height of the crop box of page `page_nr` (counting from 0), in points.
+    fn page_size(&self, page_nr: u32) -> Result<(f32, f32)>;
+    /// See `File::page_text`.
+    fn page_text(&self, page_nr: u32) -> Result<String>;
+    /// The decoded images of the resources of page `page_nr`, by resource name.
+    fn page_images(&self, page_nr: u32) -> Result<Vec<(String, ImageData)>>;
+    fn metadata(&self) -> Result<Metadata>;
+    /// What was repaired while parsing.
+    fn warnings(&self) -> Vec<String>;
+    /// The document with all changes, see `File::write_to`.
+    #[cfg(feature = "write")]
+    fn to_bytes(&self) -> Result<Vec<u8>>;
+    #[cfg(feature = "write")]
+    fn save(&self, path: &Path) -> Result<()>;
+}
+
+/// A document read from memory.
+pub struct Document {
+    file: File<Vec<u8>>,
+}
+
+impl Document {
+    pub fn open(path: &str, password: &[u8]) -> Result<Document> {
+        Ok(Document { file: File::open_with_password(path, password)? })
+    }
+    pub fn from_data(data: Vec<u8>, password: &[u8]) -> Result<Document> {
+        Ok(Document { file: File::from_backend_with_password(data, password)? })
+    }
+}
+
+impl PdfDocument for Document {
+    fn num_pages(&self) -> Result<u32> {
+        self.file.get_num_pages()
+    }
+    fn page_size(&self, page_nr: u32) -> Result<(f32, f32)> {
+        let r = self.file.get_page(page_nr)?.crop_box(&self.file)?;
+        Ok(((r.right - r.left).abs(), (r.top - r.bottom).abs()))
+    }
+    fn page_text(&self, page_nr: u32) -> Result<String> {
+        self.file.page_text(page_nr)
+    }
+    fn page_images(&self, page_nr: u32) -> Result<Vec<(String, ImageData)>> {
+        let resources = self.file.get_page(page_nr)?.resources(&self.file)?;
+        let mut images = Vec::new();
+        for (name, xobject) in &resources.xobjects {
+            if let XObject::Image(ref image) = *xobject {
+                images.push((name.clone(), image.image_data(&self.file)?));
+            }
+        }
+        Ok(images)
+    }
+    fn metadata(&self) -> Result<Metadata> {
+        self.file.metadata()
+    }
+    fn warnings(&self) -> Vec<String> {
+        self.file.warnings()
+    }
+    #[cfg(feature = "write")]
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.file.write_to(&mut out)?;
+        Ok(out)
+    }
+    #[cfg(feature = "write")]
+    fn save(&self, path: &Path) -> Result<()> {
+        self.file.save_to(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../files/xelatex.pdf")).unwrap();
+        let doc: Box<dyn PdfDocument> = Box::new(Document::from_data(data, b"").unwrap());
+        let pages = std::thread::spawn(move || doc.num_pages().unwrap()).join().unwrap();
+        assert!(pages > 0);
+    }
+}
@@ -2,9 +2,14 @@ use itertools::Itertools;
 use tuple::*;
 use inflate::inflate_bytes_zlib;
 use std::mem;
+use std::ops::Range;
 
 use crate::error::*;
-use crate::object::{Object, Resolve};
+#[cfg(feature = "images")]
+use crate::jbig2::decode_jbig2;
+use crate::object::{Object, Resolve, Stream};
+#[cfg(test)]
+use crate::object::NoResolve;
 use crate::primitive::{Primitive, Dictionary};
 
 
@@ -33,6 +38,12 @@ pub struct DCTDecodeParams {
     color_transform: Option<i32>,
 }
 
+#[derive(Debug, Clone)]
+pub struct JBIG2DecodeParams {
+    /// Decoded data of the `/JBIG2Globals` stream
+    pub globals: Option<Vec<u8>>,
+}
+
 #[derive(Debug, Clone)]
 pub enum StreamFilter {
     ASCIIHexDecode,
@@ -41,47 +52,119 @@ pub enum StreamFilter {
     FlateDecode (LZWFlateParams),
     JPXDecode, //Jpeg2k
     DCTDecode (DCTDecodeParams),
-    CCITTFaxDecode
+    CCITTFaxDecode,
+    RunLengthDecode,
+    JBIG2Decode (JBIG2DecodeParams),
+    /// Decryption already happened when the stream was read.
+    Crypt,
 }
 impl StreamFilter {
-    pub fn from_kind_and_params(kind: &str, params: Dictionary, r: &impl Resolve) -> Result<StreamFilter> {
+    /// `kind` may also be one of the abbreviations used in inline images, like `Fl`.
+    pub fn from_kind_and_params(kind: &str, mut params: Dictionary, r: &impl Resolve) -> Result<StreamFilter> {
+       if kind == "JBIG2Decode" {
+           let globals = match params.remove("JBIG2Globals") {
+               Some(p) => Some(Stream::<()>::from_primitive(p, r)?.data()?.to_vec()),
+               None => None
+           };
+           return Ok(StreamFilter::JBIG2Decode (JBIG2DecodeParams { globals }));
+       }
        let params = Primitive::Dictionary (params);
        Ok(
        match kind {
-           "ASCIIHexDecode" => StreamFilter::ASCIIHexDecode,
-           "ASCII85Decode" => StreamFilter::ASCII85Decode,
-           "LZWDecode" => StreamFilter::LZWDecode (LZWFlateParams::from_primitive(params, r)?),
-           "FlateDecode" => StreamFilter::FlateDecode (LZWFlateParams::from_primitive(params, r)?),
+           "ASCIIHexDecode" | "AHx" => StreamFilter::ASCIIHexDecode,
+           "ASCII85Decode" | "A85" => StreamFilter::ASCII85Decode,
+           "LZWDecode" | "LZW" => StreamFilter::LZWDecode (LZWFlateParams::from_primitive(params, r)?),
+           "FlateDecode" | "Fl" => StreamFilter::FlateDecode (LZWFlateParams::from_primitive(params, r)?),
            "JPXDecode" => StreamFilter::JPXDecode,
-           "DCTDecode" => StreamFilter::DCTDecode (DCTDecodeParams::from_primitive(params, r)?),
-           "CCITTFaxDecode" => StreamFilter::CCITTFaxDecode,
+           "DCTDecode" | "DCT" => StreamFilter::DCTDecode (DCTDecodeParams::from_primitive(params, r)?),
+           "CCITTFaxDecode" | "CCF" => StreamFilter::CCITTFaxDecode,
+           "RunLengthDecode" | "RL" => StreamFilter::RunLengthDecode,
+           "Crypt" => StreamFilter::Crypt,
            ty => bail!("Unrecognized filter type {:?}", ty),
        } 
        )
     }
 }
 
+/// The filters of `/Filter` (or `/FFilter`) with their `/DecodeParms` (or `/FDecodeParms`).
+///
+/// With several filters, the parameters are an array of the same length, with `null` for filters
+/// that use the defaults. A single dictionary, as some writers put it even for several filters,
+/// belongs to the first filter.
+pub fn filters_with_params(filter: Option<Primitive>, params: Option<Primitive>, r: &impl Resolve) -> Result<Vec<StreamFilter>> {
+    let kinds = match filter.map(|p| p.resolve(r)).transpose()? {
+        None | Some(Primitive::Null) => return Ok(Vec::new()),
+        Some(Primitive::Name(kind)) => vec![kind],
+        Some(Primitive::Array(kinds)) => kinds.into_iter()
+            .map(|p| p.resolve(r)?.to_name())
+            .collect::<Result<Vec<_>>>()?,
+        Some(p) => return unexpected_primitive!(Name, p.get_debug_name())
+    };
+    let mut params = match params.map(|p| p.resolve(r)).transpose()? {
+        None | Some(Primitive::Null) => Vec::new(),
+        Some(Primitive::Dictionary(dict)) => vec![Some(dict)],
+        Some(Primitive::Array(parts)) => parts.into_iter().map(|p| match p.resolve(r)? {
+            Primitive::Null => Ok(None),
+            p => Ok(Some(p.to_dictionary(r)?))
+        }).collect::<Result<Vec<_>>>()?,
+        Some(p) => return unexpected_primitive!(Dictionary, p.get_debug_name())
+    };
+    if params.len() > kinds.len() {
+        bail!("{} filters, but {} decode parameters", kinds.len(), params.len());
+    }
+    params.resize(kinds.len(), None);
+    kinds.iter().zip(params)
+        .map(|(kind, params)| StreamFilter::from_kind_and_params(kind, params.unwrap_or_default(), r))
+        .collect()
+}
+
 fn decode_nibble(c: u8) -> Option<u8> {
     match c {
         n @ b'0' ..= b'9' => Some(n - b'0'),
-        a @ b'a' ..= b'h' => Some(a - b'a' + 0xa),
-        a @ b'A' ..= b'H' => Some(a - b'A' + 0xA),
+        a @ b'a' ..= b'f' => Some(a - b'a' + 0xa),
+        a @ b'A' ..= b'F' => Some(a - b'A' + 0xA),
         _ => None
     }
 }
 
+/// Decodes hex digits up to the end or `>`. White space is ignored, a missing last digit is 0.
 pub fn decode_hex(data: &[u8]) -> Result<Vec<u8>> {
     let mut out = Vec::with_capacity(data.len() / 2);
-    for (i, (&high, &low)) in data.iter().tuples().enumerate() {
+    let digits = data.iter().cloned()
+        .enumerate()
+        .filter(|&(_, b)| !is_whitespace(b))
+        .take_while(|&(_, b)| b != b'>');
+    for ((i, high), low) in digits.batching(|it| it.next().map(|h| (h, it.next()))) {
+        let low = low.map(|(_, b)| b).unwrap_or(b'0');
         if let (Some(low), Some(high)) = (decode_nibble(low), decode_nibble(high)) {
             out.push(high << 4 | low);
         } else {
-            return Err(PdfError::HexDecode {pos: i * 2, bytes: [high, low]})
+            return Err(PdfError::HexDecode {pos: i, bytes: [high, low]})
         }
     }
     Ok(out)
 }
 
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Encodes `data` for `ASCIIHexDecode`, including the `>` at the end.
+pub fn encode_hex(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 2 + 1);
+    for &b in data {
+        out.push(HEX_DIGITS[(b >> 4) as usize]);
+        out.push(HEX_DIGITS[(b & 0xf) as usize]);
+    }
+    out.push(b'>');
+    out
+}
+
+fn is_whitespace(b: u8) -> bool {
+    match b {
+        b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' | b'\0' => true,
+        _ => false
+    }
+}
+
 #[inline]
 fn sym_85(byte: u8) -> Option<u8> {
     match byte {
@@ -92,12 +175,12 @@ fn sym_85(byte: u8) -> Option<u8> {
 fn word_85(input: &[u8]) -> Option<(u8, [u8; 4])> {
     match input.get(0).cloned() {
         Some(b'z') => Some((1, [0; 4])),
-        Some(a) => T4::from_iter(input[1 .. 5].iter().cloned()).and_then(|t| {
+        Some(a) => T4::from_iter(input.get(1 .. 5)?.iter().cloned()).and_then(|t| {
             T1(a).join(t)
             .map(sym_85).collect()
-            .map(|v| v.map(|x| x as u32))
+            .map(|v| v.map(|x| x as u64))
             .map(|T5(a, b, c, d, e)| {
-                let q: u32 = ((((a * 85) + b * 85) + c * 85) + d * 85) + e;
+                let q = ((((a * 85) + b) * 85 + c) * 85 + d) * 85 + e;
                 (5, [(q >> 24) as u8, (q >> 16) as u8, (q >> 8) as u8, q as u8])
             })
         }),
@@ -112,6 +195,8 @@ fn substr(data: &[u8], needle: &[u8]) -> Option<usize> {
 fn decode_85(data: &[u8]) -> Result<Vec<u8>> {
     use std::iter::repeat;
     
+    let data: Vec<u8> = data.iter().cloned().filter(|&b| !is_whitespace(b)).collect();
+    let data = if data.starts_with(b"<~") { &data[2..] } else { &data[..] };
     let mut out = Vec::with_capacity(data.len());
     
     let mut pos = 0;
@@ -120,7 +205,12 @@ fn decode_85(data: &[u8]) -> Result<Vec<u8>> {
         pos += advance as usize;
     }
     let tail_len = substr(&data[pos..], b"~>").ok_or(PdfError::Ascii85TailError)?;
-    assert!(tail_len < 5);
+    if tail_len == 0 {
+        return Ok(out);
+    }
+    if tail_len == 1 || tail_len >= 5 {
+        return Err(PdfError::Ascii85TailError);
+    }
     let tail: [u8; 5] = T5::from_iter(
         data[pos..pos+tail_len].iter()
         .cloned()
@@ -134,55 +224,222 @@ fn decode_85(data: &[u8]) -> Result<Vec<u8>> {
     Ok(out)
 }
 
+/// Encodes `data` for `ASCII85Decode`, including the `~>` at the end.
+pub fn encode_85(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 5 / 4 + 7);
+    for chunk in data.chunks(4) {
+        let mut word = [0; 4];
+        word[.. chunk.len()].copy_from_slice(chunk);
+        let mut q = u32::from_be_bytes(word);
+        if q == 0 && chunk.len() == 4 {
+            out.push(b'z');
+            continue;
+        }
+        let mut digits = [0; 5];
+        for d in digits.iter_mut().rev() {
+            *d = (q % 85) as u8 + 0x21;
+            q /= 85;
+        }
+        // a partial group of n bytes is written as n + 1 digits
+        out.extend_from_slice(&digits[.. chunk.len() + 1]);
+    }
+    out.extend_from_slice(b"~>");
+    out
+}
+
+fn decode_run_length(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut pos = 0;
+    while let Some(&n) = data.get(pos) {
+        pos += 1;
+        match n {
+            // EOD
+            128 => break,
+            // copy the next n + 1 bytes
+            0 ..= 127 => {
+                let end = pos + n as usize + 1;
+                match data.get(pos .. end) {
+                    Some(literal) => out.extend_from_slice(literal),
+                    None => bail!("RunLengthDecode: data ends within a literal run")
+                }
+                pos = end;
+            }
+            // repeat the next byte 257 - n times
+            _ => {
+                match data.get(pos) {
+                    Some(&b) => out.extend((0 .. 257 - n as usize).map(|_| b)),
+                    None => bail!("RunLengthDecode: data ends within a repeated run")
+                }
+                pos += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Encodes `data` for `RunLengthDecode`, including the EOD marker.
+pub fn encode_run_length(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 128 + 2);
+    let mut pos = 0;
+    while pos < data.len() {
+        let b = data[pos];
+        let run = data[pos ..].iter().take(128).take_while(|&&c| c == b).count();
+        if run >= 2 {
+            out.push((257 - run) as u8);
+            out.push(b);
+            pos += run;
+        } else {
+            // literal bytes up to the next repetition
+            let start = pos;
+            while pos < data.len() && pos - start < 128 && data.get(pos + 1) != Some(&data[pos]) {
+                pos += 1;
+            }
+            out.push((pos - start - 1) as u8);
+            out.extend_from_slice(&data[start .. pos]);
+        }
+    }
+    out.push(128);
+    out
+}
+
+
+/// Decodes a JPEG image into 8 bit samples: gray, RGB or CMYK, depending on the number of components.
+/// The color transform is chosen by the decoder from the Adobe marker, which agrees with the
+/// default of `/ColorTransform`; an explicit value is not taken into account.
+#[cfg(feature = "images")]
+fn decode_dct(data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    decoder.decode().map_err(|e| format!("DCTDecode: {}", e).into())
+}
 
 fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
-    let predictor = params.predictor as usize;;
-    let n_components = params.n_components as usize;
-    let columns = params.columns as usize;
-
-    // First flate decode
-    let decoded = inflate_bytes_zlib(data)?;
-
-    // Then unfilter (PNG)
-    // For this, take the old out as input, and write output to out
-
-    if predictor > 10 {
-        let inp = decoded; // input buffer
-        let rows = inp.len() / (columns+1);
-        
-        // output buffer
-        let mut out = vec![0; rows * columns];
-    
-        // Apply inverse predictor
-        let null_vec = vec![0; columns];
-        
-        let mut in_off = 0; // offset into input buffer
-        
-        let mut out_off = 0; // offset into output buffer
-        let mut last_out_off = 0; // last offset to output buffer
-        
-        while in_off < inp.len() {
-            
-            let predictor = PredictorType::from_u8(inp[in_off])?;
-            in_off += 1; // +1 because the first byte on each row is predictor
-            
-            let row_in = &inp[in_off .. in_off + columns];
-            let (prev_row, row_out) = if out_off == 0 {
-                (&null_vec[..], &mut out[out_off .. out_off+columns])
-            } else {
-                let (prev, curr) = out.split_at_mut(out_off);
-                (&prev[last_out_off ..], &mut curr[.. columns])
+    unpredict(inflate_bytes_zlib(data)?, params)
+}
+
+/// Reads codes of varying width, most significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> BitReader<'a> {
+    fn read(&mut self, bits: usize) -> Option<u16> {
+        if self.pos + bits > self.data.len() * 8 {
+            return None;
+        }
+        let mut code = 0;
+        for _ in 0 .. bits {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            code = code << 1 | bit as u16;
+            self.pos += 1;
+        }
+        Some(code)
+    }
+}
+
+fn lzw_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    const CLEAR: u16 = 256;
+    const EOD: u16 = 257;
+    let early_change = (params.early_change != 0) as usize;
+
+    let mut out = Vec::with_capacity(data.len() * 2);
+    // The string of each code from 258 on is the previous string plus one byte,
+    // so it can be kept as a range of the output.
+    let mut table: Vec<Range<usize>> = Vec::new();
+    let mut prev: Option<Range<usize>> = None;
+    let mut reader = BitReader { data, pos: 0 };
+    let mut width = 9;
+    while let Some(code) = reader.read(width) {
+        match code {
+            CLEAR => {
+                table.clear();
+                prev = None;
+                width = 9;
+                continue;
+            }
+            EOD => break,
+            _ => {}
+        }
+        let start = out.len();
+        if code < CLEAR {
+            out.push(code as u8);
+        } else if let Some(range) = table.get(code as usize - 258).cloned() {
+            for i in range {
+                let b = out[i];
+                out.push(b);
+            }
+        } else if code as usize == 258 + table.len() {
+            // the code that is about to be defined: previous string plus its first byte
+            let range = match prev {
+                Some(ref range) => range.clone(),
+                None => bail!("invalid LZW code {}", code)
             };
-            unfilter(predictor, n_components, prev_row, row_in, row_out);
-            
-            last_out_off = out_off;
-            
-            in_off += columns;
-            out_off += columns;
+            let first = out[range.start];
+            for i in range {
+                let b = out[i];
+                out.push(b);
+            }
+            out.push(first);
+        } else {
+            bail!("invalid LZW code {}", code);
         }
-        Ok(out)
-    } else {
-        Ok(decoded)
+        if let Some(prev) = prev {
+            if table.len() < 4096 - 258 {
+                table.push(prev.start .. start + 1);
+            }
+        }
+        prev = Some(start .. out.len());
+
+        // with EarlyChange the width increases one code early
+        width = match 258 + table.len() + early_change {
+            0 ..= 511 => 9,
+            512 ..= 1023 => 10,
+            1024 ..= 2047 => 11,
+            _ => 12
+        };
+    }
+    unpredict(out, params)
+}
+
+/// Undoes the PNG (10 and up) or TIFF (2) predictor of LZWDecode and FlateDecode.
+fn unpredict(data: Vec<u8>, params: &LZWFlateParams) -> Result<Vec<u8>> {
+    let colors = params.n_components.max(1) as usize;
+    let bpc = params.bits_per_component.max(1) as usize;
+    let columns = params.columns.max(1) as usize;
+    // bytes per pixel (at least one) and per row
+    let bpp = (colors * bpc + 7) / 8;
+    let row_len = (colors * bpc * columns + 7) / 8;
+
+    match params.predictor {
+        2 => {
+            if bpc != 8 {
+                bail!("TIFF predictor with {} bits per component is not supported", bpc);
+            }
+            let mut data = data;
+            for row in data.chunks_mut(row_len) {
+                for i in colors .. row.len() {
+                    row[i] = row[i].wrapping_add(row[i - colors]);
+                }
+            }
+            Ok(data)
+        }
+        p if p >= 10 => {
+            // every row starts with its own predictor byte
+            let mut out = Vec::with_capacity(data.len());
+            let mut prev = vec![0; row_len];
+            let mut row = vec![0; row_len];
+            for chunk in data.chunks(row_len + 1) {
+                if chunk.len() < row_len + 1 {
+                    // incomplete last row
+                    break;
+                }
+                let predictor = PredictorType::from_u8(chunk[0])?;
+                unfilter(predictor, bpp, &prev, &chunk[1..], &mut row);
+                out.extend_from_slice(&row);
+                mem::swap(&mut prev, &mut row);
+            }
+            Ok(out)
+        }
+        _ => Ok(data)
     }
 }
 
@@ -191,11 +448,28 @@ pub fn decode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
     match *filter {
         StreamFilter::ASCIIHexDecode => decode_hex(data),
         StreamFilter::ASCII85Decode => decode_85(data),
-        StreamFilter::LZWDecode (_) => unimplemented!(),
+        StreamFilter::LZWDecode (ref params) => lzw_decode(data, params),
         StreamFilter::FlateDecode (ref params) => flate_decode(data, params),
-        StreamFilter::JPXDecode => unimplemented!(),
-        StreamFilter::DCTDecode (_) => unimplemented!(),
-        StreamFilter::CCITTFaxDecode => unimplemented!(),
+        StreamFilter::JPXDecode => bail!("JPXDecode is not supported"),
+        #[cfg(feature = "images")]
+        StreamFilter::DCTDecode (ref params) => decode_dct(data, params),
+        StreamFilter::CCITTFaxDecode => bail!("CCITTFaxDecode is not supported"),
+        StreamFilter::RunLengthDecode => decode_run_length(data),
+        StreamFilter::Crypt => Ok(data.to_vec()),
+        #[cfg(feature = "images")]
+        StreamFilter::JBIG2Decode (ref params) => decode_jbig2(data, params.globals.as_ref().map(|g| g.as_slice())),
+        #[cfg(not(feature = "images"))]
+        StreamFilter::DCTDecode (_) | StreamFilter::JBIG2Decode (_) => bail!("{:?} needs the `images` feature", filter),
+    }
+}
+
+/// Encodes `data` so that `decode` with the same filter gives it back.
+pub fn encode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
+    match *filter {
+        StreamFilter::ASCIIHexDecode => Ok(encode_hex(data)),
+        StreamFilter::ASCII85Decode => Ok(encode_85(data)),
+        StreamFilter::RunLengthDecode => Ok(encode_run_length(data)),
+        ref filter => bail!("encoding with {:?} is not supported", filter)
     }
 }
 
@@ -258,6 +532,9 @@ pub fn unfilter(filter: PredictorType, bpp: usize, prev: &[u8], inp: &[u8], out:
             }
         }
         Sub => {
+            for i in 0..bpp.min(len) {
+                out[i] = inp[i];
+            }
             for i in bpp..len {
                 out[i] = inp[i].wrapping_add(out[i - bpp]);
             }
@@ -331,3 +608,64 @@ pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lzw() {
+        // example from 7.4.4.2
+        let params = LZWFlateParams {
+            predictor: 1,
+            n_components: 1,
+            bits_per_component: 8,
+            columns: 1,
+            early_change: 1,
+        };
+        let data = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+        let decoded = lzw_decode(&data, &params).unwrap();
+        assert_eq!(decoded, [45, 45, 45, 45, 45, 65, 45, 45, 45, 66]);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let data: Vec<u8> = b"hello\0\0\0\0\0\0\0\0 world!!!!".iter().cloned().chain((0 .. 300).map(|i| (i / 7) as u8)).collect();
+        for filter in &[StreamFilter::ASCIIHexDecode, StreamFilter::ASCII85Decode, StreamFilter::RunLengthDecode] {
+            let encoded = encode(&data, filter).unwrap();
+            assert_eq!(decode(&encoded, filter).unwrap(), data, "{:?}", filter);
+        }
+        assert_eq!(decode_85(b"<~87cURD]i,\"Ebo80~>").unwrap(), b"Hello World!");
+        assert_eq!(decode_hex(b"48 65 6c 6C 6>").unwrap(), b"Hell`");
+    }
+
+    #[test]
+    fn parallel_params() {
+        let name = |s: &str| Primitive::Name(s.into());
+        let mut predictor = Dictionary::new();
+        predictor.insert("Predictor".into(), Primitive::Integer(12));
+        predictor.insert("Columns".into(), Primitive::Integer(4));
+
+        let filter = Primitive::Array(vec![name("ASCII85Decode"), name("FlateDecode")]);
+        let params = Primitive::Array(vec![Primitive::Null, Primitive::Dictionary(predictor.clone())]);
+        let filters = filters_with_params(Some(filter.clone()), Some(params), &NoResolve).unwrap();
+        match filters.as_slice() {
+            &[StreamFilter::ASCII85Decode, StreamFilter::FlateDecode(ref p)] => assert_eq!((p.predictor, p.columns), (12, 4)),
+            f => panic!("{:?}", f)
+        }
+
+        // missing trailing entries use the defaults
+        let filters = filters_with_params(Some(filter), Some(Primitive::Array(vec![Primitive::Null])), &NoResolve).unwrap();
+        match filters.as_slice() {
+            &[StreamFilter::ASCII85Decode, StreamFilter::FlateDecode(ref p)] => assert_eq!(p.predictor, 1),
+            f => panic!("{:?}", f)
+        }
+
+        let filters = filters_with_params(Some(name("FlateDecode")), Some(Primitive::Dictionary(predictor)), &NoResolve).unwrap();
+        match filters.as_slice() {
+            &[StreamFilter::FlateDecode(ref p)] => assert_eq!(p.predictor, 12),
+            f => panic!("{:?}", f)
+        }
+        assert!(filters_with_params(None, None, &NoResolve).unwrap().is_empty());
+    }
+}
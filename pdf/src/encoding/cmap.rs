@@ -0,0 +1,373 @@
+//! `/ToUnicode` CMaps (9.10.3), which map the character codes of a font to text, and the
+//! CMaps of composite fonts (9.7.5), which map them to CIDs.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::error::*;
+use crate::object::*;
+use crate::parser::{ContentLexer, TokenKind};
+use crate::primitive::*;
+
+/// `bfrange`s with more codes than this are cut short.
+const MAX_RANGE: u32 = 0x10000;
+
+enum Operand {
+    Code(Vec<u8>),
+    Array(Vec<Vec<u8>>),
+    Number(u32),
+    Name(String),
+}
+
+/// Maps character codes of one or more bytes to text.
+#[derive(Debug, Clone, Default)]
+pub struct ToUnicodeMap {
+    /// The lowest and highest codes of each `codespacerange`
+    codespace: Vec<(Vec<u8>, Vec<u8>)>,
+    map: HashMap<Vec<u8>, String>,
+}
+
+impl ToUnicodeMap {
+    /// Reads the code space and the `bfchar` and `bfrange` mappings of a CMap.
+    /// Reading stops at the first token that can't be read, keeping what was read before it.
+    pub fn parse(data: &[u8]) -> ToUnicodeMap {
+        let mut cmap = ToUnicodeMap::default();
+        let mut operands = Vec::new();
+        let mut array: Option<Vec<Vec<u8>>> = None;
+        for token in ContentLexer::new(data) {
+            let token = match token {
+                Ok(token) => token,
+                Err(_) => break
+            };
+            let text = &data[token.span];
+            match token.kind {
+                TokenKind::String if text.starts_with(b"<") => {
+                    let code = hex(&text[1 .. text.len() - 1]);
+                    match array {
+                        Some(ref mut array) => array.push(code),
+                        None => operands.push(Operand::Code(code))
+                    }
+                }
+                TokenKind::ArrayStart => array = Some(Vec::new()),
+                TokenKind::ArrayEnd => if let Some(array) = array.take() {
+                    operands.push(Operand::Array(array));
+                },
+                TokenKind::Operator => {
+                    match text {
+                        b"endcodespacerange" => for pair in operands.chunks(2) {
+                            if let [Operand::Code(ref low), Operand::Code(ref high)] = *pair {
+                                if low.len() == high.len() && !low.is_empty() {
+                                    cmap.codespace.push((low.clone(), high.clone()));
+                                }
+                            }
+                        },
+                        b"endbfchar" => for pair in operands.chunks(2) {
+                            if let [Operand::Code(ref code), Operand::Code(ref text)] = *pair {
+                                cmap.map.insert(code.clone(), utf16(text, 0));
+                            }
+                        },
+                        b"endbfrange" => for range in operands.chunks(3) {
+                            if let [Operand::Code(ref low), Operand::Code(ref high), ref dst] = *range {
+                                cmap.add_range(low, high, dst);
+                            }
+                        },
+                        _ => {}
+                    }
+                    operands.clear();
+                }
+                _ => {}
+            }
+        }
+        cmap
+    }
+
+    fn add_range(&mut self, low: &[u8], high: &[u8], dst: &Operand) {
+        if low.len() != high.len() || low.is_empty() || low.len() > 4 {
+            return;
+        }
+        let number = |code: &[u8]| code.iter().fold(0u32, |n, &b| n << 8 | b as u32);
+        let (low_nr, high_nr) = (number(low), number(high));
+        if high_nr < low_nr {
+            return;
+        }
+        for i in 0 .. (high_nr - low_nr + 1).min(MAX_RANGE) {
+            let code = (low_nr + i).to_be_bytes()[4 - low.len() ..].to_vec();
+            let text = match *dst {
+                // the last byte is incremented
+                Operand::Code(ref text) => utf16(text, i),
+                Operand::Array(ref texts) => match texts.get(i as usize) {
+                    Some(text) => utf16(text, 0),
+                    None => break
+                },
+                _ => return
+            };
+            self.map.insert(code, text);
+        }
+    }
+
+    /// The text of the code `code`.
+    pub fn get(&self, code: &[u8]) -> Option<&str> {
+        self.map.get(code).map(|s| s.as_str())
+    }
+
+    /// How many bytes the code at the start of `data` has, according to the code space.
+    /// Without one, the shortest code with a mapping is taken.
+    pub fn code_len(&self, data: &[u8]) -> usize {
+        for &(ref low, ref high) in &self.codespace {
+            let n = low.len();
+            if data.len() >= n && (0 .. n).all(|k| low[k] <= data[k] && data[k] <= high[k]) {
+                return n;
+            }
+        }
+        if self.codespace.is_empty() {
+            if let Some(n) = (1 ..= data.len().min(4)).find(|&n| self.map.contains_key(&data[.. n])) {
+                return n;
+            }
+        }
+        self.codespace.iter().map(|(low, _)| low.len()).min().unwrap_or(1).min(data.len())
+    }
+
+    /// The text of `data`. Codes without a mapping are passed to `fallback`.
+    pub fn decode(&self, mut data: &[u8], mut fallback: impl FnMut(&[u8], &mut String)) -> String {
+        let mut out = String::with_capacity(data.len());
+        while !data.is_empty() {
+            let n = self.code_len(data).max(1);
+            let (code, rest) = data.split_at(n);
+            match self.get(code) {
+                Some(text) => out.push_str(text),
+                None => fallback(code, &mut out)
+            }
+            data = rest;
+        }
+        out
+    }
+}
+
+/// Maps the character codes of a composite font to CIDs.
+#[derive(Debug, Clone, Default)]
+pub struct CMap {
+    /// `/CMapName`, or the name of a predefined CMap
+    pub name: Option<String>,
+    /// Glyphs are written top to bottom
+    pub vertical: bool,
+    /// The lowest and highest codes of each `codespacerange`
+    codespace: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Codes from the first to the second, which have the same length, map to CIDs from
+    /// the third on
+    ranges: Vec<(Vec<u8>, Vec<u8>, u32)>,
+}
+
+impl CMap {
+    /// `Identity-H` or `Identity-V`: two byte codes that are CIDs.
+    pub fn identity(vertical: bool) -> CMap {
+        CMap {
+            name: Some(if vertical { "Identity-V" } else { "Identity-H" }.into()),
+            vertical,
+            codespace: vec![(vec![0, 0], vec![0xFF, 0xFF])],
+            ranges: vec![(vec![0, 0], vec![0xFF, 0xFF], 0)],
+        }
+    }
+    /// The predefined CMap `name`. Only the identity CMaps are known; codes of others are
+    /// read as two bytes, without a CID.
+    pub fn predefined(name: &str) -> CMap {
+        match name {
+            "Identity-H" => CMap::identity(false),
+            "Identity-V" => CMap::identity(true),
+            _ => CMap {
+                name: Some(name.into()),
+                vertical: name.ends_with("-V"),
+                codespace: vec![(vec![0, 0], vec![0xFF, 0xFF])],
+                ranges: Vec::new(),
+            }
+        }
+    }
+    /// Reads the code space, the `cidrange` and `cidchar` mappings, `/CMapName` and `/WMode`
+    /// of an embedded CMap. `usecmap` is not followed.
+    pub fn parse(data: &[u8]) -> CMap {
+        let mut cmap = CMap::default();
+        let mut operands = Vec::new();
+        for token in ContentLexer::new(data) {
+            let token = match token {
+                Ok(token) => token,
+                Err(_) => break
+            };
+            let text = &data[token.span];
+            match token.kind {
+                TokenKind::String if text.starts_with(b"<") => {
+                    operands.push(Operand::Code(hex(&text[1 .. text.len() - 1])));
+                }
+                TokenKind::Number => match std::str::from_utf8(text).ok().and_then(|n| n.parse().ok()) {
+                    Some(n) => operands.push(Operand::Number(n)),
+                    None => operands.clear()
+                },
+                TokenKind::Name => operands.push(Operand::Name(String::from_utf8_lossy(&text[1 ..]).into_owned())),
+                TokenKind::Operator => {
+                    match text {
+                        b"endcodespacerange" => for pair in operands.chunks(2) {
+                            if let [Operand::Code(ref low), Operand::Code(ref high)] = *pair {
+                                if low.len() == high.len() && !low.is_empty() {
+                                    cmap.codespace.push((low.clone(), high.clone()));
+                                }
+                            }
+                        },
+                        b"endcidrange" => for range in operands.chunks(3) {
+                            if let [Operand::Code(ref low), Operand::Code(ref high), Operand::Number(cid)] = *range {
+                                if low.len() == high.len() && !low.is_empty() {
+                                    cmap.ranges.push((low.clone(), high.clone(), cid));
+                                }
+                            }
+                        },
+                        b"endcidchar" => for pair in operands.chunks(2) {
+                            if let [Operand::Code(ref code), Operand::Number(cid)] = *pair {
+                                cmap.ranges.push((code.clone(), code.clone(), cid));
+                            }
+                        },
+                        b"def" => match operands[..] {
+                            [.., Operand::Name(ref key), Operand::Name(ref name)] if key == "CMapName" => {
+                                cmap.name = Some(name.clone());
+                            }
+                            [.., Operand::Name(ref key), Operand::Number(mode)] if key == "WMode" => {
+                                cmap.vertical = mode == 1;
+                            }
+                            _ => {}
+                        },
+                        _ => {}
+                    }
+                    operands.clear();
+                }
+                _ => {}
+            }
+        }
+        cmap
+    }
+
+    /// How many bytes the code at the start of `data` has. Codes outside the code space
+    /// are as long as the shortest code.
+    pub fn code_len(&self, data: &[u8]) -> usize {
+        for &(ref low, ref high) in &self.codespace {
+            let n = low.len();
+            if data.len() >= n && (0 .. n).all(|k| low[k] <= data[k] && data[k] <= high[k]) {
+                return n;
+            }
+        }
+        self.codespace.iter().map(|(low, _)| low.len()).min().unwrap_or(1).min(data.len())
+    }
+    /// The CID of `code`, if it is mapped.
+    pub fn cid(&self, code: &[u8]) -> Option<u32> {
+        let number = |code: &[u8]| code.iter().fold(0u32, |n, &b| n << 8 | b as u32);
+        self.ranges.iter()
+            .find(|&&(ref low, ref high, _)| low.len() == code.len() && &low[..] <= code && code <= &high[..])
+            .map(|&(ref low, _, cid)| cid + number(code) - number(low))
+    }
+    /// The codes of `data`, with their CIDs.
+    pub fn cids<'a>(&self, mut data: &'a [u8]) -> Vec<(&'a [u8], Option<u32>)> {
+        let mut cids = Vec::with_capacity(data.len() / 2);
+        while !data.is_empty() {
+            let (code, rest) = data.split_at(self.code_len(data).max(1));
+            cids.push((code, self.cid(code)));
+            data = rest;
+        }
+        cids
+    }
+    /// Whether the codes are UTF-16, like those of `UniGB-UTF16-H` or `UniJIS-UCS2-H`.
+    pub fn is_unicode(&self) -> bool {
+        match self.name {
+            Some(ref name) => name.starts_with("Uni") && (name.contains("-UCS2-") || name.contains("-UTF16-")),
+            None => false
+        }
+    }
+}
+
+#[derive(Object, Debug)]
+struct CMapInfo {
+    #[pdf(key="CMapName")]
+    name: Option<String>,
+
+    #[pdf(key="WMode", default="0")]
+    wmode: i32,
+}
+
+impl Object for CMap {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        // only predefined CMaps are written
+        match self.name {
+            Some(ref name) if self.ranges.is_empty() || name.starts_with("Identity-") => name.serialize(out),
+            _ => bail!("embedded CMaps can't be written")
+        }
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Name(name) => Ok(CMap::predefined(&name)),
+            p => {
+                let stream = Stream::<CMapInfo>::from_primitive(p, resolve)?;
+                let mut cmap = CMap::parse(stream.data()?);
+                if cmap.name.is_none() {
+                    cmap.name = stream.name.clone();
+                }
+                cmap.vertical |= stream.wmode == 1;
+                Ok(cmap)
+            }
+        }
+    }
+}
+
+/// The bytes of a hexadecimal string without `<` and `>`. A missing last digit is 0.
+fn hex(digits: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = digits.iter().filter_map(|&c| (c as char).to_digit(16)).map(|d| d as u8).collect();
+    digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0)).collect()
+}
+
+/// Decodes UTF-16BE after adding `increment` to the last code unit.
+fn utf16(data: &[u8], increment: u32) -> String {
+    let mut units: Vec<u16> = data.chunks(2).map(|pair| match *pair {
+        [a, b] => u16::from_be_bytes([a, b]),
+        [a] => a as u16,
+        _ => unreachable!()
+    }).collect();
+    if let Some(last) = units.last_mut() {
+        *last = last.wrapping_add(increment as u16);
+    }
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_unicode() {
+        let data = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap\n\
+            1 begincodespacerange <0000> <FFFF> endcodespacerange\n\
+            2 beginbfchar <0003> <0020> <0011> <D83DDE00> endbfchar\n\
+            2 beginbfrange <0024> <0026> <0041> <0030> <0031> [<0066006C> <00E9>] endbfrange\n\
+            endcmap CMapName currentdict /CMap defineresource pop end end";
+        let cmap = ToUnicodeMap::parse(data);
+        assert_eq!(cmap.get(&[0, 0x25]), Some("B"));
+        assert_eq!(cmap.get(&[0, 0x30]), Some("fl"));
+        let text = cmap.decode(&[0, 0x24, 0, 3, 0, 0x11, 0, 0x31, 0, 0x99], |_, out| out.push('?'));
+        assert_eq!(text, "A \u{1F600}\u{E9}?");
+    }
+
+    #[test]
+    fn cids() {
+        let data = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap\n\
+            /CMapName /Test-H def /WMode 0 def\n\
+            2 begincodespacerange <00> <80> <8140> <FFFF> endcodespacerange\n\
+            1 begincidrange <20> <7E> 1 endcidrange\n\
+            1 begincidchar <8140> 633 endcidchar\n\
+            endcmap CMapName currentdict /CMap defineresource pop end end";
+        let cmap = CMap::parse(data);
+        assert_eq!((cmap.name.as_deref(), cmap.vertical), (Some("Test-H"), false));
+        let cids: Vec<Option<u32>> = cmap.cids(b"A\x81\x40\x90").into_iter().map(|(_, cid)| cid).collect();
+        assert_eq!(cids, [Some(34), Some(633), None]);
+
+        let identity = CMap::predefined("Identity-V");
+        assert_eq!(identity.cids(b"\x01\x02"), [(&b"\x01\x02"[..], Some(0x102))]);
+        assert!(identity.vertical && !identity.is_unicode() && CMap::predefined("UniJIS-UCS2-H").is_unicode());
+
+        let mut out = Vec::new();
+        identity.serialize(&mut out).unwrap();
+        assert_eq!(out, b"/Identity-V");
+        assert!(cmap.serialize(&mut Vec::new()).is_err());
+    }
+}
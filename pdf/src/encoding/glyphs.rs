@@ -0,0 +1,233 @@
+//! Glyph names and the characters they stand for.
+
+/// The glyphs of the standard encodings and of the Symbol and ZapfDingbats fonts, sorted by name.
+static GLYPHS: &[(&str, char)] = &[
+    ("A", 'A'), ("AE", '\u{00C6}'), ("Aacute", '\u{00C1}'), ("Acircumflex", '\u{00C2}'),
+    ("Adieresis", '\u{00C4}'), ("Agrave", '\u{00C0}'), ("Alpha", '\u{0391}'), ("Aring", '\u{00C5}'),
+    ("Atilde", '\u{00C3}'), ("B", 'B'), ("Beta", '\u{0392}'), ("C", 'C'), ("Ccedilla", '\u{00C7}'),
+    ("Chi", '\u{03A7}'), ("D", 'D'), ("Delta", '\u{2206}'), ("E", 'E'), ("Eacute", '\u{00C9}'),
+    ("Ecircumflex", '\u{00CA}'), ("Edieresis", '\u{00CB}'), ("Egrave", '\u{00C8}'),
+    ("Epsilon", '\u{0395}'), ("Eta", '\u{0397}'), ("Eth", '\u{00D0}'), ("Euro", '\u{20AC}'),
+    ("F", 'F'), ("G", 'G'), ("Gamma", '\u{0393}'), ("H", 'H'), ("I", 'I'), ("Iacute", '\u{00CD}'),
+    ("Icircumflex", '\u{00CE}'), ("Idieresis", '\u{00CF}'), ("Ifraktur", '\u{2111}'),
+    ("Igrave", '\u{00CC}'), ("Iota", '\u{0399}'), ("J", 'J'), ("K", 'K'), ("Kappa", '\u{039A}'),
+    ("L", 'L'), ("Lambda", '\u{039B}'), ("Lslash", '\u{0141}'), ("M", 'M'), ("Mu", '\u{039C}'),
+    ("N", 'N'), ("Ntilde", '\u{00D1}'), ("Nu", '\u{039D}'), ("O", 'O'), ("OE", '\u{0152}'),
+    ("Oacute", '\u{00D3}'), ("Ocircumflex", '\u{00D4}'), ("Odieresis", '\u{00D6}'),
+    ("Ograve", '\u{00D2}'), ("Omega", '\u{2126}'), ("Omicron", '\u{039F}'), ("Oslash", '\u{00D8}'),
+    ("Otilde", '\u{00D5}'), ("P", 'P'), ("Phi", '\u{03A6}'), ("Pi", '\u{03A0}'),
+    ("Psi", '\u{03A8}'), ("Q", 'Q'), ("R", 'R'), ("Rfraktur", '\u{211C}'), ("Rho", '\u{03A1}'),
+    ("S", 'S'), ("Scaron", '\u{0160}'), ("Sigma", '\u{03A3}'), ("T", 'T'), ("Tau", '\u{03A4}'),
+    ("Theta", '\u{0398}'), ("Thorn", '\u{00DE}'), ("U", 'U'), ("Uacute", '\u{00DA}'),
+    ("Ucircumflex", '\u{00DB}'), ("Udieresis", '\u{00DC}'), ("Ugrave", '\u{00D9}'),
+    ("Upsilon", '\u{03A5}'), ("Upsilon1", '\u{03D2}'), ("V", 'V'), ("W", 'W'), ("X", 'X'),
+    ("Xi", '\u{039E}'), ("Y", 'Y'), ("Yacute", '\u{00DD}'), ("Ydieresis", '\u{0178}'), ("Z", 'Z'),
+    ("Zcaron", '\u{017D}'), ("Zeta", '\u{0396}'), ("a", 'a'), ("a1", '\u{2701}'),
+    ("a10", '\u{2721}'), ("a100", '\u{275E}'), ("a101", '\u{2761}'), ("a102", '\u{2762}'),
+    ("a103", '\u{2763}'), ("a104", '\u{2764}'), ("a105", '\u{2710}'), ("a106", '\u{2765}'),
+    ("a107", '\u{2766}'), ("a108", '\u{2767}'), ("a109", '\u{2660}'), ("a11", '\u{261B}'),
+    ("a110", '\u{2665}'), ("a111", '\u{2666}'), ("a112", '\u{2663}'), ("a117", '\u{2709}'),
+    ("a118", '\u{2708}'), ("a119", '\u{2707}'), ("a12", '\u{261E}'), ("a120", '\u{2460}'),
+    ("a121", '\u{2461}'), ("a122", '\u{2462}'), ("a123", '\u{2463}'), ("a124", '\u{2464}'),
+    ("a125", '\u{2465}'), ("a126", '\u{2466}'), ("a127", '\u{2467}'), ("a128", '\u{2468}'),
+    ("a129", '\u{2469}'), ("a13", '\u{270C}'), ("a130", '\u{2776}'), ("a131", '\u{2777}'),
+    ("a132", '\u{2778}'), ("a133", '\u{2779}'), ("a134", '\u{277A}'), ("a135", '\u{277B}'),
+    ("a136", '\u{277C}'), ("a137", '\u{277D}'), ("a138", '\u{277E}'), ("a139", '\u{277F}'),
+    ("a14", '\u{270D}'), ("a140", '\u{2780}'), ("a141", '\u{2781}'), ("a142", '\u{2782}'),
+    ("a143", '\u{2783}'), ("a144", '\u{2784}'), ("a145", '\u{2785}'), ("a146", '\u{2786}'),
+    ("a147", '\u{2787}'), ("a148", '\u{2788}'), ("a149", '\u{2789}'), ("a15", '\u{270E}'),
+    ("a150", '\u{278A}'), ("a151", '\u{278B}'), ("a152", '\u{278C}'), ("a153", '\u{278D}'),
+    ("a154", '\u{278E}'), ("a155", '\u{278F}'), ("a156", '\u{2790}'), ("a157", '\u{2791}'),
+    ("a158", '\u{2792}'), ("a159", '\u{2793}'), ("a16", '\u{270F}'), ("a160", '\u{2794}'),
+    ("a161", '\u{2192}'), ("a162", '\u{27A3}'), ("a163", '\u{2194}'), ("a164", '\u{2195}'),
+    ("a165", '\u{2799}'), ("a166", '\u{279B}'), ("a167", '\u{279C}'), ("a168", '\u{279D}'),
+    ("a169", '\u{279E}'), ("a17", '\u{2711}'), ("a170", '\u{279F}'), ("a171", '\u{27A0}'),
+    ("a172", '\u{27A1}'), ("a173", '\u{27A2}'), ("a174", '\u{27A4}'), ("a175", '\u{27A5}'),
+    ("a176", '\u{27A6}'), ("a177", '\u{27A7}'), ("a178", '\u{27A8}'), ("a179", '\u{27A9}'),
+    ("a18", '\u{2712}'), ("a180", '\u{27AB}'), ("a181", '\u{27AD}'), ("a182", '\u{27AF}'),
+    ("a183", '\u{27B2}'), ("a184", '\u{27B3}'), ("a185", '\u{27B5}'), ("a186", '\u{27B8}'),
+    ("a187", '\u{27BA}'), ("a188", '\u{27BB}'), ("a189", '\u{27BC}'), ("a19", '\u{2713}'),
+    ("a190", '\u{27BD}'), ("a191", '\u{27BE}'), ("a192", '\u{279A}'), ("a193", '\u{27AA}'),
+    ("a194", '\u{27B6}'), ("a195", '\u{27B9}'), ("a196", '\u{2798}'), ("a197", '\u{27B4}'),
+    ("a198", '\u{27B7}'), ("a199", '\u{27AC}'), ("a2", '\u{2702}'), ("a20", '\u{2714}'),
+    ("a200", '\u{27AE}'), ("a201", '\u{27B1}'), ("a202", '\u{2703}'), ("a203", '\u{2750}'),
+    ("a204", '\u{2752}'), ("a205", '\u{F8DD}'), ("a206", '\u{F8DF}'), ("a21", '\u{2715}'),
+    ("a22", '\u{2716}'), ("a23", '\u{2717}'), ("a24", '\u{2718}'), ("a25", '\u{2719}'),
+    ("a26", '\u{271A}'), ("a27", '\u{271B}'), ("a28", '\u{271C}'), ("a29", '\u{2722}'),
+    ("a3", '\u{2704}'), ("a30", '\u{2723}'), ("a31", '\u{2724}'), ("a32", '\u{2725}'),
+    ("a33", '\u{2726}'), ("a34", '\u{2727}'), ("a35", '\u{2605}'), ("a36", '\u{2729}'),
+    ("a37", '\u{272A}'), ("a38", '\u{272B}'), ("a39", '\u{272C}'), ("a4", '\u{260E}'),
+    ("a40", '\u{272D}'), ("a41", '\u{272E}'), ("a42", '\u{272F}'), ("a43", '\u{2730}'),
+    ("a44", '\u{2731}'), ("a45", '\u{2732}'), ("a46", '\u{2733}'), ("a47", '\u{2734}'),
+    ("a48", '\u{2735}'), ("a49", '\u{2736}'), ("a5", '\u{2706}'), ("a50", '\u{2737}'),
+    ("a51", '\u{2738}'), ("a52", '\u{2739}'), ("a53", '\u{273A}'), ("a54", '\u{273B}'),
+    ("a55", '\u{273C}'), ("a56", '\u{273D}'), ("a57", '\u{273E}'), ("a58", '\u{273F}'),
+    ("a59", '\u{2740}'), ("a6", '\u{271D}'), ("a60", '\u{2741}'), ("a61", '\u{2742}'),
+    ("a62", '\u{2743}'), ("a63", '\u{2744}'), ("a64", '\u{2745}'), ("a65", '\u{2746}'),
+    ("a66", '\u{2747}'), ("a67", '\u{2748}'), ("a68", '\u{2749}'), ("a69", '\u{274A}'),
+    ("a7", '\u{271E}'), ("a70", '\u{274B}'), ("a71", '\u{25CF}'), ("a72", '\u{274D}'),
+    ("a73", '\u{25A0}'), ("a74", '\u{274F}'), ("a75", '\u{2751}'), ("a76", '\u{25B2}'),
+    ("a77", '\u{25BC}'), ("a78", '\u{25C6}'), ("a79", '\u{2756}'), ("a8", '\u{271F}'),
+    ("a81", '\u{25D7}'), ("a82", '\u{2758}'), ("a83", '\u{2759}'), ("a84", '\u{275A}'),
+    ("a85", '\u{F8DE}'), ("a86", '\u{F8E0}'), ("a87", '\u{F8E1}'), ("a88", '\u{F8E2}'),
+    ("a89", '\u{F8D7}'), ("a9", '\u{2720}'), ("a90", '\u{F8D8}'), ("a91", '\u{F8DB}'),
+    ("a92", '\u{F8DC}'), ("a93", '\u{F8D9}'), ("a94", '\u{F8DA}'), ("a95", '\u{F8E3}'),
+    ("a96", '\u{F8E4}'), ("a97", '\u{275B}'), ("a98", '\u{275C}'), ("a99", '\u{275D}'),
+    ("aacute", '\u{00E1}'), ("acircumflex", '\u{00E2}'), ("acute", '\u{00B4}'),
+    ("adieresis", '\u{00E4}'), ("ae", '\u{00E6}'), ("agrave", '\u{00E0}'), ("aleph", '\u{2135}'),
+    ("alpha", '\u{03B1}'), ("ampersand", '&'), ("angle", '\u{2220}'), ("angleleft", '\u{2329}'),
+    ("angleright", '\u{232A}'), ("approxequal", '\u{2248}'), ("aring", '\u{00E5}'),
+    ("arrowboth", '\u{2194}'), ("arrowdblboth", '\u{21D4}'), ("arrowdbldown", '\u{21D3}'),
+    ("arrowdblleft", '\u{21D0}'), ("arrowdblright", '\u{21D2}'), ("arrowdblup", '\u{21D1}'),
+    ("arrowdown", '\u{2193}'), ("arrowhorizex", '\u{F8E7}'), ("arrowleft", '\u{2190}'),
+    ("arrowright", '\u{2192}'), ("arrowup", '\u{2191}'), ("arrowvertex", '\u{F8E6}'),
+    ("asciicircum", '^'), ("asciitilde", '~'), ("asterisk", '*'), ("asteriskmath", '\u{2217}'),
+    ("at", '@'), ("atilde", '\u{00E3}'), ("b", 'b'), ("backslash", '\\'), ("bar", '|'),
+    ("beta", '\u{03B2}'), ("braceex", '\u{F8F4}'), ("braceleft", '{'), ("braceleftbt", '\u{F8F3}'),
+    ("braceleftmid", '\u{F8F2}'), ("bracelefttp", '\u{F8F1}'), ("braceright", '}'),
+    ("bracerightbt", '\u{F8FE}'), ("bracerightmid", '\u{F8FD}'), ("bracerighttp", '\u{F8FC}'),
+    ("bracketleft", '['), ("bracketleftbt", '\u{F8F0}'), ("bracketleftex", '\u{F8EF}'),
+    ("bracketlefttp", '\u{F8EE}'), ("bracketright", ']'), ("bracketrightbt", '\u{F8FB}'),
+    ("bracketrightex", '\u{F8FA}'), ("bracketrighttp", '\u{F8F9}'), ("breve", '\u{02D8}'),
+    ("brokenbar", '\u{00A6}'), ("bullet", '\u{2022}'), ("c", 'c'), ("caron", '\u{02C7}'),
+    ("carriagereturn", '\u{21B5}'), ("ccedilla", '\u{00E7}'), ("cedilla", '\u{00B8}'),
+    ("cent", '\u{00A2}'), ("chi", '\u{03C7}'), ("circlemultiply", '\u{2297}'),
+    ("circleplus", '\u{2295}'), ("circumflex", '\u{02C6}'), ("club", '\u{2663}'), ("colon", ':'),
+    ("comma", ','), ("congruent", '\u{2245}'), ("copyright", '\u{00A9}'),
+    ("copyrightsans", '\u{F8E9}'), ("copyrightserif", '\u{F6D9}'), ("currency", '\u{00A4}'),
+    ("d", 'd'), ("dagger", '\u{2020}'), ("daggerdbl", '\u{2021}'), ("degree", '\u{00B0}'),
+    ("delta", '\u{03B4}'), ("diamond", '\u{2666}'), ("dieresis", '\u{00A8}'),
+    ("divide", '\u{00F7}'), ("dollar", '$'), ("dotaccent", '\u{02D9}'), ("dotlessi", '\u{0131}'),
+    ("dotmath", '\u{22C5}'), ("e", 'e'), ("eacute", '\u{00E9}'), ("ecircumflex", '\u{00EA}'),
+    ("edieresis", '\u{00EB}'), ("egrave", '\u{00E8}'), ("eight", '8'), ("element", '\u{2208}'),
+    ("ellipsis", '\u{2026}'), ("emdash", '\u{2014}'), ("emptyset", '\u{2205}'),
+    ("endash", '\u{2013}'), ("epsilon", '\u{03B5}'), ("equal", '='), ("equivalence", '\u{2261}'),
+    ("eta", '\u{03B7}'), ("eth", '\u{00F0}'), ("exclam", '!'), ("exclamdown", '\u{00A1}'),
+    ("existential", '\u{2203}'), ("f", 'f'), ("fi", '\u{FB01}'), ("five", '5'), ("fl", '\u{FB02}'),
+    ("florin", '\u{0192}'), ("four", '4'), ("fraction", '\u{2215}'), ("g", 'g'),
+    ("gamma", '\u{03B3}'), ("germandbls", '\u{00DF}'), ("gradient", '\u{2207}'), ("grave", '`'),
+    ("greater", '>'), ("greaterequal", '\u{2265}'), ("guillemotleft", '\u{00AB}'),
+    ("guillemotright", '\u{00BB}'), ("guilsinglleft", '\u{2039}'), ("guilsinglright", '\u{203A}'),
+    ("h", 'h'), ("heart", '\u{2665}'), ("hungarumlaut", '\u{02DD}'), ("hyphen", '-'),
+    ("i", 'i'), ("iacute", '\u{00ED}'), ("icircumflex", '\u{00EE}'), ("idieresis", '\u{00EF}'),
+    ("igrave", '\u{00EC}'), ("infinity", '\u{221E}'), ("integral", '\u{222B}'),
+    ("integralbt", '\u{2321}'), ("integralex", '\u{F8F5}'), ("integraltp", '\u{2320}'),
+    ("intersection", '\u{2229}'), ("iota", '\u{03B9}'), ("j", 'j'), ("k", 'k'),
+    ("kappa", '\u{03BA}'), ("l", 'l'), ("lambda", '\u{03BB}'), ("less", '<'),
+    ("lessequal", '\u{2264}'), ("logicaland", '\u{2227}'), ("logicalnot", '\u{00AC}'),
+    ("logicalor", '\u{2228}'), ("lozenge", '\u{25CA}'), ("lslash", '\u{0142}'), ("m", 'm'),
+    ("macron", '\u{02C9}'), ("minus", '\u{2212}'), ("minute", '\u{2032}'), ("mu", '\u{03BC}'),
+    ("multiply", '\u{00D7}'), ("n", 'n'), ("nine", '9'), ("notelement", '\u{2209}'),
+    ("notequal", '\u{2260}'), ("notsubset", '\u{2284}'), ("ntilde", '\u{00F1}'), ("nu", '\u{03BD}'),
+    ("numbersign", '#'), ("o", 'o'), ("oacute", '\u{00F3}'), ("ocircumflex", '\u{00F4}'),
+    ("odieresis", '\u{00F6}'), ("oe", '\u{0153}'), ("ogonek", '\u{02DB}'), ("ograve", '\u{00F2}'),
+    ("omega", '\u{03C9}'), ("omega1", '\u{03D6}'), ("omicron", '\u{03BF}'), ("one", '1'),
+    ("onehalf", '\u{00BD}'), ("onequarter", '\u{00BC}'), ("onesuperior", '\u{00B9}'),
+    ("ordfeminine", '\u{00AA}'), ("ordmasculine", '\u{00BA}'), ("oslash", '\u{00F8}'),
+    ("otilde", '\u{00F5}'), ("p", 'p'), ("paragraph", '\u{00B6}'), ("parenleft", '('),
+    ("parenleftbt", '\u{F8ED}'), ("parenleftex", '\u{F8EC}'), ("parenlefttp", '\u{F8EB}'),
+    ("parenright", ')'), ("parenrightbt", '\u{F8F8}'), ("parenrightex", '\u{F8F7}'),
+    ("parenrighttp", '\u{F8F6}'), ("partialdiff", '\u{2202}'), ("percent", '%'), ("period", '.'),
+    ("periodcentered", '\u{2219}'), ("perpendicular", '\u{22A5}'), ("perthousand", '\u{2030}'),
+    ("phi", '\u{03C6}'), ("phi1", '\u{03D5}'), ("pi", '\u{03C0}'), ("plus", '+'),
+    ("plusminus", '\u{00B1}'), ("product", '\u{220F}'), ("propersubset", '\u{2282}'),
+    ("propersuperset", '\u{2283}'), ("proportional", '\u{221D}'), ("psi", '\u{03C8}'), ("q", 'q'),
+    ("question", '?'), ("questiondown", '\u{00BF}'), ("quotedbl", '"'),
+    ("quotedblbase", '\u{201E}'), ("quotedblleft", '\u{201C}'), ("quotedblright", '\u{201D}'),
+    ("quoteleft", '\u{2018}'), ("quoteright", '\u{2019}'), ("quotesinglbase", '\u{201A}'),
+    ("quotesingle", '\''), ("r", 'r'), ("radical", '\u{221A}'), ("radicalex", '\u{F8E5}'),
+    ("reflexsubset", '\u{2286}'), ("reflexsuperset", '\u{2287}'), ("registered", '\u{00AE}'),
+    ("registersans", '\u{F8E8}'), ("registerserif", '\u{F6DA}'), ("rho", '\u{03C1}'),
+    ("ring", '\u{02DA}'), ("s", 's'), ("scaron", '\u{0161}'), ("second", '\u{2033}'),
+    ("section", '\u{00A7}'), ("semicolon", ';'), ("seven", '7'), ("sigma", '\u{03C3}'),
+    ("sigma1", '\u{03C2}'), ("similar", '\u{223C}'), ("six", '6'), ("slash", '/'),
+    ("space", ' '), ("spade", '\u{2660}'), ("sterling", '\u{00A3}'),
+    ("suchthat", '\u{220B}'), ("summation", '\u{2211}'), ("t", 't'), ("tau", '\u{03C4}'),
+    ("therefore", '\u{2234}'), ("theta", '\u{03B8}'), ("theta1", '\u{03D1}'), ("thorn", '\u{00FE}'),
+    ("three", '3'), ("threequarters", '\u{00BE}'), ("threesuperior", '\u{00B3}'),
+    ("tilde", '\u{02DC}'), ("trademark", '\u{2122}'), ("trademarksans", '\u{F8EA}'),
+    ("trademarkserif", '\u{F6DB}'), ("two", '2'), ("twosuperior", '\u{00B2}'), ("u", 'u'),
+    ("uacute", '\u{00FA}'), ("ucircumflex", '\u{00FB}'), ("udieresis", '\u{00FC}'),
+    ("ugrave", '\u{00F9}'), ("underscore", '_'), ("union", '\u{222A}'), ("universal", '\u{2200}'),
+    ("upsilon", '\u{03C5}'), ("v", 'v'), ("w", 'w'), ("weierstrass", '\u{2118}'), ("x", 'x'),
+    ("xi", '\u{03BE}'), ("y", 'y'), ("yacute", '\u{00FD}'), ("ydieresis", '\u{00FF}'),
+    ("yen", '\u{00A5}'), ("z", 'z'), ("zcaron", '\u{017E}'), ("zero", '0'), ("zeta", '\u{03B6}'),
+];
+
+/// Cyrillic `afii` names that are not in alphabetical order, and their characters.
+static AFII_CYRILLIC: &[(u32, char)] = &[
+    (10023, '\u{0401}'), (10050, '\u{0490}'), (10051, '\u{0402}'), (10052, '\u{0403}'),
+    (10053, '\u{0404}'), (10054, '\u{0405}'), (10055, '\u{0406}'), (10056, '\u{0407}'),
+    (10057, '\u{0408}'), (10058, '\u{0409}'), (10059, '\u{040A}'), (10060, '\u{040B}'),
+    (10061, '\u{040C}'), (10062, '\u{040E}'), (10071, '\u{0451}'), (10098, '\u{0491}'),
+    (10099, '\u{0452}'), (10100, '\u{0453}'), (10101, '\u{0454}'), (10102, '\u{0455}'),
+    (10103, '\u{0456}'), (10104, '\u{0457}'), (10105, '\u{0458}'), (10106, '\u{0459}'),
+    (10107, '\u{045A}'), (10108, '\u{045B}'), (10109, '\u{045C}'), (10110, '\u{045E}'),
+    (10145, '\u{040F}'), (10193, '\u{045F}'),
+];
+
+/// The character of the glyph named `name`.
+///
+/// Names that are not listed are read like `uni20AC`, `u1F600`, `afii10017` for Cyrillic
+/// letters, or `g41` and `c65` with the hexadecimal or decimal code of a Latin-1 character.
+/// A suffix like `.sc` is ignored.
+pub fn glyph_to_char(name: &str) -> Option<char> {
+    let name = match name.find('.') {
+        Some(n) if n > 0 => &name[.. n],
+        _ => name
+    };
+    if let Ok(i) = GLYPHS.binary_search_by_key(&name, |&(name, _)| name) {
+        return Some(GLYPHS[i].1);
+    }
+    let c = if let Some(hex) = name.strip_prefix("uni").filter(|hex| hex.len() == 4) {
+        number(hex, 16)
+    } else if let Some(hex) = name.strip_prefix('u').filter(|hex| (4 ..= 6).contains(&hex.len())) {
+        number(hex, 16)
+    } else if let Some(n) = name.strip_prefix("afii").and_then(|n| number(n, 10)) {
+        return afii(n);
+    } else if let Some(hex) = name.strip_prefix(|c: char| c == 'g' || c == 'G').filter(|hex| hex.len() == 2) {
+        number(hex, 16)
+    } else if let Some(n) = name.strip_prefix(|c: char| c == 'c' || c == 'C').filter(|n| (2 ..= 3).contains(&n.len())) {
+        number(n, 10).filter(|&n| n < 256)
+    } else {
+        None
+    };
+    // '\0' can't be decoded to
+    std::char::from_u32(c?).filter(|&c| c != '\0')
+}
+
+/// `digits` as a number, if they are all digits of `radix`.
+fn number(digits: &str, radix: u32) -> Option<u32> {
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u32::from_str_radix(digits, radix).ok()
+}
+
+fn afii(n: u32) -> Option<char> {
+    let c = match n {
+        10017 ..= 10022 => 0x0410 + n - 10017,
+        10024 ..= 10049 => 0x0416 + n - 10024,
+        10065 ..= 10070 => 0x0430 + n - 10065,
+        10072 ..= 10097 => 0x0436 + n - 10072,
+        _ => return AFII_CYRILLIC.iter().find(|&&(k, _)| k == n).map(|&(_, c)| c)
+    };
+    std::char::from_u32(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyph_names() {
+        let names = ["alpha", "A.sc", "uni20AC", "u1F600", "uniD800", "afii10017", "afii10071", "g41", "C100", "G3", "foo", ".notdef"];
+        let chars: Vec<Option<char>> = names.iter().map(|name| glyph_to_char(name)).collect();
+        assert_eq!(chars, [
+            Some('\u{3B1}'), Some('A'), Some('\u{20AC}'), Some('\u{1F600}'), None, Some('\u{410}'),
+            Some('\u{451}'), Some('A'), Some('d'), None, None, None
+        ]);
+        assert_eq!(glyph_to_char("space"), Some(' '));
+        assert_eq!(glyph_to_char("hyphen"), Some('-'));
+    }
+}
@@ -0,0 +1,34 @@
+[
+          None,          None,          None,          None,          None,          None,          None,          None,
+          None,          None,          None,          None,          None,          None,          None,          None,
+          None,          None,          None,          None,          None,          None,          None,          None,
+          None,          None,          None,          None,          None,          None,          None,          None,
+ c('\u{0020}'), c('\u{0021}'), c('\u{02DD}'), c('\u{00A2}'), c('\u{0024}'), c('\u{0024}'), c('\u{0026}'), c('\u{00B4}'),
+ c('\u{207D}'), c('\u{207E}'), c('\u{2025}'), c('\u{2024}'), c('\u{002C}'), c('\u{002D}'), c('\u{002E}'), c('\u{2044}'),
+ c('\u{0030}'), c('\u{0031}'), c('\u{0032}'), c('\u{0033}'), c('\u{0034}'), c('\u{0035}'), c('\u{0036}'), c('\u{0037}'),
+ c('\u{0038}'), c('\u{0039}'), c('\u{003A}'), c('\u{003B}'),          None, c('\u{2014}'),          None, c('\u{003F}'),
+          None,          None,          None,          None, c('\u{00D0}'),          None,          None, c('\u{00BC}'),
+ c('\u{00BD}'), c('\u{00BE}'), c('\u{215B}'), c('\u{215C}'), c('\u{215D}'), c('\u{215E}'), c('\u{2153}'), c('\u{2154}'),
+          None,          None,          None,          None,          None,          None, c('\u{FB00}'), c('\u{FB01}'),
+ c('\u{FB02}'), c('\u{FB03}'), c('\u{FB04}'), c('\u{208D}'),          None, c('\u{208E}'), c('\u{02C6}'), c('\u{002D}'),
+ c('\u{0060}'), c('\u{0041}'), c('\u{0042}'), c('\u{0043}'), c('\u{0044}'), c('\u{0045}'), c('\u{0046}'), c('\u{0047}'),
+ c('\u{0048}'), c('\u{0049}'), c('\u{004A}'), c('\u{004B}'), c('\u{004C}'), c('\u{004D}'), c('\u{004E}'), c('\u{004F}'),
+ c('\u{0050}'), c('\u{0051}'), c('\u{0052}'), c('\u{0053}'), c('\u{0054}'), c('\u{0055}'), c('\u{0056}'), c('\u{0057}'),
+ c('\u{0058}'), c('\u{0059}'), c('\u{005A}'), c('\u{20A1}'), c('\u{0031}'),          None, c('\u{02DC}'),          None,
+          None, c('\u{0061}'), c('\u{00A2}'),          None,          None,          None,          None, c('\u{00C1}'),
+ c('\u{00C0}'), c('\u{00C2}'), c('\u{00C4}'), c('\u{00C3}'), c('\u{00C5}'), c('\u{00C7}'), c('\u{00C9}'), c('\u{00C8}'),
+ c('\u{00CA}'), c('\u{00CB}'), c('\u{00CD}'), c('\u{00CC}'), c('\u{00CE}'), c('\u{00CF}'), c('\u{00D1}'), c('\u{00D3}'),
+ c('\u{00D2}'), c('\u{00D4}'), c('\u{00D6}'), c('\u{00D5}'), c('\u{00DA}'), c('\u{00D9}'), c('\u{00DB}'), c('\u{00DC}'),
+          None, c('\u{2078}'), c('\u{2084}'), c('\u{2083}'), c('\u{2086}'), c('\u{2088}'), c('\u{2087}'), c('\u{0160}'),
+          None, c('\u{00A2}'), c('\u{2082}'),          None, c('\u{00A8}'),          None, c('\u{02C7}'), c('\u{006F}'),
+ c('\u{2085}'),          None, c('\u{002C}'), c('\u{002E}'), c('\u{00DD}'),          None, c('\u{0024}'),          None,
+          None, c('\u{00DE}'),          None, c('\u{2089}'), c('\u{2080}'), c('\u{017D}'), c('\u{00C6}'), c('\u{00D8}'),
+ c('\u{00BF}'), c('\u{2081}'), c('\u{0141}'),          None,          None,          None,          None,          None,
+          None, c('\u{00B8}'),          None,          None,          None,          None,          None, c('\u{0152}'),
+ c('\u{2012}'), c('\u{002D}'),          None,          None,          None,          None, c('\u{00A1}'),          None,
+ c('\u{0178}'),          None, c('\u{00B9}'), c('\u{00B2}'), c('\u{00B3}'), c('\u{2074}'), c('\u{2075}'), c('\u{2076}'),
+ c('\u{2077}'), c('\u{2079}'), c('\u{2070}'),          None, c('\u{0065}'), c('\u{0072}'), c('\u{0074}'),          None,
+          None, c('\u{0069}'), c('\u{0073}'), c('\u{0064}'),          None,          None,          None,          None,
+          None, c('\u{006C}'), c('\u{02DB}'), c('\u{02D8}'), c('\u{00AF}'), c('\u{0062}'), c('\u{006E}'), c('\u{006D}'),
+ c('\u{002C}'), c('\u{002E}'), c('\u{02D9}'), c('\u{02DA}'),          None,          None,          None,          None,
+]
@@ -1,5 +1,12 @@
+use std::borrow::Cow;
 use std::num::NonZeroU32;
 
+mod cmap;
+mod glyphs;
+mod names;
+pub use self::cmap::{CMap, ToUnicodeMap};
+pub use self::glyphs::glyph_to_char;
+
 #[derive(Copy, Clone)]
 struct Entry(NonZeroU32);
 impl Entry {
@@ -22,6 +29,19 @@ const fn c(c: char) -> Option<Entry> {
 static STANDARD: [Option<Entry>; 256] = include!("stdenc.rs");
 static SYMBOL: [Option<Entry>; 256] = include!("symbol.rs");
 static ZDINGBAT: [Option<Entry>; 256] = include!("zdingbat.rs");
+static WINANSI: [Option<Entry>; 256] = include!("winansi.rs");
+static MACROMAN: [Option<Entry>; 256] = include!("macroman.rs");
+// small capitals and oldstyle figures are decoded as the plain characters
+static MACEXPERT: [Option<Entry>; 256] = include!("macexpert.rs");
+
+/// Every code as the Latin-1 character of the same value
+fn latin1() -> [Option<Entry>; 256] {
+    let mut map = [None; 256];
+    for b in 1 ..= 255u8 {
+        map[b as usize] = c(b as char);
+    }
+    map
+}
 
 #[derive(Object, Debug, Clone)]
 pub enum Encoding {
@@ -33,29 +53,100 @@ pub enum Encoding {
     None
 }
 
+/// Maps the codes of a simple font to glyph names and to the characters of those glyphs.
 #[derive(Clone)]
 pub struct Decoder {
-    map: Option<&'static [Option<Entry>; 256]>
+    /// characters by code, `None` if codes are taken as Latin-1
+    map: Option<Cow<'static, [Option<Entry>; 256]>>,
+    /// glyph names of the base encoding
+    names: Option<&'static [Option<&'static str>; 256]>,
+    /// glyph names that replace those of the base encoding, sorted by code
+    differences: Vec<(u8, String)>,
 }
 impl Decoder {
     pub fn new(encoding: &Encoding) -> Decoder {
-        let map = match encoding {
-            Encoding::SymbolEncoding => Some(&SYMBOL),
-            Encoding::StandardEncoding => Some(&STANDARD),
-            _ => None
+        let (map, names) = match encoding {
+            Encoding::StandardEncoding => (&STANDARD, &names::STANDARD),
+            Encoding::SymbolEncoding => (&SYMBOL, &names::SYMBOL),
+            Encoding::WinAnsiEncoding => (&WINANSI, &names::WIN_ANSI),
+            Encoding::MacRomanEncoding => (&MACROMAN, &names::MAC_ROMAN),
+            Encoding::MacExpertEncoding => (&MACEXPERT, &names::MAC_EXPERT),
+            Encoding::None => return Decoder {
+                map: None,
+                names: None,
+                differences: Vec::new(),
+            }
         };
-        Decoder { map }
+        Decoder { map: Some(Cow::Borrowed(map)), names: Some(names), differences: Vec::new() }
+    }
+    /// The built-in encoding of the ZapfDingbats font.
+    pub fn zapf_dingbats() -> Decoder {
+        Decoder { map: Some(Cow::Borrowed(&ZDINGBAT)), names: Some(&names::ZAPF_DINGBATS), differences: Vec::new() }
+    }
+    /// Maps each code to the character of its glyph, as listed by the encoding of a font program.
+    /// Codes with unknown glyph names have no character.
+    pub fn from_glyph_names<'a>(names: impl IntoIterator<Item=(u8, &'a str)>) -> Decoder {
+        Decoder { map: Some(Cow::Owned([None; 256])), names: None, differences: Vec::new() }
+            .with_differences(names)
+    }
+    /// Replaces the glyphs of some codes, like the `/Differences` of an encoding dictionary.
+    pub fn with_differences<'a>(mut self, differences: impl IntoIterator<Item=(u8, &'a str)>) -> Decoder {
+        for (code, name) in differences {
+            let map = self.map.get_or_insert_with(|| Cow::Owned(latin1()));
+            map.to_mut()[code as usize] = glyph_to_char(name).map(Entry::new);
+            match self.differences.binary_search_by_key(&code, |&(code, _)| code) {
+                Ok(i) => self.differences[i].1 = name.into(),
+                Err(i) => self.differences.insert(i, (code, name.into()))
+            }
+        }
+        self
+    }
+    /// The name of the glyph of `code`, if the encoding names it.
+    pub fn glyph_name(&self, code: u8) -> Option<&str> {
+        match self.differences.binary_search_by_key(&code, |&(code, _)| code) {
+            Ok(i) => Some(self.differences[i].1.as_str()),
+            Err(_) => self.names?[code as usize]
+        }
     }
     pub fn decode_byte(&self, b: u8) -> Option<char> {
         match self.map {
-            Some(map) => map[b as usize].map(|e| e.as_char()),
+            Some(ref map) => map[b as usize].map(|e| e.as_char()),
             None => Some(b as char)
         }
     }
     pub fn decode_bytes(&self, data: &[u8]) -> String {
         match self.map {
-            Some(map) => data.iter().flat_map(|&b| map[b as usize].map(|e| e.as_char())).collect(),
+            Some(ref map) => data.iter().flat_map(|&b| map[b as usize].map(|e| e.as_char())).collect(),
             None => data.iter().map(|&b| b as char).collect()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_differences() {
+        let win = Decoder::new(&Encoding::WinAnsiEncoding);
+        assert_eq!(win.decode_bytes(b"\x80 caf\xe9"), "\u{20AC} caf\u{E9}");
+        assert_eq!(win.glyph_name(0xe9), Some("eacute"));
+        let mac = Decoder::new(&Encoding::MacRomanEncoding);
+        assert_eq!(mac.decode_bytes(b"\x8e\xd0\xde"), "\u{E9}\u{2013}\u{FB01}");
+        assert_eq!(Decoder::zapf_dingbats().glyph_name(0x21), Some("a1"));
+        let expert = Decoder::new(&Encoding::MacExpertEncoding);
+        assert_eq!(expert.decode_bytes(b"\x56\x61\x62\x31\xe2"), "\u{FB00}AB1\u{2070}");
+        assert_eq!(expert.glyph_name(0x61), Some("Asmall"));
+
+        let decoder = Decoder::new(&Encoding::StandardEncoding)
+            .with_differences(vec![(0x41, "Adieresis"), (0x80, "uni20AC"), (0x81, "a10")]);
+        assert_eq!(decoder.decode_bytes(b"AB\x80\x81"), "\u{C4}B\u{20AC}\u{2721}");
+        assert_eq!(decoder.glyph_name(0x41), Some("Adieresis"));
+        assert_eq!(decoder.glyph_name(0x42), Some("B"));
+        assert_eq!(decoder.glyph_name(0x82), None);
+
+        // codes without a base encoding are taken as they are
+        let decoder = Decoder::new(&Encoding::None).with_differences(vec![(0x41, "alpha")]);
+        assert_eq!(decoder.decode_bytes(b"AB"), "\u{3B1}B");
+    }
+}
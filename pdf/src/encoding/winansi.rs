@@ -0,0 +1,34 @@
+[
+          None,          None,          None,          None,          None,          None,          None,          None,
+          None,          None,          None,          None,          None,          None,          None,          None,
+          None,          None,          None,          None,          None,          None,          None,          None,
+          None,          None,          None,          None,          None,          None,          None,          None,
+ c('\u{0020}'), c('\u{0021}'), c('\u{0022}'), c('\u{0023}'), c('\u{0024}'), c('\u{0025}'), c('\u{0026}'), c('\u{0027}'),
+ c('\u{0028}'), c('\u{0029}'), c('\u{002A}'), c('\u{002B}'), c('\u{002C}'), c('\u{002D}'), c('\u{002E}'), c('\u{002F}'),
+ c('\u{0030}'), c('\u{0031}'), c('\u{0032}'), c('\u{0033}'), c('\u{0034}'), c('\u{0035}'), c('\u{0036}'), c('\u{0037}'),
+ c('\u{0038}'), c('\u{0039}'), c('\u{003A}'), c('\u{003B}'), c('\u{003C}'), c('\u{003D}'), c('\u{003E}'), c('\u{003F}'),
+ c('\u{0040}'), c('\u{0041}'), c('\u{0042}'), c('\u{0043}'), c('\u{0044}'), c('\u{0045}'), c('\u{0046}'), c('\u{0047}'),
+ c('\u{0048}'), c('\u{0049}'), c('\u{004A}'), c('\u{004B}'), c('\u{004C}'), c('\u{004D}'), c('\u{004E}'), c('\u{004F}'),
+ c('\u{0050}'), c('\u{0051}'), c('\u{0052}'), c('\u{0053}'), c('\u{0054}'), c('\u{0055}'), c('\u{0056}'), c('\u{0057}'),
+ c('\u{0058}'), c('\u{0059}'), c('\u{005A}'), c('\u{005B}'), c('\u{005C}'), c('\u{005D}'), c('\u{005E}'), c('\u{005F}'),
+ c('\u{0060}'), c('\u{0061}'), c('\u{0062}'), c('\u{0063}'), c('\u{0064}'), c('\u{0065}'), c('\u{0066}'), c('\u{0067}'),
+ c('\u{0068}'), c('\u{0069}'), c('\u{006A}'), c('\u{006B}'), c('\u{006C}'), c('\u{006D}'), c('\u{006E}'), c('\u{006F}'),
+ c('\u{0070}'), c('\u{0071}'), c('\u{0072}'), c('\u{0073}'), c('\u{0074}'), c('\u{0075}'), c('\u{0076}'), c('\u{0077}'),
+ c('\u{0078}'), c('\u{0079}'), c('\u{007A}'), c('\u{007B}'), c('\u{007C}'), c('\u{007D}'), c('\u{007E}'),          None,
+ c('\u{20AC}'),          None, c('\u{201A}'), c('\u{0192}'), c('\u{201E}'), c('\u{2026}'), c('\u{2020}'), c('\u{2021}'),
+ c('\u{02C6}'), c('\u{2030}'), c('\u{0160}'), c('\u{2039}'), c('\u{0152}'),          None, c('\u{017D}'),          None,
+          None, c('\u{2018}'), c('\u{2019}'), c('\u{201C}'), c('\u{201D}'), c('\u{2022}'), c('\u{2013}'), c('\u{2014}'),
+ c('\u{02DC}'), c('\u{2122}'), c('\u{0161}'), c('\u{203A}'), c('\u{0153}'),          None, c('\u{017E}'), c('\u{0178}'),
+ c('\u{00A0}'), c('\u{00A1}'), c('\u{00A2}'), c('\u{00A3}'), c('\u{00A4}'), c('\u{00A5}'), c('\u{00A6}'), c('\u{00A7}'),
+ c('\u{00A8}'), c('\u{00A9}'), c('\u{00AA}'), c('\u{00AB}'), c('\u{00AC}'), c('\u{00AD}'), c('\u{00AE}'), c('\u{00AF}'),
+ c('\u{00B0}'), c('\u{00B1}'), c('\u{00B2}'), c('\u{00B3}'), c('\u{00B4}'), c('\u{00B5}'), c('\u{00B6}'), c('\u{00B7}'),
+ c('\u{00B8}'), c('\u{00B9}'), c('\u{00BA}'), c('\u{00BB}'), c('\u{00BC}'), c('\u{00BD}'), c('\u{00BE}'), c('\u{00BF}'),
+ c('\u{00C0}'), c('\u{00C1}'), c('\u{00C2}'), c('\u{00C3}'), c('\u{00C4}'), c('\u{00C5}'), c('\u{00C6}'), c('\u{00C7}'),
+ c('\u{00C8}'), c('\u{00C9}'), c('\u{00CA}'), c('\u{00CB}'), c('\u{00CC}'), c('\u{00CD}'), c('\u{00CE}'), c('\u{00CF}'),
+ c('\u{00D0}'), c('\u{00D1}'), c('\u{00D2}'), c('\u{00D3}'), c('\u{00D4}'), c('\u{00D5}'), c('\u{00D6}'), c('\u{00D7}'),
+ c('\u{00D8}'), c('\u{00D9}'), c('\u{00DA}'), c('\u{00DB}'), c('\u{00DC}'), c('\u{00DD}'), c('\u{00DE}'), c('\u{00DF}'),
+ c('\u{00E0}'), c('\u{00E1}'), c('\u{00E2}'), c('\u{00E3}'), c('\u{00E4}'), c('\u{00E5}'), c('\u{00E6}'), c('\u{00E7}'),
+ c('\u{00E8}'), c('\u{00E9}'), c('\u{00EA}'), c('\u{00EB}'), c('\u{00EC}'), c('\u{00ED}'), c('\u{00EE}'), c('\u{00EF}'),
+ c('\u{00F0}'), c('\u{00F1}'), c('\u{00F2}'), c('\u{00F3}'), c('\u{00F4}'), c('\u{00F5}'), c('\u{00F6}'), c('\u{00F7}'),
+ c('\u{00F8}'), c('\u{00F9}'), c('\u{00FA}'), c('\u{00FB}'), c('\u{00FC}'), c('\u{00FD}'), c('\u{00FE}'), c('\u{00FF}'),
+]
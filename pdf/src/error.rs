@@ -90,6 +90,9 @@ pub enum PdfError {
     */
     #[snafu(display("Object stream index out of bounds ({}/{}).", index, max))]
     ObjStmOutOfBounds {index: usize, max: usize},
+
+    #[snafu(display("Invalid object stream: {}", msg))]
+    InvalidObjStm {msg: String},
     
     #[snafu(display("Page out of bounds ({}/{}).", page_nr, max))]
     PageOutOfBounds {page_nr: u32, max: u32},
@@ -116,6 +119,38 @@ impl PdfError {
     pub fn trace(&self) {
         trace(self, 0);
     }
+    /// What went wrong, roughly. Meant for bindings that can only pass error codes.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            PdfError::Io { .. } => ErrorKind::Io,
+            PdfError::EOF | PdfError::Parse { .. } | PdfError::Utf8 { .. } | PdfError::UnexpectedLexeme { .. } |
+            PdfError::UnknownType { .. } | PdfError::XRefStreamType { .. } | PdfError::ContentReadPastBoundary |
+            PdfError::HexDecode { .. } | PdfError::Ascii85TailError | PdfError::IncorrectPredictorType { .. } => ErrorKind::Syntax,
+            PdfError::FromPrimitive { .. } | PdfError::MissingEntry { .. } | PdfError::KeyValueMismatch { .. } |
+            PdfError::WrongDictionaryType { .. } | PdfError::FreeObject { .. } | PdfError::NullRef { .. } |
+            PdfError::UnexpectedPrimitive { .. } | PdfError::ObjStmOutOfBounds { .. } | PdfError::InvalidObjStm { .. } |
+            PdfError::PageNotFound { .. } | PdfError::UnspecifiedXRefEntry { .. } | PdfError::Reference |
+            PdfError::UnknownVariant { .. } | PdfError::NotFound { .. } => ErrorKind::Structure,
+            PdfError::InvalidPassword => ErrorKind::Password,
+            PdfError::PageOutOfBounds { .. } => ErrorKind::PageOutOfBounds,
+            PdfError::Other { .. } | PdfError::NoneError => ErrorKind::Other,
+        }
+    }
+}
+
+/// Classes of `PdfError`, see `PdfError::kind`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Reading the file failed
+    Io,
+    /// The file is not valid PDF syntax
+    Syntax,
+    /// Objects are missing or not what they should be
+    Structure,
+    /// The password is wrong
+    Password,
+    PageOutOfBounds,
+    Other,
 }
 fn trace(err: &dyn Error, depth: usize) {
     println!("{}: {}", depth, err);
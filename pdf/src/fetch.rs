@@ -0,0 +1,301 @@
+//! Reading documents whose data is fetched asynchronously and on demand,
+//! for example with HTTP range requests or from an object store.
+//!
+//! The parser itself is synchronous. It works on a `RangeCache` that only holds the parts of
+//! the file fetched so far and notes which range was missing when a read fails. `AsyncFile`
+//! then fetches that range from the `AsyncBackend` and tries again, until the object
+//! can be read without missing data.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::Primitive;
+use crate::backend::{Backend, IndexRange};
+use crate::file::File;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// Fetches byte ranges of a file.
+pub trait AsyncBackend {
+    /// Size of the file in bytes
+    fn len(&self) -> usize;
+    /// The bytes in `range`, which is within the file.
+    fn fetch(&self, range: Range<usize>) -> BoxFuture<'_, Result<Vec<u8>>>;
+}
+
+/// The asynchronous counterpart of `Resolve`.
+pub trait AsyncResolve {
+    fn resolve_async(&self, r: PlainRef) -> BoxFuture<'_, Result<Primitive>>;
+    fn get_async<T: Object>(&self, r: Ref<T>) -> BoxFuture<'_, Result<Arc<T>>>;
+}
+
+/// Fetched data is kept in chunks of this size.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+struct CacheData {
+    len: usize,
+    /// by chunk index
+    chunks: RefCell<BTreeMap<usize, Box<[u8]>>>,
+    /// copies of runs of chunks for reads across chunks, by first and last chunk index
+    spans: RefCell<BTreeMap<(usize, usize), Box<[u8]>>>,
+    /// the size of all spans, which is limited to twice the file size
+    span_bytes: Cell<usize>,
+    /// the first range that was read but has not been fetched yet
+    missing: RefCell<Option<Range<usize>>>,
+}
+
+/// The parts of a file that were fetched so far, as a `Backend`.
+/// Clones share the data.
+#[derive(Clone)]
+pub struct RangeCache {
+    data: Rc<CacheData>,
+}
+impl RangeCache {
+    pub fn new(len: usize) -> RangeCache {
+        RangeCache {
+            data: Rc::new(CacheData {
+                len,
+                chunks: RefCell::new(BTreeMap::new()),
+                spans: RefCell::new(BTreeMap::new()),
+                span_bytes: Cell::new(0),
+                missing: RefCell::new(None),
+            })
+        }
+    }
+    /// Adds `data`, which starts at `start` and covers whole chunks or ends at the end of the file.
+    fn insert(&self, start: usize, data: &[u8]) {
+        let mut chunks = self.data.chunks.borrow_mut();
+        for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            chunks.entry(start / CHUNK_SIZE + i).or_insert_with(|| chunk.into());
+        }
+    }
+    /// The chunks of `range` that are not there yet, as a byte range.
+    fn missing_chunks(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let chunks = self.data.chunks.borrow();
+        let last = (range.end + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let first = (range.start / CHUNK_SIZE .. last).find(|i| !chunks.contains_key(i))?;
+        let end = (first .. last).rev().find(|i| !chunks.contains_key(i)).unwrap_or(first) + 1;
+        Some(first * CHUNK_SIZE .. self.data.len.min(end * CHUNK_SIZE))
+    }
+    /// Whether a read failed because of data that has not been fetched.
+    pub(crate) fn missed(&self) -> bool {
+        self.data.missing.borrow().is_some()
+    }
+    fn take_missing(&self) -> Option<Range<usize>> {
+        self.data.missing.borrow_mut().take()
+    }
+}
+impl Backend for RangeCache {
+    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
+        let r = range.to_range(self.data.len)?;
+        if r.start == r.end {
+            return Ok(&[]);
+        }
+        if let Some(missing) = self.missing_chunks(r.clone()) {
+            let mut first = self.data.missing.borrow_mut();
+            if first.is_none() {
+                *first = Some(missing);
+            }
+            bail!("bytes {} to {} have not been fetched", r.start, r.end);
+        }
+        let chunk = r.start / CHUNK_SIZE;
+        let chunk_start = chunk * CHUNK_SIZE;
+        let (ptr, len) = if r.end <= chunk_start + CHUNK_SIZE {
+            let chunks = self.data.chunks.borrow();
+            let data = &chunks[&chunk][r.start - chunk_start .. r.end - chunk_start];
+            (data.as_ptr(), data.len())
+        } else {
+            let last = (r.end - 1) / CHUNK_SIZE;
+            let mut spans = self.data.spans.borrow_mut();
+            let key = match spans.keys().find(|&&(first, end)| first <= chunk && last <= end) {
+                Some(&key) => key,
+                None => {
+                    let size = self.data.len.min((last + 1) * CHUNK_SIZE) - chunk_start;
+                    let span_bytes = self.data.span_bytes.get() + size;
+                    if span_bytes > 2 * self.data.len {
+                        bail!("too many reads across chunks to read bytes {} to {}", r.start, r.end);
+                    }
+                    let chunks = self.data.chunks.borrow();
+                    let mut data = Vec::with_capacity(size);
+                    for (_, chunk) in chunks.range(chunk ..= last) {
+                        data.extend_from_slice(chunk);
+                    }
+                    self.data.span_bytes.set(span_bytes);
+                    spans.insert((chunk, last), data.into_boxed_slice());
+                    (chunk, last)
+                }
+            };
+            let span_start = key.0 * CHUNK_SIZE;
+            let data = &spans[&key][r.start - span_start .. r.end - span_start];
+            (data.as_ptr(), data.len())
+        };
+        // Chunks and spans are never removed, and moving the boxes doesn't move their data.
+        Ok(unsafe { std::slice::from_raw_parts(ptr, len) })
+    }
+    fn write<T: IndexRange>(&mut self, _range: T) -> Result<&mut [u8]> {
+        bail!("RangeCache is read-only")
+    }
+    fn len(&self) -> usize {
+        self.data.len
+    }
+}
+
+/// A document read through an `AsyncBackend`. See the module documentation.
+pub struct AsyncFile<A> {
+    backend: A,
+    cache: RangeCache,
+    file: File<RangeCache>,
+}
+
+impl File<RangeCache> {
+    /// Opens the document of `backend`, fetching the data needed for the trailer, the catalog
+    /// and the page tree.
+    pub async fn open_async<A: AsyncBackend>(backend: A) -> Result<AsyncFile<A>> {
+        let cache = RangeCache::new(backend.len());
+        AsyncFile::open(backend, cache).await
+    }
+}
+
+impl<A: AsyncBackend> AsyncFile<A> {
+    /// Opens the document, keeping what `cache` already holds.
+    pub(crate) async fn open(backend: A, cache: RangeCache) -> Result<AsyncFile<A>> {
+        // the trailer is at the end
+        let len = backend.len();
+        fetch(&backend, &cache, len.saturating_sub(1024) .. len).await?;
+        let file = retry(&backend, &cache, || File::from_backend(cache.clone())).await?;
+        Ok(AsyncFile { backend, cache, file })
+    }
+    /// The document, for everything that has been fetched already.
+    pub fn file(&self) -> &File<RangeCache> {
+        &self.file
+    }
+    /// Runs `f` until it doesn't need more data.
+    pub async fn load<T>(&self, f: impl Fn(&File<RangeCache>) -> Result<T>) -> Result<T> {
+        retry(&self.backend, &self.cache, || {
+            let result = f(&self.file);
+            if self.cache.missed() {
+                // objects may have been read from incomplete data
+                self.file.clear_cache();
+            }
+            result
+        }).await
+    }
+    pub async fn get_num_pages(&self) -> Result<u32> {
+        self.load(|file| file.get_num_pages()).await
+    }
+    /// Page `n` (counting from 0), with its content stream.
+    pub async fn get_page(&self, n: u32) -> Result<PageRc> {
+        self.load(|file| file.get_page(n)).await
+    }
+}
+
+impl<A: AsyncBackend> AsyncResolve for AsyncFile<A> {
+    fn resolve_async(&self, r: PlainRef) -> BoxFuture<'_, Result<Primitive>> {
+        Box::pin(self.load(move |file| file.resolve(r)))
+    }
+    fn get_async<T: Object>(&self, r: Ref<T>) -> BoxFuture<'_, Result<Arc<T>>> {
+        Box::pin(self.load(move |file| file.get(r)))
+    }
+}
+
+pub(crate) async fn fetch<A: AsyncBackend>(backend: &A, cache: &RangeCache, range: Range<usize>) -> Result<()> {
+    let range = match cache.missing_chunks(range) {
+        Some(range) => range,
+        None => return Ok(())
+    };
+    let data = backend.fetch(range.clone()).await?;
+    if data.len() != range.end - range.start {
+        bail!("fetched {} bytes instead of {}", data.len(), range.end - range.start);
+    }
+    cache.insert(range.start, &data);
+    Ok(())
+}
+
+/// Runs `f` and fetches what it missed, until nothing is missing.
+pub(crate) async fn retry<A: AsyncBackend, T>(backend: &A, cache: &RangeCache, f: impl Fn() -> Result<T>) -> Result<T> {
+    loop {
+        cache.take_missing();
+        let result = f();
+        match cache.take_missing() {
+            Some(range) => fetch(backend, cache, range).await?,
+            None => return result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    struct Memory {
+        data: Vec<u8>,
+        fetched: Cell<usize>,
+    }
+    impl AsyncBackend for Memory {
+        fn len(&self) -> usize {
+            self.data.len()
+        }
+        fn fetch(&self, range: Range<usize>) -> BoxFuture<'_, Result<Vec<u8>>> {
+            self.fetched.set(self.fetched.get() + range.end - range.start);
+            let data = self.data[range].to_vec();
+            Box::pin(async move { Ok(data) })
+        }
+    }
+
+    /// Polls `future`, which never has to wait.
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        fn raw() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker { raw() }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw()) };
+        let mut future = Box::pin(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(t) => t,
+            Poll::Pending => panic!("pending")
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let data: Vec<u8> = (0 .. 3 * CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+        let cache = RangeCache::new(data.len());
+        cache.insert(0, &data);
+        // reads across the same chunks share a copy of them
+        for i in 1 .. 100 {
+            let r = CHUNK_SIZE - i .. CHUNK_SIZE + i;
+            assert_eq!(cache.read(r.clone()).unwrap(), &data[r]);
+        }
+        assert_eq!(cache.data.spans.borrow().len(), 1);
+        assert_eq!(cache.read(CHUNK_SIZE + 10 ..).unwrap(), &data[CHUNK_SIZE + 10 ..]);
+        assert_eq!(cache.read(2 * CHUNK_SIZE - 1 .. 2 * CHUNK_SIZE + 1).unwrap(), &data[2 * CHUNK_SIZE - 1 .. 2 * CHUNK_SIZE + 1]);
+        assert_eq!(cache.data.span_bytes.get(), 4 * CHUNK_SIZE);
+        // their size is limited
+        assert!(cache.read(10 ..).is_err());
+        assert_eq!(cache.read(10 .. 2 * CHUNK_SIZE).unwrap(), &data[10 .. 2 * CHUNK_SIZE]);
+    }
+
+    #[test]
+    fn open_async() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../files/xelatex.pdf")).unwrap();
+        let original = File::from_data(data.clone()).unwrap();
+        let backend = Memory { data, fetched: Cell::new(0) };
+        let file = ready(File::open_async(backend)).unwrap();
+        assert_eq!(ready(file.get_num_pages()).unwrap(), original.get_num_pages().unwrap());
+        let page = ready(file.get_page(0)).unwrap();
+        assert_eq!(page.contents.as_ref().unwrap().operations.len(),
+            original.get_page(0).unwrap().contents.as_ref().unwrap().operations.len());
+        assert!(file.backend.fetched.get() <= file.backend.data.len());
+    }
+}
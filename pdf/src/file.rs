@@ -1,22 +1,37 @@
 //! This is kind of the entry-point of the type-safe PDF functionality.
 use std;
-use std::io::Read;
+use std::io::{self, Read};
 use std::{str};
 use std::marker::PhantomData;
-use std::collections::HashMap;
-use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use once_cell::sync::OnceCell;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::error::*;
 use crate::object::*;
-use crate::primitive::{Primitive, Dictionary, PdfString};
-use crate::backend::Backend;
+use crate::primitive::{Primitive, Dictionary, PdfString, PdfStream, StreamData, format_date};
+use crate::backend::{Backend, StreamSource};
 use crate::any::Any;
 use crate::parser::Lexer;
-use crate::parser::{parse_indirect_object, parse};
-use crate::xref::{XRef, XRefTable};
+use crate::parser::{parse_indirect_object, parse, read_xref_and_trailer_at, ParseOptions};
+use crate::xref::{XRef, XRefTable, XRefLocation};
 use crate::crypt::Decoder;
 use crate::crypt::CryptDict;
+use crate::metadata::{self, DocInfo, Metadata, sync_xmp, sync_info};
+use crate::signature::SignaturePolicy;
+use crate::content::Content;
+
+mod cache;
+#[cfg(feature = "write")]
+mod write;
+#[cfg(feature = "write")]
+mod linearize;
+#[cfg(feature = "write")]
+pub use self::write::WriteOptions;
+pub use self::cache::CacheStats;
+use self::cache::{ObjectCache, estimated_size};
 
 pub struct PromisedRef<T> {
     inner:      PlainRef,
@@ -35,7 +50,7 @@ impl<'a, T> Into<Ref<T>> for &'a PromisedRef<T> {
 
 pub struct PagesIterator<'a, B: Backend> {
     file: &'a File<B>,
-    stack: Vec<(Rc<PagesNode>, usize)>, // points to nodes that have not been processed yet,
+    stack: Vec<(Arc<PagesNode>, usize)>, // points to nodes that have not been processed yet,
     error: bool
 }
 impl<'a, B: Backend> Iterator for PagesIterator<'a, B> {
@@ -69,63 +84,219 @@ impl<'a, B: Backend> Iterator for PagesIterator<'a, B> {
     }
 }
 
-struct Storage<B: Backend> {
+/// Iterates over the trailers of all revisions, from the newest to the oldest.
+/// Yields the byte offset of the xref section and the trailer dictionary.
+pub struct Trailers<'a, B: Backend> {
+    backend: &'a B,
+    next: Option<usize>,
+    visited: Vec<usize>,
+}
+impl<'a, B: Backend> Iterator for Trailers<'a, B> {
+    type Item = Result<(usize, Dictionary)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.next.take()?;
+        // a /Prev pointing back into the chain would loop forever
+        if self.visited.contains(&pos) {
+            return Some(Err(PdfError::Other { msg: format!("xref /Prev loop at {}", pos) }));
+        }
+        self.visited.push(pos);
+
+        let result = self.backend.read(pos..).and_then(|data| {
+            let mut lexer = Lexer::new(data);
+            let (_, trailer) = read_xref_and_trailer_at(&mut lexer, &NoResolve)?;
+            Ok(trailer)
+        });
+        match result {
+            Ok(trailer) => {
+                self.next = match trailer.get("Prev") {
+                    Some(p) => match p.as_integer() {
+                        Ok(n) => Some(n as usize),
+                        Err(e) => return Some(Err(e))
+                    },
+                    None => None
+                };
+                Some(Ok((pos, trailer)))
+            }
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+pub(crate) struct Storage<B: Backend> {
     // objects identical to those in the backend
-    cache: RefCell<HashMap<PlainRef, Any>>,
+    cache: ObjectCache,
     
-    // objects that differ from the backend
-    changes:    HashMap<ObjNr, Primitive>,
+    // objects that differ from the backend, ordered so that writing is reproducible
+    changes:    BTreeMap<ObjNr, Primitive>,
     
     refs:       XRefTable,
     
     decoder:    Option<Decoder>,
+
+    // the encryption dictionary, whose strings are not encrypted
+    encrypt_id: Option<ObjNr>,
     
-    backend: B
+    // offsets of all objects in the file, sorted
+    offsets:    OnceCell<Vec<usize>>,
+
+    options:    ParseOptions,
+
+    // what was repaired while parsing
+    warnings:   Mutex<Vec<String>>,
+    
+    backend: Arc<B>,
+
+    // the backend, if streams can refer to it
+    source: Option<Arc<dyn StreamSource>>,
 }
 impl<B: Backend> Storage<B> {
-    fn new(backend: B, refs: XRefTable) -> Storage<B> {
+    pub(crate) fn new(backend: B, refs: XRefTable, options: ParseOptions) -> Storage<B> {
+        let backend = Arc::new(backend);
         Storage {
+            source: B::stream_source(backend.clone()),
             backend,
             refs,
-            cache: RefCell::new(HashMap::new()),
-            changes: HashMap::new(),
-            decoder: None
+            cache: ObjectCache::new(),
+            changes: BTreeMap::new(),
+            decoder: None,
+            encrypt_id: None,
+            offsets: OnceCell::new(),
+            options,
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+    /// Forgets the objects read so far.
+    pub(crate) fn clear_cache(&self) {
+        self.cache.clear();
+    }
+    /// Where the object at `pos` ends at the latest: where the next object in the xref table begins.
+    fn object_end(&self, pos: usize) -> Option<usize> {
+        let offsets = self.offsets.get_or_init(|| {
+            let mut offsets: Vec<usize> = self.refs.iter_entries().filter_map(|xref| match xref {
+                XRef::Raw {pos, ..} => Some(pos),
+                _ => None
+            }).collect();
+            offsets.sort();
+            offsets
+        });
+        let i = match offsets.binary_search(&pos) {
+            Ok(i) => i + 1,
+            Err(i) => i
+        };
+        offsets.get(i).cloned()
+    }
+    /// Parses the object at `pos`. Streams are looked for within the object's span first,
+    /// so that a wrong `/Length` can't make them swallow the objects after them.
+    fn parse_object_at(&self, pos: usize) -> Result<(PlainRef, Primitive, usize)> {
+        // only read what is needed, for backends that read on demand
+        if let Some(end) = self.object_end(pos).filter(|&end| end <= self.backend.len()) {
+            let mut lexer = Lexer::new(self.backend.read(pos .. end)?);
+            if let Ok((r, p)) = parse_indirect_object(&mut lexer, self) {
+                return Ok((r, p, lexer.get_pos()));
+            }
+        }
+        let mut lexer = Lexer::new(self.backend.read(pos..)?);
+        let (r, p) = parse_indirect_object(&mut lexer, self)?;
+        Ok((r, p, lexer.get_pos()))
+    }
+}
+impl<B: Backend> Storage<B> {
+    /// Finds the object `id`, which the xref table places at `index` of the object stream `stream_id`.
+    /// If it isn't there, it is searched in the stream and the streams it `/Extends`, and if none
+    /// of them lists it, the xref table is trusted. Returns the stream it was found in, and the index in it.
+    /// The streams are decoded once and kept in the object cache.
+    fn object_stream(&self, id: ObjNr, stream_id: ObjNr, index: usize) -> Result<(ObjNr, Arc<ObjectStream>, usize)> {
+        let first = self.get(Ref::<ObjectStream>::new(PlainRef {id: stream_id, gen: 0 /* TODO what gen nr? */}))?;
+        if first.has_object_at(index, id) {
+            return Ok((stream_id, first, index));
+        }
+        let mut obj_stream = first.clone();
+        let mut current = stream_id;
+        // guards against cycles in /Extends
+        for _ in 0 .. 64 {
+            if let Some(i) = obj_stream.find(id) {
+                return Ok((current, obj_stream, i));
+            }
+            match obj_stream.extends() {
+                Some(r) => {
+                    obj_stream = self.get(Ref::<ObjectStream>::new(r))?;
+                    current = r.id;
+                }
+                None => break
+            }
+        }
+        if index < first.n_objects() {
+            self.warn(format!("object {} is not listed in object stream {}, using index {}", id, stream_id, index));
+            return Ok((stream_id, first, index));
+        }
+        bail!("object {} not found in object stream {} or the streams it extends", id, stream_id)
+    }
+    fn locate(&self, r: PlainRef) -> Result<(XRefLocation, usize)> {
+        if let Some(p) = self.changes.get(&r.id) {
+            let mut data = Vec::new();
+            p.serialize(&mut data)?;
+            return Ok((XRefLocation::Modified, data.len()));
+        }
+        match self.refs.get(r.id)? {
+            XRef::Raw {pos, ..} => {
+                let (_, _, size) = self.parse_object_at(pos)?;
+                Ok((XRefLocation::Offset(pos as u64), size))
+            }
+            XRef::Stream {stream_id, index} => {
+                let (stream_id, obj_stream, index) = self.object_stream(r.id, stream_id, index)?;
+                let size = obj_stream.get_object_slice(index)?.len();
+                Ok((XRefLocation::InStream { stream: stream_id, index }, size))
+            }
+            XRef::Free {..} => Ok((XRefLocation::Free, 0)),
+            XRef::Promised => Ok((XRefLocation::Modified, 0)),
+            XRef::Invalid => err!(PdfError::NullRef {obj_nr: r.id}),
         }
     }
 }
+impl<B: Backend> Storage<B> {
+    /// The object `r` as it is in the backend, ignoring changes.
+    fn resolve_backend(&self, r: PlainRef) -> Result<Primitive> {
+        match self.refs.get(r.id)? {
+            XRef::Raw {pos, gen_nr} => {
+                let (_, mut p, _) = self.parse_object_at(pos)?;
+                // objects in object streams are not encrypted on their own
+                match self.decoder {
+                    Some(ref decoder) if self.encrypt_id != Some(r.id) => decoder.decrypt_primitive(r.id, gen_nr, &mut p)?,
+                    _ => {}
+                }
+                Ok(p)
+            }
+            XRef::Stream {stream_id, index} => {
+                let (_, obj_stream, index) = self.object_stream(r.id, stream_id, index)?;
+                let slice = obj_stream.get_object_slice(index)?;
+                parse(slice, self)
+            }
+            XRef::Free {..} => err!(PdfError::FreeObject {obj_nr: r.id}),
+            XRef::Promised => err!(PdfError::NullRef {obj_nr: r.id}),
+            XRef::Invalid => err!(PdfError::NullRef {obj_nr: r.id}),
+        }
+    }
+    /// The object `id` before it was changed, `None` if it was added.
+    pub(crate) fn original(&self, id: ObjNr) -> Result<Option<Primitive>> {
+        let gen = match self.refs.get(id)? {
+            XRef::Raw { gen_nr, .. } => gen_nr,
+            XRef::Stream { .. } => 0,
+            _ => return Ok(None)
+        };
+        self.resolve_backend(PlainRef { id, gen }).map(Some)
+    }
+}
 impl<B: Backend> Resolve for Storage<B> {
     fn resolve(&self, r: PlainRef) -> Result<Primitive> {
         match self.changes.get(&r.id) {
             Some(ref p) => Ok((*p).clone()),
-            None => match self.refs.get(r.id)? {
-                XRef::Raw {pos, gen_nr} => {
-                    let mut lexer = Lexer::new(self.backend.read(pos..)?);
-                    let mut p = parse_indirect_object(&mut lexer, self)?.1;
-                    if let Some(ref decoder) = self.decoder {
-                        match p {
-                            Primitive::Stream(ref mut stream) => decoder.decrypt(r.id, gen_nr, &mut stream.data),
-                            Primitive::String(ref mut s) => decoder.decrypt(r.id, gen_nr, &mut s.data),
-                            _ => {}
-                        }
-                    }
-                    Ok(p)
-                }
-                XRef::Stream {stream_id, index} => {
-                    let obj_stream = self.resolve(PlainRef {id: stream_id, gen: 0 /* TODO what gen nr? */})?;
-                    let obj_stream = ObjectStream::from_primitive(obj_stream, self)?;
-                    let slice = obj_stream.get_object_slice(index)?;
-                    parse(slice, self)
-                }
-                XRef::Free {..} => err!(PdfError::FreeObject {obj_nr: r.id}),
-                XRef::Promised => unimplemented!(),
-                XRef::Invalid => err!(PdfError::NullRef {obj_nr: r.id}),
-            }
+            None => self.resolve_backend(r)
         }
     }
-    fn get<T: Object>(&self, r: Ref<T>) -> Result<Rc<T>> {
+    fn get<T: Object>(&self, r: Ref<T>) -> Result<Arc<T>> {
         let key = r.get_inner();
         
-        if let Some(any) = self.cache.borrow().get(&key) {
+        if let Some(any) = self.cache.get(key) {
             match any.clone().downcast() {
                 Some(rc) => return Ok(rc),
                 None => bail!("expected {}, found {}", unsafe { std::intrinsics::type_name::<T>() }, any.type_name())
@@ -133,46 +304,135 @@ impl<B: Backend> Resolve for Storage<B> {
         }
         
         let primitive = self.resolve(r.get_inner())?;
+        let size = estimated_size(&primitive);
         let obj = T::from_primitive(primitive, self)?;
-        let rc = Rc::new(obj);
-        self.cache.borrow_mut().insert(key, Any::new(rc.clone()));
+        let rc = Arc::new(obj);
+        self.cache.insert(key, Any::new(rc.clone()), size);
         
         Ok(rc)
     }
+    fn options(&self) -> ParseOptions {
+        self.options
+    }
+    fn warn(&self, msg: String) {
+        warn!("{}", msg);
+        self.warnings.lock().unwrap().push(msg);
+    }
+    fn stream_data(&self, data: &[u8]) -> StreamData {
+        if let Some(ref source) = self.source {
+            let all = source.bytes();
+            let start = (data.as_ptr() as usize).wrapping_sub(all.as_ptr() as usize);
+            if start <= all.len() && data.len() <= all.len() - start {
+                return StreamData::shared(source.clone(), start .. start + data.len());
+            }
+        }
+        data.to_vec().into()
+    }
 }
 
+/// A document. Objects are read on demand and cached, and shared as `Arc`s, so a `File`
+/// can be used from several threads when its backend can.
 pub struct File<B: Backend> {
     storage:    Storage<B>,
     trailer:    Trailer,
+    // the trailer as it will be written
+    trailer_dict: Dictionary,
+    signature_policy: SignaturePolicy,
+    // the revision the catalog was read from, counting back from the newest
+    root_revision: usize,
 }
 impl<B: Backend> Resolve for File<B> {
     fn resolve(&self, r: PlainRef) -> Result<Primitive> {
         self.storage.resolve(r)
     }
-    fn get<T: Object>(&self, r: Ref<T>) -> Result<Rc<T>> {
+    fn get<T: Object>(&self, r: Ref<T>) -> Result<Arc<T>> {
         self.storage.get(r)
     }
+    fn options(&self) -> ParseOptions {
+        self.storage.options
+    }
+    fn warn(&self, msg: String) {
+        self.storage.warn(msg)
+    }
+    fn stream_data(&self, data: &[u8]) -> StreamData {
+        self.storage.stream_data(data)
+    }
 }
 
 impl<B: Backend> File<B> {
     /// Opens the file at `path` and uses Vec<u8> as backend.
     pub fn open(path: &str) -> Result<File<Vec<u8>>> {
+        File::open_with_password(path, b"")
+    }
+
+    /// Opens the file at `path`, which is encrypted with the user or owner password `password`.
+    pub fn open_with_password(path: &str, password: &[u8]) -> Result<File<Vec<u8>>> {
         // Read file contents to Vec
         let mut backend = Vec::new();
         let mut f = std::fs::File::open(path)?;
         f.read_to_end(&mut backend)?;
 
+        File::from_backend_with_password(backend, password)
+    }
+
+    /// Parses the document held by `backend`.
+    pub fn from_backend(backend: B) -> Result<File<B>> {
+        File::from_backend_with_password(backend, b"")
+    }
+
+    /// Parses a document in memory without copying it, from an owned `Vec<u8>`,
+    /// a borrowed `&[u8]` or a shared `Arc<[u8]>`.
+    pub fn from_data(data: B) -> Result<File<B>> {
+        File::from_backend(data)
+    }
+
+    /// Parses the document held by `backend`. If it is encrypted, strings and streams are
+    /// decrypted as they are read, with the key of the user or owner password `password`.
+    /// Documents with an empty user password open with any `password`.
+    pub fn from_backend_with_password(backend: B, password: &[u8]) -> Result<File<B>> {
+        File::from_backend_with_options(backend, password, ParseOptions::default())
+    }
+
+    /// Like `from_backend_with_password`, parsing objects with `options`.
+    pub fn from_backend_with_options(backend: B, password: &[u8], options: ParseOptions) -> Result<File<B>> {
         let (refs, trailer) = backend.read_xref_table_and_trailer()?;
-        let mut storage = Storage::new(backend, refs);
+        let mut storage = Storage::new(backend, refs, options);
 
-        let trailer = Trailer::from_primitive(Primitive::Dictionary(trailer), &storage)?;
-        if let Some(ref dict) = trailer.encrypt_dict {
-            storage.decoder = Some(Decoder::default(&dict, trailer.id[0].as_bytes())?);
+        // the decoder is needed before anything else is read
+        if let Some(encrypt) = trailer.get("Encrypt") {
+            if let Primitive::Reference(r) = *encrypt {
+                storage.encrypt_id = Some(r.id);
+            }
+            let dict = CryptDict::from_primitive(encrypt.clone(), &storage)?;
+            let id = match trailer.get("ID") {
+                Some(id) => Vec::<PdfString>::from_primitive(id.clone(), &storage)?,
+                None => Vec::new()
+            };
+            let id = id.get(0).map(|s| s.as_bytes()).unwrap_or(&[]);
+            let decoder = match Decoder::from_password(&dict, id, password) {
+                Err(PdfError::InvalidPassword) if !password.is_empty() => Decoder::default(&dict, id)?,
+                result => result?
+            };
+            storage.decoder = Some(decoder);
         }
+
+        let (trailer, trailer_dict, root_revision) = read_trailer(&storage, trailer)?;
         
         Ok(File {
             storage,
             trailer,
+            trailer_dict,
+            signature_policy: SignaturePolicy::default(),
+            root_revision,
+        })
+    }
+
+    /// The trailers of all revisions along the `/Prev` chain, starting with the newest.
+    pub fn trailers(&self) -> Result<Trailers<B>> {
+        Ok(Trailers {
+            backend: &*self.storage.backend,
+            next: Some(self.storage.backend.locate_xref_offset()?),
+            visited: Vec::new(),
         })
     }
 
@@ -201,6 +461,632 @@ impl<B: Backend> File<B> {
         self.pages().nth(n as usize).unwrap()
     }
 
+    /// References of all pages in order.
+    pub fn page_refs(&self) -> Result<Vec<PlainRef>> {
+        let mut refs = Vec::new();
+        if let PagesNode::Tree(ref tree) = *self.get_root().pages {
+            self.collect_page_refs(tree, &mut refs)?;
+        }
+        Ok(refs)
+    }
+    fn collect_page_refs(&self, tree: &PageTree, refs: &mut Vec<PlainRef>) -> Result<()> {
+        for &kid in &tree.kids {
+            match *self.get(kid)? {
+                PagesNode::Tree(ref child) => self.collect_page_refs(child, refs)?,
+                PagesNode::Leaf(_) => refs.push(kid.get_inner())
+            }
+        }
+        Ok(())
+    }
+
+    /// The top level items of the document outline (bookmarks).
+    pub fn outline(&self) -> Result<Vec<Arc<OutlineEntry>>> {
+        match self.get_root().outlines {
+            Some(ref outlines) => outlines.children(self),
+            None => Ok(Vec::new())
+        }
+    }
+    /// The interactive form (`/AcroForm`), if there is one.
+    pub fn acro_form(&self) -> Result<Option<Form>> {
+        match self.get_root().acro_form {
+            Some(ref form) => Option::<Form>::from_primitive(form.clone(), self),
+            None => Ok(None)
+        }
+    }
+    /// The terminal fields of the interactive form, with inherited entries filled in.
+    pub fn form_fields(&self) -> Result<impl Iterator<Item=Field>> {
+        let fields = match self.acro_form()? {
+            Some(form) => form.terminal_fields(self)?,
+            None => Vec::new()
+        };
+        Ok(fields.into_iter())
+    }
+    /// Looks up named destinations and returns the target page and view.
+    pub fn resolve_destination(&self, dest: &Destination) -> Result<(PlainRef, Dest)> {
+        match *dest {
+            Destination::Explicit { page, ref view } => Ok((page, view.clone())),
+            Destination::Named(ref name) => {
+                let root = self.get_root();
+                let mut found = None;
+                if let Some(ref tree) = root.names.as_ref().and_then(|names| names.dests.as_ref()) {
+                    found = tree.lookup(name.as_bytes(), self)?;
+                }
+                if found.is_none() {
+                    // PDF 1.1 style: a dictionary of names
+                    if let Some(ref dests) = root.dests {
+                        let dests = dests.clone().to_dictionary(self)?;
+                        found = str::from_utf8(name.as_bytes()).ok().and_then(|key| dests.get(key).cloned());
+                    }
+                }
+                match found {
+                    Some(p) => match Destination::from_primitive(p, self)? {
+                        Destination::Explicit { page, view } => Ok((page, view)),
+                        Destination::Named(_) => bail!("named destination {:?} refers to another name", name)
+                    },
+                    None => bail!("named destination {:?} not found", name)
+                }
+            }
+        }
+    }
+    /// The index of the page a destination points to.
+    pub fn destination_page(&self, dest: &Destination) -> Result<u32> {
+        let (page, _) = self.resolve_destination(dest)?;
+        match self.page_refs()?.iter().position(|&r| r == page) {
+            Some(n) => Ok(n as u32),
+            None => bail!("destination page {:?} is not in the page tree", page)
+        }
+    }
+
+    /// Sets the private data of application `app` on the page `page` and marks the page as modified.
+    pub fn set_page_piece_data(&mut self, page: PlainRef, app: &str, data: PieceData) -> Result<()> {
+        let mut dict = self.resolve(page)?.to_dictionary(self)?;
+        // the page must not be older than the data, or the data would be considered stale
+        if let Some(ref date) = data.last_modified {
+            dict.insert("LastModified".into(), format_date(date).into());
+        }
+        let dict = self.insert_piece_data(dict, app, data)?;
+        self.update(page.id, dict.into());
+        Ok(())
+    }
+    /// Sets the private data of application `app` for the whole document.
+    pub fn set_document_piece_data(&mut self, app: &str, data: PieceData) -> Result<()> {
+        let root = self.root_ref()?;
+        let dict = self.catalog_dict()?;
+        let dict = self.insert_piece_data(dict, app, data)?;
+        self.update(root.id, dict.into());
+        Ok(())
+    }
+    fn insert_piece_data(&self, mut dict: Dictionary, app: &str, data: PieceData) -> Result<Dictionary> {
+        let mut pieces = match dict.remove("PieceInfo") {
+            Some(p) => p.to_dictionary(self)?,
+            None => Dictionary::new()
+        };
+        pieces.insert(app.into(), data.into());
+        dict.insert("PieceInfo".into(), pieces.into());
+        Ok(dict)
+    }
+
+    /// Changes the media box of the page `page` to `rect`. The contents, the other page boxes,
+    /// the annotations and the destinations that point into the page move along, so they keep their place relative to the lower left corner.
+    /// The other boxes are limited to the new media box.
+    pub fn set_page_media_box(&mut self, page: PlainRef, rect: Rect) -> Result<()> {
+        let old = Page::from_primitive(self.resolve(page)?, self)?.media_box(self)?;
+        let dx = rect.left.min(rect.right) - old.left.min(old.right);
+        let dy = rect.bottom.min(rect.top) - old.bottom.min(old.top);
+        self.transform_page(page, 1., dx, dy, Some(rect))
+    }
+    /// Scales the page `page` by `factor`: its boxes, its contents, its annotations and the
+    /// destinations that point into it.
+    pub fn scale_page(&mut self, page: PlainRef, factor: f32) -> Result<()> {
+        if !(factor > 0.) || !factor.is_finite() {
+            bail!("invalid scale factor {}", factor);
+        }
+        self.transform_page(page, factor, 0., 0., None)
+    }
+    /// Maps the page through `(x, y) → (scale x + dx, scale y + dy)`, with the new media box `media_box`
+    /// or the old one mapped, too.
+    fn transform_page(&mut self, page: PlainRef, scale: f32, dx: f32, dy: f32, media_box: Option<Rect>) -> Result<()> {
+        let map = |r: Rect| Rect {
+            left: r.left * scale + dx,
+            bottom: r.bottom * scale + dy,
+            right: r.right * scale + dx,
+            top: r.top * scale + dy,
+        };
+        // the boxes may be inherited, so the page gets its own
+        let (old_media, old_crop) = {
+            let info = Page::from_primitive(self.resolve(page)?, self)?;
+            (info.media_box(self)?, info.crop_box(self)?)
+        };
+        let media = media_box.unwrap_or_else(|| map(old_media));
+        let mut dict = self.resolve(page)?.to_dictionary(self)?;
+        dict.insert("MediaBox".into(), media.into());
+        dict.insert("CropBox".into(), intersect(map(old_crop), media).into());
+        for &key in &["BleedBox", "TrimBox", "ArtBox"] {
+            if let Some(p) = dict.remove(key) {
+                let r = Rect::from_primitive(p.resolve(self)?, self)?;
+                dict.insert(key.into(), intersect(map(r), media).into());
+            }
+        }
+
+        let identity = scale == 1. && dx == 0. && dy == 0.;
+        if let Some(contents) = dict.remove("Contents") {
+            let parts = match contents.clone().resolve(self)? {
+                Primitive::Array(parts) => parts,
+                _ => vec![contents]
+            };
+            let parts = match identity {
+                true => parts,
+                false => {
+                    let data = format!("{} 0 0 {} {} {} cm\n", scale, scale, dx, dy).into_bytes();
+                    let prefix = self.add(Primitive::Stream(PdfStream { info: Dictionary::new(), data: data.into() }));
+                    Some(prefix.get_inner().into()).into_iter().chain(parts).collect()
+                }
+            };
+            dict.insert("Contents".into(), Primitive::Array(parts));
+        }
+
+        let annots = match identity {
+            true => None,
+            false => dict.remove("Annots")
+        };
+        if let Some(annots) = annots {
+            // annotations and arrays of them that other pages list, too, are copied
+            let mut shared = HashSet::new();
+            for other in self.page_refs()? {
+                if other == page {
+                    continue;
+                }
+                if let Some(other_annots) = self.resolve(other)?.to_dictionary(self)?.remove("Annots") {
+                    if let Primitive::Reference(r) = other_annots {
+                        shared.insert(r);
+                    }
+                    for annot in other_annots.resolve(self)?.to_array(self)? {
+                        if let Primitive::Reference(r) = annot {
+                            shared.insert(r);
+                        }
+                    }
+                }
+            }
+            let (annots_ref, list) = match annots {
+                Primitive::Reference(r) => (Some(r).filter(|r| !shared.contains(r)), self.resolve(r)?.to_array(self)?),
+                p => (None, p.to_array(self)?)
+            };
+            let mut new_list = Vec::with_capacity(list.len());
+            for annot in list {
+                match annot {
+                    Primitive::Reference(r) if shared.contains(&r) => {
+                        let mut annot_dict = self.resolve(r)?.to_dictionary(self)?;
+                        annot_dict.insert("P".into(), page.into());
+                        let annot_dict = transform_annotation(annot_dict, scale, dx, dy, self)?;
+                        new_list.push(self.add(Primitive::Dictionary(annot_dict)).get_inner().into());
+                    }
+                    Primitive::Reference(r) => {
+                        let annot_dict = self.resolve(r)?.to_dictionary(self)?;
+                        let annot_dict = transform_annotation(annot_dict, scale, dx, dy, self)?;
+                        self.update(r.id, annot_dict.into());
+                        new_list.push(Primitive::Reference(r));
+                    }
+                    p => new_list.push(transform_annotation(p.to_dictionary(self)?, scale, dx, dy, self)?.into())
+                }
+            }
+            match annots_ref {
+                Some(r) => {
+                    self.update(r.id, Primitive::Array(new_list));
+                    dict.insert("Annots".into(), Primitive::Reference(r));
+                }
+                None => {
+                    dict.insert("Annots".into(), Primitive::Array(new_list));
+                }
+            }
+        }
+        self.update(page.id, dict.into());
+        if !identity {
+            self.transform_dests(PageTransform { page, scale, dx, dy })?;
+        }
+        Ok(())
+    }
+    /// Maps the destinations into the page of `t` in the outline, the link annotations and the
+    /// named destinations. Only indirect outline items, annotations and name tree nodes are changed.
+    fn transform_dests(&mut self, t: PageTransform) -> Result<()> {
+        let catalog = self.catalog_dict()?;
+        let mut visited = HashSet::new();
+
+        let mut items = Vec::new();
+        if let Some(&Primitive::Reference(r)) = catalog.get("Outlines") {
+            items.push(r);
+        }
+        while let Some(r) = items.pop() {
+            if !visited.insert(r) {
+                continue;
+            }
+            let mut item = self.resolve(r)?.to_dictionary(self)?;
+            for &key in &["First", "Next"] {
+                if let Some(&Primitive::Reference(next)) = item.get(key) {
+                    items.push(next);
+                }
+            }
+            if self.transform_link(&mut item, t)? {
+                self.update(r.id, item.into());
+            }
+        }
+
+        for page in self.page_refs()? {
+            let annots = match self.resolve(page)?.to_dictionary(self)?.remove("Annots") {
+                Some(annots) => annots.resolve(self)?.to_array(self)?,
+                None => continue
+            };
+            for annot in annots {
+                if let Primitive::Reference(r) = annot {
+                    if !visited.insert(r) {
+                        continue;
+                    }
+                    let mut annot = self.resolve(r)?.to_dictionary(self)?;
+                    if self.transform_link(&mut annot, t)? {
+                        self.update(r.id, annot.into());
+                    }
+                }
+            }
+        }
+
+        if let Some(&Primitive::Reference(r)) = catalog.get("Dests") {
+            let mut dests = self.resolve(r)?.to_dictionary(self)?;
+            let mut changed = false;
+            for (_, dest) in dests.iter_mut() {
+                if let Some(new) = self.transform_dest(dest.clone(), t, MAX_DEST_REFS)? {
+                    *dest = new;
+                    changed = true;
+                }
+            }
+            if changed {
+                self.update(r.id, dests.into());
+            }
+        }
+
+        let mut nodes = Vec::new();
+        if let Some(names) = catalog.get("Names") {
+            if let Some(&Primitive::Reference(r)) = names.clone().resolve(self)?.to_dictionary(self)?.get("Dests") {
+                nodes.push(r);
+            }
+        }
+        while let Some(r) = nodes.pop() {
+            if !visited.insert(r) {
+                continue;
+            }
+            let mut node = self.resolve(r)?.to_dictionary(self)?;
+            if let Some(kids) = node.get("Kids") {
+                for kid in kids.clone().resolve(self)?.to_array(self)? {
+                    if let Primitive::Reference(kid) = kid {
+                        nodes.push(kid);
+                    }
+                }
+            }
+            // pairs of names and destinations
+            let mut pairs = match node.get("Names") {
+                Some(pairs) => pairs.clone().resolve(self)?.to_array(self)?,
+                None => continue
+            };
+            let mut changed = false;
+            for dest in pairs.iter_mut().skip(1).step_by(2) {
+                if let Some(new) = self.transform_dest(dest.clone(), t, MAX_DEST_REFS)? {
+                    *dest = new;
+                    changed = true;
+                }
+            }
+            if changed {
+                node.insert("Names".into(), Primitive::Array(pairs));
+                self.update(r.id, node.into());
+            }
+        }
+        Ok(())
+    }
+    /// Maps `/Dest` and the destination of the `/A` action of an outline item or a link annotation.
+    /// Returns whether `dict` changed.
+    fn transform_link(&mut self, dict: &mut Dictionary, t: PageTransform) -> Result<bool> {
+        let mut changed = false;
+        for &key in &["Dest", "A"] {
+            if let Some(dest) = dict.get(key).cloned() {
+                if let Some(new) = self.transform_dest(dest, t, MAX_DEST_REFS)? {
+                    dict.insert(key.into(), new);
+                    changed = true;
+                }
+            }
+        }
+        Ok(changed)
+    }
+    /// Maps an explicit destination, or the `/D` entry of a dictionary, following at most `depth`
+    /// references. Indirect objects are updated; the new value of a direct one is returned.
+    fn transform_dest(&mut self, dest: Primitive, t: PageTransform, depth: u32) -> Result<Option<Primitive>> {
+        match dest {
+            Primitive::Reference(r) if depth > 0 => {
+                let value = self.resolve(r)?;
+                if let Some(new) = self.transform_dest(value, t, depth - 1)? {
+                    self.update(r.id, new);
+                }
+                Ok(None)
+            }
+            Primitive::Array(parts) => Ok(t.dest(&parts)?.map(Primitive::Array)),
+            Primitive::Dictionary(mut dict) => match dict.get("D").cloned() {
+                Some(d) => match self.transform_dest(d, t, depth)? {
+                    Some(new) => {
+                        dict.insert("D".into(), new);
+                        Ok(Some(dict.into()))
+                    }
+                    None => Ok(None)
+                },
+                None => Ok(None)
+            },
+            _ => Ok(None)
+        }
+    }
+
+    /// Where the object `r` is stored, and the size of its serialized form in bytes, from `obj` to `endobj`.
+    /// For objects in object streams, it is the size within the decoded stream; for modified objects,
+    /// the size they will be written with.
+    pub fn locate(&self, r: PlainRef) -> Result<(XRefLocation, usize)> {
+        self.storage.locate(r)
+    }
+
+    /// Adds a new object and returns a reference to it.
+    pub fn add<T>(&mut self, obj: T) -> Ref<T> where T: Into<Primitive> {
+        let id = self.storage.refs.len() as ObjNr;
+        self.storage.refs.push(XRef::Promised);
+        self.storage.changes.insert(id, obj.into());
+        
+        Ref::from_id(id)
+    }
+    /// Replaces the object `id`.
+    pub fn update(&mut self, id: ObjNr, primitive: Primitive) {
+        self.storage.changes.insert(id, primitive);
+        self.storage.cache.remove(id);
+    }
+    /// Reserves an object number, for objects that refer to each other. Resolving it fails until
+    /// `File::fulfill` is called.
+    pub fn promise<T: Object>(&mut self) -> PromisedRef<T> {
+        let id = self.storage.refs.len() as ObjNr;
+        self.storage.refs.push(XRef::Promised);
+        PromisedRef {
+            inner: PlainRef { id, gen: 0 },
+            _marker: PhantomData
+        }
+    }
+    /// Stores `obj` as the object that was promised.
+    pub fn fulfill<T>(&mut self, promise: PromisedRef<T>, obj: T) -> Ref<T> where T: Into<Primitive> {
+        self.update(promise.inner.id, obj.into());
+        Ref::new(promise.inner)
+    }
+
+    /// Appends a page of size `media_box` to the document. `content` refers to the entries of
+    /// `resources` (like `/Font`) by name.
+    pub fn add_page(&mut self, media_box: Rect, content: &Content, resources: Dictionary) -> Result<PlainRef> {
+        let tree = self.page_tree_ref()?;
+        let contents = self.add(Primitive::Stream(content.to_stream()?)).get_inner();
+
+        let mut page = Dictionary::new();
+        page.insert("Type".into(), "Page".into());
+        page.insert("Parent".into(), tree.into());
+        page.insert("MediaBox".into(), media_box.into());
+        page.insert("Resources".into(), resources.into());
+        page.insert("Contents".into(), contents.into());
+        let page = self.add(Primitive::Dictionary(page)).get_inner();
+        self.append_pages(&[page])?;
+        Ok(page)
+    }
+    /// Adds one of the 14 standard fonts (like `Helvetica` or `Times-Roman`), which need not be embedded,
+    /// with `WinAnsiEncoding`. Put it into the `/Font` resources of the pages that use it.
+    pub fn add_standard_font(&mut self, base_font: &str) -> PlainRef {
+        let mut font = Dictionary::new();
+        font.insert("Type".into(), "Font".into());
+        font.insert("Subtype".into(), "Type1".into());
+        font.insert("BaseFont".into(), base_font.into());
+        // Symbol and ZapfDingbats have their own encoding
+        if base_font != "Symbol" && base_font != "ZapfDingbats" {
+            font.insert("Encoding".into(), "WinAnsiEncoding".into());
+        }
+        self.add(Primitive::Dictionary(font)).get_inner()
+    }
+
+    /// Sets how writing treats changes that invalidate existing signatures.
+    pub fn set_signature_policy(&mut self, policy: SignaturePolicy) {
+        self.signature_policy = policy;
+    }
+
+    /// Forgets the objects read so far.
+    pub(crate) fn clear_cache(&self) {
+        self.storage.clear_cache();
+    }
+    /// Limits the estimated size of the objects kept in memory to `bytes`, or removes the
+    /// limit with `None`. The objects that were used the longest time ago are dropped first.
+    pub fn set_cache_limit(&self, bytes: Option<usize>) {
+        self.storage.cache.set_limit(bytes);
+    }
+    pub fn cache_stats(&self) -> CacheStats {
+        self.storage.cache.stats()
+    }
+    /// The revision the catalog was read from, counting back from the newest (0). It is an
+    /// older one if the `/Root` of the newer trailers could not be read.
+    pub fn root_revision(&self) -> usize {
+        self.root_revision
+    }
+    /// What was repaired while parsing the objects read so far.
+    pub fn warnings(&self) -> Vec<String> {
+        self.storage.warnings.lock().unwrap().clone()
+    }
+    /// The trailer as it will be written.
+    pub(crate) fn trailer_dict(&self) -> &Dictionary {
+        &self.trailer_dict
+    }
+    pub(crate) fn info_ref(&self) -> Option<PlainRef> {
+        match self.trailer_dict.get("Info") {
+            Some(&Primitive::Reference(r)) => Some(r),
+            _ => None
+        }
+    }
+    fn root_ref(&self) -> Result<PlainRef> {
+        match self.trailer_dict.get("Root") {
+            Some(&Primitive::Reference(r)) => Ok(r),
+            _ => bail!("trailer /Root is not a reference")
+        }
+    }
+    pub(crate) fn catalog_dict(&self) -> Result<Dictionary> {
+        self.resolve(self.root_ref()?)?.to_dictionary(self)
+    }
+    /// The bytes of the file as it was read.
+    pub(crate) fn data(&self) -> Result<&[u8]> {
+        self.storage.backend.read(..)
+    }
+    /// Makes the page tree node `pages` the root of the page tree.
+    pub(crate) fn set_page_tree(&mut self, pages: PlainRef) -> Result<()> {
+        let root = self.root_ref()?;
+        let mut catalog = self.catalog_dict()?;
+        catalog.insert("Pages".into(), Primitive::Reference(pages));
+        self.update(root.id, catalog.clone().into());
+        self.trailer.root = Catalog::from_primitive(catalog.into(), self)?;
+        Ok(())
+    }
+    /// All objects that are in use, in the file or added since.
+    pub(crate) fn object_refs(&self) -> Vec<PlainRef> {
+        let refs = &self.storage.refs;
+        (0 .. refs.len() as ObjNr).filter_map(|id| {
+            if self.storage.changes.contains_key(&id) {
+                return Some(PlainRef { id, gen: 0 });
+            }
+            match refs.get(id).ok()? {
+                XRef::Raw { gen_nr, .. } => Some(PlainRef { id, gen: gen_nr }),
+                XRef::Stream { .. } => Some(PlainRef { id, gen: 0 }),
+                _ => None
+            }
+        }).collect()
+    }
+    /// The root node of the page tree.
+    pub(crate) fn page_tree_ref(&self) -> Result<PlainRef> {
+        match self.catalog_dict()?.get("Pages") {
+            Some(&Primitive::Reference(r)) => Ok(r),
+            _ => bail!("/Pages of the catalog is not a reference")
+        }
+    }
+    /// Adds `pages`, whose `/Parent` is the root of the page tree already, to the end of the document.
+    pub(crate) fn append_pages(&mut self, pages: &[PlainRef]) -> Result<()> {
+        let tree = self.page_tree_ref()?;
+        let mut dict = self.resolve(tree)?.to_dictionary(self)?;
+        let mut kids = match dict.remove("Kids") {
+            Some(kids) => kids.resolve(self)?.to_array(self)?,
+            None => Vec::new()
+        };
+        kids.extend(pages.iter().map(|&page| Primitive::Reference(page)));
+        let count = match dict.get("Count") {
+            Some(count) => count.as_integer()?,
+            None => 0
+        };
+        dict.insert("Kids".into(), Primitive::Array(kids));
+        dict.insert("Count".into(), Primitive::Integer(count + pages.len() as i32));
+        self.update(tree.id, dict.into());
+        self.set_page_tree(tree)
+    }
+    /// The `/Resources` of a page as written, which may be inherited from the page tree.
+    pub(crate) fn raw_page_resources(&self, page: PlainRef) -> Result<Option<Primitive>> {
+        let mut node = page;
+        // guards against cycles in /Parent
+        for _ in 0 .. 64 {
+            let dict = self.resolve(node)?.to_dictionary(self)?;
+            if let Some(resources) = dict.get("Resources") {
+                return Ok(Some(resources.clone()));
+            }
+            match dict.get("Parent") {
+                Some(&Primitive::Reference(parent)) => node = parent,
+                _ => break
+            }
+        }
+        Ok(None)
+    }
+    /// The XObjects in `resources` that are indirect objects (and can be replaced), by name.
+    pub(crate) fn xobject_refs(&self, resources: Option<Primitive>) -> Result<BTreeMap<String, PlainRef>> {
+        let mut refs = BTreeMap::new();
+        let resources = match resources {
+            Some(resources) => resources.to_dictionary(self)?,
+            None => return Ok(refs)
+        };
+        if let Some(xobjects) = resources.get("XObject") {
+            for (name, xobject) in xobjects.clone().to_dictionary(self)?.iter() {
+                if let Primitive::Reference(r) = *xobject {
+                    refs.insert(name.clone(), r);
+                }
+            }
+        }
+        Ok(refs)
+    }
+
+    /// The document information dictionary, if present.
+    pub fn info(&self) -> Result<Option<DocInfo>> {
+        match self.trailer_dict.get("Info") {
+            Some(p) => Ok(Some(DocInfo::from_primitive(p.clone(), self)?)),
+            None => Ok(None)
+        }
+    }
+    /// Title, author, dates etc. from the information dictionary and the XMP metadata.
+    pub fn metadata(&self) -> Result<Metadata> {
+        let xmp = self.xmp()?;
+        metadata::merge(self.info()?, xmp.as_ref().map(|x| x.as_slice()))
+    }
+    /// The decoded XMP metadata stream of the document, if present.
+    pub fn xmp(&self) -> Result<Option<Vec<u8>>> {
+        match self.catalog_dict()?.remove("Metadata") {
+            Some(p) => {
+                let stream = Stream::<()>::from_primitive(p, self)?;
+                Ok(Some(stream.data()?.to_vec()))
+            }
+            None => Ok(None)
+        }
+    }
+
+    /// Replaces the information dictionary.
+    /// If the document has XMP metadata, the corresponding properties are updated as well.
+    pub fn set_info(&mut self, info: DocInfo) -> Result<()> {
+        if let Some(xml) = self.xmp()? {
+            let xml = sync_xmp(&xml, &info)?;
+            self.write_xmp(xml)?;
+        }
+        self.write_info(info);
+        Ok(())
+    }
+    /// Replaces the XMP metadata.
+    /// Title, author, dates etc. found in `xml` are copied into the information dictionary.
+    pub fn set_xmp(&mut self, xml: Vec<u8>) -> Result<()> {
+        let mut info = self.info()?.unwrap_or_default();
+        sync_info(&xml, &mut info)?;
+        self.write_xmp(xml)?;
+        self.write_info(info);
+        Ok(())
+    }
+
+    fn write_info(&mut self, info: DocInfo) {
+        match self.info_ref() {
+            Some(r) => self.update(r.id, info.into()),
+            None => {
+                let r = self.add(info).get_inner();
+                self.trailer_dict.insert("Info".into(), r.into());
+            }
+        }
+    }
+    fn write_xmp(&mut self, xml: Vec<u8>) -> Result<()> {
+        let mut info = Dictionary::new();
+        info.insert("Type".into(), "Metadata".into());
+        info.insert("Subtype".into(), "XML".into());
+        info.insert("Length".into(), Primitive::Integer(xml.len() as i32));
+        let stream = PdfStream { info, data: xml.into() };
+
+        let root = self.root_ref()?;
+        let mut catalog = self.catalog_dict()?;
+        match catalog.get("Metadata") {
+            Some(&Primitive::Reference(r)) => self.update(r.id, stream.into()),
+            _ => {
+                let r = self.add(stream).get_inner();
+                catalog.insert("Metadata".into(), r.into());
+                self.update(root.id, catalog.into());
+            }
+        }
+        Ok(())
+    }
+
     /*
     pub fn get_images(&self) -> Vec<ImageXObject> {
         let mut images = Vec::<ImageXObject>::new();
@@ -262,40 +1148,112 @@ impl<B: Backend> File<B> {
     pub fn update(&mut self, id: ObjNr, primitive: Primitive) {
         self.changes.insert(id, primitive);
     }
-    
-    pub fn promise<T: Object>(&mut self) -> PromisedRef<T> {
-        let id = self.refs.len() as u64;
-        
-        self.refs.push(XRef::Promised);
-        
-        PromisedRef {
-            inner: PlainRef {
-                id:     id,
-                gen:    0
+    */
+}
+
+impl File<Vec<u8>> {
+    /// A new document without pages. Add some with `File::add_page` and write it with `File::write_to`.
+    pub fn new() -> Result<File<Vec<u8>>> {
+        let mut refs = XRefTable::new(0);
+        refs.push(XRef::Free { next_obj_nr: 0, gen_nr: 65535 });
+        let mut storage = Storage::new(Vec::new(), refs, ParseOptions::default());
+        let add = |storage: &mut Storage<Vec<u8>>, dict: Dictionary| {
+            let id = storage.refs.len() as ObjNr;
+            storage.refs.push(XRef::Promised);
+            storage.changes.insert(id, dict.into());
+            PlainRef { id, gen: 0 }
+        };
+
+        let mut pages = Dictionary::new();
+        pages.insert("Type".into(), "Pages".into());
+        pages.insert("Kids".into(), Primitive::Array(Vec::new()));
+        pages.insert("Count".into(), Primitive::Integer(0));
+        let pages = add(&mut storage, pages);
+
+        let mut catalog = Dictionary::new();
+        catalog.insert("Type".into(), "Catalog".into());
+        catalog.insert("Pages".into(), pages.into());
+        let catalog = add(&mut storage, catalog);
+
+        let mut trailer_dict = Dictionary::new();
+        trailer_dict.insert("Size".into(), Primitive::Integer(storage.refs.len() as i32));
+        trailer_dict.insert("Root".into(), catalog.into());
+        let trailer = Trailer::from_primitive(Primitive::Dictionary(trailer_dict.clone()), &storage)?;
+
+        Ok(File {
+            storage,
+            trailer,
+            trailer_dict,
+            signature_policy: SignaturePolicy::default(),
+            root_revision: 0,
+        })
+    }
+    /// Reads `reader` to the end and parses the document.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<File<Vec<u8>>> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        File::from_backend(data)
+    }
+}
+
+impl File<Arc<[u8]>> {
+    /// Parses a document from a shared buffer without copying it.
+    pub fn from_arc(data: Arc<[u8]>) -> Result<File<Arc<[u8]>>> {
+        File::from_backend(data)
+    }
+}
+
+impl File<Rc<[u8]>> {
+    /// Parses a document from a buffer shared within one thread without copying it.
+    pub fn from_rc(data: Rc<[u8]>) -> Result<File<Rc<[u8]>>> {
+        File::from_backend(data)
+    }
+}
+
+/// Parses `trailer`. If its catalog can't be read, the `/Root` of the newest older revision
+/// whose catalog can is used instead, so that a damaged update doesn't make the whole
+/// document unreadable. Also returns the trailer as it will be written and the revision used.
+fn read_trailer<B: Backend>(storage: &Storage<B>, trailer: Dictionary) -> Result<(Trailer, Dictionary, usize)> {
+    let error = match Trailer::from_primitive(Primitive::Dictionary(trailer.clone()), storage) {
+        Ok(t) => return Ok((t, trailer, 0)),
+        Err(e) => e
+    };
+    let trailers = match storage.backend.locate_xref_offset() {
+        Ok(pos) => Trailers { backend: &*storage.backend, next: Some(pos), visited: Vec::new() },
+        Err(_) => return Err(error)
+    };
+    let mut tried: Vec<PlainRef> = trailer.get("Root").and_then(|p| p.clone().to_reference().ok()).into_iter().collect();
+    for (revision, older) in trailers.enumerate().skip(1) {
+        let root = match older {
+            Ok((_, mut dict)) => match dict.remove("Root") {
+                Some(root) => root,
+                None => continue
             },
-            _marker:    PhantomData
+            Err(_) => break
+        };
+        if let Primitive::Reference(r) = root {
+            if tried.contains(&r) {
+                continue;
+            }
+            tried.push(r);
+        }
+        let mut dict = trailer.clone();
+        dict.insert("Root".into(), root);
+        if let Ok(t) = Trailer::from_primitive(Primitive::Dictionary(dict.clone()), storage) {
+            storage.warn(format!("catalog of the newest revision is unreadable ({}), using the one of revision {} back", error, revision));
+            return Ok((t, dict, revision));
         }
     }
-    
-    pub fn fulfill<T>(&mut self, promise: PromisedRef<T>, obj: T) -> Ref<T>
-    where T: Into<Primitive>
-    {
-        self.update(promise.inner.id, obj.into());
-        
-        Ref::new(promise.inner)
-    }
-    
-    pub fn add<T>(&mut self, obj: T) -> Ref<T> where T: Into<Primitive> {
-        let id = self.refs.len() as u64;
-        self.refs.push(XRef::Promised);
-        self.update(id, obj.into());
-        
-        Ref::from_id(id)
+    Err(error)
+}
+
+impl<'a> File<&'a [u8]> {
+    /// Parses a document from borrowed data without copying it.
+    pub fn from_slice(data: &'a [u8]) -> Result<File<&'a [u8]>> {
+        File::from_backend(data)
     }
-    */
 }
 
-    
 #[derive(Object)]
 pub struct Trailer {
     #[pdf(key = "Size")]
@@ -350,7 +1308,7 @@ impl Object for XRefStream {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         let stream = p.to_stream(resolve)?;
         let info = XRefInfo::from_primitive(Primitive::Dictionary (stream.info), resolve)?;
-        let data = stream.data.clone();
+        let data = stream.data.into_vec();
         Ok(XRefStream {
             data: data,
             info: info,
@@ -358,3 +1316,209 @@ impl Object for XRefStream {
     }
 }
 */
+
+/// The part of `a` that lies in `b`, or `b` if they don't overlap.
+fn intersect(a: Rect, b: Rect) -> Rect {
+    let r = Rect {
+        left: a.left.min(a.right).max(b.left.min(b.right)),
+        bottom: a.bottom.min(a.top).max(b.bottom.min(b.top)),
+        right: a.left.max(a.right).min(b.left.max(b.right)),
+        top: a.bottom.max(a.top).min(b.bottom.max(b.top)),
+    };
+    if r.left < r.right && r.bottom < r.top { r } else { b }
+}
+
+/// How many references `File::transform_dest` follows, which guards against cycles.
+const MAX_DEST_REFS: u32 = 4;
+
+/// The page `transform_page` maps, and how.
+#[derive(Copy, Clone)]
+struct PageTransform {
+    page: PlainRef,
+    scale: f32,
+    dx: f32,
+    dy: f32,
+}
+impl PageTransform {
+    /// Maps the explicit destination `dest`, like `[page /XYZ left top zoom]`, if it points into the page.
+    fn dest(&self, dest: &[Primitive]) -> Result<Option<Vec<Primitive>>> {
+        match dest.first() {
+            Some(&Primitive::Reference(r)) if r == self.page => {}
+            _ => return Ok(None)
+        }
+        // whether the operands are x coordinates, or y coordinates
+        let horizontal: &[bool] = match dest.get(1) {
+            Some(&Primitive::Name(ref kind)) => match kind.as_str() {
+                "XYZ" => &[true, false],
+                "FitH" | "FitBH" => &[false],
+                "FitV" | "FitBV" => &[true],
+                "FitR" => &[true, false, true, false],
+                _ => return Ok(None)
+            },
+            _ => return Ok(None)
+        };
+        let mut mapped = dest.to_vec();
+        for (p, &horizontal) in mapped[2 ..].iter_mut().zip(horizontal) {
+            // null keeps the current value
+            if let Primitive::Null = *p {
+                continue;
+            }
+            let offset = if horizontal { self.dx } else { self.dy };
+            *p = Primitive::Number(p.as_number()? * self.scale + offset);
+        }
+        Ok(Some(mapped))
+    }
+}
+
+/// Maps the coordinates of an annotation through `(x, y) → (scale x + dx, scale y + dy)`.
+fn transform_annotation(mut dict: Dictionary, scale: f32, dx: f32, dy: f32, resolve: &impl Resolve) -> Result<Dictionary> {
+    // arrays of alternating x and y coordinates
+    let points = |p: Primitive| -> Result<Primitive> {
+        let coords = p.resolve(resolve)?.to_array(resolve)?;
+        let mapped = coords.iter().enumerate().map(|(i, c)| {
+            let offset = if i % 2 == 0 { dx } else { dy };
+            Ok(Primitive::Number(c.as_number()? * scale + offset))
+        }).collect::<Result<Vec<_>>>()?;
+        Ok(Primitive::Array(mapped))
+    };
+    for &key in &["Rect", "QuadPoints", "L", "Vertices", "CL"] {
+        if let Some(p) = dict.remove(key) {
+            dict.insert(key.into(), points(p)?);
+        }
+    }
+    if let Some(ink) = dict.remove("InkList") {
+        let strokes = ink.resolve(resolve)?.to_array(resolve)?.into_iter()
+            .map(|stroke| points(stroke))
+            .collect::<Result<Vec<_>>>()?;
+        dict.insert("InkList".into(), Primitive::Array(strokes));
+    }
+    Ok(dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::ContentBuilder;
+
+    fn rect(left: f32, bottom: f32, right: f32, top: f32) -> Rect {
+        Rect { left, bottom, right, top }
+    }
+    fn dict(file: &File<Vec<u8>>, r: PlainRef) -> Dictionary {
+        file.resolve(r).unwrap().to_dictionary(file).unwrap()
+    }
+    /// The numbers in an array, which leaves out the page and the kind of destinations.
+    fn numbers(file: &File<Vec<u8>>, p: &Primitive) -> Vec<f32> {
+        p.clone().resolve(file).unwrap().to_array(file).unwrap().iter().filter_map(|p| p.as_number().ok()).collect()
+    }
+
+    #[test]
+    fn transform_page() {
+        let mut file = File::new().unwrap();
+        let content = ContentBuilder::new().build();
+        let a = file.add_page(rect(0., 0., 200., 100.), &content, Dictionary::new()).unwrap();
+        let b = file.add_page(rect(0., 0., 200., 100.), &content, Dictionary::new()).unwrap();
+        let add = |file: &mut File<Vec<u8>>, text: String| file.add(parse(text.as_bytes(), &NoResolve).unwrap()).get_inner();
+
+        // a link to page a, shown on both pages
+        let link = add(&mut file, format!("<< /Type /Annot /Subtype /Link /Rect [10 10 20 20] /Dest [{} 0 R /XYZ 50 60 null] >>", a.id));
+        for &page in &[a, b] {
+            let mut page_dict = dict(&file, page);
+            page_dict.insert("Annots".into(), Primitive::Array(vec![link.into()]));
+            file.update(page.id, page_dict.into());
+        }
+        let item = add(&mut file, format!("<< /Title (A) /A << /S /GoTo /D [{} 0 R /FitR 10 10 20 20] >> >>", a.id));
+        let outlines = add(&mut file, format!("<< /Type /Outlines /First {0} 0 R /Last {0} 0 R /Count 1 >>", item.id));
+        let dests = add(&mut file, format!("<< /one [{} 0 R /FitH 80] >>", a.id));
+        let two = add(&mut file, format!("[{} 0 R /FitV 30]", a.id));
+        let names = add(&mut file, format!("<< /Names [(two) {} 0 R] >>", two.id));
+        let root = file.root_ref().unwrap();
+        let mut catalog = file.catalog_dict().unwrap();
+        catalog.insert("Outlines".into(), outlines.into());
+        catalog.insert("Dests".into(), dests.into());
+        catalog.insert("Names".into(), parse(format!("<< /Dests {} 0 R >>", names.id).as_bytes(), &NoResolve).unwrap());
+        file.update(root.id, catalog.into());
+
+        file.set_page_media_box(a, rect(100., 50., 300., 150.)).unwrap();
+        let page_a = dict(&file, a);
+        assert_eq!(numbers(&file, &page_a["MediaBox"]), [100., 50., 300., 150.]);
+        assert_eq!(numbers(&file, &page_a["CropBox"]), [100., 50., 300., 150.]);
+        let contents = page_a["Contents"].clone().to_array(&file).unwrap();
+        assert_eq!(contents.len(), 2);
+        let prefix = Stream::<()>::from_primitive(contents[0].clone(), &file).unwrap();
+        assert_eq!(prefix.data().unwrap(), &b"1 0 0 1 100 50 cm\n"[..]);
+
+        // page b keeps the link, page a gets a moved copy
+        let copy = match page_a["Annots"].clone().to_array(&file).unwrap()[0] {
+            Primitive::Reference(r) => r,
+            _ => panic!()
+        };
+        assert!(copy != link);
+        assert_eq!(numbers(&file, &dict(&file, copy)["Rect"]), [110., 60., 120., 70.]);
+        assert_eq!(dict(&file, copy)["P"].clone().to_reference().unwrap(), a);
+        assert_eq!(numbers(&file, &dict(&file, link)["Rect"]), [10., 10., 20., 20.]);
+
+        // destinations into page a
+        assert_eq!(numbers(&file, &dict(&file, link)["Dest"]), [150., 110.]);
+        assert_eq!(numbers(&file, &dict(&file, copy)["Dest"]), [150., 110.]);
+        let action = dict(&file, item)["A"].clone().to_dictionary(&file).unwrap();
+        assert_eq!(numbers(&file, &action["D"]), [110., 60., 120., 70.]);
+        assert_eq!(numbers(&file, &dict(&file, dests)["one"]), [130.]);
+        assert_eq!(numbers(&file, &two.into()), [130.]);
+
+        file.scale_page(b, 2.).unwrap();
+        assert_eq!(numbers(&file, &dict(&file, b)["MediaBox"]), [0., 0., 400., 200.]);
+        assert_eq!(numbers(&file, &dict(&file, link)["Rect"]), [20., 20., 40., 40.]);
+        assert_eq!(numbers(&file, &dict(&file, link)["Dest"]), [150., 110.]);
+        assert!(file.scale_page(b, 0.).is_err());
+    }
+
+    #[test]
+    fn extends() {
+        let mut file = File::new().unwrap();
+        let base = file.storage.refs.len() as ObjNr;
+        let (first, second, x, y, z) = (base, base + 1, base + 2, base + 3, base + 4);
+        let object_stream = |header: String, objects: &[u8], extends: Option<ObjNr>| {
+            let mut info = Dictionary::new();
+            info.insert("Type".into(), "ObjStm".into());
+            info.insert("N".into(), Primitive::Integer(header.split_whitespace().count() as i32 / 2));
+            info.insert("First".into(), Primitive::Integer(header.len() as i32));
+            if let Some(id) = extends {
+                info.insert("Extends".into(), PlainRef {id, gen: 0}.into());
+            }
+            let data = [header.as_bytes(), objects].concat();
+            Primitive::Stream(PdfStream { info, data: data.into() })
+        };
+        // the header of the first stream lists 999 instead of z
+        file.add(object_stream(format!("999 0 {} 6 ", y), b"(zed) (why)", Some(second)));
+        file.add(object_stream(format!("{} 0 ", x), b"(ex)", None));
+        file.storage.refs.push(XRef::Stream {stream_id: first, index: 0});
+        file.storage.refs.push(XRef::Stream {stream_id: first, index: 7});
+        file.storage.refs.push(XRef::Stream {stream_id: first, index: 0});
+
+        let string = |id| file.resolve(PlainRef {id, gen: 0}).unwrap().as_string().unwrap().as_bytes().to_vec();
+        assert_eq!(string(x), b"ex");
+        assert_eq!(string(y), b"why");
+        assert_eq!(string(z), b"zed");
+        assert_eq!(file.locate(PlainRef {id: x, gen: 0}).unwrap().0, XRefLocation::InStream {stream: second, index: 0});
+        assert_eq!(file.locate(PlainRef {id: y, gen: 0}).unwrap().0, XRefLocation::InStream {stream: first, index: 1});
+    }
+
+    #[test]
+    fn malformed_acro_form() {
+        let mut file = File::new().unwrap();
+        let mut catalog = file.catalog_dict().unwrap();
+        catalog.insert("AcroForm".into(), parse(b"<< /Fields [] /DA /Helv >>", &NoResolve).unwrap());
+        file.trailer.root = Catalog::from_primitive(catalog.into(), &file).unwrap();
+        assert!(file.acro_form().is_err());
+    }
+
+    #[test]
+    fn promise() {
+        let mut file = File::new().unwrap();
+        let promise = file.promise::<Primitive>();
+        let r: PlainRef = (&promise).into();
+        assert!(file.resolve(r).is_err());
+        file.fulfill(promise, Primitive::Integer(1));
+        assert_eq!(file.resolve(r).unwrap().as_integer().unwrap(), 1);
+    }
+}
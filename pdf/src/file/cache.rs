@@ -0,0 +1,184 @@
+//! The cache of objects read from a file, optionally limited to a number of bytes.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::any::Any;
+use crate::object::{ObjNr, PlainRef};
+use crate::primitive::Primitive;
+
+/// How the object cache of a `File` is doing, see `File::cache_stats`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    /// Objects in the cache
+    pub objects: usize,
+    /// Estimated size of the objects in the cache
+    pub bytes: usize,
+    /// The limit set with `File::set_cache_limit`
+    pub limit: Option<usize>,
+    /// Reads that found the object in the cache
+    pub hits: u64,
+    /// Reads that had to parse the object
+    pub misses: u64,
+    /// Objects removed to stay within the limit
+    pub evictions: u64,
+}
+
+struct Entry {
+    object: Any,
+    size: usize,
+    /// the value of the clock when the object was last read
+    used: AtomicU64,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<PlainRef, Entry>,
+    bytes: usize,
+}
+
+/// Least recently used objects are dropped first when the limit is exceeded.
+/// Objects that are still in use elsewhere stay alive, they are just read again next time.
+pub(crate) struct ObjectCache {
+    entries: RwLock<Entries>,
+    /// `usize::MAX` without a limit
+    limit: AtomicUsize,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ObjectCache {
+    pub fn new() -> ObjectCache {
+        ObjectCache {
+            entries: RwLock::new(Entries::default()),
+            limit: AtomicUsize::new(usize::MAX),
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+    pub fn get(&self, key: PlainRef) -> Option<Any> {
+        let entries = self.entries.read().unwrap();
+        match entries.map.get(&key) {
+            Some(entry) => {
+                entry.used.store(self.clock.fetch_add(1, Ordering::Relaxed), Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry.object.clone())
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+    /// Adds `object`, whose size is estimated to be `size` bytes.
+    pub fn insert(&self, key: PlainRef, object: Any, size: usize) {
+        let used = AtomicU64::new(self.clock.fetch_add(1, Ordering::Relaxed));
+        let mut entries = self.entries.write().unwrap();
+        // another thread may have read the object in the meantime, which does no harm
+        if let Some(old) = entries.map.insert(key, Entry { object, size, used }) {
+            entries.bytes -= old.size;
+        }
+        entries.bytes += size;
+        self.evict(&mut entries);
+    }
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+        entries.map.clear();
+        entries.bytes = 0;
+    }
+    /// Forgets all generations of object `id`.
+    pub fn remove(&mut self, id: ObjNr) {
+        let entries = self.entries.get_mut().unwrap();
+        let mut removed = 0;
+        entries.map.retain(|r, entry| {
+            if r.id == id {
+                removed += entry.size;
+            }
+            r.id != id
+        });
+        entries.bytes -= removed;
+    }
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+        self.evict(&mut self.entries.write().unwrap());
+    }
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.read().unwrap();
+        CacheStats {
+            objects: entries.map.len(),
+            bytes: entries.bytes,
+            limit: match self.limit.load(Ordering::Relaxed) {
+                usize::MAX => None,
+                limit => Some(limit)
+            },
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn evict(&self, entries: &mut Entries) {
+        let limit = self.limit.load(Ordering::Relaxed);
+        if entries.bytes <= limit {
+            return;
+        }
+        let mut by_use: Vec<(u64, PlainRef)> = entries.map.iter()
+            .map(|(&key, entry)| (entry.used.load(Ordering::Relaxed), key))
+            .collect();
+        by_use.sort();
+        for (_, key) in by_use {
+            if entries.bytes <= limit {
+                break;
+            }
+            if let Some(entry) = entries.map.remove(&key) {
+                entries.bytes -= entry.size;
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Roughly how many bytes the object parsed from `p` takes, for the cache limit.
+pub(crate) fn estimated_size(p: &Primitive) -> usize {
+    const BASE: usize = std::mem::size_of::<Primitive>();
+    match *p {
+        Primitive::String(ref s) => BASE + s.as_bytes().len(),
+        Primitive::Name(ref name) => BASE + name.len(),
+        Primitive::Array(ref parts) => BASE + parts.iter().map(estimated_size).sum::<usize>(),
+        Primitive::Dictionary(ref dict) => BASE + dict.iter()
+            .map(|(key, value)| key.len() + estimated_size(value)).sum::<usize>(),
+        // decoded data can be larger, but is only known once it is used
+        Primitive::Stream(ref stream) => BASE + stream.data.len() + stream.info.iter()
+            .map(|(key, value)| key.len() + estimated_size(value)).sum::<usize>(),
+        _ => BASE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let cache = ObjectCache::new();
+        let key = |id| PlainRef { id, gen: 0 };
+        for id in 0 .. 3 {
+            cache.insert(key(id), Any::new(Arc::new(id as i32)), 100);
+        }
+        assert!(cache.get(key(0)).is_some());
+        cache.set_limit(Some(250));
+        // 1 was used longest ago
+        assert!(cache.get(key(1)).is_none());
+        assert!(cache.get(key(0)).is_some());
+
+        let stats = cache.stats();
+        assert_eq!((stats.objects, stats.bytes, stats.evictions), (2, 200, 1));
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+    }
+}
@@ -0,0 +1,361 @@
+//! Writing linearized files (Annex F of the specification).
+//!
+//! The file is laid out as: the linearization dictionary, the cross-reference section of the
+//! first page, the catalog, the hint stream, the objects of the first page, those of each
+//! other page, the objects shared by several of these pages, everything else and finally the
+//! main cross-reference section. The objects are renumbered, so that each of the two
+//! cross-reference sections is a single subsection.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream};
+use crate::backend::Backend;
+use crate::merge::page_tree;
+use super::File;
+use super::write::{WriteOptions, write_object, write_xref_section};
+
+impl<B: Backend> File<B> {
+    pub(super) fn write_linearized<W: Write>(&self, out: &mut W, options: &WriteOptions) -> Result<()> {
+        if options.object_streams || options.encryption.is_some() {
+            bail!("linearized files are written without object streams and encryption");
+        }
+        let mut objects = BTreeMap::new();
+        for id in 1 .. self.storage.refs.len() as ObjNr {
+            if let Some((_, p)) = self.object_for_writing(id)? {
+                objects.insert(id, p);
+            }
+        }
+        let root = self.root_ref()?.id;
+        let pages: Vec<ObjNr> = self.page_refs()?.iter().map(|r| r.id).collect();
+        if pages.is_empty() {
+            bail!("documents without pages can't be linearized");
+        }
+
+        // the objects of each page, without the page tree and the catalog
+        let mut excluded: HashSet<ObjNr> = page_tree(self)?.iter().map(|r| r.id).collect();
+        excluded.insert(root);
+        let page_objects: Vec<Vec<ObjNr>> = pages.iter().map(|&page| reachable(&objects, page, &excluded)).collect();
+        let mut users: HashMap<ObjNr, usize> = HashMap::new();
+        for &id in page_objects.iter().flatten() {
+            *users.entry(id).or_insert(0) += 1;
+        }
+        let shared_object = |id: &ObjNr| users[id] > 1;
+
+        let mut placed: HashSet<ObjNr> = page_objects[0].iter().cloned().collect();
+        placed.insert(root);
+        let first = page_objects[0].clone();
+        let mut sections = Vec::with_capacity(pages.len() - 1);
+        for objs in &page_objects[1 ..] {
+            let section: Vec<ObjNr> = objs.iter().cloned().filter(|id| !shared_object(id) && placed.insert(*id)).collect();
+            sections.push(section);
+        }
+        let mut shared = Vec::new();
+        for objs in &page_objects[1 ..] {
+            shared.extend(objs.iter().cloned().filter(|id| shared_object(id) && placed.insert(*id)));
+        }
+        let rest: Vec<ObjNr> = objects.keys().cloned().filter(|id| !placed.contains(id)).collect();
+
+        // the main section holds the objects after the first page, numbered from 1,
+        // the first page section the linearization dictionary, the catalog, the hint stream and the first page
+        let mut map = HashMap::new();
+        for &id in sections.iter().flatten().chain(&shared).chain(&rest) {
+            let new = map.len() as ObjNr + 1;
+            map.insert(id, new);
+        }
+        let main_size = map.len() as ObjNr + 1;
+        let (lin_id, catalog_id, hint_id) = (main_size, main_size + 1, main_size + 2);
+        map.insert(root, catalog_id);
+        for (i, &id) in first.iter().enumerate() {
+            map.insert(id, hint_id + 1 + i as ObjNr);
+        }
+        let size = hint_id + 1 + first.len() as ObjNr;
+
+        let serialize = |id: ObjNr| -> Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            write_object(&mut buf, map[&id], 0, &renumber(objects[&id].clone(), &map))?;
+            Ok(buf)
+        };
+        let catalog = serialize(root)?;
+        let first: Vec<Vec<u8>> = first.iter().map(|&id| serialize(id)).collect::<Result<_>>()?;
+        let sections: Vec<Vec<Vec<u8>>> = sections.iter()
+            .map(|section| section.iter().map(|&id| serialize(id)).collect::<Result<_>>())
+            .collect::<Result<_>>()?;
+        let shared_data: Vec<Vec<u8>> = shared.iter().map(|&id| serialize(id)).collect::<Result<_>>()?;
+        let rest: Vec<Vec<u8>> = rest.iter().map(|&id| serialize(id)).collect::<Result<_>>()?;
+
+        let mut trailer = self.new_trailer();
+        trailer.remove("Prev");
+        trailer.remove("Encrypt");
+        let mut trailer = renumber_dict(trailer, &map);
+        trailer.insert("Size".into(), Primitive::Integer(size as i32));
+        trailer.insert("Root".into(), PlainRef { id: catalog_id, gen: 0 }.into());
+
+        let header = self.header(false)?;
+        let params = Params { id: lin_id, first_page: hint_id + 1, num_pages: pages.len(), .. Params::default() };
+        let lin_len = params.to_bytes().len();
+        let first_xref_len = first_page_xref(lin_id, &vec![0; first.len() + 3], &trailer, 0)?.len();
+
+        // offsets after the hint stream are given as if it wasn't there
+        let xref_pos = header.len() + lin_len;
+        let catalog_pos = xref_pos + first_xref_len;
+        let hint_pos = catalog_pos + catalog.len();
+        let mut pos = hint_pos;
+        let mut offsets = |objs: &[Vec<u8>]| -> Vec<usize> {
+            objs.iter().map(|data| {
+                let offset = pos;
+                pos += data.len();
+                offset
+            }).collect()
+        };
+        let first_offsets = offsets(&first);
+        let section_offsets: Vec<Vec<usize>> = sections.iter().map(|s| offsets(s)).collect();
+        let shared_offsets = offsets(&shared_data);
+        let rest_offsets = offsets(&rest);
+        let main_xref_pos = pos;
+
+        // page offset hint table
+        let first_len = first.iter().map(|d| d.len()).sum::<usize>();
+        let mut page_info = vec![(first.len(), first_len)];
+        page_info.extend(sections.iter().map(|s| (s.len(), s.iter().map(|d| d.len()).sum())));
+        let group = |id: &ObjNr| match page_objects[0].iter().position(|i| i == id) {
+            Some(n) => n,
+            None => first.len() + shared.iter().position(|i| i == id).unwrap()
+        };
+        let page_shared: Vec<Vec<usize>> = page_objects.iter()
+            .map(|objs| objs.iter().filter(|id| shared_object(*id)).map(&group).collect())
+            .collect();
+        let mut hints = BitWriter::default();
+        let least_objects = page_info.iter().map(|p| p.0).min().unwrap();
+        let object_bits = bits(page_info.iter().map(|p| p.0 - least_objects).max().unwrap());
+        let least_length = page_info.iter().map(|p| p.1).min().unwrap();
+        let length_bits = bits(page_info.iter().map(|p| p.1 - least_length).max().unwrap());
+        let shared_count_bits = bits(page_shared.iter().map(|s| s.len()).max().unwrap());
+        let group_bits = bits(first.len() + shared.len());
+        hints.write(least_objects as u64, 32);
+        hints.write(hint_pos as u64, 32);
+        hints.write(object_bits as u64, 16);
+        hints.write(least_length as u64, 32);
+        hints.write(length_bits as u64, 16);
+        // content stream offsets and lengths are left out
+        hints.write(0, 32);
+        hints.write(0, 16);
+        hints.write(0, 32);
+        hints.write(0, 16);
+        hints.write(shared_count_bits as u64, 16);
+        hints.write(group_bits as u64, 16);
+        // no fractional positions of shared objects
+        hints.write(0, 16);
+        hints.write(1, 16);
+        for &(n, _) in &page_info {
+            hints.write((n - least_objects) as u64, object_bits);
+        }
+        hints.align();
+        for &(_, len) in &page_info {
+            hints.write((len - least_length) as u64, length_bits);
+        }
+        hints.align();
+        for groups in &page_shared {
+            hints.write(groups.len() as u64, shared_count_bits);
+        }
+        hints.align();
+        for &group in page_shared.iter().flatten() {
+            hints.write(group as u64, group_bits);
+        }
+        hints.align();
+
+        // shared object hint table, with a group for each object
+        let shared_table = hints.data.len();
+        let group_lengths: Vec<usize> = first.iter().chain(&shared_data).map(|d| d.len()).collect();
+        let least_group = group_lengths.iter().cloned().min().unwrap_or(0);
+        let group_length_bits = bits(group_lengths.iter().map(|l| l - least_group).max().unwrap_or(0));
+        hints.write(shared.first().map(|id| map[id]).unwrap_or(0), 32);
+        hints.write(shared_offsets.first().cloned().unwrap_or(0) as u64, 32);
+        hints.write(first.len() as u64, 32);
+        hints.write(group_lengths.len() as u64, 32);
+        hints.write(0, 16);
+        hints.write(least_group as u64, 32);
+        hints.write(group_length_bits as u64, 16);
+        for &len in &group_lengths {
+            hints.write((len - least_group) as u64, group_length_bits);
+        }
+        hints.align();
+        // no MD5 signatures
+        for _ in &group_lengths {
+            hints.write(0, 1);
+        }
+        hints.align();
+
+        let mut info = Dictionary::new();
+        info.insert("S".into(), Primitive::Integer(shared_table as i32));
+        info.insert("Filter".into(), "FlateDecode".into());
+        let mut hint = Vec::new();
+        write_object(&mut hint, hint_id, 0, &PdfStream { info, data: deflate_bytes_zlib(&hints.data).into() }.into())?;
+        let shift = |offset: usize| offset + hint.len();
+
+        let main_xref_pos = shift(main_xref_pos);
+        let mut main_xref = Vec::new();
+        write!(main_xref, "xref\n")?;
+        let entries: Vec<_> = section_offsets.iter().flatten().chain(&shared_offsets).chain(&rest_offsets)
+            .map(|&pos| Some((shift(pos), 0)))
+            .collect();
+        let mut all = vec![None];
+        all.extend(entries);
+        write_xref_section(&mut main_xref, 0, &all)?;
+        // the whitespace before the first entry
+        let first_entry = main_xref_pos + format!("xref\n0 {}\n", all.len()).len() - 1;
+        write!(main_xref, "trailer\n<</Size {}>>\nstartxref\n{}\n%%EOF\n", main_size, xref_pos)?;
+
+        let first_end = shift(hint_pos + first_len);
+        let params = Params {
+            length: main_xref_pos + main_xref.len(),
+            hint_pos,
+            hint_len: hint.len(),
+            first_page_end: first_end,
+            main_xref: first_entry,
+            .. params
+        };
+        let lin = params.to_bytes();
+        if lin.len() != lin_len {
+            bail!("the file is too large to be linearized");
+        }
+        let mut first_xref_offsets = vec![header.len(), catalog_pos, hint_pos];
+        first_xref_offsets.extend(first_offsets.iter().map(|&pos| shift(pos)));
+        let first_xref = first_page_xref(lin_id, &first_xref_offsets, &trailer, main_xref_pos)?;
+
+        out.write_all(&header)?;
+        out.write_all(&lin)?;
+        out.write_all(&first_xref)?;
+        out.write_all(&catalog)?;
+        out.write_all(&hint)?;
+        for data in first.iter().chain(sections.iter().flatten()).chain(&shared_data).chain(&rest) {
+            out.write_all(data)?;
+        }
+        out.write_all(&main_xref)?;
+        Ok(())
+    }
+}
+
+/// The linearization dictionary. Offsets are written with ten digits,
+/// so that its length is known before them.
+#[derive(Default, Copy, Clone)]
+struct Params {
+    id: ObjNr,
+    length: usize,
+    hint_pos: usize,
+    hint_len: usize,
+    first_page: ObjNr,
+    first_page_end: usize,
+    num_pages: usize,
+    main_xref: usize,
+}
+impl Params {
+    fn to_bytes(&self) -> Vec<u8> {
+        format!("{} 0 obj\n<</Linearized 1/L {:010}/H[{:010} {:010}]/O {}/E {:010}/N {}/T {:010}>>\nendobj\n",
+            self.id, self.length, self.hint_pos, self.hint_len, self.first_page,
+            self.first_page_end, self.num_pages, self.main_xref
+        ).into_bytes()
+    }
+}
+
+/// The cross-reference section of the objects from `first_id` on, at `offsets`,
+/// and the trailer pointing to the main section at `prev`.
+fn first_page_xref(first_id: ObjNr, offsets: &[usize], trailer: &Dictionary, prev: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    write!(buf, "xref\n")?;
+    let entries: Vec<_> = offsets.iter().map(|&pos| Some((pos, 0))).collect();
+    write_xref_section(&mut buf, first_id, &entries)?;
+    write!(buf, "trailer\n")?;
+    trailer.serialize(&mut buf)?;
+    // /Prev with a fixed width, before the closing >>
+    buf.truncate(buf.len() - 2);
+    write!(buf, "/Prev {:010}>>\nstartxref\n0\n%%EOF\n", prev)?;
+    Ok(buf)
+}
+
+/// The objects reachable from `start`, beginning with it, without going through `excluded`.
+fn reachable(objects: &BTreeMap<ObjNr, Primitive>, start: ObjNr, excluded: &HashSet<ObjNr>) -> Vec<ObjNr> {
+    let mut found = vec![start];
+    let mut seen: HashSet<ObjNr> = found.iter().cloned().collect();
+    let mut i = 0;
+    while i < found.len() {
+        if let Some(p) = objects.get(&found[i]) {
+            visit_refs(p, &mut |id| {
+                if !excluded.contains(&id) && objects.contains_key(&id) && seen.insert(id) {
+                    found.push(id);
+                }
+            });
+        }
+        i += 1;
+    }
+    found
+}
+
+fn visit_refs(p: &Primitive, f: &mut impl FnMut(ObjNr)) {
+    match *p {
+        Primitive::Reference(r) => f(r.id),
+        Primitive::Array(ref parts) => for p in parts {
+            visit_refs(p, f);
+        },
+        Primitive::Dictionary(ref dict) => for (_, p) in dict {
+            visit_refs(p, f);
+        },
+        Primitive::Stream(ref stream) => for (_, p) in &stream.info {
+            visit_refs(p, f);
+        },
+        _ => {}
+    }
+}
+
+/// `p` with the references changed to the new numbers in `map`, or `null` where there is none.
+fn renumber(p: Primitive, map: &HashMap<ObjNr, ObjNr>) -> Primitive {
+    match p {
+        Primitive::Reference(r) => match map.get(&r.id) {
+            Some(&id) => Primitive::Reference(PlainRef { id, gen: 0 }),
+            None => Primitive::Null
+        },
+        Primitive::Array(parts) => Primitive::Array(parts.into_iter().map(|p| renumber(p, map)).collect()),
+        Primitive::Dictionary(dict) => Primitive::Dictionary(renumber_dict(dict, map)),
+        Primitive::Stream(PdfStream { info, data }) => Primitive::Stream(PdfStream { info: renumber_dict(info, map), data }),
+        p => p
+    }
+}
+fn renumber_dict(dict: Dictionary, map: &HashMap<ObjNr, ObjNr>) -> Dictionary {
+    let mut new = Dictionary::new();
+    for (key, value) in dict {
+        new.insert(key, renumber(value, map));
+    }
+    new
+}
+
+/// Number of bits needed for `n`.
+fn bits(n: usize) -> u32 {
+    0usize.leading_zeros() - n.leading_zeros()
+}
+
+/// Writes numbers of any number of bits, most significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    /// bits used in the last byte
+    used: u32,
+}
+impl BitWriter {
+    fn write(&mut self, value: u64, n: u32) {
+        for i in (0 .. n).rev() {
+            if self.used % 8 == 0 {
+                self.data.push(0);
+                self.used = 0;
+            }
+            let bit = (value >> i & 1) as u8;
+            *self.data.last_mut().unwrap() |= bit << (7 - self.used);
+            self.used += 1;
+        }
+    }
+    fn align(&mut self) {
+        self.used = 0;
+    }
+}
@@ -0,0 +1,397 @@
+//! Writing complete documents and incremental updates.
+
+use std::io::Write;
+use std::path::Path;
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream, PdfString};
+use crate::backend::Backend;
+use crate::xref::{XRef, XRefSection, xref_stream};
+use crate::signature::{self, MdpPermission, SignaturePolicy};
+use crate::crypt::{Decoder, EncryptionOptions};
+use super::File;
+
+/// Number of objects packed into one object stream.
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// How `File::write_with_options` writes the document.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Pack all objects except streams into compressed object streams and
+    /// write the cross-reference table as a stream (requires PDF 1.5).
+    pub object_streams: bool,
+    /// Encrypt the document with these passwords and permissions.
+    pub encryption: Option<EncryptionOptions>,
+    /// Write a linearized ("fast web view") file, whose first page can be shown before the
+    /// rest has been read. Can't be combined with object streams or encryption.
+    pub linearize: bool,
+}
+
+impl<B: Backend> File<B> {
+    /// Writes the complete document with all changes to the file at `path`.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        self.write_to(&mut file)
+    }
+    /// Writes the complete document with all changes.
+    ///
+    /// Objects from object streams are written as regular objects, object and xref streams
+    /// are dropped. The output is not encrypted.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<()> {
+        self.write_with_options(out, &WriteOptions::default())
+    }
+    /// Writes the complete document with all changes to the file at `path`, as specified by `options`.
+    pub fn save_with_options(&self, path: impl AsRef<Path>, options: &WriteOptions) -> Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        self.write_with_options(&mut file, options)
+    }
+    /// Writes the complete document with all changes, as specified by `options`.
+    ///
+    /// The object and xref streams of the original file are dropped;
+    /// with `options.object_streams` new ones are created.
+    /// The encryption of the original file is not kept; `options.encryption` sets a new one.
+    pub fn write_with_options<W: Write>(&self, out: &mut W, options: &WriteOptions) -> Result<()> {
+        let signed = signature::signatures(&self.catalog_dict()?, self)?.len();
+        if signed > 0 {
+            self.signature_violation(format!("rewriting the file invalidates {} signature(s)", signed))?;
+        }
+
+        if options.linearize {
+            return self.write_linearized(out, options);
+        }
+
+        let mut buf = self.header(options.object_streams)?;
+
+        let encryption = match options.encryption {
+            Some(ref encryption) => {
+                let id = match self.trailer.id.first() {
+                    Some(id) => id.as_bytes().to_vec(),
+                    None => new_file_id(self.storage.refs.len())
+                };
+                let (dict, encoder) = encryption.encoder(&id)?;
+                Some((id, dict, encoder))
+            }
+            None => None
+        };
+        let encoder = encryption.as_ref().map(|&(_, _, ref encoder)| encoder);
+
+        let num_objects = self.storage.refs.len();
+        let mut entries = vec![XRef::Free { next_obj_nr: 0, gen_nr: 0 }; num_objects];
+        // objects waiting to be packed into the next object stream
+        let mut pending = Vec::new();
+        for id in 1 .. num_objects as ObjNr {
+            let (gen, mut primitive) = match self.object_for_writing(id)? {
+                Some(t) => t,
+                None => continue
+            };
+            let compress = options.object_streams && gen == 0 && match primitive {
+                Primitive::Stream(_) => false,
+                _ => true
+            };
+            if compress {
+                pending.push((id, primitive));
+                if pending.len() == OBJECTS_PER_STREAM {
+                    write_object_stream(&mut buf, &pending, &mut entries, encoder)?;
+                    pending.clear();
+                }
+                continue;
+            }
+            if let Some(encoder) = encoder {
+                encoder.encrypt_primitive(id, gen, &mut primitive)?;
+            }
+            entries[id as usize] = XRef::Raw { pos: buf.len(), gen_nr: gen };
+            write_object(&mut buf, id, gen, &primitive)?;
+        }
+        if pending.len() > 0 {
+            write_object_stream(&mut buf, &pending, &mut entries, encoder)?;
+        }
+
+        let mut trailer = self.new_trailer();
+        trailer.remove("Prev");
+        trailer.remove("Encrypt");
+        if let Some((file_id, dict, _)) = encryption {
+            let id = entries.len() as ObjNr;
+            entries.push(XRef::Raw { pos: buf.len(), gen_nr: 0 });
+            write_object(&mut buf, id, 0, &dict.into())?;
+            trailer.insert("Encrypt".into(), Primitive::Reference(PlainRef { id, gen: 0 }));
+            if trailer.get("ID").is_none() {
+                let file_id = Primitive::String(PdfString::new(file_id));
+                trailer.insert("ID".into(), Primitive::Array(vec![file_id.clone(), file_id]));
+            }
+        }
+        if options.object_streams {
+            write_xref_stream(&mut buf, trailer, &mut entries)?;
+        } else {
+            let xref_pos = buf.len();
+            let offsets: Vec<_> = entries.iter().map(|e| match *e {
+                XRef::Raw { pos, gen_nr } => Some((pos, gen_nr)),
+                _ => None
+            }).collect();
+            write!(buf, "xref\n")?;
+            write_xref_section(&mut buf, 0, &offsets)?;
+            write_trailer(&mut buf, trailer, xref_pos)?;
+        }
+
+        out.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Appends the changed objects as an incremental update to the original file and writes it to `path`.
+    pub fn save_incremental(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        self.write_incremental(&mut file)
+    }
+    /// Writes the original file followed by an incremental update with all changed objects,
+    /// a new xref section and a trailer pointing to the previous one.
+    /// If the newest revision has an xref stream, the update gets one as well.
+    /// The original bytes stay untouched.
+    pub fn write_incremental<W: Write>(&self, out: &mut W) -> Result<()> {
+        if self.storage.decoder.is_some() {
+            bail!("incremental updates of encrypted documents are not supported");
+        }
+        let original = self.storage.backend.read(..)?;
+        let prev = self.storage.backend.locate_xref_offset()?;
+        self.check_incremental_changes()?;
+
+        let mut buf = Vec::new();
+        if !original.ends_with(b"\n") && !original.ends_with(b"\r") {
+            buf.push(b'\n');
+        }
+        let base = original.len();
+
+        let ids: Vec<ObjNr> = self.storage.changes.keys().cloned().collect();
+        let mut offsets = Vec::with_capacity(ids.len());
+        for &id in &ids {
+            let (gen, primitive) = match self.object_for_writing(id)? {
+                Some(t) => t,
+                None => continue
+            };
+            offsets.push((id, base + buf.len(), gen));
+            write_object(&mut buf, id, gen, &primitive)?;
+        }
+
+        let mut trailer = self.new_trailer();
+        trailer.insert("Prev".into(), Primitive::Integer(prev as i32));
+
+        // continue with an xref stream if the file has one
+        let xref_stream_id = match self.trailer_dict.get("Type") {
+            Some(&Primitive::Name(ref t)) if t == "XRef" => Some(self.storage.refs.len() as ObjNr),
+            _ => None
+        };
+        let xref_pos = base + buf.len();
+        if let Some(id) = xref_stream_id {
+            offsets.push((id, xref_pos, 0));
+        }
+
+        // one subsection for each run of consecutive object numbers
+        let mut sections = Vec::new();
+        let mut start = 0;
+        while start < offsets.len() {
+            let mut end = start + 1;
+            while end < offsets.len() && offsets[end].0 == offsets[end - 1].0 + 1 {
+                end += 1;
+            }
+            let mut section = XRefSection::new(offsets[start].0 as u32);
+            for &(_, pos, gen) in &offsets[start .. end] {
+                section.add_inuse_entry(pos, gen);
+            }
+            sections.push(section);
+            start = end;
+        }
+
+        match xref_stream_id {
+            Some(id) => {
+                let stream = xref_stream(&sections, id + 1, trailer);
+                write_object(&mut buf, id, 0, &stream.into())?;
+                write!(buf, "startxref\n{}\n%%EOF\n", xref_pos)?;
+            }
+            None => {
+                write!(buf, "xref\n")?;
+                for section in &sections {
+                    let entries: Vec<_> = section.entries.iter().map(|e| match *e {
+                        XRef::Raw { pos, gen_nr } => Some((pos, gen_nr)),
+                        _ => None
+                    }).collect();
+                    write_xref_section(&mut buf, section.first_id as ObjNr, &entries)?;
+                }
+                write_trailer(&mut buf, trailer, xref_pos)?;
+            }
+        }
+
+        out.write_all(original)?;
+        out.write_all(&buf)?;
+        Ok(())
+    }
+    fn signature_violation(&self, msg: String) -> Result<()> {
+        match self.signature_policy {
+            SignaturePolicy::Ignore => Ok(()),
+            SignaturePolicy::Warn => {
+                warn!("{}", msg);
+                Ok(())
+            }
+            SignaturePolicy::Refuse => Err(PdfError::Other { msg })
+        }
+    }
+    /// Checks the changes of an incremental update against the signatures.
+    fn check_incremental_changes(&self) -> Result<()> {
+        if self.signature_policy == SignaturePolicy::Ignore {
+            return Ok(());
+        }
+        let changes = &self.storage.changes;
+        for &id in changes.keys() {
+            let is_signature = match self.storage.original(id)? {
+                Some(Primitive::Dictionary(ref dict)) => signature::is_signature(dict),
+                _ => false
+            };
+            if is_signature {
+                self.signature_violation(format!("changing the signature dictionary {} invalidates the signature", id))?;
+            }
+        }
+        if let Some(permission) = signature::doc_mdp(&self.catalog_dict()?, self)? {
+            for id in signature::forbidden_changes(changes, |id| self.storage.original(id), self, permission)? {
+                let msg = match permission {
+                    MdpPermission::NoChanges => format!("the certification signature does not permit any changes (object {})", id),
+                    _ => format!("change of object {} is not permitted by the certification signature ({:?})", id, permission)
+                };
+                self.signature_violation(msg)?;
+            }
+        }
+        Ok(())
+    }
+    /// The version line of the original file, at least 1.5 with `object_streams`,
+    /// and a comment marking the file as binary.
+    pub(super) fn header(&self, object_streams: bool) -> Result<Vec<u8>> {
+        let header = self.storage.backend.read(..)?;
+        let mut version = match header.iter().position(|&b| b == b'\n' || b == b'\r') {
+            Some(n) if header.starts_with(b"%PDF-") => header[.. n].to_vec(),
+            _ => b"%PDF-1.7".to_vec()
+        };
+        if object_streams && version.get(5 .. 8).map(|v| v < &b"1.5"[..]).unwrap_or(true) {
+            version = b"%PDF-1.5".to_vec();
+        }
+        // binary marker
+        version.extend_from_slice(b"\n%\xB5\xED\xAE\xFB\n");
+        Ok(version)
+    }
+    /// The trailer with the current size and without the entries specific to xref streams.
+    pub(super) fn new_trailer(&self) -> Dictionary {
+        let mut trailer = self.trailer_dict.clone();
+        for key in &["XRefStm", "Type", "W", "Index", "Filter", "DecodeParms", "Length", "DL"] {
+            trailer.remove(key);
+        }
+        trailer.insert("Size".into(), Primitive::Integer(self.storage.refs.len() as i32));
+        trailer
+    }
+    /// The object `id` as it should be written, with its generation number.
+    /// `None` if the object is not in use or is only an internal structure of the file.
+    pub(super) fn object_for_writing(&self, id: ObjNr) -> Result<Option<(GenNr, Primitive)>> {
+        if self.storage.encrypt_id == Some(id) {
+            return Ok(None);
+        }
+        let xref = self.storage.refs.get(id)?;
+        let gen = match xref {
+            XRef::Raw { gen_nr, .. } => gen_nr,
+            _ => 0
+        };
+        let primitive = match self.storage.changes.get(&id) {
+            Some(p) => p.clone(),
+            None => match xref {
+                XRef::Raw { .. } | XRef::Stream { .. } => self.resolve(PlainRef { id, gen })?,
+                _ => return Ok(None)
+            }
+        };
+        if let Primitive::Stream(ref stream) = primitive {
+            match stream.info.get("Type") {
+                Some(&Primitive::Name(ref t)) if t == "ObjStm" || t == "XRef" => return Ok(None),
+                _ => {}
+            }
+        }
+        Ok(Some((gen, primitive)))
+    }
+}
+
+/// A file identifier for documents without one.
+fn new_file_id(num_objects: usize) -> Vec<u8> {
+    let time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    let mut hash = md5::Context::new();
+    hash.consume(time.as_nanos().to_le_bytes());
+    hash.consume(num_objects.to_le_bytes());
+    hash.compute().to_vec()
+}
+
+pub(super) fn write_object(out: &mut Vec<u8>, id: ObjNr, gen: GenNr, primitive: &Primitive) -> Result<()> {
+    write!(out, "{} {} obj\n", id, gen)?;
+    primitive.serialize(out)?;
+    write!(out, "\nendobj\n")?;
+    Ok(())
+}
+
+/// Packs `objects` into a new object stream, numbered after the last entry of `entries`,
+/// and records where the objects went.
+fn write_object_stream(out: &mut Vec<u8>, objects: &[(ObjNr, Primitive)], entries: &mut Vec<XRef>, encoder: Option<&Decoder>) -> Result<()> {
+    let id = entries.len() as ObjNr;
+    // pairs of object number and offset, followed by the objects
+    let mut data = Vec::new();
+    let mut body = Vec::new();
+    for (index, &(obj_id, ref primitive)) in objects.iter().enumerate() {
+        write!(data, "{} {} ", obj_id, body.len())?;
+        primitive.serialize(&mut body)?;
+        body.push(b'\n');
+        entries[obj_id as usize] = XRef::Stream { stream_id: id, index };
+    }
+    let first = data.len();
+    data.extend_from_slice(&body);
+
+    let mut info = Dictionary::new();
+    info.insert("Type".into(), "ObjStm".into());
+    info.insert("N".into(), Primitive::Integer(objects.len() as i32));
+    info.insert("First".into(), Primitive::Integer(first as i32));
+    info.insert("Filter".into(), "FlateDecode".into());
+    let mut stream = PdfStream { info, data: deflate_bytes_zlib(&data).into() }.into();
+    // the objects in it are encrypted as part of the stream
+    if let Some(encoder) = encoder {
+        encoder.encrypt_primitive(id, 0, &mut stream)?;
+    }
+
+    entries.push(XRef::Raw { pos: out.len(), gen_nr: 0 });
+    write_object(out, id, 0, &stream)
+}
+
+/// Writes `entries` and the xref stream itself as a cross-reference stream with `trailer` as its dictionary.
+fn write_xref_stream(out: &mut Vec<u8>, trailer: Dictionary, entries: &mut Vec<XRef>) -> Result<()> {
+    let id = entries.len() as ObjNr;
+    let xref_pos = out.len();
+    entries.push(XRef::Raw { pos: xref_pos, gen_nr: 0 });
+    let section = XRefSection { first_id: 0, entries: entries.clone() };
+    let stream = xref_stream(&[section], entries.len() as ObjNr, trailer);
+    write_object(out, id, 0, &stream.into())?;
+    write!(out, "startxref\n{}\n%%EOF\n", xref_pos)?;
+    Ok(())
+}
+
+fn write_trailer(out: &mut Vec<u8>, trailer: Dictionary, xref_pos: usize) -> Result<()> {
+    write!(out, "trailer\n")?;
+    trailer.serialize(out)?;
+    write!(out, "\nstartxref\n{}\n%%EOF\n", xref_pos)?;
+    Ok(())
+}
+
+/// Writes one classic xref subsection starting at `first_id`. Unused entries become free.
+pub(super) fn write_xref_section(out: &mut Vec<u8>, first_id: ObjNr, offsets: &[Option<(usize, GenNr)>]) -> Result<()> {
+    write!(out, "{} {}\n", first_id, offsets.len())?;
+    // free entries form a linked list
+    let mut next_free = (0 .. offsets.len()).filter(|&i| offsets[i].is_none()).skip(1);
+    for (i, entry) in offsets.iter().enumerate() {
+        match *entry {
+            Some((pos, gen)) => write!(out, "{:010} {:05} n\r\n", pos, gen)?,
+            None => {
+                let next = next_free.next().map(|n| n as ObjNr + first_id).unwrap_or(0);
+                let gen = if first_id + i as ObjNr == 0 { 65535 } else { 0 };
+                write!(out, "{:010} {:05} f\r\n", next, gen)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,16 @@
 use crate::object::*;
 use crate::primitive::*;
 use crate::error::*;
-use crate::encoding::Encoding;
+use crate::encoding::{CMap, Decoder, Encoding, ToUnicodeMap};
 use std::io;
-use std::rc::Rc;
+use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use crate::content::{Content, Op};
+use crate::text::Matrix;
+
+pub mod standard;
+#[cfg(feature = "write")]
+pub mod embed;
 
 #[allow(non_upper_case_globals, dead_code)] 
 mod flags {
@@ -34,6 +41,11 @@ pub struct Font {
     pub subtype: FontType,
     pub name: String,
     pub data: FontData,
+    /// `/Encoding`, or the `/BaseEncoding` of an encoding dictionary
+    encoding: Option<Encoding>,
+    /// `/Differences` of an encoding dictionary, sorted by code
+    differences: Vec<(u8, String)>,
+    to_unicode: Option<ToUnicodeMap>,
     //_other: Dictionary
 }
 
@@ -44,6 +56,7 @@ pub enum FontData {
     TrueType(TFont),
     CIDFontType0(CIDFont),
     CIDFontType2(CIDFont),
+    Type3(Type3Font),
     Other(Dictionary),
     Standard(&'static str)
 }
@@ -76,9 +89,38 @@ impl Object for Font {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         let mut dict = p.to_dictionary(resolve)?;
         dict.expect("Font", "Type", "Font", true)?;
-        let base_font = dict.require("Font", "BaseFont")?.to_name()?;
         let subtype = FontType::from_primitive(dict.require("Font", "Subtype")?, resolve)?;
+        let base_font = match (subtype, dict.remove("BaseFont")) {
+            (_, Some(name)) => name.to_name()?,
+            // Type3 fonts may only have a /Name
+            (FontType::Type3, None) => match dict.get("Name") {
+                Some(name) => name.clone().to_name()?,
+                None => "Type3".into()
+            },
+            (_, None) => return Err(PdfError::MissingEntry { typ: "Font", field: "BaseFont".into() })
+        };
         dbg!(&dict);
+        let to_unicode = match dict.get("ToUnicode") {
+            Some(p) => match Stream::<()>::from_primitive(p.clone(), resolve).and_then(|s| Ok(ToUnicodeMap::parse(s.data()?))) {
+                Ok(cmap) => Some(cmap),
+                Err(e) => {
+                    resolve.warn(format!("/ToUnicode of {}: {}", base_font, e));
+                    None
+                }
+            },
+            None => None
+        };
+        let (encoding, differences) = match (subtype, dict.get("Encoding")) {
+            // the CMap of a composite font
+            (FontType::Type0, _) | (_, None) => (None, Vec::new()),
+            (_, Some(p)) => match read_encoding(p.clone(), resolve) {
+                Ok(encoding) => encoding,
+                Err(e) => {
+                    resolve.warn(format!("/Encoding of {}: {}", base_font, e));
+                    (None, Vec::new())
+                }
+            }
+        };
         //let _other = dict.clone();
         let data = match STANDARD_FONTS.iter().filter(|&(name, _)| *name == base_font).next() {
             Some((_, filename)) => {
@@ -90,6 +132,7 @@ impl Object for Font {
                 FontType::TrueType => FontData::TrueType(TFont::from_dict(dict, resolve)?),
                 FontType::CIDFontType0 => FontData::CIDFontType0(CIDFont::from_dict(dict, resolve)?),
                 FontType::CIDFontType2 => FontData::CIDFontType2(CIDFont::from_dict(dict, resolve)?),
+                FontType::Type3 => FontData::Type3(Type3Font::from_dict(dict, resolve)?),
                 _ => FontData::Other(dict)
             }
         };
@@ -98,6 +141,9 @@ impl Object for Font {
             subtype,
             name: base_font,
             data,
+            encoding,
+            differences,
+            to_unicode,
             //_other
         })
     }
@@ -110,6 +156,13 @@ impl Font {
             _ => None
         }
     }
+    /// The built-in metrics of a standard 14 font.
+    pub fn standard_metrics(&self) -> Option<&'static standard::Metrics> {
+        match self.data {
+            FontData::Standard(_) => standard::metrics(&self.name),
+            _ => None
+        }
+    }
     pub fn embedded_data(&self) -> Option<Result<&[u8]>> {
         match self.data {
             FontData::Type0(ref t) => t.descendant_fonts.get(0).and_then(|f| f.embedded_data()),
@@ -119,15 +172,86 @@ impl Font {
         }
     }
     pub fn encoding(&self) -> &Encoding {
-        dbg!(&self.data);
-        if let Some(ref info) = self.info() {
-            match info.encoding {
-                Some(ref encoding) => encoding,
-                _ if info.font_descriptor.flags & flags::Symbolic != 0 => &Encoding::SymbolEncoding,
-                _ => &Encoding::StandardEncoding
-            }
+        match self.encoding {
+            Some(ref encoding) => encoding,
+            None if self.is_symbolic() => &Encoding::SymbolEncoding,
+            None => &Encoding::StandardEncoding
+        }
+    }
+    /// The glyph names that replace those of the base encoding, by code.
+    pub fn differences(&self) -> &[(u8, String)] {
+        &self.differences
+    }
+    /// How the codes of a simple font map to glyph names and text, `None` for other fonts.
+    ///
+    /// Without an `/Encoding` or `/BaseEncoding`, nonsymbolic fonts use StandardEncoding
+    /// and symbolic fonts the built-in encoding of their font program.
+    /// `/Differences` apply to either.
+    pub fn decoder(&self) -> Option<Decoder> {
+        match self.data {
+            FontData::Type1(_) | FontData::TrueType(_) | FontData::Type3(_) | FontData::Standard(_) => {}
+            _ => return None
+        }
+        let decoder = match self.encoding {
+            Some(ref encoding) => Decoder::new(encoding),
+            None if self.is_symbolic() => self.built_in_decoder(),
+            None => Decoder::new(&Encoding::StandardEncoding)
+        };
+        Some(decoder.with_differences(self.differences.iter().map(|(code, name)| (*code, name.as_str()))))
+    }
+    /// Whether the font has glyphs outside the standard Latin character set, by the
+    /// flags of its descriptor.
+    pub fn is_symbolic(&self) -> bool {
+        match self.data {
+            FontData::Standard(_) => self.name == "Symbol" || self.name == "ZapfDingbats",
+            FontData::Type1(ref info) | FontData::TrueType(ref info) => info.font_descriptor.flags & flags::Symbolic != 0,
+            _ => false
+        }
+    }
+    /// The built-in encoding of a symbolic font. Codes are taken as they are if it is not known.
+    fn built_in_decoder(&self) -> Decoder {
+        let type1 = match self.data {
+            FontData::Type1(ref info) => info.font_descriptor.font_file.as_ref().and_then(|s| s.data().ok()),
+            _ => None
+        };
+        if let Some(decoder) = type1.and_then(type1_encoding) {
+            return decoder;
+        }
+        // without the tag of a subset, like `ABCDEF+`
+        let name = match self.name.find('+') {
+            Some(6) => &self.name[7 ..],
+            _ => &self.name[..]
+        };
+        if name.starts_with("Symbol") {
+            Decoder::new(&Encoding::SymbolEncoding)
+        } else if name.starts_with("ZapfDingbats") {
+            Decoder::zapf_dingbats()
         } else {
-            &Encoding::StandardEncoding
+            Decoder::new(&Encoding::None)
+        }
+    }
+    /// Whether the codes of this font are mapped by the built-in encoding of its font program,
+    /// as it is a simple symbolic font without `/Encoding`.
+    pub fn uses_built_in_encoding(&self) -> bool {
+        self.info().is_some() && self.encoding.is_none() && self.is_symbolic()
+    }
+    /// The `/ToUnicode` CMap, which maps character codes to text.
+    pub fn to_unicode(&self) -> Option<&ToUnicodeMap> {
+        self.to_unicode.as_ref()
+    }
+    /// The CMap of a composite font, which maps its codes to CIDs.
+    pub fn cmap(&self) -> Option<&CMap> {
+        match self.data {
+            FontData::Type0(ref t0) => Some(&t0.encoding),
+            _ => None
+        }
+    }
+    /// The CIDFont of a composite font, or the font itself if it is one.
+    pub fn cid_font(&self) -> Option<&CIDFont> {
+        match self.data {
+            FontData::Type0(ref t0) => t0.descendant_fonts.get(0).and_then(|f| f.cid_font()),
+            FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => Some(cid),
+            _ => None
         }
     }
     pub fn info(&self) -> Option<&TFont> {
@@ -139,7 +263,10 @@ impl Font {
     }
     pub fn widths(&self) -> Result<Option<[f32; 256]>> {
         match self.data {
-            FontData::Type0(ref t0) => t0.descendant_fonts[0].widths(),
+            FontData::Type0(ref t0) => match t0.descendant_fonts.get(0) {
+                Some(font) => font.widths(),
+                None => Ok(None)
+            },
             FontData::Type1(ref info) | FontData::TrueType(ref info) => {
                 let mut widths = [0.0; 256];
                 widths[info.first_char as usize .. info.first_char as usize + info.widths.len()]
@@ -147,31 +274,136 @@ impl Font {
                 Ok(Some(widths))
             },
             FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => {
-                let mut widths = [cid.default_width; 256];
-                let mut iter = cid.widths.iter();
-                while let Some(ref p) = iter.next() {
-                    let c1 = p.as_integer()? as usize;
-                    match iter.next() {
-                        Some(&Primitive::Array(ref array)) => {
-                            for (i, w) in array.iter().enumerate() {
-                                widths[c1 + i] = w.as_number()?;
-                            }
-                        },
-                        Some(&Primitive::Integer(c2)) => {
-                            let w = iter.next()?.as_number()?;
-                            for c in (c1 as usize) ..= (c2 as usize) {
-                                widths[c] = w;
-                            }
-                        },
-                        p => return Err(PdfError::Other { msg: format!("unexpected primitive in W array: {:?}", p) })
-                    }
+                let mut widths = [0.0; 256];
+                for (code, w) in widths.iter_mut().enumerate() {
+                    *w = cid.width(code as u32);
+                }
+                Ok(Some(widths))
+            },
+            FontData::Type3(ref t3) => {
+                // in glyph space
+                let scale = t3.font_matrix().a * 1000.;
+                let mut widths = [0.0; 256];
+                for (w, &width) in widths[t3.first_char.max(0).min(256) as usize ..].iter_mut().zip(&t3.widths) {
+                    *w = width * scale;
+                }
+                Ok(Some(widths))
+            },
+            FontData::Standard(_) if self.standard_metrics().is_some() => {
+                let font_widths = FontWidths::from_font(self)?;
+                let mut widths = [0.0; 256];
+                for (code, w) in widths.iter_mut().enumerate() {
+                    *w = font_widths.get(code as u8);
                 }
                 Ok(Some(widths))
             },
             _ => Ok(None)
         }
     }
+    /// The width of `text` at font size `size`, in text space units.
+    /// Build a `FontWidths` once to measure a lot of text.
+    pub fn measure(&self, text: &str, size: f32) -> Result<f32> {
+        Ok(FontWidths::from_font(self)?.measure(text, size))
+    }
+}
+
+/// The widths of the glyphs of a simple font, and the code that shows each character,
+/// for line breaking and alignment when building content streams.
+///
+/// ```ignore
+/// let widths = FontWidths::new(&Decoder::new(&Encoding::WinAnsiEncoding))
+///     .widths(32, &[278., 278., 355.])
+///     .default_width(556.);
+/// let width = widths.measure("Hello!", 12.);
+/// content.show_text(widths.encode("Hello!"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FontWidths {
+    /// in thousandths of an em, by code
+    widths: [Option<f32>; 256],
+    /// the width of codes without one
+    default_width: f32,
+    codes: HashMap<char, u8>,
+}
+impl FontWidths {
+    /// The codes of `decoder`, with no widths yet.
+    pub fn new(decoder: &Decoder) -> FontWidths {
+        let mut codes = HashMap::new();
+        for code in 0 ..= 255 {
+            if let Some(c) = decoder.decode_byte(code) {
+                codes.entry(c).or_insert(code);
+            }
+        }
+        // StandardEncoding has the no-break space and the soft hyphen instead
+        for &(c, alt) in &[(' ', '\u{A0}'), ('-', '\u{AD}')] {
+            if let Some(&code) = codes.get(&alt) {
+                codes.entry(c).or_insert(code);
+            }
+        }
+        FontWidths { widths: [None; 256], default_width: 0., codes }
+    }
+    /// The `/Widths` and encoding of a simple font, or the built-in metrics of a standard font.
+    pub fn from_font(font: &Font) -> Result<FontWidths> {
+        let decoder = match font.decoder() {
+            Some(decoder) => decoder,
+            None => bail!("{} is not a simple font", font.name)
+        };
+        if let Some(metrics) = font.standard_metrics() {
+            let mut widths = FontWidths::new(&decoder);
+            for code in 0 ..= 255 {
+                if let Some(width) = decoder.glyph_name(code).and_then(|name| metrics.width(name)) {
+                    widths = widths.width(code, width);
+                }
+            }
+            return Ok(widths);
+        }
+        let info = match font.info() {
+            Some(info) => info,
+            None => bail!("no widths for {}", font.name)
+        };
+        let first_char = match info.first_char {
+            n @ 0 ..= 255 => n as u8,
+            n => bail!("/FirstChar {} of {}", n, font.name)
+        };
+        Ok(FontWidths::new(&decoder)
+            .widths(first_char, &info.widths)
+            .default_width(info.font_descriptor.missing_width))
+    }
+    /// Sets the width of `code`.
+    pub fn width(mut self, code: u8, width: f32) -> FontWidths {
+        self.widths[code as usize] = Some(width);
+        self
+    }
+    /// Sets the widths of the codes from `first_char` on, like `/FirstChar` and `/Widths`.
+    pub fn widths(mut self, first_char: u8, widths: &[f32]) -> FontWidths {
+        for (slot, &width) in self.widths[first_char as usize ..].iter_mut().zip(widths) {
+            *slot = Some(width);
+        }
+        self
+    }
+    /// Sets the width of codes without one, like `/MissingWidth`.
+    pub fn default_width(mut self, width: f32) -> FontWidths {
+        self.default_width = width;
+        self
+    }
+    /// The width of `code`, in thousandths of an em.
+    pub fn get(&self, code: u8) -> f32 {
+        self.widths[code as usize].unwrap_or(self.default_width)
+    }
+    /// The codes that show `text`. Characters without a code are left out.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        text.chars().filter_map(|c| self.codes.get(&c).cloned()).collect()
+    }
+    /// The width of `codes` at font size `size`, in text space units.
+    pub fn measure_codes(&self, codes: &[u8], size: f32) -> f32 {
+        codes.iter().map(|&code| self.get(code)).sum::<f32>() * size / 1000.
+    }
+    /// The width of `text` at font size `size`, in text space units, as shown by `encode(text)`.
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        self.measure_codes(&self.encode(text), size)
+    }
 }
+
 #[derive(Object, Debug)]
 pub struct TFont {
     #[pdf(key="Name")]
@@ -189,42 +421,292 @@ pub struct TFont {
     #[pdf(key="FontDescriptor")]
     font_descriptor: FontDescriptor,
     
-    #[pdf(key="Encoding")]
-    encoding: Option<Encoding>,
-    
     #[pdf(key="ToUnicode")]
     to_unicode: Option<Stream>
 }
 
+/// A font whose glyphs are drawn by content streams, the glyph procedures (9.6.5).
+#[derive(Object, Debug)]
+pub struct Type3Font {
+    #[pdf(key="FontBBox")]
+    pub font_bbox: Rect,
+
+    /// Maps glyph space to text space, see `font_matrix()`
+    #[pdf(key="FontMatrix")]
+    matrix: Vec<f32>,
+
+    /// The glyph procedures by glyph name
+    #[pdf(key="CharProcs")]
+    pub char_procs: BTreeMap<String, Stream>,
+
+    #[pdf(key="FirstChar")]
+    pub first_char: i32,
+
+    #[pdf(key="LastChar")]
+    pub last_char: i32,
+
+    /// In glyph space
+    #[pdf(key="Widths")]
+    pub widths: Vec<f32>,
+
+    /// Of the glyph procedures, which use those of the page (or form) without it. Not read,
+    /// as it may contain the font itself.
+    #[pdf(key="Resources")]
+    pub resources: Option<Primitive>,
+}
+impl Type3Font {
+    /// Maps glyph space to text space, usually scaling 1000 units to 1.
+    pub fn font_matrix(&self) -> Matrix {
+        match self.matrix[..] {
+            [a, b, c, d, e, f] => Matrix::new(a, b, c, d, e, f),
+            _ => Matrix::new(0.001, 0., 0., 0.001, 0., 0.)
+        }
+    }
+    /// The operations of the glyph procedure of the glyph `name`.
+    pub fn char_proc(&self, name: &str, resolve: &impl Resolve) -> Option<Result<Vec<Op>>> {
+        let stream = self.char_procs.get(name)?;
+        Some(stream.data().and_then(|data| Content::parse_ops(data, resolve)))
+    }
+}
+
+/// The encoding named by an `/Encoding` entry, or the `/BaseEncoding` and `/Differences`
+/// of an encoding dictionary.
+fn read_encoding(p: Primitive, resolve: &impl Resolve) -> Result<(Option<Encoding>, Vec<(u8, String)>)> {
+    match p.resolve(resolve)? {
+        Primitive::Dictionary(dict) => {
+            let base = match dict.get("BaseEncoding") {
+                Some(p) => Some(Encoding::from_primitive(p.clone(), resolve)?),
+                None => None
+            };
+            let differences = match dict.get("Differences") {
+                Some(p) => differences(p.clone(), resolve)?,
+                None => Vec::new()
+            };
+            Ok((base, differences))
+        }
+        p => Ok((Some(Encoding::from_primitive(p, resolve)?), Vec::new()))
+    }
+}
+
+/// A `/Differences` array: each code is followed by the glyph names of it and the next codes.
+fn differences(p: Primitive, resolve: &impl Resolve) -> Result<Vec<(u8, String)>> {
+    let mut differences: Vec<(u8, String)> = Vec::new();
+    let mut code = None;
+    for p in p.resolve(resolve)?.to_array(resolve)? {
+        match p {
+            Primitive::Integer(n) if (0 .. 256).contains(&n) => code = Some(n as u8),
+            Primitive::Integer(n) => bail!("code {} in /Differences", n),
+            Primitive::Name(name) => {
+                let c = match code {
+                    Some(c) => c,
+                    None => bail!("no code for /{} in /Differences", name)
+                };
+                match differences.binary_search_by_key(&c, |&(c, _)| c) {
+                    Ok(i) => differences[i].1 = name,
+                    Err(i) => differences.insert(i, (c, name))
+                }
+                code = c.checked_add(1);
+            }
+            p => return Err(PdfError::UnexpectedPrimitive { expected: "Integer or Name", found: p.get_debug_name() })
+        }
+    }
+    Ok(differences)
+}
+
+/// The encoding in the clear text part of the Type1 font program `data`: StandardEncoding,
+/// or an array filled with `dup code /name put`.
+fn type1_encoding(data: &[u8]) -> Option<Decoder> {
+    let clear = match data.windows(5).position(|w| w == b"eexec") {
+        Some(n) => &data[.. n],
+        None => data
+    };
+    let start = clear.windows(9).position(|w| w == b"/Encoding")? + 9;
+    // names can follow numbers without a space
+    let mut words = Vec::new();
+    for word in clear[start ..].split(|b| b.is_ascii_whitespace()).filter(|w| !w.is_empty()) {
+        let mut word = word;
+        while let Some(n) = word.iter().skip(1).position(|&b| b == b'/') {
+            words.push(&word[.. n + 1]);
+            word = &word[n + 1 ..];
+        }
+        words.push(word);
+    }
+    if words.first() == Some(&&b"StandardEncoding"[..]) {
+        return Some(Decoder::new(&Encoding::StandardEncoding));
+    }
+    let end = words.iter().position(|&w| w == b"def").unwrap_or(words.len());
+    let entries: Vec<(u8, &str)> = words[.. end].windows(4).filter_map(|w| match *w {
+        [b"dup", code, name, b"put"] if name.starts_with(b"/") => Some((
+            std::str::from_utf8(code).ok()?.parse().ok()?,
+            std::str::from_utf8(&name[1 ..]).ok()?
+        )),
+        _ => None
+    }).collect();
+    if entries.is_empty() {
+        return None;
+    }
+    Some(Decoder::from_glyph_names(entries))
+}
+
 #[derive(Object, Debug)]
 pub struct Type0Font {
     #[pdf(key="DescendantFonts")]
-    descendant_fonts: Vec<Rc<Font>>,
+    descendant_fonts: Vec<Arc<Font>>,
+    
+    /// Maps codes to CIDs
+    #[pdf(key="Encoding", default="CMap::identity(false)")]
+    pub encoding: CMap,
     
     #[pdf(key="ToUnicode")]
     to_unicode: Option<Stream>,
 }
 
+/// A font whose glyphs are selected by CIDs (9.7.4), the descendant of a Type0 font.
 #[derive(Object, Debug)]
 pub struct CIDFont {
     #[pdf(key="CIDSystemInfo")]
-    system_info: Dictionary,
+    pub system_info: CIDSystemInfo,
     
     #[pdf(key="FontDescriptor")]
     font_descriptor: FontDescriptor,
     
-    #[pdf(key="DW")]
-    default_width: f32,
+    #[pdf(key="DW", default="1000.")]
+    pub default_width: f32,
     
-    #[pdf(key="W")]
-    pub widths: Vec<Primitive>,
+    #[pdf(key="W", default="CidWidths::default()")]
+    pub widths: CidWidths,
 
-    #[pdf(key="CIDToGIDMap")]
-    map: Primitive,
+    /// Only for CIDFontType2 fonts
+    #[pdf(key="CIDToGIDMap", default="CidToGidMap::Identity")]
+    pub cid_to_gid: CidToGidMap,
     
     #[pdf(other)]
     _other: Dictionary
 }
+impl CIDFont {
+    /// The width of the glyph of `cid`, in thousandths of an em.
+    pub fn width(&self, cid: u32) -> f32 {
+        self.widths.get(cid).unwrap_or(self.default_width)
+    }
+    /// The glyph index of `cid` in the font program.
+    pub fn gid(&self, cid: u32) -> Option<u32> {
+        match self.cid_to_gid {
+            CidToGidMap::Identity => Some(cid),
+            CidToGidMap::Table(ref table) => table.get(cid as usize).map(|&gid| gid as u32)
+        }
+    }
+}
+
+/// The character collection of a CIDFont, like Adobe-Japan1-6.
+#[derive(Object, Debug, Clone)]
+pub struct CIDSystemInfo {
+    #[pdf(key="Registry")]
+    pub registry: PdfString,
+
+    #[pdf(key="Ordering")]
+    pub ordering: PdfString,
+
+    #[pdf(key="Supplement")]
+    pub supplement: i32,
+}
+
+/// `/W` of a CIDFont: the widths of glyphs by CID.
+#[derive(Debug, Clone, Default)]
+pub struct CidWidths {
+    /// First and last CID of a run of glyphs, and their widths. Either one for all of them,
+    /// or one for each.
+    ranges: Vec<(u32, u32, Vec<f32>)>,
+}
+impl CidWidths {
+    /// The width of the glyph of `cid`, if it is listed.
+    pub fn get(&self, cid: u32) -> Option<f32> {
+        self.ranges.iter()
+            .find(|&&(first, last, _)| first <= cid && cid <= last)
+            .map(|&(first, _, ref widths)| match widths.len() {
+                1 => widths[0],
+                _ => widths[(cid - first) as usize]
+            })
+    }
+}
+impl Object for CidWidths {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        write!(out, "[")?;
+        for &(first, last, ref widths) in &self.ranges {
+            match widths.len() {
+                1 => write!(out, "{} {} {} ", first, last, widths[0])?,
+                _ => {
+                    write!(out, "{} [", first)?;
+                    for w in widths {
+                        write!(out, "{} ", w)?;
+                    }
+                    write!(out, "] ")?;
+                }
+            }
+        }
+        write!(out, "]")?;
+        Ok(())
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let array = match p.resolve(resolve)? {
+            Primitive::Array(array) => array,
+            p => return Err(PdfError::UnexpectedPrimitive { expected: "Array", found: p.get_debug_name() })
+        };
+        let mut ranges = Vec::new();
+        let mut iter = array.into_iter();
+        while let Some(p) = iter.next() {
+            let first = p.as_integer()? as u32;
+            match iter.next() {
+                // c [w1 w2 …]
+                Some(Primitive::Array(widths)) => {
+                    let widths = widths.iter().map(|w| w.as_number()).collect::<Result<Vec<f32>>>()?;
+                    // a single width would be taken for the whole run
+                    if widths.len() == 1 {
+                        ranges.push((first, first, widths));
+                    } else if !widths.is_empty() {
+                        ranges.push((first, first + widths.len() as u32 - 1, widths));
+                    }
+                }
+                // c_first c_last w
+                Some(Primitive::Integer(last)) => match iter.next() {
+                    Some(w) => ranges.push((first, last as u32, vec![w.as_number()?])),
+                    None => bail!("W array ends after {} {}", first, last)
+                },
+                p => bail!("unexpected primitive in W array: {:?}", p)
+            }
+        }
+        Ok(CidWidths { ranges })
+    }
+}
+
+/// `/CIDToGIDMap` of a CIDFontType2 font.
+#[derive(Debug, Clone)]
+pub enum CidToGidMap {
+    /// CIDs are glyph indices
+    Identity,
+    /// The glyph index of each CID
+    Table(Vec<u16>),
+}
+impl Object for CidToGidMap {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        match *self {
+            CidToGidMap::Identity => write!(out, "/Identity")?,
+            CidToGidMap::Table(_) => bail!("CIDToGIDMap streams can't be written")
+        }
+        Ok(())
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Name(ref name) if name == "Identity" => Ok(CidToGidMap::Identity),
+            p @ Primitive::Stream(_) => {
+                let stream = Stream::<()>::from_primitive(p, resolve)?;
+                Ok(CidToGidMap::Table(stream.data()?.chunks(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).cloned().unwrap_or(0)]))
+                    .collect()))
+            }
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Stream", found: p.get_debug_name() })
+        }
+    }
+}
 
 
 #[derive(Object, Debug)]
@@ -331,3 +813,130 @@ pub enum FontStretch {
     ExtraExpanded,
     UltraExpanded
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn font(data: &[u8]) -> Font {
+        Font::from_primitive(parse(data, &NoResolve).unwrap(), &NoResolve).unwrap()
+    }
+
+    fn descriptor(flags: u32) -> String {
+        format!("/FontDescriptor << /FontName /F /Flags {} /FontBBox [0 0 1000 1000] \
+            /ItalicAngle 0 /Ascent 800 /Descent -200 /CapHeight 700 >>", flags)
+    }
+
+    #[test]
+    fn decoder_by_flags() {
+        let simple = |name: &str, rest: &str| font(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} \
+            /FirstChar 32 /LastChar 32 /Widths [250] {} >>", name, rest).as_bytes());
+        let decode = |font: &Font| font.decoder().unwrap().decode_bytes(b"a`");
+
+        // nonsymbolic fonts default to StandardEncoding
+        assert_eq!(decode(&simple("ABCDEF+Serif", &descriptor(32))), "a\u{2018}");
+        // symbolic fonts use their own encoding
+        assert_eq!(decode(&simple("ABCDEF+Symbol", &descriptor(4))), "\u{3B1}\u{F8E5}");
+        assert_eq!(decode(&simple("Pi", &descriptor(4))), "a`");
+        assert_eq!(decode(&font(b"<< /Type /Font /Subtype /Type1 /BaseFont /Symbol >>")), "\u{3B1}\u{F8E5}");
+        // unless the encoding dictionary names a base encoding
+        let with_base = format!("{} /Encoding << /BaseEncoding /StandardEncoding /Differences [97 /b] >>", descriptor(4));
+        assert_eq!(decode(&simple("ABCDEF+Symbol", &with_base)), "b\u{2018}");
+    }
+
+    #[test]
+    fn differences() {
+        let font = font(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding << \
+            /BaseEncoding /WinAnsiEncoding /Differences [39 /quoteright 128 /Adieresis /uni0416 96 /quoteleft] >> >>");
+        assert_eq!(font.differences(), &[
+            (39, "quoteright".into()), (96, "quoteleft".into()), (128, "Adieresis".into()), (129, "uni0416".into())
+        ]);
+        let decoder = font.decoder().unwrap();
+        assert_eq!(decoder.decode_bytes(b"'`\x80\x81\xe9"), "\u{2019}\u{2018}\u{C4}\u{416}\u{E9}");
+        assert_eq!(decoder.glyph_name(0x81), Some("uni0416"));
+        assert_eq!(decoder.glyph_name(0xe9), Some("eacute"));
+    }
+
+    #[test]
+    fn measure() {
+        let font = font(format!("<< /Type /Font /Subtype /TrueType /BaseFont /ABCDEF+Sans \
+            /Encoding /WinAnsiEncoding /FirstChar 32 /LastChar 34 /Widths [250 300 400] {} >>",
+            descriptor(32).replace(">>", "/MissingWidth 500 >>")).as_bytes());
+        assert_eq!(font.measure(" !\"", 10.).unwrap(), 9.5);
+        let widths = FontWidths::from_font(&font).unwrap();
+        assert_eq!(widths.encode("\u{20AC} \u{416}"), b"\x80 ");
+        assert_eq!(widths.measure("\u{20AC} ", 2.), 1.5);
+
+        let widths = FontWidths::new(&Decoder::new(&Encoding::StandardEncoding)).width(b' ', 250.);
+        assert_eq!(widths.encode("a b-"), b"a b-");
+        assert_eq!(widths.measure_codes(b"  ", 12.), 6.);
+    }
+
+    #[test]
+    fn standard_metrics() {
+        let helvetica = font(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>");
+        let widths = helvetica.widths().unwrap().unwrap();
+        assert_eq!((widths[b'A' as usize], widths[0xC4], widths[0x80], widths[0x07]), (667., 667., 556., 0.));
+        assert_eq!(helvetica.measure("Hi \u{20AC}", 10.).unwrap(), 17.78);
+        assert_eq!(helvetica.standard_metrics().unwrap().cap_height, 718);
+
+        let courier = font(b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier-Bold >>");
+        assert_eq!(courier.widths().unwrap().unwrap()[0xE9], 600.);
+        let dingbats = font(b"<< /Type /Font /Subtype /Type1 /BaseFont /ZapfDingbats >>");
+        assert_eq!(dingbats.widths().unwrap().unwrap()[b'!' as usize], 974.);
+        assert_eq!(standard::metrics("Symbol").unwrap().width("alpha"), Some(631.));
+        assert!(standard::metrics("Arial").is_none());
+    }
+
+    #[test]
+    fn type3() {
+        let font = font(b"<< /Type /Font /Subtype /Type3 /FontBBox [0 0 100 100] /FontMatrix [0.01 0 0 0.01 0 0] \
+            /CharProcs << /square << /Length 37 >> stream\n100 0 0 0 100 100 d1 0 0 100 100 re f\nendstream >> \
+            /Encoding << /Type /Encoding /Differences [65 /square] >> /FirstChar 65 /LastChar 65 /Widths [100] >>");
+        assert_eq!(font.name, "Type3");
+        assert_eq!(font.widths().unwrap().unwrap()[65], 1000.);
+        assert_eq!(font.decoder().unwrap().glyph_name(65), Some("square"));
+        let t3 = match font.data {
+            FontData::Type3(ref t3) => t3,
+            _ => panic!()
+        };
+        assert_eq!(t3.font_matrix().a, 0.01);
+        let ops = t3.char_proc("square", &NoResolve).unwrap().unwrap();
+        match ops[..] {
+            [Op::CharWidthBBox { .. }, Op::Rect { .. }, Op::Fill(_)] => {}
+            ref ops => panic!("{:?}", ops)
+        }
+        assert!(t3.char_proc("circle", &NoResolve).is_none());
+    }
+
+    #[test]
+    fn cid_widths() {
+        let type0 = font(format!("<< /Type /Font /Subtype /Type0 /BaseFont /ABCDEF+Mincho /Encoding /Identity-H \
+            /DescendantFonts [<< /Type /Font /Subtype /CIDFontType2 /BaseFont /ABCDEF+Mincho \
+            /CIDSystemInfo << /Registry (Adobe) /Ordering (Japan1) /Supplement 6 >> {} \
+            /DW 500 /W [1 [250 300] 1000 2000 1000] >>] >>", descriptor(4)).as_bytes());
+        let cid = type0.cid_font().unwrap();
+        assert_eq!(cid.system_info.ordering.as_bytes(), b"Japan1");
+        assert_eq!((cid.width(1), cid.width(2), cid.width(3)), (250., 300., 500.));
+        assert_eq!((cid.width(1500), cid.width(2001)), (1000., 500.));
+        assert_eq!(cid.gid(1500), Some(1500));
+        let mut out = Vec::new();
+        cid.cid_to_gid.serialize(&mut out).unwrap();
+        assert_eq!(out, b"/Identity");
+        assert!(CidToGidMap::Table(vec![0, 3]).serialize(&mut Vec::new()).is_err());
+        assert_eq!(type0.widths().unwrap().unwrap()[2], 300.);
+        assert_eq!(type0.cmap().unwrap().cids(b"\x07\xD0"), [(&b"\x07\xD0"[..], Some(2000))]);
+    }
+
+    #[test]
+    fn type1_built_in_encoding() {
+        let program = b"%!PS-AdobeFont-1.0: Pi 001.000\n/FontBBox {0 0 1000 1000} readonly def\n\
+            /Encoding 256 array\n0 1 255 {1 index exch /.notdef put} for\n\
+            dup 97 /alpha put\ndup 98/bullet put\nreadonly def\ncurrentfile eexec\n\x9e\x4f";
+        let decoder = type1_encoding(program).unwrap();
+        assert_eq!(decoder.decode_bytes(b"abc"), "\u{3B1}\u{2022}");
+        let decoder = type1_encoding(b"/Encoding StandardEncoding def currentfile eexec").unwrap();
+        assert_eq!(decoder.decode_bytes(b"`"), "\u{2018}");
+    }
+}
@@ -0,0 +1,719 @@
+//! Embedding TrueType and OpenType fonts into generated documents.
+//!
+//! The font becomes a composite font whose codes are the two byte glyph ids (`/Identity-H`),
+//! with a `/ToUnicode` map so that the text can be extracted again.
+//! TrueType outlines are subset to the glyphs of the given characters; the glyph ids stay the
+//! same, the other glyphs are left empty. OpenType fonts with CFF outlines are embedded whole,
+//! except CID-keyed ones, whose CIDs are not the glyph ids.
+//!
+//! ```ignore
+//! let font = file.embed_font(&data, text.chars())?;
+//! let mut fonts = Dictionary::new();
+//! fonts.insert("F1".into(), font.font.into());
+//! content.begin_text().set_font("F1", 12.).show_text(font.encode(text)).end_text();
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream, PdfString};
+use crate::backend::Backend;
+use crate::file::File;
+use super::flags;
+
+/// A font added with `File::embed_font`.
+#[derive(Debug, Clone)]
+pub struct EmbeddedFont {
+    /// The Type0 font, for the `/Font` resources of the pages that use it
+    pub font: PlainRef,
+    /// The base font name, with a subset tag if the font was subset
+    pub name: String,
+    glyphs: HashMap<char, u16>,
+    /// in thousandths of an em
+    widths: HashMap<u16, f32>,
+}
+impl EmbeddedFont {
+    /// The codes that show `text`. Characters that were not embedded are left out.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let mut codes = Vec::with_capacity(2 * text.len());
+        for gid in text.chars().filter_map(|c| self.glyphs.get(&c)) {
+            codes.extend_from_slice(&gid.to_be_bytes());
+        }
+        codes
+    }
+    /// The width of `text` at font size `size`, in text space units.
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        let width: f32 = text.chars()
+            .filter_map(|c| self.glyphs.get(&c))
+            .map(|gid| self.widths[gid])
+            .sum();
+        width * size / 1000.
+    }
+    /// Whether `c` can be shown with this font.
+    pub fn contains(&self, c: char) -> bool {
+        self.glyphs.contains_key(&c)
+    }
+}
+
+impl<B: Backend> File<B> {
+    /// Embeds the TrueType or OpenType font `data`, for showing `chars`.
+    /// Characters the font has no glyph for are ignored.
+    pub fn embed_font(&mut self, data: &[u8], chars: impl IntoIterator<Item=char>) -> Result<EmbeddedFont> {
+        let sfnt = Sfnt::parse(data)?;
+        let cff = sfnt.tables.contains_key(b"CFF ");
+        if cff && sfnt.cff_is_cid_keyed()? {
+            bail!("CID-keyed CFF fonts can't be embedded");
+        }
+        let cmap = sfnt.cmap()?;
+        let mut glyphs = HashMap::new();
+        for c in chars {
+            if let Some(gid) = cmap.lookup(c as u32)? {
+                glyphs.insert(c, gid);
+            }
+        }
+        let mut gids: BTreeSet<u16> = glyphs.values().cloned().collect();
+        // .notdef
+        gids.insert(0);
+
+        let head = sfnt.table(b"head")?;
+        let scale = 1000. / match u16_at(head, 18)? {
+            0 => bail!("unitsPerEm is 0"),
+            units => units as f32
+        };
+        let advances = sfnt.advances()?;
+        let widths: HashMap<u16, f32> = gids.iter()
+            .map(|&gid| (gid, advances.get(gid as usize).or(advances.last()).cloned().unwrap_or(0) as f32 * scale))
+            .collect();
+
+        let ps_name = sfnt.postscript_name().unwrap_or_else(|| "Font".into());
+        let (program, name) = if cff {
+            (data.to_vec(), ps_name)
+        } else {
+            (sfnt.subset(&gids)?, format!("{}+{}", subset_tag(&gids), ps_name))
+        };
+
+        let mut file_info = Dictionary::new();
+        file_info.insert("Filter".into(), "FlateDecode".into());
+        if cff {
+            file_info.insert("Subtype".into(), "OpenType".into());
+        } else {
+            file_info.insert("Length1".into(), Primitive::Integer(program.len() as i32));
+        }
+        let font_file = self.add(Primitive::Stream(PdfStream { info: file_info, data: deflate_bytes_zlib(&program).into() })).get_inner();
+        let descriptor = self.add(Primitive::Dictionary(sfnt.descriptor(&name, scale, font_file, cff)?)).get_inner();
+
+        let mut system_info = Dictionary::new();
+        system_info.insert("Registry".into(), PdfString::new(b"Adobe".to_vec()).into());
+        system_info.insert("Ordering".into(), PdfString::new(b"Identity".to_vec()).into());
+        system_info.insert("Supplement".into(), Primitive::Integer(0));
+
+        let mut cid_font = Dictionary::new();
+        cid_font.insert("Type".into(), "Font".into());
+        cid_font.insert("Subtype".into(), if cff { "CIDFontType0" } else { "CIDFontType2" }.into());
+        cid_font.insert("BaseFont".into(), name.as_str().into());
+        cid_font.insert("CIDSystemInfo".into(), Primitive::Dictionary(system_info));
+        cid_font.insert("FontDescriptor".into(), descriptor.into());
+        cid_font.insert("W".into(), cid_widths(&widths));
+        if !cff {
+            cid_font.insert("CIDToGIDMap".into(), "Identity".into());
+        }
+        let cid_font = self.add(Primitive::Dictionary(cid_font)).get_inner();
+
+        let to_unicode = to_unicode(&glyphs);
+        let mut to_unicode_info = Dictionary::new();
+        to_unicode_info.insert("Filter".into(), "FlateDecode".into());
+        let to_unicode = self.add(Primitive::Stream(PdfStream {
+            info: to_unicode_info,
+            data: deflate_bytes_zlib(to_unicode.as_bytes()).into()
+        })).get_inner();
+
+        let mut font = Dictionary::new();
+        font.insert("Type".into(), "Font".into());
+        font.insert("Subtype".into(), "Type0".into());
+        font.insert("BaseFont".into(), name.as_str().into());
+        font.insert("Encoding".into(), "Identity-H".into());
+        font.insert("DescendantFonts".into(), Primitive::Array(vec![cid_font.into()]));
+        font.insert("ToUnicode".into(), to_unicode.into());
+        let font = self.add(Primitive::Dictionary(font)).get_inner();
+
+        Ok(EmbeddedFont { font, name, glyphs, widths })
+    }
+}
+
+/// `/W` with a run for each range of consecutive glyph ids.
+fn cid_widths(widths: &HashMap<u16, f32>) -> Primitive {
+    let widths: BTreeMap<u16, f32> = widths.iter().map(|(&gid, &w)| (gid, w)).collect();
+    let mut array = Vec::new();
+    let mut run: Vec<Primitive> = Vec::new();
+    let mut next = None;
+    for (&gid, &width) in &widths {
+        if next != Some(gid) && !run.is_empty() {
+            array.push(Primitive::Array(run.split_off(0)));
+        }
+        if run.is_empty() {
+            array.push(Primitive::Integer(gid as i32));
+        }
+        run.push(Primitive::Number(width.round()));
+        next = gid.checked_add(1);
+    }
+    if !run.is_empty() {
+        array.push(Primitive::Array(run));
+    }
+    Primitive::Array(array)
+}
+
+/// A CMap from the glyph ids to the characters. If several characters share a glyph,
+/// the first of them is used.
+fn to_unicode(glyphs: &HashMap<char, u16>) -> String {
+    let mut chars = BTreeMap::new();
+    for (&c, &gid) in glyphs {
+        let entry = chars.entry(gid).or_insert(c);
+        if c < *entry {
+            *entry = c;
+        }
+    }
+    let mut cmap = String::from("/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+        /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+        /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+        1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n");
+    let chars: Vec<(u16, char)> = chars.into_iter().collect();
+    // at most 100 entries per block
+    for block in chars.chunks(100) {
+        writeln!(cmap, "{} beginbfchar", block.len()).unwrap();
+        for &(gid, c) in block {
+            write!(cmap, "<{:04X}> <", gid).unwrap();
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                write!(cmap, "{:04X}", unit).unwrap();
+            }
+            cmap.push_str(">\n");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMapResource defineresource pop\nend\nend\n");
+    cmap
+}
+
+/// Six capital letters that tell subsets of the same font apart.
+fn subset_tag(gids: &BTreeSet<u16>) -> String {
+    let mut data = Vec::with_capacity(2 * gids.len());
+    for gid in gids {
+        data.extend_from_slice(&gid.to_be_bytes());
+    }
+    let digest = md5::compute(&data);
+    digest[.. 6].iter().map(|&b| (b'A' + b % 26) as char).collect()
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    match data.get(pos .. pos + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => bail!("font data ends early")
+    }
+}
+fn i16_at(data: &[u8], pos: usize) -> Result<i16> {
+    u16_at(data, pos).map(|n| n as i16)
+}
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    match data.get(pos .. pos + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => bail!("font data ends early")
+    }
+}
+
+/// The first item of the CFF INDEX at `pos`, and where the INDEX ends.
+fn cff_index(data: &[u8], pos: usize) -> Result<(Option<&[u8]>, usize)> {
+    let count = u16_at(data, pos)? as usize;
+    if count == 0 {
+        return Ok((None, pos + 2));
+    }
+    let off_size = match data.get(pos + 2) {
+        Some(&n) if n >= 1 && n <= 4 => n as usize,
+        _ => bail!("invalid CFF INDEX")
+    };
+    let offset = |i: usize| -> Result<usize> {
+        let start = pos + 3 + i * off_size;
+        match data.get(start .. start + off_size) {
+            Some(b) => Ok(b.iter().fold(0, |n, &b| n << 8 | b as usize)),
+            None => bail!("font data ends early")
+        }
+    };
+    // offsets count from the byte before the data
+    let base = pos + 2 + (count + 1) * off_size;
+    let item = data.get(base + offset(0)? .. base + offset(1)?);
+    Ok((item, base + offset(count)?))
+}
+
+/// A Unicode subtable of `cmap`, in format 4 or 12.
+struct Cmap<'a> {
+    table: &'a [u8],
+}
+impl<'a> Cmap<'a> {
+    /// The glyph id of the code point `c`, `None` if the font has no glyph for it.
+    fn lookup(&self, c: u32) -> Result<Option<u16>> {
+        let table = self.table;
+        if u16_at(table, 0)? == 12 {
+            // the groups are sorted
+            let (mut low, mut high) = (0, u32_at(table, 12)? as usize);
+            while low < high {
+                let mid = (low + high) / 2;
+                let group = 16 + 12 * mid;
+                let (start, end) = (u32_at(table, group)?, u32_at(table, group + 4)?);
+                if c < start {
+                    high = mid;
+                } else if c > end {
+                    low = mid + 1;
+                } else {
+                    let gid = u32_at(table, group + 8)?.checked_add(c - start);
+                    return Ok(gid.filter(|&gid| gid != 0 && gid <= 0xFFFF).map(|gid| gid as u16));
+                }
+            }
+            return Ok(None);
+        }
+        if c >= 0xFFFF {
+            return Ok(None);
+        }
+        let c = c as u16;
+        let seg_count = u16_at(table, 6)? as usize / 2;
+        let ends = 14;
+        let starts = ends + 2 * seg_count + 2;
+        let deltas = starts + 2 * seg_count;
+        let range_offsets = deltas + 2 * seg_count;
+        // the first segment that ends at or after `c`
+        for i in 0 .. seg_count {
+            if u16_at(table, ends + 2 * i)? < c {
+                continue;
+            }
+            let start = u16_at(table, starts + 2 * i)?;
+            if start > c {
+                return Ok(None);
+            }
+            let delta = u16_at(table, deltas + 2 * i)?;
+            let range_offset = u16_at(table, range_offsets + 2 * i)? as usize;
+            let gid = match range_offset {
+                0 => c.wrapping_add(delta),
+                _ => match u16_at(table, range_offsets + 2 * i + range_offset + 2 * (c - start) as usize)? {
+                    0 => 0,
+                    gid => gid.wrapping_add(delta)
+                }
+            };
+            return Ok(Some(gid).filter(|&gid| gid != 0));
+        }
+        Ok(None)
+    }
+}
+
+/// The tables needed to show the glyphs of a TrueType font, the rest is left out of subsets.
+const SUBSET_TABLES: [&[u8; 4]; 9] = [b"head", b"hhea", b"maxp", b"hmtx", b"loca", b"glyf", b"cvt ", b"fpgm", b"prep"];
+
+/// A TrueType or OpenType font program.
+struct Sfnt<'a> {
+    tables: BTreeMap<[u8; 4], &'a [u8]>,
+}
+impl<'a> Sfnt<'a> {
+    fn parse(data: &'a [u8]) -> Result<Sfnt<'a>> {
+        match u32_at(data, 0)? {
+            0x00010000 | 0x74727565 | 0x4F54544F => {} // 1.0, 'true', 'OTTO'
+            0x74746366 => bail!("font collections can't be embedded"),
+            _ => bail!("not a TrueType or OpenType font")
+        }
+        let mut tables = BTreeMap::new();
+        for i in 0 .. u16_at(data, 4)? as usize {
+            let record = 12 + 16 * i;
+            let mut tag = [0; 4];
+            match data.get(record .. record + 4) {
+                Some(t) => tag.copy_from_slice(t),
+                None => bail!("font data ends early")
+            }
+            let offset = u32_at(data, record + 8)? as usize;
+            let len = u32_at(data, record + 12)? as usize;
+            match data.get(offset .. offset + len) {
+                Some(table) => tables.insert(tag, table),
+                None => bail!("table {} is out of bounds", String::from_utf8_lossy(&tag))
+            };
+        }
+        Ok(Sfnt { tables })
+    }
+    fn table(&self, tag: &[u8; 4]) -> Result<&'a [u8]> {
+        match self.tables.get(tag) {
+            Some(&table) => Ok(table),
+            None => bail!("no {} table", String::from_utf8_lossy(tag))
+        }
+    }
+    fn num_glyphs(&self) -> Result<usize> {
+        Ok(u16_at(self.table(b"maxp")?, 4)? as usize)
+    }
+
+    /// The best Unicode subtable of `cmap`.
+    fn cmap(&self) -> Result<Cmap<'a>> {
+        let cmap = self.table(b"cmap")?;
+        let mut best: Option<(u32, usize)> = None;
+        for i in 0 .. u16_at(cmap, 2)? as usize {
+            let record = 4 + 8 * i;
+            let platform = u16_at(cmap, record)?;
+            let encoding = u16_at(cmap, record + 2)?;
+            let offset = u32_at(cmap, record + 4)? as usize;
+            let rank = match (platform, encoding, u16_at(cmap, offset)?) {
+                (3, 10, 12) | (0, _, 12) => 3,
+                (3, 1, 4) => 2,
+                (0, _, 4) => 1,
+                _ => continue
+            };
+            if best.map_or(true, |(r, _)| rank > r) {
+                best = Some((rank, offset));
+            }
+        }
+        match best {
+            Some((_, offset)) => Ok(Cmap { table: &cmap[offset ..] }),
+            None => bail!("no Unicode cmap")
+        }
+    }
+    /// Whether the `CFF ` table is a CID-keyed font, which selects glyphs by CID instead of glyph id.
+    fn cff_is_cid_keyed(&self) -> Result<bool> {
+        let cff = self.table(b"CFF ")?;
+        let header_size = match cff.get(2) {
+            Some(&n) => n as usize,
+            None => bail!("font data ends early")
+        };
+        // the Name INDEX comes first, then the Top DICT INDEX
+        let (_, names_end) = cff_index(cff, header_size)?;
+        let top_dict = match cff_index(cff, names_end)? {
+            (Some(dict), _) => dict,
+            (None, _) => bail!("CFF without fonts")
+        };
+        // ROS has to be the first operator, so skip the operands before it
+        let mut i = 0;
+        while let Some(&b) = top_dict.get(i) {
+            match b {
+                28 => i += 3,
+                29 => i += 5,
+                30 => {
+                    // a real number, in nibbles up to 0xf
+                    i += 1;
+                    while let Some(&b) = top_dict.get(i) {
+                        i += 1;
+                        if b >> 4 == 0xf || b & 0xf == 0xf {
+                            break;
+                        }
+                    }
+                }
+                32 ..= 246 => i += 1,
+                247 ..= 254 => i += 2,
+                _ => break
+            }
+        }
+        Ok(top_dict.get(i .. i + 2) == Some(&[12, 30][..]))
+    }
+
+    /// The advance widths of all glyphs, in font units.
+    fn advances(&self) -> Result<Vec<u16>> {
+        let num_metrics = u16_at(self.table(b"hhea")?, 34)? as usize;
+        let hmtx = self.table(b"hmtx")?;
+        (0 .. num_metrics).map(|i| u16_at(hmtx, 4 * i)).collect()
+    }
+
+    /// The PostScript name from the `name` table, without characters that are not allowed in names.
+    fn postscript_name(&self) -> Option<String> {
+        let name = self.table(b"name").ok()?;
+        let strings = u16_at(name, 4).ok()? as usize;
+        for i in 0 .. u16_at(name, 2).ok()? as usize {
+            let record = 6 + 12 * i;
+            if u16_at(name, record + 6).ok()? != 6 {
+                continue;
+            }
+            let platform = u16_at(name, record).ok()?;
+            let len = u16_at(name, record + 8).ok()? as usize;
+            let offset = strings + u16_at(name, record + 10).ok()? as usize;
+            let data = name.get(offset .. offset + len)?;
+            let text: String = match platform {
+                // UTF-16
+                0 | 3 => data.chunks(2).filter(|c| c.len() == 2 && c[0] == 0).map(|c| c[1] as char).collect(),
+                _ => data.iter().map(|&b| b as char).collect()
+            };
+            let text: String = text.chars()
+                .filter(|&c| c.is_ascii_graphic() && !"[](){}<>/%".contains(c))
+                .collect();
+            if !text.is_empty() {
+                return Some(text);
+            }
+        }
+        None
+    }
+
+    /// The font descriptor, with values in thousandths of an em.
+    fn descriptor(&self, name: &str, scale: f32, font_file: PlainRef, cff: bool) -> Result<Dictionary> {
+        let head = self.table(b"head")?;
+        let hhea = self.table(b"hhea")?;
+        let bbox = [i16_at(head, 36)?, i16_at(head, 38)?, i16_at(head, 40)?, i16_at(head, 42)?];
+        let ascent = i16_at(hhea, 4)? as f32 * scale;
+        let descent = i16_at(hhea, 6)? as f32 * scale;
+        let (italic_angle, fixed_pitch) = match self.table(b"post") {
+            Ok(post) => (u32_at(post, 4)? as i32 as f32 / 65536., u32_at(post, 12)? != 0),
+            Err(_) => (0., false)
+        };
+        let os2 = self.table(b"OS/2").ok();
+        let cap_height = match os2 {
+            Some(os2) if u16_at(os2, 0)? >= 2 => i16_at(os2, 88)? as f32 * scale,
+            _ => ascent
+        };
+        let weight = match os2 {
+            Some(os2) => u16_at(os2, 4)? as f32,
+            None => 400.
+        };
+
+        // the glyphs are not in the standard Latin character set
+        let mut flags = flags::Symbolic;
+        if fixed_pitch {
+            flags |= flags::FixedPitch;
+        }
+        if italic_angle != 0. {
+            flags |= flags::Italic;
+        }
+
+        let mut descriptor = Dictionary::new();
+        descriptor.insert("Type".into(), "FontDescriptor".into());
+        descriptor.insert("FontName".into(), name.into());
+        descriptor.insert("Flags".into(), Primitive::Integer(flags as i32));
+        descriptor.insert("FontBBox".into(), Primitive::Array(
+            bbox.iter().map(|&n| Primitive::Number((n as f32 * scale).round())).collect()
+        ));
+        descriptor.insert("ItalicAngle".into(), Primitive::Number(italic_angle));
+        descriptor.insert("Ascent".into(), Primitive::Number(ascent.round()));
+        descriptor.insert("Descent".into(), Primitive::Number(descent.round()));
+        descriptor.insert("CapHeight".into(), Primitive::Number(cap_height.round()));
+        // fonts don't say, this is a common estimate from the weight
+        descriptor.insert("StemV".into(), Primitive::Number((50. + (weight / 65.).powi(2)).round()));
+        descriptor.insert(if cff { "FontFile3" } else { "FontFile2" }.into(), font_file.into());
+        Ok(descriptor)
+    }
+
+    /// A font program with the glyphs `gids` and the glyphs they are composed of.
+    /// The other glyphs are empty, so that the glyph ids don't change.
+    fn subset(&self, gids: &BTreeSet<u16>) -> Result<Vec<u8>> {
+        let num_glyphs = self.num_glyphs()?;
+        let head = self.table(b"head")?;
+        if head.len() < 54 {
+            bail!("head table is too short");
+        }
+        let loca = self.table(b"loca")?;
+        let glyf = self.table(b"glyf")?;
+        let long_offsets = i16_at(head, 50)? != 0;
+        let glyph = |gid: usize| -> Result<&[u8]> {
+            let (start, end) = if long_offsets {
+                (u32_at(loca, 4 * gid)? as usize, u32_at(loca, 4 * gid + 4)? as usize)
+            } else {
+                (2 * u16_at(loca, 2 * gid)? as usize, 2 * u16_at(loca, 2 * gid + 2)? as usize)
+            };
+            match glyf.get(start .. end) {
+                Some(data) => Ok(data),
+                None => bail!("glyph {} is out of bounds", gid)
+            }
+        };
+
+        // add the components of composite glyphs
+        let mut keep = BTreeSet::new();
+        let mut todo: Vec<u16> = gids.iter().cloned().filter(|&gid| (gid as usize) < num_glyphs).collect();
+        while let Some(gid) = todo.pop() {
+            if !keep.insert(gid) {
+                continue;
+            }
+            let data = glyph(gid as usize)?;
+            if data.len() < 10 || i16_at(data, 0)? >= 0 {
+                continue;
+            }
+            let mut pos = 10;
+            loop {
+                let flags = u16_at(data, pos)?;
+                let component = u16_at(data, pos + 2)?;
+                if (component as usize) < num_glyphs {
+                    todo.push(component);
+                }
+                pos += 4;
+                pos += if flags & 0x0001 != 0 { 4 } else { 2 }; // arguments are words
+                pos += match flags {
+                    f if f & 0x0008 != 0 => 2, // a scale
+                    f if f & 0x0040 != 0 => 4, // x and y scale
+                    f if f & 0x0080 != 0 => 8, // a 2x2 matrix
+                    _ => 0
+                };
+                if flags & 0x0020 == 0 { // more components
+                    break;
+                }
+            }
+        }
+
+        let mut new_glyf = Vec::new();
+        let mut new_loca = Vec::with_capacity(4 * (num_glyphs + 1));
+        for gid in 0 .. num_glyphs {
+            new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+            if keep.contains(&(gid as u16)) {
+                new_glyf.extend_from_slice(glyph(gid)?);
+                while new_glyf.len() % 4 != 0 {
+                    new_glyf.push(0);
+                }
+            }
+        }
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+        let mut new_head = head.to_vec();
+        // checkSumAdjustment is set once the file is complete
+        new_head[8 .. 12].copy_from_slice(&[0; 4]);
+        new_head[50 .. 52].copy_from_slice(&1u16.to_be_bytes());
+
+        let mut tables: BTreeMap<[u8; 4], &[u8]> = BTreeMap::new();
+        for &tag in SUBSET_TABLES.iter() {
+            let data = match tag {
+                b"head" => &new_head[..],
+                b"loca" => &new_loca[..],
+                b"glyf" => &new_glyf[..],
+                _ => match self.tables.get(tag) {
+                    Some(&data) => data,
+                    None => continue
+                }
+            };
+            tables.insert(*tag, data);
+        }
+        let mut font = write_sfnt(&tables);
+
+        let head_offset = tables.keys().position(|tag| tag == b"head").unwrap();
+        let head_offset = u32_at(&font, 12 + 16 * head_offset + 8)? as usize;
+        let adjustment = 0xB1B0AFBAu32.wrapping_sub(checksum(&font));
+        font[head_offset + 8 .. head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+        Ok(font)
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[.. chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// A TrueType font with `tables`, each padded to four bytes.
+fn write_sfnt(tables: &BTreeMap<[u8; 4], &[u8]>) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x00010000u32.to_be_bytes());
+    for &n in &[num_tables, search_range, entry_selector, num_tables * 16 - search_range] {
+        font.extend_from_slice(&n.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += (data.len() + 3) / 4 * 4;
+    }
+    for data in tables.values() {
+        font.extend_from_slice(data);
+        while font.len() % 4 != 0 {
+            font.push(0);
+        }
+    }
+    font
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Font;
+
+    /// A font with an empty .notdef, a simple glyph, 'A' made of it and 'B'.
+    fn test_font() -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18 .. 20].copy_from_slice(&1000u16.to_be_bytes());
+        head[40 .. 44].copy_from_slice(&[2, 0x58, 3, 0x20]); // xMax 600, yMax 800
+        let mut hhea = vec![0; 36];
+        hhea[4 .. 6].copy_from_slice(&800u16.to_be_bytes());
+        hhea[6 .. 8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34 .. 36].copy_from_slice(&4u16.to_be_bytes());
+        let maxp = [0, 0, 0x50, 0, 0, 4];
+        let hmtx = [1, 0xF4, 0, 0, 2, 0x58, 0, 0, 2, 0x58, 0, 0, 2, 0x26, 0, 0]; // 500, 600, 600, 550
+        let cmap = [
+            0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12,
+            0, 4, 0, 32, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0,
+            0, 0x42, 0xFF, 0xFF, 0, 0, // end codes
+            0, 0x41, 0xFF, 0xFF, // start codes
+            0xFF, 0xC1, 0, 1, // 'A' is glyph 2
+            0, 0, 0, 0,
+        ];
+        let simple = [0, 1, 0, 0, 0, 0, 0, 10, 0, 10, 0, 0, 0, 0, 1, 0];
+        let composite = [0xFF, 0xFF, 0, 0, 0, 0, 0, 10, 0, 10, 0, 3, 0, 1, 0, 0, 0, 0];
+        let mut glyf = Vec::new();
+        let mut loca = vec![0, 0, 0, 0];
+        for glyph in &[&simple[..], &composite[..], &simple[..]] {
+            glyf.extend_from_slice(glyph);
+            loca.extend_from_slice(&(glyf.len() as u16 / 2).to_be_bytes());
+        }
+        let mut tables: BTreeMap<[u8; 4], &[u8]> = BTreeMap::new();
+        tables.insert(*b"head", &head);
+        tables.insert(*b"hhea", &hhea);
+        tables.insert(*b"maxp", &maxp);
+        tables.insert(*b"hmtx", &hmtx);
+        tables.insert(*b"cmap", &cmap);
+        tables.insert(*b"loca", &loca);
+        tables.insert(*b"glyf", &glyf);
+        write_sfnt(&tables)
+    }
+
+    #[test]
+    fn embed_subset() {
+        let mut file = File::new().unwrap();
+        let embedded = file.embed_font(&test_font(), "AC".chars()).unwrap();
+        assert_eq!(embedded.encode("ABA"), [0, 2, 0, 2]);
+        assert_eq!(embedded.measure("AA", 10.), 12.);
+        assert!(embedded.name.ends_with("+Font") && embedded.name.len() == 11);
+
+        let font = file.get(Ref::<Font>::new(embedded.font)).unwrap();
+        assert_eq!(font.to_unicode().unwrap().get(&[0, 2]), Some("A"));
+        let cid = font.cid_font().unwrap();
+        assert_eq!((cid.width(0), cid.width(2)), (500., 600.));
+
+        // glyph 1 is kept as part of 'A', 'B' is left out
+        let program = font.embedded_data().unwrap().unwrap();
+        assert_eq!(checksum(program), 0xB1B0AFBA);
+        let sfnt = Sfnt::parse(program).unwrap();
+        assert!(!sfnt.tables.contains_key(b"cmap"));
+        let loca = sfnt.table(b"loca").unwrap();
+        let lengths: Vec<u32> = (0 .. 4).map(|i| u32_at(loca, 4 * i + 4).unwrap() - u32_at(loca, 4 * i).unwrap()).collect();
+        assert_eq!(lengths, [0, 16, 20, 0]);
+    }
+
+    #[test]
+    fn cmap_format_12() {
+        let mut table = vec![0, 12, 0, 0, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 2];
+        for &(start, end, gid) in &[(0x10u32, 0x20u32, 0xFFFF_FFF0u32), (0x41, 0x5A, 1)] {
+            for n in &[start, end, gid] {
+                table.extend_from_slice(&n.to_be_bytes());
+            }
+        }
+        let cmap = Cmap { table: &table };
+        assert_eq!(cmap.lookup(0x43).unwrap(), Some(3));
+        assert_eq!(cmap.lookup(0x5B).unwrap(), None);
+        // glyph ids past 0xFFFF and past u32::MAX
+        assert_eq!(cmap.lookup(0x10).unwrap(), None);
+        assert_eq!(cmap.lookup(0x20).unwrap(), None);
+    }
+
+    #[test]
+    fn cid_keyed_cff() {
+        let cff = |top_dict: &[u8]| {
+            let mut data = vec![1, 0, 4, 1, 0, 1, 1, 1, 2, b'F', 0, 1, 1, 1, top_dict.len() as u8 + 1];
+            data.extend_from_slice(top_dict);
+            data
+        };
+        let cid = cff(&[28, 1, 0, 139, 30, 0x1f, 12, 30]);
+        let plain = cff(&[139, 139, 139, 139, 5]);
+        let sfnt = |data: &[u8]| Sfnt { tables: vec![(*b"CFF ", data)].into_iter().collect() }.cff_is_cid_keyed().unwrap();
+        assert!(sfnt(&cid));
+        assert!(!sfnt(&plain));
+    }
+}
@@ -0,0 +1,251 @@
+//! The metrics of the standard 14 fonts from their Adobe AFM files, so that text in these
+//! fonts can be measured without a font program.
+//!
+//! The Latin fonts have the glyphs of StandardEncoding and WinAnsiEncoding.
+
+/// AFM metrics of one of the standard 14 fonts, in thousandths of an em.
+#[derive(Debug)]
+pub struct Metrics {
+    pub name: &'static str,
+    pub font_bbox: [i16; 4],
+    pub ascent: i16,
+    pub descent: i16,
+    pub cap_height: i16,
+    widths: Widths,
+}
+
+#[derive(Debug)]
+enum Widths {
+    /// every glyph of the Courier fonts
+    Fixed(u16),
+    /// by the glyph names in `LATIN`
+    Latin(&'static [u16; 170]),
+    /// sorted by glyph name
+    Named(&'static [(&'static str, u16)]),
+}
+
+impl Metrics {
+    /// The width of glyph `name`, if the font has it.
+    pub fn width(&self, name: &str) -> Option<f32> {
+        let width = match self.widths {
+            Widths::Fixed(width) => latin_index(name).map(|_| width),
+            Widths::Latin(widths) => latin_index(name).map(|i| widths[i]),
+            Widths::Named(widths) => widths.binary_search_by_key(&name, |&(n, _)| n).ok().map(|i| widths[i].1)
+        };
+        width.map(|w| w as f32)
+    }
+}
+
+/// The metrics of the standard font `base_font`, like `Helvetica-Bold`.
+pub fn metrics(base_font: &str) -> Option<&'static Metrics> {
+    METRICS.iter().find(|m| m.name == base_font)
+}
+
+fn latin_index(name: &str) -> Option<usize> {
+    let base = ACCENTED.iter().find(|&&(accented, _)| accented == name).map_or(name, |&(_, base)| base);
+    LATIN.iter().position(|&n| n == base)
+}
+
+macro_rules! metrics {
+    ($name:expr, [$($bbox:expr),*], $ascent:expr, $descent:expr, $cap_height:expr, $widths:expr) => {
+        Metrics { name: $name, font_bbox: [$($bbox),*], ascent: $ascent, descent: $descent, cap_height: $cap_height, widths: $widths }
+    }
+}
+
+static METRICS: [Metrics; 14] = [
+    metrics!("Courier", [-23, -250, 715, 805], 629, -157, 562, Widths::Fixed(600)),
+    metrics!("Courier-Bold", [-113, -250, 749, 801], 629, -157, 562, Widths::Fixed(600)),
+    metrics!("Courier-Oblique", [-27, -250, 849, 805], 629, -157, 562, Widths::Fixed(600)),
+    metrics!("Courier-BoldOblique", [-57, -250, 869, 801], 629, -157, 562, Widths::Fixed(600)),
+    metrics!("Helvetica", [-166, -225, 1000, 931], 718, -207, 718, Widths::Latin(&HELVETICA)),
+    metrics!("Helvetica-Bold", [-170, -228, 1003, 962], 718, -207, 718, Widths::Latin(&HELVETICA_BOLD)),
+    metrics!("Helvetica-Oblique", [-170, -225, 1116, 931], 718, -207, 718, Widths::Latin(&HELVETICA)),
+    metrics!("Helvetica-BoldOblique", [-174, -228, 1114, 962], 718, -207, 718, Widths::Latin(&HELVETICA_BOLD)),
+    metrics!("Times-Roman", [-168, -218, 1000, 898], 683, -217, 662, Widths::Latin(&TIMES_ROMAN)),
+    metrics!("Times-Bold", [-168, -218, 1000, 935], 683, -217, 676, Widths::Latin(&TIMES_BOLD)),
+    metrics!("Times-Italic", [-169, -217, 1010, 883], 683, -217, 653, Widths::Latin(&TIMES_ITALIC)),
+    metrics!("Times-BoldItalic", [-200, -218, 996, 921], 683, -217, 669, Widths::Latin(&TIMES_BOLDITALIC)),
+    // the symbol fonts have no ascender, descender and cap height, these are their bounding boxes
+    metrics!("Symbol", [-180, -293, 1090, 1010], 1010, -293, 1010, Widths::Named(&SYMBOL)),
+    metrics!("ZapfDingbats", [-1, -143, 981, 820], 820, -143, 820, Widths::Named(&ZAPF_DINGBATS)),
+];
+
+/// The glyphs of the Latin fonts, in the order of their widths in `HELVETICA` and the others.
+static LATIN: [&str; 170] = [
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quotesingle",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "grave", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "quoteleft",
+    "quoteright", "Euro", "quotesinglbase", "florin", "quotedblbase", "ellipsis", "dagger", "daggerdbl",
+    "circumflex", "perthousand", "guilsinglleft", "OE", "quotedblleft", "quotedblright", "bullet", "endash",
+    "emdash", "tilde", "trademark", "guilsinglright", "oe", "exclamdown", "cent", "sterling",
+    "currency", "yen", "brokenbar", "section", "dieresis", "copyright", "ordfeminine", "guillemotleft",
+    "logicalnot", "registered", "macron", "degree", "plusminus", "twosuperior", "threesuperior", "acute",
+    "mu", "paragraph", "periodcentered", "cedilla", "onesuperior", "ordmasculine", "guillemotright", "onequarter",
+    "onehalf", "threequarters", "questiondown", "AE", "Eth", "multiply", "Oslash", "Thorn",
+    "germandbls", "ae", "eth", "divide", "oslash", "thorn", "dotlessi", "Lslash",
+    "lslash", "fi", "fl", "fraction", "breve", "dotaccent", "ring", "hungarumlaut",
+    "ogonek", "caron",
+];
+
+static HELVETICA: [u16; 170] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, 222,
+    222, 556, 222, 556, 333, 1000, 556, 556, 333, 1000, 333, 1000, 333, 333, 350, 556,
+    1000, 333, 1000, 333, 944, 333, 556, 556, 556, 556, 260, 556, 333, 737, 370, 556,
+    584, 737, 333, 400, 584, 333, 333, 333, 556, 537, 278, 333, 333, 365, 556, 834,
+    834, 834, 611, 1000, 722, 584, 778, 667, 611, 889, 556, 584, 611, 556, 278, 556,
+    222, 500, 500, 167, 333, 333, 333, 333, 333, 333,
+];
+
+static HELVETICA_BOLD: [u16; 170] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, 278,
+    278, 556, 278, 556, 500, 1000, 556, 556, 333, 1000, 333, 1000, 500, 500, 350, 556,
+    1000, 333, 1000, 333, 944, 333, 556, 556, 556, 556, 280, 556, 333, 737, 370, 556,
+    584, 737, 333, 400, 584, 333, 333, 333, 611, 556, 278, 333, 333, 365, 556, 834,
+    834, 834, 611, 1000, 722, 584, 778, 667, 611, 889, 611, 584, 611, 611, 278, 611,
+    278, 611, 611, 167, 333, 333, 333, 333, 333, 333,
+];
+
+static TIMES_ROMAN: [u16; 170] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541, 333,
+    333, 500, 333, 500, 444, 1000, 500, 500, 333, 1000, 333, 889, 444, 444, 350, 500,
+    1000, 333, 980, 333, 722, 333, 500, 500, 500, 500, 200, 500, 333, 760, 276, 500,
+    564, 760, 333, 400, 564, 300, 300, 333, 500, 453, 250, 333, 300, 310, 500, 750,
+    750, 750, 444, 889, 722, 564, 722, 556, 500, 667, 500, 564, 500, 500, 278, 611,
+    278, 556, 556, 167, 333, 333, 333, 333, 333, 333,
+];
+
+static TIMES_BOLD: [u16; 170] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520, 333,
+    333, 500, 333, 500, 500, 1000, 500, 500, 333, 1000, 333, 1000, 500, 500, 350, 500,
+    1000, 333, 1000, 333, 722, 333, 500, 500, 500, 500, 220, 500, 333, 747, 300, 500,
+    570, 747, 333, 400, 570, 300, 300, 333, 556, 540, 250, 333, 300, 330, 500, 750,
+    750, 750, 500, 1000, 722, 570, 778, 611, 556, 722, 500, 570, 500, 556, 278, 667,
+    278, 556, 556, 167, 333, 333, 333, 333, 333, 333,
+];
+
+static TIMES_ITALIC: [u16; 170] = [
+    250, 333, 420, 500, 500, 833, 778, 214, 333, 333, 500, 675, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 675, 675, 675, 500,
+    920, 611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833, 667, 722,
+    611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556, 389, 278, 389, 422, 500,
+    333, 500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722, 500, 500,
+    500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389, 400, 275, 400, 541, 333,
+    333, 500, 333, 500, 556, 889, 500, 500, 333, 1000, 333, 944, 556, 556, 350, 500,
+    889, 333, 980, 333, 667, 389, 500, 500, 500, 500, 275, 500, 333, 760, 276, 500,
+    675, 760, 333, 400, 675, 300, 300, 333, 500, 523, 250, 333, 300, 310, 500, 750,
+    750, 750, 500, 889, 722, 675, 722, 611, 500, 667, 500, 675, 500, 500, 278, 556,
+    278, 500, 500, 167, 333, 333, 333, 333, 333, 333,
+];
+
+static TIMES_BOLDITALIC: [u16; 170] = [
+    250, 389, 555, 500, 500, 833, 778, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    832, 667, 667, 667, 722, 667, 667, 722, 778, 389, 500, 667, 611, 889, 722, 722,
+    611, 722, 667, 556, 611, 722, 667, 889, 667, 611, 611, 333, 278, 333, 570, 500,
+    333, 500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500, 278, 778, 556, 500,
+    500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389, 348, 220, 348, 570, 333,
+    333, 500, 333, 500, 500, 1000, 500, 500, 333, 1000, 333, 944, 500, 500, 350, 500,
+    1000, 333, 1000, 333, 722, 389, 500, 500, 500, 500, 220, 500, 333, 747, 266, 500,
+    606, 747, 333, 400, 570, 300, 300, 333, 576, 500, 250, 333, 300, 300, 500, 750,
+    750, 750, 500, 944, 722, 570, 722, 611, 500, 722, 500, 570, 500, 500, 278, 611,
+    278, 556, 556, 167, 333, 333, 333, 333, 333, 333,
+];
+
+/// Accented letters of the Latin fonts, which are as wide as the letter.
+static ACCENTED: [(&str, &str); 58] = [
+    ("Agrave", "A"), ("Aacute", "A"), ("Acircumflex", "A"), ("Atilde", "A"), ("Adieresis", "A"), ("Aring", "A"),
+    ("Ccedilla", "C"), ("Egrave", "E"), ("Eacute", "E"), ("Ecircumflex", "E"), ("Edieresis", "E"), ("Igrave", "I"),
+    ("Iacute", "I"), ("Icircumflex", "I"), ("Idieresis", "I"), ("Ntilde", "N"), ("Ograve", "O"), ("Oacute", "O"),
+    ("Ocircumflex", "O"), ("Otilde", "O"), ("Odieresis", "O"), ("Ugrave", "U"), ("Uacute", "U"), ("Ucircumflex", "U"),
+    ("Udieresis", "U"), ("Yacute", "Y"), ("Ydieresis", "Y"), ("Scaron", "S"), ("Zcaron", "Z"), ("agrave", "a"),
+    ("aacute", "a"), ("acircumflex", "a"), ("atilde", "a"), ("adieresis", "a"), ("aring", "a"), ("ccedilla", "c"),
+    ("egrave", "e"), ("eacute", "e"), ("ecircumflex", "e"), ("edieresis", "e"), ("igrave", "dotlessi"), ("iacute", "dotlessi"),
+    ("icircumflex", "dotlessi"), ("idieresis", "dotlessi"), ("ntilde", "n"), ("ograve", "o"), ("oacute", "o"), ("ocircumflex", "o"),
+    ("otilde", "o"), ("odieresis", "o"), ("ugrave", "u"), ("uacute", "u"), ("ucircumflex", "u"), ("udieresis", "u"),
+    ("yacute", "y"), ("ydieresis", "y"), ("scaron", "s"), ("zcaron", "z"),
+];
+
+static SYMBOL: [(&str, u16); 190] = [
+    ("Alpha", 722), ("Beta", 667), ("Chi", 722), ("Delta", 612), ("Epsilon", 611), ("Eta", 722), ("Euro", 750), ("Gamma", 603),
+    ("Ifraktur", 686), ("Iota", 333), ("Kappa", 722), ("Lambda", 686), ("Mu", 889), ("Nu", 722), ("Omega", 768), ("Omicron", 722),
+    ("Phi", 763), ("Pi", 768), ("Psi", 795), ("Rfraktur", 795), ("Rho", 556), ("Sigma", 592), ("Tau", 611), ("Theta", 741),
+    ("Upsilon", 690), ("Upsilon1", 620), ("Xi", 645), ("Zeta", 611), ("aleph", 823), ("alpha", 631), ("ampersand", 778), ("angle", 768),
+    ("angleleft", 329), ("angleright", 329), ("apple", 790), ("approxequal", 549), ("arrowboth", 1042), ("arrowdblboth", 1042), ("arrowdbldown", 603), ("arrowdblleft", 987),
+    ("arrowdblright", 987), ("arrowdblup", 603), ("arrowdown", 603), ("arrowhorizex", 1000), ("arrowleft", 987), ("arrowright", 987), ("arrowup", 603), ("arrowvertex", 603),
+    ("asteriskmath", 500), ("bar", 200), ("beta", 549), ("braceex", 494), ("braceleft", 480), ("braceleftbt", 494), ("braceleftmid", 494), ("bracelefttp", 494),
+    ("braceright", 480), ("bracerightbt", 494), ("bracerightmid", 494), ("bracerighttp", 494), ("bracketleft", 333), ("bracketleftbt", 384), ("bracketleftex", 384), ("bracketlefttp", 384),
+    ("bracketright", 333), ("bracketrightbt", 384), ("bracketrightex", 384), ("bracketrighttp", 384), ("bullet", 460), ("carriagereturn", 658), ("chi", 549), ("circlemultiply", 768),
+    ("circleplus", 768), ("club", 753), ("colon", 278), ("comma", 250), ("congruent", 549), ("copyrightsans", 790), ("copyrightserif", 790), ("degree", 400),
+    ("delta", 494), ("diamond", 753), ("divide", 549), ("dotmath", 250), ("eight", 500), ("element", 713), ("ellipsis", 1000), ("emptyset", 823),
+    ("epsilon", 439), ("equal", 549), ("equivalence", 549), ("eta", 603), ("exclam", 333), ("existential", 549), ("five", 500), ("florin", 500),
+    ("four", 500), ("fraction", 167), ("gamma", 411), ("gradient", 713), ("greater", 549), ("greaterequal", 549), ("heart", 753), ("infinity", 713),
+    ("integral", 274), ("integralbt", 686), ("integralex", 686), ("integraltp", 686), ("intersection", 768), ("iota", 329), ("kappa", 549), ("lambda", 549),
+    ("less", 549), ("lessequal", 549), ("logicaland", 603), ("logicalnot", 713), ("logicalor", 603), ("lozenge", 494), ("minus", 549), ("minute", 247),
+    ("mu", 576), ("multiply", 549), ("nine", 500), ("notelement", 713), ("notequal", 549), ("notsubset", 713), ("nu", 521), ("numbersign", 500),
+    ("omega", 686), ("omega1", 713), ("omicron", 549), ("one", 500), ("parenleft", 333), ("parenleftbt", 384), ("parenleftex", 384), ("parenlefttp", 384),
+    ("parenright", 333), ("parenrightbt", 384), ("parenrightex", 384), ("parenrighttp", 384), ("partialdiff", 494), ("percent", 833), ("period", 250), ("perpendicular", 658),
+    ("phi", 521), ("phi1", 603), ("pi", 549), ("plus", 549), ("plusminus", 549), ("product", 823), ("propersubset", 713), ("propersuperset", 713),
+    ("proportional", 713), ("psi", 686), ("question", 444), ("radical", 549), ("radicalex", 500), ("reflexsubset", 713), ("reflexsuperset", 713), ("registersans", 790),
+    ("registerserif", 790), ("rho", 549), ("second", 411), ("semicolon", 278), ("seven", 500), ("sigma", 603), ("sigma1", 439), ("similar", 549),
+    ("six", 500), ("slash", 278), ("space", 250), ("spade", 753), ("suchthat", 439), ("summation", 713), ("tau", 439), ("therefore", 863),
+    ("theta", 521), ("theta1", 631), ("three", 500), ("trademarksans", 786), ("trademarkserif", 890), ("two", 500), ("underscore", 500), ("union", 768),
+    ("universal", 713), ("upsilon", 576), ("weierstrass", 987), ("xi", 493), ("zero", 500), ("zeta", 494),
+];
+
+static ZAPF_DINGBATS: [(&str, u16); 202] = [
+    ("a1", 974), ("a10", 692), ("a100", 668), ("a101", 732), ("a102", 544), ("a103", 544), ("a104", 910), ("a105", 911),
+    ("a106", 667), ("a107", 760), ("a108", 760), ("a109", 626), ("a11", 960), ("a110", 694), ("a111", 595), ("a112", 776),
+    ("a117", 690), ("a118", 791), ("a119", 790), ("a12", 939), ("a120", 788), ("a121", 788), ("a122", 788), ("a123", 788),
+    ("a124", 788), ("a125", 788), ("a126", 788), ("a127", 788), ("a128", 788), ("a129", 788), ("a13", 549), ("a130", 788),
+    ("a131", 788), ("a132", 788), ("a133", 788), ("a134", 788), ("a135", 788), ("a136", 788), ("a137", 788), ("a138", 788),
+    ("a139", 788), ("a14", 855), ("a140", 788), ("a141", 788), ("a142", 788), ("a143", 788), ("a144", 788), ("a145", 788),
+    ("a146", 788), ("a147", 788), ("a148", 788), ("a149", 788), ("a15", 911), ("a150", 788), ("a151", 788), ("a152", 788),
+    ("a153", 788), ("a154", 788), ("a155", 788), ("a156", 788), ("a157", 788), ("a158", 788), ("a159", 788), ("a16", 933),
+    ("a160", 894), ("a161", 838), ("a162", 924), ("a163", 1016), ("a164", 458), ("a165", 924), ("a166", 918), ("a167", 927),
+    ("a168", 928), ("a169", 928), ("a17", 945), ("a170", 834), ("a171", 873), ("a172", 828), ("a173", 924), ("a174", 917),
+    ("a175", 930), ("a176", 931), ("a177", 463), ("a178", 883), ("a179", 836), ("a18", 974), ("a180", 867), ("a181", 696),
+    ("a182", 874), ("a183", 760), ("a184", 946), ("a185", 865), ("a186", 967), ("a187", 831), ("a188", 873), ("a189", 927),
+    ("a19", 755), ("a190", 970), ("a191", 918), ("a192", 748), ("a193", 836), ("a194", 771), ("a195", 888), ("a196", 748),
+    ("a197", 771), ("a198", 888), ("a199", 867), ("a2", 961), ("a20", 846), ("a200", 696), ("a201", 874), ("a202", 974),
+    ("a203", 762), ("a204", 759), ("a205", 509), ("a206", 410), ("a21", 762), ("a22", 761), ("a23", 571), ("a24", 677),
+    ("a25", 763), ("a26", 760), ("a27", 759), ("a28", 754), ("a29", 786), ("a3", 980), ("a30", 788), ("a31", 788),
+    ("a32", 790), ("a33", 793), ("a34", 794), ("a35", 816), ("a36", 823), ("a37", 789), ("a38", 841), ("a39", 823),
+    ("a4", 719), ("a40", 833), ("a41", 816), ("a42", 831), ("a43", 923), ("a44", 744), ("a45", 723), ("a46", 749),
+    ("a47", 790), ("a48", 792), ("a49", 695), ("a5", 789), ("a50", 776), ("a51", 768), ("a52", 792), ("a53", 759),
+    ("a54", 707), ("a55", 708), ("a56", 682), ("a57", 701), ("a58", 826), ("a59", 815), ("a6", 494), ("a60", 789),
+    ("a61", 789), ("a62", 707), ("a63", 687), ("a64", 696), ("a65", 689), ("a66", 786), ("a67", 787), ("a68", 713),
+    ("a69", 791), ("a7", 552), ("a70", 785), ("a71", 791), ("a72", 873), ("a73", 761), ("a74", 762), ("a75", 759),
+    ("a76", 892), ("a77", 892), ("a78", 788), ("a79", 784), ("a8", 537), ("a81", 438), ("a82", 138), ("a83", 277),
+    ("a84", 415), ("a85", 509), ("a86", 410), ("a87", 234), ("a88", 234), ("a89", 390), ("a9", 577), ("a90", 390),
+    ("a91", 276), ("a92", 276), ("a93", 317), ("a94", 317), ("a95", 334), ("a96", 334), ("a97", 392), ("a98", 392),
+    ("a99", 668), ("space", 278),
+];
@@ -0,0 +1,705 @@
+//! Functions (7.10): sampled, exponential, stitching and PostScript calculator functions.
+
+use std::io;
+use std::f32::consts::PI;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::*;
+
+/// A function with `m` inputs and `n` outputs, as used by shadings, separation color spaces
+/// and transfer functions.
+#[derive(Debug, Clone)]
+pub enum Function {
+    /// Type 0
+    Sampled (SampledFunction),
+    /// Type 2
+    Exponential (ExponentialFunction),
+    /// Type 3
+    Stitching (StitchingFunction),
+    /// Type 4
+    PostScript (PostScriptFunction),
+}
+
+impl Function {
+    /// Evaluates the function. Inputs are clipped to the domain and outputs to the range.
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        match *self {
+            Function::Sampled(ref f) => f.eval(input),
+            Function::Exponential(ref f) => f.eval(input),
+            Function::Stitching(ref f) => f.eval(input),
+            Function::PostScript(ref f) => f.eval(input),
+        }
+    }
+    /// `[min0 max0 min1 max1 ...]` of the inputs
+    pub fn domain(&self) -> &[f32] {
+        match *self {
+            Function::Sampled(ref f) => &f.domain,
+            Function::Exponential(ref f) => &f.domain,
+            Function::Stitching(ref f) => &f.domain,
+            Function::PostScript(ref f) => &f.domain,
+        }
+    }
+    /// Number of outputs
+    pub fn output_count(&self) -> usize {
+        match *self {
+            Function::Sampled(ref f) => f.range.len() / 2,
+            Function::Exponential(ref f) => f.c0.len(),
+            Function::Stitching(ref f) => f.functions.first().map(|f| f.output_count()).unwrap_or(0),
+            Function::PostScript(ref f) => f.range.len() / 2,
+        }
+    }
+}
+
+/// Sampled functions interpolate between 2^m samples for m inputs.
+const MAX_INPUTS: usize = 16;
+/// How deep stitching functions may be nested
+const MAX_DEPTH: usize = 8;
+
+impl Object for Function {
+    fn serialize<W: io::Write>(&self, _out: &mut W) -> Result<()> {
+        bail!("functions can't be written")
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        Function::parse(p, resolve, 0)
+    }
+}
+
+impl Function {
+    /// Reads a function that is nested in `depth` stitching functions.
+    fn parse(p: Primitive, resolve: &impl Resolve, depth: usize) -> Result<Function> {
+        if depth > MAX_DEPTH {
+            bail!("stitching functions nested more than {} deep", MAX_DEPTH);
+        }
+        let (mut dict, data) = match p.resolve(resolve)? {
+            Primitive::Dictionary(dict) => (dict, None),
+            Primitive::Stream(stream) => {
+                let dict = stream.info.clone();
+                let data = Stream::<()>::from_primitive(Primitive::Stream(stream), resolve)?.data()?.to_vec();
+                (dict, Some(data))
+            }
+            p => return unexpected_primitive!(Dictionary, p.get_debug_name())
+        };
+        let function_type = dict.require("Function", "FunctionType")?.as_integer()?;
+        let domain = numbers(dict.require("Function", "Domain")?, resolve)?;
+        if domain.len() % 2 != 0 {
+            bail!("function domain with {} numbers", domain.len());
+        }
+        let range = match dict.remove("Range") {
+            Some(p) => numbers(p, resolve)?,
+            None => Vec::new()
+        };
+        let data = || data.ok_or_else(|| PdfError::from(format!("function type {} needs a stream", function_type)));
+        match function_type {
+            0 => {
+                let size = numbers(dict.require("Function", "Size")?, resolve)?
+                    .into_iter().map(|s| s.max(1.) as usize).collect::<Vec<_>>();
+                if size.len() > MAX_INPUTS {
+                    bail!("sampled function with {} inputs", size.len());
+                }
+                let bits_per_sample = dict.require("Function", "BitsPerSample")?.as_integer()?;
+                match bits_per_sample {
+                    1 | 2 | 4 | 8 | 12 | 16 | 24 | 32 => {}
+                    n => bail!("invalid BitsPerSample {}", n)
+                }
+                let encode = match dict.remove("Encode") {
+                    Some(p) => numbers(p, resolve)?,
+                    None => size.iter().flat_map(|&s| vec![0., (s - 1) as f32]).collect()
+                };
+                let decode = match dict.remove("Decode") {
+                    Some(p) => numbers(p, resolve)?,
+                    None => range.clone()
+                };
+                if size.len() * 2 != domain.len() || encode.len() != domain.len() || decode.len() != range.len() {
+                    bail!("inconsistent sampled function");
+                }
+                let data = data()?;
+                let count = size.iter().try_fold(range.len() / 2, |count, &s| count.checked_mul(s))
+                    .ok_or_else(|| PdfError::from(format!("sampled function with {:?} samples", size)))?;
+                let samples = read_samples(&data, bits_per_sample as u32, count);
+                Ok(Function::Sampled(SampledFunction {
+                    domain, range, size, encode, decode,
+                    max: ((1u64 << bits_per_sample) - 1) as f32,
+                    samples
+                }))
+            }
+            2 => {
+                let c0 = match dict.remove("C0") {
+                    Some(p) => numbers(p, resolve)?,
+                    None => vec![0.]
+                };
+                let c1 = match dict.remove("C1") {
+                    Some(p) => numbers(p, resolve)?,
+                    None => vec![1.]
+                };
+                if c0.len() != c1.len() {
+                    bail!("C0 and C1 differ in length");
+                }
+                let n = dict.require("Function", "N")?.as_number()?;
+                Ok(Function::Exponential(ExponentialFunction { domain, range, c0, c1, n }))
+            }
+            3 => {
+                let functions = dict.require("Function", "Functions")?.resolve(resolve)?.to_array(resolve)?
+                    .into_iter().map(|f| Function::parse(f, resolve, depth + 1))
+                    .collect::<Result<Vec<_>>>()?;
+                let bounds = numbers(dict.require("Function", "Bounds")?, resolve)?;
+                let encode = numbers(dict.require("Function", "Encode")?, resolve)?;
+                if domain.len() != 2 || functions.len() == 0 || bounds.len() + 1 != functions.len() || encode.len() != 2 * functions.len() {
+                    bail!("inconsistent stitching function");
+                }
+                Ok(Function::Stitching(StitchingFunction { domain, range, functions, bounds, encode }))
+            }
+            4 => {
+                let code = PostScriptFunction::parse(&data()?)?;
+                Ok(Function::PostScript(PostScriptFunction { domain, range, code }))
+            }
+            n => bail!("invalid FunctionType {}", n)
+        }
+    }
+}
+
+fn numbers(p: Primitive, resolve: &impl Resolve) -> Result<Vec<f32>> {
+    p.resolve(resolve)?.to_array(resolve)?.iter().map(|p| p.as_number()).collect()
+}
+
+/// Maps `x` from `[x0, x1]` to `[y0, y1]`.
+fn interpolate(x: f32, x0: f32, x1: f32, y0: f32, y1: f32) -> f32 {
+    if x1 == x0 {
+        y0
+    } else {
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+fn clip(x: f32, min: f32, max: f32) -> f32 {
+    if x < min { min } else if x > max { max } else { x }
+}
+
+/// Clips `values` to the `[min max]` pairs in `range`, if there is one.
+fn clip_to(mut values: Vec<f32>, range: &[f32]) -> Vec<f32> {
+    for (v, r) in values.iter_mut().zip(range.chunks(2)) {
+        if r.len() == 2 {
+            *v = clip(*v, r[0], r[1]);
+        }
+    }
+    values
+}
+
+/// Reads `count` big-endian samples of `bits` bits each, at most as many as `data` holds.
+/// Missing samples are 0.
+fn read_samples(data: &[u8], bits: u32, count: usize) -> Vec<u32> {
+    let count = count.min(data.len() * 8 / bits as usize);
+    let mut samples = Vec::with_capacity(count);
+    let mut pos = 0u64;
+    for _ in 0 .. count {
+        let mut value = 0u64;
+        for _ in 0 .. bits {
+            let byte = data.get((pos / 8) as usize).cloned().unwrap_or(0);
+            let bit = (byte >> (7 - pos % 8)) & 1;
+            value = value << 1 | bit as u64;
+            pos += 1;
+        }
+        samples.push(value as u32);
+    }
+    samples
+}
+
+/// Type 0: a table of samples, interpolated linearly.
+#[derive(Debug, Clone)]
+pub struct SampledFunction {
+    pub domain: Vec<f32>,
+    pub range: Vec<f32>,
+    /// Number of samples in each input dimension
+    pub size: Vec<usize>,
+    pub encode: Vec<f32>,
+    pub decode: Vec<f32>,
+    /// Largest sample value
+    max: f32,
+    /// The first dimension varies fastest, the outputs of one sample are together.
+    samples: Vec<u32>,
+}
+impl SampledFunction {
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let m = self.size.len();
+        let n = self.range.len() / 2;
+        // position in the table, split into the index of the lower sample and the fraction towards the next one
+        let mut index = Vec::with_capacity(m);
+        let mut frac = Vec::with_capacity(m);
+        for i in 0 .. m {
+            let x = clip(input.get(i).cloned().unwrap_or(0.), self.domain[2 * i], self.domain[2 * i + 1]);
+            let e = interpolate(x, self.domain[2 * i], self.domain[2 * i + 1], self.encode[2 * i], self.encode[2 * i + 1]);
+            let e = clip(e, 0., (self.size[i] - 1) as f32);
+            let lower = (e.floor() as usize).min(self.size[i].saturating_sub(2));
+            index.push(lower);
+            frac.push(if self.size[i] > 1 { e - lower as f32 } else { 0. });
+        }
+        let mut out = vec![0.; n];
+        // multilinear interpolation between the 2^m surrounding samples
+        for corner in 0 .. 1usize << m {
+            let mut weight = 1.;
+            let mut offset = 0;
+            let mut stride = 1;
+            for i in 0 .. m {
+                let upper = corner & (1 << i) != 0;
+                weight *= if upper { frac[i] } else { 1. - frac[i] };
+                let idx = if upper && self.size[i] > 1 { index[i] + 1 } else { index[i] };
+                offset += idx * stride;
+                stride *= self.size[i];
+            }
+            if weight == 0. {
+                continue;
+            }
+            for j in 0 .. n {
+                let sample = self.samples.get(offset * n + j).cloned().unwrap_or(0) as f32;
+                out[j] += weight * sample;
+            }
+        }
+        let out = out.iter().enumerate()
+            .map(|(j, &s)| interpolate(s, 0., self.max, self.decode[2 * j], self.decode[2 * j + 1]))
+            .collect();
+        clip_to(out, &self.range)
+    }
+}
+
+/// Type 2: `C0 + x^N × (C1 − C0)`
+#[derive(Debug, Clone)]
+pub struct ExponentialFunction {
+    pub domain: Vec<f32>,
+    pub range: Vec<f32>,
+    pub c0: Vec<f32>,
+    pub c1: Vec<f32>,
+    pub n: f32,
+}
+impl ExponentialFunction {
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let x = input.get(0).cloned().unwrap_or(0.);
+        let x = match self.domain.len() {
+            2 => clip(x, self.domain[0], self.domain[1]),
+            _ => x
+        };
+        let p = x.powf(self.n);
+        let out = self.c0.iter().zip(&self.c1).map(|(&c0, &c1)| c0 + p * (c1 - c0)).collect();
+        clip_to(out, &self.range)
+    }
+}
+
+/// Type 3: one input, with a function for each of the subdomains between `bounds`.
+#[derive(Debug, Clone)]
+pub struct StitchingFunction {
+    pub domain: Vec<f32>,
+    pub range: Vec<f32>,
+    pub functions: Vec<Function>,
+    pub bounds: Vec<f32>,
+    pub encode: Vec<f32>,
+}
+impl StitchingFunction {
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let (d0, d1) = (self.domain[0], self.domain[1]);
+        let x = clip(input.get(0).cloned().unwrap_or(0.), d0, d1);
+        // the subdomains include their lower bound, the last one also its upper bound
+        let k = self.bounds.iter().take_while(|&&b| x >= b).count();
+        let low = if k == 0 { d0 } else { self.bounds[k - 1] };
+        let high = if k == self.bounds.len() { d1 } else { self.bounds[k] };
+        let e = interpolate(x, low, high, self.encode[2 * k], self.encode[2 * k + 1]);
+        clip_to(self.functions[k].eval(&[e]), &self.range)
+    }
+}
+
+/// Type 4: a program in a subset of PostScript.
+#[derive(Debug, Clone)]
+pub struct PostScriptFunction {
+    pub domain: Vec<f32>,
+    pub range: Vec<f32>,
+    code: Vec<PsOp>,
+}
+
+#[derive(Debug, Clone)]
+enum PsOp {
+    Value (PsValue),
+    Operator (String),
+    If (Vec<PsOp>),
+    IfElse (Vec<PsOp>, Vec<PsOp>),
+}
+
+#[derive(Debug, Copy, Clone)]
+enum PsValue {
+    Number (f32),
+    Bool (bool),
+}
+
+/// Stack depth allowed by the specification
+const PS_STACK_LIMIT: usize = 100;
+/// How deep procedures may be nested
+const PS_MAX_DEPTH: usize = 32;
+
+impl PostScriptFunction {
+    /// Parses `{ ... }`.
+    fn parse(data: &[u8]) -> Result<Vec<PsOp>> {
+        let text = String::from_utf8_lossy(data);
+        let mut tokens = Vec::new();
+        for word in text.split(|c: char| c.is_whitespace()).filter(|w| w.len() > 0) {
+            // braces need not be separated
+            let mut rest = word;
+            while rest.len() > 0 {
+                match rest.find(|c| c == '{' || c == '}') {
+                    Some(0) => {
+                        tokens.push(&rest[.. 1]);
+                        rest = &rest[1 ..];
+                    }
+                    Some(i) => {
+                        tokens.push(&rest[.. i]);
+                        rest = &rest[i ..];
+                    }
+                    None => {
+                        tokens.push(rest);
+                        rest = "";
+                    }
+                }
+            }
+        }
+        let mut tokens = tokens.into_iter();
+        match tokens.next() {
+            Some("{") => {}
+            _ => bail!("PostScript function does not start with {{")
+        }
+        parse_block(&mut tokens, 0)
+    }
+
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let m = self.domain.len() / 2;
+        let mut stack = Vec::with_capacity(PS_STACK_LIMIT);
+        for i in 0 .. m {
+            let x = clip(input.get(i).cloned().unwrap_or(0.), self.domain[2 * i], self.domain[2 * i + 1]);
+            stack.push(PsValue::Number(x));
+        }
+        let n = self.range.len() / 2;
+        if let Err(e) = run(&self.code, &mut stack, 0) {
+            warn!("PostScript function failed: {}", e);
+            return self.range.chunks(2).map(|r| r[0]).collect();
+        }
+        // the outputs are the topmost n values
+        let start = stack.len().saturating_sub(n);
+        let mut out: Vec<f32> = stack[start ..].iter().map(|v| match *v {
+            PsValue::Number(x) => x,
+            PsValue::Bool(b) => if b { 1. } else { 0. }
+        }).collect();
+        while out.len() < n {
+            out.insert(0, 0.);
+        }
+        clip_to(out, &self.range)
+    }
+}
+
+/// Parses a procedure nested in `depth` others, up to its closing `}`.
+fn parse_block<'a>(tokens: &mut impl Iterator<Item=&'a str>, depth: usize) -> Result<Vec<PsOp>> {
+    if depth > PS_MAX_DEPTH {
+        bail!("PostScript procedures nested more than {} deep", PS_MAX_DEPTH);
+    }
+    let mut ops = Vec::new();
+    // blocks that wait for their `if` or `ifelse`
+    let mut pending: Vec<Vec<PsOp>> = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "{" => pending.push(parse_block(tokens, depth + 1)?),
+            "}" => {
+                if pending.len() > 0 {
+                    bail!("procedure without if or ifelse");
+                }
+                return Ok(ops);
+            }
+            "if" => {
+                let then = pending.pop().ok_or_else(|| PdfError::from("if without procedure".to_owned()))?;
+                ops.push(PsOp::If(then));
+            }
+            "ifelse" => {
+                let otherwise = pending.pop();
+                let then = pending.pop();
+                match (then, otherwise) {
+                    (Some(then), Some(otherwise)) => ops.push(PsOp::IfElse(then, otherwise)),
+                    _ => bail!("ifelse without two procedures")
+                }
+            }
+            "true" => ops.push(PsOp::Value(PsValue::Bool(true))),
+            "false" => ops.push(PsOp::Value(PsValue::Bool(false))),
+            t => match t.parse::<f32>() {
+                Ok(x) => ops.push(PsOp::Value(PsValue::Number(x))),
+                Err(_) => ops.push(PsOp::Operator(t.to_owned()))
+            }
+        }
+    }
+    bail!("PostScript function is missing a closing }}")
+}
+
+fn run(code: &[PsOp], stack: &mut Vec<PsValue>, depth: usize) -> Result<()> {
+    if depth > PS_MAX_DEPTH {
+        bail!("PostScript procedures nested more than {} deep", PS_MAX_DEPTH);
+    }
+    fn num(stack: &mut Vec<PsValue>) -> Result<f32> {
+        match stack.pop() {
+            Some(PsValue::Number(x)) => Ok(x),
+            Some(PsValue::Bool(_)) => bail!("typecheck: expected a number"),
+            None => bail!("stack underflow")
+        }
+    }
+    fn int(stack: &mut Vec<PsValue>) -> Result<i32> {
+        Ok(num(stack)? as i32)
+    }
+    fn boolean(stack: &mut Vec<PsValue>) -> Result<bool> {
+        match stack.pop() {
+            Some(PsValue::Bool(b)) => Ok(b),
+            Some(PsValue::Number(_)) => bail!("typecheck: expected a boolean"),
+            None => bail!("stack underflow")
+        }
+    }
+    use self::PsValue::{Number, Bool};
+
+    for op in code {
+        let name = match *op {
+            PsOp::Value(v) => {
+                stack.push(v);
+                continue;
+            }
+            PsOp::If(ref then) => {
+                if boolean(stack)? {
+                    run(then, stack, depth + 1)?;
+                }
+                continue;
+            }
+            PsOp::IfElse(ref then, ref otherwise) => {
+                if boolean(stack)? {
+                    run(then, stack, depth + 1)?;
+                } else {
+                    run(otherwise, stack, depth + 1)?;
+                }
+                continue;
+            }
+            PsOp::Operator(ref name) => name.as_str()
+        };
+        let result = match name {
+            // arithmetic
+            "abs" => Number(num(stack)?.abs()),
+            "add" => { let b = num(stack)?; Number(num(stack)? + b) }
+            "sub" => { let b = num(stack)?; Number(num(stack)? - b) }
+            "mul" => { let b = num(stack)?; Number(num(stack)? * b) }
+            "div" => {
+                let b = num(stack)?;
+                if b == 0. {
+                    bail!("undefinedresult: division by zero");
+                }
+                Number(num(stack)? / b)
+            }
+            "idiv" | "mod" => {
+                let b = int(stack)?;
+                let a = int(stack)?;
+                if b == 0 {
+                    bail!("undefinedresult: division by zero");
+                }
+                Number(if name == "idiv" { a / b } else { a % b } as f32)
+            }
+            "neg" => Number(-num(stack)?),
+            "ceiling" => Number(num(stack)?.ceil()),
+            "floor" => Number(num(stack)?.floor()),
+            "round" => Number((num(stack)? + 0.5).floor()),
+            "truncate" => Number(num(stack)?.trunc()),
+            "cvi" => Number(num(stack)?.trunc()),
+            "cvr" => Number(num(stack)?),
+            "sqrt" => Number(num(stack)?.sqrt()),
+            // angles are in degrees
+            "sin" => Number((num(stack)? * PI / 180.).sin()),
+            "cos" => Number((num(stack)? * PI / 180.).cos()),
+            "atan" => {
+                let den = num(stack)?;
+                let angle = num(stack)?.atan2(den) * 180. / PI;
+                Number(if angle < 0. { angle + 360. } else { angle })
+            }
+            "exp" => { let e = num(stack)?; Number(num(stack)?.powf(e)) }
+            "ln" => Number(num(stack)?.ln()),
+            "log" => Number(num(stack)?.log10()),
+            // relational, boolean and bitwise
+            "eq" | "ne" => {
+                let b = stack.pop();
+                let a = stack.pop();
+                let equal = match (a, b) {
+                    (Some(Number(a)), Some(Number(b))) => a == b,
+                    (Some(Bool(a)), Some(Bool(b))) => a == b,
+                    (Some(_), Some(_)) => false,
+                    _ => bail!("stack underflow")
+                };
+                Bool(equal == (name == "eq"))
+            }
+            "gt" => { let b = num(stack)?; Bool(num(stack)? > b) }
+            "ge" => { let b = num(stack)?; Bool(num(stack)? >= b) }
+            "lt" => { let b = num(stack)?; Bool(num(stack)? < b) }
+            "le" => { let b = num(stack)?; Bool(num(stack)? <= b) }
+            "and" | "or" | "xor" => match (stack.pop(), stack.pop()) {
+                (Some(Bool(b)), Some(Bool(a))) => Bool(match name {
+                    "and" => a & b,
+                    "or" => a | b,
+                    _ => a ^ b
+                }),
+                (Some(Number(b)), Some(Number(a))) => {
+                    let (a, b) = (a as i32, b as i32);
+                    Number(match name {
+                        "and" => a & b,
+                        "or" => a | b,
+                        _ => a ^ b
+                    } as f32)
+                }
+                _ => bail!("typecheck in {}", name)
+            },
+            "not" => match stack.pop() {
+                Some(Bool(b)) => Bool(!b),
+                Some(Number(x)) => Number(!(x as i32) as f32),
+                None => bail!("stack underflow")
+            },
+            "bitshift" => {
+                let shift = int(stack)?;
+                let x = int(stack)?;
+                Number(if shift >= 0 { x.wrapping_shl(shift as u32) } else { x.wrapping_shr((-shift) as u32) } as f32)
+            }
+            // stack
+            "pop" => {
+                stack.pop().ok_or_else(|| PdfError::from("stack underflow".to_owned()))?;
+                continue;
+            }
+            "dup" => *stack.last().ok_or_else(|| PdfError::from("stack underflow".to_owned()))?,
+            "exch" => {
+                let b = stack.pop();
+                let a = stack.pop();
+                match (a, b) {
+                    (Some(a), Some(b)) => {
+                        stack.push(b);
+                        a
+                    }
+                    _ => bail!("stack underflow")
+                }
+            }
+            "copy" => {
+                let n = int(stack)?;
+                if n < 0 || n as usize > stack.len() {
+                    bail!("rangecheck in copy");
+                }
+                let start = stack.len() - n as usize;
+                let copy = stack[start ..].to_vec();
+                stack.extend(copy);
+                continue;
+            }
+            "index" => {
+                let n = int(stack)?;
+                if n < 0 || n as usize >= stack.len() {
+                    bail!("rangecheck in index");
+                }
+                stack[stack.len() - 1 - n as usize]
+            }
+            "roll" => {
+                let j = int(stack)?;
+                let n = int(stack)?;
+                if n < 0 || n as usize > stack.len() {
+                    bail!("rangecheck in roll");
+                }
+                if n > 0 {
+                    let start = stack.len() - n as usize;
+                    let j = j.rem_euclid(n) as usize;
+                    stack[start ..].rotate_right(j);
+                }
+                continue;
+            }
+            op => bail!("unknown PostScript operator {}", op)
+        };
+        stack.push(result);
+        if stack.len() > PS_STACK_LIMIT {
+            bail!("stack overflow");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_and_stitching() {
+        let ramp = |c0: f32, c1: f32| Function::Exponential(ExponentialFunction {
+            domain: vec![0., 1.], range: vec![], c0: vec![c0], c1: vec![c1], n: 1.
+        });
+        let f = StitchingFunction {
+            domain: vec![0., 1.], range: vec![],
+            functions: vec![ramp(0., 1.), ramp(1., 0.)],
+            bounds: vec![0.5],
+            encode: vec![0., 1., 0., 1.],
+        };
+        assert_eq!(f.eval(&[0.25]), vec![0.5]);
+        assert_eq!(f.eval(&[0.75]), vec![0.5]);
+        assert_eq!(f.eval(&[2.]), vec![0.]);
+    }
+
+    #[test]
+    fn postscript() {
+        let f = PostScriptFunction {
+            domain: vec![0., 1., 0., 1.],
+            range: vec![0., 1., 0., 1.],
+            code: PostScriptFunction::parse(b"{ 2 copy gt { exch } if pop dup 0.5 mul }").unwrap(),
+        };
+        // the smaller input, and half of it
+        assert_eq!(f.eval(&[0.8, 0.4]), vec![0.4, 0.2]);
+        assert_eq!(f.eval(&[0.2, 0.6]), vec![0.2, 0.1]);
+    }
+
+    #[test]
+    fn sampled() {
+        let f = SampledFunction {
+            domain: vec![0., 1.], range: vec![0., 1.],
+            size: vec![3], encode: vec![0., 2.], decode: vec![0., 1.],
+            max: 255., samples: vec![0, 255, 0],
+        };
+        assert_eq!(f.eval(&[0.25]), vec![0.5]);
+        assert_eq!(f.eval(&[0.5]), vec![1.]);
+        assert_eq!(f.eval(&[1.]), vec![0.]);
+    }
+
+    #[test]
+    fn limits() {
+        use crate::parser::parse;
+        let function = |data: &[u8]| Function::from_primitive(parse(data, &NoResolve).unwrap(), &NoResolve);
+
+        // a huge table with only two samples
+        let f = function(b"<< /FunctionType 0 /Domain [0 1 0 1] /Range [0 1] /Size [100000 100000] \
+            /BitsPerSample 8 /Length 2 >> stream\n\x00\xff\nendstream").unwrap();
+        match f {
+            Function::Sampled(ref f) => assert_eq!(f.samples, [0, 255]),
+            _ => panic!()
+        }
+        // too many inputs
+        let sizes = "1 ".repeat(17);
+        let domain = "0 1 ".repeat(17);
+        let data = format!("<< /FunctionType 0 /Domain [{}] /Range [0 1] /Size [{}] /BitsPerSample 8 /Length 1 >> stream\n\x00\nendstream", domain, sizes);
+        assert!(function(data.as_bytes()).is_err());
+        // more samples than there are numbers
+        let data = format!("<< /FunctionType 0 /Domain [{}] /Range [0 1] /Size [{}] /BitsPerSample 8 /Length 1 >> stream\n\x00\nendstream",
+            "0 1 ".repeat(5), "65536 ".repeat(5));
+        assert!(function(data.as_bytes()).is_err());
+
+        /// Object 1 is a stitching function of itself
+        struct Recursive;
+        impl Resolve for Recursive {
+            fn resolve(&self, _: PlainRef) -> Result<Primitive> {
+                parse(b"<< /FunctionType 3 /Domain [0 1] /Functions [1 0 R] /Bounds [] /Encode [0 1] >>", &NoResolve)
+            }
+            fn get<T: Object>(&self, _: Ref<T>) -> Result<std::sync::Arc<T>> {
+                Err(PdfError::Reference)
+            }
+        }
+        assert!(Function::from_primitive(Primitive::Reference(PlainRef { id: 1, gen: 0 }), &Recursive).is_err());
+        // deeply nested procedures
+        let data = format!("<< /FunctionType 4 /Domain [0 1] /Range [0 1] /Length 100000 >> stream\n{}\nendstream", "{".repeat(100000));
+        assert!(function(data.as_bytes()).is_err());
+        // domains that don't come in pairs
+        assert!(function(b"<< /FunctionType 3 /Domain [] /Functions [<< /FunctionType 2 /Domain [0 1] /N 1 >>] \
+            /Bounds [] /Encode [0 1] >>").is_err());
+        assert!(function(b"<< /FunctionType 2 /Domain [0] /N 1 >>").is_err());
+
+        let mut out = Vec::new();
+        assert!(f.serialize(&mut out).is_err());
+    }
+}
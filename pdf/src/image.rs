@@ -0,0 +1,273 @@
+//! Decoding image XObjects into 8 bit samples.
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::Primitive;
+use crate::enc::StreamFilter;
+
+#[cfg(feature = "write")]
+pub mod embed;
+
+/// Decoded image with 8 bits per channel.
+#[derive(Debug, Clone)]
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    /// Number of channels per pixel
+    pub components: usize,
+    /// Bits per component of the samples in the file. `data` always has 8.
+    pub bits_per_component: u8,
+    /// Whether the image should be smoothed when scaled up
+    pub interpolate: bool,
+    /// `width * height * components` bytes, row by row.
+    /// For image masks 0 marks the pixels to be painted.
+    pub data: Vec<u8>,
+    /// `width * height` opacity values, if the image has a color key mask
+    pub alpha: Option<Vec<u8>>,
+}
+
+impl ImageData {
+    /// Fraction of the pixels that are darker than the gray level `threshold`.
+    /// Transparent pixels are not dark.
+    pub fn dark_fraction(&self, threshold: u8) -> f32 {
+        let n = self.width as usize * self.height as usize;
+        if n == 0 || self.components == 0 {
+            return 0.;
+        }
+        let mut dark = 0;
+        for (i, pixel) in self.data.chunks(self.components).take(n).enumerate() {
+            if let Some(ref alpha) = self.alpha {
+                if alpha[i] == 0 {
+                    continue;
+                }
+            }
+            let gray = match *pixel {
+                [g] => g as u32,
+                [r, g, b] => (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000,
+                [c, m, y, k] => 255 - (c.max(m).max(y) as u32 + k as u32).min(255),
+                _ => continue
+            };
+            if gray < threshold as u32 {
+                dark += 1;
+            }
+        }
+        dark as f32 / n as f32
+    }
+}
+
+/// What the samples of an image mean.
+#[derive(Debug, Clone)]
+enum ImageSpace {
+    /// Color components in the range 0 to 1
+    Color (usize),
+    /// Index into `lookup`, which has `base` components for each of the `hival + 1` entries
+    Indexed { base: usize, hival: usize, lookup: Vec<u8> },
+}
+
+fn image_space(cs: Primitive, resolve: &impl Resolve) -> Result<ImageSpace> {
+    let space = match ColorSpace::from_primitive(cs, resolve)? {
+        ColorSpace::Indexed { base, hival, lookup } => ImageSpace::Indexed {
+            base: base.components(),
+            hival: hival as usize,
+            lookup
+        },
+        cs => match cs.components() {
+            0 => bail!("color space {:?} can not be used for images", cs),
+            n => ImageSpace::Color(n)
+        }
+    };
+    Ok(space)
+}
+
+/// Reads the `bpc` bit wide samples of one row.
+fn unpack_row(row: &[u8], bpc: usize, count: usize, out: &mut Vec<u32>) {
+    match bpc {
+        8 => out.extend(row[.. count].iter().map(|&b| b as u32)),
+        16 => out.extend(row[.. 2 * count].chunks(2).map(|c| (c[0] as u32) << 8 | c[1] as u32)),
+        1 | 2 | 4 => {
+            let per_byte = 8 / bpc;
+            let mask = (1 << bpc) - 1;
+            out.extend((0 .. count).map(|i| {
+                let shift = 8 - bpc * (i % per_byte + 1);
+                (row[i / per_byte] as u32 >> shift) & mask
+            }));
+        }
+        _ => unreachable!()
+    }
+}
+
+impl ImageXObject {
+    /// Decodes the samples, applies the `/Decode` array and expands them to 8 bits.
+    /// Indexed images are mapped through their palette.
+    /// A color key `/Mask` results in an alpha channel.
+    /// JPEG (`DCTDecode`) images are decompressed into gray, RGB or CMYK samples.
+    pub fn image_data(&self, resolve: &impl Resolve) -> Result<ImageData> {
+        if self.width <= 0 || self.height <= 0 {
+            bail!("invalid image size {}x{}", self.width, self.height);
+        }
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let (space, bpc) = if self.image_mask {
+            (ImageSpace::Color(1), 1)
+        } else {
+            let cs = match self.color_space {
+                Some(ref cs) => cs.clone(),
+                None => bail!("image without /ColorSpace")
+            };
+            (image_space(cs, resolve)?, self.bits_per_component as usize)
+        };
+        // JPEG samples always have 8 bits, whatever the dictionary says
+        let is_jpeg = self.filters.iter().any(|f| match *f {
+            StreamFilter::DCTDecode(_) => true,
+            _ => false
+        });
+        let bpc = if is_jpeg && !self.image_mask { 8 } else { bpc };
+        match bpc {
+            1 | 2 | 4 | 8 | 16 => {}
+            n => bail!("invalid BitsPerComponent {}", n)
+        }
+        let components = match space {
+            ImageSpace::Color(n) => n,
+            ImageSpace::Indexed { .. } => 1,
+        };
+        let max = ((1u32 << bpc) - 1) as f32;
+
+        // rows start at byte boundaries
+        let stride = (width * components * bpc + 7) / 8;
+        let data = self.data()?;
+        if data.len() / stride < height {
+            bail!("image data too short: {} bytes for {} rows of {}", data.len(), height, stride);
+        }
+
+        let decode: Vec<(f32, f32)> = if self.decode.len() >= 2 * components {
+            self.decode.chunks(2).take(components).map(|c| (c[0], c[1])).collect()
+        } else {
+            match space {
+                ImageSpace::Indexed { .. } => vec![(0., max)],
+                ImageSpace::Color(n) => vec![(0., 1.); n]
+            }
+        };
+
+        // color key mask: [min_1 max_1 … min_n max_n] of raw sample values
+        let color_key = match self.mask {
+            Some(ref mask) if !self.image_mask => match mask.clone().resolve(resolve)? {
+                Primitive::Array(ranges) => {
+                    if ranges.len() < 2 * components {
+                        bail!("color key mask needs {} entries, found {}", 2 * components, ranges.len());
+                    }
+                    let ranges = ranges.iter().map(|p| p.as_integer().map(|n| n.max(0) as u32)).collect::<Result<Vec<u32>>>()?;
+                    Some(ranges)
+                }
+                // a stencil mask stream
+                _ => None
+            },
+            _ => None
+        };
+        let mut alpha = color_key.as_ref().map(|_| Vec::with_capacity(width * height));
+
+        let mut samples = Vec::with_capacity(width * components);
+        let mut out = Vec::with_capacity(width * height * components);
+        for row in data.chunks(stride).take(height) {
+            samples.clear();
+            unpack_row(row, bpc, width * components, &mut samples);
+            for pixel in samples.chunks(components) {
+                if let (Some(ranges), Some(alpha)) = (&color_key, &mut alpha) {
+                    let masked = pixel.iter().zip(ranges.chunks(2)).all(|(&s, r)| r[0] <= s && s <= r[1]);
+                    alpha.push(if masked { 0 } else { 255 });
+                }
+                for (&s, &(dmin, dmax)) in pixel.iter().zip(decode.iter()) {
+                    let v = dmin + s as f32 * (dmax - dmin) / max;
+                    match space {
+                        ImageSpace::Indexed { base, hival, ref lookup } => {
+                            let index = (v.round().max(0.) as usize).min(hival);
+                            match lookup.get(index * base .. (index + 1) * base) {
+                                Some(color) => out.extend_from_slice(color),
+                                // lookup tables are sometimes truncated
+                                None => out.extend((0 .. base).map(|_| 0))
+                            }
+                        }
+                        ImageSpace::Color(_) => out.push((v * 255.).round().max(0.).min(255.) as u8)
+                    }
+                }
+            }
+        }
+
+        Ok(ImageData {
+            width: width as u32,
+            height: height as u32,
+            components: match space {
+                ImageSpace::Indexed { base, .. } => base,
+                ImageSpace::Color(n) => n
+            },
+            bits_per_component: bpc as u8,
+            interpolate: self.interpolate,
+            data: out,
+            alpha,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::{Dictionary, PdfStream, PdfString};
+
+    fn image(entries: Vec<(&str, Primitive)>, data: &[u8]) -> ImageXObject {
+        let mut info = Dictionary::new();
+        info.insert("Type".into(), "XObject".into());
+        info.insert("Subtype".into(), "Image".into());
+        info.insert("Length".into(), Primitive::Integer(data.len() as i32));
+        for (key, value) in entries {
+            info.insert(key.into(), value);
+        }
+        ImageXObject::from_primitive(Primitive::Stream(PdfStream { info, data: data.to_vec().into() }), &NoResolve).unwrap()
+    }
+    fn gray(width: i32, height: i32, bpc: i32) -> Vec<(&'static str, Primitive)> {
+        vec![
+            ("Width", width.into()),
+            ("Height", height.into()),
+            ("BitsPerComponent", bpc.into()),
+            ("ColorSpace", "DeviceGray".into()),
+        ]
+    }
+
+    #[test]
+    fn samples() {
+        let mut entries = gray(2, 2, 8);
+        entries.push(("Decode", Primitive::Array(vec![Primitive::Integer(1), Primitive::Integer(0)])));
+        let data = image(entries, &[0, 255, 128, 64]).image_data(&NoResolve).unwrap();
+        assert_eq!(data.data, [255, 0, 127, 191]);
+
+        // rows of 1 bit samples are padded to whole bytes
+        let data = image(gray(3, 2, 1), &[0b1010_0000, 0b0100_0000]).image_data(&NoResolve).unwrap();
+        assert_eq!(data.data, [255, 0, 255, 0, 255, 0]);
+        assert_eq!(data.bits_per_component, 1);
+
+        assert!(image(gray(2, 2, 8), &[0, 0, 0]).image_data(&NoResolve).is_err());
+        assert!(image(gray(0, 2, 8), &[0, 0]).image_data(&NoResolve).is_err());
+        assert!(image(gray(2, -1, 8), &[0, 0]).image_data(&NoResolve).is_err());
+        assert!(image(gray(2, 1, 3), &[0, 0]).image_data(&NoResolve).is_err());
+    }
+
+    #[test]
+    fn indexed() {
+        let palette = PdfString::new(vec![255, 0, 0, 0, 0, 255]);
+        let mut entries = gray(3, 1, 8);
+        entries[3].1 = Primitive::Array(vec!["Indexed".into(), "DeviceRGB".into(), Primitive::Integer(1), palette.into()]);
+        let data = image(entries, &[1, 0, 7]).image_data(&NoResolve).unwrap();
+        assert_eq!(data.components, 3);
+        // indices above hival are clamped
+        assert_eq!(data.data, [0, 0, 255, 255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn color_key_mask() {
+        let mut entries = gray(3, 1, 8);
+        entries.push(("Mask", Primitive::Array(vec![Primitive::Integer(0), Primitive::Integer(10)])));
+        let data = image(entries, &[5, 200, 10]).image_data(&NoResolve).unwrap();
+        assert_eq!(data.alpha, Some(vec![0, 255, 0]));
+        assert_eq!(data.data, [5, 200, 10]);
+
+        assert_eq!(image(gray(1, 1, 8), &[5]).image_data(&NoResolve).unwrap().alpha, None);
+    }
+}
@@ -0,0 +1,193 @@
+//! Adding images to generated documents.
+//!
+//! ```ignore
+//! let image = file.add_image(&std::fs::read("photo.jpg")?, ImageFormat::Jpeg)?;
+//! let mut xobjects = Dictionary::new();
+//! xobjects.insert("Im1".into(), image.xobject.into());
+//! image.draw(&mut content, "Im1", 72., 400., 200., 200. * image.height as f32 / image.width as f32);
+//! ```
+
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream};
+use crate::backend::Backend;
+use crate::file::File;
+use crate::content::ContentBuilder;
+use crate::text::Matrix;
+
+/// The kind of data passed to `File::add_image`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ImageFormat {
+    /// A JPEG file. It is embedded as it is, with `DCTDecode`.
+    Jpeg,
+    /// 8 bit RGBA samples, row by row from the top left.
+    /// The alpha channel becomes a soft mask, unless the image is opaque.
+    Rgba { width: u32, height: u32 },
+}
+
+/// An image XObject added with `File::add_image`.
+#[derive(Debug, Copy, Clone)]
+pub struct AddedImage {
+    /// For the `/XObject` resources of the pages that show it
+    pub xobject: PlainRef,
+    /// In pixels
+    pub width: u32,
+    pub height: u32,
+}
+impl AddedImage {
+    /// Shows the image, which is `name` in the XObject resources of the page, scaled to
+    /// `width` and `height` with the lower left corner at `x`, `y`.
+    pub fn draw(&self, content: &mut ContentBuilder, name: &str, x: f32, y: f32, width: f32, height: f32) {
+        content.save()
+            .transform(Matrix::new(width, 0., 0., height, x, y))
+            .draw_xobject(name)
+            .restore();
+    }
+}
+
+impl<B: Backend> File<B> {
+    /// Adds an image XObject with the image `data`.
+    pub fn add_image(&mut self, data: &[u8], format: ImageFormat) -> Result<AddedImage> {
+        let (info, data, width, height) = match format {
+            ImageFormat::Jpeg => {
+                let jpeg = jpeg_info(data)?;
+                let mut info = image_info(jpeg.width, jpeg.height, match jpeg.components {
+                    1 => "DeviceGray",
+                    3 => "DeviceRGB",
+                    4 => "DeviceCMYK",
+                    n => bail!("JPEG with {} components", n)
+                });
+                // Adobe applications write CMYK JPEGs inverted
+                if jpeg.components == 4 && jpeg.adobe {
+                    info.insert("Decode".into(), Primitive::Array([1, 0, 1, 0, 1, 0, 1, 0].iter().map(|&n| Primitive::Integer(n)).collect()));
+                }
+                info.insert("Filter".into(), "DCTDecode".into());
+                (info, data.to_vec(), jpeg.width, jpeg.height)
+            }
+            ImageFormat::Rgba { width, height } => {
+                let pixels = width as usize * height as usize;
+                if data.len() != 4 * pixels {
+                    bail!("{} bytes of RGBA data for {}x{} pixels", data.len(), width, height);
+                }
+                let mut rgb = Vec::with_capacity(3 * pixels);
+                let mut alpha = Vec::with_capacity(pixels);
+                for pixel in data.chunks(4) {
+                    rgb.extend_from_slice(&pixel[.. 3]);
+                    alpha.push(pixel[3]);
+                }
+                let mut info = image_info(width, height, "DeviceRGB");
+                if alpha.iter().any(|&a| a != 255) {
+                    let mut mask_info = image_info(width, height, "DeviceGray");
+                    mask_info.insert("Filter".into(), "FlateDecode".into());
+                    let mask = self.add(Primitive::Stream(PdfStream { info: mask_info, data: deflate_bytes_zlib(&alpha).into() }));
+                    info.insert("SMask".into(), mask.get_inner().into());
+                }
+                info.insert("Filter".into(), "FlateDecode".into());
+                (info, deflate_bytes_zlib(&rgb), width, height)
+            }
+        };
+        let xobject = self.add(Primitive::Stream(PdfStream { info, data: data.into() })).get_inner();
+        Ok(AddedImage { xobject, width, height })
+    }
+}
+
+fn image_info(width: u32, height: u32, color_space: &str) -> Dictionary {
+    let mut info = Dictionary::new();
+    info.insert("Type".into(), "XObject".into());
+    info.insert("Subtype".into(), "Image".into());
+    info.insert("Width".into(), Primitive::Integer(width as i32));
+    info.insert("Height".into(), Primitive::Integer(height as i32));
+    info.insert("ColorSpace".into(), color_space.into());
+    info.insert("BitsPerComponent".into(), Primitive::Integer(8));
+    info
+}
+
+struct JpegInfo {
+    width: u32,
+    height: u32,
+    components: u8,
+    /// has an Adobe APP14 segment
+    adobe: bool,
+}
+
+/// The size and components of a JPEG file, from its frame header.
+fn jpeg_info(data: &[u8]) -> Result<JpegInfo> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        bail!("not a JPEG file");
+    }
+    let mut adobe = false;
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            bail!("no JPEG marker at {}", pos);
+        }
+        let marker = data[pos + 1];
+        // fill bytes
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = match data.get(pos + 4 .. pos + 2 + len) {
+            Some(segment) => segment,
+            None => break
+        };
+        match marker {
+            0xEE if segment.starts_with(b"Adobe") => adobe = true,
+            // start of frame, but not DHT, JPG and DAC
+            0xC0 ..= 0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                if segment.len() < 6 {
+                    break;
+                }
+                if segment[0] != 8 {
+                    bail!("JPEG with {} bits per component", segment[0]);
+                }
+                return Ok(JpegInfo {
+                    height: u16::from_be_bytes([segment[1], segment[2]]) as u32,
+                    width: u16::from_be_bytes([segment[3], segment[4]]) as u32,
+                    components: segment[5],
+                    adobe,
+                });
+            }
+            // start of scan, the frame header comes before
+            0xDA => break,
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+    bail!("JPEG without frame header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_image() {
+        let mut file = File::new().unwrap();
+        let rgba = [255, 0, 0, 255, 0, 0, 255, 128];
+        let image = file.add_image(&rgba, ImageFormat::Rgba { width: 2, height: 1 }).unwrap();
+        let xobject = file.get(Ref::<XObject>::new(image.xobject)).unwrap();
+        let xobject = match *xobject {
+            XObject::Image(ref image) => image,
+            _ => panic!()
+        };
+        assert!(xobject.smask.is_some());
+        let data = xobject.image_data(&file).unwrap();
+        assert_eq!((data.width, data.height, data.components), (2, 1, 3));
+        assert_eq!(data.data, [255, 0, 0, 0, 0, 255]);
+        assert!(file.add_image(&rgba, ImageFormat::Rgba { width: 3, height: 1 }).is_err());
+
+        // SOI, APP0 and SOF0 of a 640x480 RGB image
+        let jpeg = b"\xFF\xD8\xFF\xE0\x00\x04JF\xFF\xC0\x00\x11\x08\x01\xE0\x02\x80\x03\x01\x22\x00\x02\x11\x01\x03\x11\x01\xFF\xDA";
+        let image = file.add_image(jpeg, ImageFormat::Jpeg).unwrap();
+        assert_eq!((image.width, image.height), (640, 480));
+        assert!(file.add_image(b"GIF89a", ImageFormat::Jpeg).is_err());
+
+        let mut content = ContentBuilder::new();
+        image.draw(&mut content, "Im1", 10., 20., 64., 48.);
+        assert_eq!(content.build().to_data().unwrap(), &b"q\n64 0 0 48 10 20 cm\n/Im1 Do\nQ\n"[..]);
+    }
+}
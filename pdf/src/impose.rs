@@ -0,0 +1,253 @@
+//! Imposition: placing several pages on one sheet, for n-up printing and booklets.
+
+use std::io::Write;
+
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream};
+use crate::backend::Backend;
+use crate::file::File;
+use crate::merge::{Copier, page_tree};
+
+/// In which order pages are placed on the sheets.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ImposeOrder {
+    /// Page after page, each sheet row by row from the top left
+    Sequential,
+    /// Two pages on each side of a sheet, so that the printed sheets (duplex, flipped on the
+    /// short edge), stacked and folded in the middle, read in order.
+    Booklet,
+}
+
+impl<B: Backend> File<B> {
+    /// Replaces the pages of the document by sheets of `sheet_size`, each showing `pages_per_sheet`
+    /// of the old pages. The pages become form XObjects, scaled to fit and centered in the cells
+    /// of a grid; the grid is chosen so that the pages are as large as possible.
+    ///
+    /// Booklets need two pages per sheet, and are padded with blank pages to a multiple of four.
+    /// Pages whose crop box has no area leave their cell empty.
+    /// `/Rotate` of the pages and their annotations are not carried over.
+    pub fn impose(&mut self, pages_per_sheet: usize, sheet_size: Rect, order: ImposeOrder) -> Result<()> {
+        if pages_per_sheet == 0 {
+            bail!("no pages per sheet");
+        }
+        let sheet = normalize(sheet_size);
+        let (sheet_width, sheet_height) = (sheet.right - sheet.left, sheet.top - sheet.bottom);
+        if !(sheet_width > 0. && sheet_height > 0.) {
+            bail!("sheets of {}x{}", sheet_width, sheet_height);
+        }
+        let page_refs = self.page_refs()?;
+        let mut forms = Vec::with_capacity(page_refs.len());
+        for (&page_ref, page) in page_refs.iter().zip(self.pages()) {
+            let crop = normalize(page?.crop_box(self)?);
+            forms.push((self.page_form(page_ref, crop)?, crop));
+        }
+        let forms: Vec<(PlainRef, Rect)> = forms.into_iter()
+            .map(|(form, crop)| (self.add(Primitive::Stream(form)).get_inner(), crop))
+            .collect();
+
+        let count = forms.len();
+        let sheets: Vec<Vec<Option<usize>>> = match order {
+            ImposeOrder::Sequential => (0 .. count).collect::<Vec<_>>()
+                .chunks(pages_per_sheet)
+                .map(|pages| pages.iter().map(|&i| Some(i)).collect())
+                .collect(),
+            ImposeOrder::Booklet => {
+                if pages_per_sheet != 2 {
+                    bail!("booklets have two pages per sheet, not {}", pages_per_sheet);
+                }
+                let padded = (count + 3) / 4 * 4;
+                let page = |i: usize| if i < count { Some(i) } else { None };
+                (0 .. padded / 2).map(|side| match side % 2 {
+                    0 => vec![page(padded - 1 - side), page(side)],
+                    _ => vec![page(side), page(padded - 1 - side)],
+                }).collect()
+            }
+        };
+
+        let (page_width, page_height) = forms.iter().fold((0f32, 0f32), |(w, h), &(_, crop)| {
+            (w.max(crop.right - crop.left), h.max(crop.top - crop.bottom))
+        });
+        // the grid that shows the largest page largest
+        let mut grid = (pages_per_sheet, 1);
+        let mut best = 0.;
+        for cols in (1 ..= pages_per_sheet).filter(|c| pages_per_sheet % c == 0) {
+            let rows = pages_per_sheet / cols;
+            let scale = (sheet_width / cols as f32 / page_width).min(sheet_height / rows as f32 / page_height);
+            if scale > best {
+                best = scale;
+                grid = (cols, rows);
+            }
+        }
+        let (cols, rows) = grid;
+        let (cell_width, cell_height) = (sheet_width / cols as f32, sheet_height / rows as f32);
+
+        let tree = self.add(Primitive::Dictionary(Dictionary::new())).get_inner();
+        let mut kids = Vec::with_capacity(sheets.len());
+        for pages in sheets {
+            let mut xobjects = Dictionary::new();
+            let mut content = Vec::new();
+            for (i, &slot) in pages.iter().enumerate() {
+                let (form, crop) = match slot {
+                    Some(n) => forms[n],
+                    None => continue
+                };
+                let (col, row) = (i % cols, i / cols);
+                let (width, height) = (crop.right - crop.left, crop.top - crop.bottom);
+                // they can't be scaled to fit
+                if !(width > 0. && height > 0.) {
+                    continue;
+                }
+                let scale = (cell_width / width).min(cell_height / height);
+                // rows count from the top
+                let x = sheet.left + col as f32 * cell_width + (cell_width - width * scale) / 2. - crop.left * scale;
+                let y = sheet.top - (row + 1) as f32 * cell_height + (cell_height - height * scale) / 2. - crop.bottom * scale;
+                let name = format!("P{}", i);
+                writeln!(content, "q {} 0 0 {} {} {} cm /{} Do Q", scale, scale, x, y, name)?;
+                xobjects.insert(name, Primitive::Reference(form));
+            }
+            let mut resources = Dictionary::new();
+            resources.insert("XObject".into(), xobjects.into());
+            let contents = self.add(Primitive::Stream(PdfStream { info: Dictionary::new(), data: content.into() }));
+
+            let mut page = Dictionary::new();
+            page.insert("Type".into(), "Page".into());
+            page.insert("Parent".into(), tree.into());
+            page.insert("MediaBox".into(), sheet_size.into());
+            page.insert("Resources".into(), resources.into());
+            page.insert("Contents".into(), contents.get_inner().into());
+            kids.push(self.add(Primitive::Dictionary(page)).get_inner().into());
+        }
+        let mut pages = Dictionary::new();
+        pages.insert("Type".into(), "Pages".into());
+        pages.insert("Count".into(), Primitive::Integer(kids.len() as i32));
+        pages.insert("Kids".into(), Primitive::Array(kids));
+        self.update(tree.id, pages.into());
+        self.set_page_tree(tree)
+    }
+
+    /// Makes the page `page_nr` (counting from 0) a form XObject, to be painted on other pages
+    /// with `Do`, for example as a letterhead. It shows the crop box of the page, in the
+    /// coordinates of the page. `/Rotate` and annotations are not carried over.
+    pub fn page_as_xobject(&mut self, page_nr: u32) -> Result<PlainRef> {
+        let (page_ref, crop) = self.page_and_crop_box(page_nr)?;
+        let form = self.page_form(page_ref, crop)?;
+        Ok(self.add(Primitive::Stream(form)).get_inner())
+    }
+
+    /// Like `page_as_xobject` for the page `page_nr` of `other`, which is copied into this
+    /// document together with the resources it uses.
+    pub fn import_page_as_xobject<C: Backend>(&mut self, other: &File<C>, page_nr: u32) -> Result<PlainRef> {
+        let (page_ref, crop) = other.page_and_crop_box(page_nr)?;
+        let form = other.page_form(page_ref, crop)?;
+        let mut copier = Copier::new(other, page_tree(other)?);
+        let form = copier.remap(self, Primitive::Stream(form));
+        copier.finish(self)?;
+        Ok(self.add(form).get_inner())
+    }
+
+    fn page_and_crop_box(&self, page_nr: u32) -> Result<(PlainRef, Rect)> {
+        let page_refs = self.page_refs()?;
+        let page_ref = match page_refs.get(page_nr as usize) {
+            Some(&r) => r,
+            None => return Err(PdfError::PageOutOfBounds { page_nr, max: page_refs.len() as u32 })
+        };
+        Ok((page_ref, normalize(self.get_page(page_nr)?.crop_box(self)?)))
+    }
+
+    /// A form XObject showing `bbox` of the page `page`.
+    fn page_form(&self, page: PlainRef, bbox: Rect) -> Result<PdfStream> {
+        let dict = self.resolve(page)?.to_dictionary(self)?;
+        let mut data = Vec::new();
+        if let Some(contents) = dict.get("Contents") {
+            let parts = match contents.clone().resolve(self)? {
+                Primitive::Array(parts) => parts,
+                p => vec![p]
+            };
+            for part in parts {
+                data.extend_from_slice(Stream::<()>::from_primitive(part, self)?.data()?);
+                data.push(b'\n');
+            }
+        }
+        let mut info = Dictionary::new();
+        info.insert("Type".into(), "XObject".into());
+        info.insert("Subtype".into(), "Form".into());
+        info.insert("BBox".into(), bbox.into());
+        if let Some(resources) = self.raw_page_resources(page)? {
+            info.insert("Resources".into(), resources);
+        }
+        info.insert("Filter".into(), "FlateDecode".into());
+        Ok(PdfStream { info, data: deflate_bytes_zlib(&data).into() })
+    }
+}
+
+/// `r` with left < right and bottom < top
+fn normalize(r: Rect) -> Rect {
+    Rect {
+        left: r.left.min(r.right),
+        bottom: r.bottom.min(r.top),
+        right: r.left.max(r.right),
+        top: r.bottom.max(r.top),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::Content;
+
+    /// A document with pages of `sizes`, each filling a square as large as its number
+    fn document(sizes: &[(f32, f32)]) -> File<Vec<u8>> {
+        let mut file = File::new().unwrap();
+        for (i, &(width, height)) in sizes.iter().enumerate() {
+            let ops = format!("0 0 {} {} re f", i + 1, i + 1);
+            let content = Content::parse_from(ops.as_bytes(), &NoResolve).unwrap();
+            file.add_page(Rect { left: 0., bottom: 0., right: width, top: height }, &content, Dictionary::new()).unwrap();
+        }
+        file
+    }
+
+    /// The content stream of each sheet, and the cell and number of the pages on it
+    fn sheets(file: &File<Vec<u8>>) -> Vec<(String, Vec<(usize, usize)>)> {
+        file.page_refs().unwrap().into_iter().map(|r| {
+            let sheet = file.resolve(r).unwrap().to_dictionary(file).unwrap();
+            let content = Stream::<()>::from_primitive(sheet.get("Contents").unwrap().clone(), file).unwrap();
+            let content = String::from_utf8(content.data().unwrap().to_vec()).unwrap();
+            let resources = sheet.get("Resources").unwrap().clone().to_dictionary(file).unwrap();
+            let xobjects = resources.get("XObject").unwrap().clone().to_dictionary(file).unwrap();
+            let pages = xobjects.iter().map(|(name, form)| {
+                let form = Stream::<()>::from_primitive(form.clone(), file).unwrap();
+                let ops = Content::parse_from(form.data().unwrap(), file).unwrap().operations;
+                (name[1..].parse().unwrap(), ops[0].operands[2].as_integer().unwrap() as usize - 1)
+            }).collect();
+            (content, pages)
+        }).collect()
+    }
+
+    #[test]
+    fn booklet() {
+        let mut file = document(&[(100., 200.); 5]);
+        let sheet = Rect { left: 0., bottom: 0., right: 200., top: 200. };
+        assert!(file.impose(4, sheet, ImposeOrder::Booklet).is_err());
+        file.impose(2, sheet, ImposeOrder::Booklet).unwrap();
+        // padded to eight pages: 8 1, 2 7, 6 3, 4 5
+        let pages: Vec<_> = sheets(&file).into_iter().map(|(_, pages)| pages).collect();
+        assert_eq!(pages, [vec![(1, 0)], vec![(0, 1)], vec![(1, 2)], vec![(0, 3), (1, 4)]]);
+    }
+
+    #[test]
+    fn grid() {
+        // portrait pages on a landscape sheet fit best side by side
+        let mut file = document(&[(100., 200.), (100., 200.), (0., 200.), (100., 200.)]);
+        let sheet = Rect { left: 0., bottom: 0., right: 400., top: 200. };
+        assert!(file.impose(4, Rect { right: 0., .. sheet }, ImposeOrder::Sequential).is_err());
+        file.impose(4, sheet, ImposeOrder::Sequential).unwrap();
+        let sheets = sheets(&file);
+        assert_eq!(sheets.len(), 1);
+        // the page without width is left out
+        assert_eq!(sheets[0].0, "q 1 0 0 1 0 0 cm /P0 Do Q\nq 1 0 0 1 100 0 cm /P1 Do Q\nq 1 0 0 1 300 0 cm /P3 Do Q\n");
+        assert_eq!(sheets[0].1, [(0, 0), (1, 1), (3, 3)]);
+    }
+}
@@ -0,0 +1,432 @@
+//! JBIG2 decoding (ITU T.88) of embedded streams (7.4.7).
+//!
+//! Only generic regions with arithmetic coding are supported. Symbol dictionaries,
+//! text, halftone and refinement regions as well as MMR coding result in an error.
+
+use crate::error::*;
+
+/// Probability estimation table (Table E.1): Qe, NMPS, NLPS, SWITCH
+static QE_TABLE: [(u32, u8, u8, bool); 47] = [
+    (0x5601,  1,  1, true),  (0x3401,  2,  6, false), (0x1801,  3,  9, false), (0x0AC1,  4, 12, false),
+    (0x0521,  5, 29, false), (0x0221, 38, 33, false), (0x5601,  7,  6, true),  (0x5401,  8, 14, false),
+    (0x4801,  9, 14, false), (0x3801, 10, 14, false), (0x3001, 11, 17, false), (0x2401, 12, 18, false),
+    (0x1C01, 13, 20, false), (0x1601, 29, 21, false), (0x5601, 15, 14, true),  (0x5401, 16, 14, false),
+    (0x5101, 17, 15, false), (0x4801, 18, 16, false), (0x3801, 19, 17, false), (0x3401, 20, 18, false),
+    (0x3001, 21, 19, false), (0x2801, 22, 19, false), (0x2401, 23, 20, false), (0x2201, 24, 21, false),
+    (0x1C01, 25, 22, false), (0x1801, 26, 23, false), (0x1601, 27, 24, false), (0x1401, 28, 25, false),
+    (0x1201, 29, 26, false), (0x1101, 30, 27, false), (0x0AC1, 31, 28, false), (0x09C1, 32, 29, false),
+    (0x08A1, 33, 30, false), (0x0521, 34, 31, false), (0x0441, 35, 32, false), (0x02A1, 36, 33, false),
+    (0x0221, 37, 34, false), (0x0141, 38, 35, false), (0x0111, 39, 36, false), (0x0085, 40, 37, false),
+    (0x0049, 41, 38, false), (0x0025, 42, 39, false), (0x0015, 43, 40, false), (0x0009, 44, 41, false),
+    (0x0005, 45, 42, false), (0x0001, 45, 43, false), (0x5601, 46, 46, false),
+];
+
+/// Adaptive state of one context: index into `QE_TABLE` and the more probable symbol.
+#[derive(Copy, Clone, Default)]
+struct Context {
+    index: u8,
+    mps: u8,
+}
+
+/// MQ arithmetic decoder (Annex E.3)
+struct ArithmeticDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    c: u32,
+    a: u32,
+    ct: u32,
+}
+impl<'a> ArithmeticDecoder<'a> {
+    fn new(data: &'a [u8]) -> ArithmeticDecoder<'a> {
+        let mut decoder = ArithmeticDecoder { data, pos: 0, c: 0, a: 0x8000, ct: 0 };
+        decoder.c = (decoder.byte(0) as u32) << 16;
+        decoder.byte_in();
+        decoder.c <<= 7;
+        decoder.ct -= 7;
+        decoder
+    }
+    /// Reading past the end gives 0xFF, which acts as a marker and stops the input.
+    fn byte(&self, pos: usize) -> u8 {
+        self.data.get(pos).cloned().unwrap_or(0xFF)
+    }
+    fn byte_in(&mut self) {
+        if self.byte(self.pos) == 0xFF {
+            if self.byte(self.pos + 1) > 0x8F {
+                self.c += 0xFF00;
+                self.ct = 8;
+            } else {
+                self.pos += 1;
+                self.c += (self.byte(self.pos) as u32) << 9;
+                self.ct = 7;
+            }
+        } else {
+            self.pos += 1;
+            self.c += (self.byte(self.pos) as u32) << 8;
+            self.ct = 8;
+        }
+    }
+    fn decode(&mut self, cx: &mut Context) -> u8 {
+        let (qe, nmps, nlps, switch) = QE_TABLE[cx.index as usize];
+        self.a -= qe;
+        let d;
+        if (self.c >> 16) < qe {
+            // LPS exchange
+            if self.a < qe {
+                d = cx.mps;
+                cx.index = nmps;
+            } else {
+                d = 1 - cx.mps;
+                if switch {
+                    cx.mps = 1 - cx.mps;
+                }
+                cx.index = nlps;
+            }
+            self.a = qe;
+        } else {
+            self.c -= qe << 16;
+            if self.a & 0x8000 != 0 {
+                return cx.mps;
+            }
+            // MPS exchange
+            if self.a < qe {
+                d = 1 - cx.mps;
+                if switch {
+                    cx.mps = 1 - cx.mps;
+                }
+                cx.index = nlps;
+            } else {
+                d = cx.mps;
+                cx.index = nmps;
+            }
+        }
+        // renormalize
+        while self.a & 0x8000 == 0 {
+            if self.ct == 0 {
+                self.byte_in();
+            }
+            self.a <<= 1;
+            self.c <<= 1;
+            self.ct -= 1;
+        }
+        d
+    }
+}
+
+/// Largest bitmap that is decoded, in pixels
+const MAX_PIXELS: usize = 1 << 28;
+
+/// One pixel per byte, 1 is black.
+struct Bitmap {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+impl Bitmap {
+    fn new(width: usize, height: usize, value: u8) -> Result<Bitmap> {
+        Ok(Bitmap { width, height, data: vec![value; pixels(width, height)?] })
+    }
+    fn get(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return 0;
+        }
+        self.data[y as usize * self.width + x as usize]
+    }
+    /// Combines `region` into this bitmap at (x, y) with the combination operator `op` (7.4.8.5).
+    fn combine(&mut self, region: &Bitmap, x: usize, y: usize, op: u8) {
+        for ry in 0 .. region.height {
+            let py = y + ry;
+            if py >= self.height {
+                break;
+            }
+            for rx in 0 .. region.width {
+                let px = x + rx;
+                if px >= self.width {
+                    break;
+                }
+                let src = region.data[ry * region.width + rx];
+                let dst = &mut self.data[py * self.width + px];
+                *dst = match op {
+                    0 => *dst | src,
+                    1 => *dst & src,
+                    2 => *dst ^ src,
+                    3 => 1 - (*dst ^ src),
+                    _ => src,
+                };
+            }
+        }
+    }
+}
+
+fn pixels(width: usize, height: usize) -> Result<usize> {
+    match width.checked_mul(height) {
+        Some(n) if n <= MAX_PIXELS => Ok(n),
+        _ => bail!("JBIG2 bitmap of {}x{} pixels is too large", width, height)
+    }
+}
+
+/// Template pixels relative to the current one (6.2.5.3), without the adaptive ones.
+static TEMPLATES: [&[(i8, i8)]; 4] = [
+    &[(-1, -2), (0, -2), (1, -2), (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1), (-4, 0), (-3, 0), (-2, 0), (-1, 0)],
+    &[(-1, -2), (0, -2), (1, -2), (2, -2), (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1), (-3, 0), (-2, 0), (-1, 0)],
+    &[(-1, -2), (0, -2), (1, -2), (-2, -1), (-1, -1), (0, -1), (1, -1), (-2, 0), (-1, 0)],
+    &[(-3, -1), (-2, -1), (-1, -1), (0, -1), (1, -1), (-4, 0), (-3, 0), (-2, 0), (-1, 0)],
+];
+/// Context used to decode SLTP for typical prediction (6.2.5.7)
+static TPGDON_CONTEXTS: [usize; 4] = [0x9B25, 0x0795, 0x00E5, 0x0195];
+
+/// Generic region decoding procedure with arithmetic coding (6.2.5.7)
+fn decode_generic(data: &[u8], width: usize, height: usize, template: usize, tpgdon: bool, at: &[(i8, i8)]) -> Result<Bitmap> {
+    let mut pixels: Vec<(i8, i8)> = TEMPLATES[template].iter().chain(at.iter()).cloned().collect();
+    // Row by row, this is the order of the context bits in the specification when the adaptive
+    // pixels are at their nominal positions. `TPGDON_CONTEXTS` depend on it.
+    pixels.sort_by_key(|&(x, y)| (y, x));
+
+    let mut contexts = vec![Context::default(); 1 << 16];
+    let mut decoder = ArithmeticDecoder::new(data);
+    let mut bitmap = Bitmap::new(width, height, 0)?;
+    let mut ltp = 0;
+    for y in 0 .. height {
+        if tpgdon {
+            ltp ^= decoder.decode(&mut contexts[TPGDON_CONTEXTS[template]]);
+            if ltp == 1 {
+                // same as the row above
+                if y > 0 {
+                    let (above, row) = bitmap.data.split_at_mut(y * width);
+                    row[.. width].copy_from_slice(&above[(y - 1) * width ..]);
+                }
+                continue;
+            }
+        }
+        for x in 0 .. width {
+            let mut cx = 0;
+            for &(dx, dy) in &pixels {
+                cx = cx << 1 | bitmap.get(x as i32 + dx as i32, y as i32 + dy as i32) as usize;
+            }
+            bitmap.data[y * width + x] = decoder.decode(&mut contexts[cx]);
+        }
+    }
+    Ok(bitmap)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        match self.data.get(self.pos .. self.pos + n) {
+            Some(bytes) => {
+                self.pos += n;
+                Ok(bytes)
+            }
+            None => bail!("JBIG2 data ends unexpectedly")
+        }
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok((b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32)
+    }
+}
+
+/// Segment header (7.2)
+struct Segment<'a> {
+    number: u32,
+    kind: u8,
+    data: &'a [u8],
+    /// The data ends with an end marker and the row count of the region.
+    unknown_length: bool,
+}
+
+fn read_segment<'a>(r: &mut Reader<'a>) -> Result<Segment<'a>> {
+    let number = r.u32()?;
+    let flags = r.u8()?;
+    let kind = flags & 0x3F;
+    let large_page = flags & 0x40 != 0;
+
+    let count_byte = r.u8()?;
+    let referred = match count_byte >> 5 {
+        7 => {
+            r.pos -= 1;
+            let count = (r.u32()? & 0x1FFF_FFFF) as usize;
+            r.bytes((count + 8) / 8)?;
+            count
+        }
+        5 | 6 => bail!("invalid JBIG2 referred-to segment count"),
+        n => n as usize
+    };
+    let ref_size = match number {
+        0 ..= 256 => 1,
+        257 ..= 65536 => 2,
+        _ => 4
+    };
+    r.bytes(referred * ref_size)?;
+    r.bytes(if large_page { 4 } else { 1 })?;
+
+    let length = r.u32()?;
+    let unknown_length = length == 0xFFFF_FFFF;
+    let data = if unknown_length {
+        // only allowed for immediate generic regions: ends with FF AC and the row count
+        let rest = &r.data[r.pos ..];
+        match rest.windows(2).position(|w| w == [0xFF, 0xAC]) {
+            Some(end) if end + 6 <= rest.len() => r.bytes(end + 6)?,
+            _ => bail!("JBIG2 segment of unknown length without end marker")
+        }
+    } else {
+        r.bytes(length as usize)?
+    };
+    Ok(Segment { number, kind, data, unknown_length })
+}
+
+/// Page information segment (7.4.8)
+struct PageInfo {
+    height: Option<usize>,
+    default_pixel: u8,
+}
+
+/// Decodes an embedded JBIG2 stream with optional global segments.
+/// The result has one bit per pixel, rows start at byte boundaries and 0 is black (like DeviceGray).
+pub fn decode_jbig2(data: &[u8], globals: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut segments = Vec::new();
+    for &part in globals.iter().chain(Some(data).iter()) {
+        let mut r = Reader { data: part, pos: 0 };
+        while r.pos < part.len() {
+            segments.push(read_segment(&mut r)?);
+        }
+    }
+
+    let mut page: Option<(PageInfo, Bitmap)> = None;
+    for segment in segments {
+        let mut r = Reader { data: segment.data, pos: 0 };
+        match segment.kind {
+            // page information
+            48 => {
+                let width = r.u32()? as usize;
+                let height = r.u32()?;
+                r.u32()?; // resolution
+                r.u32()?;
+                let flags = r.u8()?;
+                let info = PageInfo {
+                    height: if height == 0xFFFF_FFFF { None } else { Some(height as usize) },
+                    default_pixel: (flags >> 2) & 1,
+                };
+                let bitmap = Bitmap::new(width, info.height.unwrap_or(0), info.default_pixel)?;
+                page = Some((info, bitmap));
+            }
+            // immediate (lossless) generic region
+            38 | 39 => {
+                let (info, bitmap) = match page {
+                    Some(ref mut page) => (&page.0, &mut page.1),
+                    None => bail!("JBIG2 region segment {} before the page information", segment.number)
+                };
+                let width = r.u32()? as usize;
+                let mut height = r.u32()? as usize;
+                let x = r.u32()? as usize;
+                let y = r.u32()? as usize;
+                let op = r.u8()? & 7;
+                let flags = r.u8()?;
+                if flags & 1 != 0 {
+                    bail!("JBIG2 MMR coded generic regions are not supported");
+                }
+                let template = ((flags >> 1) & 3) as usize;
+                let tpgdon = flags & 8 != 0;
+                let mut at = Vec::new();
+                for _ in 0 .. if template == 0 { 4 } else { 1 } {
+                    let ax = r.u8()? as i8;
+                    let ay = r.u8()? as i8;
+                    at.push((ax, ay));
+                }
+                let mut coded = &segment.data[r.pos ..];
+                if segment.unknown_length {
+                    if coded.len() < 6 {
+                        bail!("JBIG2 region segment {} is too short", segment.number);
+                    }
+                    // the row count follows the end marker
+                    let mut tail = Reader { data: &coded[coded.len() - 4 ..], pos: 0 };
+                    height = tail.u32()? as usize;
+                    coded = &coded[.. coded.len() - 6];
+                }
+                let region = decode_generic(coded, width, height, template, tpgdon, &at)?;
+
+                if info.height.is_none() && y + height > bitmap.height {
+                    // striped page of unknown height
+                    bitmap.height = y + height;
+                    bitmap.data.resize(pixels(bitmap.width, bitmap.height)?, info.default_pixel);
+                }
+                bitmap.combine(&region, x, y, op);
+            }
+            // end of page, end of file
+            49 | 51 => break,
+            // intermediate generic region, end of stripe, profiles, tables, extensions
+            36 | 50 | 52 | 53 | 62 => {}
+            kind => bail!("JBIG2 segment type {} is not supported", kind)
+        }
+    }
+
+    let bitmap = match page {
+        Some((_, bitmap)) => bitmap,
+        None => bail!("JBIG2 data without page information")
+    };
+    let stride = (bitmap.width + 7) / 8;
+    let mut out = vec![0xFF; stride * bitmap.height];
+    for (row, out_row) in bitmap.data.chunks(bitmap.width.max(1)).zip(out.chunks_mut(stride)) {
+        for (x, &pixel) in row.iter().enumerate() {
+            if pixel == 1 {
+                out_row[x / 8] &= !(0x80 >> (x % 8));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_decoder() {
+        // test sequence of Annex H.2, coded with a single context
+        let coded = [
+            0x84, 0xC7, 0x3B, 0xFC, 0xE1, 0xA1, 0x43, 0x04, 0x02, 0x20, 0x00, 0x00, 0x41, 0x0D, 0xBB, 0x86,
+            0xF4, 0x31, 0x7F, 0xFF, 0x88, 0xFF, 0x37, 0x47, 0x1A, 0xDB, 0x6A, 0xDF, 0xFF, 0xAC
+        ];
+        let expected = [
+            0x00, 0x02, 0x00, 0x51, 0x00, 0x00, 0x00, 0xC0, 0x03, 0x52, 0x87, 0x2A, 0xAA, 0xAA, 0xAA, 0xAA,
+            0x82, 0xC0, 0x20, 0x00, 0xFC, 0xD7, 0x9E, 0xF6, 0xBF, 0x7F, 0xED, 0x90, 0x4F, 0x46, 0xA3, 0xBF
+        ];
+        let mut decoder = ArithmeticDecoder::new(&coded);
+        let mut cx = Context::default();
+        let decoded: Vec<u8> = (0 .. expected.len())
+            .map(|_| (0 .. 8).fold(0, |byte, _| byte << 1 | decoder.decode(&mut cx)))
+            .collect();
+        assert_eq!(decoded, &expected[..]);
+    }
+
+    #[test]
+    fn generic_region() {
+        // a 10x5 page with one immediate generic region (template 0, typical prediction):
+        // 1100110011 / 0111111110 / 0111111110 / 1000000001 / 1000000001
+        let data = [
+            // page information
+            0x00, 0x00, 0x00, 0x00, 0x30, 0x00, 0x01, 0x00, 0x00, 0x00, 0x13,
+            0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00,
+            // immediate lossless generic region
+            0x00, 0x00, 0x00, 0x01, 0x26, 0x00, 0x01, 0x00, 0x00, 0x00, 0x22,
+            0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x08, 0x03, 0xFF, 0xFD, 0xFF, 0x02, 0xFE, 0xFE, 0xFE,
+            0x22, 0xD2, 0x81, 0xD5, 0xFF, 0x7F, 0xFF, 0xAC,
+            // end of page
+            0x00, 0x00, 0x00, 0x02, 0x31, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00
+        ];
+        let decoded = decode_jbig2(&data, None).unwrap();
+        assert_eq!(decoded, [0x33, 0x3F, 0x80, 0x7F, 0x80, 0x7F, 0x7F, 0xBF, 0x7F, 0xBF]);
+
+        // the page information claims 4 billion rows
+        let mut huge = data;
+        huge[15 .. 19].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFE]);
+        assert!(decode_jbig2(&huge, None).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! The object graph as JSON, for inspection, much like `qpdf --json`.
+//!
+//! Dictionary keys and names keep their leading slash, references that aren't expanded are
+//! written as `"N G R"`, and strings as text if they are valid UTF-8 and as bytes otherwise.
+
+use serde_json::{Value, Map};
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary};
+use crate::backend::Backend;
+use crate::file::File;
+
+impl<B: Backend> File<B> {
+    /// The trailer and all objects as JSON:
+    /// `{"trailer": …, "objects": {"1 0 R": …, …}}`.
+    ///
+    /// References are replaced by the object they point to, `depth` levels deep, except where
+    /// that would repeat an object that is already being expanded. Streams become
+    /// `{"stream": {"dict": …, "length": …}}`, with the raw (still filtered) data
+    /// base64 encoded in `"data"` if `include_streams` is set.
+    pub fn to_json(&self, depth: usize, include_streams: bool) -> Result<Value> {
+        let mut json = Json { file: self, include_streams, path: Vec::new() };
+        let trailer = json.value(Primitive::Dictionary(self.trailer_dict().clone()), depth);
+        let mut objects = Map::new();
+        for r in self.object_refs() {
+            let value = match self.resolve(r) {
+                Ok(p) => {
+                    json.path.push(r);
+                    let value = json.value(p, depth);
+                    json.path.pop();
+                    value
+                }
+                Err(e) => {
+                    warn!("object {} {}: {}", r.id, r.gen, e);
+                    Value::Null
+                }
+            };
+            objects.insert(reference(r), value);
+        }
+        let mut root = Map::new();
+        root.insert("trailer".into(), trailer);
+        root.insert("objects".into(), Value::Object(objects));
+        Ok(Value::Object(root))
+    }
+}
+
+struct Json<'a, B: Backend> {
+    file: &'a File<B>,
+    include_streams: bool,
+    /// The references being expanded
+    path: Vec<PlainRef>,
+}
+impl<'a, B: Backend> Json<'a, B> {
+    fn value(&mut self, p: Primitive, depth: usize) -> Value {
+        match p {
+            Primitive::Null => Value::Null,
+            Primitive::Integer(i) => i.into(),
+            Primitive::Number(n) => n.into(),
+            Primitive::Boolean(b) => b.into(),
+            Primitive::String(s) => serde_json::to_value(&s).unwrap_or(Value::Null),
+            Primitive::Name(name) => format!("/{}", name).into(),
+            Primitive::Array(parts) => parts.into_iter().map(|p| self.value(p, depth)).collect(),
+            Primitive::Dictionary(dict) => self.dict(dict, depth),
+            Primitive::Stream(stream) => {
+                let mut map = Map::new();
+                map.insert("dict".into(), self.dict(stream.info, depth));
+                map.insert("length".into(), stream.data.len().into());
+                if self.include_streams {
+                    map.insert("data".into(), base64::encode(&stream.data).into());
+                }
+                let mut outer = Map::new();
+                outer.insert("stream".into(), Value::Object(map));
+                Value::Object(outer)
+            }
+            Primitive::Reference(r) => {
+                if depth == 0 || self.path.contains(&r) {
+                    return reference(r).into();
+                }
+                match self.file.resolve(r) {
+                    Ok(p) => {
+                        self.path.push(r);
+                        let value = self.value(p, depth - 1);
+                        self.path.pop();
+                        value
+                    }
+                    Err(_) => reference(r).into()
+                }
+            }
+        }
+    }
+    fn dict(&mut self, dict: Dictionary, depth: usize) -> Value {
+        let mut map = Map::new();
+        for (key, p) in dict {
+            let value = self.value(p, depth);
+            map.insert(format!("/{}", key), value);
+        }
+        Value::Object(map)
+    }
+}
+
+fn reference(r: PlainRef) -> String {
+    format!("{} {} R", r.id, r.gen)
+}
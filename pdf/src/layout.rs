@@ -0,0 +1,309 @@
+//! Breaking text into lines within a rectangle, and tables of such text, for generated documents.
+//!
+//! ```ignore
+//! let widths = FontWidths::from_font(&font)?;
+//! let mut content = ContentBuilder::new();
+//! let rest = Paragraph::new("F1", &widths, 11.)
+//!     .align(Align::Justify)
+//!     .draw(&mut content, text, Rect { left: 72., bottom: 72., right: 523., top: 770. });
+//!
+//! let rows = vec![vec!["Item", "Price"], vec!["Coffee", "3.20"]];
+//! let (drawn, y) = Table::new("F1", &widths, 11., vec![300., 151.])
+//!     .align(1, Align::Right)
+//!     .draw(&mut content, &rows, 72., 770., 72.);
+//! ```
+
+use crate::content::ContentBuilder;
+use crate::font::FontWidths;
+use crate::object::Rect;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    /// Stretches the spaces of all lines but the last of each paragraph to the full width
+    Justify,
+}
+
+/// A line of text found by `Paragraph::lines`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// Where the line is in the text, without the spaces around it
+    pub start: usize,
+    pub end: usize,
+    /// In text space units
+    pub width: f32,
+    /// The line ends a paragraph, or the text
+    pub last: bool,
+}
+
+/// Text in one font and size, broken into lines by the widths of its glyphs.
+#[derive(Debug, Clone)]
+pub struct Paragraph<'a> {
+    font: &'a str,
+    widths: &'a FontWidths,
+    size: f32,
+    leading: f32,
+    align: Align,
+}
+impl<'a> Paragraph<'a> {
+    /// `font` is the name of the font in the resources of the page. The leading is 1.2 times `size`.
+    pub fn new(font: &'a str, widths: &'a FontWidths, size: f32) -> Paragraph<'a> {
+        Paragraph { font, widths, size, leading: 1.2 * size, align: Align::Left }
+    }
+    /// The distance between the baselines of two lines.
+    pub fn leading(mut self, leading: f32) -> Self {
+        self.leading = leading;
+        self
+    }
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Breaks `text` into lines no wider than `width`, at spaces and newlines.
+    /// A word wider than `width` gets a line of its own.
+    pub fn lines(&self, text: &str, width: f32) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        for paragraph in text.split('\n') {
+            let mut line: Option<Line> = None;
+            for (start, end) in words(paragraph) {
+                let (start, end) = (offset + start, offset + end);
+                if let Some(ref mut l) = line {
+                    let wider = l.width + self.widths.measure(&text[l.end .. end], self.size);
+                    if wider <= width {
+                        l.end = end;
+                        l.width = wider;
+                        continue;
+                    }
+                }
+                lines.extend(line.take());
+                line = Some(Line { start, end, width: self.widths.measure(&text[start .. end], self.size), last: false });
+            }
+            match line {
+                Some(mut l) => {
+                    l.last = true;
+                    lines.push(l);
+                }
+                // an empty line
+                None => lines.push(Line { start: offset, end: offset, width: 0., last: true })
+            }
+            offset += paragraph.len() + 1;
+        }
+        lines
+    }
+
+    /// Shows as many lines of `text` as fit into `rect`, from its top, and returns the rest.
+    pub fn draw<'t>(&self, content: &mut ContentBuilder, text: &'t str, rect: Rect) -> &'t str {
+        let width = rect.right - rect.left;
+        let lines = self.lines(text, width);
+        // the baseline of the first line is one font size below the top,
+        // with a little tolerance for the rounding of `rect`
+        let fitting = match self.leading {
+            leading if leading > 0. => ((rect.top - rect.bottom - self.size) / leading + 1e-3).floor() as isize + 1,
+            _ => lines.len() as isize
+        };
+        let fitting = fitting.max(0).min(lines.len() as isize) as usize;
+        if fitting == 0 {
+            return text;
+        }
+
+        content.begin_text().set_font(self.font, self.size);
+        // word spacing only applies to code 32
+        let space_is_32 = self.widths.encode(" ") == b" ";
+        let mut word_spacing = 0.;
+        let (mut x, mut y) = (0., 0.);
+        for (i, line) in lines[.. fitting].iter().enumerate() {
+            let free = width - line.width;
+            let dx = match self.align {
+                Align::Left | Align::Justify => 0.,
+                Align::Center => free / 2.,
+                Align::Right => free,
+            };
+            let spacing = match self.align {
+                Align::Justify if !line.last && space_is_32 => {
+                    let spaces = text[line.start .. line.end].matches(' ').count();
+                    if spaces > 0 { free / spaces as f32 } else { 0. }
+                }
+                _ => 0.
+            };
+            if spacing != word_spacing {
+                content.word_spacing(spacing);
+                word_spacing = spacing;
+            }
+            if i == 0 {
+                content.move_text(rect.left + dx, rect.top - self.size);
+            } else {
+                content.move_text(dx - x, -y);
+            }
+            x = dx;
+            y = self.leading;
+            if line.end > line.start {
+                content.show_text(self.widths.encode(&text[line.start .. line.end]));
+            }
+        }
+        // word spacing is part of the graphics state
+        if word_spacing != 0. {
+            content.word_spacing(0.);
+        }
+        content.end_text();
+
+        match lines.get(fitting) {
+            Some(next) => &text[next.start ..],
+            None => ""
+        }
+    }
+}
+
+/// Rows of text cells in columns of fixed widths, each row as high as its tallest cell.
+#[derive(Debug, Clone)]
+pub struct Table<'a> {
+    paragraph: Paragraph<'a>,
+    columns: Vec<f32>,
+    aligns: Vec<Align>,
+    padding: f32,
+    border: f32,
+    min_row_height: f32,
+}
+impl<'a> Table<'a> {
+    /// Text in `font` (the name in the resources of the page) and `size`, in columns of the given
+    /// widths. Cells have a padding of a quarter of `size` and borders 0.5 wide.
+    pub fn new(font: &'a str, widths: &'a FontWidths, size: f32, columns: Vec<f32>) -> Table<'a> {
+        let aligns = vec![Align::Left; columns.len()];
+        Table { paragraph: Paragraph::new(font, widths, size), columns, aligns, padding: size / 4., border: 0.5, min_row_height: 0. }
+    }
+    /// How the text of `column` is aligned.
+    pub fn align(mut self, column: usize, align: Align) -> Self {
+        if let Some(a) = self.aligns.get_mut(column) {
+            *a = align;
+        }
+        self
+    }
+    /// The space between the borders and the text of a cell.
+    pub fn padding(mut self, padding: f32) -> Self {
+        self.padding = padding;
+        self
+    }
+    /// The line width of the cell borders, 0 for none.
+    pub fn border(mut self, width: f32) -> Self {
+        self.border = width;
+        self
+    }
+    pub fn min_row_height(mut self, height: f32) -> Self {
+        self.min_row_height = height;
+        self
+    }
+    /// The distance between the baselines of the lines within a cell.
+    pub fn leading(mut self, leading: f32) -> Self {
+        self.paragraph.leading = leading;
+        self
+    }
+
+    /// The height of a row with `cells`, one for each column.
+    pub fn row_height(&self, cells: &[&str]) -> f32 {
+        let lines = cells.iter().zip(&self.columns)
+            .map(|(text, &width)| self.paragraph.lines(text, width - 2. * self.padding).len())
+            .max().unwrap_or(0);
+        // like `Paragraph::draw`, from the top to the baseline of the last line
+        let text_height = match lines {
+            0 => 0.,
+            n => self.paragraph.size + (n - 1) as f32 * self.paragraph.leading.max(0.)
+        };
+        (text_height + 2. * self.padding).max(self.min_row_height)
+    }
+
+    /// Draws as many of `rows` as fit between `top` and `bottom`, with the left edge at `left`.
+    /// Returns the number of rows drawn and where the last of them ends.
+    pub fn draw(&self, content: &mut ContentBuilder, rows: &[Vec<&str>], left: f32, top: f32, bottom: f32) -> (usize, f32) {
+        let mut cells = Vec::new();
+        let mut y = top;
+        let mut drawn = 0;
+        for row in rows {
+            let height = self.row_height(row);
+            if y - height < bottom {
+                break;
+            }
+            let mut x = left;
+            for ((text, &width), &align) in row.iter().zip(&self.columns).zip(&self.aligns) {
+                let text_rect = Rect {
+                    left: x + self.padding,
+                    right: x + width - self.padding,
+                    top: y - self.padding,
+                    bottom: y - height + self.padding,
+                };
+                self.paragraph.clone().align(align).draw(content, text, text_rect);
+                cells.push((x, y - height, width, height));
+                x += width;
+            }
+            y -= height;
+            drawn += 1;
+        }
+        if self.border > 0. && !cells.is_empty() {
+            content.save().line_width(self.border);
+            for (x, y, width, height) in cells {
+                content.rect(x, y, width, height);
+            }
+            content.stroke().restore();
+        }
+        (drawn, y)
+    }
+}
+
+/// The byte ranges of the words of `text`, which are separated by spaces.
+fn words(text: &str) -> impl Iterator<Item=(usize, usize)> + '_ {
+    text.split(' ').scan(0, |pos, word| {
+        let start = *pos;
+        *pos += word.len() + 1;
+        Some((start, start + word.len()))
+    }).filter(|&(start, end)| end > start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{Decoder, Encoding};
+
+    #[test]
+    fn paragraph() {
+        // every glyph is 5 units wide at size 10
+        let widths = FontWidths::new(&Decoder::new(&Encoding::WinAnsiEncoding)).default_width(500.);
+        let paragraph = Paragraph::new("F1", &widths, 10.).leading(12.);
+        let text = "aaa bbb ccc\nlongword  d";
+        let lines: Vec<&str> = paragraph.lines(text, 40.).iter().map(|l| &text[l.start .. l.end]).collect();
+        assert_eq!(lines, ["aaa bbb", "ccc", "longword", "d"]);
+
+        let mut content = ContentBuilder::new();
+        let rect = Rect { left: 10., bottom: 70., right: 50., top: 100. };
+        let rest = paragraph.clone().align(Align::Right).draw(&mut content, text, rect);
+        assert_eq!(rest, "longword  d");
+        assert_eq!(content.build().to_data().unwrap(), &b"BT\n/F1 10 Tf\n15 90 Td\n(aaa bbb) Tj\n20 -12 Td\n(ccc) Tj\nET\n"[..]);
+
+        let mut content = ContentBuilder::new();
+        let rest = paragraph.align(Align::Justify).draw(&mut content, "a b cc dd", rect);
+        assert_eq!(rest, "");
+        assert_eq!(content.build().to_data().unwrap(), &b"BT\n/F1 10 Tf\n5 Tw\n10 90 Td\n(a b cc) Tj\n0 Tw\n0 -12 Td\n(dd) Tj\nET\n"[..]);
+    }
+
+    #[test]
+    fn table() {
+        let widths = FontWidths::new(&Decoder::new(&Encoding::WinAnsiEncoding)).default_width(500.);
+        let table = Table::new("F1", &widths, 10., vec![40., 30.]).leading(12.).padding(2.).border(1.);
+        let rows = vec![vec!["aaa bbb ccc", "d"], vec!["e", "f"]];
+        assert_eq!((table.row_height(&rows[0]), table.row_height(&rows[1])), (26., 14.));
+
+        let mut content = ContentBuilder::new();
+        assert_eq!(table.draw(&mut content, &rows, 10., 100., 61.), (1, 74.));
+        assert_eq!(content.build().to_data().unwrap(), &b"BT\n/F1 10 Tf\n12 88 Td\n(aaa bbb) Tj\n0 -12 Td\n(ccc) Tj\nET\n\
+            BT\n/F1 10 Tf\n52 88 Td\n(d) Tj\nET\n\
+            q\n1 w\n10 74 40 26 re\n50 74 30 26 re\nS\nQ\n"[..]);
+
+        // lines closer than the font size
+        let tight = Table::new("F1", &widths, 10., vec![40.]).leading(8.).padding(2.).border(0.);
+        assert_eq!(tight.row_height(&["aaa bbb ccc"]), 22.);
+        let mut content = ContentBuilder::new();
+        assert_eq!(tight.draw(&mut content, &[vec!["aaa bbb ccc"]], 0., 100., 0.), (1, 78.));
+        assert_eq!(content.build().to_data().unwrap(), &b"BT\n/F1 10 Tf\n2 88 Td\n(aaa bbb) Tj\n0 -8 Td\n(ccc) Tj\nET\n"[..]);
+    }
+}
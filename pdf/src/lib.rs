@@ -21,10 +21,49 @@ pub mod parser;
 pub mod font;
 pub mod any;
 pub mod encoding;
+pub mod outline;
+pub mod metadata;
+pub mod image;
+pub mod signature;
+pub mod text;
+pub mod function;
+pub mod report;
+pub mod merge;
+pub mod fetch;
+pub mod linearized;
+pub mod document;
+pub mod layout;
+#[cfg(feature = "optimize")]
+pub mod optimize;
+#[cfg(feature = "write")]
+pub mod convert;
+#[cfg(feature = "write")]
+pub mod impose;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "barcode")]
+pub mod barcode;
 
 // mod content;
 mod enc;
+#[cfg(feature = "images")]
+mod jbig2;
+mod bbox;
 pub mod crypt;
 
 // pub use content::*;
 pub use crate::error::PdfError;
+
+/// The types most programs need: `use pdf::prelude::*;`
+pub mod prelude {
+    pub use crate::error::{PdfError, Result};
+    pub use crate::file::File;
+    #[cfg(feature = "write")]
+    pub use crate::file::WriteOptions;
+    pub use crate::backend::Backend;
+    pub use crate::object::{Object, Resolve, Ref, PlainRef, ObjNr, GenNr, Stream};
+    pub use crate::object::{Catalog, Page, PageRc, PagesNode, Resources, XObject, ImageXObject, FormXObject, Rect};
+    pub use crate::primitive::{Primitive, Dictionary, PdfString, PdfStream};
+    pub use crate::content::{Content, ContentBuilder, Operation, Op};
+    pub use crate::font::Font;
+}
@@ -0,0 +1,293 @@
+//! Linearized documents ("fast web view"), whose first page can be shown before the rest
+//! of the file has been fetched.
+//!
+//! A linearized file starts with the linearization dictionary, followed by a cross-reference
+//! section for the objects of the first page, which come next. The page offset hint table
+//! tells where the other pages are.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::Primitive;
+use crate::backend::Backend;
+use crate::file::{File, Storage};
+use crate::fetch::{AsyncBackend, AsyncFile, RangeCache, fetch, retry};
+use crate::parser::{Lexer, ParseOptions, parse_indirect_object, parse_indirect_stream, read_xref_and_trailer_at};
+use crate::xref::XRefTable;
+
+/// The linearization parameter dictionary, the first object of a linearized file.
+#[derive(Object, Debug, Clone)]
+pub struct Linearization {
+    #[pdf(key="Linearized")]
+    pub version: f32,
+
+    /// Length of the file
+    #[pdf(key="L")]
+    pub length: usize,
+
+    /// Offset and length of the primary hint stream, possibly followed by those of the overflow hint stream
+    #[pdf(key="H")]
+    pub hint_stream: Vec<usize>,
+
+    /// Object number of the first page's page object
+    #[pdf(key="O")]
+    pub first_page_object: u32,
+
+    /// Offset of the end of the first page
+    #[pdf(key="E")]
+    pub first_page_end: usize,
+
+    #[pdf(key="N")]
+    pub num_pages: u32,
+
+    /// Offset of the first entry of the main cross-reference table
+    #[pdf(key="T")]
+    pub main_xref_offset: usize,
+
+    /// The page that comes first in the file, if not page 0
+    #[pdf(key="P", default="0")]
+    pub first_page: u32,
+}
+
+/// A linearized document, of which only the first page has been fetched.
+pub struct ProgressiveFile<A> {
+    backend: A,
+    cache: RangeCache,
+    /// the objects of the first page section
+    storage: Storage<RangeCache>,
+    linearization: Linearization,
+    /// where each page starts, and where the last one ends. Empty without usable hints.
+    page_offsets: Vec<usize>,
+}
+
+impl File<RangeCache> {
+    /// Opens the linearized document of `backend`, fetching only the first page section
+    /// and the hint stream. Use `open_async` for documents that are not linearized.
+    pub async fn open_progressive<A: AsyncBackend>(backend: A) -> Result<ProgressiveFile<A>> {
+        let len = backend.len();
+        let cache = RangeCache::new(len);
+        // the linearization dictionary is within the first 1024 bytes
+        fetch(&backend, &cache, 0 .. len.min(1024)).await?;
+        let mut lexer = Lexer::new(cache.read(0 .. len.min(1024))?);
+        let linearization = match parse_indirect_object(&mut lexer, &NoResolve)
+            .and_then(|(_, p)| Linearization::from_primitive(p, &NoResolve))
+        {
+            Ok(linearization) => linearization,
+            Err(_) => bail!("not a linearized file")
+        };
+        if linearization.length != len {
+            bail!("the file was changed after it was linearized");
+        }
+        let end = linearization.first_page_end.min(len);
+        fetch(&backend, &cache, 0 .. end).await?;
+
+        // the first page cross-reference section follows the linearization dictionary
+        let mut lexer = Lexer::new(cache.read(lexer.get_pos() .. end)?);
+        let (sections, trailer) = read_xref_and_trailer_at(&mut lexer, &NoResolve)?;
+        if trailer.contains_key("Encrypt") {
+            bail!("encrypted documents can't be read progressively");
+        }
+        let size = trailer.get("Size")
+            .ok_or_else(|| PdfError::MissingEntry {field: "Size".into(), typ: "XRefTable"})?
+            .clone().as_integer()?;
+        let mut refs = XRefTable::new(size as ObjNr);
+        for section in sections {
+            refs.add_entries_from(section);
+        }
+        let storage = Storage::new(cache.clone(), refs, ParseOptions::default());
+
+        let page_offsets = match page_offsets(&backend, &cache, &storage, &linearization).await {
+            Ok(offsets) => offsets,
+            Err(e) => {
+                storage.warn(format!("hint stream: {}", e));
+                Vec::new()
+            }
+        };
+        Ok(ProgressiveFile { backend, cache, storage, linearization, page_offsets })
+    }
+}
+
+impl<A: AsyncBackend> ProgressiveFile<A> {
+    pub fn linearization(&self) -> &Linearization {
+        &self.linearization
+    }
+    /// The page that comes first in the file, which is page `linearization().first_page`.
+    /// Attributes it inherits from the page tree are not set, as the page tree is not part
+    /// of the first page section.
+    pub async fn first_page(&self) -> Result<Arc<Page>> {
+        let page = Ref::<Page>::new(PlainRef { id: self.linearization.first_page_object as ObjNr, gen: 0 });
+        retry(&self.backend, &self.cache, || {
+            let result = self.storage.get(page);
+            if self.cache.missed() {
+                self.storage.clear_cache();
+            }
+            result
+        }).await
+    }
+    /// The bytes of page `n` (counting from 0) according to the hint table:
+    /// its objects except those shared with other pages.
+    pub fn page_range(&self, n: u32) -> Option<Range<usize>> {
+        let n = n as usize;
+        Some(*self.page_offsets.get(n)? .. *self.page_offsets.get(n + 1)?)
+    }
+    /// Fetches the bytes of page `n`, so that reading it later needs no more requests for them.
+    pub async fn fetch_page(&self, n: u32) -> Result<()> {
+        match self.page_range(n) {
+            Some(range) => fetch(&self.backend, &self.cache, range).await,
+            None => Err(PdfError::PageOutOfBounds { page_nr: n, max: self.linearization.num_pages })
+        }
+    }
+    /// Reads the whole document, keeping the data fetched so far.
+    pub async fn complete(self) -> Result<AsyncFile<A>> {
+        AsyncFile::open(self.backend, self.cache).await
+    }
+}
+
+/// Where the pages start and end, from the page offset hint table.
+async fn page_offsets<A: AsyncBackend>(backend: &A, cache: &RangeCache, storage: &Storage<RangeCache>, linearization: &Linearization) -> Result<Vec<usize>> {
+    let (hint_pos, hint_len) = match linearization.hint_stream[..] {
+        [pos, len, ..] => (pos, len),
+        _ => bail!("/H needs an offset and a length")
+    };
+    let overflow = || PdfError::from("hint table offsets overflow".to_owned());
+    let hint_end = hint_pos.checked_add(hint_len).ok_or_else(overflow)?.min(cache.len());
+    if hint_pos >= hint_end {
+        bail!("hint stream at {} is outside of the file", hint_pos);
+    }
+    fetch(backend, cache, hint_pos .. hint_end).await?;
+    let mut lexer = Lexer::new(cache.read(hint_pos .. hint_end)?);
+    let (_, stream) = parse_indirect_stream(&mut lexer, storage)?;
+    let stream = Stream::<()>::from_primitive(Primitive::Stream(stream), storage)?;
+
+    let mut bits = Bits { data: stream.data()?, pos: 0 };
+    let num_pages = linearization.num_pages as usize;
+    if num_pages > cache.len() {
+        bail!("{} pages in a file of {} bytes", num_pages, cache.len());
+    }
+    let _least_objects = bits.read(32)?;
+    let first_page = bits.read(32)? as usize;
+    let object_bits = bits.read(16)? as u32;
+    let least_length = bits.read(32)? as usize;
+    let length_bits = bits.read(16)? as u32;
+    // the rest of the header is about content streams and shared objects
+    bits.pos = num_pages.checked_mul(object_bits as usize)
+        .and_then(|n| n.checked_add(36 * 8))
+        .ok_or_else(overflow)?;
+    bits.align();
+
+    // offsets in the hint tables are as if the hint stream was not there
+    let adjust = |offset: usize| -> Result<usize> {
+        if offset > linearization.length {
+            bail!("pages end at {}, after the end of the file", offset);
+        }
+        if offset >= hint_pos {
+            offset.checked_add(hint_len).ok_or_else(overflow)
+        } else {
+            Ok(offset)
+        }
+    };
+    // no more lengths than the hint stream holds
+    let capacity = match length_bits {
+        0 => num_pages,
+        n => num_pages.min(bits.data.len() * 8 / n as usize)
+    };
+    let mut offsets = Vec::with_capacity(capacity + 1);
+    let mut offset = first_page;
+    offsets.push(adjust(offset)?);
+    for _ in 0 .. num_pages {
+        let length = least_length.checked_add(bits.read(length_bits)? as usize).ok_or_else(overflow)?;
+        offset = offset.checked_add(length).ok_or_else(overflow)?;
+        offsets.push(adjust(offset)?);
+    }
+    Ok(offsets)
+}
+
+/// Reads numbers of any number of bits, most significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    /// in bits
+    pos: usize,
+}
+impl<'a> Bits<'a> {
+    fn read(&mut self, n: u32) -> Result<u64> {
+        if n > 32 {
+            bail!("{} bit numbers in a hint table", n);
+        }
+        let mut value = 0;
+        for _ in 0 .. n {
+            let byte = match self.data.get(self.pos / 8) {
+                Some(&b) => b,
+                None => bail!("hint table ends early")
+            };
+            value = value << 1 | (byte >> (7 - self.pos % 8) & 1) as u64;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+    fn align(&mut self) {
+        self.pos = (self.pos + 7) / 8 * 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use crate::fetch::BoxFuture;
+
+    struct Memory {
+        data: Vec<u8>,
+        fetched: Cell<usize>,
+    }
+    impl AsyncBackend for Memory {
+        fn len(&self) -> usize {
+            self.data.len()
+        }
+        fn fetch(&self, range: Range<usize>) -> BoxFuture<'_, Result<Vec<u8>>> {
+            self.fetched.set(self.fetched.get() + range.end - range.start);
+            let data = self.data[range].to_vec();
+            Box::pin(async move { Ok(data) })
+        }
+    }
+
+    /// Polls `future`, which never has to wait.
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        fn raw() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker { raw() }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw()) };
+        let mut future = Box::pin(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(t) => t,
+            Poll::Pending => panic!("pending")
+        }
+    }
+
+    #[test]
+    fn first_page() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../files/pdf-sample.pdf")).unwrap();
+        let original = File::from_data(data.clone()).unwrap();
+        let len = data.len();
+        let file = ready(File::open_progressive(Memory { data, fetched: Cell::new(0) })).unwrap();
+        assert_eq!(file.linearization().num_pages, original.get_num_pages().unwrap());
+
+        let page = ready(file.first_page()).unwrap();
+        assert_eq!(page.contents.as_ref().unwrap().operations.len(),
+            original.get_page(0).unwrap().contents.as_ref().unwrap().operations.len());
+        let range = file.page_range(0).unwrap();
+        assert!(range.start < range.end && range.end <= len);
+
+        let file = ready(file.complete()).unwrap();
+        assert_eq!(ready(file.get_num_pages()).unwrap(), original.get_num_pages().unwrap());
+
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../files/xelatex.pdf")).unwrap();
+        assert!(ready(File::open_progressive(Memory { data, fetched: Cell::new(0) })).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! Copying pages from one document into another.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfStream};
+use crate::backend::Backend;
+use crate::file::File;
+
+/// Page attributes that pages inherit from the page tree (7.7.3.4)
+const INHERITED: &[&str] = &["Resources", "MediaBox", "CropBox", "Rotate"];
+
+impl<B: Backend> File<B> {
+    /// Appends the pages `range` (counting from 0) of `other` to this document, together with
+    /// everything they refer to. The copies get new object numbers; objects that several of the
+    /// pages share are copied once.
+    ///
+    /// References to pages of `other` that are not imported, like the targets of links, become `null`,
+    /// as do references to its page tree. Returns the new pages.
+    pub fn import_pages<C: Backend>(&mut self, other: &File<C>, range: Range<usize>) -> Result<Vec<PlainRef>> {
+        let page_refs = other.page_refs()?;
+        let imported = match page_refs.get(range.clone()) {
+            Some(pages) => pages.to_vec(),
+            None => bail!("pages {:?} out of range, the document has {}", range, page_refs.len())
+        };
+
+        // the page tree nodes and the pages that stay behind
+        let mut excluded = page_tree(other)?;
+        for page in &imported {
+            excluded.remove(page);
+        }
+
+        let tree = self.page_tree_ref()?;
+        let mut copier = Copier::new(other, excluded);
+        let new_pages: Vec<PlainRef> = imported.iter().map(|&page| copier.map_ref(self, page)).collect();
+        // the queue holds the pages now, which need their inherited attributes
+        copier.queue.clear();
+        for (&old, &new) in imported.iter().zip(&new_pages) {
+            let mut dict = page_dict(other, old)?;
+            for key in &["Parent", "B", "StructParents"] {
+                dict.remove(key);
+            }
+            let mut dict = copier.remap_dict(self, dict);
+            dict.insert("Parent".into(), Primitive::Reference(tree));
+            self.update(new.id, Primitive::Dictionary(dict));
+        }
+        copier.finish(self)?;
+
+        self.append_pages(&new_pages)?;
+        Ok(new_pages)
+    }
+
+    /// A new document with the pages `range` (counting from 0) and everything they need,
+    /// for example to split a document. The document information is copied, too.
+    pub fn extract_pages(&self, range: Range<usize>) -> Result<File<Vec<u8>>> {
+        let mut file = File::new()?;
+        file.import_pages(self, range)?;
+        if let Some(info) = self.info()? {
+            file.set_info(info)?;
+        }
+        Ok(file)
+    }
+}
+
+/// The pages and page tree nodes of `file`.
+pub(crate) fn page_tree<C: Backend>(file: &File<C>) -> Result<HashSet<PlainRef>> {
+    let page_refs = file.page_refs()?;
+    let mut refs: HashSet<PlainRef> = page_refs.iter().cloned().collect();
+    for &page in &page_refs {
+        let mut node = page;
+        while let Some(&Primitive::Reference(parent)) = file.resolve(node)?.to_dictionary(file)?.get("Parent") {
+            if !refs.insert(parent) {
+                break;
+            }
+            node = parent;
+        }
+    }
+    Ok(refs)
+}
+
+/// The dictionary of the page `page` with the attributes it inherits.
+fn page_dict<C: Backend>(file: &File<C>, page: PlainRef) -> Result<Dictionary> {
+    let mut dict = file.resolve(page)?.to_dictionary(file)?;
+    let mut parent = dict.get("Parent").cloned();
+    // guards against cycles in /Parent
+    for _ in 0 .. 64 {
+        let node = match parent {
+            Some(Primitive::Reference(r)) => file.resolve(r)?.to_dictionary(file)?,
+            _ => break
+        };
+        for &key in INHERITED {
+            if dict.get(key).is_none() {
+                if let Some(value) = node.get(key) {
+                    dict.insert(key.into(), value.clone());
+                }
+            }
+        }
+        parent = node.get("Parent").cloned();
+    }
+    Ok(dict)
+}
+
+/// Copies objects of `other`, giving them new numbers.
+pub(crate) struct Copier<'a, C: Backend> {
+    other: &'a File<C>,
+    /// Objects that are not copied, and replaced by `null`
+    excluded: HashSet<PlainRef>,
+    /// old number → new number
+    map: HashMap<PlainRef, PlainRef>,
+    /// Objects that have a number, but are not copied yet
+    queue: Vec<(PlainRef, PlainRef)>,
+}
+impl<'a, C: Backend> Copier<'a, C> {
+    pub(crate) fn new(other: &'a File<C>, excluded: HashSet<PlainRef>) -> Copier<'a, C> {
+        Copier { other, excluded, map: HashMap::new(), queue: Vec::new() }
+    }
+    /// Copies the objects that references were remapped to.
+    pub(crate) fn finish<B: Backend>(&mut self, file: &mut File<B>) -> Result<()> {
+        while let Some((old, new)) = self.queue.pop() {
+            let p = self.other.resolve(old)?;
+            let p = self.remap(file, p);
+            file.update(new.id, p);
+        }
+        Ok(())
+    }
+    /// The number of the copy of `r`.
+    fn map_ref<B: Backend>(&mut self, file: &mut File<B>, r: PlainRef) -> PlainRef {
+        if let Some(&new) = self.map.get(&r) {
+            return new;
+        }
+        let new = file.add(Primitive::Null).get_inner();
+        self.map.insert(r, new);
+        self.queue.push((r, new));
+        new
+    }
+    pub(crate) fn remap<B: Backend>(&mut self, file: &mut File<B>, p: Primitive) -> Primitive {
+        match p {
+            Primitive::Reference(r) if self.excluded.contains(&r) => Primitive::Null,
+            Primitive::Reference(r) => Primitive::Reference(self.map_ref(file, r)),
+            Primitive::Array(parts) => Primitive::Array(parts.into_iter().map(|p| self.remap(file, p)).collect()),
+            Primitive::Dictionary(dict) => Primitive::Dictionary(self.remap_dict(file, dict)),
+            Primitive::Stream(PdfStream { info, data }) => Primitive::Stream(PdfStream { info: self.remap_dict(file, info), data }),
+            p => p
+        }
+    }
+    fn remap_dict<B: Backend>(&mut self, file: &mut File<B>, dict: Dictionary) -> Dictionary {
+        let mut new = Dictionary::new();
+        for (key, value) in dict.into_iter() {
+            new.insert(key, self.remap(file, value));
+        }
+        new
+    }
+}
@@ -0,0 +1,359 @@
+//! Document metadata: the information dictionary and the XMP packet.
+//!
+//! XMP is handled textually. Only the properties that have an equivalent in the
+//! information dictionary are read or replaced, everything else is left as it is.
+
+use std::ops::Range;
+use std::str;
+use chrono::{DateTime, FixedOffset};
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfString, format_date};
+
+/// Document information dictionary (14.3.3)
+#[derive(Object, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocInfo {
+    #[pdf(key="Title")]
+    pub title: Option<PdfString>,
+
+    #[pdf(key="Author")]
+    pub author: Option<PdfString>,
+
+    #[pdf(key="Subject")]
+    pub subject: Option<PdfString>,
+
+    #[pdf(key="Keywords")]
+    pub keywords: Option<PdfString>,
+
+    /// The application that created the original document
+    #[pdf(key="Creator")]
+    pub creator: Option<PdfString>,
+
+    /// The application that converted it to PDF
+    #[pdf(key="Producer")]
+    pub producer: Option<PdfString>,
+
+    #[pdf(key="CreationDate")]
+    #[cfg_attr(feature = "serde", serde(with = "serde_date"))]
+    pub creation_date: Option<DateTime<FixedOffset>>,
+
+    #[pdf(key="ModDate")]
+    #[cfg_attr(feature = "serde", serde(with = "serde_date"))]
+    pub mod_date: Option<DateTime<FixedOffset>>,
+
+    #[pdf(key="Trapped")]
+    pub trapped: Option<String>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+impl From<DocInfo> for Primitive {
+    fn from(info: DocInfo) -> Primitive {
+        let mut dict = info.other;
+        let strings = vec![
+            ("Title", info.title),
+            ("Author", info.author),
+            ("Subject", info.subject),
+            ("Keywords", info.keywords),
+            ("Creator", info.creator),
+            ("Producer", info.producer),
+        ];
+        for (key, val) in strings {
+            if let Some(s) = val {
+                dict.insert(key.into(), s.into());
+            }
+        }
+        if let Some(ref date) = info.creation_date {
+            dict.insert("CreationDate".into(), format_date(date).into());
+        }
+        if let Some(ref date) = info.mod_date {
+            dict.insert("ModDate".into(), format_date(date).into());
+        }
+        if let Some(trapped) = info.trapped {
+            dict.insert("Trapped".into(), trapped.into());
+        }
+        Primitive::Dictionary(dict)
+    }
+}
+
+/// The document metadata as text, merged from the information dictionary and the XMP packet.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_date"))]
+    pub creation_date: Option<DateTime<FixedOffset>>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_date"))]
+    pub mod_date: Option<DateTime<FixedOffset>>,
+    pub trapped: Option<String>,
+}
+impl<'a> From<&'a DocInfo> for Metadata {
+    fn from(info: &'a DocInfo) -> Metadata {
+        let text = |s: &Option<PdfString>| s.as_ref().map(|s| s.to_text());
+        Metadata {
+            title: text(&info.title),
+            author: text(&info.author),
+            subject: text(&info.subject),
+            keywords: text(&info.keywords),
+            creator: text(&info.creator),
+            producer: text(&info.producer),
+            creation_date: info.creation_date,
+            mod_date: info.mod_date,
+            trapped: info.trapped.clone(),
+        }
+    }
+}
+
+/// Dates as RFC 3339 strings
+#[cfg(feature = "serde")]
+mod serde_date {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(date: &Option<DateTime<FixedOffset>>, s: S) -> Result<S::Ok, S::Error> {
+        date.map(|d| d.to_rfc3339()).serialize(s)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(s) => DateTime::parse_from_rfc3339(&s).map(Some).map_err(D::Error::custom),
+            None => Ok(None)
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Kind {
+    Simple,
+    /// language alternatives, we only use x-default
+    Alt,
+    /// ordered list
+    Seq,
+}
+
+static NAMESPACES: &[(&str, &str)] = &[
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("pdf", "http://ns.adobe.com/pdf/1.3/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+];
+
+const EMPTY_PACKET: &str = "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+</rdf:RDF>\n\
+</x:xmpmeta>\n\
+<?xpacket end=\"w\"?>";
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Finds `<name …>…</name>` and returns the range of the whole element and of its content.
+fn find_element(xml: &str, name: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let open = format!("<{}", name);
+    let mut search = 0;
+    while let Some(off) = xml[search..].find(&open) {
+        let start = search + off;
+        let after = start + open.len();
+        match xml[after..].chars().next() {
+            Some('>') | Some('/') | Some(' ') | Some('\t') | Some('\r') | Some('\n') => {}
+            _ => {
+                // only a prefix of a longer name
+                search = after;
+                continue;
+            }
+        }
+        let tag_end = after + xml[after..].find('>')? + 1;
+        if xml[..tag_end].ends_with("/>") {
+            return Some((start .. tag_end, tag_end .. tag_end));
+        }
+        let close = format!("</{}>", name);
+        let content_end = tag_end + xml[tag_end..].find(&close)?;
+        return Some((start .. content_end + close.len(), tag_end .. content_end));
+    }
+    None
+}
+
+/// Finds the value of the attribute `name="…"`.
+fn find_attribute(xml: &str, name: &str) -> Option<Range<usize>> {
+    let pattern = format!("{}=", name);
+    let mut search = 0;
+    while let Some(off) = xml[search..].find(&pattern) {
+        let start = search + off;
+        search = start + pattern.len();
+        if !xml[..start].ends_with(|c: char| c.is_whitespace()) {
+            continue;
+        }
+        let quote = xml[search..].chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value_start = search + 1;
+        let value_end = value_start + xml[value_start..].find(quote)?;
+        return Some(value_start .. value_end);
+    }
+    None
+}
+
+/// Reads an XMP property. Entries of lists are joined by ", ".
+pub fn xmp_property(xml: &str, name: &str) -> Option<String> {
+    if let Some((_, content)) = find_element(xml, name) {
+        let mut content = &xml[content];
+        if !content.contains("<rdf:li") {
+            return Some(unescape(content.trim()));
+        }
+        let mut items = Vec::new();
+        while let Some((element, inner)) = find_element(content, "rdf:li") {
+            items.push(unescape(&content[inner]));
+            content = &content[element.end ..];
+        }
+        return Some(items.join(", "));
+    }
+    find_attribute(xml, name).map(|r| unescape(&xml[r]))
+}
+
+fn set_property(xml: &mut String, name: &str, kind: Kind, value: &str) {
+    let value = escape(value);
+    let element = match kind {
+        Kind::Simple => format!("<{0}>{1}</{0}>", name, value),
+        Kind::Alt => format!("<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>", name, value),
+        Kind::Seq => format!("<{0}><rdf:Seq><rdf:li>{1}</rdf:li></rdf:Seq></{0}>", name, value),
+    };
+    if let Some((range, _)) = find_element(xml, name) {
+        xml.replace_range(range, &element);
+        return;
+    }
+    if let Some(range) = find_attribute(xml, name) {
+        if let Kind::Simple = kind {
+            xml.replace_range(range, &value);
+            return;
+        }
+    }
+    if !xml.contains("</rdf:RDF>") {
+        *xml = EMPTY_PACKET.into();
+    }
+    let prefix = name.split(':').next().unwrap();
+    let ns = NAMESPACES.iter().find(|&&(p, _)| p == prefix).map(|&(_, ns)| ns).unwrap_or("");
+    let description = format!("<rdf:Description rdf:about=\"\" xmlns:{}=\"{}\">{}</rdf:Description>\n", prefix, ns, element);
+    let pos = xml.rfind("</rdf:RDF>").unwrap();
+    xml.insert_str(pos, &description);
+}
+
+fn parse_xmp_date(s: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(s).ok()
+}
+
+/// Updates the properties of the XMP packet `xml` that correspond to entries present in `info`.
+pub fn sync_xmp(xml: &[u8], info: &DocInfo) -> Result<Vec<u8>> {
+    let mut xml = String::from_utf8(xml.to_vec())?;
+    let strings = [
+        ("dc:title", Kind::Alt, &info.title),
+        ("dc:creator", Kind::Seq, &info.author),
+        ("dc:description", Kind::Alt, &info.subject),
+        ("pdf:Keywords", Kind::Simple, &info.keywords),
+        ("xmp:CreatorTool", Kind::Simple, &info.creator),
+        ("pdf:Producer", Kind::Simple, &info.producer),
+    ];
+    for &(name, kind, value) in strings.iter() {
+        if let Some(ref s) = *value {
+            set_property(&mut xml, name, kind, &s.to_text());
+        }
+    }
+    if let Some(ref date) = info.creation_date {
+        set_property(&mut xml, "xmp:CreateDate", Kind::Simple, &date.to_rfc3339());
+    }
+    if let Some(ref date) = info.mod_date {
+        set_property(&mut xml, "xmp:ModifyDate", Kind::Simple, &date.to_rfc3339());
+        set_property(&mut xml, "xmp:MetadataDate", Kind::Simple, &date.to_rfc3339());
+    }
+    Ok(xml.into_bytes())
+}
+
+/// Copies the properties of the XMP packet `xml` that have an equivalent in the information dictionary into `info`.
+pub fn sync_info(xml: &[u8], info: &mut DocInfo) -> Result<()> {
+    let xml = str::from_utf8(xml)?;
+    {
+        let strings = vec![
+            ("dc:title", &mut info.title),
+            ("dc:creator", &mut info.author),
+            ("dc:description", &mut info.subject),
+            ("pdf:Keywords", &mut info.keywords),
+            ("xmp:CreatorTool", &mut info.creator),
+            ("pdf:Producer", &mut info.producer),
+        ];
+        for (name, field) in strings {
+            if let Some(s) = xmp_property(xml, name) {
+                *field = Some(PdfString::from_text(&s));
+            }
+        }
+    }
+    if let Some(date) = xmp_property(xml, "xmp:CreateDate").and_then(|s| parse_xmp_date(&s)) {
+        info.creation_date = Some(date);
+    }
+    if let Some(date) = xmp_property(xml, "xmp:ModifyDate").and_then(|s| parse_xmp_date(&s)) {
+        info.mod_date = Some(date);
+    }
+    Ok(())
+}
+
+/// Merges the information dictionary and the XMP packet.
+/// XMP takes precedence, unless the information dictionary was modified after the XMP was last written
+/// (by a tool that only knows the dictionary).
+pub fn merge(info: Option<DocInfo>, xmp: Option<&[u8]>) -> Result<Metadata> {
+    let info = info.unwrap_or_default();
+    let xml = match xmp {
+        Some(xml) => xml,
+        None => return Ok(Metadata::from(&info))
+    };
+    let text = str::from_utf8(xml)?;
+    let xmp_date = xmp_property(text, "xmp:MetadataDate")
+        .or_else(|| xmp_property(text, "xmp:ModifyDate"))
+        .and_then(|s| parse_xmp_date(&s));
+    let stale = match (info.mod_date, xmp_date) {
+        (Some(info_date), Some(xmp_date)) => info_date > xmp_date,
+        _ => false
+    };
+    if stale {
+        return Ok(Metadata::from(&info));
+    }
+    let mut merged = info;
+    sync_info(xml, &mut merged)?;
+    Ok(Metadata::from(&merged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xmp_roundtrip() {
+        let xml = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:pdf="http://ns.adobe.com/pdf/1.3/" pdf:Producer="foo">
+<dc:title><rdf:Alt><rdf:li xml:lang="x-default">Old &amp; dusty</rdf:li></rdf:Alt></dc:title>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        assert_eq!(xmp_property(xml, "dc:title").unwrap(), "Old & dusty");
+        assert_eq!(xmp_property(xml, "pdf:Producer").unwrap(), "foo");
+
+        let mut info = DocInfo::default();
+        info.title = Some(PdfString::from_text("Neu"));
+        info.author = Some(PdfString::from_text("Ada"));
+        info.producer = Some(PdfString::from_text("pdf-rs"));
+        let updated = sync_xmp(xml.as_bytes(), &info).unwrap();
+
+        let mut read = DocInfo::default();
+        sync_info(&updated, &mut read).unwrap();
+        assert_eq!(read.title.unwrap().to_text(), "Neu");
+        assert_eq!(read.author.unwrap().to_text(), "Ada");
+        assert_eq!(read.producer.unwrap().to_text(), "pdf-rs");
+    }
+}
@@ -0,0 +1,344 @@
+//! Color spaces (8.6)
+
+use crate::object::*;
+use crate::primitive::*;
+use crate::error::*;
+
+use std::io;
+use std::sync::Arc;
+
+/// A color space, as found in `/ColorSpace` resources, image dictionaries and shadings.
+#[derive(Debug, Clone)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRGB,
+    DeviceCMYK,
+    CalGray (Dictionary),
+    CalRGB (Dictionary),
+    Lab (Dictionary),
+    /// Described by an ICC profile; `stream` is the primitive it was read from (usually a reference).
+    /// `alternate` is the profile's `/Alternate`, if it could be read.
+    ICCBased { profile: Arc<IccStream>, alternate: Option<Box<ColorSpace>>, stream: Primitive },
+    /// A palette of `hival + 1` colors in `base`
+    Indexed { base: Box<ColorSpace>, hival: u8, lookup: Vec<u8> },
+    /// A single colorant; `tint_transform` maps the tint to `alternate`
+    Separation { name: String, alternate: Box<ColorSpace>, tint_transform: Primitive },
+    DeviceN { names: Vec<String>, alternate: Box<ColorSpace>, tint_transform: Primitive, attributes: Option<Dictionary> },
+    /// Pattern, with the color space of the color given to uncolored patterns
+    Pattern (Option<Box<ColorSpace>>),
+    /// A color space that couldn't be read, as it was found
+    Other (Primitive),
+}
+
+/// How deep base and alternate color spaces may be nested
+const MAX_DEPTH: usize = 8;
+
+static DEVICE_GRAY: ColorSpace = ColorSpace::DeviceGray;
+static DEVICE_RGB: ColorSpace = ColorSpace::DeviceRGB;
+static DEVICE_CMYK: ColorSpace = ColorSpace::DeviceCMYK;
+static PATTERN: ColorSpace = ColorSpace::Pattern(None);
+
+impl ColorSpace {
+    /// The color spaces that can be given by name alone, including the abbreviations of inline images.
+    pub fn from_name(name: &str) -> Option<&'static ColorSpace> {
+        match name {
+            "DeviceGray" | "G" => Some(&DEVICE_GRAY),
+            "DeviceRGB" | "RGB" => Some(&DEVICE_RGB),
+            "DeviceCMYK" | "CMYK" => Some(&DEVICE_CMYK),
+            "Pattern" => Some(&PATTERN),
+            _ => None
+        }
+    }
+    /// Number of color components (of the underlying color space for uncolored patterns).
+    pub fn components(&self) -> usize {
+        match *self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) | ColorSpace::Indexed { .. } | ColorSpace::Separation { .. } => 1,
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) | ColorSpace::Lab(_) => 3,
+            ColorSpace::DeviceCMYK => 4,
+            ColorSpace::ICCBased { ref profile, .. } => profile.components(),
+            ColorSpace::DeviceN { ref names, .. } => names.len(),
+            ColorSpace::Pattern(ref base) => base.as_ref().map(|cs| cs.components()).unwrap_or(0),
+            ColorSpace::Other(_) => 0,
+        }
+    }
+    /// The color that `cs` and `CS` select (8.6.5, 8.6.6).
+    pub fn initial_color(&self) -> Vec<f32> {
+        match *self {
+            ColorSpace::DeviceCMYK => vec![0., 0., 0., 1.],
+            ColorSpace::Lab(_) => vec![0., 0., 0.],
+            ColorSpace::Separation { .. } | ColorSpace::DeviceN { .. } => vec![1.; self.components()],
+            _ => vec![0.; self.components()]
+        }
+    }
+    /// Converts `color` to RGB (each 0 to 1), without color management.
+    /// `None` for color spaces that need a tint transform or a pattern.
+    pub fn to_rgb(&self, color: &[f32]) -> Option<(f32, f32, f32)> {
+        let c = |i: usize| color.get(i).cloned().unwrap_or(0.).max(0.).min(1.);
+        match *self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => Some((c(0), c(0), c(0))),
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => Some((c(0), c(1), c(2))),
+            ColorSpace::DeviceCMYK => {
+                let k = c(3);
+                Some(((1. - c(0)) * (1. - k), (1. - c(1)) * (1. - k), (1. - c(2)) * (1. - k)))
+            }
+            ColorSpace::ICCBased { ref profile, ref alternate, .. } => match *alternate {
+                Some(ref alt) => alt.to_rgb(color),
+                None => match profile.components() {
+                    1 => DEVICE_GRAY.to_rgb(color),
+                    3 => DEVICE_RGB.to_rgb(color),
+                    4 => DEVICE_CMYK.to_rgb(color),
+                    _ => None
+                }
+            },
+            ColorSpace::Indexed { ref base, hival, ref lookup } => {
+                let n = base.components();
+                let index = (color.get(0).cloned().unwrap_or(0.).round().max(0.) as usize).min(hival as usize);
+                let entry = lookup.get(index * n .. (index + 1) * n)?;
+                let entry: Vec<f32> = entry.iter().map(|&b| b as f32 / 255.).collect();
+                base.to_rgb(&entry)
+            }
+            _ => None
+        }
+    }
+}
+
+fn alternate(p: Primitive, resolve: &impl Resolve, depth: usize) -> Result<Box<ColorSpace>> {
+    Ok(Box::new(ColorSpace::parse(p, resolve, depth + 1)?))
+}
+
+impl ColorSpace {
+    /// Reads a color space whose base or alternate color spaces are nested `depth` deep.
+    fn parse(p: Primitive, resolve: &impl Resolve, depth: usize) -> Result<ColorSpace> {
+        if depth > MAX_DEPTH {
+            bail!("color spaces nested more than {} deep", MAX_DEPTH);
+        }
+        let mut parts = match p.resolve(resolve)? {
+            Primitive::Name(name) => match ColorSpace::from_name(&name) {
+                Some(cs) => return Ok(cs.clone()),
+                None => bail!("unknown color space {}", name)
+            },
+            Primitive::Array(parts) => parts,
+            p => return unexpected_primitive!(Array, p.get_debug_name())
+        };
+        if parts.len() == 0 {
+            bail!("empty color space array");
+        }
+        let name = parts[0].as_name()?.to_owned();
+        let mut args = parts.drain(1..);
+        let mut next = |what: &'static str| args.next().ok_or(PdfError::MissingEntry { typ: "ColorSpace", field: what.into() });
+        let cs = match name.as_str() {
+            "CalGray" => ColorSpace::CalGray(next("CalGray")?.to_dictionary(resolve)?),
+            "CalRGB" => ColorSpace::CalRGB(next("CalRGB")?.to_dictionary(resolve)?),
+            "Lab" => ColorSpace::Lab(next("Lab")?.to_dictionary(resolve)?),
+            "ICCBased" => {
+                let stream = next("ICCBased")?;
+                let profile = Arc::<IccStream>::from_primitive(stream.clone(), resolve)?;
+                // only a fallback, so the profile is usable without it
+                let alternate = match profile.alternate {
+                    Some(ref p) => alternate(p.clone(), resolve, depth).ok(),
+                    None => None
+                };
+                ColorSpace::ICCBased { profile, alternate, stream }
+            }
+            "Indexed" | "I" => {
+                let base = alternate(next("Base")?, resolve, depth)?;
+                let hival = next("HiVal")?.resolve(resolve)?.as_integer()?;
+                if hival < 0 || hival > 255 {
+                    bail!("invalid hival {} in Indexed color space", hival);
+                }
+                let lookup = match next("Lookup")?.resolve(resolve)? {
+                    Primitive::String(s) => s.into_bytes(),
+                    p @ Primitive::Stream(_) => Stream::<()>::from_primitive(p, resolve)?.data()?.to_vec(),
+                    p => return unexpected_primitive!(String, p.get_debug_name())
+                };
+                ColorSpace::Indexed { base, hival: hival as u8, lookup }
+            }
+            "Separation" => {
+                let name = next("Name")?.to_name()?;
+                let alternate = alternate(next("AlternateSpace")?, resolve, depth)?;
+                ColorSpace::Separation { name, alternate, tint_transform: next("TintTransform")? }
+            }
+            "DeviceN" => {
+                let names = Vec::<String>::from_primitive(next("Names")?, resolve)?;
+                let alternate = alternate(next("AlternateSpace")?, resolve, depth)?;
+                let tint_transform = next("TintTransform")?;
+                let attributes = match args.next() {
+                    Some(p) => Some(p.to_dictionary(resolve)?),
+                    None => None
+                };
+                ColorSpace::DeviceN { names, alternate, tint_transform, attributes }
+            }
+            "Pattern" => ColorSpace::Pattern(match args.next() {
+                Some(p) => Some(alternate(p, resolve, depth)?),
+                None => None
+            }),
+            _ => match ColorSpace::from_name(&name) {
+                // device spaces are sometimes wrapped in an array
+                Some(cs) => cs.clone(),
+                None => bail!("unknown color space {}", name)
+            }
+        };
+        Ok(cs)
+    }
+}
+
+impl Object for ColorSpace {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        Primitive::from(self.clone()).serialize(out)
+    }
+    /// Color spaces that can't be read become `Other`, so that one of them doesn't make
+    /// the resources it is part of unreadable.
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match ColorSpace::parse(p.clone(), resolve, 0) {
+            Ok(cs) => Ok(cs),
+            Err(e) => {
+                resolve.warn(format!("unreadable color space: {:?}", e));
+                Ok(ColorSpace::Other(p))
+            }
+        }
+    }
+}
+
+impl From<ColorSpace> for Primitive {
+    fn from(cs: ColorSpace) -> Primitive {
+        let name = |s: &str| Primitive::Name(s.into());
+        let parts = match cs {
+            ColorSpace::DeviceGray => return name("DeviceGray"),
+            ColorSpace::DeviceRGB => return name("DeviceRGB"),
+            ColorSpace::DeviceCMYK => return name("DeviceCMYK"),
+            ColorSpace::Pattern(None) => return name("Pattern"),
+            ColorSpace::Other(p) => return p,
+            ColorSpace::Pattern(Some(base)) => vec![name("Pattern"), (*base).into()],
+            ColorSpace::CalGray(dict) => vec![name("CalGray"), Primitive::Dictionary(dict)],
+            ColorSpace::CalRGB(dict) => vec![name("CalRGB"), Primitive::Dictionary(dict)],
+            ColorSpace::Lab(dict) => vec![name("Lab"), Primitive::Dictionary(dict)],
+            ColorSpace::ICCBased { stream, .. } => vec![name("ICCBased"), stream],
+            ColorSpace::Indexed { base, hival, lookup } => vec![
+                name("Indexed"), (*base).into(), Primitive::Integer(hival as i32), Primitive::String(PdfString::new(lookup))
+            ],
+            ColorSpace::Separation { name: colorant, alternate, tint_transform } => vec![
+                name("Separation"), Primitive::Name(colorant), (*alternate).into(), tint_transform
+            ],
+            ColorSpace::DeviceN { names, alternate, tint_transform, attributes } => {
+                let mut parts = vec![
+                    name("DeviceN"), Primitive::Array(names.into_iter().map(Primitive::Name).collect()),
+                    (*alternate).into(), tint_transform
+                ];
+                if let Some(attributes) = attributes {
+                    parts.push(Primitive::Dictionary(attributes));
+                }
+                parts
+            }
+        };
+        Primitive::Array(parts)
+    }
+}
+
+/// Stream dictionary of an ICC profile (8.6.5.5)
+#[derive(Object, Debug, Clone)]
+pub struct IccInfo {
+    /// Number of color components: 1, 3 or 4
+    #[pdf(key="N")]
+    pub n: i32,
+
+    /// Used when the profile can't be interpreted, read into `ColorSpace::ICCBased`
+    #[pdf(key="Alternate")]
+    pub alternate: Option<Primitive>,
+
+    /// `[min0 max0 min1 max1 ...]`, defaults to 0 to 1 for every component
+    #[pdf(key="Range")]
+    pub range: Vec<f32>,
+
+    #[pdf(key="Metadata")]
+    pub metadata: Option<Primitive>,
+}
+
+/// An ICC profile; the stream data is the profile itself, ready to hand to a color management system.
+pub type IccStream = Stream<IccInfo>;
+
+impl Stream<IccInfo> {
+    pub fn components(&self) -> usize {
+        self.n.max(0) as usize
+    }
+    /// The range of each component.
+    pub fn ranges(&self) -> Vec<(f32, f32)> {
+        match self.range.len() {
+            0 => vec![(0., 1.); self.components()],
+            _ => self.range.chunks(2).filter(|c| c.len() == 2).map(|c| (c[0], c[1])).collect()
+        }
+    }
+    /// The raw (decoded) ICC profile.
+    pub fn profile(&self) -> Result<&[u8]> {
+        self.data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Objects 1 to 3 are color spaces that refer to themselves
+    struct Objects;
+    impl Resolve for Objects {
+        fn resolve(&self, r: PlainRef) -> Result<Primitive> {
+            let this = Primitive::Reference(r);
+            Ok(match r.id {
+                1 => Primitive::Array(vec!["Indexed".into(), this, Primitive::Integer(0), Primitive::String(PdfString::new(vec![0]))]),
+                2 => Primitive::Array(vec!["Separation".into(), "Spot".into(), this, Primitive::Null]),
+                3 => {
+                    let mut info = Dictionary::new();
+                    info.insert("N".into(), Primitive::Integer(3));
+                    info.insert("Alternate".into(), Primitive::Array(vec!["ICCBased".into(), this]));
+                    info.insert("Length".into(), Primitive::Integer(0));
+                    Primitive::Stream(PdfStream { info, data: Vec::new().into() })
+                }
+                _ => return Err(PdfError::Reference)
+            })
+        }
+        fn get<T: Object>(&self, r: Ref<T>) -> Result<Arc<T>> {
+            Ok(Arc::new(T::from_primitive(self.resolve(r.get_inner())?, self)?))
+        }
+    }
+
+    fn color_space(p: Primitive) -> ColorSpace {
+        ColorSpace::from_primitive(p, &Objects).unwrap()
+    }
+    fn serialized(cs: ColorSpace) -> String {
+        let mut out = Vec::new();
+        cs.serialize(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn unreadable() {
+        let lookup = Primitive::String(PdfString::new(vec![0, 0, 0, 255, 255, 255]));
+        match color_space(Primitive::Array(vec!["Indexed".into(), "DeviceRGB".into(), Primitive::Integer(1), lookup])) {
+            ColorSpace::Indexed { ref base, hival: 1, .. } => assert_eq!(base.components(), 3),
+            cs => panic!("{:?}", cs)
+        }
+
+        // unknown and broken color spaces are kept as they are
+        let unknown = color_space("CS0".into());
+        assert_eq!(unknown.components(), 0);
+        assert_eq!(serialized(unknown), "/CS0");
+        let broken = color_space(Primitive::Array(vec!["Indexed".into(), "DeviceRGB".into(), Primitive::Integer(300)]));
+        assert!(match broken { ColorSpace::Other(_) => true, _ => false });
+        assert_eq!(serialized(broken), "[/Indexed /DeviceRGB 300]");
+    }
+
+    #[test]
+    fn nesting() {
+        let reference = |id| Primitive::Reference(PlainRef { id, gen: 0 });
+        for id in 1 ..= 2 {
+            match color_space(reference(id)) {
+                ColorSpace::Other(p) => assert_eq!(p.to_reference().unwrap().id, id),
+                cs => panic!("{:?}", cs)
+            }
+        }
+        // the alternate of an ICC profile is optional
+        match color_space(Primitive::Array(vec!["ICCBased".into(), reference(3)])) {
+            ColorSpace::ICCBased { ref profile, .. } => assert_eq!(profile.components(), 3),
+            cs => panic!("{:?}", cs)
+        }
+    }
+}
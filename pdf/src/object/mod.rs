@@ -4,26 +4,44 @@
 
 mod types;
 mod stream;
+mod color;
+mod shading;
 
 pub use self::types::*;
 pub use self::stream::*;
+pub use self::color::*;
+pub use self::shading::*;
 
 use crate::primitive::*;
 use crate::error::*;
 use crate::enc::*;
+use crate::parser::ParseOptions;
 
 use std::io;
 use std::fmt;
 use std::marker::PhantomData;
 use std::collections::BTreeMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub type ObjNr = u64;
 pub type GenNr = u16;
 
 pub trait Resolve: {
     fn resolve(&self, r: PlainRef) -> Result<Primitive>;
-    fn get<T: Object>(&self, r: Ref<T>) -> Result<Rc<T>>;
+    fn get<T: Object>(&self, r: Ref<T>) -> Result<Arc<T>>;
+    /// How objects read through this are parsed
+    fn options(&self) -> ParseOptions {
+        ParseOptions::strict()
+    }
+    /// Reports a violation of the specification that was repaired while parsing.
+    fn warn(&self, msg: String) {
+        warn!("{}", msg);
+    }
+    /// The data of a stream that was parsed from `data`, which may refer to the backend
+    /// instead of copying `data` when that is where it is.
+    fn stream_data(&self, data: &[u8]) -> StreamData {
+        data.to_vec().into()
+    }
 }
 
 pub struct NoResolve;
@@ -31,13 +49,13 @@ impl Resolve for NoResolve {
     fn resolve(&self, _: PlainRef) -> Result<Primitive> {
         Err(PdfError::Reference)
     }
-    fn get<T: Object>(&self, r: Ref<T>) -> Result<Rc<T>> {
+    fn get<T: Object>(&self, r: Ref<T>) -> Result<Arc<T>> {
         Err(PdfError::Reference)
     }
 }
 
 /// A PDF Object
-pub trait Object: Sized + 'static {
+pub trait Object: Sized + Send + Sync + 'static {
     /// Write object as a byte stream
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()>;
     /// Convert primitive to Self
@@ -54,6 +72,7 @@ pub trait Object: Sized + 'static {
 
 // TODO move to primitive.rs
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PlainRef {
     pub id:     ObjNr,
     pub gen:    GenNr,
@@ -161,10 +180,10 @@ impl Object for usize {
         Ok(i32::from_primitive(p, r)? as usize)
     }
 }
+/// Written like `SmallNumber`.
 impl Object for f32 {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
-        write!(out, "{}", self)?;
-        Ok(())
+        SmallNumber(*self).serialize(out)
     }
     fn from_primitive(p: Primitive, _: &impl Resolve) -> Result<Self> {
         p.as_number()
@@ -183,7 +202,8 @@ impl Object for Dictionary {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
         write!(out, "<<")?;
         for (key, val) in self.iter() {
-            write!(out, "/{} ", key)?;
+            write_name(out, key)?;
+            write!(out, " ")?;
             val.serialize(out)?;
         }
         write!(out, ">>")?;
@@ -198,17 +218,10 @@ impl Object for Dictionary {
     }
 }
 
+/// `String`s are names
 impl Object for String {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
-        for b in self.as_str().chars() {
-            match b {
-                '\\' | '(' | ')' => write!(out, r"\")?,
-                c if c > '~' => panic!("only ASCII"),
-                _ => ()
-            }
-            write!(out, "{}", b)?;
-        }
-        Ok(())
+        write_name(out, self)
     }
     fn from_primitive(p: Primitive, _: &impl Resolve) -> Result<Self> {
         Ok(p.to_name()?)
@@ -239,6 +252,10 @@ impl<T: Object> Object for Vec<T> {
     }
 }
 
+/// `Primitive` is an `Object` itself, so derived structs can keep an entry exactly as it was
+/// found in the file. References are *not* followed; this keeps the identity of shared objects
+/// intact for writing. Use `Primitive` (or `Option<Primitive>`) for entries that are not modeled
+/// yet and call `Primitive::resolve` once the value is actually needed.
 impl Object for Primitive {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
         match *self {
@@ -280,14 +297,14 @@ impl<V: Object> Object for BTreeMap<String, V> {
     }
 }
 
-impl<T: Object + std::fmt::Debug> Object for Rc<T> {
+impl<T: Object + std::fmt::Debug> Object for Arc<T> {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
         (**self).serialize(out)
     }
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         match p {
             Primitive::Reference(r) => resolve.get(Ref::new(r)),
-            p => Ok(Rc::new(T::from_primitive(p, resolve)?))
+            p => Ok(Arc::new(T::from_primitive(p, resolve)?))
         }
     }
 }
@@ -0,0 +1,286 @@
+//! Shadings (8.7.4.5) and patterns (8.7.3)
+
+use crate::object::*;
+use crate::primitive::*;
+use crate::error::*;
+use crate::function::Function;
+use crate::text::Matrix;
+
+use std::io;
+use std::sync::Arc;
+
+/// Shading dictionary or stream, as painted by `sh` or used in a shading pattern.
+#[derive(Debug, Clone)]
+pub struct Shading {
+    pub color_space: ColorSpace,
+    /// Color of the area outside the shading, in `color_space`. Only used by shading patterns.
+    pub background: Option<Vec<f32>>,
+    /// In shading space
+    pub bbox: Option<Rect>,
+    pub anti_alias: bool,
+    pub kind: ShadingKind,
+    /// A single function, or one for each color component. Empty if the colors are given directly.
+    pub function: Vec<Function>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ShadingKind {
+    /// Type 1: the color at `(x, y)` is `function(x, y)`
+    Function { domain: [f32; 4], matrix: Matrix },
+    /// Type 2: `t` varies along the line from `(x0, y0)` to `(x1, y1)`
+    Axial { coords: [f32; 4], domain: [f32; 2], extend: [bool; 2] },
+    /// Type 3: `t` varies between the circles `(x0, y0, r0)` and `(x1, y1, r1)`
+    Radial { coords: [f32; 6], domain: [f32; 2], extend: [bool; 2] },
+    /// Types 4 to 7: triangle meshes and patch meshes, with the vertex data as in the stream
+    Mesh (MeshShading),
+}
+
+/// Free-form (4) and lattice-form (5) triangle meshes, Coons (6) and tensor-product (7) patch meshes
+#[derive(Debug, Clone)]
+pub struct MeshShading {
+    pub shading_type: i32,
+    pub bits_per_coordinate: Option<i32>,
+    pub bits_per_component: Option<i32>,
+    pub bits_per_flag: Option<i32>,
+    /// Only for lattice-form meshes
+    pub vertices_per_row: Option<i32>,
+    pub decode: Vec<f32>,
+    /// The decoded stream data
+    pub data: Vec<u8>,
+}
+
+impl Shading {
+    /// The color for the parametric value `t` (for type 1 `[x, y]`), in `color_space`.
+    pub fn color(&self, t: &[f32]) -> Vec<f32> {
+        match self.function.len() {
+            0 => t.to_vec(),
+            1 => self.function[0].eval(t),
+            _ => self.function.iter().map(|f| f.eval(t).get(0).cloned().unwrap_or(0.)).collect()
+        }
+    }
+}
+
+fn numbers(p: Primitive, resolve: &impl Resolve) -> Result<Vec<f32>> {
+    p.resolve(resolve)?.to_array(resolve)?.iter().map(|p| p.as_number()).collect()
+}
+
+/// The numbers of the array `p`, which must have as many as `A` holds
+fn fixed<A: Default + AsMut<[f32]>>(p: Primitive, resolve: &impl Resolve) -> Result<A> {
+    let n = numbers(p, resolve)?;
+    let mut a = A::default();
+    if n.len() != a.as_mut().len() {
+        bail!("expected {} numbers, found {}", a.as_mut().len(), n.len());
+    }
+    a.as_mut().copy_from_slice(&n);
+    Ok(a)
+}
+
+/// Reads a `/Matrix` entry.
+fn matrix(p: Option<Primitive>, resolve: &impl Resolve) -> Result<Matrix> {
+    match p {
+        Some(p) => {
+            let [a, b, c, d, e, f]: [f32; 6] = fixed(p, resolve)?;
+            Ok(Matrix::new(a, b, c, d, e, f))
+        }
+        None => Ok(Matrix::identity())
+    }
+}
+
+fn extend(p: Option<Primitive>, resolve: &impl Resolve) -> Result<[bool; 2]> {
+    match p {
+        Some(p) => {
+            let e = p.resolve(resolve)?.to_array(resolve)?;
+            match e.as_slice() {
+                &[Primitive::Boolean(a), Primitive::Boolean(b)] => Ok([a, b]),
+                _ => bail!("invalid /Extend")
+            }
+        }
+        None => Ok([false, false])
+    }
+}
+
+impl Object for Shading {
+    fn serialize<W: io::Write>(&self, _out: &mut W) -> Result<()> {
+        bail!("shadings can't be written")
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let (mut dict, data) = match p.resolve(resolve)? {
+            Primitive::Dictionary(dict) => (dict, None),
+            Primitive::Stream(stream) => {
+                let dict = stream.info.clone();
+                let data = Stream::<()>::from_primitive(Primitive::Stream(stream), resolve)?.data()?.to_vec();
+                (dict, Some(data))
+            }
+            p => return unexpected_primitive!(Dictionary, p.get_debug_name())
+        };
+        let shading_type = dict.require("Shading", "ShadingType")?.as_integer()?;
+        let color_space = ColorSpace::from_primitive(dict.require("Shading", "ColorSpace")?, resolve)?;
+        let background = match dict.remove("Background") {
+            Some(p) => Some(numbers(p, resolve)?),
+            None => None
+        };
+        let bbox = match dict.remove("BBox") {
+            Some(p) => Some(Rect::from_primitive(p.resolve(resolve)?, resolve)?),
+            None => None
+        };
+        let anti_alias = match dict.remove("AntiAlias") {
+            Some(p) => p.resolve(resolve)?.as_bool()?,
+            None => false
+        };
+        let function = match dict.remove("Function").map(|p| p.resolve(resolve)).transpose()? {
+            Some(Primitive::Array(parts)) => parts.into_iter()
+                .map(|f| Function::from_primitive(f, resolve))
+                .collect::<Result<Vec<_>>>()?,
+            Some(f) => vec![Function::from_primitive(f, resolve)?],
+            None => Vec::new()
+        };
+        let domain = |dict: &mut Dictionary| -> Result<[f32; 2]> {
+            match dict.remove("Domain") {
+                Some(p) => fixed(p, resolve),
+                None => Ok([0., 1.])
+            }
+        };
+        let kind = match shading_type {
+            1 => ShadingKind::Function {
+                domain: match dict.remove("Domain") {
+                    Some(p) => fixed(p, resolve)?,
+                    None => [0., 1., 0., 1.]
+                },
+                matrix: matrix(dict.remove("Matrix"), resolve)?,
+            },
+            2 => ShadingKind::Axial {
+                coords: fixed(dict.require("Shading", "Coords")?, resolve)?,
+                domain: domain(&mut dict)?,
+                extend: extend(dict.remove("Extend"), resolve)?,
+            },
+            3 => ShadingKind::Radial {
+                coords: fixed(dict.require("Shading", "Coords")?, resolve)?,
+                domain: domain(&mut dict)?,
+                extend: extend(dict.remove("Extend"), resolve)?,
+            },
+            4 ..= 7 => {
+                let int = |dict: &mut Dictionary, key: &str| -> Result<Option<i32>> {
+                    match dict.remove(key) {
+                        Some(p) => Ok(Some(p.resolve(resolve)?.as_integer()?)),
+                        None => Ok(None)
+                    }
+                };
+                ShadingKind::Mesh(MeshShading {
+                    shading_type,
+                    bits_per_coordinate: int(&mut dict, "BitsPerCoordinate")?,
+                    bits_per_component: int(&mut dict, "BitsPerComponent")?,
+                    bits_per_flag: int(&mut dict, "BitsPerFlag")?,
+                    vertices_per_row: int(&mut dict, "VerticesPerRow")?,
+                    decode: match dict.remove("Decode") {
+                        Some(p) => numbers(p, resolve)?,
+                        None => Vec::new()
+                    },
+                    data: data.ok_or_else(|| PdfError::from(format!("shading type {} needs a stream", shading_type)))?,
+                })
+            }
+            n => bail!("invalid ShadingType {}", n)
+        };
+        Ok(Shading { color_space, background, bbox, anti_alias, kind, function })
+    }
+}
+
+/// Pattern, as found in `/Pattern` resources and selected with `scn`.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Type 1: a cell that is repeated
+    Tiling (Arc<TilingPattern>),
+    /// Type 2
+    Shading (ShadingPattern),
+}
+
+/// A variant of Pattern
+pub type TilingPattern = Stream<TilingPatternDict>;
+
+#[derive(Object, Debug)]
+pub struct TilingPatternDict {
+    /// 1: the cell has colors of its own, 2: it is painted with the color given to `scn`
+    #[pdf(key="PaintType")]
+    pub paint_type: i32,
+
+    /// 1: constant spacing, 2: no distortion, 3: constant spacing and faster tiling
+    #[pdf(key="TilingType")]
+    pub tiling_type: i32,
+
+    #[pdf(key="BBox")]
+    pub bbox: Rect,
+
+    #[pdf(key="XStep")]
+    pub x_step: f32,
+
+    #[pdf(key="YStep")]
+    pub y_step: f32,
+
+    #[pdf(key="Resources")]
+    pub resources: Option<Arc<Resources>>,
+
+    #[pdf(key="Matrix")]
+    pub matrix: Option<Primitive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadingPattern {
+    pub shading: Arc<Shading>,
+    /// Maps pattern space to the default coordinate space of the page
+    pub matrix: Matrix,
+    /// Graphics state parameters applied while painting
+    pub graphics_state: Option<Primitive>,
+}
+
+impl Object for Pattern {
+    fn serialize<W: io::Write>(&self, _out: &mut W) -> Result<()> {
+        bail!("patterns can't be written")
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let p = p.resolve(resolve)?;
+        let pattern_type = match p {
+            Primitive::Stream(ref s) => s.info.get("PatternType"),
+            Primitive::Dictionary(ref d) => d.get("PatternType"),
+            ref p => return unexpected_primitive!(Dictionary, p.get_debug_name())
+        }.ok_or(PdfError::MissingEntry { typ: "Pattern", field: "PatternType".into() })?.as_integer()?;
+        match pattern_type {
+            1 => Ok(Pattern::Tiling(Arc::new(TilingPattern::from_primitive(p, resolve)?))),
+            2 => {
+                let mut dict = p.to_dictionary(resolve)?;
+                Ok(Pattern::Shading(ShadingPattern {
+                    shading: Arc::<Shading>::from_primitive(dict.require("Pattern", "Shading")?, resolve)?,
+                    matrix: matrix(dict.remove("Matrix"), resolve)?,
+                    graphics_state: dict.remove("ExtGState"),
+                }))
+            }
+            n => bail!("invalid PatternType {}", n)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn lazy_resources() {
+        let resources = parse(b"<< /Shading << /Sh1 << /ShadingType 2 /ColorSpace /DeviceGray /Coords [0 0 1 0] \
+            /Function << /FunctionType 2 /Domain [0 1] /N 1 >> /Extend [true false] >> /Sh2 << /ShadingType 9 >> >> \
+            /Pattern << /P1 << /PatternType 2 /Shading << /ShadingType 99 >> >> >> >>", &NoResolve).unwrap();
+        // only read when they are used
+        let resources = Resources::from_primitive(resources, &NoResolve).unwrap();
+        let shading = resources.shading("Sh1", &NoResolve).unwrap();
+        match shading.kind {
+            ShadingKind::Axial { coords, extend, .. } => assert_eq!((coords, extend), ([0., 0., 1., 0.], [true, false])),
+            ref kind => panic!("{:?}", kind)
+        }
+        assert_eq!(shading.color(&[0.25]), [0.25]);
+        assert!(resources.shading("Sh2", &NoResolve).is_err());
+        assert!(resources.shading("Sh3", &NoResolve).is_err());
+        assert!(resources.pattern("P1", &NoResolve).is_err());
+
+        assert!(shading.serialize(&mut Vec::new()).is_err());
+        let pattern = Pattern::Shading(ShadingPattern { shading: Arc::new(shading), matrix: Matrix::identity(), graphics_state: None });
+        assert!(pattern.serialize(&mut Vec::new()).is_err());
+    }
+}
@@ -2,9 +2,9 @@ use crate::object::*;
 use crate::primitive::*;
 use crate::error::*;
 use crate::parser::Lexer;
-use crate::enc::decode;
+use crate::enc::{decode, filters_with_params};
 
-use once_cell::unsync::OnceCell;
+use once_cell::sync::OnceCell;
 
 use std::borrow::Cow;
 use std::io;
@@ -16,7 +16,7 @@ use std::fmt;
 /// Simple Stream object with only some additional entries from the stream dict (I).
 pub struct Stream<I: Object=()> {
     pub info: StreamInfo<I>,
-    raw_data: Vec<u8>,
+    raw_data: StreamData,
     decoded: OnceCell<Vec<u8>>
 }
 impl<I: Object + fmt::Debug> Stream<I> {
@@ -129,48 +129,16 @@ impl<T: Object> Object for StreamInfo<T> {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         let mut dict = Dictionary::from_primitive(p, resolve)?;
 
-        let _length = usize::from_primitive(
-            dict.remove("Length").ok_or(PdfError::MissingEntry{ typ: "SteamInfo", field: "Length".into() })?,
-            resolve)?;
-
-        let filters = Vec::<String>::from_primitive(
-            dict.remove("Filter").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
+        // the parser already used it to find the data, and streams created in memory don't have it
+        dict.remove("Length");
 
-        let decode_params = Vec::<Dictionary>::from_primitive(
-            dict.remove("DecodeParms").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
+        let new_filters = filters_with_params(dict.remove("Filter"), dict.remove("DecodeParms"), resolve)?;
 
         let file = Option::<FileSpec>::from_primitive(
             dict.remove("F").or(Some(Primitive::Null)).unwrap(),
             resolve)?;
 
-        let file_filters = Vec::<String>::from_primitive(
-            dict.remove("FFilter").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
-
-        let file_decode_params = Vec::<Dictionary>::from_primitive(
-            dict.remove("FDecodeParms").or(Some(Primitive::Null)).unwrap(),
-            resolve)?;
-
-
-        let mut new_filters = Vec::new();
-        let mut new_file_filters = Vec::new();
-
-        for (i, filter) in filters.iter().enumerate() {
-            let params = match decode_params.get(i) {
-                Some(params) => params.clone(),
-                None => Dictionary::default(),
-            };
-            new_filters.push(StreamFilter::from_kind_and_params(filter, params, resolve)?);
-        }
-        for (i, filter) in file_filters.iter().enumerate() {
-            let params = match file_decode_params.get(i) {
-                Some(params) => params.clone(),
-                None => Dictionary::default(),
-            };
-            new_file_filters.push(StreamFilter::from_kind_and_params(filter, params, resolve)?);
-        }
+        let new_file_filters = filters_with_params(dict.remove("FFilter"), dict.remove("FDecodeParms"), resolve)?;
 
         Ok(StreamInfo {
             // General
@@ -196,7 +164,7 @@ pub struct ObjStmInfo {
 
     #[pdf(key = "Extends")]
     /// A reference to an eventual ObjectStream which this ObjectStream extends.
-    pub extends: Option<i32>,
+    pub extends: Option<PlainRef>,
 
 }
 
@@ -204,6 +172,8 @@ pub struct ObjStmInfo {
 pub struct ObjectStream {
     /// Byte offset of each object. Index is the object number.
     offsets:    Vec<usize>,
+    /// Object number of each object
+    ids:        Vec<ObjNr>,
     /// The object number of this object.
     id:         ObjNr,
     
@@ -216,19 +186,44 @@ impl Object for ObjectStream {
     }
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<ObjectStream> {
         let stream: Stream<ObjStmInfo> = Stream::from_primitive(p, resolve)?;
+        let invalid = |msg: String| Err(PdfError::InvalidObjStm {msg});
+
+        let (n, first) = (stream.info.num_objects, stream.info.first);
+        let data = stream.data()?;
+        if n < 0 {
+            return invalid(format!("/N is {}", n));
+        }
+        if first < 0 || first as usize > data.len() {
+            return invalid(format!("/First is {}, but the stream has {} bytes", first, data.len()));
+        }
+        let first = first as usize;
 
-        let mut offsets = Vec::new();
+        let mut offsets = Vec::with_capacity((n as usize).min(first / 4));
+        let mut ids = Vec::with_capacity(offsets.capacity());
         {
-            let mut lexer = Lexer::new(stream.data()?);
-            for _ in 0..(stream.info.num_objects as ObjNr) {
-                let _obj_nr = lexer.next()?.to::<ObjNr>()?;
-                let offset = lexer.next()?.to::<usize>()?;
+            // the header of `N` pairs of numbers ends before `First`
+            let mut lexer = Lexer::new(&data[.. first]);
+            for i in 0 .. n as usize {
+                let (obj_nr, offset) = match (lexer.next(), lexer.next()) {
+                    (Ok(obj_nr), Ok(offset)) => (obj_nr.to::<ObjNr>()?, offset.to::<usize>()?),
+                    _ => return invalid(format!("header ends after {} of {} objects", i, n))
+                };
+                if first.checked_add(offset).map_or(true, |end| end > data.len()) {
+                    return invalid(format!("object {} at offset {} is outside the stream", obj_nr, offset));
+                }
+                if let Some(&last) = offsets.last() {
+                    if offset <= last {
+                        return invalid(format!("offset {} of object {} does not follow {}", offset, obj_nr, last));
+                    }
+                }
+                ids.push(obj_nr);
                 offsets.push(offset);
             }
         }
 
         Ok(ObjectStream {
             offsets: offsets,
+            ids: ids,
             id: 0, // TODO
             inner: stream
         })
@@ -240,18 +235,62 @@ impl ObjectStream {
         if index >= self.offsets.len() {
             err!(PdfError::ObjStmOutOfBounds {index: index, max: self.offsets.len()});
         }
-        let start = self.inner.info.first as usize + self.offsets[index];
+        let first = self.inner.info.first as usize;
         let data = self.inner.data()?;
-        let end = if index == self.offsets.len() - 1 {
-            data.len()
-        } else {
-            self.inner.info.first as usize + self.offsets[index + 1]
+        let start = first + self.offsets[index];
+        let end = match self.offsets.get(index + 1) {
+            Some(&next) => first + next,
+            None => data.len()
         };
-
-        Ok(&data[start..end])
+        // checked by from_primitive
+        data.get(start .. end).ok_or_else(|| PdfError::InvalidObjStm {msg: format!("object {} is outside the stream", index)})
     }
     /// Returns the number of contained objects
     pub fn n_objects(&self) -> usize {
         self.offsets.len()
     }
+    /// The index of the object number `id` in this stream.
+    pub fn find(&self, id: ObjNr) -> Option<usize> {
+        self.ids.iter().position(|&i| i == id)
+    }
+    /// Whether the object at `index` has the number `id`.
+    pub fn has_object_at(&self, index: usize, id: ObjNr) -> bool {
+        self.ids.get(index) == Some(&id)
+    }
+    /// The object stream this one extends, which holds further objects.
+    pub fn extends(&self) -> Option<PlainRef> {
+        self.inner.info.extends
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object_stream(n: i32, first: i32, data: &[u8]) -> Result<ObjectStream> {
+        let mut info = Dictionary::new();
+        info.insert("Type".into(), "ObjStm".into());
+        info.insert("N".into(), Primitive::Integer(n));
+        info.insert("First".into(), Primitive::Integer(first));
+        info.insert("Length".into(), Primitive::Integer(data.len() as i32));
+        ObjectStream::from_primitive(Primitive::Stream(PdfStream { info, data: data.to_vec().into() }), &NoResolve)
+    }
+
+    #[test]
+    fn validation() {
+        let data = b"7 0 8 2 1 2";
+        let stream = object_stream(2, 8, data).unwrap();
+        assert_eq!(stream.get_object_slice(0).unwrap(), b"1 ");
+        assert_eq!(stream.get_object_slice(1).unwrap(), b"2");
+        assert!(stream.get_object_slice(2).is_err());
+
+        // too many objects for the header
+        assert!(object_stream(3, 8, data).is_err());
+        // /First past the end
+        assert!(object_stream(2, 20, data).is_err());
+        // offsets out of order
+        assert!(object_stream(2, 8, b"7 2 8 0 1 2").is_err());
+        // offset past the end
+        assert!(object_stream(2, 8, b"7 0 8 9 1 2").is_err());
+    }
 }
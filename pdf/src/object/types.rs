@@ -1,15 +1,18 @@
 //! Models of PDF types
 
 use std::io;
-use std::rc::Rc;
+use std::fmt;
+use std::sync::Arc;
 use std::ops::Deref;
+use chrono::{DateTime, FixedOffset, Local};
 
 use crate::object::*;
 use crate::error::*;
-use crate::content::Content;
-use crate::font::Font;
+use crate::content::{Content, Operation};
+use crate::font::{Font, FontData};
 use crate::file::File;
 use crate::backend::Backend;
+use crate::bbox;
 
 /// Node in a page tree - type is either `Page` or `PageTree`
 #[derive(Debug)]
@@ -35,7 +38,7 @@ impl Object for PagesNode {
 }
 
 #[derive(Debug, Clone)]
-pub struct PageRc(pub Rc<PagesNode>);
+pub struct PageRc(pub Arc<PagesNode>);
 impl Deref for PageRc {
     type Target = Page;
     fn deref(&self) -> &Page {
@@ -49,37 +52,87 @@ impl Deref for PageRc {
 
 #[derive(Object, Debug)]
 pub struct Catalog {
-// Version: Name,
+    #[pdf(key="Version")]
+    pub version: Option<String>,
+
     #[pdf(key="Pages")]
-    pub pages: Rc<PagesNode>,
-// PageLabels: number_tree,
+    pub pages: Arc<PagesNode>,
+
+    #[pdf(key="PageLabels")]
+    pub page_labels: Option<Primitive>,
+
     #[pdf(key="Names")]
     pub names: Option<NameDictionary>,
     
-// Dests: Dict
-// ViewerPreferences: dict
-// PageLayout: name
-// PageMode: name
-// Outlines: dict
-// Threads: array
-// OpenAction: array or dict
-// AA: dict
-// URI: dict
-// AcroForm: dict
-// Metadata: stream
+    #[pdf(key="Dests")]
+    pub dests: Option<Primitive>,
+
+    #[pdf(key="ViewerPreferences")]
+    pub viewer_preferences: Option<Primitive>,
+
+    #[pdf(key="PageLayout")]
+    pub page_layout: Option<String>,
+
+    #[pdf(key="PageMode")]
+    pub page_mode: Option<String>,
+
+    #[pdf(key="Outlines")]
+    pub outlines: Option<Outlines>,
+
+    #[pdf(key="Threads")]
+    pub threads: Option<Primitive>,
+
+    #[pdf(key="OpenAction")]
+    pub open_action: Option<Primitive>,
+
+    #[pdf(key="AA")]
+    pub additional_actions: Option<Primitive>,
+
+    #[pdf(key="URI")]
+    pub uri: Option<Primitive>,
+
+    /// The interactive form, read by `File::acro_form()` so that a malformed one doesn't make the catalog unreadable
+    #[pdf(key="AcroForm")]
+    pub acro_form: Option<Primitive>,
+
+    #[pdf(key="Metadata")]
+    pub metadata: Option<Primitive>,
+
     #[pdf(key="StructTreeRoot")]
     pub struct_tree_root: Option<StructTreeRoot>,
-// MarkInfo: dict
-// Lang: text string
-// SpiderInfo: dict
-// OutputIntents: array
-// PieceInfo: dict
-// OCProperties: dict
-// Perms: dict
-// Legal: dict
-// Requirements: array
-// Collection: dict
-// NeedsRendering: bool
+
+    #[pdf(key="MarkInfo")]
+    pub mark_info: Option<MarkInformation>,
+
+    #[pdf(key="Lang")]
+    pub lang: Option<PdfString>,
+
+    #[pdf(key="SpiderInfo")]
+    pub spider_info: Option<Primitive>,
+
+    #[pdf(key="OutputIntents")]
+    pub output_intents: Option<Primitive>,
+
+    #[pdf(key="PieceInfo")]
+    pub piece_info: Option<PieceInfo>,
+
+    #[pdf(key="OCProperties")]
+    pub oc_properties: Option<Primitive>,
+
+    #[pdf(key="Perms")]
+    pub perms: Option<Primitive>,
+
+    #[pdf(key="Legal")]
+    pub legal: Option<Primitive>,
+
+    #[pdf(key="Requirements")]
+    pub requirements: Option<Primitive>,
+
+    #[pdf(key="Collection")]
+    pub collection: Option<Primitive>,
+
+    #[pdf(key="NeedsRendering", default="false")]
+    pub needs_rendering: bool,
 }
 
 
@@ -98,7 +151,7 @@ pub struct PageTree {
     // Note about inheritance..= if we wanted to 'inherit' things at the time of reading, we would
     // want Option<Ref<Resources>> here most likely.
     #[pdf(key="Resources")]
-    pub resources: Option<Rc<Resources>>,
+    pub resources: Option<Arc<Resources>>,
     
     #[pdf(key="MediaBox")]
     pub media_box:  Option<Rect>,
@@ -113,7 +166,7 @@ pub struct Page {
     pub parent: Ref<PagesNode>,
 
     #[pdf(key="Resources")]
-    pub resources: Option<Rc<Resources>>,
+    pub resources: Option<Arc<Resources>>,
     
     #[pdf(key="MediaBox")]
     pub media_box:  Option<Rect>,
@@ -121,11 +174,45 @@ pub struct Page {
     #[pdf(key="CropBox")]
     pub crop_box:   Option<Rect>,
     
+    #[pdf(key="BleedBox")]
+    pub bleed_box:  Option<Rect>,
+    
     #[pdf(key="TrimBox")]
     pub trim_box:   Option<Rect>,
     
+    #[pdf(key="ArtBox")]
+    pub art_box:    Option<Rect>,
+    
+    #[pdf(key="Rotate")]
+    pub rotate:     Option<i32>,
+    
     #[pdf(key="Contents")]
-    pub contents:   Option<Content>
+    pub contents:   Option<Content>,
+    
+    #[pdf(key="Annots")]
+    pub annots:     Option<Primitive>,
+    
+    #[pdf(key="Group")]
+    pub group:      Option<Primitive>,
+    
+    #[pdf(key="Metadata")]
+    pub metadata:   Option<Primitive>,
+    
+    #[pdf(key="PieceInfo")]
+    pub piece_info: Option<PieceInfo>,
+    
+    /// Date of the last modification, see `modified_date()`
+    #[pdf(key="LastModified")]
+    pub last_modified: Option<Primitive>,
+    
+    #[pdf(key="StructParents")]
+    pub struct_parents: Option<i32>,
+    
+    #[pdf(key="AA")]
+    pub additional_actions: Option<Primitive>,
+    
+    #[pdf(key="UserUnit")]
+    pub user_unit:  Option<f32>,
 }
 fn inherit<T, F, B: Backend>(mut parent: Ref<PagesNode>, file: &File<B>, f: F) -> Result<Option<T>>
     where F: Fn(&PageTree) -> Option<T>
@@ -147,11 +234,26 @@ impl Page {
             parent:     parent,
             media_box:  None,
             crop_box:   None,
+            bleed_box:  None,
             trim_box:   None,
+            art_box:    None,
+            rotate:     None,
             resources:  None,
-            contents:   None
+            contents:   None,
+            annots:     None,
+            group:      None,
+            metadata:   None,
+            piece_info: None,
+            last_modified: None,
+            struct_parents: None,
+            additional_actions: None,
+            user_unit:  None,
         }
     }
+    /// The `/LastModified` date, `None` if it is missing or malformed.
+    pub fn modified_date(&self, resolve: &impl Resolve) -> Option<DateTime<FixedOffset>> {
+        lenient_date(self.last_modified.clone()?, resolve)
+    }
     pub fn media_box<B: Backend>(&self, file: &File<B>) -> Result<Rect> {
         match self.media_box {
             Some(b) => Ok(b),
@@ -168,13 +270,250 @@ impl Page {
             }
         }
     }
-    pub fn resources<B: Backend>(&self, file: &File<B>) -> Result<Rc<Resources>> {
+    /// The annotations of this page.
+    pub fn annotations<B: Backend>(&self, file: &File<B>) -> Result<Vec<Annot>> {
+        let annots = match self.annots {
+            Some(ref annots) => annots.clone().resolve(file)?,
+            None => return Ok(Vec::new())
+        };
+        match annots {
+            Primitive::Array(annots) => annots.into_iter()
+                .map(|a| Annot::from_primitive(a, file))
+                .collect(),
+            Primitive::Null => Ok(Vec::new()),
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Array", found: p.get_debug_name() })
+        }
+    }
+    /// Bounding box of everything painted on the page in user space, limited to the crop box.
+    /// `None` if the page is empty.
+    pub fn content_bbox<B: Backend>(&self, file: &File<B>) -> Result<Option<Rect>> {
+        let content = match self.contents {
+            Some(ref content) => content,
+            None => return Ok(None)
+        };
+        let resources = self.resources(file).ok();
+        bbox::content_bbox(content, resources.as_ref().map(|r| &**r), self.crop_box(file)?, file)
+    }
+    /// Whether at most `tolerance` (0 to 1) of the crop box is covered by marks.
+    /// Images count with the fraction of their pixels that are not near white, so empty scans are blank, too.
+    pub fn is_blank<B: Backend>(&self, file: &File<B>, tolerance: f32) -> Result<bool> {
+        let content = match self.contents {
+            Some(ref content) => content,
+            None => return Ok(true)
+        };
+        let crop = self.crop_box(file)?;
+        let resources = self.resources(file).ok();
+        let covered = bbox::coverage(content, resources.as_ref().map(|r| &**r), crop, file)?;
+        let area = ((crop.right - crop.left) * (crop.top - crop.bottom)).abs();
+        Ok(covered <= tolerance * area)
+    }
+    pub fn resources<B: Backend>(&self, file: &File<B>) -> Result<Arc<Resources>> {
         match self.resources {
             Some(ref r) => Ok(r.clone()),
             None => inherit(self.parent, file, |pt| pt.resources.clone())?
                 .ok_or_else(|| PdfError::MissingEntry { typ: "Page", field: "Resources".into() })
         }
     }
+    /// Entries of the page resources that the content stream never refers to.
+    /// Form XObjects, tiling patterns, Type3 glyphs and appearance streams of annotations
+    /// without resources of their own are searched, too.
+    pub fn unused_resources<B: Backend>(&self, file: &File<B>) -> Result<UnusedResources> {
+        let resources = self.resources(file)?;
+        let mut used = UnusedResources::default();
+        if let Some(ref content) = self.contents {
+            used_names(&content.operations, &resources, &mut used, 0, file)?;
+        }
+        // appearance streams are meant to have resources, but viewers use those of the page
+        for annot in self.annotations(file)? {
+            let ap = match annot.annotation().appearance {
+                Some(ref ap) => ap,
+                None => continue
+            };
+            for entry in Some(&ap.normal).into_iter().chain(ap.rollover.as_ref()).chain(ap.down.as_ref()) {
+                let streams = match *entry {
+                    AppearanceEntry::Stream(r) => vec![r],
+                    AppearanceEntry::States(ref states) => states.values().cloned().collect()
+                };
+                for r in streams {
+                    if let XObject::Form(ref form) = *file.get(Ref::<XObject>::new(r))? {
+                        if form.resources.is_none() {
+                            used_in_stream(form.data()?, &resources, &mut used, 0, file)?;
+                        }
+                    }
+                }
+            }
+        }
+        fn unused<T>(map: &BTreeMap<String, T>, used: &[String]) -> Vec<String> {
+            map.keys().filter(|k| !used.contains(k)).cloned().collect()
+        }
+        Ok(UnusedResources {
+            fonts:           unused(&resources.fonts, &used.fonts),
+            xobjects:        unused(&resources.xobjects, &used.xobjects),
+            graphics_states: unused(&resources.graphics_states, &used.graphics_states),
+            color_spaces:    unused(&resources.color_spaces, &used.color_spaces),
+            patterns:        unused(&resources.patterns, &used.patterns),
+            shadings:        unused(&resources.shadings, &used.shadings),
+            properties:      unused(&resources.properties, &used.properties),
+        })
+    }
+}
+
+/// Names in a resource dictionary, by category (the result of `Page::unused_resources`).
+#[derive(Debug, Default, Clone)]
+pub struct UnusedResources {
+    pub fonts: Vec<String>,
+    pub xobjects: Vec<String>,
+    pub graphics_states: Vec<String>,
+    pub color_spaces: Vec<String>,
+    pub patterns: Vec<String>,
+    pub shadings: Vec<String>,
+    pub properties: Vec<String>,
+}
+impl UnusedResources {
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty() && self.xobjects.is_empty() && self.graphics_states.is_empty()
+            && self.color_spaces.is_empty() && self.patterns.is_empty() && self.shadings.is_empty()
+            && self.properties.is_empty()
+    }
+}
+
+/// Form XObjects can contain each other.
+const MAX_FORM_DEPTH: usize = 16;
+
+/// Adds the names in `ops` to `used`, including those of the content streams drawn by
+/// `ops` that inherit `resources`.
+fn used_names(ops: &[Operation], resources: &Resources, used: &mut UnusedResources, depth: usize, resolve: &impl Resolve) -> Result<()> {
+    /// The name in `p`, if it wasn't in `names` yet
+    fn add<'a>(names: &mut Vec<String>, p: Option<&'a Primitive>) -> Option<&'a str> {
+        match p {
+            Some(&Primitive::Name(ref name)) if !names.contains(name) => {
+                names.push(name.clone());
+                Some(name)
+            }
+            _ => None
+        }
+    }
+    for op in ops {
+        let operands = &op.operands;
+        match op.operator.as_str() {
+            "Tf" => if let Some(name) = add(&mut used.fonts, operands.first()) {
+                // glyph procedures without resources use those of the page
+                if let Some(font) = resources.fonts.get(name) {
+                    if let FontData::Type3(ref t3) = font.data {
+                        if t3.resources.is_none() {
+                            for char_proc in t3.char_procs.values() {
+                                used_in_stream(char_proc.data()?, resources, used, depth, resolve)?;
+                            }
+                        }
+                    }
+                }
+            },
+            "gs" => { add(&mut used.graphics_states, operands.first()); }
+            "cs" | "CS" => { add(&mut used.color_spaces, operands.first()); }
+            // the name of a pattern follows the components of uncolored patterns
+            "scn" | "SCN" => if let Some(name) = add(&mut used.patterns, operands.last()) {
+                // patterns that can't be read don't use anything either
+                if let Ok(Pattern::Tiling(ref tiling)) = resources.pattern(name, resolve) {
+                    if tiling.resources.is_none() {
+                        used_in_stream(tiling.data()?, resources, used, depth, resolve)?;
+                    }
+                }
+            },
+            "sh" => { add(&mut used.shadings, operands.first()); }
+            "BDC" | "DP" => { add(&mut used.properties, operands.get(1)); }
+            "BI" => if let Some(&Primitive::Stream(ref image)) = operands.first() {
+                match image.info.get("CS").or_else(|| image.info.get("ColorSpace")) {
+                    // the base of an indexed color space can be a name, too
+                    Some(&Primitive::Array(ref parts)) => { add(&mut used.color_spaces, parts.get(1)); }
+                    cs => { add(&mut used.color_spaces, cs); }
+                }
+            },
+            "Do" => if let Some(name) = add(&mut used.xobjects, operands.first()) {
+                // forms without resources use those of the page
+                if let Some(&XObject::Form(ref form)) = resources.xobjects.get(name) {
+                    if form.resources.is_none() {
+                        used_in_stream(form.data()?, resources, used, depth, resolve)?;
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `used_names` of a content stream that inherits `resources`.
+fn used_in_stream(data: &[u8], resources: &Resources, used: &mut UnusedResources, depth: usize, resolve: &impl Resolve) -> Result<()> {
+    if depth >= MAX_FORM_DEPTH {
+        return Ok(());
+    }
+    let content = Content::parse_from(data, resolve)?;
+    used_names(&content.operations, resources, used, depth + 1, resolve)
+}
+
+/// Page-piece dictionary (14.5): private data of the applications that worked on a
+/// page, form or document, keyed by application name.
+pub type PieceInfo = BTreeMap<String, PieceData>;
+
+/// Data dictionary of one application in a `PieceInfo`
+#[derive(Debug, Clone)]
+pub struct PieceData {
+    /// When the application last changed `private`, `None` if it is missing or malformed.
+    /// Consumers should ignore `private` if this is older than the modification date of the owner.
+    pub last_modified: Option<DateTime<FixedOffset>>,
+
+    pub private: Option<Primitive>,
+}
+/// Reads a date that is only informational, so that a malformed one is a warning instead of an error.
+fn lenient_date(p: Primitive, resolve: &impl Resolve) -> Option<DateTime<FixedOffset>> {
+    match p.resolve(resolve).and_then(|p| DateTime::from_primitive(p, resolve)) {
+        Ok(date) => Some(date),
+        Err(e) => {
+            resolve.warn(format!("malformed date: {}", e));
+            None
+        }
+    }
+}
+impl Object for PieceData {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        Primitive::from(self.clone()).serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let mut dict = p.to_dictionary(resolve)?;
+        Ok(PieceData {
+            last_modified: dict.remove("LastModified").and_then(|p| lenient_date(p, resolve)),
+            private: dict.remove("Private"),
+        })
+    }
+}
+impl PieceData {
+    /// Data modified now.
+    pub fn new(private: Primitive) -> PieceData {
+        let now = Local::now();
+        PieceData {
+            last_modified: Some(now.with_timezone(now.offset())),
+            private: Some(private),
+        }
+    }
+    /// Whether the data is at least as recent as `modified`, the date of the page or document.
+    pub fn is_current(&self, modified: Option<DateTime<FixedOffset>>) -> bool {
+        match (self.last_modified, modified) {
+            (Some(data), Some(owner)) => data >= owner,
+            _ => true
+        }
+    }
+}
+impl From<PieceData> for Primitive {
+    fn from(data: PieceData) -> Primitive {
+        let mut dict = Dictionary::new();
+        if let Some(ref date) = data.last_modified {
+            dict.insert("LastModified".into(), format_date(date).into());
+        }
+        if let Some(private) = data.private {
+            dict.insert("Private".into(), private);
+        }
+        Primitive::Dictionary(dict)
+    }
 }
 
 #[derive(Object)]
@@ -193,34 +532,161 @@ pub struct PageLabel {
 pub struct Resources {
     #[pdf(key="ExtGState")]
     pub graphics_states: BTreeMap<String, GraphicsStateParameters>,
-    // color_space: Option<ColorSpace>,
-    // pattern: Option<Pattern>,
-    // shading: Option<Shading>,
+    
+    #[pdf(key="ColorSpace")]
+    pub color_spaces: BTreeMap<String, ColorSpace>,
+    
+    /// Read with `Resources::pattern`
+    #[pdf(key="Pattern")]
+    pub patterns: BTreeMap<String, Primitive>,
+    
+    /// Read with `Resources::shading`
+    #[pdf(key="Shading")]
+    pub shadings: BTreeMap<String, Primitive>,
+    
+    // /XObject is a dictionary that map arbitrary names to XObjects
     #[pdf(key="XObject")]
     pub xobjects: BTreeMap<String, XObject>,
-    // /XObject is a dictionary that map arbitrary names to XObjects
+    
     #[pdf(key="Font")]
-    pub fonts: BTreeMap<String, Rc<Font>>,
+    pub fonts: BTreeMap<String, Arc<Font>>,
+    
+    #[pdf(key="Properties")]
+    pub properties: BTreeMap<String, Primitive>,
+    
+    #[pdf(key="ProcSet")]
+    pub proc_set: Option<Primitive>,
 }
 impl Resources {
-    pub fn fonts(&self) -> impl Iterator<Item=(&str, &Rc<Font>)> {
+    pub fn fonts(&self) -> impl Iterator<Item=(&str, &Arc<Font>)> {
         self.fonts.iter().map(|(k, v)| (k.as_str(), v))
     }
+    /// Looks up the operand of `cs`/`CS`: the device color spaces and `Pattern` by name, anything else in `/ColorSpace`.
+    pub fn color_space(&self, name: &str) -> Option<&ColorSpace> {
+        ColorSpace::from_name(name).or_else(|| self.color_spaces.get(name))
+    }
+    /// The pattern `name`. Patterns are only read when they are used, so that one that can't
+    /// be read doesn't make the whole resource dictionary unreadable.
+    pub fn pattern(&self, name: &str, resolve: &impl Resolve) -> Result<Pattern> {
+        match self.patterns.get(name) {
+            Some(p) => Pattern::from_primitive(p.clone(), resolve),
+            None => Err(PdfError::MissingEntry { typ: "Pattern", field: name.into() })
+        }
+    }
+    /// The shading `name`, read like `pattern`.
+    pub fn shading(&self, name: &str, resolve: &impl Resolve) -> Result<Shading> {
+        match self.shadings.get(name) {
+            Some(p) => Shading::from_primitive(p.clone(), resolve),
+            None => Err(PdfError::MissingEntry { typ: "Shading", field: name.into() })
+        }
+    }
 }
 
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Copy, Clone)]
 pub enum LineCap {
     Butt = 0,
     Round = 1,
     Square = 2
 }
-#[derive(Object, Debug)]
+#[derive(Object, Debug, Copy, Clone)]
 pub enum LineJoin {
     Miter = 0,
     Round = 1,
     Bevel = 2
 }
 
+/// `[dash_array dash_phase]` (8.4.3.6)
+#[derive(Debug, Clone)]
+pub struct DashPattern {
+    /// Lengths of the dashes and gaps. Empty for a solid line.
+    pub array: Vec<f32>,
+    pub phase: f32,
+}
+impl Object for DashPattern {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        write!(out, "[")?;
+        self.array.serialize(out)?;
+        write!(out, " {}]", self.phase)?;
+        Ok(())
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let mut parts = p.resolve(resolve)?.to_array(resolve)?;
+        if parts.len() != 2 {
+            bail!("dash pattern needs 2 entries, found {}", parts.len());
+        }
+        let phase = parts.pop().unwrap().resolve(resolve)?.as_number()?;
+        let array = Vec::<f32>::from_primitive(parts.pop().unwrap().resolve(resolve)?, resolve)?;
+        Ok(DashPattern { array, phase })
+    }
+}
+
+/// Blend mode (11.3.5)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+impl BlendMode {
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        Some(match name {
+            // Compatible is the old name of Normal
+            "Normal" | "Compatible" => BlendMode::Normal,
+            "Multiply" => BlendMode::Multiply,
+            "Screen" => BlendMode::Screen,
+            "Overlay" => BlendMode::Overlay,
+            "Darken" => BlendMode::Darken,
+            "Lighten" => BlendMode::Lighten,
+            "ColorDodge" => BlendMode::ColorDodge,
+            "ColorBurn" => BlendMode::ColorBurn,
+            "HardLight" => BlendMode::HardLight,
+            "SoftLight" => BlendMode::SoftLight,
+            "Difference" => BlendMode::Difference,
+            "Exclusion" => BlendMode::Exclusion,
+            "Hue" => BlendMode::Hue,
+            "Saturation" => BlendMode::Saturation,
+            "Color" => BlendMode::Color,
+            "Luminosity" => BlendMode::Luminosity,
+            _ => return None
+        })
+    }
+}
+impl Object for BlendMode {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        write!(out, "/{:?}", self)?;
+        Ok(())
+    }
+    /// A name, or (in older files) an array of names of which the first known one applies.
+    /// Unknown blend modes are `Normal`, as the specification demands.
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let names = match p.resolve(resolve)? {
+            Primitive::Name(name) => vec![name],
+            Primitive::Array(parts) => parts.into_iter().filter_map(|p| p.to_name().ok()).collect(),
+            p => return unexpected_primitive!(Name, p.get_debug_name())
+        };
+        match names.iter().filter_map(|name| BlendMode::from_name(name)).next() {
+            Some(mode) => Ok(mode),
+            None => {
+                resolve.warn(format!("unknown blend mode {:?}, using Normal", names));
+                Ok(BlendMode::Normal)
+            }
+        }
+    }
+}
+
 #[derive(Object, Debug)]
 #[pdf(Type = "ExtGState?")]
 /// `ExtGState`
@@ -231,18 +697,82 @@ pub struct GraphicsStateParameters {
     #[pdf(key="LC")]
     pub line_cap: Option<LineCap>,
     
-    #[pdf(key="LC")]
+    #[pdf(key="LJ")]
     pub line_join: Option<LineJoin>,
     
     #[pdf(key="ML")]
     pub miter_limit: Option<f32>,
     
-    // D : dash pattern
+    #[pdf(key="D")]
+    pub dash_pattern: Option<DashPattern>,
+    
     #[pdf(key="RI")]
     pub rendering_intent: Option<String>,
     
+    /// Overprint for stroking, and for filling if `OP` is missing
+    #[pdf(key="OP")]
+    pub overprint: Option<bool>,
+    
+    #[pdf(key="op")]
+    pub overprint_fill: Option<bool>,
+    
+    #[pdf(key="OPM")]
+    pub overprint_mode: Option<i32>,
+    
     #[pdf(key="Font")]
-    pub font: Option<(Rc<Font>, f32)>
+    pub font: Option<(Arc<Font>, f32)>,
+    
+    /// Transfer function(s), or the name `Identity`
+    #[pdf(key="TR")]
+    pub transfer: Option<Primitive>,
+    
+    #[pdf(key="TR2")]
+    pub transfer2: Option<Primitive>,
+    
+    /// Halftone dictionary or stream, or the name `Default`
+    #[pdf(key="HT")]
+    pub halftone: Option<Primitive>,
+    
+    /// Flatness tolerance
+    #[pdf(key="FL")]
+    pub flatness: Option<f32>,
+    
+    /// Smoothness tolerance
+    #[pdf(key="SM")]
+    pub smoothness: Option<f32>,
+    
+    /// Automatic stroke adjustment
+    #[pdf(key="SA")]
+    pub stroke_adjustment: Option<bool>,
+    
+    #[pdf(key="BM")]
+    pub blend_mode: Option<BlendMode>,
+    
+    /// Soft mask dictionary or the name `None`
+    #[pdf(key="SMask")]
+    pub soft_mask: Option<Primitive>,
+    
+    /// Constant alpha for stroking
+    #[pdf(key="CA")]
+    pub stroke_alpha: Option<f32>,
+    
+    /// Constant alpha for everything else
+    #[pdf(key="ca")]
+    pub fill_alpha: Option<f32>,
+    
+    /// Whether soft masks and alpha are shape rather than opacity
+    #[pdf(key="AIS")]
+    pub alpha_is_shape: Option<bool>,
+    
+    /// Text knockout
+    #[pdf(key="TK")]
+    pub text_knockout: Option<bool>,
+}
+impl GraphicsStateParameters {
+    /// `op`, which defaults to `OP`
+    pub fn fill_overprint(&self) -> Option<bool> {
+        self.overprint_fill.or(self.overprint)
+    }
 }
 
 #[derive(Object, Debug)]
@@ -275,7 +805,11 @@ pub struct ImageDict {
     pub width: i32,
     #[pdf(key="Height")]
     pub height: i32,
-    // ColorSpace: name or array
+    
+    /// name or array
+    #[pdf(key="ColorSpace")]
+    pub color_space: Option<Primitive>,
+    
     #[pdf(key="BitsPerComponent")]
     pub bits_per_component: i32,
     // Note: only allowed values are 1, 2, 4, 8, 16. Enum?
@@ -288,22 +822,30 @@ pub struct ImageDict {
     #[pdf(key="ImageMask", default="false")]
     pub image_mask: bool,
 
-    // Mask: stream or array
-    //
+    /// stream or array
+    #[pdf(key="Mask")]
+    pub mask: Option<Primitive>,
+    
     /// Describes how to map image samples into the range of values appropriate for the image’s color space.
     /// If `image_mask`: either [0 1] or [1 0]. Else, the length must be twice the number of color
     /// components required by `color_space` (key ColorSpace)
     // (see Decode arrays page 344)
     #[pdf(key="Decode")]
-    pub decode: Vec<i32>,
+    pub decode: Vec<f32>,
 
     #[pdf(key="Interpolate", default="false")]
     pub interpolate: bool,
 
-    // Alternates: Vec<AlternateImage>
+    #[pdf(key="Alternates")]
+    pub alternates: Option<Primitive>,
+
+    /// soft mask (stream)
+    #[pdf(key="SMask")]
+    pub smask: Option<Primitive>,
+
+    #[pdf(key="SMaskInData", default="0")]
+    pub smask_in_data: i32,
 
-    // SMask (soft mask): stream
-    // SMaskInData: i32
     ///The integer key of the image’s entry in the structural parent tree
     #[pdf(key="StructParent")]
     pub struct_parent: Option<i32>,
@@ -311,10 +853,14 @@ pub struct ImageDict {
     #[pdf(key="ID")]
     pub id: Option<PdfString>,
 
-    // OPI: dict
-    // Metadata: stream
-    // OC: dict
-    
+    #[pdf(key="OPI")]
+    pub opi: Option<Primitive>,
+
+    #[pdf(key="Metadata")]
+    pub metadata: Option<Primitive>,
+
+    #[pdf(key="OC")]
+    pub oc: Option<Primitive>,
 }
 
 
@@ -330,7 +876,41 @@ pub enum RenderingIntent {
 #[derive(Object, Debug)]
 #[pdf(Type="XObject?", Subtype="Form")]
 pub struct FormDict {
-    // TODO
+    #[pdf(key="FormType", default="1")]
+    pub form_type: i32,
+
+    #[pdf(key="BBox")]
+    pub bbox: Rect,
+
+    #[pdf(key="Matrix")]
+    pub matrix: Option<Primitive>,
+
+    #[pdf(key="Resources")]
+    pub resources: Option<Arc<Resources>>,
+
+    #[pdf(key="Group")]
+    pub group: Option<Primitive>,
+
+    #[pdf(key="Ref")]
+    pub reference: Option<Primitive>,
+
+    #[pdf(key="Metadata")]
+    pub metadata: Option<Primitive>,
+
+    #[pdf(key="PieceInfo")]
+    pub piece_info: Option<PieceInfo>,
+
+    #[pdf(key="StructParent")]
+    pub struct_parent: Option<i32>,
+
+    #[pdf(key="StructParents")]
+    pub struct_parents: Option<i32>,
+
+    #[pdf(key="OPI")]
+    pub opi: Option<Primitive>,
+
+    #[pdf(key="OC")]
+    pub oc: Option<Primitive>,
 }
 
 
@@ -374,6 +954,39 @@ pub struct NameTree<T> {
     node: NameTreeNode<T>,
 }
 
+/// How deep name trees may be nested, which also stops kids that refer to their ancestors
+const MAX_NAME_TREE_DEPTH: usize = 32;
+
+impl<T: Object + Clone> NameTree<T> {
+    /// Finds the value for `name`.
+    pub fn lookup(&self, name: &[u8], resolve: &impl Resolve) -> Result<Option<T>> {
+        self.lookup_at(name, resolve, 0)
+    }
+    fn lookup_at(&self, name: &[u8], resolve: &impl Resolve, depth: usize) -> Result<Option<T>> {
+        if depth > MAX_NAME_TREE_DEPTH {
+            bail!("name tree nested more than {} deep", MAX_NAME_TREE_DEPTH);
+        }
+        if let Some((ref min, ref max)) = self.limits {
+            if name < min.as_bytes() || name > max.as_bytes() {
+                return Ok(None);
+            }
+        }
+        match self.node {
+            NameTreeNode::Leaf(ref names) => Ok(names.iter()
+                .find(|&(ref key, _)| key.as_bytes() == name)
+                .map(|&(_, ref value)| value.clone())),
+            NameTreeNode::Intermediate(ref kids) => {
+                for &kid in kids {
+                    if let Some(value) = resolve.get(kid)?.lookup_at(name, resolve, depth + 1)? {
+                        return Ok(Some(value));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
 impl<T: Object> Object for NameTree<T> {
     fn serialize<W: io::Write>(&self, _out: &mut W) -> Result<()> {
         unimplemented!();
@@ -440,28 +1053,33 @@ impl<T: Object> Object for NameTree<T> {
 pub struct NameDictionary {
     #[pdf(key="Pages")]
     pages: Option<NameTree<Primitive>>,
-    /*
+    
     #[pdf(key="Dests")]
-    ap: NameTree<T>,
+    pub dests: Option<NameTree<Primitive>>,
+    
     #[pdf(key="AP")]
-    ap: NameTree<T>,
+    pub ap: Option<Primitive>,
+    
     #[pdf(key="JavaScript")]
-    javascript: NameTree<T>,
+    pub javascript: Option<Primitive>,
+    
     #[pdf(key="Templates")]
-    templates: NameTree<T>,
+    pub templates: Option<Primitive>,
+    
     #[pdf(key="IDS")]
-    ids: NameTree<T>,
+    pub ids: Option<Primitive>,
+    
     #[pdf(key="URLS")]
-    urls: NameTree<T>,
-    */
+    pub urls: Option<Primitive>,
+    
     #[pdf(key="EmbeddedFiles")]
     embedded_files: Option<FileSpec>,
-    /*
-    #[pdf(key="AlternativePresentations")]
-    alternate_presentations: NameTree<AlternatePresentation>,
+    
+    #[pdf(key="AlternatePresentations")]
+    pub alternate_presentations: Option<Primitive>,
+    
     #[pdf(key="Renditions")]
-    renditions: NameTree<Rendition>,
-    */
+    pub renditions: Option<Primitive>,
 }
 
 /* Embedded file streams can be associated with the document as a whole through
@@ -538,7 +1156,7 @@ pub fn write_list<'a, W, T: 'a, I>(out: &mut W, mut iter: I) -> Result<()>
         first.serialize(out)?;
         
         for other in iter {
-            out.write_all(b", ")?;
+            out.write_all(b" ")?;
             other.serialize(out)?;
         }
     }
@@ -547,22 +1165,1006 @@ pub fn write_list<'a, W, T: 'a, I>(out: &mut W, mut iter: I) -> Result<()>
     Ok(())
 }
 
-#[derive(Object)]
+/// How the target page of a destination is displayed (12.3.2.2).
+/// `None` leaves the respective parameter unchanged.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dest {
+    XYZ { left: Option<f32>, top: Option<f32>, zoom: Option<f32> },
+    Fit,
+    FitH { top: Option<f32> },
+    FitV { left: Option<f32> },
+    FitR (Rect),
+    FitB,
+    FitBH { top: Option<f32> },
+    FitBV { left: Option<f32> },
+}
+impl Dest {
+    /// `arr` is the part of the destination array following the page.
+    fn from_array(arr: &[Primitive]) -> Result<Dest> {
+        let num = |i: usize| -> Result<Option<f32>> {
+            match arr.get(i) {
+                None | Some(&Primitive::Null) => Ok(None),
+                Some(p) => p.as_number().map(Some)
+            }
+        };
+        let kind = match arr.get(0) {
+            Some(p) => p.as_name()?,
+            None => bail!("destination without type")
+        };
+        Ok(match kind {
+            "XYZ" => Dest::XYZ { left: num(1)?, top: num(2)?, zoom: num(3)? },
+            "Fit" => Dest::Fit,
+            "FitH" => Dest::FitH { top: num(1)? },
+            "FitV" => Dest::FitV { left: num(1)? },
+            "FitR" => Dest::FitR (Rect {
+                left:   num(1)?.unwrap_or(0.),
+                bottom: num(2)?.unwrap_or(0.),
+                right:  num(3)?.unwrap_or(0.),
+                top:    num(4)?.unwrap_or(0.),
+            }),
+            "FitB" => Dest::FitB,
+            "FitBH" => Dest::FitBH { top: num(1)? },
+            "FitBV" => Dest::FitBV { left: num(1)? },
+            other => bail!("unknown destination type {}", other)
+        })
+    }
+    fn to_primitives(&self) -> Vec<Primitive> {
+        fn opt(n: Option<f32>) -> Primitive {
+            n.map(Primitive::Number).unwrap_or(Primitive::Null)
+        }
+        match *self {
+            Dest::XYZ { left, top, zoom } => vec!["XYZ".into(), opt(left), opt(top), opt(zoom)],
+            Dest::Fit => vec!["Fit".into()],
+            Dest::FitH { top } => vec!["FitH".into(), opt(top)],
+            Dest::FitV { left } => vec!["FitV".into(), opt(left)],
+            Dest::FitR (r) => vec!["FitR".into(), r.left.into(), r.bottom.into(), r.right.into(), r.top.into()],
+            Dest::FitB => vec!["FitB".into()],
+            Dest::FitBH { top } => vec!["FitBH".into(), opt(top)],
+            Dest::FitBV { left } => vec!["FitBV".into(), opt(left)],
+        }
+    }
+}
+
+/// Target of a link, outline item or GoTo action (12.3.2)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Destination {
+    /// A page of this document and how to display it.
+    Explicit { page: PlainRef, view: Dest },
+    /// Has to be looked up in `/Dests` of the catalog or the `Dests` name tree.
+    Named (PdfString),
+}
+impl Object for Destination {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        Primitive::from(self.clone()).serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        Destination::parse(p, resolve, 0)
+    }
+}
+impl Destination {
+    /// Reads a destination that is wrapped in `depth` references or dictionaries.
+    fn parse(p: Primitive, resolve: &impl Resolve, depth: usize) -> Result<Destination> {
+        if depth > 8 {
+            bail!("destination wrapped more than 8 times");
+        }
+        match p {
+            Primitive::Array(arr) => {
+                let page = match arr.get(0) {
+                    Some(&Primitive::Reference(r)) => r,
+                    Some(p) => return Err(PdfError::UnexpectedPrimitive { expected: "Reference", found: p.get_debug_name() }),
+                    None => bail!("empty destination array")
+                };
+                Ok(Destination::Explicit { page, view: Dest::from_array(&arr[1..])? })
+            }
+            Primitive::String(s) => Ok(Destination::Named(s)),
+            Primitive::Name(n) => Ok(Destination::Named(PdfString::new(n.into_bytes()))),
+            // destination dictionaries wrap the array in /D
+            Primitive::Dictionary(mut dict) => Destination::parse(dict.require("Destination", "D")?, resolve, depth + 1),
+            Primitive::Reference(r) => Destination::parse(resolve.resolve(r)?, resolve, depth + 1),
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Array, String or Name", found: p.get_debug_name() })
+        }
+    }
+}
+impl From<Destination> for Primitive {
+    fn from(dest: Destination) -> Primitive {
+        match dest {
+            Destination::Explicit { page, view } => {
+                let mut arr = vec![Primitive::Reference(page)];
+                arr.extend(view.to_primitives());
+                Primitive::Array(arr)
+            }
+            Destination::Named(name) => Primitive::String(name)
+        }
+    }
+}
+
+/// Action dictionary (12.6). Only the common action types are modeled.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    GoTo (Destination),
+    URI (PdfString),
+    Named (String),
+    Other (Dictionary),
+}
+impl Object for Action {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        Primitive::from(self.clone()).serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        let mut dict = p.to_dictionary(resolve)?;
+        let kind = match dict.get("S") {
+            Some(s) => s.as_name()?.to_owned(),
+            None => return Ok(Action::Other(dict))
+        };
+        Ok(match kind.as_str() {
+            "GoTo" => Action::GoTo(Destination::from_primitive(dict.require("Action", "D")?, resolve)?),
+            "URI" => Action::URI(dict.require("Action", "URI")?.resolve(resolve)?.to_string()?),
+            "Named" => Action::Named(dict.require("Action", "N")?.to_name()?),
+            _ => Action::Other(dict)
+        })
+    }
+}
+impl From<Action> for Primitive {
+    fn from(action: Action) -> Primitive {
+        let mut dict = Dictionary::new();
+        dict.insert("Type".into(), "Action".into());
+        match action {
+            Action::GoTo(dest) => {
+                dict.insert("S".into(), "GoTo".into());
+                dict.insert("D".into(), dest.into());
+            }
+            Action::URI(uri) => {
+                dict.insert("S".into(), "URI".into());
+                dict.insert("URI".into(), uri.into());
+            }
+            Action::Named(name) => {
+                dict.insert("S".into(), "Named".into());
+                dict.insert("N".into(), name.into());
+            }
+            Action::Other(other) => return Primitive::Dictionary(other)
+        }
+        Primitive::Dictionary(dict)
+    }
+}
+
+/// Annotation flags (`/F`), 12.5.3
+#[allow(non_upper_case_globals, dead_code)]
+pub mod annot_flags {
+    pub const Invisible: u32        = 1 << 0;
+    pub const Hidden: u32           = 1 << 1;
+    pub const Print: u32            = 1 << 2;
+    pub const NoZoom: u32           = 1 << 3;
+    pub const NoRotate: u32         = 1 << 4;
+    pub const NoView: u32           = 1 << 5;
+    pub const ReadOnly: u32         = 1 << 6;
+    pub const Locked: u32           = 1 << 7;
+    pub const ToggleNoView: u32     = 1 << 8;
+    pub const LockedContents: u32   = 1 << 9;
+}
+
+/// Annotation dictionary (12.5.2) with the entries of link and text annotations.
+/// Entries of other subtypes end up in `other`.
+#[derive(Object, Debug, Clone)]
+#[pdf(Type="Annot?")]
+pub struct Annotation {
+    #[pdf(key="Subtype")]
+    pub subtype: String,
+    
+    #[pdf(key="Rect")]
+    pub rect: Rect,
+    
+    #[pdf(key="Contents")]
+    pub contents: Option<PdfString>,
+    
+    /// The page this annotation is on
+    #[pdf(key="P")]
+    pub page: Option<PlainRef>,
+    
+    #[pdf(key="NM")]
+    pub name: Option<PdfString>,
+    
+    /// Date of the last modification, see `modified_date()`
+    #[pdf(key="M")]
+    pub modified: Option<PdfString>,
+    
+    /// see `annot_flags`
+    #[pdf(key="F", default="0")]
+    pub flags: u32,
+    
+    #[pdf(key="AP")]
+    pub appearance: Option<AppearanceStreams>,
+    
+    #[pdf(key="AS")]
+    pub appearance_state: Option<String>,
+    
+    #[pdf(key="Border")]
+    pub border: Option<Primitive>,
+    
+    #[pdf(key="C")]
+    pub color: Vec<f32>,
+    
+    #[pdf(key="StructParent")]
+    pub struct_parent: Option<i32>,
+    
+    /// Link: where to go when activated (exclusive with `action`)
+    #[pdf(key="Dest")]
+    pub dest: Option<Destination>,
+    
+    #[pdf(key="A")]
+    pub action: Option<Action>,
+    
+    /// Text: whether the note is initially open
+    #[pdf(key="Open", default="false")]
+    pub open: bool,
+    
+    /// Text: icon name (Note, Comment, Help, …)
+    #[pdf(key="Name")]
+    pub icon: Option<String>,
+    
+    /// Text markup: quadrilaterals of the marked text, 8 numbers each
+    #[pdf(key="QuadPoints")]
+    pub quad_points: Vec<f32>,
+    
+    /// Markup: the associated pop-up annotation
+    #[pdf(key="Popup")]
+    pub popup: Option<PlainRef>,
+    
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+impl Annotation {
+    /// Creates an annotation with the printable flag set and the modification date set to now.
+    pub fn new(subtype: &str, rect: Rect) -> Annotation {
+        let now = Local::now();
+        Annotation {
+            subtype: subtype.into(),
+            rect,
+            contents: None,
+            page: None,
+            name: None,
+            modified: Some(format_date(&now.with_timezone(now.offset()))),
+            flags: annot_flags::Print,
+            appearance: None,
+            appearance_state: None,
+            border: None,
+            color: Vec::new(),
+            struct_parent: None,
+            dest: None,
+            action: None,
+            open: false,
+            icon: None,
+            quad_points: Vec::new(),
+            popup: None,
+            other: Dictionary::new(),
+        }
+    }
+    /// Borderless link to `dest`.
+    pub fn link(rect: Rect, dest: Destination) -> Annotation {
+        let mut annot = Annotation::new("Link", rect);
+        annot.border = Some(Primitive::Array(vec![Primitive::Integer(0); 3]));
+        annot.dest = Some(dest);
+        annot
+    }
+    /// Borderless link opening `uri`.
+    pub fn uri_link(rect: Rect, uri: &str) -> Annotation {
+        let mut annot = Annotation::new("Link", rect);
+        annot.border = Some(Primitive::Array(vec![Primitive::Integer(0); 3]));
+        annot.action = Some(Action::URI(PdfString::new(uri.as_bytes().to_vec())));
+        annot
+    }
+    /// Sticky note showing `contents`. Like in most viewers, it does not scale or rotate with the page.
+    pub fn text_note(rect: Rect, contents: &str) -> Annotation {
+        let mut annot = Annotation::new("Text", rect);
+        annot.flags |= annot_flags::NoZoom | annot_flags::NoRotate;
+        annot.contents = Some(PdfString::from_text(contents));
+        annot.icon = Some("Note".into());
+        annot
+    }
+    pub fn modified_date(&self) -> Option<DateTime<FixedOffset>> {
+        let m = self.modified.clone()?;
+        DateTime::from_primitive(Primitive::String(m), &NoResolve).ok()
+    }
+}
+impl From<Annotation> for Primitive {
+    fn from(a: Annotation) -> Primitive {
+        let mut dict = a.other;
+        dict.insert("Type".into(), "Annot".into());
+        dict.insert("Subtype".into(), a.subtype.into());
+        dict.insert("Rect".into(), a.rect.into());
+        if let Some(contents) = a.contents {
+            dict.insert("Contents".into(), contents.into());
+        }
+        if let Some(page) = a.page {
+            dict.insert("P".into(), page.into());
+        }
+        if let Some(name) = a.name {
+            dict.insert("NM".into(), name.into());
+        }
+        if let Some(modified) = a.modified {
+            dict.insert("M".into(), modified.into());
+        }
+        if a.flags != 0 {
+            dict.insert("F".into(), Primitive::Integer(a.flags as i32));
+        }
+        if let Some(ap) = a.appearance {
+            dict.insert("AP".into(), ap.into());
+        }
+        if let Some(state) = a.appearance_state {
+            dict.insert("AS".into(), state.into());
+        }
+        if let Some(border) = a.border {
+            dict.insert("Border".into(), border);
+        }
+        if a.color.len() > 0 {
+            dict.insert("C".into(), Primitive::Array(a.color.into_iter().map(Primitive::Number).collect()));
+        }
+        if let Some(n) = a.struct_parent {
+            dict.insert("StructParent".into(), n.into());
+        }
+        if let Some(dest) = a.dest {
+            dict.insert("Dest".into(), dest.into());
+        }
+        if let Some(action) = a.action {
+            dict.insert("A".into(), action.into());
+        }
+        if a.open {
+            dict.insert("Open".into(), true.into());
+        }
+        if let Some(icon) = a.icon {
+            dict.insert("Name".into(), icon.into());
+        }
+        if a.quad_points.len() > 0 {
+            dict.insert("QuadPoints".into(), Primitive::Array(a.quad_points.into_iter().map(Primitive::Number).collect()));
+        }
+        if let Some(popup) = a.popup {
+            dict.insert("Popup".into(), popup.into());
+        }
+        Primitive::Dictionary(dict)
+    }
+}
+
+/// Annotation by subtype (12.5.6)
+#[derive(Debug, Clone)]
+pub enum Annot {
+    Text (Annotation),
+    Link (Annotation),
+    FreeText (Annotation),
+    Line (Annotation),
+    Square (Annotation),
+    Circle (Annotation),
+    Polygon (Annotation),
+    PolyLine (Annotation),
+    Highlight (Annotation),
+    Underline (Annotation),
+    Squiggly (Annotation),
+    StrikeOut (Annotation),
+    Stamp (Annotation),
+    Caret (Annotation),
+    Ink (Annotation),
+    Popup (Annotation),
+    FileAttachment (Annotation),
+    Sound (Annotation),
+    Movie (Annotation),
+    /// Form field widget; the field entries are in `other`.
+    Widget (Annotation),
+    Screen (Annotation),
+    PrinterMark (Annotation),
+    TrapNet (Annotation),
+    Watermark (Annotation),
+    Redact (Annotation),
+    Other (Annotation),
+}
+impl Annot {
+    pub fn annotation(&self) -> &Annotation {
+        match *self {
+            Annot::Text(ref a) | Annot::Link(ref a) | Annot::FreeText(ref a) | Annot::Line(ref a) |
+            Annot::Square(ref a) | Annot::Circle(ref a) | Annot::Polygon(ref a) | Annot::PolyLine(ref a) |
+            Annot::Highlight(ref a) | Annot::Underline(ref a) | Annot::Squiggly(ref a) | Annot::StrikeOut(ref a) |
+            Annot::Stamp(ref a) | Annot::Caret(ref a) | Annot::Ink(ref a) | Annot::Popup(ref a) |
+            Annot::FileAttachment(ref a) | Annot::Sound(ref a) | Annot::Movie(ref a) | Annot::Widget(ref a) |
+            Annot::Screen(ref a) | Annot::PrinterMark(ref a) | Annot::TrapNet(ref a) | Annot::Watermark(ref a) |
+            Annot::Redact(ref a) | Annot::Other(ref a) => a
+        }
+    }
+    pub fn into_annotation(self) -> Annotation {
+        match self {
+            Annot::Text(a) | Annot::Link(a) | Annot::FreeText(a) | Annot::Line(a) |
+            Annot::Square(a) | Annot::Circle(a) | Annot::Polygon(a) | Annot::PolyLine(a) |
+            Annot::Highlight(a) | Annot::Underline(a) | Annot::Squiggly(a) | Annot::StrikeOut(a) |
+            Annot::Stamp(a) | Annot::Caret(a) | Annot::Ink(a) | Annot::Popup(a) |
+            Annot::FileAttachment(a) | Annot::Sound(a) | Annot::Movie(a) | Annot::Widget(a) |
+            Annot::Screen(a) | Annot::PrinterMark(a) | Annot::TrapNet(a) | Annot::Watermark(a) |
+            Annot::Redact(a) | Annot::Other(a) => a
+        }
+    }
+    /// Whether this is a markup annotation (12.5.6.2) that can carry a comment.
+    pub fn is_markup(&self) -> bool {
+        match *self {
+            Annot::Link(_) | Annot::Popup(_) | Annot::Movie(_) | Annot::Widget(_) | Annot::Screen(_) |
+            Annot::PrinterMark(_) | Annot::TrapNet(_) | Annot::Watermark(_) | Annot::Other(_) => false,
+            _ => true
+        }
+    }
+}
+impl Deref for Annot {
+    type Target = Annotation;
+    fn deref(&self) -> &Annotation {
+        self.annotation()
+    }
+}
+impl From<Annotation> for Annot {
+    fn from(a: Annotation) -> Annot {
+        match a.subtype.as_str() {
+            "Text" => Annot::Text(a),
+            "Link" => Annot::Link(a),
+            "FreeText" => Annot::FreeText(a),
+            "Line" => Annot::Line(a),
+            "Square" => Annot::Square(a),
+            "Circle" => Annot::Circle(a),
+            "Polygon" => Annot::Polygon(a),
+            "PolyLine" => Annot::PolyLine(a),
+            "Highlight" => Annot::Highlight(a),
+            "Underline" => Annot::Underline(a),
+            "Squiggly" => Annot::Squiggly(a),
+            "StrikeOut" => Annot::StrikeOut(a),
+            "Stamp" => Annot::Stamp(a),
+            "Caret" => Annot::Caret(a),
+            "Ink" => Annot::Ink(a),
+            "Popup" => Annot::Popup(a),
+            "FileAttachment" => Annot::FileAttachment(a),
+            "Sound" => Annot::Sound(a),
+            "Movie" => Annot::Movie(a),
+            "Widget" => Annot::Widget(a),
+            "Screen" => Annot::Screen(a),
+            "PrinterMark" => Annot::PrinterMark(a),
+            "TrapNet" => Annot::TrapNet(a),
+            "Watermark" => Annot::Watermark(a),
+            "Redact" => Annot::Redact(a),
+            _ => Annot::Other(a)
+        }
+    }
+}
+impl Object for Annot {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        Primitive::from(self.annotation().clone()).serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        Ok(Annotation::from_primitive(p, resolve)?.into())
+    }
+}
+
+/// One entry of an appearance dictionary: a single stream or one stream per appearance state.
+/// Appearance streams are form XObjects and always indirect.
+#[derive(Debug, Clone)]
+pub enum AppearanceEntry {
+    Stream (PlainRef),
+    States (BTreeMap<String, PlainRef>),
+}
+impl AppearanceEntry {
+    /// The stream for the appearance state `state` (`/AS` of the annotation).
+    pub fn get(&self, state: Option<&str>) -> Option<PlainRef> {
+        match (self, state) {
+            (AppearanceEntry::Stream(r), _) => Some(*r),
+            (AppearanceEntry::States(states), Some(state)) => states.get(state).cloned(),
+            (AppearanceEntry::States(_), None) => None
+        }
+    }
+}
+impl Object for AppearanceEntry {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        Primitive::from(self.clone()).serialize(out)
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p {
+            Primitive::Reference(r) => match resolve.resolve(r)? {
+                Primitive::Stream(_) => Ok(AppearanceEntry::Stream(r)),
+                p => AppearanceEntry::from_primitive(p, resolve)
+            },
+            Primitive::Dictionary(dict) => {
+                let mut states = BTreeMap::new();
+                for (name, value) in dict.iter() {
+                    match *value {
+                        Primitive::Reference(r) => { states.insert(name.clone(), r); }
+                        ref p => return Err(PdfError::UnexpectedPrimitive { expected: "Reference", found: p.get_debug_name() })
+                    }
+                }
+                Ok(AppearanceEntry::States(states))
+            }
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Reference or Dictionary", found: p.get_debug_name() })
+        }
+    }
+}
+impl From<AppearanceEntry> for Primitive {
+    fn from(entry: AppearanceEntry) -> Primitive {
+        match entry {
+            AppearanceEntry::Stream(r) => r.into(),
+            AppearanceEntry::States(states) => {
+                let mut dict = Dictionary::new();
+                for (name, r) in states {
+                    dict.insert(name, r.into());
+                }
+                Primitive::Dictionary(dict)
+            }
+        }
+    }
+}
+
+/// Appearance dictionary (12.5.5)
+#[derive(Object, Debug, Clone)]
+pub struct AppearanceStreams {
+    #[pdf(key="N")]
+    pub normal: AppearanceEntry,
+
+    #[pdf(key="R")]
+    pub rollover: Option<AppearanceEntry>,
+
+    #[pdf(key="D")]
+    pub down: Option<AppearanceEntry>,
+}
+impl From<AppearanceStreams> for Primitive {
+    fn from(ap: AppearanceStreams) -> Primitive {
+        let mut dict = Dictionary::new();
+        dict.insert("N".into(), ap.normal.into());
+        if let Some(r) = ap.rollover {
+            dict.insert("R".into(), r.into());
+        }
+        if let Some(d) = ap.down {
+            dict.insert("D".into(), d.into());
+        }
+        Primitive::Dictionary(dict)
+    }
+}
+
+/// Interactive form dictionary (12.7.2)
+#[derive(Object, Debug)]
+pub struct Form {
+    /// The root fields
+    #[pdf(key="Fields")]
+    pub fields: Vec<PlainRef>,
+
+    #[pdf(key="NeedAppearances", default="false")]
+    pub need_appearances: bool,
+
+    /// 1: the document has signatures, 2: append only
+    #[pdf(key="SigFlags", default="0")]
+    pub sig_flags: u32,
+
+    /// Calculation order of fields with calculate actions
+    #[pdf(key="CO")]
+    pub calculation_order: Vec<PlainRef>,
+
+    #[pdf(key="DR")]
+    pub default_resources: Option<Primitive>,
+
+    #[pdf(key="DA")]
+    pub default_appearance: Option<PdfString>,
+
+    #[pdf(key="Q")]
+    pub quadding: Option<i32>,
+
+    #[pdf(key="XFA")]
+    pub xfa: Option<Primitive>,
+}
+impl Form {
+    /// Walks the field tree and returns the terminal fields in document order.
+    pub fn terminal_fields(&self, resolve: &impl Resolve) -> Result<Vec<Field>> {
+        let root = Field {
+            id: PlainRef { id: 0, gen: 0 },
+            name: String::new(),
+            alt_name: None,
+            typ: None,
+            flags: 0,
+            value: None,
+            default_value: None,
+            default_appearance: self.default_appearance.clone(),
+            options: None,
+            max_len: None,
+            widgets: Vec::new(),
+        };
+        let mut out = Vec::new();
+        for &id in &self.fields {
+            collect_fields(id, &root, resolve, 0, &mut out)?;
+        }
+        Ok(out)
+    }
+}
+
+/// Field types (`/FT`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FieldType {
+    /// Push buttons, check boxes and radio buttons
+    Button,
+    Text,
+    /// List and combo boxes
+    Choice,
+    Signature,
+}
+impl FieldType {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FieldType::Button => "Btn",
+            FieldType::Text => "Tx",
+            FieldType::Choice => "Ch",
+            FieldType::Signature => "Sig",
+        }
+    }
+}
+impl Object for FieldType {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        write_name(out, self.name())
+    }
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)?.to_name()?.as_str() {
+            "Btn" => Ok(FieldType::Button),
+            "Tx" => Ok(FieldType::Text),
+            "Ch" => Ok(FieldType::Choice),
+            "Sig" => Ok(FieldType::Signature),
+            name => bail!("unknown field type {}", name)
+        }
+    }
+}
+impl From<FieldType> for Primitive {
+    fn from(typ: FieldType) -> Primitive {
+        Primitive::Name(typ.name().into())
+    }
+}
+
+/// Field flags (`/Ff`), 12.7.3.1 and the sections of the field types
+#[allow(non_upper_case_globals, dead_code)]
+pub mod field_flags {
+    pub const ReadOnly: u32         = 1 << 0;
+    pub const Required: u32         = 1 << 1;
+    pub const NoExport: u32         = 1 << 2;
+    // buttons
+    pub const NoToggleToOff: u32    = 1 << 14;
+    pub const Radio: u32            = 1 << 15;
+    pub const Pushbutton: u32       = 1 << 16;
+    pub const RadiosInUnison: u32   = 1 << 25;
+    // text fields
+    pub const Multiline: u32        = 1 << 12;
+    pub const Password: u32         = 1 << 13;
+    pub const FileSelect: u32       = 1 << 20;
+    pub const DoNotSpellCheck: u32  = 1 << 22;
+    pub const DoNotScroll: u32      = 1 << 23;
+    pub const Comb: u32             = 1 << 24;
+    pub const RichText: u32         = 1 << 25;
+    // choice fields
+    pub const Combo: u32            = 1 << 17;
+    pub const Edit: u32             = 1 << 18;
+    pub const Sort: u32             = 1 << 19;
+    pub const MultiSelect: u32      = 1 << 21;
+    pub const CommitOnSelChange: u32 = 1 << 26;
+}
+
+/// Field dictionary (12.7.3) as found in the file, without inherited entries.
+/// A terminal field with a single widget is often merged with it; the annotation entries then end up in `other`.
+#[derive(Object, Debug, Clone)]
+pub struct FieldDictionary {
+    #[pdf(key="FT")]
+    pub typ: Option<FieldType>,
+
+    #[pdf(key="Parent")]
+    pub parent: Option<PlainRef>,
+
+    /// Child fields or widget annotations
+    #[pdf(key="Kids")]
+    pub kids: Vec<PlainRef>,
+
+    /// Partial field name
+    #[pdf(key="T")]
+    pub name: Option<PdfString>,
+
+    /// Name shown in the user interface
+    #[pdf(key="TU")]
+    pub alt_name: Option<PdfString>,
+
+    /// Name used when exporting
+    #[pdf(key="TM")]
+    pub mapping_name: Option<PdfString>,
+
+    /// see `field_flags`
+    #[pdf(key="Ff")]
+    pub flags: Option<u32>,
+
+    #[pdf(key="V")]
+    pub value: Option<Primitive>,
+
+    #[pdf(key="DV")]
+    pub default_value: Option<Primitive>,
+
+    #[pdf(key="DA")]
+    pub default_appearance: Option<PdfString>,
+
+    /// Choice fields: the options, check boxes and radio buttons: the export values
+    #[pdf(key="Opt")]
+    pub options: Option<Primitive>,
+
+    /// Text fields: the maximum length
+    #[pdf(key="MaxLen")]
+    pub max_len: Option<i32>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+impl FieldDictionary {
+    /// Fields without a name whose only purpose is to be a widget of their parent.
+    fn is_widget(&self) -> bool {
+        self.name.is_none() && match self.other.get("Subtype") {
+            Some(&Primitive::Name(ref subtype)) => subtype == "Widget",
+            _ => false
+        }
+    }
+}
+
+/// A terminal field with the inheritable entries taken from its ancestors and the values resolved.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub id: PlainRef,
+    /// Fully qualified name: the partial names of the field and its ancestors, joined by `.`
+    pub name: String,
+    pub alt_name: Option<String>,
+    pub typ: Option<FieldType>,
+    /// see `field_flags`
+    pub flags: u32,
+    pub value: Option<Primitive>,
+    pub default_value: Option<Primitive>,
+    pub default_appearance: Option<PdfString>,
+    pub options: Option<Primitive>,
+    pub max_len: Option<i32>,
+    /// The widget annotations; the field itself if it is merged with its widget.
+    pub widgets: Vec<PlainRef>,
+}
+impl Field {
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+    /// The value as text: the string of text fields, the selected option(s) of choice fields
+    /// and the state of buttons (`Off` if not checked). Multiple values are joined by ", ".
+    pub fn value_text(&self) -> Option<String> {
+        fn text(p: &Primitive) -> Option<String> {
+            match *p {
+                Primitive::String(ref s) => Some(s.to_text()),
+                Primitive::Name(ref n) => Some(n.clone()),
+                _ => None
+            }
+        }
+        match self.value {
+            Some(Primitive::Array(ref values)) => Some(values.iter().filter_map(text).collect::<Vec<_>>().join(", ")),
+            Some(ref p) => text(p),
+            None => None
+        }
+    }
+    /// Check boxes and radio buttons: whether a state other than `Off` is selected.
+    pub fn is_checked(&self) -> bool {
+        match self.value {
+            Some(Primitive::Name(ref state)) => state != "Off",
+            _ => false
+        }
+    }
+}
+
+fn collect_fields(id: PlainRef, parent: &Field, resolve: &impl Resolve, depth: usize, out: &mut Vec<Field>) -> Result<()> {
+    if depth > 32 {
+        bail!("form field tree too deep");
+    }
+    let dict = FieldDictionary::from_primitive(resolve.resolve(id)?, resolve)?;
+    let resolved = |p: &Option<Primitive>| -> Result<Option<Primitive>> {
+        match *p {
+            Some(ref p) => Ok(Some(p.clone().resolve(resolve)?)),
+            None => Ok(None)
+        }
+    };
+
+    let mut field = parent.clone();
+    field.id = id;
+    if let Some(ref partial) = dict.name {
+        if field.name.len() > 0 {
+            field.name.push('.');
+        }
+        field.name.push_str(&partial.to_text());
+    }
+    if let Some(ref alt) = dict.alt_name {
+        field.alt_name = Some(alt.to_text());
+    }
+    if dict.typ.is_some() {
+        field.typ = dict.typ;
+    }
+    if let Some(flags) = dict.flags {
+        field.flags = flags;
+    }
+    if dict.value.is_some() {
+        field.value = resolved(&dict.value)?;
+    }
+    if dict.default_value.is_some() {
+        field.default_value = resolved(&dict.default_value)?;
+    }
+    if dict.default_appearance.is_some() {
+        field.default_appearance = dict.default_appearance.clone();
+    }
+    if dict.options.is_some() {
+        field.options = resolved(&dict.options)?;
+    }
+    if dict.max_len.is_some() {
+        field.max_len = dict.max_len;
+    }
+
+    let mut kids = Vec::new();
+    let mut widgets = Vec::new();
+    for &kid in &dict.kids {
+        let kid_dict = FieldDictionary::from_primitive(resolve.resolve(kid)?, resolve)?;
+        if kid_dict.is_widget() {
+            widgets.push(kid);
+        } else {
+            kids.push(kid);
+        }
+    }
+    if kids.len() == 0 {
+        if widgets.len() == 0 && dict.other.get("Subtype").is_some() {
+            widgets.push(id);
+        }
+        field.widgets = widgets;
+        out.push(field);
+        return Ok(());
+    }
+    for kid in kids {
+        collect_fields(kid, &field, resolve, depth + 1, out)?;
+    }
+    Ok(())
+}
+
+/// Outline dictionary (12.3.3)
+#[derive(Object, Debug)]
+#[pdf(Type="Outlines?")]
 pub struct Outlines {
-    #[pdf(key="Count")]
-    pub count:  usize
+    /// Number of visible items
+    #[pdf(key="Count", default="0")]
+    pub count:  i32,
+
+    #[pdf(key="First")]
+    pub first:  Option<Ref<OutlineEntry>>,
+
+    #[pdf(key="Last")]
+    pub last:   Option<Ref<OutlineEntry>>,
+}
+impl Outlines {
+    /// The top level items in order.
+    pub fn children(&self, resolve: &impl Resolve) -> Result<Vec<Arc<OutlineEntry>>> {
+        siblings(self.first, resolve)
+    }
+}
+
+/// Item of the outline (bookmark)
+#[derive(Object, Debug)]
+pub struct OutlineEntry {
+    #[pdf(key="Title")]
+    pub title:  Option<PdfString>,
+
+    /// Either another item or the outline dictionary
+    #[pdf(key="Parent")]
+    pub parent: Option<PlainRef>,
+
+    #[pdf(key="Prev")]
+    pub prev:   Option<Ref<OutlineEntry>>,
+
+    #[pdf(key="Next")]
+    pub next:   Option<Ref<OutlineEntry>>,
+
+    #[pdf(key="First")]
+    pub first:  Option<Ref<OutlineEntry>>,
+
+    #[pdf(key="Last")]
+    pub last:   Option<Ref<OutlineEntry>>,
+
+    /// Positive if open: the number of visible descendants.
+    /// Negative if closed: the number of descendants that would be visible if opened.
+    #[pdf(key="Count", default="0")]
+    pub count:  i32,
+
+    #[pdf(key="Dest")]
+    pub dest:   Option<Destination>,
+
+    #[pdf(key="A")]
+    pub action: Option<Action>,
+
+    /// Structure element
+    #[pdf(key="SE")]
+    pub se:     Option<Primitive>,
+
+    /// RGB color of the title
+    #[pdf(key="C")]
+    pub color:  Vec<f32>,
+
+    /// 1: italic, 2: bold
+    #[pdf(key="F", default="0")]
+    pub flags:  u32,
+}
+impl OutlineEntry {
+    pub fn title(&self) -> String {
+        self.title.as_ref().map(|t| t.to_text()).unwrap_or_default()
+    }
+    pub fn children(&self, resolve: &impl Resolve) -> Result<Vec<Arc<OutlineEntry>>> {
+        siblings(self.first, resolve)
+    }
+    /// The destination, either from `/Dest` or a `GoTo` action.
+    pub fn destination(&self) -> Option<&Destination> {
+        match (&self.dest, &self.action) {
+            (Some(dest), _) => Some(dest),
+            (None, Some(Action::GoTo(dest))) => Some(dest),
+            _ => None
+        }
+    }
+}
+fn siblings(first: Option<Ref<OutlineEntry>>, resolve: &impl Resolve) -> Result<Vec<Arc<OutlineEntry>>> {
+    let mut items = Vec::new();
+    let mut seen = Vec::new();
+    let mut next = first;
+    while let Some(r) = next {
+        // broken files can contain loops
+        if seen.contains(&r.get_inner()) {
+            break;
+        }
+        seen.push(r.get_inner());
+        let item = resolve.get(r)?;
+        next = item.next;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// A real number, written the way the syntax has them: without exponent. Reading accepts
+/// exponent notation like `1e-5`, which some writers use. NaN is written as 0 and infinities
+/// as the largest finite values.
+#[derive(Debug, Copy, Clone, Default, PartialEq, PartialOrd)]
+pub struct SmallNumber(pub f32);
+impl fmt::Display for SmallNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let x = match self.0 {
+            x if x.is_nan() => 0.,
+            x => x.max(std::f32::MIN).min(std::f32::MAX)
+        };
+        // `Display` of `f32` never uses an exponent
+        write!(f, "{}", x)
+    }
+}
+impl Object for SmallNumber {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        write!(out, "{}", self)?;
+        Ok(())
+    }
+    fn from_primitive(p: Primitive, _: &impl Resolve) -> Result<Self> {
+        p.as_number().map(SmallNumber)
+    }
+}
+impl From<f32> for SmallNumber {
+    fn from(x: f32) -> SmallNumber {
+        SmallNumber(x)
+    }
+}
+impl From<SmallNumber> for f32 {
+    fn from(x: SmallNumber) -> f32 {
+        x.0
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     pub left:   f32,
     pub bottom: f32,
     pub right:  f32,
     pub top:    f32,
 }
+impl From<Rect> for Primitive {
+    fn from(r: Rect) -> Primitive {
+        Primitive::Array(vec![r.left.into(), r.bottom.into(), r.right.into(), r.top.into()])
+    }
+}
 impl Object for Rect {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
-        write!(out, "[{} {} {} {}]", self.left, self.top, self.right, self.bottom)?;
+        write!(out, "[{} {} {} {}]", SmallNumber(self.left), SmallNumber(self.bottom), SmallNumber(self.right), SmallNumber(self.top))?;
         Ok(())
     }
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
@@ -635,3 +2237,107 @@ pub enum StructType {
     Book,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn stream(dict: &str, data: &str) -> String {
+        format!("<< {} /Length {} >> stream\n{}\nendstream", dict, data.len(), data)
+    }
+
+    #[test]
+    fn small_number() {
+        let x = SmallNumber::from_primitive(parse(b"-2.5E-4", &NoResolve).unwrap(), &NoResolve).unwrap();
+        assert_eq!(x, SmallNumber(-0.00025));
+        assert_eq!(x.to_string(), "-0.00025");
+        assert_eq!(SmallNumber(1.5e10).to_string(), "15000000000");
+        assert_eq!(SmallNumber(std::f32::NAN).to_string(), "0");
+        let mut out = Vec::new();
+        Rect { left: 0., bottom: 1e-3, right: std::f32::INFINITY, top: 2. }.serialize(&mut out).unwrap();
+        assert!(out.starts_with(b"[0 0.001 340282350000000000000000000000000000000 2]"));
+    }
+
+    #[test]
+    fn unused_resources() {
+        let form = "/Type /XObject /Subtype /Form /BBox [0 0 10 10]";
+        let resources = format!("<< \
+            /Font << /F1 << /Type /Font /Subtype /Type3 /FontBBox [0 0 1 1] /FontMatrix [1 0 0 1 0 0] \
+                /CharProcs << /a {} >> /FirstChar 97 /LastChar 97 /Widths [1] >> \
+                /F2 << /Type /Font /Subtype /Type1 /BaseFont /Helvetica >> >> \
+            /XObject << /Fm1 {} /Fm2 {} >> \
+            /Pattern << /P1 {} >> \
+            /ExtGState << /GS1 << >> /GS2 << >> /GS3 << >> >> \
+            /ColorSpace << /CS1 /DeviceRGB /CS2 /DeviceGray /CS3 /DeviceCMYK /CS4 /DeviceRGB >> >>",
+            stream("", "1 0 d0 /GS1 gs"),
+            stream(form, "/Fm2 Do"),
+            stream(form, "/CS2 cs"),
+            stream("/PatternType 1 /PaintType 1 /TilingType 1 /BBox [0 0 10 10] /XStep 10 /YStep 10", "/CS3 cs"));
+        let resources = parse(resources.as_bytes(), &NoResolve).unwrap().to_dictionary(&NoResolve).unwrap();
+
+        let mut file = File::new().unwrap();
+        let ops = b"BT /F1 1 Tf (a) Tj ET /Pattern cs /P1 scn /Fm1 Do BI /CS /CS1 /W 1 /H 1 /BPC 8 ID abc EI";
+        let content = Content::parse_from(ops, &NoResolve).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 100., top: 100. };
+        let page = file.add_page(media_box, &content, resources).unwrap();
+
+        // an annotation whose appearance uses the page resources
+        let appearance = file.add(parse(stream(form, "/GS2 gs").as_bytes(), &NoResolve).unwrap()).get_inner();
+        let annot = format!("<< /Type /Annot /Subtype /Square /Rect [0 0 10 10] /AP << /N {} {} R >> >>", appearance.id, appearance.gen);
+        let mut page_dict = file.resolve(page).unwrap().to_dictionary(&file).unwrap();
+        page_dict.insert("Annots".into(), Primitive::Array(vec![parse(annot.as_bytes(), &NoResolve).unwrap()]));
+        file.update(page.id, Primitive::Dictionary(page_dict));
+
+        let unused = file.get_page(0).unwrap().unused_resources(&file).unwrap();
+        assert_eq!(unused.fonts, ["F2"]);
+        assert_eq!(unused.graphics_states, ["GS3"]);
+        assert_eq!(unused.color_spaces, ["CS4"]);
+        assert!(unused.xobjects.is_empty() && unused.patterns.is_empty());
+    }
+
+    #[test]
+    fn cycles() {
+        let mut file = File::new().unwrap();
+        // a name tree node and a destination dictionary that contain themselves
+        let mut cyclic = |text: &str| {
+            let promise = file.promise::<Primitive>();
+            let r: PlainRef = (&promise).into();
+            let p = parse(text.replace("SELF", &format!("{} 0 R", r.id)).as_bytes(), &NoResolve).unwrap();
+            file.fulfill(promise, p);
+            Primitive::Reference(r)
+        };
+        let tree = cyclic("<< /Kids [SELF] >>");
+        let dest = cyclic("<< /D SELF >>");
+        let tree = NameTree::<Primitive>::from_primitive(tree, &file).unwrap();
+        assert!(tree.lookup(b"a", &file).is_err());
+        assert!(Destination::from_primitive(dest, &file).is_err());
+    }
+
+    #[test]
+    fn blend_mode() {
+        let mode = |s: &[u8]| BlendMode::from_primitive(parse(s, &NoResolve).unwrap(), &NoResolve).unwrap();
+        assert_eq!(mode(b"/Multiply"), BlendMode::Multiply);
+        assert_eq!(mode(b"[/Fancy 1 /Screen]"), BlendMode::Screen);
+        assert_eq!(mode(b"/Fancy"), BlendMode::Normal);
+    }
+
+    #[test]
+    fn malformed_dates() {
+        let mut file = File::new().unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 100., top: 100. };
+        let page = file.add_page(media_box, &Content::parse_from(b"", &NoResolve).unwrap(), Dictionary::new()).unwrap();
+        let date = file.add(Primitive::String(PdfString::new(b"D:20200102".to_vec()))).get_inner();
+        let mut page_dict = file.resolve(page).unwrap().to_dictionary(&file).unwrap();
+        page_dict.insert("LastModified".into(), date.into());
+        page_dict.insert("PieceInfo".into(), parse(b"<< /A << /LastModified (D:20200002) /Private 1 >> \
+            /B << /LastModified /Today >> >>", &NoResolve).unwrap());
+        file.update(page.id, Primitive::Dictionary(page_dict));
+
+        let page = file.get_page(0).unwrap();
+        assert_eq!(page.modified_date(&file).unwrap().to_rfc3339(), "2020-01-02T00:00:00+00:00");
+        let pieces = page.piece_info.as_ref().unwrap();
+        assert!(pieces["A"].last_modified.is_none() && pieces["A"].private.is_some());
+        assert!(pieces["B"].last_modified.is_none());
+    }
+}
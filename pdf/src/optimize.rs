@@ -0,0 +1,318 @@
+//! Making documents smaller by downsampling and recompressing their images.
+
+use std::collections::{BTreeMap, HashMap};
+
+use ::image::ColorType;
+use ::image::codecs::jpeg::JpegEncoder;
+use deflate::deflate_bytes_zlib;
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, PdfStream};
+use crate::backend::Backend;
+use crate::file::File;
+use crate::content::{Content, Operation};
+use crate::image::ImageData;
+use crate::text::Matrix;
+
+/// Form XObjects can contain each other.
+const MAX_DEPTH: usize = 16;
+
+/// What `File::optimize_images` does to images.
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    /// Images with a higher resolution (in pixels per inch, at the largest size they are shown at) are downsampled …
+    pub threshold_dpi: f32,
+    /// … to this resolution.
+    pub target_dpi: f32,
+    /// JPEG quality (1 to 100) for gray and RGB images. `None` compresses them losslessly with Flate.
+    pub jpeg_quality: Option<u8>,
+    /// Images with smaller streams (in bytes) are left alone.
+    pub min_size: usize,
+}
+impl Default for ImageOptions {
+    fn default() -> ImageOptions {
+        ImageOptions {
+            threshold_dpi: 225.,
+            target_dpi: 150.,
+            jpeg_quality: Some(75),
+            min_size: 16 * 1024,
+        }
+    }
+}
+
+/// What `File::optimize_images` did.
+#[derive(Debug, Clone, Default)]
+pub struct OptimizeReport {
+    /// Number of images that were replaced
+    pub images: usize,
+    /// Size of their streams before
+    pub bytes_before: usize,
+    /// … and after
+    pub bytes_after: usize,
+}
+
+impl<B: Backend> File<B> {
+    /// Downsamples images that are shown at a higher resolution than `options.threshold_dpi`
+    /// and recompresses them with JPEG or Flate, keeping the result where it is smaller.
+    ///
+    /// Image masks, images with a color key mask and 1 bit images are left alone,
+    /// as are images that can't be decoded. The new images are updates of the file;
+    /// save it to get the smaller document.
+    pub fn optimize_images(&mut self, options: &ImageOptions) -> Result<OptimizeReport> {
+        let mut scan = Placements { file: self, sizes: HashMap::new() };
+        for (page_ref, page) in self.page_refs()?.into_iter().zip(self.pages()) {
+            let page = page?;
+            let content = match page.contents {
+                Some(ref content) => content,
+                None => continue
+            };
+            let xobjects = self.xobject_refs(self.raw_page_resources(page_ref)?)?;
+            scan.scan(&content.operations, &xobjects, Matrix::identity(), 0)?;
+        }
+        let mut sizes: Vec<_> = scan.sizes.into_iter().collect();
+        sizes.sort_by_key(|&(r, _)| r.id);
+
+        let mut report = OptimizeReport::default();
+        for (r, size) in sizes {
+            let (before, stream) = match self.downsample_image(r, size, options) {
+                Ok(Some(result)) => result,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("image {} {}: {}", r.id, r.gen, e);
+                    continue;
+                }
+            };
+            report.images += 1;
+            report.bytes_before += before;
+            report.bytes_after += stream.data.len();
+            self.update(r.id, Primitive::Stream(stream));
+        }
+        Ok(report)
+    }
+
+    /// The replacement for the image `r`, painted at most `size` inches large, and the size of the old stream.
+    fn downsample_image(&self, r: PlainRef, size: (f32, f32), options: &ImageOptions) -> Result<Option<(usize, PdfStream)>> {
+        let PdfStream { mut info, data } = self.resolve(r)?.to_stream(self)?;
+        let before = data.len();
+        if before < options.min_size || info.get("Mask").is_some() {
+            return Ok(None);
+        }
+        let image = ImageXObject::from_primitive(Primitive::Stream(PdfStream { info: info.clone(), data }), self)?;
+        if image.image_mask || image.bits_per_component == 1 {
+            return Ok(None);
+        }
+        let (width, height) = (image.width as f32, image.height as f32);
+        let dpi = (width / size.0).min(height / size.1);
+        if !dpi.is_finite() || dpi <= options.threshold_dpi {
+            return Ok(None);
+        }
+        let scale = (options.target_dpi / dpi).min(1.);
+        let new_width = ((width * scale).round() as u32).max(1);
+        let new_height = ((height * scale).round() as u32).max(1);
+
+        let decoded = image.image_data(self)?;
+        // decoders don't always produce as many samples as the image dictionary promises
+        let samples = decoded.width as usize * decoded.height as usize * decoded.components;
+        if decoded.components == 0 || decoded.data.len() != samples {
+            return Ok(None);
+        }
+        let pixels = downsample(&decoded, new_width, new_height);
+        let (data, filter) = encode(&pixels, new_width, new_height, decoded.components, options.jpeg_quality)?;
+        if data.len() >= before {
+            return Ok(None);
+        }
+
+        // the samples are now 8 bit values in the base color space, with the Decode array applied
+        if let Some(ref cs) = image.color_space {
+            if let ColorSpace::Indexed { base, .. } = ColorSpace::from_primitive(cs.clone(), self)? {
+                info.insert("ColorSpace".into(), (*base).into());
+            }
+        }
+        for key in &["Decode", "DecodeParms", "Length"] {
+            info.remove(key);
+        }
+        info.insert("Width".into(), Primitive::Integer(new_width as i32));
+        info.insert("Height".into(), Primitive::Integer(new_height as i32));
+        info.insert("BitsPerComponent".into(), Primitive::Integer(8));
+        info.insert("Filter".into(), Primitive::Name(filter.into()));
+        Ok(Some((before, PdfStream { info, data: data.into() })))
+    }
+}
+
+/// Collects the largest size each image XObject is painted at.
+struct Placements<'a, B: Backend> {
+    file: &'a File<B>,
+    /// width and height in inches
+    sizes: HashMap<PlainRef, (f32, f32)>,
+}
+impl<'a, B: Backend> Placements<'a, B> {
+    fn scan(&mut self, ops: &[Operation], xobjects: &BTreeMap<String, PlainRef>, ctm: Matrix, depth: usize) -> Result<()> {
+        let mut ctm = ctm;
+        let mut stack = Vec::new();
+        for op in ops {
+            match op.operator.as_str() {
+                "q" => stack.push(ctm),
+                "Q" => if let Some(m) = stack.pop() {
+                    ctm = m;
+                },
+                "cm" => if let Some(m) = matrix(&op.operands) {
+                    ctm = m * ctm;
+                },
+                "Do" => {
+                    let r = match op.operands.first() {
+                        Some(&Primitive::Name(ref name)) => match xobjects.get(name) {
+                            Some(&r) => r,
+                            None => continue
+                        },
+                        _ => continue
+                    };
+                    let stream = self.file.resolve(r)?.to_stream(self.file)?;
+                    let subtype = match stream.info.get("Subtype") {
+                        Some(&Primitive::Name(ref name)) => name.clone(),
+                        _ => continue
+                    };
+                    match subtype.as_str() {
+                        "Image" => {
+                            // images fill the unit square; 72 units are an inch
+                            let width = ctm.a.hypot(ctm.b) / 72.;
+                            let height = ctm.c.hypot(ctm.d) / 72.;
+                            let size = self.sizes.entry(r).or_insert((0., 0.));
+                            size.0 = size.0.max(width);
+                            size.1 = size.1.max(height);
+                        }
+                        "Form" if depth < MAX_DEPTH => {
+                            // forms without resources use those of the page
+                            let form_xobjects = match stream.info.get("Resources") {
+                                Some(res) => self.file.xobject_refs(Some(res.clone()))?,
+                                None => xobjects.clone()
+                            };
+                            let form = FormXObject::from_primitive(Primitive::Stream(stream), self.file)?;
+                            let form_ctm = match form.matrix {
+                                Some(ref m) => match matrix(&m.clone().resolve(self.file)?.to_array(self.file)?) {
+                                    Some(m) => m * ctm,
+                                    None => ctm
+                                },
+                                None => ctm
+                            };
+                            let content = Content::parse_from(form.data()?, self.file)?;
+                            self.scan(&content.operations, &form_xobjects, form_ctm, depth + 1)?;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn matrix(operands: &[Primitive]) -> Option<Matrix> {
+    let n = operands.iter().map(|p| p.as_number()).collect::<Result<Vec<f32>>>().ok()?;
+    match n.len() {
+        6 => Some(Matrix::new(n[0], n[1], n[2], n[3], n[4], n[5])),
+        _ => None
+    }
+}
+
+/// Scales `image` down to `width` × `height` pixels, averaging the pixels that fall into each new one.
+fn downsample(image: &ImageData, width: u32, height: u32) -> Vec<u8> {
+    let n = image.components;
+    let (src_width, src_height) = (image.width as usize, image.height as usize);
+    let (width, height) = (width as usize, height as usize);
+    let span = |i: usize, len: usize, src_len: usize| {
+        let start = i * src_len / len;
+        (start, ((i + 1) * src_len / len).max(start + 1).min(src_len))
+    };
+    let mut out = Vec::with_capacity(width * height * n);
+    let mut sum = vec![0u32; n];
+    for y in 0 .. height {
+        let (y0, y1) = span(y, height, src_height);
+        for x in 0 .. width {
+            let (x0, x1) = span(x, width, src_width);
+            for s in sum.iter_mut() {
+                *s = 0;
+            }
+            for row in y0 .. y1 {
+                let row = &image.data[row * src_width * n ..];
+                for pixel in row[x0 * n .. x1 * n].chunks(n) {
+                    for (s, &v) in sum.iter_mut().zip(pixel) {
+                        *s += v as u32;
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+            out.extend(sum.iter().map(|&s| ((s + count / 2) / count) as u8));
+        }
+    }
+    out
+}
+
+/// Compresses 8 bit samples, returning the data and the name of the filter.
+fn encode(data: &[u8], width: u32, height: u32, components: usize, jpeg_quality: Option<u8>) -> Result<(Vec<u8>, &'static str)> {
+    let color_type = match components {
+        1 => Some(ColorType::L8),
+        3 => Some(ColorType::Rgb8),
+        _ => None
+    };
+    if let (Some(quality), Some(color_type)) = (jpeg_quality, color_type) {
+        let mut out = Vec::new();
+        JpegEncoder::new_with_quality(&mut out, quality.max(1).min(100))
+            .encode(data, width, height, color_type)
+            .map_err(|e| PdfError::from(format!("can't encode JPEG: {}", e)))?;
+        return Ok((out, "DCTDecode"));
+    }
+    Ok((deflate_bytes_zlib(data), "FlateDecode"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::embed::ImageFormat;
+    use crate::primitive::Dictionary;
+
+    #[test]
+    fn optimize_images() {
+        let mut file = File::new().unwrap();
+        // noise doesn't compress, so that less pixels make smaller streams
+        let mut seed = 1u32;
+        let rgba: Vec<u8> = (0 .. 200 * 200 * 4).map(|i| match i % 4 {
+            3 => 255,
+            _ => {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            }
+        }).collect();
+        let small = file.add_image(&rgba, ImageFormat::Rgba { width: 200, height: 200 }).unwrap();
+        let large = file.add_image(&rgba, ImageFormat::Rgba { width: 200, height: 200 }).unwrap();
+
+        let mut xobjects = Dictionary::new();
+        xobjects.insert("Im1".into(), small.xobject.into());
+        xobjects.insert("Im2".into(), large.xobject.into());
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".into(), xobjects.into());
+        // 400 and 72 dpi
+        let content = Content::parse_from(b"q 36 0 0 36 0 0 cm /Im1 Do Q q 200 0 0 200 0 0 cm /Im2 Do Q", &NoResolve).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+        file.add_page(media_box, &content, resources).unwrap();
+
+        let options = ImageOptions { jpeg_quality: None, min_size: 0, .. ImageOptions::default() };
+        let report = file.optimize_images(&options).unwrap();
+        assert_eq!(report.images, 1);
+        assert!(report.bytes_after < report.bytes_before);
+
+        let size = |r: PlainRef| {
+            let info = file.resolve(r).unwrap().to_stream(&file).unwrap().info;
+            (info.get("Width").unwrap().as_integer().unwrap(), info.get("Height").unwrap().as_integer().unwrap())
+        };
+        // 150 dpi
+        assert_eq!(size(small.xobject), (75, 75));
+        assert_eq!(size(large.xobject), (200, 200));
+        let image = match *file.get(Ref::<XObject>::new(small.xobject)).unwrap() {
+            XObject::Image(ref image) => image.image_data(&file).unwrap(),
+            _ => panic!()
+        };
+        assert_eq!(image.data.len(), 75 * 75 * 3);
+    }
+}
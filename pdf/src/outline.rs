@@ -0,0 +1,243 @@
+//! Building document outlines (bookmarks) for writing.
+//!
+//! The outline is kept as a plain tree of `OutlineItem`s that can be changed freely.
+//! Only `OutlineBuilder::build` turns it into the doubly linked structure of 12.3.3.
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary, PdfString};
+
+/// Where an outline item leads to.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutlineTarget {
+    /// Page by index (starting at 0), resolved when building.
+    Page { index: u32, view: Dest },
+    Dest (Destination),
+    Action (Action),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutlineItem {
+    pub title: String,
+    pub target: Option<OutlineTarget>,
+    /// Whether the children are shown initially.
+    pub open: bool,
+    /// RGB color of the title
+    pub color: Option<[f32; 3]>,
+    /// 1: italic, 2: bold
+    pub flags: u32,
+    pub children: Vec<OutlineItem>,
+}
+impl OutlineItem {
+    pub fn new(title: &str, target: Option<OutlineTarget>) -> OutlineItem {
+        OutlineItem {
+            title: title.into(),
+            target,
+            open: false,
+            color: None,
+            flags: 0,
+            children: Vec::new(),
+        }
+    }
+    /// Item pointing to the top of page `index`.
+    pub fn page(title: &str, index: u32) -> OutlineItem {
+        OutlineItem::new(title, Some(OutlineTarget::Page {
+            index,
+            view: Dest::XYZ { left: None, top: None, zoom: None }
+        }))
+    }
+    /// Appends a child and returns it for further modification.
+    pub fn add(&mut self, item: OutlineItem) -> &mut OutlineItem {
+        self.children.push(item);
+        self.children.last_mut().unwrap()
+    }
+    pub fn set_page(&mut self, index: u32) {
+        self.target = Some(OutlineTarget::Page {
+            index,
+            view: Dest::XYZ { left: None, top: None, zoom: None }
+        });
+    }
+    /// Number of descendants that are visible if this item is open.
+    fn visible_descendants(&self) -> usize {
+        visible_count(&self.children)
+    }
+}
+
+fn visible_count(items: &[OutlineItem]) -> usize {
+    items.len() + items.iter()
+        .filter(|item| item.open)
+        .map(|item| item.visible_descendants())
+        .sum::<usize>()
+}
+
+/// The top level of an outline. Items can be modified through `items` or the helper methods.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutlineBuilder {
+    pub items: Vec<OutlineItem>,
+}
+impl OutlineBuilder {
+    pub fn new() -> OutlineBuilder {
+        OutlineBuilder::default()
+    }
+    /// Appends a top-level item and returns it for further modification.
+    pub fn add(&mut self, item: OutlineItem) -> &mut OutlineItem {
+        self.items.push(item);
+        self.items.last_mut().unwrap()
+    }
+    /// Follows `path` (child indices, starting at the top level).
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut OutlineItem> {
+        let (&first, rest) = path.split_first()?;
+        let mut item = self.items.get_mut(first)?;
+        for &i in rest {
+            item = item.children.get_mut(i)?;
+        }
+        Some(item)
+    }
+    /// Removes the item at `path` together with its children.
+    pub fn remove(&mut self, path: &[usize]) -> Option<OutlineItem> {
+        let (&last, parent) = path.split_last()?;
+        let siblings = match parent.len() {
+            0 => &mut self.items,
+            _ => &mut self.get_mut(parent)?.children
+        };
+        if last < siblings.len() {
+            Some(siblings.remove(last))
+        } else {
+            None
+        }
+    }
+    /// Moves the item at `path` to position `new_index` among its siblings.
+    pub fn reorder(&mut self, path: &[usize], new_index: usize) -> Result<()> {
+        let (&last, parent) = match path.split_last() {
+            Some(t) => t,
+            None => bail!("empty outline path")
+        };
+        let siblings = match parent.len() {
+            0 => &mut self.items,
+            _ => match self.get_mut(parent) {
+                Some(item) => &mut item.children,
+                None => bail!("no outline item at {:?}", parent)
+            }
+        };
+        if last >= siblings.len() || new_index >= siblings.len() {
+            bail!("outline index out of bounds");
+        }
+        let item = siblings.remove(last);
+        siblings.insert(new_index, item);
+        Ok(())
+    }
+
+    /// Creates the outline dictionary and all item dictionaries.
+    /// `pages` are the references of the pages in order, `alloc` hands out new object references.
+    /// Returns the reference of the outline dictionary and all objects to be added to the file.
+    pub fn build(&self, pages: &[PlainRef], mut alloc: impl FnMut() -> PlainRef) -> Result<(PlainRef, Vec<(PlainRef, Primitive)>)> {
+        let root = alloc();
+        let mut objects = Vec::new();
+
+        let mut dict = Dictionary::new();
+        dict.insert("Type".into(), "Outlines".into());
+        if let Some((first, last)) = build_level(&self.items, root, pages, &mut alloc, &mut objects)? {
+            dict.insert("First".into(), first.into());
+            dict.insert("Last".into(), last.into());
+            dict.insert("Count".into(), Primitive::Integer(visible_count(&self.items) as i32));
+        }
+        objects.push((root, Primitive::Dictionary(dict)));
+
+        Ok((root, objects))
+    }
+}
+
+/// Returns the first and last item of this level.
+fn build_level(items: &[OutlineItem], parent: PlainRef, pages: &[PlainRef], alloc: &mut impl FnMut() -> PlainRef, objects: &mut Vec<(PlainRef, Primitive)>) -> Result<Option<(PlainRef, PlainRef)>> {
+    if items.len() == 0 {
+        return Ok(None);
+    }
+    let refs: Vec<PlainRef> = items.iter().map(|_| alloc()).collect();
+
+    for (i, item) in items.iter().enumerate() {
+        let mut dict = Dictionary::new();
+        dict.insert("Title".into(), PdfString::from_text(&item.title).into());
+        dict.insert("Parent".into(), parent.into());
+        if i > 0 {
+            dict.insert("Prev".into(), refs[i-1].into());
+        }
+        if i + 1 < refs.len() {
+            dict.insert("Next".into(), refs[i+1].into());
+        }
+        if let Some((first, last)) = build_level(&item.children, refs[i], pages, alloc, objects)? {
+            dict.insert("First".into(), first.into());
+            dict.insert("Last".into(), last.into());
+            // negative if closed: the number of items that would be visible when opened
+            let count = item.visible_descendants() as i32;
+            dict.insert("Count".into(), Primitive::Integer(if item.open { count } else { -count }));
+        }
+        match item.target {
+            Some(OutlineTarget::Page { index, ref view }) => {
+                let page = *pages.get(index as usize).ok_or(PdfError::PageOutOfBounds {
+                    page_nr: index,
+                    max: pages.len() as u32
+                })?;
+                dict.insert("Dest".into(), Destination::Explicit { page, view: view.clone() }.into());
+            }
+            Some(OutlineTarget::Dest(ref dest)) => {
+                dict.insert("Dest".into(), dest.clone().into());
+            }
+            Some(OutlineTarget::Action(ref action)) => {
+                dict.insert("A".into(), action.clone().into());
+            }
+            None => {}
+        }
+        if let Some([r, g, b]) = item.color {
+            dict.insert("C".into(), Primitive::Array(vec![r.into(), g.into(), b.into()]));
+        }
+        if item.flags != 0 {
+            dict.insert("F".into(), Primitive::Integer(item.flags as i32));
+        }
+        objects.push((refs[i], Primitive::Dictionary(dict)));
+    }
+
+    Ok(Some((refs[0], refs[refs.len()-1])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count() {
+        let mut outline = OutlineBuilder::new();
+        {
+            let chapter = outline.add(OutlineItem::page("1", 0));
+            chapter.open = true;
+            chapter.add(OutlineItem::page("1.1", 0)).add(OutlineItem::page("1.1.1", 1));
+            chapter.add(OutlineItem::page("1.2", 1));
+        }
+        outline.add(OutlineItem::page("2", 1)).add(OutlineItem::page("2.1", 1));
+
+        let pages = [PlainRef { id: 1, gen: 0 }, PlainRef { id: 2, gen: 0 }];
+        let mut next = 10;
+        let (root, objects) = outline.build(&pages, || { next += 1; PlainRef { id: next, gen: 0 } }).unwrap();
+
+        let count = |title: &[u8]| objects.iter()
+            .filter_map(|(_, p)| match p { Primitive::Dictionary(d) => Some(d), _ => None })
+            .find(|d| d.get("Title").map(|t| t.as_string().unwrap().as_bytes() == title).unwrap_or(false))
+            .and_then(|d| d.get("Count").map(|c| c.as_integer().unwrap()));
+
+        assert_eq!(count(b"1"), Some(2));
+        assert_eq!(count(b"1.1"), Some(-1));
+        assert_eq!(count(b"1.2"), None);
+        assert_eq!(count(b"2"), Some(-1));
+
+        let (_, root_dict) = objects.iter().find(|(r, _)| *r == root).unwrap();
+        match root_dict {
+            Primitive::Dictionary(d) => assert_eq!(d["Count"].as_integer().unwrap(), 4),
+            _ => panic!()
+        }
+        assert!(outline.remove(&[0, 0]).is_some());
+        outline.reorder(&[1], 0).unwrap();
+        assert_eq!(outline.items[0].title, "2");
+    }
+}
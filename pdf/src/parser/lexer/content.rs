@@ -0,0 +1,239 @@
+//! Breaking a content stream into tokens with their position, without parsing objects,
+//! for tools like syntax highlighters, debuggers and diffs.
+
+use std::ops::Range;
+
+use crate::error::*;
+
+/// What a token is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Number,
+    /// A name including its `/`
+    Name,
+    /// A literal string including its parentheses, or a hexadecimal string including `<` and `>`
+    String,
+    /// `true`, `false` or `null`
+    Keyword,
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+    /// A comment including its `%`, without the end of the line
+    Comment,
+    /// Any other word, like `BT` or `Tj`
+    Operator,
+    /// The data of an inline image, between `ID` and `EI`
+    InlineImageData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Where the token is in the data
+    pub span: Range<usize>,
+}
+
+/// Yields the tokens of a content stream.
+///
+/// The end of inline image data is found by looking for `EI` between whitespace, as the data
+/// of an image has no length. Stops after the first error.
+pub struct ContentLexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// the last token was `ID`
+    image_data: bool,
+    failed: bool,
+}
+
+impl<'a> ContentLexer<'a> {
+    pub fn new(data: &'a [u8]) -> ContentLexer<'a> {
+        ContentLexer { data, pos: 0, image_data: false, failed: false }
+    }
+    /// The bytes of `token`.
+    pub fn text(&self, token: &Token) -> &'a [u8] {
+        &self.data[token.span.clone()]
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>> {
+        if self.image_data {
+            self.image_data = false;
+            return Ok(Some(self.inline_image_data()));
+        }
+        let data = self.data;
+        while self.pos < data.len() && is_whitespace(data[self.pos]) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let c = match data.get(start) {
+            Some(&c) => c,
+            None => return Ok(None)
+        };
+        let kind = match c {
+            b'%' => {
+                self.pos = data[start ..].iter().position(|&b| b == b'\r' || b == b'\n')
+                    .map(|n| start + n).unwrap_or(data.len());
+                TokenKind::Comment
+            }
+            b'/' => {
+                self.pos = self.word_end(start + 1);
+                TokenKind::Name
+            }
+            b'(' => {
+                self.pos = self.literal_string_end(start)?;
+                TokenKind::String
+            }
+            b'<' if data.get(start + 1) == Some(&b'<') => {
+                self.pos = start + 2;
+                TokenKind::DictStart
+            }
+            b'<' => {
+                match data[start ..].iter().position(|&b| b == b'>') {
+                    Some(n) => self.pos = start + n + 1,
+                    None => bail!("hexadecimal string at {} is not closed", start)
+                }
+                TokenKind::String
+            }
+            b'>' if data.get(start + 1) == Some(&b'>') => {
+                self.pos = start + 2;
+                TokenKind::DictEnd
+            }
+            b'[' => {
+                self.pos = start + 1;
+                TokenKind::ArrayStart
+            }
+            b']' => {
+                self.pos = start + 1;
+                TokenKind::ArrayEnd
+            }
+            c if is_delimiter(c) => bail!("unexpected {:?} at {}", c as char, start),
+            _ => {
+                self.pos = self.word_end(start);
+                let word = &data[start .. self.pos];
+                if word.iter().any(|b| b.is_ascii_digit()) && word.iter().all(|b| b"+-.0123456789".contains(b)) {
+                    TokenKind::Number
+                } else if word == b"true" || word == b"false" || word == b"null" {
+                    TokenKind::Keyword
+                } else {
+                    self.image_data = word == b"ID";
+                    TokenKind::Operator
+                }
+            }
+        };
+        Ok(Some(Token { kind, span: start .. self.pos }))
+    }
+
+    fn word_end(&self, mut pos: usize) -> usize {
+        while pos < self.data.len() && !is_whitespace(self.data[pos]) && !is_delimiter(self.data[pos]) {
+            pos += 1;
+        }
+        pos
+    }
+
+    /// The position after the `)` closing the string that starts at `start`.
+    fn literal_string_end(&self, start: usize) -> Result<usize> {
+        let mut depth = 0;
+        let mut pos = start;
+        while pos < self.data.len() {
+            match self.data[pos] {
+                b'\\' => pos += 1,
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(pos + 1);
+                    }
+                }
+                _ => {}
+            }
+            pos += 1;
+        }
+        bail!("string at {} is not closed", start)
+    }
+
+    /// The data after `ID` and the single whitespace byte following it, up to the whitespace
+    /// before `EI`, or to the end.
+    fn inline_image_data(&mut self) -> Token {
+        let start = (self.pos + 1).min(self.data.len());
+        let end = inline_image_end(self.data, start);
+        self.pos = end;
+        Token { kind: TokenKind::InlineImageData, span: start .. end }
+    }
+}
+
+/// Where the inline image data starting at `start` ends: at the whitespace before the
+/// first `EI` that is followed by whitespace, a delimiter or the end, or at the end.
+pub(crate) fn inline_image_end(data: &[u8], start: usize) -> usize {
+    let mut pos = start;
+    while pos + 2 <= data.len() {
+        if &data[pos .. pos + 2] == b"EI"
+            && pos > start && is_whitespace(data[pos - 1])
+            && data.get(pos + 2).map(|&b| is_whitespace(b) || is_delimiter(b)).unwrap_or(true)
+        {
+            return pos - 1;
+        }
+        pos += 1;
+    }
+    data.len()
+}
+
+impl<'a> Iterator for ContentLexer<'a> {
+    type Item = Result<Token>;
+    fn next(&mut self) -> Option<Result<Token>> {
+        if self.failed {
+            return None;
+        }
+        match self.next_token() {
+            Ok(token) => token.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    match b {
+        b' ' | b'\t' | b'\r' | b'\n' | b'\x0C' | 0 => true,
+        _ => false
+    }
+}
+
+fn is_delimiter(b: u8) -> bool {
+    match b {
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' => true,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use self::TokenKind::*;
+
+    #[test]
+    fn tokens() {
+        let data = b"BT /F1 12 Tf (a (b) \\) c) Tj [<41> -2.5] TJ % note\nET\n\
+            /Span <</MCID 0>> BDC EMC BI /W 2 /H 1 ID \x00EI\xff EI Q true";
+        let lexer = ContentLexer::new(data);
+        let tokens: Vec<(TokenKind, &[u8])> = ContentLexer::new(data)
+            .map(|t| t.map(|t| (t.kind, lexer.text(&t))))
+            .collect::<Result<_>>().unwrap();
+        let expected: &[(TokenKind, &[u8])] = &[
+            (Operator, b"BT"), (Name, b"/F1"), (Number, b"12"), (Operator, b"Tf"),
+            (String, b"(a (b) \\) c)"), (Operator, b"Tj"),
+            (ArrayStart, b"["), (String, b"<41>"), (Number, b"-2.5"), (ArrayEnd, b"]"), (Operator, b"TJ"),
+            (Comment, b"% note"), (Operator, b"ET"),
+            (Name, b"/Span"), (DictStart, b"<<"), (Name, b"/MCID"), (Number, b"0"), (DictEnd, b">>"),
+            (Operator, b"BDC"), (Operator, b"EMC"),
+            (Operator, b"BI"), (Name, b"/W"), (Number, b"2"), (Name, b"/H"), (Number, b"1"), (Operator, b"ID"),
+            (InlineImageData, b"\x00EI\xff"), (Operator, b"EI"), (Operator, b"Q"), (Keyword, b"true"),
+        ];
+        assert_eq!(tokens, expected);
+
+        let mut lexer = ContentLexer::new(b"(open");
+        assert!(lexer.next().unwrap().is_err());
+        assert!(lexer.next().is_none());
+    }
+}
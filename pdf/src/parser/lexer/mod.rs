@@ -10,6 +10,9 @@ use crate::error::*;
 
 mod str;
 pub use self::str::{StringLexer, HexStringLexer};
+mod content;
+pub use self::content::{ContentLexer, Token, TokenKind};
+pub(crate) use self::content::inline_image_end;
 
 
 /// `Lexer` has functionality to jump around and traverse the PDF lexemes of a string in any direction.
@@ -81,8 +84,10 @@ impl<'a> Lexer<'a> {
         }
         
         while self.buf.get(pos) == Some(&b'%') {
-            if let Some(off) = self.buf[pos+1..].iter().position(|&b| b == b'\n') {
-                pos += off+2;
+            // a comment at the end of the buffer ends it
+            match self.buf[pos+1..].iter().position(|&b| b == b'\n') {
+                Some(off) => pos += off+2,
+                None => return Err(PdfError::EOF)
             }
             
             // Move away from eventual whitespace
@@ -97,8 +102,8 @@ impl<'a> Lexer<'a> {
         //  - except << and >> which go together
         if self.is_delimiter(pos) {
             // TODO +- 1
-            if self.buf[pos] == b'<' && self.buf[pos+1] == b'<'
-                || self.buf[pos] == b'>' && self.buf[pos+1] == b'>' {
+            let c = self.buf[pos];
+            if (c == b'<' || c == b'>') && self.buf.get(pos+1) == Some(&c) {
                 pos = self.advance_pos(pos, forward)?;
 
             }
@@ -150,6 +155,7 @@ impl<'a> Lexer<'a> {
         self.pos
     }
 
+    /// The bytes in `range`, cut to the end of the buffer.
     pub fn new_substr(&self, mut range: Range<usize>) -> Substr<'a> {
         // if the range is backward, fix it
         // start is inclusive, end is exclusive. keep that in mind
@@ -158,6 +164,8 @@ impl<'a> Lexer<'a> {
             range.start = range.end + 1;
             range.end = new_end;
         }
+        range.end = range.end.min(self.buf.len());
+        range.start = range.start.min(range.end);
 
         Substr {
             slice: &self.buf[range],
@@ -166,20 +174,20 @@ impl<'a> Lexer<'a> {
 
 
     /// Just a helper function for set_pos, set_pos_from_end and offset_pos.
+    /// Positions past the end of the buffer are moved to the end.
     fn seek(&mut self, new_pos: SeekFrom) -> Substr<'a> {
-        let wanted_pos;
-        match new_pos {
-            SeekFrom::Start(offset) => wanted_pos = offset as usize,
-            SeekFrom::End(offset) => wanted_pos = self.buf.len() - offset as usize - 1,
-            SeekFrom::Current(offset) => wanted_pos = self.pos + offset as usize,
-        }
+        let wanted_pos = match new_pos {
+            SeekFrom::Start(offset) => offset as usize,
+            SeekFrom::End(offset) => self.buf.len().saturating_sub(offset as usize + 1),
+            SeekFrom::Current(offset) => self.pos.saturating_add(offset as usize),
+        }.min(self.buf.len());
 
         let range = if self.pos < wanted_pos {
             self.pos..wanted_pos
         } else {
             wanted_pos..self.pos
         };
-        self.pos = wanted_pos; // TODO restrict
+        self.pos = wanted_pos;
         self.new_substr(range)
     }
 
@@ -198,13 +206,14 @@ impl<'a> Lexer<'a> {
 
     /// Moves pos to start of next line. Returns the skipped-over substring.
     #[allow(dead_code)]
-    pub fn seek_newline(&mut self) -> Substr{
+    pub fn seek_newline(&mut self) -> Result<Substr<'a>> {
         let start = self.pos;
-        while self.buf[self.pos] != b'\n' 
-            && self.incr_pos() { }
-        self.incr_pos();
-
-        self.new_substr(start..self.pos)
+        let rest = self.buf.get(start ..).filter(|rest| !rest.is_empty()).ok_or(PdfError::EOF)?;
+        self.pos = match rest.iter().position(|&b| b == b'\n') {
+            Some(off) => start + off + 1,
+            None => self.buf.len()
+        };
+        Ok(self.new_substr(start..self.pos))
     }
 
 
@@ -215,8 +224,11 @@ impl<'a> Lexer<'a> {
         //
         let start = self.pos;
         let mut matched = 0;
+        if substr.is_empty() {
+            return Some(self.new_substr(start..start));
+        }
         loop {
-            if self.buf[self.pos] == substr[matched] {
+            if self.buf.get(self.pos)? == &substr[matched] {
                 matched += 1;
             } else {
                 matched = 0;
@@ -240,8 +252,11 @@ impl<'a> Lexer<'a> {
     pub fn seek_substr_back(&mut self, substr: &[u8]) -> Result<Substr<'a>> {
         let start = self.pos;
         let mut matched = substr.len();
+        if matched == 0 {
+            return Ok(self.new_substr(start..start));
+        }
         loop {
-            if self.buf[self.pos] == substr[matched - 1] {
+            if self.buf.get(self.pos) == Some(&substr[matched - 1]) {
                 matched -= 1;
             } else {
                 matched = substr.len();
@@ -261,31 +276,35 @@ impl<'a> Lexer<'a> {
     /// Read and return slice of at most n bytes.
     #[allow(dead_code)]
     pub fn read_n(&mut self, n: usize) -> Substr<'a> {
-        let start_pos = self.pos;
-        self.pos += n;
-        if self.pos >= self.buf.len() {
-            self.pos = self.buf.len() - 1;
-        }
-        if start_pos < self.buf.len() {
-            self.new_substr(start_pos..self.pos)
-        } else {
-            self.new_substr(0..0)
-        }
+        let start_pos = self.pos.min(self.buf.len());
+        self.pos = start_pos.saturating_add(n).min(self.buf.len());
+        self.new_substr(start_pos..self.pos)
     }
 
     /// Returns slice from current position to end.
-    pub fn get_remaining_slice(&self) -> &[u8] {
-        &self.buf[self.pos..]
+    pub fn get_remaining_slice(&self) -> &'a [u8] {
+        self.buf.get(self.pos..).unwrap_or(&[])
     }
 
-    fn incr_pos(&mut self) -> bool {
-        if self.pos >= self.buf.len() - 1 {
-            false
-        } else {
+    /// Moves past the `stream` keyword and the end of line after it, where the stream data begins.
+    /// Unlike `next`, this doesn't skip whitespace that is part of the data.
+    pub fn next_stream_keyword(&mut self) -> Result<()> {
+        while self.is_whitespace(self.pos) {
             self.pos += 1;
-            true
         }
+        if !self.get_remaining_slice().starts_with(b"stream") {
+            return Err(PdfError::UnexpectedLexeme {pos: self.pos, lexeme: self.peek()?.to_string(), expected: "stream"});
+        }
+        self.pos += 6;
+        // CR LF or LF, but some writers only use CR
+        if self.get_remaining_slice().starts_with(b"\r\n") {
+            self.pos += 2;
+        } else if self.buf.get(self.pos) == Some(&b'\n') || self.buf.get(self.pos) == Some(&b'\r') {
+            self.pos += 1;
+        }
+        Ok(())
     }
+
     fn is_whitespace(&self, pos: usize) -> bool {
         if pos >= self.buf.len() {
             false
@@ -330,11 +349,14 @@ impl<'a> Substr<'a> {
             Err(_) => false,
         }
     }
+    /// Whether this is a real number. Exponents are accepted, as some writers use them
+    /// although the syntax has none, but not `inf` or `NaN`.
     pub fn is_real_number(&self) -> bool {
-        match self.to::<f32>() {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.slice.iter().all(|&b| b"+-.0123456789eE".contains(&b)) && self.to::<f32>().is_ok()
+    }
+    /// Whether this is a number in exponent notation, like `1e-5`.
+    pub fn has_exponent(&self) -> bool {
+        self.slice.iter().any(|&b| b == b'e' || b == b'E')
     }
 
     
@@ -352,3 +374,30 @@ impl<'a> Substr<'a> {
         self.slice == other
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn end_of_buffer() {
+        for input in &[&b""[..], b"% comment", b"  "] {
+            let mut lexer = Lexer::new(input);
+            assert!(lexer.next().is_err());
+        }
+        assert_eq!(Lexer::new(b"<").next().unwrap().as_slice(), b"<");
+        assert_eq!(Lexer::new(b"<<").next().unwrap().as_slice(), b"<<");
+
+        let mut lexer = Lexer::new(b"abc");
+        assert_eq!(lexer.seek_newline().unwrap().as_slice(), b"abc");
+        assert!(lexer.seek_newline().is_err());
+        assert!(lexer.read_n(10).as_slice().is_empty());
+        assert!(lexer.seek_substr(b"x").is_none());
+
+        let mut lexer = Lexer::new(b"");
+        lexer.set_pos_from_end(0);
+        assert!(lexer.seek_substr_back(b"startxref").is_err());
+        lexer.offset_pos(5);
+        assert!(lexer.get_remaining_slice().is_empty());
+    }
+}
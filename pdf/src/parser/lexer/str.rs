@@ -60,28 +60,43 @@ impl<'a> StringLexer<'a> {
                     b'(' => Some(b'('),
                     b')' => Some(b')'),
                     b'\n' => self.next_lexeme()?, // ignore \\\n
+                    b'\r' => {
+                        // ignore \\\r and \\\r\n
+                        if self.peek_byte()? == b'\n' {
+                            self.next_byte()?;
+                        }
+                        self.next_lexeme()?
+                    }
                     b'\\' => Some(b'\\'),
 
-                    _ => {
+                    b'0' ..= b'7' => {
                         self.back()?;
-                        let _start = self.get_offset();
-                        let mut char_code: u8 = 0;
+                        let mut char_code: u32 = 0;
                         
-                        // A character code must follow. 1-3 numbers.
+                        // octal character code, 1-3 digits. overflow is ignored.
                         for _ in 0..3 {
                             let c = self.peek_byte()?;
-                            if c >= b'0' && c <= b'9' {
+                            if c >= b'0' && c <= b'7' {
                                 self.next_byte()?;
-                                char_code = char_code * 8 + (c - b'0');
+                                char_code = char_code * 8 + (c - b'0') as u32;
                             } else {
                                 break;
                             }
                         }
-                        Some(char_code)
+                        Some(char_code as u8)
                     }
+                    // the backslash is ignored for unknown escapes
+                    c => Some(c)
                 }
                 )
             },
+            // an unescaped end-of-line marker is a single \n
+            b'\r' => {
+                if self.peek_byte()? == b'\n' {
+                    self.next_byte()?;
+                }
+                Ok(Some(b'\n'))
+            }
 
             b'(' => {
                 self.nested += 1;
@@ -270,6 +285,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn escapes() {
+        let vec = b"\\0053\\101\\7\\q\\\r\nx\r\ny)";
+        let mut lexer = StringLexer::new(vec);
+        let lexemes: Vec<u8> = lexer.iter().map(Result::unwrap).collect();
+        assert_eq!(lexemes, b"\x053A\x07qx\ny".to_vec());
+    }
+
     #[test]
     fn hex_test() {
         let input = b"901FA3>";
@@ -14,6 +14,23 @@ use crate::primitive::{Primitive, Dictionary, PdfStream, PdfString};
 use crate::object::{ObjNr, GenNr, PlainRef, Resolve};
 use self::lexer::{HexStringLexer, StringLexer};
 
+/// How strictly the parser follows the specification. Objects are parsed with the options
+/// of the `Resolve` they are read through, which also collects the warnings about repairs.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    /// Repair minor violations, such as a missing `endobj` or garbage between the entries
+    /// of a dictionary, instead of failing.
+    pub lenient: bool,
+}
+impl ParseOptions {
+    pub fn strict() -> ParseOptions {
+        ParseOptions { lenient: false }
+    }
+    pub fn lenient() -> ParseOptions {
+        ParseOptions { lenient: true }
+    }
+}
+
 /// Can parse stream but only if its dictionary does not contain indirect references.
 /// Use `parse_stream` if this is insufficient.
 pub fn parse(data: &[u8], r: &impl Resolve) -> Result<Primitive> {
@@ -36,29 +53,20 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &impl Resolve) -> Result<Primitive
                 dict.insert(key, obj);
             } else if delimiter.equals(b">>") {
                 break;
+            } else if r.options().lenient {
+                r.warn(format!("skipped {:?} in a dictionary at {}", delimiter.as_str(), lexer.get_pos()));
             } else {
                 err!(PdfError::UnexpectedLexeme{ pos: lexer.get_pos(), lexeme: delimiter.to_string(), expected: "/ or >>"});
             }
         }
         // It might just be the dictionary in front of a stream.
         if lexer.peek()?.equals(b"stream") {
-            lexer.next()?;
-
-            let length = match dict.get("Length") {
-                Some(&Primitive::Integer (n)) => n,
-                Some(&Primitive::Reference (n)) => r.resolve(n)?.as_integer()?,
-                _ => err!(PdfError::MissingEntry {field: "Length".into(), typ: "<Stream>"}),
-            };
-
-            
-            let stream_substr = lexer.offset_pos(length as usize);
-
-            // Finish
-            lexer.next_expect("endstream")?;
+            lexer.next_stream_keyword()?;
+            let data = stream_data(lexer, stream_length(&dict, r), r)?;
 
             Primitive::Stream(PdfStream {
                 info: dict,
-                data: stream_substr.to_vec(),
+                data: r.stream_data(data),
             })
         } else {
             Primitive::Dictionary (dict)
@@ -90,6 +98,9 @@ pub fn parse_with_lexer(lexer: &mut Lexer, r: &impl Resolve) -> Result<Primitive
         }
     } else if first_lexeme.is_real_number() {
         // Real Number
+        if first_lexeme.has_exponent() {
+            r.warn(format!("real number {} in exponent notation", first_lexeme.as_str()));
+        }
         Primitive::Number (first_lexeme.to::<f32>()?)
     } else if first_lexeme.equals(b"/") {
         // Name
@@ -176,30 +187,20 @@ fn parse_stream_with_lexer(lexer: &mut Lexer, r: &impl Resolve) -> Result<PdfStr
                 dict.insert(key, obj);
             } else if delimiter.equals(b">>") {
                 break;
+            } else if r.options().lenient {
+                r.warn(format!("skipped {:?} in a dictionary at {}", delimiter.as_str(), lexer.get_pos()));
             } else {
                 err!(PdfError::UnexpectedLexeme{ pos: lexer.get_pos(), lexeme: delimiter.to_string(), expected: "/ or >>"});
             }
         }
         // It might just be the dictionary in front of a stream.
         if lexer.peek()?.equals(b"stream") {
-            lexer.next()?;
-
-            // Get length - look up in `resolve_fn` if necessary
-            let length = match dict.get("Length") {
-                Some(&Primitive::Reference (reference)) => r.resolve(reference)?.as_integer()?,
-                Some(&Primitive::Integer (n)) => n,
-                Some(other) => err!(PdfError::UnexpectedPrimitive {expected: "Integer or Reference", found: other.get_debug_name()}),
-                None => err!(PdfError::MissingEntry {typ: "<Dictionary>", field: "Length".into()}),
-            };
-
-            
-            let stream_substr = lexer.offset_pos(length as usize);
-            // Finish
-            lexer.next_expect("endstream")?;
+            lexer.next_stream_keyword()?;
+            let data = stream_data(lexer, stream_length(&dict, r), r)?;
 
             PdfStream {
                 info: dict,
-                data: stream_substr.to_vec(),
+                data: r.stream_data(data),
             }
         } else {
             err!(PdfError::UnexpectedPrimitive { expected: "Stream", found: "Dictionary" });
@@ -211,4 +212,101 @@ fn parse_stream_with_lexer(lexer: &mut Lexer, r: &impl Resolve) -> Result<PdfStr
     Ok(obj)
 }
 
+/// The `/Length` of a stream, `None` if it is missing, negative or can't be resolved.
+fn stream_length(dict: &Dictionary, r: &impl Resolve) -> Option<usize> {
+    let length = match dict.get("Length") {
+        Some(&Primitive::Integer (n)) => n,
+        Some(&Primitive::Reference (reference)) => r.resolve(reference).and_then(|p| p.as_integer()).ok()?,
+        _ => return None
+    };
+    if length >= 0 { Some(length as usize) } else { None }
+}
+
+/// Reads the data of a stream, from the start of the data to past `endstream`.
+///
+/// `length` is used if `endstream` follows the data it gives. Otherwise, as some writers put 0
+/// or nonsense in `/Length`, the data ends before the next `endstream`.
+fn stream_data<'a>(lexer: &mut Lexer<'a>, length: Option<usize>, r: &impl Resolve) -> Result<&'a [u8]> {
+    let rest = lexer.get_remaining_slice();
+    if let Some(length) = length {
+        if length <= rest.len() && Lexer::new(&rest[length ..]).next().map(|w| w.equals(b"endstream")).unwrap_or(false) {
+            lexer.offset_pos(length);
+            lexer.next_expect("endstream")?;
+            return Ok(&rest[.. length]);
+        }
+    }
+    let end = match rest.windows(9).position(|w| w == b"endstream") {
+        Some(end) => end,
+        None => err!(PdfError::MissingEntry {typ: "<Stream>", field: "endstream".into()})
+    };
+    // the end of line before endstream is not part of the data
+    let mut data_end = end;
+    if data_end > 0 && rest[data_end - 1] == b'\n' {
+        data_end -= 1;
+    }
+    if data_end > 0 && rest[data_end - 1] == b'\r' {
+        data_end -= 1;
+    }
+    match length {
+        Some(length) => r.warn(format!("stream /Length {} is wrong, the data has {} bytes", length, data_end)),
+        None => r.warn(format!("stream without a valid /Length has {} bytes", data_end))
+    }
+    lexer.offset_pos(end + 9);
+    Ok(&rest[.. data_end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{NoResolve, Ref, Object};
+    use std::cell::RefCell;
+    use std::sync::Arc;
 
+    #[test]
+    fn stream_length() {
+        let data = |p: Primitive| match p {
+            Primitive::Stream(s) => s.data.into_vec(),
+            p => panic!("{:?}", p)
+        };
+        let right = b"<< /Length 4 >>\nstream\r\n \x00ab\nendstream";
+        assert_eq!(data(parse(right, &NoResolve).unwrap()), b" \x00ab");
+        let zero = b"<< /Length 0 >>\nstream\nabc\nendstream";
+        assert_eq!(data(parse(zero, &NoResolve).unwrap()), b"abc");
+        let huge = b"<< /Length 99999 >> stream\nabc\r\nendstream";
+        assert_eq!(data(parse(huge, &NoResolve).unwrap()), b"abc");
+    }
+
+    struct Lenient(RefCell<Vec<String>>);
+    impl Resolve for Lenient {
+        fn resolve(&self, _: PlainRef) -> Result<Primitive> {
+            Err(PdfError::Reference)
+        }
+        fn get<T: Object>(&self, _: Ref<T>) -> Result<Arc<T>> {
+            Err(PdfError::Reference)
+        }
+        fn options(&self) -> ParseOptions {
+            ParseOptions::lenient()
+        }
+        fn warn(&self, msg: String) {
+            self.0.borrow_mut().push(msg);
+        }
+    }
+
+    #[test]
+    fn lenient() {
+        let missing_endobj = b"1 0 obj << /A 1 >>\n2 0 obj";
+        assert!(parse_indirect_object(&mut Lexer::new(missing_endobj), &NoResolve).is_err());
+        let r = Lenient(RefCell::new(Vec::new()));
+        let mut lexer = Lexer::new(missing_endobj);
+        let (id, p) = parse_indirect_object(&mut lexer, &r).unwrap();
+        assert_eq!(id.id, 1);
+        assert_eq!(p.to_dictionary(&r).unwrap()["A"].as_integer().unwrap(), 1);
+        assert!(lexer.next().unwrap().equals(b"2"));
+
+        let garbage = b"<< /A 1 ] /B 2 >>";
+        assert!(parse(garbage, &NoResolve).is_err());
+        let dict = parse(garbage, &r).unwrap().to_dictionary(&r).unwrap();
+        assert_eq!(dict.len(), 2);
+        assert_eq!(r.0.borrow().len(), 2);
+    }
+}
@@ -20,7 +20,7 @@ pub fn parse_indirect_object(lexer: &mut Lexer, r: &impl Resolve) -> Result<(Pla
 
     let obj = parse_with_lexer(lexer, r)?;
 
-    lexer.next_expect("endobj")?;
+    expect_endobj(lexer, r)?;
 
     Ok((PlainRef {id: obj_nr, gen: gen_nr}, obj))
 }
@@ -31,7 +31,23 @@ pub fn parse_indirect_stream(lexer: &mut Lexer, r: &impl Resolve) -> Result<(Pla
 
     let stm = parse_stream_with_lexer(lexer, r)?;
 
-    lexer.next_expect("endobj")?;
+    expect_endobj(lexer, r)?;
 
     Ok((PlainRef {id: obj_nr, gen: gen_nr}, stm))
 }
+
+/// Reads `endobj`. If it is missing, lenient parsing leaves the lexer behind the object.
+fn expect_endobj(lexer: &mut Lexer, r: &impl Resolve) -> Result<()> {
+    if !r.options().lenient {
+        return lexer.next_expect("endobj");
+    }
+    let pos = lexer.get_pos();
+    match lexer.next() {
+        Ok(ref word) if word.equals(b"endobj") => {}
+        _ => {
+            lexer.set_pos(pos);
+            r.warn(format!("missing endobj at {}", pos));
+        }
+    }
+    Ok(())
+}
@@ -1,15 +1,18 @@
 use crate::error::*;
 use crate::object::{PlainRef, Resolve, Object};
+use crate::backend::StreamSource;
 
 use std::collections::{btree_map, BTreeMap};
 use std::{str, fmt, io};
 use std::ops::{Index, Range};
+use std::sync::Arc;
 use chrono::{DateTime, FixedOffset};
 use std::ops::Deref;
 use std::convert::TryInto;
 use itertools::Itertools;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Primitive {
     Null,
     Integer (i32),
@@ -42,6 +45,7 @@ impl fmt::Display for Primitive {
 
 /// Primitive Dictionary type.
 #[derive(Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Dictionary {
     dict: BTreeMap<String, Primitive>
 }
@@ -61,6 +65,9 @@ impl Dictionary {
     pub fn iter(&self) -> btree_map::Iter<String, Primitive> {
         self.dict.iter()
     }
+    pub fn iter_mut(&mut self) -> btree_map::IterMut<String, Primitive> {
+        self.dict.iter_mut()
+    }
     pub fn remove(&mut self, key: &str) -> Option<Primitive> {
         let v = self.dict.remove(key);
         debug!("{} -> {:?}", key, v);
@@ -129,26 +136,128 @@ impl<'a> IntoIterator for &'a Dictionary {
         (&self.dict).into_iter()
     }
 }
+impl IntoIterator for Dictionary {
+    type Item = (String, Primitive);
+    type IntoIter = btree_map::IntoIter<String, Primitive>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.dict.into_iter()
+    }
+}
+
+/// Writes `/name`, escaping bytes that are not allowed in names as `#xx`.
+pub fn write_name<W: io::Write>(out: &mut W, name: &str) -> Result<()> {
+    out.write_all(b"/")?;
+    for &b in name.as_bytes() {
+        match b {
+            b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' | b'#' => write!(out, "#{:02X}", b)?,
+            b'!' ..= b'~' => out.write_all(&[b])?,
+            b => write!(out, "#{:02X}", b)?
+        }
+    }
+    Ok(())
+}
+
+/// The raw data of a stream. Streams read from a backend that can share its data, like
+/// `Vec<u8>` or `Arc<[u8]>`, only refer to their bytes in it until they are changed,
+/// so streams that are never used take no memory of their own.
+#[derive(Clone)]
+pub struct StreamData {
+    inner: StreamDataInner,
+}
+#[derive(Clone)]
+enum StreamDataInner {
+    Owned(Vec<u8>),
+    /// `range` of `source`, checked when the stream was parsed
+    Shared { source: Arc<dyn StreamSource>, range: Range<usize> },
+}
+impl StreamData {
+    /// Refers to `range` of `source`, which has to be within it.
+    pub(crate) fn shared(source: Arc<dyn StreamSource>, range: Range<usize>) -> StreamData {
+        StreamData { inner: StreamDataInner::Shared { source, range } }
+    }
+    /// Whether the data still refers to the backend.
+    pub fn is_shared(&self) -> bool {
+        match self.inner {
+            StreamDataInner::Shared { .. } => true,
+            StreamDataInner::Owned(_) => false
+        }
+    }
+    /// The data to change, copied out of the backend first if needed.
+    pub fn to_mut(&mut self) -> &mut Vec<u8> {
+        if let StreamDataInner::Shared { .. } = self.inner {
+            self.inner = StreamDataInner::Owned(self.to_vec());
+        }
+        match self.inner {
+            StreamDataInner::Owned(ref mut data) => data,
+            StreamDataInner::Shared { .. } => unreachable!()
+        }
+    }
+    pub fn into_vec(self) -> Vec<u8> {
+        match self.inner {
+            StreamDataInner::Owned(data) => data,
+            StreamDataInner::Shared { .. } => self.to_vec()
+        }
+    }
+}
+impl Deref for StreamData {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self.inner {
+            StreamDataInner::Owned(ref data) => data,
+            StreamDataInner::Shared { ref source, ref range } => &source.bytes()[range.clone()]
+        }
+    }
+}
+impl AsRef<[u8]> for StreamData {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+impl From<Vec<u8>> for StreamData {
+    fn from(data: Vec<u8>) -> StreamData {
+        StreamData { inner: StreamDataInner::Owned(data) }
+    }
+}
+impl fmt::Debug for StreamData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for StreamData {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StreamData {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<StreamData, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(StreamData::from)
+    }
+}
 
 /// Primitive Stream (as opposed to the higher-level `Stream`)
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PdfStream {
     pub info: Dictionary,
-    pub data: Vec<u8>,
+    pub data: StreamData,
 }
 impl Object for PdfStream {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()>  {
-        writeln!(out, "<<")?;
+        write!(out, "<<")?;
         for (k, v) in &self.info {
-            write!(out, "  {} ", k)?;
+            // always written directly, so it matches the data
+            if k == "Length" {
+                continue;
+            }
+            write_name(out, k)?;
+            write!(out, " ")?;
             v.serialize(out)?;
-            writeln!(out, "")?;
         }
-        writeln!(out, ">>")?;
-        
-        writeln!(out, "stream")?;
+        write!(out, "/Length {}>>\nstream\n", self.data.len())?;
         out.write_all(&self.data)?;
-        writeln!(out, "\nendstream");
+        write!(out, "\nendstream")?;
         Ok(())
     }
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
@@ -192,15 +301,17 @@ impl fmt::Debug for PdfString {
 }
 impl Object for PdfString {
     fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
-        write!(out, r"\")?;
+        write!(out, "(")?;
         for &b in &self.data {
             match b {
-                b'\\' | b'(' | b')' => write!(out, r"\")?,
-                c if c > b'~' => panic!("only ASCII"),
-                _ => ()
+                b'\\' | b'(' | b')' => out.write_all(&[b'\\', b])?,
+                b'\n' => out.write_all(b"\\n")?,
+                b'\r' => out.write_all(b"\\r")?,
+                b' ' ..= b'~' => out.write_all(&[b])?,
+                b => write!(out, "\\{:03o}", b)?
             }
-            write!(out, "{}", b)?;
         }
+        write!(out, ")")?;
         Ok(())
     }
     fn from_primitive(p: Primitive, _: &impl Resolve) -> Result<Self> {
@@ -210,6 +321,42 @@ impl Object for PdfString {
         }
     }
 }
+/// Text if the string is valid UTF-8, bytes otherwise.
+#[cfg(feature = "serde")]
+impl serde::Serialize for PdfString {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        match str::from_utf8(&self.data) {
+            Ok(text) => s.serialize_str(text),
+            Err(_) => s.serialize_bytes(&self.data)
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PdfString {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = PdfString;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a string or bytes")
+            }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<PdfString, E> {
+                Ok(PdfString::new(v.as_bytes().to_vec()))
+            }
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> std::result::Result<PdfString, E> {
+                Ok(PdfString::new(v.to_vec()))
+            }
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<PdfString, A::Error> {
+                let mut data = Vec::new();
+                while let Some(b) = seq.next_element()? {
+                    data.push(b);
+                }
+                Ok(PdfString::new(data))
+            }
+        }
+        d.deserialize_any(Visitor)
+    }
+}
 impl AsRef<[u8]> for PdfString {
     fn as_ref(&self) -> &[u8] {
         self.as_bytes()
@@ -234,6 +381,31 @@ impl PdfString {
     pub fn into_string(self) -> Result<String> {
         Ok(String::from_utf8(self.data)?)
     }
+    /// Decodes a text string: UTF-16BE if it starts with a byte order mark, PDFDocEncoding otherwise.
+    pub fn to_text(&self) -> String {
+        if self.data.starts_with(&[0xFE, 0xFF]) {
+            let units: Vec<u16> = self.data[2..].chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], c.get(1).cloned().unwrap_or(0)]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        } else {
+            // PDFDocEncoding agrees with Latin-1 for the printable range
+            self.data.iter().map(|&b| b as char).collect()
+        }
+    }
+    /// Encodes `text` as a PDF text string: plain bytes if it is ASCII, UTF-16BE with a
+    /// byte order mark otherwise.
+    pub fn from_text(text: &str) -> PdfString {
+        if text.is_ascii() {
+            PdfString::new(text.as_bytes().to_vec())
+        } else {
+            let mut data = vec![0xFE, 0xFF];
+            for unit in text.encode_utf16() {
+                data.extend_from_slice(&unit.to_be_bytes());
+            }
+            PdfString::new(data)
+        }
+    }
 }
 
 
@@ -256,6 +428,18 @@ impl Primitive {
             Primitive::Name (..) => "Name",
         }
     }
+    /// Follows references until a direct object is reached.
+    /// Gives up after 32 references, which are most likely a cycle.
+    pub fn resolve(self, r: &impl Resolve) -> Result<Primitive> {
+        let mut p = self;
+        for _ in 0 .. 32 {
+            match p {
+                Primitive::Reference(id) => p = r.resolve(id)?,
+                p => return Ok(p)
+            }
+        }
+        bail!("too many references in a row at {:?}", p)
+    }
     pub fn as_integer(&self) -> Result<i32> {
         match *self {
             Primitive::Integer(n) => Ok(n),
@@ -385,6 +569,11 @@ impl From<String> for Primitive {
         Primitive::Name (x)
     }
 }
+impl<'a> From<&'a str> for Primitive {
+    fn from(x: &'a str) -> Primitive {
+        Primitive::Name (x.into())
+    }
+}
 impl<'a> TryInto<f32> for &'a Primitive {
     type Error = PdfError;
     fn try_into(self) -> Result<f32> {
@@ -430,52 +619,189 @@ impl<'a> TryInto<&'a str> for &'a Primitive {
     }
 }
 
-fn parse_or<T: str::FromStr + Clone>(buffer: &str, range: Range<usize>, default: T) -> T {
-    buffer.get(range)
-        .map(|s| str::parse::<T>(s).unwrap_or(default.clone()))
-        .unwrap_or(default)
+/// A date as written in PDF strings (7.9.4): `D:YYYYMMDDHHmmSSOHH'mm'`.
+/// Everything after the year is optional.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Offset from UTC in minutes, `None` if the time zone is not given.
+    pub utc_offset: Option<i16>,
+}
+impl Date {
+    /// Parses the date syntax. The `D:` prefix and the apostrophes of the time zone are optional,
+    /// as many writers get them wrong.
+    pub fn parse(s: &[u8]) -> Result<Date> {
+        let s = if s.starts_with(b"D:") { &s[2..] } else { s };
+        let mut pos = 0;
+        // reads a field of `n` digits, or returns `None` if the date ends here
+        let field = |n: usize, pos: &mut usize| -> Option<u16> {
+            let digits = s.get(*pos .. *pos + n).filter(|d| d.iter().all(|b| b.is_ascii_digit()))?;
+            *pos += n;
+            Some(digits.iter().fold(0, |v, &b| v * 10 + (b - b'0') as u16))
+        };
+        let year = match field(4, &mut pos) {
+            Some(year) => year,
+            None => bail!("date without year: {:?}", String::from_utf8_lossy(s))
+        };
+        // month, day, hour, minute, second
+        let mut fields = [1, 1, 0, 0, 0];
+        let ranges = [(1, 12), (1, 31), (0, 23), (0, 59), (0, 59)];
+        for (value, &(min, max)) in fields.iter_mut().zip(ranges.iter()) {
+            match field(2, &mut pos) {
+                Some(v) if v >= min && v <= max => *value = v as u8,
+                Some(v) => bail!("date field {} out of range {} – {}", v, min, max),
+                None => break
+            }
+        }
+        let [month, day, hour, minute, second] = fields;
+        let mut date = Date { year, month, day, hour, minute, second, utc_offset: None };
+        let sign = match s.get(pos) {
+            Some(&b'Z') => {
+                date.utc_offset = Some(0);
+                return Ok(date);
+            }
+            Some(&b'+') => 1,
+            Some(&b'-') => -1,
+            _ => return Ok(date)
+        };
+        pos += 1;
+        let hours = field(2, &mut pos).unwrap_or(0);
+        if s.get(pos) == Some(&b'\'') {
+            pos += 1;
+        }
+        let minutes = field(2, &mut pos).unwrap_or(0);
+        if hours > 23 || minutes > 59 {
+            bail!("invalid time zone offset {}:{}", hours, minutes);
+        }
+        date.utc_offset = Some(sign * (hours * 60 + minutes) as i16);
+        Ok(date)
+    }
+    /// The date in the PDF syntax. An unknown time zone is left out.
+    pub fn to_pdf_string(&self) -> PdfString {
+        let mut s = format!("D:{:04}{:02}{:02}{:02}{:02}{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second);
+        match self.utc_offset {
+            Some(0) => s.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let minutes = offset.abs();
+                s.push_str(&format!("{}{:02}'{:02}'", sign, minutes / 60, minutes % 60));
+            }
+            None => {}
+        }
+        PdfString::new(s.into_bytes())
+    }
+    /// Converts to a chrono date. An unknown time zone is taken as UTC.
+    pub fn to_datetime(&self) -> Result<DateTime<FixedOffset>> {
+        use chrono::{NaiveDate, TimeZone};
+        let naive = NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+            .and_then(|d| d.and_hms_opt(self.hour as u32, self.minute as u32, self.second as u32));
+        let naive = match naive {
+            Some(naive) => naive,
+            None => bail!("invalid date {:?}", self)
+        };
+        let tz = FixedOffset::east(self.utc_offset.unwrap_or(0) as i32 * 60);
+        match tz.from_local_datetime(&naive).single() {
+            Some(date) => Ok(date),
+            None => bail!("invalid date {:?}", self)
+        }
+    }
 }
-
-impl Object for DateTime<FixedOffset> {
-    fn serialize<W: io::Write>(&self, _out: &mut W) -> Result<()> {
-        // TODO: smal/avg amount of work.
-        unimplemented!();
+impl<'a> From<&'a DateTime<FixedOffset>> for Date {
+    fn from(date: &'a DateTime<FixedOffset>) -> Date {
+        use chrono::{Datelike, Timelike};
+        Date {
+            year: date.year() as u16,
+            month: date.month() as u8,
+            day: date.day() as u8,
+            hour: date.hour() as u8,
+            minute: date.minute() as u8,
+            second: date.second() as u8,
+            utc_offset: Some((date.offset().local_minus_utc() / 60) as i16),
+        }
+    }
+}
+impl Object for Date {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        self.to_pdf_string().serialize(out)
     }
     fn from_primitive(p: Primitive, _: &impl Resolve) -> Result<Self> {
-        use chrono::{NaiveDateTime, NaiveDate, NaiveTime};
         match p {
-            Primitive::String (PdfString {data}) => {
-                let s = str::from_utf8(&data)?;
-                let len = s.len();
-                if len > 2 && &s[0..2] == "D:" {
+            Primitive::String (PdfString {data}) => Date::parse(&data),
+            _ => unexpected_primitive!(String, p.get_debug_name()),
+        }
+    }
+}
+impl From<Date> for Primitive {
+    fn from(date: Date) -> Primitive {
+        Primitive::String(date.to_pdf_string())
+    }
+}
+
+/// Formats `date` as a PDF date string (`D:YYYYMMDDHHmmSSOHH'mm'`).
+pub fn format_date(date: &DateTime<FixedOffset>) -> PdfString {
+    Date::from(date).to_pdf_string()
+}
+
+impl Object for DateTime<FixedOffset> {
+    fn serialize<W: io::Write>(&self, out: &mut W) -> Result<()> {
+        format_date(self).serialize(out)
+    }
+    fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
+        Date::from_primitive(p, r)?.to_datetime()
+    }
+}
 
-                    let year = match s.get(2..6) {
-                        Some(year) => {
-                            str::parse::<i32>(year)?
-                        }
-                        None => bail!("Missing obligatory year in date")
-                    };
-                    let month = parse_or(s, 6..8, 1);
-                    let day = parse_or(s, 8..10, 1);
-                    let hour = parse_or(s, 10..12, 0);
-                    let minute = parse_or(s, 12..14, 0);
-                    let second = parse_or(s, 14..16, 0);
-                    let tz_hour = parse_or(s, 16..18, 0);
-                    let tz_minute = parse_or(s, 19..21, 0);
-                    let tz = FixedOffset::east(tz_hour * 60 + tz_minute);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    Ok(DateTime::from_utc(
-                            NaiveDateTime::new(NaiveDate::from_ymd(year, month, day),
-                                               NaiveTime::from_hms(hour, minute, second)),
-                          tz
-                      ))
+    #[test]
+    fn date() {
+        let date = Date::parse(b"D:20190102030405+05'30'").unwrap();
+        assert_eq!(date.utc_offset, Some(330));
+        assert_eq!(date.to_datetime().unwrap().to_rfc3339(), "2019-01-02T03:04:05+05:30");
+        assert_eq!(date.to_pdf_string().as_bytes(), b"D:20190102030405+05'30'");
 
-                } else {
-                    bail!("Failed parsing date");
-                }
+        // sloppy time zones
+        assert_eq!(Date::parse(b"D:20190102030405-08'00").unwrap().utc_offset, Some(-480));
+        assert_eq!(Date::parse(b"20190102030405Z").unwrap().utc_offset, Some(0));
+
+        let date = Date::parse(b"D:2019").unwrap();
+        assert_eq!((date.month, date.day, date.utc_offset), (1, 1, None));
+        assert!(Date::parse(b"D:20191302").is_err());
+    }
+
+    #[test]
+    fn real_numbers() {
+        let write = |x: f32| {
+            let mut out = Vec::new();
+            Primitive::Number(x).serialize(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(write(1e-5), "0.00001");
+        assert_eq!(write(1.5e10), "15000000000");
+        assert_eq!(write(std::f32::NAN), "0");
+        assert!(!write(std::f32::INFINITY).contains("inf"));
+    }
+
+    #[test]
+    fn reference_cycle() {
+        // 1 0 R points to 2 0 R, which points back
+        struct Cycle;
+        impl Resolve for Cycle {
+            fn resolve(&self, r: PlainRef) -> Result<Primitive> {
+                Ok(Primitive::Reference(PlainRef { id: 3 - r.id, gen: 0 }))
+            }
+            fn get<T: Object>(&self, _: crate::object::Ref<T>) -> Result<Arc<T>> {
+                Err(PdfError::Reference)
             }
-            _ => unexpected_primitive!(String, p.get_debug_name()),
         }
+        assert!(Primitive::Reference(PlainRef { id: 1, gen: 0 }).resolve(&Cycle).is_err());
+        assert_eq!(Primitive::Integer(1).resolve(&Cycle).unwrap().as_integer().unwrap(), 1);
     }
 }
-
@@ -0,0 +1,201 @@
+//! Where the bytes of a document go.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary};
+use crate::backend::Backend;
+use crate::file::File;
+use crate::xref::XRefLocation;
+
+/// What an object is used for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SizeCategory {
+    Images,
+    /// Font dictionaries, descriptors, embedded font programs and CMaps
+    Fonts,
+    /// Page content streams and form XObjects
+    Content,
+    /// XMP metadata and the document information dictionary
+    Metadata,
+    /// Catalog, page tree, outlines, annotations, structure tree, object and xref streams
+    Structure,
+    Other,
+}
+
+/// Number and size of the objects of one category.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CategorySize {
+    pub objects: usize,
+    pub bytes: usize,
+}
+
+/// See `File::size_report`.
+#[derive(Debug, Clone, Default)]
+pub struct SizeReport {
+    /// Sum of all object sizes
+    pub total: usize,
+    pub categories: BTreeMap<SizeCategory, CategorySize>,
+    /// The largest objects, largest first
+    pub largest: Vec<(PlainRef, SizeCategory, usize)>,
+}
+
+impl<B: Backend> File<B> {
+    /// Sums the sizes of the objects (as given by `File::locate`) by category, and lists the `top` largest.
+    ///
+    /// Objects in object streams count with their uncompressed size, while the object streams
+    /// themselves count as structure with their compressed size, so the total exceeds the file size
+    /// for files that use them.
+    pub fn size_report(&self, top: usize) -> Result<SizeReport> {
+        let refs = self.object_refs();
+
+        // objects that are only recognized by who refers to them
+        let mut referred = HashMap::new();
+        if let Some(info) = self.info_ref() {
+            referred.insert(info.id, SizeCategory::Metadata);
+        }
+        let mut mark = |p: Option<&Primitive>, category: SizeCategory| match p {
+            Some(&Primitive::Reference(r)) => { referred.insert(r.id, category); }
+            Some(&Primitive::Array(ref parts)) => for part in parts {
+                if let Primitive::Reference(r) = *part {
+                    referred.insert(r.id, category);
+                }
+            },
+            _ => {}
+        };
+
+        // the objects are dropped as soon as they are categorized
+        let mut sizes = Vec::with_capacity(refs.len());
+        for r in refs {
+            let size = match self.locate(r) {
+                Ok((XRefLocation::Free, _)) => continue,
+                Ok((_, size)) => size,
+                Err(e) => {
+                    warn!("object {} {}: {}", r.id, r.gen, e);
+                    continue;
+                }
+            };
+            let p = match self.resolve(r) {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("object {} {}: {}", r.id, r.gen, e);
+                    sizes.push((r, SizeCategory::Other, size));
+                    continue;
+                }
+            };
+            let dict = match p {
+                Primitive::Dictionary(ref dict) => Some(dict),
+                Primitive::Stream(ref stream) => Some(&stream.info),
+                _ => None
+            };
+            if let Some(dict) = dict {
+                match type_of(dict) {
+                    Some("Page") => mark(dict.get("Contents"), SizeCategory::Content),
+                    Some("FontDescriptor") => for &key in &["FontFile", "FontFile2", "FontFile3"] {
+                        mark(dict.get(key), SizeCategory::Fonts);
+                    },
+                    Some("Font") => for &key in &["FontDescriptor", "DescendantFonts", "ToUnicode", "Widths", "Encoding"] {
+                        mark(dict.get(key), SizeCategory::Fonts);
+                    },
+                    _ => {}
+                }
+                if let Some(metadata) = dict.get("Metadata") {
+                    mark(Some(metadata), SizeCategory::Metadata);
+                }
+            }
+            sizes.push((r, category(&p), size));
+        }
+
+        let mut report = SizeReport::default();
+        for &mut (r, ref mut category, size) in sizes.iter_mut() {
+            if let Some(&referred_as) = referred.get(&r.id) {
+                *category = referred_as;
+            }
+            let entry = report.categories.entry(*category).or_insert_with(CategorySize::default);
+            entry.objects += 1;
+            entry.bytes += size;
+            report.total += size;
+        }
+        sizes.sort_by(|a, b| b.2.cmp(&a.2));
+        sizes.truncate(top);
+        report.largest = sizes;
+        Ok(report)
+    }
+}
+
+fn type_of(dict: &Dictionary) -> Option<&str> {
+    match dict.get("Type") {
+        Some(&Primitive::Name(ref name)) => Some(name.as_str()),
+        _ => None
+    }
+}
+
+/// The category of an object by its own entries.
+fn category(p: &Primitive) -> SizeCategory {
+    let dict = match *p {
+        Primitive::Dictionary(ref dict) => dict,
+        Primitive::Stream(ref stream) => &stream.info,
+        _ => return SizeCategory::Other
+    };
+    let subtype = match dict.get("Subtype") {
+        Some(&Primitive::Name(ref name)) => Some(name.as_str()),
+        _ => None
+    };
+    match (type_of(dict), subtype) {
+        (_, Some("Image")) => SizeCategory::Images,
+        (_, Some("Form")) => SizeCategory::Content,
+        (Some("Font"), _) | (Some("FontDescriptor"), _) | (Some("CMap"), _) => SizeCategory::Fonts,
+        (Some("Metadata"), _) => SizeCategory::Metadata,
+        (Some("Catalog"), _) | (Some("Pages"), _) | (Some("Page"), _) | (Some("Annot"), _) |
+        (Some("Outlines"), _) | (Some("StructTreeRoot"), _) | (Some("StructElem"), _) |
+        (Some("ObjStm"), _) | (Some("XRef"), _) => SizeCategory::Structure,
+        // annotations and outline items often lack /Type
+        _ if dict.get("Rect").is_some() || (dict.get("Title").is_some() && dict.get("Parent").is_some()) => SizeCategory::Structure,
+        _ => SizeCategory::Other
+    }
+}
+
+#[cfg(all(test, feature = "write"))]
+mod tests {
+    use super::*;
+    use crate::content::ContentBuilder;
+    use crate::image::embed::ImageFormat;
+
+    #[test]
+    fn size_report() {
+        let mut file = File::new().unwrap();
+        // noise, which doesn't compress
+        let mut state = 1u32;
+        let pixels: Vec<u8> = (0 .. 64 * 64 * 4).map(|i| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            if i % 4 == 3 { 255 } else { (state >> 16) as u8 }
+        }).collect();
+        let image = file.add_image(&pixels, ImageFormat::Rgba { width: 64, height: 64 }).unwrap();
+        let font = file.add_standard_font("Helvetica");
+        let mut resources = Dictionary::new();
+        let mut fonts = Dictionary::new();
+        fonts.insert("F1".into(), font.into());
+        resources.insert("Font".into(), fonts.into());
+        let mut xobjects = Dictionary::new();
+        xobjects.insert("Im1".into(), image.xobject.into());
+        resources.insert("XObject".into(), xobjects.into());
+        let mut content = ContentBuilder::new();
+        content.begin_text().set_font("F1", 12.).show_text("Hello").end_text();
+        image.draw(&mut content, "Im1", 0., 0., 64., 64.);
+        let media_box = Rect { left: 0., bottom: 0., right: 100., top: 100. };
+        file.add_page(media_box, &content.build(), resources).unwrap();
+
+        let report = file.size_report(2).unwrap();
+        let count = |category: SizeCategory| report.categories.get(&category).map(|c| c.objects).unwrap_or(0);
+        assert_eq!(count(SizeCategory::Images), 1);
+        assert_eq!(count(SizeCategory::Fonts), 1);
+        assert_eq!(count(SizeCategory::Content), 1);
+        assert!(count(SizeCategory::Structure) >= 3);
+        assert_eq!(report.total, report.categories.values().map(|c| c.bytes).sum::<usize>());
+
+        assert_eq!(report.largest.len(), 2);
+        assert_eq!((report.largest[0].0, report.largest[0].1), (image.xobject, SizeCategory::Images));
+        assert!(report.largest[0].2 > 64 * 64 * 3 && report.largest[0].2 >= report.largest[1].2);
+    }
+}
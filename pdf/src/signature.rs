@@ -0,0 +1,833 @@
+//! Digital signatures (12.8)
+
+use crate::error::*;
+use crate::object::*;
+use crate::primitive::{Primitive, Dictionary};
+use crate::backend::Backend;
+use crate::file::File;
+
+/// How writing treats changes that would invalidate existing signatures.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SignaturePolicy {
+    /// Write anyway.
+    Ignore,
+    /// Write, but log a warning.
+    Warn,
+    /// Fail with an error instead of writing.
+    Refuse,
+}
+impl Default for SignaturePolicy {
+    fn default() -> SignaturePolicy {
+        SignaturePolicy::Warn
+    }
+}
+
+/// Changes permitted by a certification signature (DocMDP, 12.8.2.2)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum MdpPermission {
+    NoChanges = 1,
+    /// Filling in forms, instantiating page templates and signing
+    FillForms = 2,
+    /// In addition annotation creation, deletion and modification
+    Annotate = 3,
+}
+
+fn field_signatures(field: &Dictionary, resolve: &impl Resolve, depth: usize, out: &mut Vec<Dictionary>) -> Result<()> {
+    if depth > 32 {
+        bail!("form field tree too deep");
+    }
+    let is_sig = match field.get("FT") {
+        Some(&Primitive::Name(ref ft)) => ft == "Sig",
+        _ => false
+    };
+    if is_sig {
+        if let Some(v) = field.get("V") {
+            match v.clone().resolve(resolve)? {
+                Primitive::Dictionary(dict) => out.push(dict),
+                Primitive::Null => {}
+                p => return unexpected_primitive!(Dictionary, p.get_debug_name())
+            }
+        }
+    }
+    if let Some(kids) = field.get("Kids") {
+        for kid in kids.clone().resolve(resolve)?.as_array()? {
+            let kid = kid.clone().to_dictionary(resolve)?;
+            field_signatures(&kid, resolve, depth + 1, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// The signature dictionaries of all signed signature fields of the document with catalog `catalog`.
+pub fn signatures(catalog: &Dictionary, resolve: &impl Resolve) -> Result<Vec<Dictionary>> {
+    let mut out = Vec::new();
+    let form = match catalog.get("AcroForm") {
+        Some(p) => p.clone().to_dictionary(resolve)?,
+        None => return Ok(out)
+    };
+    if let Some(fields) = form.get("Fields") {
+        for field in fields.clone().resolve(resolve)?.as_array()? {
+            let field = field.clone().to_dictionary(resolve)?;
+            field_signatures(&field, resolve, 0, &mut out)?;
+        }
+    }
+    Ok(out)
+}
+
+/// The permissions of the certification signature, if the document has one.
+pub fn doc_mdp(catalog: &Dictionary, resolve: &impl Resolve) -> Result<Option<MdpPermission>> {
+    let sig = match catalog.get("Perms") {
+        Some(perms) => match perms.clone().to_dictionary(resolve)?.remove("DocMDP") {
+            Some(sig) => sig.to_dictionary(resolve)?,
+            None => return Ok(None)
+        },
+        None => return Ok(None)
+    };
+    let references = match sig.get("Reference") {
+        Some(r) => r.clone().resolve(resolve)?,
+        None => return Ok(None)
+    };
+    for reference in references.as_array()? {
+        let reference = reference.clone().to_dictionary(resolve)?;
+        match reference.get("TransformMethod") {
+            Some(&Primitive::Name(ref m)) if m == "DocMDP" => {}
+            _ => continue
+        }
+        let p = match reference.get("TransformParams") {
+            Some(params) => match params.clone().to_dictionary(resolve)?.get("P") {
+                Some(p) => p.as_integer()?,
+                None => 2
+            },
+            None => 2
+        };
+        return Ok(Some(match p {
+            1 => MdpPermission::NoChanges,
+            3 => MdpPermission::Annotate,
+            _ => MdpPermission::FillForms,
+        }));
+    }
+    Ok(None)
+}
+
+fn dict_of(p: &Primitive) -> Option<&Dictionary> {
+    match *p {
+        Primitive::Dictionary(ref dict) => Some(dict),
+        Primitive::Stream(ref stream) => Some(&stream.info),
+        _ => None
+    }
+}
+fn name<'a>(dict: &'a Dictionary, key: &str) -> Option<&'a str> {
+    match dict.get(key) {
+        Some(&Primitive::Name(ref n)) => Some(n.as_str()),
+        _ => None
+    }
+}
+fn serialized(p: &Primitive) -> Vec<u8> {
+    let mut data = Vec::new();
+    // serializing into a Vec can't fail
+    let _ = p.serialize(&mut data);
+    data
+}
+
+/// A signature or document time stamp dictionary
+pub fn is_signature(dict: &Dictionary) -> bool {
+    match name(dict, "Type") {
+        Some("Sig") | Some("DocTimeStamp") => true,
+        _ => dict.get("ByteRange").is_some() && dict.get("Contents").is_some()
+    }
+}
+fn is_widget(dict: &Dictionary) -> bool {
+    name(dict, "Subtype") == Some("Widget")
+}
+/// Field dictionaries that are not merged with their widget have a type or inherit one.
+fn is_field(dict: &Dictionary) -> bool {
+    dict.get("FT").is_some() || (dict.get("Parent").is_some() && dict.get("T").is_some())
+}
+fn is_annotation(dict: &Dictionary) -> bool {
+    name(dict, "Type") == Some("Annot") || (dict.get("Subtype").is_some() && dict.get("Rect").is_some())
+}
+
+/// Entries that filling in a field changes
+const FIELD_VALUE_KEYS: &[&str] = &["V", "AS", "AP"];
+/// Entries of the interactive form dictionary that filling in and signing change
+const ACRO_FORM_KEYS: &[&str] = &["Fields", "NeedAppearances", "SigFlags", "DR"];
+
+/// The entries that differ between `old` and `new`.
+fn changed_keys<'a>(old: &'a Dictionary, new: &'a Dictionary) -> Vec<&'a str> {
+    let mut keys = Vec::new();
+    for (key, value) in new.iter() {
+        match old.get(key) {
+            Some(old_value) if serialized(old_value) == serialized(value) => {}
+            _ => keys.push(key.as_str())
+        }
+    }
+    keys.extend(old.iter().map(|(key, _)| key.as_str()).filter(|key| new.get(key).is_none()));
+    keys
+}
+
+/// Whether the list of annotations or fields may change from `old` to `new`:
+/// signing adds widgets and signature fields, `Annotate` adds and removes any annotation.
+fn list_change_permitted(old: &Primitive, new: &Primitive, permission: MdpPermission, resolve: &impl Resolve) -> Result<bool> {
+    let items = |p: &Primitive| -> Result<Vec<Primitive>> {
+        match p.clone().resolve(resolve)? {
+            Primitive::Array(items) => Ok(items),
+            Primitive::Null => Ok(Vec::new()),
+            p => unexpected_primitive!(Array, p.get_debug_name())
+        }
+    };
+    let old_items: Vec<Vec<u8>> = items(old)?.iter().map(serialized).collect();
+    let new_items = items(new)?;
+    let new_serialized: Vec<Vec<u8>> = new_items.iter().map(serialized).collect();
+    if permission < MdpPermission::Annotate && old_items.iter().any(|item| !new_serialized.contains(item)) {
+        return Ok(false);
+    }
+    for (item, data) in new_items.into_iter().zip(new_serialized) {
+        if old_items.contains(&data) {
+            continue;
+        }
+        let dict = item.to_dictionary(resolve)?;
+        let permitted = is_widget(&dict) || name(&dict, "FT") == Some("Sig")
+            || (permission == MdpPermission::Annotate && is_annotation(&dict));
+        if !permitted {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether the existing object `old` may be changed to `new`. Appearance streams are checked separately.
+fn change_permitted(old: &Primitive, new: &Primitive, permission: MdpPermission, resolve: &impl Resolve) -> Result<bool> {
+    let (old_dict, new_dict) = match (old, new) {
+        (&Primitive::Array(_), &Primitive::Array(_)) => return list_change_permitted(old, new, permission, resolve),
+        _ => match (dict_of(old), dict_of(new)) {
+            (Some(old_dict), Some(new_dict)) => (old_dict, new_dict),
+            _ => return Ok(false)
+        }
+    };
+    let changed = changed_keys(old_dict, new_dict);
+    let only = |keys: &[&str]| changed.iter().all(|key| keys.contains(key));
+
+    // changing a signature invalidates it
+    if is_signature(old_dict) {
+        return Ok(false);
+    }
+    if is_field(old_dict) || is_widget(old_dict) {
+        return Ok(only(FIELD_VALUE_KEYS));
+    }
+    if is_annotation(old_dict) {
+        return Ok(permission == MdpPermission::Annotate);
+    }
+    if name(old_dict, "Type") == Some("Page") {
+        return match (old_dict.get("Annots"), new_dict.get("Annots")) {
+            _ if !only(&["Annots"]) => Ok(false),
+            (old, Some(new)) => list_change_permitted(old.unwrap_or(&Primitive::Null), new, permission, resolve),
+            (_, None) => Ok(permission == MdpPermission::Annotate)
+        };
+    }
+    if old_dict.get("Fields").is_some() {
+        return match (old_dict.get("Fields"), new_dict.get("Fields")) {
+            _ if !only(ACRO_FORM_KEYS) => Ok(false),
+            (Some(old), Some(new)) => list_change_permitted(old, new, permission, resolve),
+            _ => Ok(false)
+        };
+    }
+    Ok(false)
+}
+
+/// The objects among `changes` that the certification signature with `permission` does not allow
+/// to change (DocMDP, 12.8.2.2). `original` gives the objects as they were signed, `None` for added ones.
+///
+/// Added objects are permitted; what matters is whether the objects referring to them may change.
+/// The appearance streams of fields (and with `Annotate`, of annotations) that are changed as well
+/// may change.
+pub fn forbidden_changes<'a>(
+    changes: impl IntoIterator<Item=(&'a ObjNr, &'a Primitive)>,
+    original: impl Fn(ObjNr) -> Result<Option<Primitive>>,
+    resolve: &impl Resolve,
+    permission: MdpPermission
+) -> Result<Vec<ObjNr>> {
+    let changes: Vec<(ObjNr, &Primitive)> = changes.into_iter().map(|(&id, p)| (id, p)).collect();
+    let mut forbidden = Vec::new();
+    if permission == MdpPermission::NoChanges {
+        for &(id, _) in &changes {
+            if original(id)?.is_some() {
+                forbidden.push(id);
+            }
+        }
+        return Ok(forbidden);
+    }
+
+    // appearance streams of the changed fields and annotations
+    let mut appearances = Vec::new();
+    for &(_, p) in &changes {
+        let dict = match dict_of(p) {
+            Some(dict) => dict,
+            None => continue
+        };
+        let has_appearance = is_field(dict) || is_widget(dict) || (permission == MdpPermission::Annotate && is_annotation(dict));
+        if let (true, Some(ap)) = (has_appearance, dict.get("AP")) {
+            collect_appearances(ap, resolve, 0, &mut appearances)?;
+        }
+    }
+
+    for (id, p) in changes {
+        let old = match original(id)? {
+            Some(old) => old,
+            None => continue
+        };
+        let permitted = appearances.contains(&id) || change_permitted(&old, p, permission, resolve)?;
+        if !permitted {
+            forbidden.push(id);
+        }
+    }
+    Ok(forbidden)
+}
+
+/// The objects of the appearance dictionary `ap`: the streams and the dictionaries of appearance states.
+fn collect_appearances(ap: &Primitive, resolve: &impl Resolve, depth: usize, out: &mut Vec<ObjNr>) -> Result<()> {
+    if let Primitive::Reference(r) = *ap {
+        out.push(r.id);
+    }
+    if depth < 2 {
+        if let Primitive::Dictionary(dict) = ap.clone().resolve(resolve)? {
+            for (_, p) in dict.iter() {
+                collect_appearances(p, resolve, depth + 1, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hash algorithm of a signature
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+impl DigestAlgorithm {
+    fn from_oid(oid: &[u8]) -> Option<DigestAlgorithm> {
+        match oid {
+            [0x2b, 0x0e, 0x03, 0x02, 0x1a] => Some(DigestAlgorithm::Sha1),
+            [0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, n] => match n {
+                1 => Some(DigestAlgorithm::Sha256),
+                2 => Some(DigestAlgorithm::Sha384),
+                3 => Some(DigestAlgorithm::Sha512),
+                _ => None
+            },
+            _ => None
+        }
+    }
+    #[cfg(feature = "verify")]
+    fn digest(self, parts: &[&[u8]]) -> Option<Vec<u8>> {
+        use sha2::{Digest, Sha256, Sha384, Sha512};
+        fn hash<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut d = D::new();
+            for part in parts {
+                d.update(part);
+            }
+            d.finalize().to_vec()
+        }
+        Some(match self {
+            DigestAlgorithm::Sha1 => hash::<sha1::Sha1>(parts),
+            DigestAlgorithm::Sha256 => hash::<Sha256>(parts),
+            DigestAlgorithm::Sha384 => hash::<Sha384>(parts),
+            DigestAlgorithm::Sha512 => hash::<Sha512>(parts),
+        })
+    }
+    #[cfg(not(feature = "verify"))]
+    fn digest(self, _parts: &[&[u8]]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// What `File::verify_signatures` found out about a signature.
+///
+/// Only the digest of the signed bytes is checked, not the cryptographic signature
+/// or the trust chain of the certificate.
+#[derive(Debug, Clone)]
+pub struct SignatureInfo {
+    /// `/SubFilter`, the format of `/Contents`
+    pub sub_filter: Option<String>,
+    /// Common name of the signing certificate, or `/Name` of the signature
+    pub signer: Option<String>,
+    /// `/M`
+    pub signing_time: Option<String>,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    pub digest_algorithm: Option<DigestAlgorithm>,
+    /// Whether the digest in the signature is that of the signed bytes,
+    /// `None` if the format isn't supported or the digest can't be computed.
+    pub digest_matches: Option<bool>,
+    /// Whether the signed bytes reach to the end of the file, so that no updates came after signing.
+    pub covers_whole_file: bool,
+}
+
+/// Checks the signature dictionary `sig` of the document `data`.
+pub fn verify(sig: &Dictionary, data: &[u8]) -> Result<SignatureInfo> {
+    let text = |key: &str| match sig.get(key) {
+        Some(&Primitive::String(ref s)) => Some(s.to_text()),
+        _ => None
+    };
+    let range = match sig.get("ByteRange") {
+        Some(r) => r.as_array()?.iter().map(|p| Ok(p.as_integer()? as usize)).collect::<Result<Vec<usize>>>()?,
+        None => bail!("signature without /ByteRange")
+    };
+    if range.len() < 4 || range.len() % 2 != 0 {
+        bail!("invalid /ByteRange");
+    }
+    let mut parts = Vec::with_capacity(range.len() / 2);
+    for pair in range.chunks(2) {
+        match data.get(pair[0] .. pair[0].saturating_add(pair[1])) {
+            Some(part) => parts.push(part),
+            None => bail!("/ByteRange {} {} is outside the file", pair[0], pair[1])
+        }
+    }
+    let end = range.chunks(2).map(|pair| pair[0] + pair[1]).max().unwrap_or(0);
+
+    let sub_filter = match sig.get("SubFilter") {
+        Some(&Primitive::Name(ref name)) => Some(name.clone()),
+        _ => None
+    };
+    let contents = match sig.get("Contents") {
+        Some(&Primitive::String(ref s)) => s.as_bytes(),
+        _ => bail!("signature without /Contents")
+    };
+    let cms = match sub_filter.as_ref().map(|s| s.as_str()) {
+        Some("adbe.pkcs7.detached") | Some("ETSI.CAdES.detached") | Some("adbe.pkcs7.sha1") => Some(SignedData::parse(contents)?),
+        _ => None
+    };
+
+    let (digest_algorithm, digest_matches) = match (sub_filter.as_ref().map(|s| s.as_str()), cms.as_ref()) {
+        // the encapsulated content is the SHA-1 digest of the signed bytes
+        (Some("adbe.pkcs7.sha1"), Some(cms)) => {
+            let expected = cms.content.as_ref().map(|c| c.as_slice());
+            let matches = DigestAlgorithm::Sha1.digest(&parts).map(|d| Some(d.as_slice()) == expected);
+            (Some(DigestAlgorithm::Sha1), matches)
+        }
+        (_, Some(cms)) => {
+            let matches = match (cms.algorithm, cms.message_digest.as_ref()) {
+                (Some(algorithm), Some(expected)) => algorithm.digest(&parts).map(|d| &d == expected),
+                _ => None
+            };
+            (cms.algorithm, matches)
+        }
+        _ => (None, None)
+    };
+
+    Ok(SignatureInfo {
+        signer: cms.and_then(|cms| cms.signer).or_else(|| text("Name")),
+        sub_filter,
+        signing_time: text("M"),
+        reason: text("Reason"),
+        location: text("Location"),
+        digest_algorithm,
+        digest_matches,
+        covers_whole_file: end >= data.len(),
+    })
+}
+
+impl<B: Backend> File<B> {
+    /// Checks all signatures of the document, see `SignatureInfo`.
+    pub fn verify_signatures(&self) -> Result<Vec<SignatureInfo>> {
+        let data = self.data()?;
+        signatures(&self.catalog_dict()?, self)?.iter()
+            .map(|sig| verify(sig, data))
+            .collect()
+    }
+}
+
+/// The parts of a CMS `SignedData` (RFC 5652) needed to check the digest
+struct SignedData {
+    /// The encapsulated content, if the signature is not detached
+    content: Option<Vec<u8>>,
+    algorithm: Option<DigestAlgorithm>,
+    /// The `messageDigest` signed attribute
+    message_digest: Option<Vec<u8>>,
+    signer: Option<String>,
+}
+
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+impl SignedData {
+    fn parse(data: &[u8]) -> Result<SignedData> {
+        // ContentInfo
+        let mut info = Children::new(data).expect(TAG_SEQUENCE)?.children();
+        if info.expect(TAG_OID)?.content != OID_SIGNED_DATA {
+            bail!("signature is not CMS SignedData");
+        }
+        let mut signed_data = info.expect(0xa0)?.children().expect(TAG_SEQUENCE)?.children();
+        signed_data.expect(TAG_INTEGER)?; // version
+        signed_data.expect(TAG_SET)?; // digestAlgorithms
+
+        let mut encap = signed_data.expect(TAG_SEQUENCE)?.children();
+        encap.expect(TAG_OID)?;
+        let content = match encap.next() {
+            Some(explicit) => Some(explicit?.children().expect_octets()?),
+            None => None
+        };
+
+        let mut certificates = Vec::new();
+        let mut signer_infos = None;
+        for element in signed_data {
+            let element = element?;
+            match element.tag {
+                0xa0 => certificates.extend(element.children()),
+                TAG_SET => signer_infos = Some(element),
+                _ => {}
+            }
+        }
+        let mut signer_info = match signer_infos {
+            Some(set) => set.children().expect(TAG_SEQUENCE)?.children(),
+            None => bail!("SignedData without signerInfos")
+        };
+        signer_info.expect(TAG_INTEGER)?; // version
+        let sid = signer_info.next().ok_or(PdfError::EOF)??;
+        let algorithm = DigestAlgorithm::from_oid(signer_info.expect(TAG_SEQUENCE)?.children().expect(TAG_OID)?.content);
+        let mut message_digest = None;
+        if let Some(Ok(attrs)) = signer_info.next() {
+            if attrs.tag == 0xa0 {
+                for attr in attrs.children() {
+                    let mut attr = attr?.children();
+                    if attr.expect(TAG_OID)?.content == OID_MESSAGE_DIGEST {
+                        message_digest = Some(attr.expect(TAG_SET)?.children().expect_octets()?);
+                    }
+                }
+            }
+        }
+
+        // the certificate with the serial number of the signer, else the first one
+        let serial = match sid.tag {
+            TAG_SEQUENCE => sid.children().nth(1).and_then(|p| p.ok()).map(|p| p.content),
+            _ => None
+        };
+        let mut signer = None;
+        for cert in certificates {
+            let mut tbs = cert?.children().expect(TAG_SEQUENCE)?.children();
+            let mut field = tbs.next().ok_or(PdfError::EOF)??;
+            if field.tag == 0xa0 {
+                // version
+                field = tbs.next().ok_or(PdfError::EOF)??;
+            }
+            let is_signer = serial.map(|s| s == field.content).unwrap_or(true);
+            if signer.is_none() || is_signer {
+                // signature algorithm, issuer, validity, subject
+                let subject = tbs.nth(3).ok_or(PdfError::EOF)??;
+                signer = common_name(subject)?;
+            }
+            if is_signer {
+                break;
+            }
+        }
+        Ok(SignedData { content, algorithm, message_digest, signer })
+    }
+}
+
+/// The common name in the X.501 `Name` `name`.
+fn common_name(name: Der) -> Result<Option<String>> {
+    for rdn in name.children() {
+        for attr in rdn?.children() {
+            let mut attr = attr?.children();
+            if attr.expect(TAG_OID)?.content == OID_COMMON_NAME {
+                let value = attr.next().ok_or(PdfError::EOF)??;
+                return Ok(Some(String::from_utf8_lossy(value.content).into_owned()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTETS: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+
+/// A BER encoded element (X.690), with definite or indefinite length
+#[derive(Copy, Clone)]
+struct Der<'a> {
+    tag: u8,
+    content: &'a [u8],
+}
+impl<'a> Der<'a> {
+    fn children(self) -> Children<'a> {
+        Children { data: self.content }
+    }
+}
+
+/// The elements in the content of a constructed element
+struct Children<'a> {
+    data: &'a [u8],
+}
+impl<'a> Children<'a> {
+    /// The elements in `data`; trailing zeros (like the padding of `/Contents`) are ignored.
+    fn new(data: &'a [u8]) -> Children<'a> {
+        Children { data }
+    }
+    /// The next element, which must have the tag `tag`.
+    fn expect(&mut self, tag: u8) -> Result<Der<'a>> {
+        match self.next() {
+            Some(Ok(element)) if element.tag == tag => Ok(element),
+            Some(Ok(element)) => bail!("expected ASN.1 tag {:#x}, found {:#x}", tag, element.tag),
+            Some(Err(e)) => Err(e),
+            None => Err(PdfError::EOF)
+        }
+    }
+    /// The next element, an octet string that may be split into parts.
+    fn expect_octets(&mut self) -> Result<Vec<u8>> {
+        match self.next().ok_or(PdfError::EOF)?? {
+            Der { tag: TAG_OCTETS, content } => Ok(content.to_vec()),
+            Der { tag: 0x24, content } => {
+                let mut out = Vec::new();
+                let mut parts = Children::new(content);
+                while !parts.data.is_empty() {
+                    out.extend(parts.expect_octets()?);
+                }
+                Ok(out)
+            }
+            Der { tag, .. } => bail!("expected ASN.1 octet string, found {:#x}", tag)
+        }
+    }
+    /// Length of the element at the start of `data`, and where its content is
+    fn element(data: &[u8], depth: usize) -> Result<(u8, std::ops::Range<usize>, usize)> {
+        if depth > 32 {
+            bail!("ASN.1 nested too deep");
+        }
+        let tag = *data.get(0).ok_or(PdfError::EOF)?;
+        if tag & 0x1f == 0x1f {
+            bail!("multi-byte ASN.1 tags are not supported");
+        }
+        let first = *data.get(1).ok_or(PdfError::EOF)?;
+        let (start, len) = match first {
+            0x80 => {
+                // indefinite length, up to the end-of-contents marker
+                let mut pos = 2;
+                while data.get(pos .. pos + 2).ok_or(PdfError::EOF)? != &[0, 0][..] {
+                    pos += Children::element(&data[pos ..], depth + 1)?.2;
+                }
+                return Ok((tag, 2 .. pos, pos + 2));
+            }
+            n if n < 0x80 => (2, n as usize),
+            n => {
+                let bytes = (n & 0x7f) as usize;
+                if bytes > 4 {
+                    bail!("ASN.1 length with {} bytes", bytes);
+                }
+                let len = data.get(2 .. 2 + bytes).ok_or(PdfError::EOF)?
+                    .iter().fold(0usize, |len, &b| len << 8 | b as usize);
+                (2 + bytes, len)
+            }
+        };
+        if start + len > data.len() {
+            return Err(PdfError::EOF);
+        }
+        Ok((tag, start .. start + len, start + len))
+    }
+}
+impl<'a> Iterator for Children<'a> {
+    type Item = Result<Der<'a>>;
+    fn next(&mut self) -> Option<Result<Der<'a>>> {
+        // end-of-contents markers and padding end the list
+        if self.data.is_empty() || self.data[0] == 0 {
+            return None;
+        }
+        match Children::element(self.data, 0) {
+            Ok((tag, content, len)) => {
+                let element = Der { tag, content: &self.data[content] };
+                self.data = &self.data[len ..];
+                Some(Ok(element))
+            }
+            Err(e) => {
+                self.data = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::{PdfString, PdfStream};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    struct Objects(BTreeMap<ObjNr, Primitive>);
+    impl Resolve for Objects {
+        fn resolve(&self, r: PlainRef) -> Result<Primitive> {
+            self.0.get(&r.id).cloned().ok_or(PdfError::NullRef { obj_nr: r.id })
+        }
+        fn get<T: Object>(&self, _: Ref<T>) -> Result<Arc<T>> {
+            Err(PdfError::Reference)
+        }
+    }
+    fn r(id: ObjNr) -> Primitive {
+        Primitive::Reference(PlainRef { id, gen: 0 })
+    }
+    fn dict(entries: Vec<(&str, Primitive)>) -> Primitive {
+        let mut dict = Dictionary::new();
+        for (key, value) in entries {
+            dict.insert(key.into(), value);
+        }
+        Primitive::Dictionary(dict)
+    }
+    fn rect() -> Primitive {
+        Primitive::Array(vec![Primitive::Integer(0), Primitive::Integer(0), Primitive::Integer(10), Primitive::Integer(10)])
+    }
+    fn text(s: &str) -> Primitive {
+        Primitive::String(PdfString::new(s.as_bytes().to_vec()))
+    }
+    fn page(annots: Vec<Primitive>) -> Primitive {
+        dict(vec![("Type", "Page".into()), ("Annots", Primitive::Array(annots))])
+    }
+    fn field(value: &str, rect: Primitive) -> Primitive {
+        dict(vec![
+            ("Type", "Annot".into()), ("Subtype", "Widget".into()), ("FT", "Tx".into()), ("Rect", rect),
+            ("V", text(value)), ("AP", dict(vec![("N", r(3))]))
+        ])
+    }
+    fn appearance(data: &[u8]) -> Primitive {
+        let info = match dict(vec![("Subtype", "Form".into())]) {
+            Primitive::Dictionary(info) => info,
+            _ => unreachable!()
+        };
+        Primitive::Stream(PdfStream { info, data: data.to_vec().into() })
+    }
+    fn note(contents: &str) -> Primitive {
+        dict(vec![("Type", "Annot".into()), ("Subtype", "Text".into()), ("Rect", rect()), ("Contents", text(contents))])
+    }
+
+    /// Page 1 with the text field 2 (appearance 3) and the note 4, the form 5, the catalog 6
+    /// and the signature 9
+    fn signed_document() -> Objects {
+        let mut objects = BTreeMap::new();
+        objects.insert(1, page(vec![r(2), r(4)]));
+        objects.insert(2, field("old", rect()));
+        objects.insert(3, appearance(b"old"));
+        objects.insert(4, note("old"));
+        objects.insert(5, dict(vec![("Fields", Primitive::Array(vec![r(2)]))]));
+        objects.insert(6, dict(vec![("Type", "Catalog".into())]));
+        objects.insert(9, dict(vec![("Type", "Sig".into()), ("Reason", text("old"))]));
+        Objects(objects)
+    }
+    /// The objects of `changes` that `permission` forbids changing
+    fn forbidden(changes: Vec<(ObjNr, Primitive)>, permission: MdpPermission) -> Vec<ObjNr> {
+        let original = signed_document();
+        let mut current = signed_document();
+        current.0.extend(changes.iter().cloned());
+        let changes: BTreeMap<ObjNr, Primitive> = changes.into_iter().collect();
+        forbidden_changes(&changes, |id| Ok(original.0.get(&id).cloned()), &current, permission).unwrap()
+    }
+
+    #[test]
+    fn doc_mdp_permissions() {
+        use super::MdpPermission::*;
+
+        // filling in the field, with a new appearance
+        let fill = || vec![(2, field("new", rect())), (3, appearance(b"new"))];
+        assert_eq!(forbidden(fill(), NoChanges), [2, 3]);
+        assert!(forbidden(fill(), FillForms).is_empty());
+        assert!(forbidden(fill(), Annotate).is_empty());
+        // the appearance alone
+        assert_eq!(forbidden(vec![(3, appearance(b"new"))], Annotate), [3]);
+        // moving the field
+        let moved = Primitive::Array(vec![Primitive::Integer(5), Primitive::Integer(5), Primitive::Integer(10), Primitive::Integer(10)]);
+        assert_eq!(forbidden(vec![(2, field("old", moved))], Annotate), [2]);
+
+        // signing: a new signature field with its widget on the page
+        let sign = || vec![
+            (1, page(vec![r(2), r(4), r(7)])),
+            (5, dict(vec![("Fields", Primitive::Array(vec![r(2), r(7)])), ("SigFlags", Primitive::Integer(3))])),
+            (7, dict(vec![("Type", "Annot".into()), ("Subtype", "Widget".into()), ("FT", "Sig".into()), ("Rect", rect()), ("V", r(8))])),
+            (8, dict(vec![("Type", "Sig".into())])),
+        ];
+        assert_eq!(forbidden(sign(), NoChanges), [1, 5]);
+        assert!(forbidden(sign(), FillForms).is_empty());
+
+        // adding, changing and removing annotations
+        let add = || vec![(1, page(vec![r(2), r(4), r(7)])), (7, note("new"))];
+        assert_eq!(forbidden(add(), FillForms), [1]);
+        assert!(forbidden(add(), Annotate).is_empty());
+        assert_eq!(forbidden(vec![(4, note("new"))], FillForms), [4]);
+        assert!(forbidden(vec![(4, note("new"))], Annotate).is_empty());
+        assert_eq!(forbidden(vec![(1, page(vec![r(2)]))], FillForms), [1]);
+        assert!(forbidden(vec![(1, page(vec![r(2)]))], Annotate).is_empty());
+
+        // anything else
+        let other = || vec![(6, dict(vec![("Type", "Catalog".into()), ("Lang", text("en"))]))];
+        assert_eq!(forbidden(other(), FillForms), [6]);
+        assert_eq!(forbidden(other(), Annotate), [6]);
+        // changing a signature invalidates it
+        assert_eq!(forbidden(vec![(9, dict(vec![("Type", "Sig".into()), ("Reason", text("new"))]))], Annotate), [9]);
+    }
+
+    #[cfg(feature = "verify")]
+    fn tlv(tag: u8, parts: &[Vec<u8>]) -> Vec<u8> {
+        let content = parts.concat();
+        let mut out = vec![tag];
+        if content.len() < 0x80 {
+            out.push(content.len() as u8);
+        } else {
+            out.extend_from_slice(&[0x82, (content.len() >> 8) as u8, content.len() as u8]);
+        }
+        out.extend(content);
+        out
+    }
+
+    /// A detached SignedData whose signer has the common name `name` and signs `digest`
+    #[cfg(feature = "verify")]
+    fn signed_data(digest: &[u8], name: &str) -> Vec<u8> {
+        let sha256 = tlv(TAG_SEQUENCE, &[tlv(TAG_OID, &[vec![0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01]])]);
+        let subject = tlv(TAG_SEQUENCE, &[tlv(TAG_SET, &[tlv(TAG_SEQUENCE, &[
+            tlv(TAG_OID, &[OID_COMMON_NAME.to_vec()]), tlv(0x0c, &[name.as_bytes().to_vec()])
+        ])])]);
+        let serial = tlv(TAG_INTEGER, &[vec![7]]);
+        let cert = tlv(TAG_SEQUENCE, &[tlv(TAG_SEQUENCE, &[
+            serial.clone(), sha256.clone(), subject.clone(), tlv(TAG_SEQUENCE, &[]), subject.clone()
+        ])]);
+        let attrs = tlv(0xa0, &[tlv(TAG_SEQUENCE, &[
+            tlv(TAG_OID, &[OID_MESSAGE_DIGEST.to_vec()]), tlv(TAG_SET, &[tlv(TAG_OCTETS, &[digest.to_vec()])])
+        ])]);
+        let signer_info = tlv(TAG_SEQUENCE, &[
+            tlv(TAG_INTEGER, &[vec![1]]), tlv(TAG_SEQUENCE, &[subject.clone(), serial]),
+            sha256.clone(), attrs, sha256.clone(), tlv(TAG_OCTETS, &[vec![0; 8]])
+        ]);
+        let signed_data = tlv(TAG_SEQUENCE, &[
+            tlv(TAG_INTEGER, &[vec![1]]), tlv(TAG_SET, &[sha256]),
+            tlv(TAG_SEQUENCE, &[tlv(TAG_OID, &[vec![0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01]])]),
+            tlv(0xa0, &[cert]), tlv(TAG_SET, &[signer_info])
+        ]);
+        let mut out = tlv(TAG_SEQUENCE, &[tlv(TAG_OID, &[OID_SIGNED_DATA.to_vec()]), tlv(0xa0, &[signed_data])]);
+        // padding, as in a reserved /Contents
+        out.extend_from_slice(&[0; 16]);
+        out
+    }
+
+    #[test]
+    #[cfg(feature = "verify")]
+    fn detached_digest() {
+        let data = b"%PDF-1.7 signed part <...> signed part too";
+        let parts: &[&[u8]] = &[&data[.. 21], &data[26 ..]];
+        let digest = DigestAlgorithm::Sha256.digest(parts).unwrap();
+
+        let mut sig = Dictionary::new();
+        sig.insert("SubFilter".into(), "adbe.pkcs7.detached".into());
+        sig.insert("ByteRange".into(), Primitive::Array(vec![
+            Primitive::Integer(0), Primitive::Integer(21), Primitive::Integer(26), Primitive::Integer(data.len() as i32 - 26)
+        ]));
+        sig.insert("Contents".into(), Primitive::String(PdfString::new(signed_data(&digest, "Jane Doe"))));
+
+        let info = verify(&sig, data).unwrap();
+        assert_eq!(info.digest_algorithm, Some(DigestAlgorithm::Sha256));
+        assert_eq!(info.digest_matches, Some(true));
+        assert_eq!(info.signer.as_ref().map(|s| s.as_str()), Some("Jane Doe"));
+        assert!(info.covers_whole_file);
+
+        let mut changed = data.to_vec();
+        changed[10] = b'S';
+        assert_eq!(verify(&sig, &changed).unwrap().digest_matches, Some(false));
+    }
+}
@@ -0,0 +1,713 @@
+//! Text state (9.3) and text positioning, shared by everything that interprets text operators.
+
+use std::collections::HashSet;
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::backend::Backend;
+use crate::file::File;
+use crate::content::{Op, TextItem};
+use crate::font::Font;
+use crate::encoding::Decoder;
+use crate::primitive::{Dictionary, PdfString, Primitive};
+use crate::object::{Page, PlainRef, Rect, Resolve, Resources};
+
+#[cfg(feature = "extract")]
+mod extract;
+#[cfg(feature = "extract")]
+pub use self::extract::*;
+
+/// Affine transformation `[a b c d e f]` (8.3.3), applied to row vectors: `[x y 1] × M`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Matrix {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+impl Matrix {
+    pub fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Matrix {
+        Matrix { a, b, c, d, e, f }
+    }
+    pub fn identity() -> Matrix {
+        Matrix::new(1., 0., 0., 1., 0., 0.)
+    }
+    pub fn translate(tx: f32, ty: f32) -> Matrix {
+        Matrix::new(1., 0., 0., 1., tx, ty)
+    }
+    pub fn scale(sx: f32, sy: f32) -> Matrix {
+        Matrix::new(sx, 0., 0., sy, 0., 0.)
+    }
+    pub fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+}
+impl Default for Matrix {
+    fn default() -> Matrix {
+        Matrix::identity()
+    }
+}
+/// `m1 * m2` applies `m1` first, like `m1 × m2` in the PDF reference.
+impl Mul for Matrix {
+    type Output = Matrix;
+    fn mul(self, m: Matrix) -> Matrix {
+        Matrix {
+            a: self.a * m.a + self.b * m.c,
+            b: self.a * m.b + self.b * m.d,
+            c: self.c * m.a + self.d * m.c,
+            d: self.c * m.b + self.d * m.d,
+            e: self.e * m.a + self.f * m.c + m.e,
+            f: self.e * m.b + self.f * m.d + m.f,
+        }
+    }
+}
+
+/// Text state parameters and the text and line matrices.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextState {
+    pub text_matrix: Matrix,
+    pub line_matrix: Matrix,
+    /// `Tc`
+    pub char_space: f32,
+    /// `Tw`
+    pub word_space: f32,
+    /// `Tz`, as a factor (`Tz 50` gives 0.5)
+    pub horiz_scale: f32,
+    /// `TL`
+    pub leading: f32,
+    /// `Tf`
+    pub font_size: f32,
+    /// `Ts`
+    pub rise: f32,
+    /// `Tr`
+    pub render_mode: i32,
+}
+impl TextState {
+    pub fn new() -> TextState {
+        TextState {
+            text_matrix: Matrix::identity(),
+            line_matrix: Matrix::identity(),
+            char_space: 0.,
+            word_space: 0.,
+            horiz_scale: 1.,
+            leading: 0.,
+            font_size: 0.,
+            rise: 0.,
+            render_mode: 0,
+        }
+    }
+    /// `BT`: resets the matrices. The parameters are part of the graphics state and stay.
+    pub fn begin_text(&mut self) {
+        self.text_matrix = Matrix::identity();
+        self.line_matrix = Matrix::identity();
+    }
+    /// `Tm`
+    pub fn set_matrix(&mut self, m: Matrix) {
+        self.text_matrix = m;
+        self.line_matrix = m;
+    }
+    /// `Td`: moves to the start of the next line, offset by `(tx, ty)`.
+    pub fn translate(&mut self, tx: f32, ty: f32) {
+        let m = Matrix::translate(tx, ty) * self.line_matrix;
+        self.set_matrix(m);
+    }
+    /// `T*`
+    pub fn next_line(&mut self) {
+        let leading = self.leading;
+        self.translate(0., -leading);
+    }
+    /// Maps text space to user space for the next glyph (9.4.4):
+    /// `[Tfs·Th 0 0 Tfs 0 Trise] × Tm`.
+    /// Horizontal scaling applies to the glyph shapes as well as to the advances.
+    pub fn text_rendering_matrix(&self) -> Matrix {
+        Matrix::new(self.font_size * self.horiz_scale, 0., 0., self.font_size, 0., self.rise) * self.text_matrix
+    }
+    /// Moves behind a glyph that is `width` wide (in text space units, i.e. glyph width / 1000 for most fonts).
+    /// `is_space` adds the word spacing (single byte code 32).
+    pub fn advance_glyph(&mut self, width: f32, is_space: bool) {
+        let mut tx = width * self.font_size + self.char_space;
+        if is_space {
+            tx += self.word_space;
+        }
+        self.advance(tx * self.horiz_scale);
+    }
+    /// A number in a `TJ` array, in thousandths of text space units.
+    pub fn adjust(&mut self, amount: f32) {
+        let tx = -0.001 * amount * self.font_size * self.horiz_scale;
+        self.advance(tx);
+    }
+    /// Moves by `tx` along the baseline, in unscaled text space units.
+    pub fn advance(&mut self, tx: f32) {
+        self.text_matrix = Matrix::translate(tx, 0.) * self.text_matrix;
+    }
+}
+impl Default for TextState {
+    fn default() -> TextState {
+        TextState::new()
+    }
+}
+
+/// `TJ` offsets below this (in thousandths of an em) are taken as spaces.
+const SPACE_OFFSET: f32 = -200.;
+/// Width of glyphs whose widths are not known, in ems
+const ESTIMATED_WIDTH: f32 = 0.5;
+/// How far glyphs usually reach below and above the baseline, in ems
+const DESCENT: f32 = -0.2;
+const ASCENT: f32 = 0.8;
+
+impl Page {
+    /// The text shown on the page in the order it is painted. Codes are mapped to text with the
+    /// `/ToUnicode` CMap of their font, or else with its encoding, and strings in fonts without
+    /// either are read as text strings. Words are separated where the glyphs leave a gap, and
+    /// lines where the baseline moves.
+    pub fn extract_text<B: Backend>(&self, file: &File<B>) -> Result<String> {
+        Ok(file.text_of(self, false, false)?.text)
+    }
+    /// The strings shown on the page in the order they are painted, with their position,
+    /// font and extent. The extent is a guess for fonts whose glyph widths are not known.
+    pub fn text_fragments<B: Backend>(&self, file: &File<B>) -> Result<Vec<TextFragment>> {
+        Ok(file.text_of(self, false, false)?.fragments)
+    }
+}
+
+impl<B: Backend> File<B> {
+    /// The text of page `page_nr` (counting from 0), see `Page::extract_text`.
+    pub fn page_text(&self, page_nr: u32) -> Result<String> {
+        Ok(self.text_of_page(page_nr, false, false)?.text)
+    }
+    /// The marked content sequences of page `page_nr` that have an `/ActualText`, `/Alt`
+    /// or `/E`, in the order they end.
+    pub fn page_marked_content(&self, page_nr: u32) -> Result<Vec<MarkedContent>> {
+        Ok(self.text_of_page(page_nr, false, false)?.marked)
+    }
+    pub(crate) fn text_of_page(&self, page_nr: u32, actual_text: bool, skip_artifacts: bool) -> Result<PageText> {
+        let page = self.get_page(page_nr)?;
+        self.text_of(&page, actual_text, skip_artifacts)
+    }
+    /// The text of a page, its marked content with accessibility text and where its lines are.
+    /// With `actual_text`, the glyphs inside marked content with an `/ActualText` are replaced
+    /// by it. With `skip_artifacts`, marked content tagged `/Artifact` (14.8.2.2) is left out.
+    fn text_of(&self, page: &Page, actual_text: bool, skip_artifacts: bool) -> Result<PageText> {
+        let content = match page.contents {
+            Some(ref content) => content,
+            None => return Ok(PageText::default())
+        };
+        let resources = page.resources(self).ok();
+        let mut font: Option<CurrentFont> = None;
+        let mut out = TextCollector::default();
+        let mut ctm = Matrix::identity();
+        let mut saved = Vec::new();
+        let mut state = TextState::new();
+        // open marked content, and whether each hides the text of its glyphs
+        let mut marked: Vec<(MarkedContent, bool, usize)> = Vec::new();
+        let mut hidden = 0;
+        for op in content.ops() {
+            let show = hidden == 0;
+            match op {
+                Ok(Op::Save) => saved.push(ctm),
+                Ok(Op::Restore) => if let Some(m) = saved.pop() {
+                    ctm = m;
+                },
+                Ok(Op::Transform(m)) => ctm = m * ctm,
+                Ok(Op::CharSpacing(n)) => state.char_space = n,
+                Ok(Op::WordSpacing(n)) => state.word_space = n,
+                Ok(Op::HorizontalScaling(n)) => state.horiz_scale = 0.01 * n,
+                Ok(Op::Leading(n)) => state.leading = n,
+                Ok(Op::TextRise(n)) => state.rise = n,
+                Ok(Op::SetFont { name, size }) => {
+                    font = resources.as_ref().and_then(|r| r.fonts.get(&name)).map(|font| CurrentFont {
+                        // only simple fonts have one byte per glyph
+                        widths: match font.info().is_some() || font.standard_metrics().is_some() {
+                            true => font.widths().ok().and_then(|w| w),
+                            false => None
+                        },
+                        decoder: font.decoder(),
+                        font: font.clone(),
+                    });
+                    state.font_size = size;
+                }
+                Ok(Op::BeginText) => {
+                    state.begin_text();
+                    out.moved = true;
+                }
+                Ok(Op::SetTextMatrix(m)) => {
+                    state.set_matrix(m);
+                    out.moved = true;
+                }
+                Ok(Op::MoveText(p)) => {
+                    state.translate(p.x, p.y);
+                    out.moved = true;
+                }
+                Ok(Op::MoveTextSetLeading(p)) => {
+                    state.leading = -p.y;
+                    state.translate(p.x, p.y);
+                    out.moved = true;
+                }
+                Ok(Op::NextLine) => {
+                    state.next_line();
+                    out.moved = true;
+                }
+                Ok(Op::ShowText(text)) => out.show(&mut state, &ctm, font.as_ref(), &text, show),
+                Ok(Op::NextLineShowText(text)) => {
+                    state.next_line();
+                    out.moved = true;
+                    out.show(&mut state, &ctm, font.as_ref(), &text, show);
+                }
+                Ok(Op::NextLineShowTextSpacing { word_spacing, char_spacing, text }) => {
+                    state.word_space = word_spacing;
+                    state.char_space = char_spacing;
+                    state.next_line();
+                    out.moved = true;
+                    out.show(&mut state, &ctm, font.as_ref(), &text, show);
+                }
+                Ok(Op::ShowTextArray(items)) => for item in items {
+                    match item {
+                        TextItem::Text(text) => out.show(&mut state, &ctm, font.as_ref(), &text, show),
+                        TextItem::Offset(offset) => {
+                            state.adjust(offset);
+                            if show && offset < SPACE_OFFSET {
+                                out.space();
+                            }
+                        }
+                    }
+                },
+                Ok(Op::BeginMarkedContent { tag, properties }) => {
+                    let properties = properties.and_then(|p| self.properties(p, resources.as_ref().map(|r| &**r)));
+                    let span = MarkedContent {
+                        tag,
+                        mcid: properties.as_ref().and_then(|d| d.get("MCID")).and_then(|p| p.as_integer().ok()),
+                        alt_text: properties.as_ref().map(|d| AltText::from_dict(d, self)).unwrap_or_default(),
+                        text: String::new(),
+                    };
+                    // nested replacements are part of the outer one
+                    let replace = actual_text && show && span.alt_text.actual_text.is_some();
+                    let skip = skip_artifacts && span.tag == "Artifact";
+                    if replace && !skip {
+                        out.push(&state, &ctm, span.alt_text.actual_text.as_ref().unwrap());
+                    }
+                    if replace || skip {
+                        hidden += 1;
+                    }
+                    marked.push((span, replace || skip, out.page.text.len()));
+                }
+                Ok(Op::EndMarkedContent) => if let Some((mut span, hides, start)) = marked.pop() {
+                    if hides {
+                        hidden -= 1;
+                    }
+                    if !span.alt_text.is_empty() {
+                        span.text = out.page.text[start ..].into();
+                        out.page.marked.push(span);
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(out.page)
+    }
+    /// The properties of marked content, given inline or by name in `/Properties`.
+    fn properties(&self, properties: Primitive, resources: Option<&Resources>) -> Option<Dictionary> {
+        let properties = match properties {
+            Primitive::Name(name) => resources?.properties.get(&name)?.clone(),
+            p => p
+        };
+        properties.to_dictionary(self).ok()
+    }
+
+    /// The structure elements (14.7) that have an `/ActualText`, `/Alt` or `/E`,
+    /// in document order.
+    pub fn structure_alt_text(&self) -> Result<Vec<StructAltText>> {
+        let root = match self.catalog_dict()?.remove("StructTreeRoot") {
+            Some(root) => root.to_dictionary(self)?,
+            None => return Ok(Vec::new())
+        };
+        let mut out = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = kids(&root);
+        stack.reverse();
+        while let Some(kid) = stack.pop() {
+            if let Primitive::Reference(r) = kid {
+                if !visited.insert(r) {
+                    continue;
+                }
+            }
+            let elem = match kid.resolve(self)? {
+                Primitive::Dictionary(dict) => dict,
+                _ => continue
+            };
+            let struct_type = match elem.get("S") {
+                Some(&Primitive::Name(ref name)) => name.clone(),
+                // marked content and object references
+                _ => continue
+            };
+            let mut mcids = Vec::new();
+            let mut children = Vec::new();
+            for kid in kids(&elem) {
+                match kid {
+                    Primitive::Integer(mcid) => mcids.push(mcid),
+                    Primitive::Dictionary(ref dict) if dict.get("S").is_none() => {
+                        if let Some(mcid) = dict.get("MCID").and_then(|p| p.as_integer().ok()) {
+                            mcids.push(mcid);
+                        }
+                    }
+                    kid => children.push(kid)
+                }
+            }
+            let alt_text = AltText::from_dict(&elem, self);
+            if !alt_text.is_empty() {
+                out.push(StructAltText {
+                    struct_type,
+                    page: elem.get("Pg").and_then(|p| p.clone().to_reference().ok()),
+                    mcids,
+                    alt_text,
+                });
+            }
+            stack.extend(children.into_iter().rev());
+        }
+        Ok(out)
+    }
+}
+
+/// The `/K` entry of a structure element as a list.
+fn kids(elem: &Dictionary) -> Vec<Primitive> {
+    match elem.get("K") {
+        Some(&Primitive::Array(ref kids)) => kids.clone(),
+        Some(kid) => vec![kid.clone()],
+        None => Vec::new()
+    }
+}
+
+/// Accessibility text of marked content or a structure element (14.9.3 – 14.9.5).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AltText {
+    /// `/ActualText`: what the content reads as, replacing the text of its glyphs
+    pub actual_text: Option<String>,
+    /// `/Alt`: a description of the content, like that of an image
+    pub alt: Option<String>,
+    /// `/E`: the expansion of an abbreviation
+    pub expansion: Option<String>,
+}
+impl AltText {
+    /// Reads the entries of `dict`, ignoring those that are not text strings.
+    pub fn from_dict(dict: &Dictionary, resolve: &impl Resolve) -> AltText {
+        let text = |key: &str| dict.get(key)
+            .and_then(|p| p.clone().resolve(resolve).ok())
+            .and_then(|p| p.as_string().ok().map(|s| s.to_text()));
+        AltText {
+            actual_text: text("ActualText"),
+            alt: text("Alt"),
+            expansion: text("E"),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.actual_text.is_none() && self.alt.is_none() && self.expansion.is_none()
+    }
+}
+
+/// Marked content with accessibility text, see `File::page_marked_content`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MarkedContent {
+    pub tag: String,
+    /// `/MCID`, by which structure elements refer to it
+    pub mcid: Option<i32>,
+    pub alt_text: AltText,
+    /// The text of its glyphs
+    pub text: String,
+}
+
+/// A structure element with accessibility text, see `File::structure_alt_text`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructAltText {
+    /// `/S`, like `Figure`, or a type mapped to a standard one by the `/RoleMap`
+    pub struct_type: String,
+    /// `/Pg`: the page of its content
+    pub page: Option<PlainRef>,
+    /// The marked content it contains directly, by `/MCID`
+    pub mcids: Vec<i32>,
+    pub alt_text: AltText,
+}
+
+/// A string shown by a text operator, see `Page::text_fragments`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextFragment {
+    pub text: String,
+    /// The text rendering matrix times the CTM where the text starts: maps glyph space,
+    /// in ems, to user space
+    pub transform: Matrix,
+    /// `/BaseFont` of the font, `None` if it is not in the resources
+    pub font: Option<String>,
+    /// The size set with `Tf`
+    pub font_size: f32,
+    /// In user space
+    pub bbox: Rect,
+}
+
+/// The text of a page, see `File::text_of_page`.
+#[derive(Default)]
+pub(crate) struct PageText {
+    pub text: String,
+    pub marked: Vec<MarkedContent>,
+    /// Where each line starts in `text`, and the height of its baseline in user space
+    pub lines: Vec<(usize, f32)>,
+    pub fragments: Vec<TextFragment>,
+}
+impl PageText {
+    fn push(&mut self, y: f32, text: &str) {
+        if !text.is_empty() && (self.text.is_empty() || self.text.ends_with('\n')) {
+            self.lines.push((self.text.len(), y));
+        }
+        self.text.push_str(text);
+    }
+}
+
+/// Collects the text of a page, separating words and lines by the position of the glyphs.
+#[derive(Default)]
+struct TextCollector {
+    page: PageText,
+    /// the text position was set since the last text
+    moved: bool,
+    /// where the last text ended in user space, if the widths of its glyphs are known,
+    /// and its baseline
+    end: Option<(Option<f32>, f32)>,
+}
+impl TextCollector {
+    /// Shows `text`, adding it unless it is hidden, and moves behind it.
+    fn show(&mut self, state: &mut TextState, ctm: &Matrix, font: Option<&CurrentFont>, text: &PdfString, show: bool) {
+        let decoded = decode(font, text);
+        let advances = font.and_then(|f| f.advances(text.as_bytes()));
+        let start = state.text_rendering_matrix() * *ctm;
+        if show {
+            self.push(state, ctm, &decoded);
+        }
+        match advances {
+            Some(ref advances) => for &(width, is_space) in advances {
+                state.advance_glyph(width, is_space);
+            },
+            // only for the bounding box
+            None => for c in decoded.chars() {
+                state.advance_glyph(ESTIMATED_WIDTH, c == ' ');
+            }
+        }
+        if show && !text.as_bytes().is_empty() {
+            let m = state.text_matrix * *ctm;
+            self.end = Some((if advances.is_some() { Some(m.e) } else { None }, m.f));
+        }
+        let end = state.text_rendering_matrix() * *ctm;
+        self.page.fragments.push(TextFragment {
+            bbox: fragment_bbox(&start, &end),
+            text: decoded,
+            transform: start,
+            font: font.map(|f| f.font.name.clone()),
+            font_size: state.font_size,
+        });
+    }
+    /// Adds `text` at the current text position, after a space or a line break if it
+    /// was moved away from the last text.
+    fn push(&mut self, state: &TextState, ctm: &Matrix, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let m = state.text_matrix * *ctm;
+        if self.moved && !self.page.text.is_empty() {
+            let size = glyph_size(&(state.text_rendering_matrix() * *ctm));
+            match self.end {
+                Some((_, y)) if (m.f - y).abs() > 0.5 * size => {
+                    if !self.page.text.ends_with('\n') {
+                        self.page.text.push('\n');
+                    }
+                }
+                // close enough to continue the word
+                Some((Some(x), _)) if m.e - x < 0.2 * size => {}
+                _ => self.space()
+            }
+        }
+        self.moved = false;
+        self.page.push(m.f, text);
+    }
+    fn space(&mut self) {
+        if !self.page.text.is_empty() && !self.page.text.ends_with(char::is_whitespace) {
+            self.page.text.push(' ');
+        }
+    }
+}
+
+/// The box from the start of the text at `start` to its end at `end`, both text rendering
+/// matrices, between the usual descent and ascent.
+fn fragment_bbox(start: &Matrix, end: &Matrix) -> Rect {
+    let points = [
+        start.transform_point(0., DESCENT), start.transform_point(0., ASCENT),
+        end.transform_point(0., DESCENT), end.transform_point(0., ASCENT),
+    ];
+    let mut rect = Rect { left: points[0].0, bottom: points[0].1, right: points[0].0, top: points[0].1 };
+    for &(x, y) in &points[1 ..] {
+        rect.left = rect.left.min(x);
+        rect.right = rect.right.max(x);
+        rect.bottom = rect.bottom.min(y);
+        rect.top = rect.top.max(y);
+    }
+    rect
+}
+
+/// The height of a glyph that `m` maps from text space.
+fn glyph_size(m: &Matrix) -> f32 {
+    (m.c * m.c + m.d * m.d).sqrt()
+}
+
+/// The font set with `Tf`, with what is needed to decode and measure its strings.
+struct CurrentFont {
+    font: Arc<Font>,
+    widths: Option<[f32; 256]>,
+    /// `None` for composite fonts
+    decoder: Option<Decoder>,
+}
+impl CurrentFont {
+    /// The widths of the glyphs of `data` in ems, and whether each is a space, if they are known.
+    fn advances(&self, data: &[u8]) -> Option<Vec<(f32, bool)>> {
+        if let Some(ref widths) = self.widths {
+            return Some(data.iter().map(|&b| (0.001 * widths[b as usize], b == b' ')).collect());
+        }
+        let (cmap, cid_font) = (self.font.cmap()?, self.font.cid_font()?);
+        // codes without a CID show the glyph of CID 0
+        Some(cmap.cids(data).into_iter()
+            .map(|(code, cid)| (0.001 * cid_font.width(cid.unwrap_or(0)), code == b" "))
+            .collect())
+    }
+}
+
+/// The text of `text`: by the `/ToUnicode` map of `font`, falling back to its encoding for
+/// simple fonts, UTF-16 for composite fonts with a Unicode CMap, or as a text string.
+fn decode(font: Option<&CurrentFont>, text: &PdfString) -> String {
+    let font = match font {
+        Some(font) => font,
+        None => return text.to_text()
+    };
+    match (font.font.to_unicode(), font.decoder.as_ref()) {
+        (Some(cmap), Some(encoding)) => cmap.decode(text.as_bytes(), |code, out| {
+            out.extend(code.iter().filter_map(|&b| encoding.decode_byte(b)))
+        }),
+        (Some(cmap), None) => cmap.decode(text.as_bytes(), |_, out| out.push('\u{FFFD}')),
+        (None, Some(encoding)) => encoding.decode_bytes(text.as_bytes()),
+        (None, None) => match font.font.cmap() {
+            Some(cmap) if cmap.is_unicode() => {
+                let units: Vec<u16> = text.as_bytes().chunks(2).map(|pair| match *pair {
+                    [a, b] => u16::from_be_bytes([a, b]),
+                    _ => 0xFFFD
+                }).collect();
+                String::from_utf16_lossy(&units)
+            }
+            _ => text.to_text()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::Content;
+    use crate::object::{NoResolve, Rect};
+
+    #[test]
+    fn text_state() {
+        let mut state = TextState::new();
+        state.font_size = 10.;
+        state.horiz_scale = 0.5;
+        state.rise = 2.;
+        state.set_matrix(Matrix::translate(100., 200.));
+        // Tz scales the glyphs horizontally, Ts moves them up
+        assert_eq!(state.text_rendering_matrix(), Matrix::new(5., 0., 0., 10., 100., 202.));
+
+        // advances are scaled by Tz as well, including Tc, Tw and TJ offsets
+        state.advance_glyph(0.5, false);
+        assert_eq!(state.text_matrix.e, 102.5);
+        state.char_space = 1.;
+        state.word_space = 2.;
+        state.advance_glyph(0.5, true);
+        assert_eq!(state.text_matrix.e, 106.5);
+        state.adjust(-1000.);
+        assert_eq!(state.text_matrix.e, 111.5);
+
+        // Td and T* start from the line, not from the last glyph
+        state.translate(0., -12.);
+        assert_eq!((state.text_matrix.e, state.text_matrix.f), (100., 188.));
+        state.leading = 14.;
+        state.next_line();
+        assert_eq!(state.line_matrix, Matrix::translate(100., 174.));
+
+        state.begin_text();
+        assert_eq!(state.text_matrix, Matrix::identity());
+        assert_eq!(state.font_size, 10.);
+    }
+
+    #[test]
+    fn words_and_lines() {
+        let mut file = File::new().unwrap();
+        let ops = Content::parse_ops(b"BT /F1 12 Tf 72 700 Td (Hello) Tj ET BT /F1 12 Tf 110 700 Td (world) Tj ET \
+            BT /F1 12 Tf 72 680 Td (next) Tj 0 -20 Td [(li) -300 (ne)] TJ ET", &NoResolve).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+        file.add_page(media_box, &Content::from_ops(&ops), Dictionary::new()).unwrap();
+        let page = file.get_page(0).unwrap();
+        assert_eq!(page.extract_text(&file).unwrap(), "Hello world\nnext\nli ne");
+    }
+
+    #[test]
+    fn fragments() {
+        let mut file = File::new().unwrap();
+        let ops = Content::parse_ops(b"BT /F1 10 Tf 1 0 0 1 100 200 Tm (ab) Tj [(c) -1000 (d)] TJ ET", &NoResolve).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+        file.add_page(media_box, &Content::from_ops(&ops), Dictionary::new()).unwrap();
+        let fragments = file.get_page(0).unwrap().text_fragments(&file).unwrap();
+        let texts: Vec<_> = fragments.iter().map(|f| (f.text.as_str(), f.transform.e, f.font_size)).collect();
+        // glyphs without known widths are taken to be half an em wide
+        assert_eq!(texts, [("ab", 100., 10.), ("c", 110., 10.), ("d", 125., 10.)]);
+        let bbox = fragments[0].bbox;
+        let rounded = |x: f32| (x * 100.).round() / 100.;
+        assert_eq!([bbox.left, bbox.bottom, bbox.right, bbox.top].iter().map(|&x| rounded(x)).collect::<Vec<_>>(), [100., 198., 110., 208.]);
+    }
+
+    #[test]
+    fn alt_text() {
+        let mut file = File::new().unwrap();
+        let ops = Content::parse_ops(b"/Figure <</MCID 0 /Alt (a cat)>> BDC EMC \
+            /Span <</E (for example)>> BDC BT (e.g.) Tj ET EMC /P <</MCID 1>> BDC EMC", &NoResolve).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+        let page = file.add_page(media_box, &Content::from_ops(&ops), Dictionary::new()).unwrap();
+
+        let marked = file.page_marked_content(0).unwrap();
+        assert_eq!(marked.len(), 2);
+        assert_eq!((marked[0].mcid, marked[0].alt_text.alt.as_deref()), (Some(0), Some("a cat")));
+        assert_eq!((marked[1].text.as_str(), marked[1].alt_text.expansion.as_deref()), ("e.g.", Some("for example")));
+
+        let mut figure = Dictionary::new();
+        figure.insert("S".into(), Primitive::Name("Figure".into()));
+        figure.insert("Pg".into(), page.into());
+        figure.insert("K".into(), Primitive::Integer(0));
+        figure.insert("Alt".into(), Primitive::String(PdfString::from_text("a cat")));
+        let figure = file.add(Primitive::Dictionary(figure)).get_inner();
+        let mut document = Dictionary::new();
+        document.insert("S".into(), Primitive::Name("Document".into()));
+        document.insert("K".into(), Primitive::Array(vec![figure.into()]));
+        let document = file.add(Primitive::Dictionary(document)).get_inner();
+        let mut root = Dictionary::new();
+        root.insert("Type".into(), Primitive::Name("StructTreeRoot".into()));
+        root.insert("K".into(), document.into());
+        let root = file.add(Primitive::Dictionary(root)).get_inner();
+
+        let catalog_ref = file.trailer_dict().get("Root").unwrap().clone().to_reference().unwrap();
+        let mut catalog = file.catalog_dict().unwrap();
+        catalog.insert("StructTreeRoot".into(), root.into());
+        file.update(catalog_ref.id, Primitive::Dictionary(catalog));
+
+        let elems = file.structure_alt_text().unwrap();
+        assert_eq!(elems.len(), 1);
+        assert_eq!((elems[0].struct_type.as_str(), elems[0].page, &elems[0].mcids[..]), ("Figure", Some(page), &[0][..]));
+        assert_eq!(elems[0].alt_text.alt.as_deref(), Some("a cat"));
+    }
+}
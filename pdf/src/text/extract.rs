@@ -0,0 +1,453 @@
+//! Post-processing of extracted text.
+
+use std::collections::{HashMap, HashSet};
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_bidi::BidiInfo;
+
+use crate::error::*;
+use crate::backend::Backend;
+use crate::file::File;
+use crate::object::{Annot, FieldType, Object, Page, PlainRef, Rect, Resolve, field_flags, annot_flags};
+use crate::primitive::Primitive;
+use super::PageText;
+
+/// Post-processing of extracted text.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractOptions {
+    /// Compose combining sequences (NFC).
+    pub normalize: bool,
+    /// Replace the ligature presentation forms (U+FB00 – U+FB06) by their letters.
+    pub expand_ligatures: bool,
+    /// Glyphs are painted in visual order. Reorder each line into logical order
+    /// with the Unicode bidirectional algorithm (right-to-left scripts).
+    pub logical_order: bool,
+    /// What to do with words split by a hyphen at the end of a line.
+    pub hyphenation: Hyphenation,
+    /// What to do with the soft hyphens (U+00AD) left after joining hyphenated words.
+    pub soft_hyphens: SoftHyphens,
+    /// What to do with tabs.
+    pub tabs: CharPolicy,
+    /// What to do with control characters other than tabs and line breaks, which
+    /// `/ToUnicode` maps sometimes produce.
+    pub control_chars: CharPolicy,
+    /// Use the `/ActualText` of marked content (14.9.4) instead of the text of its glyphs.
+    /// Only applies to `File::page_text_with_options`.
+    pub actual_text: bool,
+    /// What to do with headers, footers, page numbers and other pagination artifacts.
+    /// Only applies to `File::page_text_with_options` and `File::document_text_with_options`.
+    pub artifacts: Artifacts,
+    /// Add the values of text and choice fields and the text of `FreeText` annotations,
+    /// as if the annotations were flattened into the page. They are placed on the line at
+    /// their height, or between the lines above and below them.
+    /// Only applies to `File::page_text_with_options` and `File::document_text_with_options`.
+    pub annotation_text: bool,
+}
+
+/// Treatment of pagination artifacts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Artifacts {
+    Keep,
+    /// Leave out marked content tagged `/Artifact` (14.8.2.2).
+    Remove,
+    /// Also leave out lines that are at the same height on many pages, with the same text
+    /// apart from numbers, for documents that don't tag their artifacts.
+    /// Needs the whole document, so it only applies to `File::document_text_with_options`.
+    RemoveRepeated,
+}
+impl Default for Artifacts {
+    fn default() -> Artifacts {
+        Artifacts::Keep
+    }
+}
+
+/// Treatment of a kind of character.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CharPolicy {
+    Keep,
+    Remove,
+    /// Replace each by a space.
+    Space,
+}
+impl Default for CharPolicy {
+    fn default() -> CharPolicy {
+        CharPolicy::Keep
+    }
+}
+
+/// Treatment of soft hyphens, which mark where a word may be broken.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SoftHyphens {
+    Keep,
+    Remove,
+    /// Replace each by `-`.
+    Hyphen,
+}
+impl Default for SoftHyphens {
+    fn default() -> SoftHyphens {
+        SoftHyphens::Keep
+    }
+}
+
+/// Treatment of hyphens at the end of a line.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Hyphenation {
+    /// Leave the lines as they are.
+    Keep,
+    /// Join the word if the next line continues in lower case. Soft hyphens are always joined.
+    Join,
+    /// Join whenever there is a letter before the hyphen and at the start of the next line.
+    JoinAll,
+}
+impl Default for Hyphenation {
+    fn default() -> Hyphenation {
+        Hyphenation::Keep
+    }
+}
+
+fn ligature(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{FB00}' => "ff",
+        '\u{FB01}' => "fi",
+        '\u{FB02}' => "fl",
+        '\u{FB03}' => "ffi",
+        '\u{FB04}' => "ffl",
+        '\u{FB05}' => "\u{17F}t",
+        '\u{FB06}' => "st",
+        _ => return None
+    })
+}
+
+/// Reorders one line of text from visual to logical order, by reordering it with the
+/// bidirectional algorithm as if it were in logical order. This reverses right-to-left runs
+/// and keeps numbers in them in order. The base direction comes from the first strong
+/// character of the visual line, so in a right-to-left line that starts (on the left) with
+/// left-to-right text, the runs stay in left-to-right order.
+pub fn visual_to_logical(line: &str) -> String {
+    let info = BidiInfo::new(line, None);
+    let mut out = String::with_capacity(line.len());
+    for para in &info.paragraphs {
+        out.push_str(&info.reorder_line(para, para.range.clone()));
+    }
+    out
+}
+
+/// Joins words that are split over two lines according to `mode`:
+/// the hyphen and the line break are removed, so the next line continues the current one.
+pub fn join_hyphenated(text: &str, mode: Hyphenation) -> String {
+    if mode == Hyphenation::Keep {
+        return text.into();
+    }
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut out = String::with_capacity(text.len());
+    let mut continued = false;
+    for (i, &line) in lines.iter().enumerate() {
+        let line = if continued { line.trim_start() } else { line };
+        let trimmed = line.trim_end();
+        let mut end = trimmed.chars().rev();
+        let next = lines.get(i + 1).and_then(|l| l.trim_start().chars().next());
+        continued = match (end.next(), end.next(), next) {
+            (Some(hyphen), Some(before), Some(after)) if before.is_alphabetic() && after.is_alphabetic() => match hyphen {
+                '\u{AD}' => true,
+                '-' | '\u{2010}' => mode == Hyphenation::JoinAll || after.is_lowercase(),
+                _ => false
+            },
+            _ => false
+        };
+        if continued {
+            let hyphen_len = trimmed.chars().next_back().unwrap().len_utf8();
+            out.push_str(&trimmed[.. trimmed.len() - hyphen_len]);
+        } else {
+            out.push_str(line);
+            if i + 1 < lines.len() {
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Applies `options` to extracted text.
+pub fn postprocess(text: &str, options: &ExtractOptions) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let policy = match c {
+            '\t' => options.tabs,
+            '\n' => CharPolicy::Keep,
+            c if c.is_control() => options.control_chars,
+            _ => CharPolicy::Keep
+        };
+        let expanded = if options.expand_ligatures { ligature(c) } else { None };
+        match (policy, expanded) {
+            (CharPolicy::Remove, _) => {}
+            (CharPolicy::Space, _) => out.push(' '),
+            (CharPolicy::Keep, Some(s)) => out.push_str(s),
+            (CharPolicy::Keep, None) => out.push(c)
+        }
+    }
+    if options.logical_order {
+        out = out.split('\n').map(visual_to_logical).collect::<Vec<_>>().join("\n");
+    }
+    out = join_hyphenated(&out, options.hyphenation);
+    match options.soft_hyphens {
+        SoftHyphens::Keep => {}
+        SoftHyphens::Remove => out = out.replace('\u{AD}', ""),
+        SoftHyphens::Hyphen => out = out.replace('\u{AD}', "-"),
+    }
+    if options.normalize {
+        out = out.nfc().collect();
+    }
+    out
+}
+
+/// A line without surrounding whitespace and with each run of digits replaced by `#`,
+/// and its height rounded to a point. `None` for blank lines.
+fn line_key(line: &str, y: f32) -> Option<(String, i32)> {
+    let mut key = String::with_capacity(line.len());
+    for c in line.trim().chars() {
+        match c {
+            '0' ..= '9' if key.ends_with('#') => {}
+            '0' ..= '9' => key.push('#'),
+            c => key.push(c)
+        }
+    }
+    if key.is_empty() {
+        return None;
+    }
+    Some((key, y.round() as i32))
+}
+
+/// The lines of a page and their height.
+fn lines(page: &PageText) -> impl Iterator<Item=(&str, f32)> + '_ {
+    let ends = page.lines.iter().skip(1).map(|&(start, _)| start).chain(Some(page.text.len()));
+    page.lines.iter().zip(ends).map(move |(&(start, y), end)| (&page.text[start .. end], y))
+}
+
+/// The lines that repeat on at least a quarter of the pages, and at least two.
+fn repeated_lines(pages: &[PageText]) -> HashSet<(String, i32)> {
+    let mut counts = HashMap::new();
+    for page in pages {
+        let keys: HashSet<_> = lines(page).filter_map(|(line, y)| line_key(line, y)).collect();
+        for key in keys {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    let min = (pages.len() / 4).max(2);
+    counts.into_iter().filter(|&(_, n)| n >= min).map(|(key, _)| key).collect()
+}
+
+/// Adds each text in `items` to the line within its rectangle, or else as a line of its own
+/// before the first line below the middle of the rectangle.
+fn insert_annotation_text(page: PageText, items: Vec<(Rect, String)>) -> PageText {
+    let mut lines: Vec<(String, f32)> = lines(&page)
+        .map(|(line, y)| (line.trim_end_matches('\n').into(), y))
+        .collect();
+    for (rect, text) in items {
+        let (bottom, top) = (rect.bottom.min(rect.top), rect.bottom.max(rect.top));
+        if let Some(line) = lines.iter_mut().find(|line| bottom <= line.1 && line.1 <= top) {
+            line.0.push(' ');
+            line.0.push_str(&text);
+            continue;
+        }
+        let middle = 0.5 * (bottom + top);
+        let i = lines.iter().position(|&(_, y)| y < middle).unwrap_or(lines.len());
+        lines.insert(i, (text, middle));
+    }
+    let mut out = PageText { marked: page.marked, fragments: page.fragments, .. PageText::default() };
+    for (line, y) in lines {
+        if !out.text.is_empty() {
+            out.text.push('\n');
+        }
+        out.push(y, &line);
+    }
+    out
+}
+
+impl<B: Backend> File<B> {
+    /// The text of page `page_nr` like `File::page_text`, processed according to `options`.
+    pub fn page_text_with_options(&self, page_nr: u32, options: &ExtractOptions) -> Result<String> {
+        let page = self.extracted_page(page_nr, options)?;
+        Ok(postprocess(&page.text, options))
+    }
+    /// The text of all pages like `File::page_text_with_options`, one string per page.
+    /// With `Artifacts::RemoveRepeated`, lines that repeat on many pages are left out.
+    pub fn document_text_with_options(&self, options: &ExtractOptions) -> Result<Vec<String>> {
+        let pages = (0 .. self.get_num_pages()?)
+            .map(|n| self.extracted_page(n, options))
+            .collect::<Result<Vec<_>>>()?;
+        let repeated = match options.artifacts {
+            Artifacts::RemoveRepeated => repeated_lines(&pages),
+            _ => HashSet::new()
+        };
+        Ok(pages.iter().map(|page| {
+            let text: String = lines(page)
+                .filter(|&(line, y)| line_key(line, y).map(|key| !repeated.contains(&key)).unwrap_or(true))
+                .map(|(line, _)| line)
+                .collect();
+            postprocess(text.trim_end_matches('\n'), options)
+        }).collect())
+    }
+
+    fn extracted_page(&self, page_nr: u32, options: &ExtractOptions) -> Result<PageText> {
+        let text = self.text_of_page(page_nr, options.actual_text, options.artifacts != Artifacts::Keep)?;
+        if !options.annotation_text {
+            return Ok(text);
+        }
+        let items = self.annotation_text(&self.get_page(page_nr)?)?;
+        Ok(insert_annotation_text(text, items))
+    }
+
+    /// The values of the form fields and the text of the `FreeText` annotations on `page`
+    /// that are shown, with their rectangles.
+    fn annotation_text(&self, page: &Page) -> Result<Vec<(Rect, String)>> {
+        let refs: Vec<PlainRef> = match page.annots {
+            Some(ref annots) => match annots.clone().resolve(self)? {
+                Primitive::Array(annots) => annots.into_iter().filter_map(|a| a.to_reference().ok()).collect(),
+                _ => Vec::new()
+            },
+            None => return Ok(Vec::new())
+        };
+        if refs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut values = HashMap::new();
+        for field in self.form_fields()? {
+            let shown = match field.typ {
+                Some(FieldType::Text) => !field.has_flag(field_flags::Password),
+                Some(FieldType::Choice) => true,
+                _ => false
+            };
+            if let (true, Some(value)) = (shown, field.value_text()) {
+                for &widget in &field.widgets {
+                    values.insert(widget, value.clone());
+                }
+            }
+        }
+
+        let mut items = Vec::new();
+        for r in refs {
+            let annot = match self.resolve(r).and_then(|p| Annot::from_primitive(p, self)) {
+                Ok(annot) => annot,
+                Err(e) => {
+                    self.warn(format!("annotation {:?}: {}", r, e));
+                    continue;
+                }
+            };
+            if annot.flags & (annot_flags::Hidden | annot_flags::NoView) != 0 {
+                continue;
+            }
+            let text = match annot {
+                Annot::FreeText(ref a) => a.contents.as_ref().map(|s| s.to_text()),
+                Annot::Widget(_) => values.get(&r).cloned(),
+                _ => None
+            };
+            match text {
+                Some(text) if !text.trim().is_empty() => items.push((annot.rect, text)),
+                _ => {}
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::Content;
+    use crate::object::{Annotation, NoResolve};
+    use crate::primitive::{Dictionary, PdfString};
+
+    #[test]
+    fn hyphenation() {
+        let text = "a hyphen-\n  ated word\nNew-\nYork\nsoft\u{AD}\nHyphen";
+        assert_eq!(join_hyphenated(text, Hyphenation::Join), "a hyphenated word\nNew-\nYork\nsoftHyphen");
+        assert_eq!(join_hyphenated(text, Hyphenation::JoinAll), "a hyphenated word\nNewYork\nsoftHyphen");
+        assert_eq!(join_hyphenated(text, Hyphenation::Keep), text);
+    }
+
+    #[test]
+    fn logical_order() {
+        assert_eq!(visual_to_logical("\u{5DD}\u{5D5}\u{5DC}\u{5E9}"), "\u{5E9}\u{5DC}\u{5D5}\u{5DD}");
+        // numbers in right-to-left text are written left to right
+        assert_eq!(visual_to_logical("123 \u{5DD}\u{5D5}\u{5DC}\u{5E9}"), "\u{5E9}\u{5DC}\u{5D5}\u{5DD} 123");
+        assert_eq!(visual_to_logical("abc \u{5DD}\u{5D5}\u{5DC}\u{5E9} def"), "abc \u{5E9}\u{5DC}\u{5D5}\u{5DD} def");
+
+        let options = ExtractOptions { logical_order: true, .. ExtractOptions::default() };
+        assert_eq!(postprocess("abc\n\u{5D1}\u{5D0}", &options), "abc\n\u{5D0}\u{5D1}");
+        assert_eq!(postprocess("\u{5D1}\u{5D0}", &ExtractOptions::default()), "\u{5D1}\u{5D0}");
+    }
+
+    #[test]
+    fn characters() {
+        let options = ExtractOptions {
+            soft_hyphens: SoftHyphens::Remove,
+            tabs: CharPolicy::Space,
+            control_chars: CharPolicy::Remove,
+            .. ExtractOptions::default()
+        };
+        assert_eq!(postprocess("a\tb\u{1}c\u{AD}d\ne", &options), "a bcd\ne");
+        let options = ExtractOptions { soft_hyphens: SoftHyphens::Hyphen, .. ExtractOptions::default() };
+        assert_eq!(postprocess("a\tb\u{AD}c", &options), "a\tb-c");
+    }
+
+    #[test]
+    fn artifacts() {
+        let mut file = File::new().unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+        for (n, body) in ["one", "two", "three"].iter().enumerate() {
+            let data = format!("/Artifact BMC BT 1 0 0 1 72 750 Tm (Report) Tj ET EMC \
+                BT 1 0 0 1 72 700 Tm ({}) Tj ET BT 1 0 0 1 300 40 Tm ({}) Tj ET", body, n + 1);
+            let ops = Content::parse_ops(data.as_bytes(), &NoResolve).unwrap();
+            file.add_page(media_box, &Content::from_ops(&ops), Dictionary::new()).unwrap();
+        }
+        assert_eq!(file.page_text(0).unwrap(), "Report\none\n1");
+
+        let options = ExtractOptions { artifacts: Artifacts::Remove, .. ExtractOptions::default() };
+        assert_eq!(file.page_text_with_options(1, &options).unwrap(), "two\n2");
+        let options = ExtractOptions { artifacts: Artifacts::RemoveRepeated, .. ExtractOptions::default() };
+        assert_eq!(file.document_text_with_options(&options).unwrap(), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn annotation_text() {
+        let mut file = File::new().unwrap();
+        let ops = Content::parse_ops(b"BT /F1 12 Tf 72 700 Td (Name:) Tj 0 -100 Td (Signature) Tj ET", &NoResolve).unwrap();
+        let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+        let page = file.add_page(media_box, &Content::from_ops(&ops), Dictionary::new()).unwrap();
+
+        // a text field merged with its widget
+        let mut field = Dictionary::new();
+        field.insert("Type".into(), "Annot".into());
+        field.insert("Subtype".into(), "Widget".into());
+        field.insert("Rect".into(), Rect { left: 120., bottom: 695., right: 300., top: 715. }.into());
+        field.insert("FT".into(), "Tx".into());
+        field.insert("T".into(), Primitive::String(PdfString::from_text("name")));
+        field.insert("V".into(), Primitive::String(PdfString::from_text("Jane")));
+        let field = file.add(Primitive::Dictionary(field)).get_inner();
+        let mut note = Annotation::new("FreeText", Rect { left: 72., bottom: 640., right: 300., top: 660. });
+        note.contents = Some(PdfString::from_text("Approved"));
+        let note = file.add(Primitive::from(note)).get_inner();
+
+        let mut page_dict = file.resolve(page).unwrap().to_dictionary(&file).unwrap();
+        page_dict.insert("Annots".into(), Primitive::Array(vec![field.into(), note.into()]));
+        file.update(page.id, Primitive::Dictionary(page_dict));
+        let catalog_ref = file.trailer_dict().get("Root").unwrap().clone().to_reference().unwrap();
+        let mut catalog = file.catalog_dict().unwrap();
+        let mut form = Dictionary::new();
+        form.insert("Fields".into(), Primitive::Array(vec![field.into()]));
+        catalog.insert("AcroForm".into(), Primitive::Dictionary(form));
+        file.update(catalog_ref.id, Primitive::Dictionary(catalog));
+
+        let mut data = Vec::new();
+        file.write_to(&mut data).unwrap();
+        let file = File::from_data(data).unwrap();
+        assert_eq!(file.page_text(0).unwrap(), "Name:\nSignature");
+        let options = ExtractOptions { annotation_text: true, .. ExtractOptions::default() };
+        assert_eq!(file.page_text_with_options(0, &options).unwrap(), "Name: Jane\nApproved\nSignature");
+    }
+}
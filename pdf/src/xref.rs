@@ -2,6 +2,10 @@ use std;
 use std::fmt::{Debug, Formatter};
 use crate::error::*;
 use crate::object::*;
+#[cfg(feature = "write")]
+use crate::primitive::{Primitive, Dictionary, PdfStream};
+#[cfg(feature = "write")]
+use deflate::deflate_bytes_zlib;
 
 ///////////////////////////
 // Cross-reference table //
@@ -42,6 +46,21 @@ impl XRef {
     }
 }
 
+/// Where an object is stored, see `File::locate`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum XRefLocation {
+    /// At this byte offset in the file
+    Offset(u64),
+    /// Object number `index` of the object stream `stream`
+    InStream {
+        stream: ObjNr,
+        index: usize,
+    },
+    /// Added or replaced, and not written yet
+    Modified,
+    Free,
+}
+
 
 /// Runtime lookup table of all objects
 pub struct XRefTable {
@@ -67,6 +86,11 @@ impl XRefTable {
         }
     }
 
+    /// All entries, by object number
+    pub fn iter_entries<'a>(&'a self) -> impl Iterator<Item=XRef> + 'a {
+        self.entries.iter().cloned()
+    }
+
     pub fn get(&self, id: ObjNr) -> Result<XRef> {
         match self.entries.get(id as usize) {
             Some(&entry) => Ok(entry),
@@ -151,11 +175,60 @@ impl XRefSection {
     pub fn add_inuse_entry(&mut self, pos: usize, gen_nr: u16) {
         self.entries.push(XRef::Raw{pos: pos, gen_nr: gen_nr});
     }
+    pub fn add_compressed_entry(&mut self, stream_id: ObjNr, index: usize) {
+        self.entries.push(XRef::Stream{stream_id: stream_id, index: index});
+    }
     pub fn entries(&self) -> impl Iterator<Item=(usize, &XRef)> {
         self.entries.iter().enumerate().map(move |(i, e)| (i + self.first_id as usize, e))
     }
 }
 
+/// Number of bytes needed for `n`, at least one.
+#[cfg(feature = "write")]
+fn field_width(n: u64) -> usize {
+    (1 .. 8).find(|&w| n >> (8 * w) == 0).unwrap_or(8)
+}
+
+/// Builds a cross-reference stream (7.5.8) from `sections`, sorted by object number.
+/// The trailer entries are taken from `dict`; `/Type`, `/Size`, `/W`, `/Index` and `/Filter` are set here.
+/// `size` is one more than the highest object number in the document.
+#[cfg(feature = "write")]
+pub fn xref_stream(sections: &[XRefSection], size: ObjNr, mut dict: Dictionary) -> PdfStream {
+    // type, offset or object stream, generation or index
+    let fields: Vec<(u8, u64, u64)> = sections.iter().flat_map(|s| s.entries()).map(|(id, e)| match *e {
+        XRef::Free { next_obj_nr, gen_nr } => (0, next_obj_nr, if id == 0 { 65535 } else { gen_nr as u64 }),
+        XRef::Raw { pos, gen_nr } => (1, pos as u64, gen_nr as u64),
+        XRef::Stream { stream_id, index } => (2, stream_id, index as u64),
+        XRef::Promised | XRef::Invalid => (0, 0, 0)
+    }).collect();
+    let w1 = field_width(fields.iter().map(|f| f.1).max().unwrap_or(0));
+    let w2 = field_width(fields.iter().map(|f| f.2).max().unwrap_or(0));
+    let mut data = Vec::with_capacity(fields.len() * (1 + w1 + w2));
+    for &(typ, a, b) in &fields {
+        data.push(typ);
+        data.extend((0 .. w1).rev().map(|i| (a >> (8 * i)) as u8));
+        data.extend((0 .. w2).rev().map(|i| (b >> (8 * i)) as u8));
+    }
+
+    dict.insert("Type".into(), "XRef".into());
+    dict.insert("Size".into(), Primitive::Integer(size as i32));
+    dict.insert("W".into(), Primitive::Array(vec![Primitive::Integer(1), Primitive::Integer(w1 as i32), Primitive::Integer(w2 as i32)]));
+    // the default is a single section covering all objects
+    match sections {
+        [ref s] if s.first_id == 0 && s.entries.len() as ObjNr == size => {
+            dict.remove("Index");
+        }
+        _ => {
+            let index = sections.iter()
+                .flat_map(|s| vec![Primitive::Integer(s.first_id as i32), Primitive::Integer(s.entries.len() as i32)])
+                .collect();
+            dict.insert("Index".into(), Primitive::Array(index));
+        }
+    }
+    dict.insert("Filter".into(), "FlateDecode".into());
+    dict.remove("DecodeParms");
+    PdfStream { info: dict, data: deflate_bytes_zlib(&data).into() }
+}
 
 /// Iterates over the used object numbers in this xref table, skips the free objects.
 pub struct ObjectNrIter<'a> {
@@ -182,3 +255,25 @@ impl<'a> Iterator for ObjectNrIter<'a> {
 // read_xref_table
 // read_xref_stream
 // read_xref_and_trailer_at
+
+#[cfg(all(test, feature = "write"))]
+mod tests {
+    use super::*;
+    use inflate::inflate_bytes_zlib;
+
+    fn ints(p: &Primitive) -> Vec<i32> {
+        p.as_array().unwrap().iter().map(|p| p.as_integer().unwrap()).collect()
+    }
+
+    #[test]
+    fn stream() {
+        let mut section = XRefSection::new(3);
+        section.add_inuse_entry(0x1234, 0);
+        section.add_compressed_entry(7, 2);
+        let stream = xref_stream(&[section], 10, Dictionary::new());
+        assert_eq!(ints(&stream.info["W"]), [1, 2, 1]);
+        assert_eq!(ints(&stream.info["Index"]), [3, 2]);
+        let data = inflate_bytes_zlib(&stream.data).unwrap();
+        assert_eq!(data, [1, 0x12, 0x34, 0, 2, 0, 7, 2]);
+    }
+}
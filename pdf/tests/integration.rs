@@ -63,4 +63,389 @@ fn parse_objects_from_stream() {
     }
 }
 
+#[test]
+fn locate_objects() {
+    use pdf::xref::XRefLocation;
+    use pdf::primitive::Primitive;
+
+    let mut file = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let data = std::fs::read(file_path!("xelatex.pdf")).unwrap();
+    // object 13 is an object stream, see above
+    let obj_stream = run!(file.get(Ref::<ObjectStream>::new(PlainRef {id: 13, gen: 0})));
+    let (mut raw, mut in_stream) = (0, 0);
+    for id in 1 .. 1000 {
+        match file.locate(PlainRef {id, gen: 0}) {
+            Ok((XRefLocation::Offset(pos), size)) => {
+                let object = &data[pos as usize .. pos as usize + size];
+                assert!(object.starts_with(format!("{} 0 obj", id).as_bytes()));
+                let end = object.iter().rposition(|b| !b.is_ascii_whitespace()).unwrap();
+                assert!(object[..= end].ends_with(b"endobj"));
+                raw += 1;
+            }
+            Ok((XRefLocation::InStream {stream, index}, size)) => {
+                assert_eq!(stream, 13);
+                assert_eq!(run!(obj_stream.get_object_slice(index)).len(), size);
+                in_stream += 1;
+            }
+            _ => {}
+        }
+    }
+    assert!(raw > 0 && in_stream > 0);
+    assert_eq!(run!(file.locate(PlainRef {id: 0, gen: 65535})).0, XRefLocation::Free);
+
+    file.update(13, Primitive::Integer(42));
+    assert_eq!(run!(file.locate(PlainRef {id: 13, gen: 0})), (XRefLocation::Modified, 2));
+}
+
+#[test]
+fn write_and_reopen() {
+    let file = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rewritten.pdf");
+    run!(file.save_to(&path));
+
+    let rewritten = run!(File::<Vec<u8>>::open(path.to_str().unwrap()));
+    assert_eq!(run!(rewritten.get_num_pages()), run!(file.get_num_pages()));
+    for page in rewritten.pages() {
+        run!(page);
+    }
+}
+
+#[test]
+fn create_document() {
+    use pdf::content::ContentBuilder;
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let mut file = run!(File::new());
+    let font = file.add_standard_font("Helvetica");
+    let mut fonts = Dictionary::new();
+    fonts.insert("F1".into(), font.into());
+    let mut resources = Dictionary::new();
+    resources.insert("Font".into(), Primitive::Dictionary(fonts));
+
+    let mut content = ContentBuilder::new();
+    content.begin_text().set_font("F1", 24.).move_text(72., 720.).show_text("Hello").end_text();
+    let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+    run!(file.add_page(media_box, &content.build(), resources));
+    assert_eq!(run!(file.get_num_pages()), 1);
+
+    let mut data = Vec::new();
+    run!(file.write_to(&mut data));
+    let reopened = run!(File::from_data(data));
+    assert_eq!(run!(reopened.get_num_pages()), 1);
+    let page = run!(reopened.get_page(0));
+    assert_eq!(run!(page.media_box(&reopened)).right, 612.);
+}
+
+/// The names of the fonts of page `n`, with the names of the fonts.
+fn page_fonts<B: pdf::backend::Backend>(file: &File<B>, n: u32) -> Vec<(String, String)> {
+    let page = run!(file.get_page(n));
+    let resources = run!(page.resources(file));
+    resources.fonts.iter().map(|(name, font)| (name.clone(), font.name.clone())).collect()
+}
+
+#[test]
+fn import_pages() {
+    use pdf::content::ContentBuilder;
+    use pdf::primitive::Dictionary;
+
+    let libreoffice = run!(File::<Vec<u8>>::open(file_path!("libreoffice.pdf")));
+    let example = run!(File::<Vec<u8>>::open(file_path!("example.pdf")));
+    let n = run!(libreoffice.get_num_pages());
+    assert!(!page_fonts(&libreoffice, 0).is_empty());
+
+    let mut file = run!(File::new());
+    let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+    run!(file.add_page(media_box, &ContentBuilder::new().build(), Dictionary::new()));
+    assert_eq!(run!(file.import_pages(&libreoffice, 0 .. n as usize)).len(), n as usize);
+    assert_eq!(run!(file.import_pages(&example, 0 .. 1)).len(), 1);
+    assert!(file.import_pages(&example, 0 .. 2).is_err());
+
+    let mut data = Vec::new();
+    run!(file.write_to(&mut data));
+    let merged = run!(File::from_data(data));
+    assert_eq!(run!(merged.get_num_pages()), n + 2);
+    for i in 0 .. n {
+        assert_eq!(page_fonts(&merged, i + 1), page_fonts(&libreoffice, i));
+        let media_box = run!(run!(merged.get_page(i + 1)).media_box(&merged));
+        assert_eq!(media_box.right, run!(run!(libreoffice.get_page(i)).media_box(&libreoffice)).right);
+    }
+    assert_eq!(page_fonts(&merged, n + 1), page_fonts(&example, 0));
+}
+
+#[test]
+fn extract_pages() {
+    let libreoffice = run!(File::<Vec<u8>>::open(file_path!("libreoffice.pdf")));
+    let n = run!(libreoffice.get_num_pages());
+    let title = |file: &File<Vec<u8>>| run!(file.info()).and_then(|info| info.title).map(|title| title.as_bytes().to_vec());
+    for i in 0 .. n {
+        let part = run!(libreoffice.extract_pages(i as usize .. i as usize + 1));
+        let mut data = Vec::new();
+        run!(part.write_to(&mut data));
+        let part = run!(File::from_data(data));
+        assert_eq!(run!(part.get_num_pages()), 1);
+        assert_eq!(page_fonts(&part, 0), page_fonts(&libreoffice, i));
+        assert_eq!(title(&part), title(&libreoffice));
+    }
+    let rest = run!(libreoffice.extract_pages(1 .. n as usize));
+    assert_eq!(run!(rest.get_num_pages()), n - 1);
+    assert!(libreoffice.extract_pages(0 .. n as usize + 1).is_err());
+}
+
+#[test]
+fn encrypted_roundtrip() {
+    use pdf::crypt::{EncryptionOptions, EncryptionAlgorithm, Permissions};
+    use pdf::file::WriteOptions;
+
+    let file = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let operations = run!(file.get_page(0)).contents.as_ref().unwrap().operations.len();
+    for &algorithm in &[EncryptionAlgorithm::Rc4_128, EncryptionAlgorithm::Aes128, EncryptionAlgorithm::Aes256] {
+        let options = WriteOptions {
+            encryption: Some(EncryptionOptions {
+                user_password: b"user".to_vec(),
+                owner_password: b"owner".to_vec(),
+                permissions: Permissions::PRINT | Permissions::COPY,
+                algorithm,
+            }),
+            .. WriteOptions::default()
+        };
+        let mut data = Vec::new();
+        run!(file.write_with_options(&mut data, &options));
+        assert!(File::from_data(&data[..]).is_err());
+
+        for password in &[&b"user"[..], b"owner"] {
+            let reopened = run!(File::from_backend_with_password(data.clone(), password));
+            assert_eq!(run!(reopened.get_num_pages()), run!(file.get_num_pages()));
+            let page = run!(reopened.get_page(0));
+            assert_eq!(page.contents.as_ref().unwrap().operations.len(), operations);
+        }
+    }
+}
+
+#[test]
+fn page_template() {
+    use pdf::content::ContentBuilder;
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let letterhead = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let mut file = run!(File::new());
+    let form = run!(file.import_page_as_xobject(&letterhead, 0));
+    let mut xobjects = Dictionary::new();
+    xobjects.insert("Letterhead".into(), form.into());
+    let mut resources = Dictionary::new();
+    resources.insert("XObject".into(), Primitive::Dictionary(xobjects));
+
+    let mut content = ContentBuilder::new();
+    content.save().draw_xobject("Letterhead").restore();
+    let media_box = run!(run!(letterhead.get_page(0)).media_box(&letterhead));
+    run!(file.add_page(media_box, &content.build(), resources));
+
+    let mut data = Vec::new();
+    run!(file.write_to(&mut data));
+    let reopened = run!(File::from_data(data));
+    let page = run!(reopened.get_page(0));
+    let resources = run!(page.resources(&reopened));
+    match resources.xobjects.get("Letterhead") {
+        Some(&XObject::Form(ref form)) => assert!(run!(form.data()).len() > 0),
+        _ => panic!("no form")
+    }
+}
+
+#[cfg(feature = "extract")]
+#[test]
+fn actual_text() {
+    use pdf::content::Content;
+    use pdf::primitive::{Dictionary, Primitive};
+    use pdf::text::{ExtractOptions, SoftHyphens};
+
+    let mut file = run!(File::new());
+    let font = file.add_standard_font("Helvetica");
+    let mut fonts = Dictionary::new();
+    fonts.insert("F1".into(), font.into());
+    let mut resources = Dictionary::new();
+    resources.insert("Font".into(), Primitive::Dictionary(fonts));
+
+    let ops = run!(Content::parse_ops(b"BT /F1 12 Tf (a) Tj /Span <</ActualText (ffi)>> BDC (X) Tj EMC (b\\255c) Tj ET", &pdf::object::NoResolve));
+    let media_box = Rect { left: 0., bottom: 0., right: 612., top: 792. };
+    run!(file.add_page(media_box, &Content::from_ops(&ops), resources));
+
+    assert_eq!(run!(file.page_text(0)), "aXb\u{AD}c");
+    let options = ExtractOptions { actual_text: true, soft_hyphens: SoftHyphens::Remove, .. ExtractOptions::default() };
+    assert_eq!(run!(file.page_text_with_options(0, &options)), "affibc");
+}
+
+#[test]
+fn broken_xref() {
+    let mut data = std::fs::read(file_path!("xelatex.pdf")).unwrap();
+    let pos = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+    data.truncate(pos);
+    data.extend_from_slice(b"startxref\n0\n%%EOF\n");
+
+    let original = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let file = run!(File::from_data(data));
+    assert_eq!(run!(file.get_num_pages()), run!(original.get_num_pages()));
+    for page in file.pages() {
+        run!(page);
+    }
+}
+
+#[test]
+fn broken_xref_with_objects_in_streams() {
+    // the stream data looks like objects, one of them with a huge number
+    let stream = "4294967295 0 obj << >> endobj\n3 0 obj << /Type /Catalog >> endobj\n";
+    let data = format!("%PDF-1.7\n\
+        1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+        2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj\n\
+        3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 100 100] /Contents 4 0 R >> endobj\n\
+        4 0 obj << /Length {} >> stream\n{}endstream\nendobj\n\
+        trailer\n<< /Size 5 /Root 1 0 R >>\nstartxref\n0\n%%EOF\n", stream.len(), stream);
+
+    let file = run!(File::from_data(data.into_bytes()));
+    assert_eq!(run!(file.get_num_pages()), 1);
+    let page = run!(file.get_page(0));
+    assert_eq!(run!(page.media_box(&file)).right, 100.);
+    assert!(file.locate(PlainRef {id: 4294967295, gen: 0}).is_err());
+}
+
+#[test]
+fn broken_root() {
+    let original = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let mut data = std::fs::read(file_path!("xelatex.pdf")).unwrap();
+    let pos = data.windows(9).rposition(|w| w == b"startxref").unwrap();
+    let prev: usize = std::str::from_utf8(&data[pos + 9 ..]).unwrap().split_whitespace().next().unwrap().parse().unwrap();
+    // an update whose /Root doesn't exist
+    let xref = data.len() + 1;
+    let update = format!("\nxref\ntrailer\n<< /Size 100000 /Root 99999 0 R /Prev {} >>\nstartxref\n{}\n%%EOF\n", prev, xref);
+    data.extend_from_slice(update.as_bytes());
+
+    let file = run!(File::from_data(data));
+    assert_eq!(file.root_revision(), 1);
+    assert_eq!(run!(file.get_num_pages()), run!(original.get_num_pages()));
+}
+
+#[test]
+fn threads() {
+    use std::sync::Arc;
+
+    let file = Arc::new(run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf"))));
+    let num_pages = run!(file.get_num_pages());
+    let threads: Vec<_> = (0 .. 4).map(|_| {
+        let file = file.clone();
+        std::thread::spawn(move || {
+            for page in file.pages() {
+                let page = run!(page);
+                run!(page.resources(&*file));
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(file.pages().count() as u32, num_pages);
+}
+
+#[test]
+fn shared_stream_data() {
+    use pdf::primitive::Primitive;
+    use pdf::backend::SeekBackend;
+
+    let file = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let reader = std::fs::File::open(file_path!("xelatex.pdf")).unwrap();
+    let seek = run!(File::from_backend(run!(SeekBackend::new(reader))));
+    let mut streams = 0;
+    for id in 1 .. 100 {
+        let r = PlainRef { id, gen: 0 };
+        if let (Ok(Primitive::Stream(stream)), Ok(Primitive::Stream(copy))) = (file.resolve(r), seek.resolve(r)) {
+            // the data stays in the Vec<u8>, which SeekBackend can't share
+            assert!(stream.data.is_shared());
+            assert!(!copy.data.is_shared());
+            assert_eq!(&*stream.data, &*copy.data);
+            streams += 1;
+        }
+    }
+    assert!(streams > 0);
+}
+
+#[test]
+fn seek_backend() {
+    use pdf::backend::SeekBackend;
+
+    let reader = std::fs::File::open(file_path!("xelatex.pdf")).unwrap();
+    let file = run!(File::from_backend(run!(SeekBackend::with_chunk_size(reader, 1024))));
+    let original = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    assert_eq!(run!(file.get_num_pages()), run!(original.get_num_pages()));
+    for (page, original) in file.pages().zip(original.pages()) {
+        let (page, original) = (run!(page), run!(original));
+        assert_eq!(page.contents.as_ref().unwrap().operations.len(), original.contents.as_ref().unwrap().operations.len());
+    }
+}
+
+#[test]
+fn write_linearized() {
+    use std::future::Future;
+    use std::ops::Range;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use pdf::fetch::{AsyncBackend, BoxFuture};
+    use pdf::file::WriteOptions;
+
+    struct Memory(Vec<u8>);
+    impl AsyncBackend for Memory {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+        fn fetch(&self, range: Range<usize>) -> BoxFuture<'_, pdf::error::Result<Vec<u8>>> {
+            let data = self.0[range].to_vec();
+            Box::pin(async move { Ok(data) })
+        }
+    }
+    fn ready<T>(future: impl Future<Output = T>) -> T {
+        fn raw() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker { raw() }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw()) };
+        match Box::pin(future).as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(t) => t,
+            Poll::Pending => panic!("pending")
+        }
+    }
+
+    let file = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let mut data = Vec::new();
+    run!(file.write_with_options(&mut data, &WriteOptions { linearize: true, .. WriteOptions::default() }));
+
+    let reopened = run!(File::from_data(data.clone()));
+    assert_eq!(run!(reopened.get_num_pages()), run!(file.get_num_pages()));
+    for (page, original) in reopened.pages().zip(file.pages()) {
+        let (page, original) = (run!(page), run!(original));
+        assert_eq!(page.contents.as_ref().unwrap().operations.len(), original.contents.as_ref().unwrap().operations.len());
+    }
+
+    let len = data.len();
+    let progressive = run!(ready(File::open_progressive(Memory(data))));
+    let page = run!(ready(progressive.first_page()));
+    assert_eq!(page.contents.as_ref().unwrap().operations.len(),
+        run!(file.get_page(0)).contents.as_ref().unwrap().operations.len());
+    let mut end = 0;
+    for n in 0 .. run!(file.get_num_pages()) {
+        let range = progressive.page_range(n).unwrap();
+        assert!(range.start >= end && range.end <= len);
+        end = range.end;
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_export() {
+    let file = run!(File::<Vec<u8>>::open(file_path!("xelatex.pdf")));
+    let json = run!(file.to_json(0, false));
+    let root = json["trailer"]["/Root"].as_str().unwrap();
+    assert_eq!(json["objects"][root]["/Type"], "/Catalog");
+
+    let json = run!(file.to_json(2, true));
+    assert_eq!(json["trailer"]["/Root"]["/Pages"]["/Type"], "/Pages");
+}
+
 // TODO test decoding
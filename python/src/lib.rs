@@ -0,0 +1,132 @@
+//! Python bindings: pages, text, images and metadata of a document, and saving it.
+//!
+//! Build with `maturin build` and use it as
+//! ```python
+//! import pdf_rs
+//! doc = pdf_rs.Document("file.pdf")
+//! print(doc.page_count(), doc.metadata().get("Title"))
+//! ```
+//! Errors are raised as `IOError` and `IndexError` where these fit, and as `pdf_rs.PdfError`
+//! otherwise.
+
+use std::path::Path;
+
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError, PyIndexError};
+use pyo3::types::{PyBytes, PyDict};
+
+use pdf::document::{self, PdfDocument};
+use pdf::error::ErrorKind;
+
+create_exception!(pdf_rs, PdfError, PyException);
+
+fn error(e: pdf::error::PdfError) -> PyErr {
+    match e.kind() {
+        ErrorKind::Io => PyIOError::new_err(e.to_string()),
+        ErrorKind::PageOutOfBounds => PyIndexError::new_err(e.to_string()),
+        _ => PdfError::new_err(e.to_string()),
+    }
+}
+
+/// A PDF document, read into memory.
+#[pyclass]
+struct Document {
+    inner: Box<dyn PdfDocument>,
+}
+
+#[pymethods]
+impl Document {
+    #[new]
+    fn open(path: &str, password: Option<&str>) -> PyResult<Self> {
+        let doc = document::Document::open(path, password.unwrap_or("").as_bytes()).map_err(error)?;
+        Ok(Document { inner: Box::new(doc) })
+    }
+    #[staticmethod]
+    fn from_bytes(data: &[u8], password: Option<&str>) -> PyResult<Self> {
+        let doc = document::Document::from_data(data.to_vec(), password.unwrap_or("").as_bytes()).map_err(error)?;
+        Ok(Document { inner: Box::new(doc) })
+    }
+    fn page_count(&self) -> PyResult<u32> {
+        self.inner.num_pages().map_err(error)
+    }
+    /// (width, height) of page `page` (counting from 0) in points
+    fn page_size(&self, page: u32) -> PyResult<(f32, f32)> {
+        self.inner.page_size(page).map_err(error)
+    }
+    fn page_text(&self, page: u32) -> PyResult<String> {
+        self.inner.page_text(page).map_err(error)
+    }
+    fn page_images(&self, page: u32) -> PyResult<Vec<Image>> {
+        let images = self.inner.page_images(page).map_err(error)?;
+        Ok(images.into_iter().map(|(name, image)| Image {
+            name,
+            width: image.width,
+            height: image.height,
+            components: image.components,
+            data: image.data,
+        }).collect())
+    }
+    /// The metadata entries the document has, with the dates in RFC 3339.
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let meta = self.inner.metadata().map_err(error)?;
+        let dict = PyDict::new(py);
+        let entries = vec![
+            ("Title", meta.title),
+            ("Author", meta.author),
+            ("Subject", meta.subject),
+            ("Keywords", meta.keywords),
+            ("Creator", meta.creator),
+            ("Producer", meta.producer),
+            ("CreationDate", meta.creation_date.map(|d| d.to_rfc3339())),
+            ("ModDate", meta.mod_date.map(|d| d.to_rfc3339())),
+            ("Trapped", meta.trapped),
+        ];
+        for (key, value) in entries {
+            if let Some(value) = value {
+                dict.set_item(key, value)?;
+            }
+        }
+        Ok(dict)
+    }
+    fn warnings(&self) -> Vec<String> {
+        self.inner.warnings()
+    }
+    fn save(&self, path: &str) -> PyResult<()> {
+        self.inner.save(Path::new(path)).map_err(error)
+    }
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let data = self.inner.to_bytes().map_err(error)?;
+        Ok(PyBytes::new(py, &data))
+    }
+}
+
+/// A decoded image with 8 bits per component, `width * height * components` bytes row by row.
+#[pyclass]
+struct Image {
+    #[pyo3(get)]
+    name: String,
+    #[pyo3(get)]
+    width: u32,
+    #[pyo3(get)]
+    height: u32,
+    #[pyo3(get)]
+    components: usize,
+    data: Vec<u8>,
+}
+
+#[pymethods]
+impl Image {
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.data)
+    }
+}
+
+#[pymodule]
+fn pdf_rs(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Document>()?;
+    m.add_class::<Image>()?;
+    m.add("PdfError", py.get_type::<PdfError>())?;
+    Ok(())
+}
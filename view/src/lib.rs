@@ -4,26 +4,30 @@ extern crate env_logger;
 
 use std::io::Write;
 use std::mem;
+use std::ops::{Deref, DerefMut};
 use std::convert::TryInto;
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 use std::fs;
 
 use pdf::file::File as PdfFile;
 use pdf::object::*;
 use pdf::primitive::Primitive;
 use pdf::backend::Backend;
-use pdf::font::{Font as PdfFont, FontType};
+use pdf::font::{Font as PdfFont, FontType, FontData, Type3Font};
+use pdf::content::{Op, Point};
+use pdf::image::ImageData;
 use pdf::error::{PdfError, Result};
-use pdf::encoding::{Encoding, Decoder};
+use pdf::encoding::Decoder;
+use pdf::text::{self, Matrix};
 
 use pathfinder_content::color::ColorU;
 use pathfinder_geometry::{
     vector::Vector2F, rect::RectF, transform2d::Transform2F
 };
-use pathfinder_canvas::{CanvasRenderingContext2D, CanvasFontContext, Path2D, FillStyle};
+use pathfinder_canvas::{CanvasRenderingContext2D, CanvasFontContext, Path2D, FillStyle, LineCap as CanvasLineCap, LineJoin as CanvasLineJoin, CompositeOperation};
 use pathfinder_renderer::scene::Scene;
-use font::{Font, CffFont, TrueTypeFont, Type1Font, Glyphs};
+use font::{Font, CffFont, TrueTypeFont, Type1Font, Glyph, Glyphs};
 
 macro_rules! ops_p {
     ($ops:ident, $($point:ident),* => $block:block) => ({
@@ -51,18 +55,196 @@ macro_rules! ops {
 
 type P = Vector2F;
 fn rgb2fill(r: f32, g: f32, b: f32) -> FillStyle {
-    let c = |v: f32| (v * 255.) as u8;
-    FillStyle::Color(ColorU { r: c(r), g: c(g), b: c(b), a: 255 })
+    rgba2fill(r, g, b, 1.)
 }
-fn gray2fill(g: f32) -> FillStyle {
-    rgb2fill(g, g, g)
+fn rgba2fill(r: f32, g: f32, b: f32, a: f32) -> FillStyle {
+    let c = |v: f32| (v.max(0.).min(1.) * 255.) as u8;
+    FillStyle::Color(ColorU { r: c(r), g: c(g), b: c(b), a: c(a) })
 }
-fn cymk2fill(c: f32, y: f32, m: f32, k: f32) -> FillStyle {
-    rgb2fill(
-        (1.0 - c) * (1.0 - k),
-        (1.0 - m) * (1.0 - k),
-        (1.0 - y) * (1.0 - k)
-    )
+/// The color space implied by `g`, `rg` and `k` (and their stroking versions).
+fn device_space(op: &str) -> &'static ColorSpace {
+    let name = match op {
+        "g" | "G" => "DeviceGray",
+        "rg" | "RG" => "DeviceRGB",
+        _ => "DeviceCMYK"
+    };
+    ColorSpace::from_name(name).unwrap()
+}
+/// Converts color operands in `space`. No operands give the initial color of the space.
+fn color2rgb(space: &ColorSpace, ops: &[Primitive]) -> Option<(f32, f32, f32)> {
+    let color: Vec<f32> = match ops.len() {
+        0 => space.initial_color(),
+        _ => ops.iter().filter_map(|p| p.as_number().ok()).collect()
+    };
+    space.to_rgb(&color)
+}
+fn blend2composite(mode: BlendMode) -> CompositeOperation {
+    match mode {
+        BlendMode::Normal => CompositeOperation::SourceOver,
+        BlendMode::Multiply => CompositeOperation::Multiply,
+        BlendMode::Screen => CompositeOperation::Screen,
+        BlendMode::Overlay => CompositeOperation::Overlay,
+        BlendMode::Darken => CompositeOperation::Darken,
+        BlendMode::Lighten => CompositeOperation::Lighten,
+        BlendMode::ColorDodge => CompositeOperation::ColorDodge,
+        BlendMode::ColorBurn => CompositeOperation::ColorBurn,
+        BlendMode::HardLight => CompositeOperation::HardLight,
+        BlendMode::SoftLight => CompositeOperation::SoftLight,
+        BlendMode::Difference => CompositeOperation::Difference,
+        BlendMode::Exclusion => CompositeOperation::Exclusion,
+        BlendMode::Hue => CompositeOperation::Hue,
+        BlendMode::Saturation => CompositeOperation::Saturation,
+        BlendMode::Color => CompositeOperation::Color,
+        BlendMode::Luminosity => CompositeOperation::Luminosity,
+    }
+}
+fn set_line_cap(canvas: &mut CanvasRenderingContext2D, cap: LineCap) {
+    canvas.set_line_cap(match cap {
+        LineCap::Butt => CanvasLineCap::Butt,
+        LineCap::Round => CanvasLineCap::Round,
+        LineCap::Square => CanvasLineCap::Square,
+    });
+}
+fn set_line_join(canvas: &mut CanvasRenderingContext2D, join: LineJoin) {
+    canvas.set_line_join(match join {
+        LineJoin::Miter => CanvasLineJoin::Miter,
+        LineJoin::Round => CanvasLineJoin::Round,
+        LineJoin::Bevel => CanvasLineJoin::Bevel,
+    });
+}
+fn set_dash(canvas: &mut CanvasRenderingContext2D, dash: &DashPattern) {
+    canvas.set_line_dash(dash.array.clone());
+    canvas.set_line_dash_offset(dash.phase);
+}
+
+/// The colors of the graphics state. Alpha is part of the fill styles, as the stroking and
+/// nonstroking alpha differ.
+#[derive(Clone)]
+struct ColorState<'a> {
+    fill_space: &'a ColorSpace,
+    stroke_space: &'a ColorSpace,
+    fill: (f32, f32, f32),
+    stroke: (f32, f32, f32),
+    fill_alpha: f32,
+    stroke_alpha: f32,
+}
+impl<'a> ColorState<'a> {
+    fn new() -> ColorState<'a> {
+        ColorState {
+            fill_space: device_space("g"),
+            stroke_space: device_space("G"),
+            fill: (0., 0., 0.),
+            stroke: (0., 0., 0.),
+            fill_alpha: 1.,
+            stroke_alpha: 1.,
+        }
+    }
+    fn set_fill(&mut self, canvas: &mut CanvasRenderingContext2D, ops: &[Primitive]) {
+        if let Some(rgb) = color2rgb(self.fill_space, ops) {
+            self.fill = rgb;
+        }
+        self.apply_fill(canvas);
+    }
+    fn set_stroke(&mut self, canvas: &mut CanvasRenderingContext2D, ops: &[Primitive]) {
+        if let Some(rgb) = color2rgb(self.stroke_space, ops) {
+            self.stroke = rgb;
+        }
+        self.apply_stroke(canvas);
+    }
+    fn apply_fill(&self, canvas: &mut CanvasRenderingContext2D) {
+        let (r, g, b) = self.fill;
+        canvas.set_fill_style(rgba2fill(r, g, b, self.fill_alpha));
+    }
+    fn apply_stroke(&self, canvas: &mut CanvasRenderingContext2D) {
+        let (r, g, b) = self.stroke;
+        canvas.set_stroke_style(rgba2fill(r, g, b, self.stroke_alpha));
+    }
+}
+
+/// Steps shadings are approximated with
+const SHADING_STEPS: usize = 64;
+
+/// Paints `shading` with solid bands. Clipping is not implemented, so it covers `area`.
+fn draw_shading(canvas: &mut CanvasRenderingContext2D, shading: &Shading, area: Rect) {
+    let fill = |canvas: &mut CanvasRenderingContext2D, t: f32, points: &[P]| {
+        let color = shading.color(&[t]);
+        if let Some((r, g, b)) = shading.color_space.to_rgb(&color) {
+            let mut path = Path2D::new();
+            path.move_to(points[0]);
+            for &p in &points[1..] {
+                path.line_to(p);
+            }
+            path.close_path();
+            canvas.set_fill_style(rgb2fill(r, g, b));
+            canvas.fill_path(path);
+        }
+    };
+    let span = (area.right - area.left).hypot(area.top - area.bottom);
+    canvas.save();
+    match shading.kind {
+        ShadingKind::Axial { coords: [x0, y0, x1, y1], domain: [t0, t1], extend } => {
+            let start = P::new(x0, y0);
+            let axis = P::new(x1 - x0, y1 - y0);
+            let len = axis.length();
+            if len == 0. {
+                canvas.restore();
+                return;
+            }
+            // perpendicular to the axis, long enough to cover the area
+            let normal = P::new(-axis.y(), axis.x()) * (span / len);
+            let band = |canvas: &mut CanvasRenderingContext2D, s0: f32, s1: f32, t: f32| {
+                let (a, b) = (start + axis * s0, start + axis * s1);
+                fill(canvas, t, &[a - normal, b - normal, b + normal, a + normal]);
+            };
+            let reach = span / len;
+            if extend[0] {
+                band(canvas, -reach, 0., t0);
+            }
+            if extend[1] {
+                band(canvas, 1., 1. + reach, t1);
+            }
+            for i in 0 .. SHADING_STEPS {
+                let (s0, s1) = (i as f32 / SHADING_STEPS as f32, (i + 1) as f32 / SHADING_STEPS as f32);
+                band(canvas, s0, s1, t0 + (s0 + s1) / 2. * (t1 - t0));
+            }
+        }
+        ShadingKind::Radial { coords: [x0, y0, r0, x1, y1, r1], domain: [t0, t1], extend } => {
+            let circle = |canvas: &mut CanvasRenderingContext2D, s: f32, t: f32| {
+                let center = P::new(x0 + s * (x1 - x0), y0 + s * (y1 - y0));
+                let r = r0 + s * (r1 - r0);
+                if r <= 0. {
+                    return;
+                }
+                let points: Vec<P> = (0 .. 32).map(|i| {
+                    let a = i as f32 * std::f32::consts::PI / 16.;
+                    center + P::new(a.cos(), a.sin()) * r
+                }).collect();
+                fill(canvas, t, &points);
+            };
+            // larger circles first, so the smaller ones are painted on top
+            if extend[1] && r1 >= r0 {
+                circle(canvas, 1. + span / (r1 - r0).max(1.), t1);
+            }
+            for i in (0 ..= SHADING_STEPS).rev() {
+                let s = i as f32 / SHADING_STEPS as f32;
+                circle(canvas, s, t0 + s * (t1 - t0));
+            }
+        }
+        ShadingKind::Function { domain: [x0, x1, y0, y1], .. } => {
+            // a single color from the middle of the domain
+            let color = shading.color(&[(x0 + x1) / 2., (y0 + y1) / 2.]);
+            if let Some((r, g, b)) = shading.color_space.to_rgb(&color) {
+                canvas.set_fill_style(rgb2fill(r, g, b));
+                let mut path = Path2D::new();
+                path.rect(RectF::from_points(P::new(area.left, area.bottom), P::new(area.right, area.top)));
+                canvas.fill_path(path);
+            }
+        }
+        ShadingKind::Mesh(ref mesh) => {
+            warn!("mesh shadings (type {}) are not supported", mesh.shading_type);
+        }
+    }
+    canvas.restore();
 }
 
 struct FontEntry {
@@ -82,67 +264,48 @@ enum TextMode {
 }
 
 struct TextState<'a> {
-    text_matrix: Transform2F, // tracks current glyph
-    line_matrix: Transform2F, // tracks current line
-    char_space: f32, // Character spacing
-    word_space: f32, // Word spacing
-    horiz_scale: f32, // Horizontal scaling
-    leading: f32, // Leading
+    text: text::TextState,
     font: Option<&'a FontEntry>, // Text font
-    font_size: f32, // Text font size
     mode: TextMode, // Text rendering mode
-    rise: f32, // Text rise
-    knockout: f32 //Text knockout
+}
+impl<'a> Deref for TextState<'a> {
+    type Target = text::TextState;
+    fn deref(&self) -> &text::TextState {
+        &self.text
+    }
+}
+impl<'a> DerefMut for TextState<'a> {
+    fn deref_mut(&mut self) -> &mut text::TextState {
+        &mut self.text
+    }
+}
+fn transform(m: Matrix) -> Transform2F {
+    Transform2F::row_major(m.a, m.b, m.c, m.d, m.e, m.f)
 }
 impl<'a> TextState<'a> {
     fn new() -> TextState<'a> {
         TextState {
-            text_matrix: Transform2F::default(),
-            line_matrix: Transform2F::default(),
-            char_space: 0.,
-            word_space: 0.,
-            horiz_scale: 1.,
-            leading: 0.,
+            text: text::TextState::new(),
             font: None,
-            font_size: 0.,
             mode: TextMode::Fill,
-            rise: 0.,
-            knockout: 0.
         }
     }
-    fn translate(&mut self, v: Vector2F) {
-        let m = self.line_matrix * Transform2F::from_translation(v);
-        self.set_matrix(m);
-    }
-    
-    // move to the next line
-    fn next_line(&mut self) {
-        self.translate(Vector2F::new(0., -self.leading * self.font_size));
-    }
-    // set text and line matrix
-    fn set_matrix(&mut self, m: Transform2F) {
-        self.text_matrix = m;
-        self.line_matrix = m;
-    }
     fn add_glyphs(&mut self, canvas: &mut CanvasRenderingContext2D, glyphs: impl Iterator<Item=(u32, bool)>) {
-        let base = Transform2F::row_major(self.horiz_scale, 0., 0., -1.0, 0., self.rise);
-        let font = self.font.as_ref().unwrap();
-        let mut advance = 0.;
+        let font = self.font.unwrap();
+        let ctm = canvas.current_transform();
         for (gid, is_space) in glyphs {
             let glyph = font.glyphs.get(gid as u32).unwrap();
             
-            let transform = base * self.text_matrix * font.font_matrix;
+            // horizontal scaling is part of the text rendering matrix, so it applies to the shape and the advance
+            let transform = ctm * transform(self.text.text_rendering_matrix()) * font.font_matrix;
             
             canvas.set_current_transform(&transform);
             canvas.fill_path(glyph.path.clone());
             
-            let dx = match is_space {
-                true => self.word_space,
-                false => self.char_space
-            };
-            
-            self.text_matrix = self.text_matrix * Transform2F::from_translation(Vector2F::new(glyph.width + dx, 0.));
+            let width = (font.font_matrix * Vector2F::new(glyph.width, 0.) - font.font_matrix * Vector2F::default()).x();
+            self.text.advance_glyph(width, is_space);
         }
+        canvas.set_current_transform(&ctm);
     }
     fn add_text_cid(&mut self, canvas: &mut CanvasRenderingContext2D, data: &[u8]) {
         self.add_glyphs(canvas, data.chunks_exact(2).map(|s| {
@@ -157,26 +320,23 @@ impl<'a> TextState<'a> {
             }
             
             let cmap = font.cmap.as_ref().expect("no cmap");
-            self.add_glyphs(canvas, data.iter().map(|&b| {
-                (*cmap.get(&(b as u16)).expect("can't decode byte"), b == 0x20)
+            // codes without a glyph are skipped
+            self.add_glyphs(canvas, data.iter().filter_map(|&b| {
+                cmap.get(&(b as u16)).map(|&gid| (gid, b == 0x20))
             }));
         }
     }
-    fn advance(&mut self, v: Vector2F) {
-        self.text_matrix = self.text_matrix * Transform2F::from_translation(v);
-    }
 }
 
 pub struct Cache {
     // shared mapping of fontname -> font
-    fonts: HashMap<String, FontEntry>
+    fonts: BTreeMap<String, FontEntry>
 }
 
-fn truetype(data: &[u8], encoding: &Encoding) -> FontEntry {
+fn truetype(data: &[u8], decoder: Decoder) -> FontEntry {
     let font = TrueTypeFont::parse(data)
         .expect("can't parse TrueType font");
     
-    let decoder = Decoder::new(encoding);
     // build cmap
     let cmap = (0 ..= 255)
         .filter_map(|b| decoder.decode_byte(b).map(|c| (b as u16, font.info.find_glyph_index(c as u32))))
@@ -190,54 +350,209 @@ fn truetype(data: &[u8], encoding: &Encoding) -> FontEntry {
         font_matrix: font.font_matrix()
     }
 }
-fn opentype(data: &[u8], encoding: &Encoding) -> FontEntry {
+/// Maps each code to the glyph of the name the encoding gives it.
+fn names_cmap(font: &impl Font, decoder: &Decoder) -> HashMap<u16, u32> {
+    (0 ..= 255)
+        .filter_map(|b| Some((b as u16, font.glyph_for_name(decoder.glyph_name(b)?)?)))
+        .collect()
+}
+/// The encoding of the font program `font` with the `/Differences` of `pdf_font`,
+/// if the PDF font uses it.
+fn built_in_decoder(font: &impl Font, pdf_font: &PdfFont) -> Option<Decoder> {
+    if !pdf_font.uses_built_in_encoding() {
+        return None;
+    }
+    let names = font.built_in_encoding()?;
+    Some(Decoder::from_glyph_names(names.iter().map(|(code, name)| (*code, name.as_str())))
+        .with_differences(pdf_font.differences().iter().map(|(code, name)| (*code, name.as_str()))))
+}
+fn opentype(data: &[u8], decoder: Decoder) -> FontEntry {
     let font = CffFont::parse_opentype(data, 0).unwrap();
     FontEntry {
         glyphs: font.glyphs(),
-        cmap: None,
-        decoder: Decoder::new(encoding),
+        cmap: Some(names_cmap(&font, &decoder)),
+        decoder,
         is_cid: false,
         font_matrix: font.font_matrix()
     }
 }
-fn cff(data: &[u8], encoding: &Encoding) -> FontEntry {
+fn cff(data: &[u8], decoder: Decoder, pdf_font: &PdfFont) -> FontEntry {
     let font = CffFont::parse(data, 0).unwrap();
+    let decoder = built_in_decoder(&font, pdf_font).unwrap_or(decoder);
     FontEntry {
         glyphs: font.glyphs(),
-        cmap: None,
-        decoder: Decoder::new(encoding),
+        cmap: Some(names_cmap(&font, &decoder)),
+        decoder,
         is_cid: false,
         font_matrix: font.font_matrix()
     }
 }
-fn type1(data: &[u8], encoding: &Encoding) -> FontEntry {
+fn type1(data: &[u8], decoder: Decoder, pdf_font: &PdfFont) -> FontEntry {
     let font = Type1Font::parse(data)
         .expect("can't parse Type1 font");
-    let decoder = Decoder::new(encoding);
+    let decoder = built_in_decoder(&font, pdf_font).unwrap_or(decoder);
     
     FontEntry {
         glyphs: font.glyphs(),
-        cmap: None,
+        cmap: Some(names_cmap(&font, &decoder)),
         decoder,
         is_cid: false,
         font_matrix: font.font_matrix()
     }
 }
 
+/// The glyph procedures of a Type3 font as glyphs, in the order of their names.
+fn type3(font: &Type3Font, decoder: Decoder, resolve: &impl Resolve) -> FontEntry {
+    let glyphs = font.char_procs.keys().map(|name| {
+        let ops = font.char_proc(name, resolve).unwrap_or_else(|| Ok(vec![])).unwrap_or_else(|e| {
+            warn!("glyph procedure {}: {}", name, e);
+            vec![]
+        });
+        type3_glyph(&ops, resolve)
+    }).collect();
+    let cmap = (0 ..= 255)
+        .filter_map(|b| {
+            let name = decoder.glyph_name(b)?;
+            Some((b as u16, font.char_procs.keys().position(|n| n == name)? as u32))
+        })
+        .collect();
+    FontEntry {
+        glyphs: Glyphs::new(glyphs),
+        cmap: Some(cmap),
+        decoder,
+        is_cid: false,
+        font_matrix: transform(font.font_matrix())
+    }
+}
+/// The shape of a glyph procedure: the paths it fills and the image masks it paints, in glyph space.
+/// Strokes are left out, and the color is that of the text.
+fn type3_glyph(ops: &[Op], resolve: &impl Resolve) -> Glyph {
+    enum Segment {
+        Move(Vector2F),
+        Line(Vector2F),
+        Curve(Vector2F, Vector2F, Vector2F),
+        Close
+    }
+    let mut shape = Path2D::new();
+    let mut segments = Vec::new();
+    let mut last = Vector2F::default();
+    let mut ctm = Transform2F::row_major(1., 0., 0., 1., 0., 0.);
+    let mut stack = Vec::new();
+    let mut width = 0.;
+    let point = |p: &Point| Vector2F::new(p.x, p.y);
+    for op in ops {
+        match *op {
+            Op::CharWidth(ref w) | Op::CharWidthBBox { width: ref w, .. } => width = w.x,
+            Op::Save => stack.push(ctm),
+            Op::Restore => ctm = stack.pop().unwrap_or(ctm),
+            Op::Transform(m) => ctm = ctm * transform(m),
+            Op::MoveTo(ref p) => {
+                last = ctm * point(p);
+                segments.push(Segment::Move(last));
+            }
+            Op::LineTo(ref p) => {
+                last = ctm * point(p);
+                segments.push(Segment::Line(last));
+            }
+            Op::CurveTo { ref c1, ref c2, ref p } => {
+                let p = ctm * point(p);
+                segments.push(Segment::Curve(ctm * point(c1), ctm * point(c2), p));
+                last = p;
+            }
+            Op::CurveToV { ref c2, ref p } => {
+                let p = ctm * point(p);
+                segments.push(Segment::Curve(last, ctm * point(c2), p));
+                last = p;
+            }
+            Op::CurveToY { ref c1, ref p } => {
+                let p = ctm * point(p);
+                segments.push(Segment::Curve(ctm * point(c1), p, p));
+                last = p;
+            }
+            Op::Close => segments.push(Segment::Close),
+            Op::Rect { x, y, width, height } => {
+                last = ctm * Vector2F::new(x, y);
+                segments.push(Segment::Move(last));
+                segments.push(Segment::Line(ctm * Vector2F::new(x + width, y)));
+                segments.push(Segment::Line(ctm * Vector2F::new(x + width, y + height)));
+                segments.push(Segment::Line(ctm * Vector2F::new(x, y + height)));
+                segments.push(Segment::Close);
+            }
+            Op::Fill(_) | Op::FillStroke(_) | Op::CloseFillStroke(_) => {
+                for segment in segments.drain(..) {
+                    match segment {
+                        Segment::Move(p) => shape.move_to(p),
+                        Segment::Line(p) => shape.line_to(p),
+                        Segment::Curve(c1, c2, p) => shape.bezier_curve_to(c1, c2, p),
+                        Segment::Close => shape.close_path()
+                    }
+                }
+                shape.close_path();
+            }
+            Op::Stroke | Op::CloseStroke | Op::EndPath => segments.clear(),
+            Op::InlineImage(ref image) => match image.image().and_then(|image| {
+                let is_mask = image.image_mask;
+                image.image_data(resolve).map(|data| (is_mask, data))
+            }) {
+                Ok((true, mask)) => add_image_mask(&mut shape, &mask, ctm),
+                Ok((false, _)) => warn!("only image masks are shown in Type3 glyphs"),
+                Err(e) => warn!("image in a glyph procedure: {}", e)
+            },
+            _ => {}
+        }
+    }
+    Glyph { width, path: shape }
+}
+/// Adds the painted pixels of `mask`, which fills the unit square mapped by `ctm`, as rectangles.
+fn add_image_mask(shape: &mut Path2D, mask: &ImageData, ctm: Transform2F) {
+    let (w, h) = (mask.width as usize, mask.height as usize);
+    if mask.data.len() < w * h {
+        return;
+    }
+    for row in 0 .. h {
+        let line = &mask.data[row * w .. (row + 1) * w];
+        // the first row is at the top
+        let top = 1. - row as f32 / h as f32;
+        let bottom = 1. - (row + 1) as f32 / h as f32;
+        let mut x = 0;
+        while x < w {
+            // 0 marks the pixels to be painted
+            if line[x] != 0 {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < w && line[x] == 0 {
+                x += 1;
+            }
+            let (left, right) = (start as f32 / w as f32, x as f32 / w as f32);
+            shape.move_to(ctm * Vector2F::new(left, bottom));
+            shape.line_to(ctm * Vector2F::new(right, bottom));
+            shape.line_to(ctm * Vector2F::new(right, top));
+            shape.line_to(ctm * Vector2F::new(left, top));
+            shape.close_path();
+        }
+    }
+}
+
 impl Cache {
     pub fn new() -> Cache {
         Cache {
-            fonts: HashMap::new()
+            fonts: BTreeMap::new()
         }
     }
-    fn load_font(&mut self, pdf_font: &PdfFont) {
+    fn load_font(&mut self, pdf_font: &PdfFont, resolve: &impl Resolve) {
         if self.fonts.get(&pdf_font.name).is_some() {
             return;
         }
         dbg!(pdf_font);
         
-        let encoding = pdf_font.encoding();
-        let decoder = Decoder::new(encoding);
+        // composite fonts map codes to CIDs instead
+        let decoder = pdf_font.decoder().unwrap_or_else(|| Decoder::new(pdf_font.encoding()));
+        if let FontData::Type3(ref t3) = pdf_font.data {
+            self.fonts.insert(pdf_font.name.clone(), type3(t3, decoder, resolve));
+            return;
+        }
         
         let mut entry = match (pdf_font.standard_font(), pdf_font.embedded_data()) {
             (_, Some(Ok(data))) => {
@@ -250,8 +565,8 @@ impl Cache {
                 
                 
                 match pdf_font.subtype {
-                    FontType::TrueType | FontType::CIDFontType2 => truetype(data, encoding),
-                    FontType::CIDFontType0 => cff(data, encoding),
+                    FontType::TrueType | FontType::CIDFontType2 => truetype(data, decoder),
+                    FontType::CIDFontType0 => cff(data, decoder, pdf_font),
                     t => panic!("Fonttype {:?} not yet implemented")
                 }
             }
@@ -259,11 +574,18 @@ impl Cache {
                 let font_path = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap()
                     .join("fonts")
                     .join(filename);
-                let data = fs::read(font_path).unwrap();
+                // the metrics are built in, only the glyphs are missing
+                let data = match fs::read(&font_path) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!("can't read {:?} for {}: {}. Glyphs will be missing.", font_path, pdf_font.name, e);
+                        return;
+                    }
+                };
                 match filename.rsplit(".").nth(0).unwrap() {
-                    "otf" => opentype(&data, encoding),
-                    "ttf" => truetype(&data, encoding),
-                    "PFB" => type1(&data, encoding),
+                    "otf" => opentype(&data, decoder),
+                    "ttf" => truetype(&data, decoder),
+                    "PFB" => type1(&data, decoder, pdf_font),
                     e => panic!("unknown file extension .{}", e)
                 }
             }
@@ -301,17 +623,20 @@ impl Cache {
         
         // make sure all fonts are in the cache, so we can reference them
         for font in resources.fonts.values() {
-            self.load_font(font);
+            self.load_font(font, file);
         }
         for gs in resources.graphics_states.values() {
             if let Some((ref font, _)) = gs.font {
-                self.load_font(font);
+                self.load_font(font, file);
             }
         }
         
         let mut path = Path2D::new();
         let mut last = Vector2F::default();
         let mut state = TextState::new();
+        let mut compat = 0usize;
+        let mut colors = ColorState::new();
+        let mut stack = Vec::new();
         
         let mut iter = page.contents.as_ref()?.operations.iter();
         while let Some(op) = iter.next() {
@@ -386,9 +711,13 @@ impl Cache {
                 }
                 "q" => { // save state
                     canvas.save();
+                    stack.push(colors.clone());
                 }
                 "Q" => { // restore
                     canvas.restore();
+                    if let Some(c) = stack.pop() {
+                        colors = c;
+                    }
                 }
                 "cm" => { // modify transformation matrix 
                     ops!(ops, a: f32, b: f32, c: f32, d: f32, e: f32, f: f32 => {
@@ -402,12 +731,26 @@ impl Cache {
                     })
                 }
                 "J" => { // line cap
+                    ops!(ops, cap: i32 => {
+                        set_line_cap(&mut canvas, LineCap::from_primitive(Primitive::Integer(cap), file)?);
+                    })
                 }
                 "j" => { // line join 
+                    ops!(ops, join: i32 => {
+                        set_line_join(&mut canvas, LineJoin::from_primitive(Primitive::Integer(join), file)?);
+                    })
                 }
                 "M" => { // miter limit
+                    ops!(ops, limit: f32 => {
+                        canvas.set_miter_limit(limit);
+                    })
                 }
                 "d" => { // line dash [ array phase ]
+                    let dash = DashPattern::from_primitive(Primitive::Array(ops.clone()), file);
+                    match dash {
+                        Ok(dash) => set_dash(&mut canvas, &dash),
+                        Err(e) => warn!("invalid dash pattern: {}", e)
+                    }
                 }
                 "gs" => ops!(ops, gs: &str => { // set from graphic state dictionary
                     let gs = resources.graphics_states.get(gs)?;
@@ -415,6 +758,29 @@ impl Cache {
                     if let Some(lw) = gs.line_width {
                         canvas.set_line_width(lw);
                     }
+                    if let Some(cap) = gs.line_cap {
+                        set_line_cap(&mut canvas, cap);
+                    }
+                    if let Some(join) = gs.line_join {
+                        set_line_join(&mut canvas, join);
+                    }
+                    if let Some(limit) = gs.miter_limit {
+                        canvas.set_miter_limit(limit);
+                    }
+                    if let Some(ref dash) = gs.dash_pattern {
+                        set_dash(&mut canvas, dash);
+                    }
+                    if let Some(mode) = gs.blend_mode {
+                        canvas.set_global_composite_operation(blend2composite(mode));
+                    }
+                    if let Some(alpha) = gs.stroke_alpha {
+                        colors.stroke_alpha = alpha;
+                        colors.apply_stroke(&mut canvas);
+                    }
+                    if let Some(alpha) = gs.fill_alpha {
+                        colors.fill_alpha = alpha;
+                        colors.apply_fill(&mut canvas);
+                    }
                     if let Some((ref font, size)) = gs.font {
                         if let Some(e) = self.get_font(&font.name) {
                             state.font = Some(e);
@@ -425,38 +791,38 @@ impl Cache {
                         }
                     }
                 }),
+                "sh" => ops!(ops, name: &str => { // paint shading
+                    let shading = resources.shading(name, file)?;
+                    let area = shading.bbox.unwrap_or(Rect { left, right, top, bottom });
+                    draw_shading(&mut canvas, &shading, area);
+                }),
                 "W" | "W*" => { // clipping path
                 
                 }
-                "SC" | "RG" => { // stroke color
-                    ops!(ops, r: f32, g: f32, b: f32 => {
-                        canvas.set_stroke_style(rgb2fill(r, g, b));
-                    });
+                "RG" | "G" | "K" => { // stroke color in a device color space
+                    colors.stroke_space = device_space(op.operator.as_str());
+                    colors.set_stroke(&mut canvas, ops);
                 }
-                "sc" | "rg" => { // fill color
-                    ops!(ops, r: f32, g: f32, b: f32 => {
-                        canvas.set_fill_style(rgb2fill(r, g, b));
-                    });
+                "rg" | "g" | "k" => { // fill color in a device color space
+                    colors.fill_space = device_space(op.operator.as_str());
+                    colors.set_fill(&mut canvas, ops);
                 }
-                "G" => { // stroke gray
-                    ops!(ops, gray: f32 => {
-                        canvas.set_stroke_style(gray2fill(gray));
-                    })
-                }
-                "g" => { // stroke gray
-                    ops!(ops, gray: f32 => {
-                        canvas.set_fill_style(gray2fill(gray));
-                    })
+                "SC" | "SCN" => { // stroke color
+                    colors.set_stroke(&mut canvas, ops);
                 }
-                "k" => { // fill color
-                    ops!(ops, c: f32, y: f32, m: f32, k: f32 => {
-                        canvas.set_fill_style(cymk2fill(c, y, m, k));
-                    });
-                }
-                "cs" => { // color space
+                "sc" | "scn" => { // fill color
+                    colors.set_fill(&mut canvas, ops);
                 }
+                "CS" => ops!(ops, name: &str => { // stroke color space
+                    colors.stroke_space = resources.color_space(name)?;
+                    colors.set_stroke(&mut canvas, &[]);
+                }),
+                "cs" => ops!(ops, name: &str => { // fill color space
+                    colors.fill_space = resources.color_space(name)?;
+                    colors.set_fill(&mut canvas, &[]);
+                }),
                 "BT" => {
-                    state = TextState::new();
+                    state.begin_text();
                 }
                 "ET" => {
                     state.font = None;
@@ -519,17 +885,17 @@ impl Cache {
                 // positioning operators
                 // Move to the start of the next line
                 "Td" => ops_p!(ops, t => {
-                    state.translate(t);
+                    state.translate(t.x(), t.y());
                 }),
                 
                 "TD" => ops_p!(ops, t => {
                     state.leading = -t.y();
-                    state.translate(t);
+                    state.translate(t.x(), t.y());
                 }),
                 
                 // Set the text matrix and the text line matrix
                 "Tm" => ops!(ops, a: f32, b: f32, c: f32, d: f32, e: f32, f: f32 => {
-                    state.set_matrix(Transform2F::row_major(a, b, c, d, e, f));
+                    state.set_matrix(Matrix::new(a, b, c, d, e, f));
                 }),
                 
                 // Move to the start of the next line
@@ -566,13 +932,17 @@ impl Cache {
                                 },
                                 p => {
                                     let offset = p.as_number().expect("wrong argument to TJ");
-                                    state.advance(Vector2F::new(-0.001 * offset, 0.)); // because why not PDF…
+                                    state.adjust(offset);
                                 }
                             }
                         }
                         debug!("Text: {}", font.decoder.decode_bytes(&text));
                     }
                 }),
+                // compatibility section: unknown operators are expected
+                "BX" => compat += 1,
+                "EX" => compat = compat.saturating_sub(1),
+                _ if compat == 0 && !op.is_known() => warn!("unknown operator {}", op.operator),
                 _ => {}
             }
         }